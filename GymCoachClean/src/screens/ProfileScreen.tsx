@@ -20,7 +20,12 @@ import FloatingSettingsButton from '../components/common/FloatingSettingsButton'
 import {useLocale} from '../contexts/LocaleContext';
 import * as notificationService from '../services/notifications';
 import {useTheme} from '../theme';
-import {pickImage, uploadImageToS3, getFileInfo} from '../services/imageUpload';
+import {
+  pickImage,
+  uploadImageToS3,
+  getFileInfo,
+  getFileSize,
+} from '../services/imageUpload';
 import {LegacyBodyMeasurement} from '../types';
 
 export default function ProfileScreen() {
@@ -440,14 +445,21 @@ export default function ProfileScreen() {
 
       // Get file info
       const fileInfo = getFileInfo(image.uri);
+      const fileSize = await getFileSize(image.uri);
 
-      // Get upload URL
+      // Get upload URL; the type and size are signed into it
       const uploadData = await apiClient.generateProfileImageUploadUrl(
         fileInfo.type,
+        fileSize,
       );
 
-      // Upload to S3
-      await uploadImageToS3(uploadData.upload_url, image.uri, fileInfo.type);
+      // Upload to S3 with exactly the signed headers
+      await uploadImageToS3(
+        uploadData.upload_url,
+        image.uri,
+        fileInfo.type,
+        uploadData.headers,
+      );
 
       // Update profile with new image key
       await apiClient.updateProfileImage(uploadData.key);
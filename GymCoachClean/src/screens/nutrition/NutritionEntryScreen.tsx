@@ -152,7 +152,8 @@ export default function NutritionEntryScreen({route, navigation}: any) {
       const response = await apiClient.searchFoods(query);
 
       // The response might be wrapped in different structures
-      const foods = response?.foods || response?.data || response || [];
+      const foods =
+        response?.items || response?.foods || response?.data || response || [];
       setSearchResults(foods);
     } catch (error) {
       console.error('Error searching foods:', error);
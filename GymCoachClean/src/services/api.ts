@@ -1014,16 +1014,18 @@ class ApiClient {
 
   // Profile Image Upload
   async generateProfileImageUploadUrl(
-    fileType: string = 'image/jpeg',
+    fileType: string,
+    fileSize: number,
   ): Promise<{
     upload_url: string;
     key: string;
     bucket_name: string;
     expires_in: number;
+    headers: Record<string, string>;
   }> {
     return this.apiFetch('/api/user-profiles/profile/upload', {
       method: 'POST',
-      body: JSON.stringify({file_type: fileType}),
+      body: JSON.stringify({file_type: fileType, file_size: fileSize}),
     });
   }
 
@@ -132,10 +132,13 @@ export async function pickImage(
   }
 }
 
+// Pass the `headers` returned with a presigned URL as `signedHeaders`; the
+// upload must send exactly the headers that were signed
 export async function uploadImageToS3(
   uploadUrl: string,
   imageUri: string,
   fileType: string,
+  signedHeaders?: Record<string, string>,
 ): Promise<boolean> {
   try {
     console.log('Uploading image to S3:', {uploadUrl, imageUri, fileType});
@@ -147,7 +150,7 @@ export async function uploadImageToS3(
     // Upload to S3 using presigned URL
     const uploadResponse = await fetch(uploadUrl, {
       method: 'PUT',
-      headers: {
+      headers: signedHeaders ?? {
         'Content-Type': fileType,
       },
       body: blob,
@@ -165,6 +168,13 @@ export async function uploadImageToS3(
   }
 }
 
+// Get the size in bytes of the file at a URI
+export async function getFileSize(uri: string): Promise<number> {
+  const response = await fetch(uri);
+  const blob = await response.blob();
+  return blob.size;
+}
+
 // Get file info from URI
 export function getFileInfo(uri: string): {
   name: string;
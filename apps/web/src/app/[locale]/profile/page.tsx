@@ -405,12 +405,12 @@ export default function ProfilePage() {
       setError(null);
 
       // Generate presigned URL
-      const uploadResponse = await api.generateUploadUrl(file.type);
+      const uploadResponse = await api.generateUploadUrl(file.type, file.size);
 
-      const { upload_url, key, bucket_name } = uploadResponse;
+      const { upload_url, key, bucket_name, headers } = uploadResponse;
 
       // Upload file to S3
-      await api.uploadImage(file, upload_url);
+      await api.uploadImage(file, upload_url, headers);
 
       // Update profile with new image URL
       // The presigned URL already contains the correct S3 URL format
@@ -186,8 +186,9 @@ export default function EnhancedWorkoutHistoryPage() {
               : response.body;
         }
         console.log('Fetched workout history data:', data);
+        const sessions = Array.isArray(data) ? data : data.sessions || [];
         const transformedHistory: WorkoutHistory = {
-          sessions: data.map((session: any) => ({
+          sessions: sessions.map((session: any) => ({
             id:
               session.id ||
               session.WorkoutSessionId ||
@@ -934,13 +934,15 @@ export const api = {
     const url = `/api/nutrition/foods/search?q=${encodeURIComponent(query)}${cursor ? `&cursor=${encodeURIComponent(cursor)}` : ''}`;
     const res = await apiFetch<any>(url);
 
-    // Backend returns: { body: { foods: [...], next_cursor: "..." } }
+    // Backend returns: { body: { items: [...], next_cursor: "..." } }
     const bodyData = res;
-    const rawFoods = Array.isArray(bodyData.foods)
-      ? bodyData.foods
-      : Array.isArray(bodyData)
-        ? bodyData
-        : [];
+    const rawFoods = Array.isArray(bodyData.items)
+      ? bodyData.items
+      : Array.isArray(bodyData.foods)
+        ? bodyData.foods
+        : Array.isArray(bodyData)
+          ? bodyData
+          : [];
 
     const mapped = (rawFoods as any[]).map((f) => {
       const nf = f.nutrition_facts || f.nutritionFacts || {};
@@ -1068,27 +1070,32 @@ export const api = {
   },
 
   // Image upload endpoints
-  async generateUploadUrl(fileType: string) {
+  async generateUploadUrl(fileType: string, fileSize: number) {
     const res = await apiFetch<{
       upload_url: string;
       key: string;
       bucket_name: string;
       expires_in: number;
+      headers: Record<string, string>;
     }>(`/api/user-profiles/profile/upload`, {
       method: 'POST',
-      body: JSON.stringify({ file_type: fileType }),
+      body: JSON.stringify({ file_type: fileType, file_size: fileSize }),
     });
     return res;
   },
 
-  async uploadImage(file: File, uploadUrl: string): Promise<void> {
+  async uploadImage(
+    file: File,
+    uploadUrl: string,
+    signedHeaders: Record<string, string>
+  ): Promise<void> {
+    // The browser sets Content-Length itself from the body
+    const { 'content-length': _contentLength, ...headers } = signedHeaders;
     const response = await fetch(uploadUrl, {
       method: 'PUT',
       body: file,
-      headers: {
-        'Content-Type': file.type,
-        // Don't add any other headers as S3 presigned URLs are very specific
-      },
+      // Send exactly the signed headers; S3 rejects anything else
+      headers,
       mode: 'cors', // Explicitly set CORS mode
     });
 
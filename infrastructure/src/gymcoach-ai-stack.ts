@@ -48,6 +48,7 @@ export class GymCoachAIStack extends cdk.Stack {
       sortKey: { name: 'SK', type: dynamodb.AttributeType.STRING },
       billingMode: dynamodb.BillingMode.PAY_PER_REQUEST,
       removalPolicy: cdk.RemovalPolicy.DESTROY,
+      timeToLiveAttribute: 'ttl',
       // Removed pointInTimeRecovery to avoid costs (20% of table cost)
     });
 
@@ -626,6 +627,9 @@ export class GymCoachAIStack extends cdk.Stack {
     );
 
     // Create Notification Service Lambda
+    // Lets the scheduler call the notification service without a user JWT
+    const schedulerApiKey = process.env.SCHEDULER_API_KEY || '';
+
     const notificationServiceLambda = this.createLambdaFunction(
       'NotificationService',
       'notification-service',
@@ -637,6 +641,8 @@ export class GymCoachAIStack extends cdk.Stack {
         FCM_SERVER_KEY: process.env.FCM_SERVER_KEY || 'placeholder',
         FIREBASE_PROJECT_ID:
           process.env.FIREBASE_PROJECT_ID || 'gymcoach-73528',
+        // Service principals, as comma-separated `service=key` pairs
+        SERVICE_API_KEYS: `notification-scheduler=${schedulerApiKey}`,
       }
     );
 
@@ -646,6 +652,11 @@ export class GymCoachAIStack extends cdk.Stack {
       'notification-scheduler',
       {
         NOTIFICATION_SERVICE_FUNCTION_ARN: '', // Will be set after creation
+        NOTIFICATION_INVOKE_CONCURRENCY: '10',
+        NOTIFICATION_DEDUP_WINDOW_MINUTES: '5',
+        CIRCUIT_BREAKER_FAILURE_THRESHOLD: '5',
+        CIRCUIT_BREAKER_COOLDOWN_SECONDS: '30',
+        NOTIFICATION_SERVICE_API_KEY: schedulerApiKey,
       }
     );
 
@@ -1158,6 +1169,19 @@ export class GymCoachAIStack extends cdk.Stack {
       })
     );
 
+    // Scheduler lists preference items through GSI1, reads them per user and
+    // writes de-duplication records before each send
+    notificationSchedulerLambda.addToRolePolicy(
+      new iam.PolicyStatement({
+        effect: iam.Effect.ALLOW,
+        actions: ['dynamodb:Query', 'dynamodb:GetItem', 'dynamodb:PutItem'],
+        resources: [
+          this.mainTable.tableArn,
+          `${this.mainTable.tableArn}/index/GSI1`,
+        ],
+      })
+    );
+
     // Add EventBridge targets
     workoutReminderRule.addTarget(
       new targets.LambdaFunction(notificationSchedulerLambda)
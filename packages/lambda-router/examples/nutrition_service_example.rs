@@ -27,9 +27,8 @@ impl Middleware for AuthMiddleware {
     async fn handle(&self, mut req: Request, next: Next) -> Result<Response, Error> {
         // Convert to auth event format
         let auth_event = AuthLambdaEvent {
-            headers: Some(req.headers.clone()),
-            request_context: req.raw_event()
-                .get("requestContext")
+            headers: Some(req.headers.to_hash_map()),
+            request_context: req.request_context()
                 .and_then(|v| serde_json::from_value(v.clone()).ok()),
             path_parameters: req.path_params
                 .iter()
@@ -42,6 +41,7 @@ impl Middleware for AuthMiddleware {
                 .collect::<std::collections::HashMap<_, _>>()
                 .into(),
             body: req.body.clone(),
+            required_permissions: req.required_permissions().to_vec(),
         };
 
         // Authenticate request
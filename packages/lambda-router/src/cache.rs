@@ -0,0 +1,240 @@
+use async_trait::async_trait;
+use futures::lock::Mutex;
+use lambda_runtime::Error;
+use performance_optimizer::{OptimizationConfig, PerformanceOptimizer};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::{Middleware, Next, Request, Response};
+
+/// Header telling the caller whether the response came from the cache (`HIT`) or the
+/// handler (`MISS`)
+pub const CACHE_STATUS_HEADER: &str = "X-Cache";
+
+/// Caches successful GET responses for the routes given a TTL, in the
+/// `PerformanceOptimizer` cache of this Lambda container. Entries are keyed on the
+/// method, request path, query string and caller, so `/exercises/a` and
+/// `/exercises/b` never share an entry and users never see each other's responses. Register it after auth so the caller is known.
+///
+/// A request with `Cache-Control: no-cache` skips the cached copy and refreshes it;
+/// `no-store` neither reads nor writes. A successful non-GET request drops the
+/// caller's entries, so users see their own writes straight away. Other containers
+/// may serve their copy until it expires
+pub struct CacheMiddleware {
+    optimizer: Arc<Mutex<PerformanceOptimizer>>,
+    route_ttls: HashMap<String, u64>,
+}
+
+impl CacheMiddleware {
+    /// A cache with its own `PerformanceOptimizer`; no routes are cached until given
+    /// a TTL with `route`
+    pub fn new() -> Self {
+        Self::with_optimizer(Arc::new(Mutex::new(PerformanceOptimizer::new(
+            OptimizationConfig::default(),
+        ))))
+    }
+
+    /// Share an existing optimizer's cache (and its size limit and TTL jitter)
+    pub fn with_optimizer(optimizer: Arc<Mutex<PerformanceOptimizer>>) -> Self {
+        Self {
+            optimizer,
+            route_ttls: HashMap::new(),
+        }
+    }
+
+    /// Cache GET responses of the route registered as `pattern`, e.g.
+    /// `/api/workouts/exercises/:exerciseId`, for `ttl_seconds`
+    pub fn route(mut self, pattern: &str, ttl_seconds: u64) -> Self {
+        self.route_ttls.insert(pattern.to_string(), ttl_seconds);
+        self
+    }
+}
+
+impl Default for CacheMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl Middleware for CacheMiddleware {
+    async fn handle(&self, req: Request, next: Next) -> Result<Response, Error> {
+        let user_prefix = user_prefix(&req);
+
+        if !req.method.eq_ignore_ascii_case("GET") {
+            let response = next(req).await?;
+            if (200..300).contains(&response.status_code) {
+                self.optimizer
+                    .lock()
+                    .await
+                    .remove_cached_prefix(&user_prefix);
+            }
+            return Ok(response);
+        }
+
+        let ttl = req
+            .matched_route()
+            .and_then(|route| self.route_ttls.get(route))
+            .copied();
+        let Some(ttl) = ttl else {
+            return next(req).await;
+        };
+
+        let (no_cache, no_store) = cache_control(&req);
+        let key = format!("{}GET {}?{}", user_prefix, req.path, sorted_query(&req));
+
+        if !no_cache && !no_store {
+            let cached: Option<Response> = self.optimizer.lock().await.get_cached(&key).await;
+            if let Some(response) = cached {
+                return Ok(response.header(CACHE_STATUS_HEADER, "HIT"));
+            }
+        }
+
+        let response = next(req).await?;
+        if no_store || response.status_code != 200 || !response.cookies.is_empty() {
+            return Ok(response);
+        }
+
+        // A failed write only costs a cache miss next time
+        let _ = self
+            .optimizer
+            .lock()
+            .await
+            .set_cached(&key, &response, Some(ttl))
+            .await;
+        Ok(response.header(CACHE_STATUS_HEADER, "MISS"))
+    }
+}
+
+/// Key prefix shared by all of one caller's entries
+fn user_prefix(req: &Request) -> String {
+    format!(
+        "route-cache:{}:",
+        req.context.user_id.as_deref().unwrap_or("anonymous")
+    )
+}
+
+/// Query string in a stable order, so `?a=1&b=2` and `?b=2&a=1` share an entry
+fn sorted_query(req: &Request) -> String {
+    let mut params: Vec<_> = req.query_params.iter().collect();
+    params.sort();
+    params
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Whether the request's `Cache-Control` asks for `no-cache` and `no-store`
+fn cache_control(req: &Request) -> (bool, bool) {
+    let directives: Vec<String> = req
+        .header("Cache-Control")
+        .map(|value| {
+            value
+                .split(',')
+                .map(|directive| directive.trim().to_ascii_lowercase())
+                .collect()
+        })
+        .unwrap_or_default();
+    (
+        directives.iter().any(|d| d == "no-cache"),
+        directives.iter().any(|d| d == "no-store"),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handler, Context, Router};
+    use serde_json::{json, Value};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    static CALLS: AtomicUsize = AtomicUsize::new(0);
+
+    async fn counted(req: Request, _ctx: Context) -> crate::Result<Response> {
+        let calls = CALLS.fetch_add(1, Ordering::SeqCst) + 1;
+        Ok(Response::ok(json!({ "calls": calls, "q": req.query("q") })))
+    }
+
+    fn request(method: &str, path: &str, query: &str, headers: Value) -> Value {
+        json!({
+            "rawPath": path,
+            "rawQueryString": query,
+            "queryStringParameters": { "q": query },
+            "requestContext": { "http": { "method": method }, "requestId": "req-1" },
+            "headers": headers
+        })
+    }
+
+    async fn calls_for(router: &Router, event: Value) -> (Value, Option<Value>) {
+        let value = router
+            .handle(event, lambda_runtime::Context::default())
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_str(value["body"].as_str().unwrap()).unwrap();
+        (
+            body["calls"].clone(),
+            value["headers"].get(CACHE_STATUS_HEADER).cloned(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_cached_routes_skip_the_handler_within_ttl() {
+        let mut router = Router::new();
+        router.get("/api/exercises", handler!(counted));
+        router.get("/api/uncached", handler!(counted));
+        router.post("/api/exercises", handler!(counted));
+        router.use_middleware(CacheMiddleware::new().route("/api/exercises", 60));
+
+        let get = |q: &str| request("GET", "/api/exercises", q, json!({}));
+        let (first, status) = calls_for(&router, get("squat")).await;
+        assert_eq!(status, Some(json!("MISS")));
+        let (again, status) = calls_for(&router, get("squat")).await;
+        assert_eq!((again, status), (first.clone(), Some(json!("HIT"))));
+
+        // Different query, different entry
+        let (other, _) = calls_for(&router, get("bench")).await;
+        assert_ne!(other, first);
+
+        // no-cache refreshes the entry
+        let refresh = request(
+            "GET",
+            "/api/exercises",
+            "squat",
+            json!({ "cache-control": "no-cache" }),
+        );
+        let (refreshed, status) = calls_for(&router, refresh).await;
+        assert_ne!(refreshed, first);
+        assert_eq!(status, Some(json!("MISS")));
+        let (again, _) = calls_for(&router, get("squat")).await;
+        assert_eq!(again, refreshed);
+
+        // A write drops the caller's entries
+        calls_for(&router, request("POST", "/api/exercises", "", json!({}))).await;
+        let (after_write, status) = calls_for(&router, get("squat")).await;
+        assert_ne!(after_write, refreshed);
+        assert_eq!(status, Some(json!("MISS")));
+
+        // Routes without a TTL are never cached
+        let uncached = request("GET", "/api/uncached", "", json!({}));
+        let (_, status) = calls_for(&router, uncached).await;
+        assert_eq!(status, None);
+    }
+
+    #[tokio::test]
+    async fn test_path_params_get_their_own_entries() {
+        let mut router = Router::new();
+        router.get("/api/exercises/:exerciseId", handler!(counted));
+        router.use_middleware(CacheMiddleware::new().route("/api/exercises/:exerciseId", 60));
+
+        let get = |path: &str| request("GET", path, "", json!({}));
+        let (squat, status) = calls_for(&router, get("/api/exercises/squat")).await;
+        assert_eq!(status, Some(json!("MISS")));
+        let (bench, status) = calls_for(&router, get("/api/exercises/bench")).await;
+        assert_eq!(status, Some(json!("MISS")));
+        assert_ne!(bench, squat);
+
+        let (again, status) = calls_for(&router, get("/api/exercises/squat")).await;
+        assert_eq!((again, status), (squat, Some(json!("HIT"))));
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::fmt;
+
+/// `SameSite` attribute of a `Set-Cookie` header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None,
+}
+
+impl fmt::Display for SameSite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SameSite::Strict => write!(f, "Strict"),
+            SameSite::Lax => write!(f, "Lax"),
+            SameSite::None => write!(f, "None"),
+        }
+    }
+}
+
+/// Attributes for cookies set through `Response::set_cookie`. Defaults to
+/// `HttpOnly; Secure; SameSite=Lax; Path=/`
+#[derive(Debug, Clone)]
+pub struct CookieOptions {
+    pub http_only: bool,
+    pub secure: bool,
+    pub same_site: Option<SameSite>,
+    pub max_age: Option<i64>,
+    pub path: Option<String>,
+    pub domain: Option<String>,
+}
+
+impl CookieOptions {
+    pub fn new() -> Self {
+        Self {
+            http_only: true,
+            secure: true,
+            same_site: Some(SameSite::Lax),
+            max_age: None,
+            path: Some("/".to_string()),
+            domain: None,
+        }
+    }
+
+    pub fn http_only(mut self, http_only: bool) -> Self {
+        self.http_only = http_only;
+        self
+    }
+
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = Some(same_site);
+        self
+    }
+
+    /// Lifetime in seconds; `0` or a negative value expires the cookie immediately
+    pub fn max_age(mut self, seconds: i64) -> Self {
+        self.max_age = Some(seconds);
+        self
+    }
+
+    pub fn path(mut self, path: impl Into<String>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    pub fn domain(mut self, domain: impl Into<String>) -> Self {
+        self.domain = Some(domain.into());
+        self
+    }
+
+    /// Render a `Set-Cookie` value. The cookie value is percent-encoded so characters
+    /// such as `;` or spaces cannot inject extra attributes
+    pub fn to_set_cookie(&self, name: &str, value: &str) -> String {
+        let mut cookie = format!("{}={}", name, urlencoding::encode(value));
+
+        if let Some(max_age) = self.max_age {
+            cookie.push_str(&format!("; Max-Age={}", max_age));
+        }
+        if let Some(domain) = &self.domain {
+            cookie.push_str(&format!("; Domain={}", domain));
+        }
+        if let Some(path) = &self.path {
+            cookie.push_str(&format!("; Path={}", path));
+        }
+        if self.secure {
+            cookie.push_str("; Secure");
+        }
+        if self.http_only {
+            cookie.push_str("; HttpOnly");
+        }
+        if let Some(same_site) = self.same_site {
+            cookie.push_str(&format!("; SameSite={}", same_site));
+        }
+
+        cookie
+    }
+}
+
+impl Default for CookieOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Parse a `Cookie` header (`a=1; b="two"`) as described in RFC 6265 section 5.4.
+/// Surrounding double quotes are stripped and percent-escapes decoded; pairs without
+/// a name are skipped and the first occurrence of a name wins
+pub fn parse_cookie_header(header: &str) -> HashMap<String, String> {
+    let mut cookies = HashMap::new();
+
+    for pair in header.split(';') {
+        let Some((name, value)) = pair.split_once('=') else {
+            continue;
+        };
+        let name = name.trim();
+        if name.is_empty() {
+            continue;
+        }
+
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or(value);
+        let value = urlencoding::decode(value)
+            .map(|decoded| decoded.into_owned())
+            .unwrap_or_else(|_| value.to_string());
+
+        cookies.entry(name.to_string()).or_insert(value);
+    }
+
+    cookies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cookie_header() {
+        let cookies = parse_cookie_header(
+            r#"session=abc123; theme="dark mode"; flag; =orphan; session=second"#,
+        );
+
+        assert_eq!(cookies.get("session"), Some(&"abc123".to_string()));
+        assert_eq!(cookies.get("theme"), Some(&"dark mode".to_string()));
+        assert!(!cookies.contains_key("flag"));
+        assert_eq!(cookies.len(), 2);
+    }
+
+    #[test]
+    fn test_set_cookie_attributes() {
+        let cookie = CookieOptions::new()
+            .same_site(SameSite::Strict)
+            .max_age(3600)
+            .path("/api/auth")
+            .to_set_cookie("refresh_token", "abc.def;ghi");
+
+        assert_eq!(
+            cookie,
+            "refresh_token=abc.def%3Bghi; Max-Age=3600; Path=/api/auth; Secure; HttpOnly; SameSite=Strict"
+        );
+    }
+}
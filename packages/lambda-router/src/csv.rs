@@ -0,0 +1,156 @@
+use crate::Request;
+
+/// A record that can be written as one CSV row. Implement this for item types; lists
+/// of them get `ToCsv` for free
+pub trait CsvRow {
+    /// Column names, in the order `csv_row` returns values
+    fn csv_header() -> Vec<&'static str>;
+
+    fn csv_row(&self) -> Vec<String>;
+}
+
+/// Tabular data that can be served as `text/csv` (see `Response::negotiated`)
+pub trait ToCsv {
+    fn to_csv(&self) -> String;
+}
+
+impl<T: CsvRow> ToCsv for [T] {
+    fn to_csv(&self) -> String {
+        let mut csv = csv_line(T::csv_header());
+        for row in self {
+            csv.push_str(&csv_line(row.csv_row()));
+        }
+        csv
+    }
+}
+
+impl<T: CsvRow> ToCsv for Vec<T> {
+    fn to_csv(&self) -> String {
+        self.as_slice().to_csv()
+    }
+}
+
+/// One CRLF-terminated line, with fields quoted per RFC 4180 where needed
+fn csv_line<S: AsRef<str>>(fields: Vec<S>) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| escape_field(field.as_ref()))
+        .collect();
+    format!("{}\r\n", fields.join(","))
+}
+
+fn escape_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Whether the client asked for CSV: `?format=csv` (or `?format=json`) wins, otherwise
+/// `text/csv` must be weighted above `application/json` in `Accept`
+pub(crate) fn wants_csv(req: &Request) -> bool {
+    if let Some(format) = req.query("format") {
+        return format.eq_ignore_ascii_case("csv");
+    }
+
+    let Some(accept) = req.header("Accept") else {
+        return false;
+    };
+    accept_quality(accept, "text/csv") > accept_quality(accept, "application/json")
+}
+
+/// The `q` weight `accept` gives to an exact media type, 0 when it isn't listed
+fn accept_quality(accept: &str, media_type: &str) -> f32 {
+    accept
+        .split(',')
+        .filter_map(|range| {
+            let mut params = range.split(';');
+            let mime = params.next()?.trim();
+            if !mime.eq_ignore_ascii_case(media_type) {
+                return None;
+            }
+            let quality = params
+                .filter_map(|param| param.trim().strip_prefix("q="))
+                .find_map(|q| q.trim().parse::<f32>().ok())
+                .unwrap_or(1.0);
+            Some(quality)
+        })
+        .fold(0.0, f32::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    struct Lift {
+        exercise: String,
+        weight: f32,
+    }
+
+    impl CsvRow for Lift {
+        fn csv_header() -> Vec<&'static str> {
+            vec!["exercise", "weight"]
+        }
+
+        fn csv_row(&self) -> Vec<String> {
+            vec![self.exercise.clone(), self.weight.to_string()]
+        }
+    }
+
+    #[test]
+    fn test_to_csv_quotes_fields_when_needed() {
+        let lifts = vec![
+            Lift {
+                exercise: "Bench Press".to_string(),
+                weight: 80.0,
+            },
+            Lift {
+                exercise: "Row, \"Pendlay\"".to_string(),
+                weight: 72.5,
+            },
+        ];
+
+        assert_eq!(
+            lifts.to_csv(),
+            "exercise,weight\r\nBench Press,80\r\n\"Row, \"\"Pendlay\"\"\",72.5\r\n"
+        );
+        assert_eq!(Vec::<Lift>::new().to_csv(), "exercise,weight\r\n");
+    }
+
+    fn request(accept: Option<&str>, format: Option<&str>) -> Request {
+        let mut headers = json!({});
+        if let Some(accept) = accept {
+            headers["accept"] = json!(accept);
+        }
+        let mut event = json!({
+            "rawPath": "/api/analytics/me/body-measurements",
+            "requestContext": { "http": { "method": "GET" } },
+            "headers": headers
+        });
+        if let Some(format) = format {
+            event["queryStringParameters"] = json!({ "format": format });
+        }
+        Request::from_lambda_event(event)
+    }
+
+    #[test]
+    fn test_wants_csv() {
+        assert!(wants_csv(&request(Some("text/csv"), None)));
+        assert!(wants_csv(&request(
+            Some("application/json;q=0.5, text/csv"),
+            None
+        )));
+        assert!(!wants_csv(&request(
+            Some("application/json, text/csv"),
+            None
+        )));
+        assert!(!wants_csv(&request(Some("*/*"), None)));
+        assert!(!wants_csv(&request(None, None)));
+
+        // The query parameter overrides the header either way
+        assert!(wants_csv(&request(None, Some("csv"))));
+        assert!(!wants_csv(&request(Some("text/csv"), Some("json"))));
+    }
+}
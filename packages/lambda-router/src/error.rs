@@ -1,26 +1,54 @@
 use crate::response::Response;
 use thiserror::Error;
 
+/// `Retry-After` used for `TooManyRequests` errors, which carry no window of their own
+const DEFAULT_RETRY_AFTER_SECS: u32 = 60;
+
 /// Router-specific errors
 #[derive(Error, Debug)]
 pub enum RouterError {
     #[error("Route not found: {method} {path}")]
     RouteNotFound { method: String, path: String },
 
+    /// The path exists but not for this method; `allowed` lists the methods it does
+    /// take and becomes the `Allow` header
     #[error("Method not allowed: {method}")]
-    MethodNotAllowed { method: String },
+    MethodNotAllowed {
+        method: String,
+        allowed: Vec<String>,
+    },
 
     #[error("Bad request: {0}")]
     BadRequest(String),
 
+    /// The route takes bodies of type `expected` only
+    #[error("Unsupported media type: expected {expected}")]
+    UnsupportedMediaType {
+        expected: String,
+        actual: Option<String>,
+    },
+
     #[error("Unauthorized: {0}")]
     Unauthorized(String),
 
     #[error("Forbidden: {0}")]
     Forbidden(String),
 
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
+    /// A conditional request header (e.g. `If-Match`) no longer holds
+    #[error("Precondition failed: {0}")]
+    PreconditionFailed(String),
+
+    #[error("Too many requests: {0}")]
+    TooManyRequests(String),
+
     #[error("Internal server error: {0}")]
-    InternalError(String),
+    Internal(String),
 
     #[error("JSON parsing error: {0}")]
     JsonError(#[from] serde_json::Error),
@@ -32,38 +60,165 @@ pub enum RouterError {
 // Implement From<&str> for convenience
 impl From<&str> for RouterError {
     fn from(s: &str) -> Self {
-        RouterError::InternalError(s.to_string())
+        RouterError::Internal(s.to_string())
     }
 }
 
 // Implement From<String> for convenience
 impl From<String> for RouterError {
     fn from(s: String) -> Self {
-        RouterError::InternalError(s)
+        RouterError::Internal(s)
+    }
+}
+
+// Boxed errors from services keep their status if they already are a RouterError
+impl From<Box<dyn std::error::Error + Send + Sync>> for RouterError {
+    fn from(e: Box<dyn std::error::Error + Send + Sync>) -> Self {
+        match e.downcast::<RouterError>() {
+            Ok(router_error) => *router_error,
+            Err(e) => RouterError::Internal(e.to_string()),
+        }
     }
 }
 
 impl RouterError {
-    /// Convert RouterError to HTTP Response
+    /// Convert RouterError to HTTP Response. The body is `{error, message}`; the router
+    /// adds the request id when it sends the response
     pub fn to_response(&self) -> Response {
         match self {
             RouterError::RouteNotFound { method, path } => {
                 Response::not_found(&format!("Route not found: {} {}", method, path))
             }
-            RouterError::MethodNotAllowed { method } => {
-                Response::method_not_allowed(&format!("Method not allowed: {}", method))
+            RouterError::MethodNotAllowed { method, allowed } => {
+                let response =
+                    Response::method_not_allowed(&format!("Method not allowed: {}", method));
+                if allowed.is_empty() {
+                    response
+                } else {
+                    response.header("Allow", allowed.join(", "))
+                }
             }
             RouterError::BadRequest(msg) => Response::bad_request(msg),
+            RouterError::UnsupportedMediaType { expected, actual } => {
+                let message = match actual {
+                    Some(actual) => format!("Expected Content-Type {}, got {}", expected, actual),
+                    None => format!("Expected Content-Type {}", expected),
+                };
+                Response::unsupported_media_type(&message)
+            }
             RouterError::Unauthorized(msg) => Response::unauthorized(msg),
             RouterError::Forbidden(msg) => Response::forbidden(msg),
-            RouterError::InternalError(msg) => Response::internal_error(msg),
-            RouterError::JsonError(e) => Response::bad_request(&format!("Invalid JSON: {}", e)),
-            RouterError::HandlerError(e) => {
-                Response::internal_error(&format!("Handler error: {}", e))
+            RouterError::NotFound(msg) => Response::not_found(msg),
+            RouterError::Conflict(msg) => Response::conflict(msg),
+            RouterError::PreconditionFailed(msg) => Response::precondition_failed(msg),
+            RouterError::TooManyRequests(msg) => {
+                Response::too_many_requests(DEFAULT_RETRY_AFTER_SECS, msg)
             }
+            RouterError::Internal(msg) => Response::internal_error(msg),
+            RouterError::JsonError(e) => Response::bad_request(&format!("Invalid JSON: {}", e)),
+            RouterError::HandlerError(e) => match e.downcast_ref::<RouterError>() {
+                Some(router_error) => router_error.to_response(),
+                None => Response::internal_error(&format!("Handler error: {}", e)),
+            },
         }
     }
 }
 
+impl From<RouterError> for Response {
+    fn from(e: RouterError) -> Self {
+        e.to_response()
+    }
+}
+
 /// Result type alias for router operations
 pub type Result<T> = std::result::Result<T, RouterError>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+
+    fn status_and_body(error: RouterError) -> (u16, Value) {
+        let response = error.to_response();
+        (
+            response.status_code,
+            serde_json::from_str(&response.body).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_error_variants_map_to_status() {
+        let cases = [
+            (RouterError::BadRequest("bad".into()), 400, "Bad Request"),
+            (RouterError::Unauthorized("who".into()), 401, "Unauthorized"),
+            (RouterError::Forbidden("no".into()), 403, "Forbidden"),
+            (RouterError::NotFound("gone".into()), 404, "Not Found"),
+            (RouterError::Conflict("stale".into()), 409, "Conflict"),
+            (
+                RouterError::PreconditionFailed("stale".into()),
+                412,
+                "Precondition Failed",
+            ),
+            (
+                RouterError::TooManyRequests("slow".into()),
+                429,
+                "Too Many Requests",
+            ),
+            (
+                RouterError::Internal("boom".into()),
+                500,
+                "Internal Server Error",
+            ),
+        ];
+
+        for (error, status, label) in cases {
+            let message = match &error {
+                RouterError::BadRequest(m)
+                | RouterError::Unauthorized(m)
+                | RouterError::Forbidden(m)
+                | RouterError::NotFound(m)
+                | RouterError::Conflict(m)
+                | RouterError::PreconditionFailed(m)
+                | RouterError::TooManyRequests(m)
+                | RouterError::Internal(m) => m.clone(),
+                _ => unreachable!(),
+            };
+            let (code, body) = status_and_body(error);
+            assert_eq!(code, status);
+            assert_eq!(body["error"], label);
+            assert_eq!(body["message"], message.as_str());
+        }
+    }
+
+    #[test]
+    fn test_method_not_allowed_lists_allowed_methods() {
+        let response = RouterError::MethodNotAllowed {
+            method: "DELETE".into(),
+            allowed: vec!["GET".into(), "POST".into()],
+        }
+        .to_response();
+        assert_eq!(response.status_code, 405);
+        assert_eq!(
+            response.headers.get("Allow"),
+            Some(&"GET, POST".to_string())
+        );
+    }
+
+    #[test]
+    fn test_boxed_router_error_keeps_status() {
+        let boxed: Box<dyn std::error::Error + Send + Sync> =
+            Box::new(RouterError::Conflict("Plan was modified".into()));
+        let (code, _) = status_and_body(RouterError::from(boxed));
+        assert_eq!(code, 409);
+
+        let boxed: Box<dyn std::error::Error + Send + Sync> = "socket closed".into();
+        let (code, body) = status_and_body(RouterError::from(boxed));
+        assert_eq!(code, 500);
+        assert_eq!(body["message"], "socket closed");
+
+        let (code, _) = status_and_body(RouterError::HandlerError(anyhow::Error::new(
+            RouterError::NotFound("Plan".into()),
+        )));
+        assert_eq!(code, 404);
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::hash_map;
+use std::collections::HashMap;
+
+/// Request headers with case-insensitive names. Names are stored lowercased (the form
+/// HTTP/2 and payload format 2.0 already use) and lookups lowercase the name they are
+/// given, so `get("Authorization")` and `get("authorization")` find the same value
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HeaderMap {
+    inner: HashMap<String, String>,
+}
+
+impl HeaderMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get a header value by name, in any casing
+    pub fn get(&self, name: &str) -> Option<&String> {
+        self.inner.get(&name.to_ascii_lowercase())
+    }
+
+    pub fn contains_key(&self, name: &str) -> bool {
+        self.inner.contains_key(&name.to_ascii_lowercase())
+    }
+
+    /// Set a header, replacing any value stored under the same name in another casing
+    pub fn insert(&mut self, name: impl AsRef<str>, value: impl Into<String>) -> Option<String> {
+        self.inner
+            .insert(name.as_ref().to_ascii_lowercase(), value.into())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        self.inner.remove(&name.to_ascii_lowercase())
+    }
+
+    /// Iterate over `(name, value)` pairs; names are lowercase
+    pub fn iter(&self) -> hash_map::Iter<'_, String, String> {
+        self.inner.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// The headers as a plain map with lowercase names, for APIs that take one
+    pub fn to_hash_map(&self) -> HashMap<String, String> {
+        self.inner.clone()
+    }
+}
+
+impl<K: AsRef<str>, V: Into<String>> FromIterator<(K, V)> for HeaderMap {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut headers = Self::new();
+        headers.extend(iter);
+        headers
+    }
+}
+
+impl<K: AsRef<str>, V: Into<String>> Extend<(K, V)> for HeaderMap {
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (name, value) in iter {
+            self.insert(name, value);
+        }
+    }
+}
+
+impl From<HashMap<String, String>> for HeaderMap {
+    fn from(headers: HashMap<String, String>) -> Self {
+        headers.into_iter().collect()
+    }
+}
+
+impl From<HeaderMap> for HashMap<String, String> {
+    fn from(headers: HeaderMap) -> Self {
+        headers.inner
+    }
+}
+
+impl<'a> IntoIterator for &'a HeaderMap {
+    type Item = (&'a String, &'a String);
+    type IntoIter = hash_map::Iter<'a, String, String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.inner.iter()
+    }
+}
+
+impl IntoIterator for HeaderMap {
+    type Item = (String, String);
+    type IntoIter = hash_map::IntoIter<String, String>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.inner.into_iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lookups_ignore_name_casing() {
+        let mut headers: HeaderMap = [("Authorization", "Bearer abc"), ("X-Api-Key", "k1")]
+            .into_iter()
+            .collect();
+
+        assert_eq!(
+            headers.get("authorization"),
+            Some(&"Bearer abc".to_string())
+        );
+        assert_eq!(
+            headers.get("AUTHORIZATION"),
+            Some(&"Bearer abc".to_string())
+        );
+        assert!(headers.contains_key("x-api-key"));
+
+        // A differently-cased insert replaces rather than duplicates
+        headers.insert("x-API-key", "k2");
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers.get("X-Api-Key"), Some(&"k2".to_string()));
+
+        assert_eq!(headers.remove("X-API-KEY"), Some("k2".to_string()));
+        assert!(headers.iter().all(|(name, _)| name == "authorization"));
+    }
+}
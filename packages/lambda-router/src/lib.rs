@@ -10,8 +10,17 @@
 //! - Type-safe request/response handling
 //! - Path parameter extraction
 //! - Query string parsing
-//! - JSON body parsing
+//! - Case-insensitive header and method matching
+//! - JSON body parsing, with optional JSON Schema validation (`Request::validated_json`)
+//! - Cursor-paginated list responses with optional totals (`Request::page_params`, `ListResponse`)
 //! - Error handling with proper HTTP status codes
+//! - JSON / CSV content negotiation (`Accept` or `?format=csv`)
+//! - `ETag` / `If-None-Match` handling with `304 Not Modified` responses
+//! - Optional `Server-Timing` phase breakdown (`SERVER_TIMING_DEBUG=1`)
+//! - Optional X-Ray subsegments per route and timed phase (`Router::enable_xray`)
+//! - Per-route GET response caching (`CacheMiddleware`)
+//! - API Gateway WebSocket events routed by route key (`Router::websocket`)
+//! - Shutdown hooks for flushing buffered metrics on `SIGTERM` (`Router::on_shutdown`)
 //!
 //! ## Example
 //! ```rust,no_run
@@ -37,19 +46,40 @@
 //! }
 //! ```
 
+pub mod cache;
+pub mod cookie;
 pub mod cors;
+pub mod csv;
 pub mod error;
+pub mod headers;
 pub mod matcher;
 pub mod middleware;
+pub mod openapi;
+pub mod pagination;
 pub mod request;
 pub mod response;
 pub mod router;
+pub mod schema;
+pub mod server_timing;
+pub mod xray;
 
 // Re-export main types
+pub use cache::{CacheMiddleware, CACHE_STATUS_HEADER};
+pub use cookie::{CookieOptions, SameSite};
 pub use cors::CorsConfig;
+pub use csv::{CsvRow, ToCsv};
 pub use error::{Result, RouterError};
+pub use headers::HeaderMap;
 pub use matcher::PathMatcher;
 pub use middleware::{Middleware, Next};
-pub use request::{Context, Request};
-pub use response::Response;
-pub use router::{Handler, HandlerFn, Router};
+pub use openapi::RouteInfo;
+pub use pagination::{ListResponse, PageCursor, PageParams};
+pub use request::{
+    correlation_id_from_headers, parse_bearer_token, Context, HandlerContext, Request,
+    CORRELATION_ID_HEADER,
+};
+pub use response::{compute_etag, etag_matches, PayloadFormat, Response, ETAG_HEADER};
+pub use router::{AfterHookFn, Handler, HandlerFn, Router, ShutdownHookFn};
+pub use schema::{FieldError, JsonSchema};
+pub use server_timing::{record_phase, time_phase, ServerTiming};
+pub use xray::{trace_subsegment, Subsegment};
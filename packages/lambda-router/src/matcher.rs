@@ -1,9 +1,43 @@
 use regex::Regex;
-use lazy_static::lazy_static;
 use std::collections::HashMap;
 
-lazy_static! {
-    static ref PARAM_REGEX: Regex = Regex::new(r":([a-zA-Z_][a-zA-Z0-9_]*)").unwrap();
+/// One `/`-separated piece of a route pattern, in increasing order of how much it
+/// matches (and decreasing precedence)
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Segment {
+    Static(String),
+    /// `:name`, exactly one path segment
+    Param,
+    /// `*name`, the rest of the path (one or more segments); only valid last
+    Wildcard,
+}
+
+impl Segment {
+    fn parse(segment: &str) -> (Self, Option<String>) {
+        if let Some(name) = segment.strip_prefix(':') {
+            (Segment::Param, Some(name.to_string()))
+        } else if let Some(name) = segment.strip_prefix('*') {
+            (Segment::Wildcard, Some(name.to_string()))
+        } else {
+            (Segment::Static(segment.to_string()), None)
+        }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Segment::Static(_) => 0,
+            Segment::Param => 1,
+            Segment::Wildcard => 2,
+        }
+    }
+
+    fn regex(&self) -> String {
+        match self {
+            Segment::Static(segment) => regex::escape(segment),
+            Segment::Param => "([^/]+)".to_string(),
+            Segment::Wildcard => "(.+)".to_string(),
+        }
+    }
 }
 
 /// Path matcher with parameter extraction
@@ -11,31 +45,33 @@ lazy_static! {
 pub struct PathMatcher {
     pattern: String,
     regex: Regex,
+    segments: Vec<Segment>,
     param_names: Vec<String>,
 }
 
 impl PathMatcher {
     /// Create a new PathMatcher from a route pattern
-    /// Supports Express-like patterns: /api/users/:userId/posts/:postId
+    /// Supports Express-like patterns: /api/users/:userId/posts/:postId, plus a trailing
+    /// `*name` wildcard that captures the rest of the path: /api/files/*key
     pub fn new(pattern: &str) -> Self {
+        let mut segments = Vec::new();
         let mut param_names = Vec::new();
-        
-        // Extract parameter names
-        for cap in PARAM_REGEX.captures_iter(pattern) {
-            param_names.push(cap[1].to_string());
+        for segment in pattern.split('/').skip(1) {
+            let (segment, name) = Segment::parse(segment);
+            segments.push(segment);
+            param_names.extend(name);
         }
-        
-        // Convert Express-style pattern to regex
-        let regex_pattern = PARAM_REGEX
-            .replace_all(pattern, r"([^/]+)")
-            .replace("/", r"/");
-        
-        let regex_pattern = format!("^{}$", regex_pattern);
-        let regex = Regex::new(&regex_pattern).unwrap();
-        
+
+        let regex_pattern: String = segments
+            .iter()
+            .map(|segment| format!("/{}", segment.regex()))
+            .collect();
+        let regex = Regex::new(&format!("^{}$", regex_pattern)).unwrap();
+
         Self {
             pattern: pattern.to_string(),
             regex,
+            segments,
             param_names,
         }
     }
@@ -57,6 +93,34 @@ impl PathMatcher {
     pub fn pattern(&self) -> &str {
         &self.pattern
     }
+    
+    /// Get the parameter names in the order they appear in the pattern
+    pub fn param_names(&self) -> &[String] {
+        &self.param_names
+    }
+
+    /// Sort key for choosing between patterns that both match a path: compared segment
+    /// by segment, static beats `:param` beats `*wildcard` at the first difference
+    pub(crate) fn precedence(&self) -> Vec<u8> {
+        self.segments.iter().map(Segment::precedence).collect()
+    }
+
+    /// Whether some path could match both patterns
+    pub fn could_overlap(&self, other: &PathMatcher) -> bool {
+        fn overlap(a: &[Segment], b: &[Segment]) -> bool {
+            match (a.split_first(), b.split_first()) {
+                (None, None) => true,
+                // A wildcard swallows whatever is left, as long as something is
+                (Some((Segment::Wildcard, _)), Some(_)) | (Some(_), Some((Segment::Wildcard, _))) => true,
+                (Some((Segment::Static(x), a_rest)), Some((Segment::Static(y), b_rest))) => {
+                    x == y && overlap(a_rest, b_rest)
+                }
+                (Some((_, a_rest)), Some((_, b_rest))) => overlap(a_rest, b_rest),
+                _ => false,
+            }
+        }
+        overlap(&self.segments, &other.segments)
+    }
 }
 
 #[cfg(test)]
@@ -98,4 +162,42 @@ mod tests {
         assert_eq!(params.get("userId"), Some(&"user123".to_string()));
         assert_eq!(params.get("mealId"), Some(&"meal456".to_string()));
     }
+
+    #[test]
+    fn test_wildcard_captures_rest_of_path() {
+        let matcher = PathMatcher::new("/api/files/*key");
+
+        let params = matcher.matches("/api/files/users/1/photo.png").unwrap();
+        assert_eq!(params.get("key"), Some(&"users/1/photo.png".to_string()));
+        assert!(matcher.matches("/api/files").is_none());
+    }
+
+    #[test]
+    fn test_static_segments_are_literal() {
+        let matcher = PathMatcher::new("/api/v1.0/health");
+        assert!(matcher.matches("/api/v1.0/health").is_some());
+        assert!(matcher.matches("/api/v1x0/health").is_none());
+    }
+
+    #[test]
+    fn test_precedence_prefers_static_then_param_then_wildcard() {
+        let literal = PathMatcher::new("/api/workouts/plans/schedule");
+        let param = PathMatcher::new("/api/workouts/plans/:planId");
+        let wildcard = PathMatcher::new("/api/workouts/*rest");
+
+        assert!(literal.precedence() < param.precedence());
+        assert!(param.precedence() < wildcard.precedence());
+    }
+
+    #[test]
+    fn test_could_overlap() {
+        let param = PathMatcher::new("/api/workouts/plans/:planId");
+
+        assert!(param.could_overlap(&PathMatcher::new("/api/workouts/plans/schedule")));
+        assert!(param.could_overlap(&PathMatcher::new("/api/workouts/*rest")));
+        assert!(param.could_overlap(&PathMatcher::new("/api/workouts/plans/:id")));
+        assert!(!param.could_overlap(&PathMatcher::new("/api/workouts/plans")));
+        assert!(!param.could_overlap(&PathMatcher::new("/api/workouts/sessions/:id")));
+        assert!(!param.could_overlap(&PathMatcher::new("/api/workouts/plans/:planId/restore")));
+    }
 }
@@ -2,10 +2,17 @@ use async_trait::async_trait;
 use lambda_runtime::Error;
 use crate::{Request, Response};
 
-/// Next function type for middleware chain
+/// Next function type for middleware chain. Call it at most once: a second call
+/// fails with an internal error instead of running the rest of the chain again
 pub type Next = Box<dyn Fn(Request) -> futures::future::BoxFuture<'static, Result<Response, Error>> + Send + Sync>;
 
 /// Middleware trait
+///
+/// Middlewares run outer-to-inner in the order they are registered with
+/// `Router::use_middleware`, and unwind in reverse on the way back out. A middleware
+/// either passes the request on by awaiting `next` once, or answers itself by
+/// returning without calling it (e.g. auth rejecting a request), in which case the
+/// inner middlewares and the handler never run
 #[async_trait]
 pub trait Middleware: Send + Sync {
     /// Execute middleware
@@ -39,15 +46,17 @@ where
     }
 }
 
-/// Logging middleware
+/// Logging middleware. The duration is measured from when the router built the
+/// request context, so it includes middleware registered before this one
 pub struct LoggingMiddleware;
 
 #[async_trait]
 impl Middleware for LoggingMiddleware {
     async fn handle(&self, req: Request, next: Next) -> Result<Response, Error> {
         println!("→ {} {}", req.method, req.path);
+        let ctx = req.context.clone();
         let response = next(req).await?;
-        println!("← {}", response.status_code);
+        println!("← {} ({} ms)", response.status_code, ctx.elapsed().as_millis());
         Ok(response)
     }
 }
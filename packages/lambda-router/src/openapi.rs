@@ -0,0 +1,116 @@
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use crate::PathMatcher;
+
+/// A registered route as exposed by `Router::routes`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RouteInfo {
+    pub method: String,
+    pub pattern: String,
+}
+
+impl RouteInfo {
+    /// Path parameter names inferred from `:name` segments
+    pub fn path_params(&self) -> Vec<String> {
+        PathMatcher::new(&self.pattern).param_names().to_vec()
+    }
+
+    /// The pattern in OpenAPI form, e.g. `/api/users/:userId` -> `/api/users/{userId}`
+    pub fn openapi_path(&self) -> String {
+        self.pattern
+            .split('/')
+            .map(|segment| match segment.strip_prefix(':') {
+                Some(name) => format!("{{{}}}", name),
+                None => segment.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("/")
+    }
+}
+
+/// Build a minimal OpenAPI 3 document listing paths, methods and path parameters.
+/// No request/response schemas are generated
+pub fn document(title: &str, version: &str, routes: &[RouteInfo]) -> Value {
+    let mut paths = Map::new();
+
+    for route in routes {
+        let parameters: Vec<Value> = route
+            .path_params()
+            .into_iter()
+            .map(|name| {
+                json!({
+                    "name": name,
+                    "in": "path",
+                    "required": true,
+                    "schema": { "type": "string" }
+                })
+            })
+            .collect();
+
+        let operation = json!({
+            "parameters": parameters,
+            "responses": { "default": { "description": "Response" } }
+        });
+
+        let path_item = paths
+            .entry(route.openapi_path())
+            .or_insert_with(|| Value::Object(Map::new()));
+        if let Value::Object(methods) = path_item {
+            methods.insert(route.method.to_lowercase(), operation);
+        }
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": { "title": title, "version": version },
+        "paths": paths
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{handler, Context, Request, Response, Result, Router};
+    use serde_json::json;
+
+    async fn noop(_req: Request, _ctx: Context) -> Result<Response> {
+        Ok(Response::no_content())
+    }
+
+    fn router() -> Router {
+        let mut router = Router::new();
+        router.get("/api/users/:userId", handler!(noop));
+        router.put("/api/users/:userId", handler!(noop));
+        router.get("/api/users/:userId/posts/:postId", handler!(noop));
+        router.post("/api/health", handler!(noop));
+        router
+    }
+
+    #[test]
+    fn test_routes_lists_registered_routes() {
+        let routes = router().routes();
+
+        assert_eq!(routes.len(), 4);
+        assert_eq!(routes[0].method, "GET");
+        assert_eq!(routes[0].pattern, "/api/users/:userId");
+        assert_eq!(routes[2].path_params(), vec!["userId", "postId"]);
+    }
+
+    #[test]
+    fn test_openapi_paths_and_params() {
+        let doc = router().openapi("Users API", "1.0.0");
+
+        assert_eq!(doc["info"]["title"], "Users API");
+
+        let user = &doc["paths"]["/api/users/{userId}"];
+        assert!(user["get"].is_object());
+        assert!(user["put"].is_object());
+        assert_eq!(user["get"]["parameters"][0]["name"], "userId");
+        assert_eq!(user["get"]["parameters"][0]["in"], "path");
+
+        let post = &doc["paths"]["/api/users/{userId}/posts/{postId}"]["get"];
+        assert_eq!(post["parameters"].as_array().unwrap().len(), 2);
+
+        assert_eq!(doc["paths"]["/api/health"]["post"]["parameters"], json!([]));
+    }
+}
@@ -0,0 +1,171 @@
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL, Engine as _};
+use serde::{Deserialize, Serialize};
+
+use crate::{Request, Response};
+
+/// Page size when a paginated request doesn't give `limit`
+pub const DEFAULT_PAGE_LIMIT: usize = 20;
+/// Largest `limit` a client can ask for; bigger values are clamped
+pub const MAX_PAGE_LIMIT: usize = 100;
+
+/// One page of a list endpoint, as
+/// `{"items", "page", "limit", "total", "next_cursor"}`. `next_cursor` is sent back as
+/// `?cursor=` for the following page and is `null` on the last one. `total` is only
+/// counted when the request asks for it with `?include_total=true`, since it costs a
+/// separate query
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ListResponse<T> {
+    pub items: Vec<T>,
+    /// 1-based
+    pub page: u32,
+    pub limit: usize,
+    pub total: Option<u64>,
+    pub next_cursor: Option<String>,
+}
+
+impl<T> ListResponse<T> {
+    /// The page `params` asked for. `last_key` is the sort key to resume after when more
+    /// items remain
+    pub fn new(items: Vec<T>, params: &PageParams, last_key: Option<String>, total: Option<u64>) -> Self {
+        let page = params.page();
+        Self {
+            items,
+            page,
+            limit: params.limit,
+            total,
+            next_cursor: last_key.map(|last_key| PageCursor { page: page + 1, last_key }.encode()),
+        }
+    }
+}
+
+/// Where a page starts: the sort key of the last item before it, and its page number.
+/// Clients treat the encoded form as opaque
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PageCursor {
+    pub page: u32,
+    pub last_key: String,
+}
+
+impl PageCursor {
+    pub fn encode(&self) -> String {
+        BASE64_URL.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        let bytes = BASE64_URL.decode(cursor).ok()?;
+        serde_json::from_slice(&bytes).ok()
+    }
+}
+
+/// The pagination query parameters of a list request
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageParams {
+    pub limit: usize,
+    pub cursor: Option<PageCursor>,
+    pub include_total: bool,
+}
+
+impl PageParams {
+    /// 1-based number of the requested page
+    pub fn page(&self) -> u32 {
+        self.cursor.as_ref().map_or(1, |cursor| cursor.page)
+    }
+
+    /// Sort key to resume after, `None` for the first page
+    pub fn start_key(&self) -> Option<&str> {
+        self.cursor.as_ref().map(|cursor| cursor.last_key.as_str())
+    }
+}
+
+impl Request {
+    /// Read `limit`, `cursor` and `include_total` from the query string. `None` when none
+    /// of them are given, so endpoints that used to return a bare list can keep doing so
+    /// for existing clients. The error is a ready `400` (boxed, as `Response` is large)
+    /// for an unreadable limit or cursor
+    pub fn page_params(&self) -> Result<Option<PageParams>, Box<Response>> {
+        let (limit, cursor, include_total) = (
+            self.query("limit"),
+            self.query("cursor"),
+            self.query("include_total"),
+        );
+        if limit.is_none() && cursor.is_none() && include_total.is_none() {
+            return Ok(None);
+        }
+
+        let limit = match limit {
+            Some(limit) => limit
+                .parse::<usize>()
+                .map_err(|_| Box::new(Response::bad_request("Invalid limit")))?
+                .clamp(1, MAX_PAGE_LIMIT),
+            None => DEFAULT_PAGE_LIMIT,
+        };
+        let cursor = match cursor {
+            Some(cursor) => {
+                Some(PageCursor::decode(cursor).ok_or_else(|| Box::new(Response::bad_request("Invalid cursor")))?)
+            }
+            None => None,
+        };
+
+        Ok(Some(PageParams {
+            limit,
+            cursor,
+            include_total: include_total.is_some_and(|value| value == "true" || value == "1"),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn request(query: Value) -> Request {
+        Request::from_lambda_event(json!({
+            "rawPath": "/api/items",
+            "requestContext": { "http": { "method": "GET" } },
+            "queryStringParameters": query
+        }))
+    }
+
+    #[test]
+    fn test_page_params_are_opt_in() {
+        assert_eq!(request(json!({})).page_params().unwrap(), None);
+
+        let params = request(json!({ "include_total": "true" })).page_params().unwrap().unwrap();
+        assert_eq!(params.limit, DEFAULT_PAGE_LIMIT);
+        assert_eq!(params.page(), 1);
+        assert!(params.include_total);
+
+        let params = request(json!({ "limit": "500" })).page_params().unwrap().unwrap();
+        assert_eq!(params.limit, MAX_PAGE_LIMIT);
+        assert!(!params.include_total);
+    }
+
+    #[test]
+    fn test_invalid_limit_or_cursor_is_rejected() {
+        let response = request(json!({ "limit": "ten" })).page_params().unwrap_err();
+        assert_eq!(response.status_code, 400);
+
+        let response = request(json!({ "cursor": "not-a-cursor" })).page_params().unwrap_err();
+        assert_eq!(response.status_code, 400);
+    }
+
+    #[test]
+    fn test_next_cursor_leads_to_the_following_page() {
+        let first = PageParams { limit: 2, cursor: None, include_total: false };
+        let page = ListResponse::new(vec!["a", "b"], &first, Some("ITEM#b".to_string()), Some(5));
+        assert_eq!(page.page, 1);
+        assert_eq!(page.total, Some(5));
+
+        let cursor = page.next_cursor.unwrap();
+        let second = request(json!({ "limit": "2", "cursor": cursor })).page_params().unwrap().unwrap();
+        assert_eq!(second.page(), 2);
+        assert_eq!(second.start_key(), Some("ITEM#b"));
+
+        let last = ListResponse::new(vec!["c"], &second, None, None);
+        assert_eq!(last.next_cursor, None);
+
+        let body = serde_json::to_value(&last).unwrap();
+        assert_eq!(body, json!({ "items": ["c"], "page": 2, "limit": 2, "total": null, "next_cursor": null }));
+    }
+}
@@ -1,28 +1,89 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+use uuid::Uuid;
+
+use crate::cookie::parse_cookie_header;
+use crate::headers::HeaderMap;
+use crate::response::is_websocket_event;
+use crate::RouterError;
+
+/// Extract the token from a `Bearer <token>` authorization value; the scheme is
+/// matched case-insensitively
+pub fn parse_bearer_token(value: &str) -> Option<&str> {
+    let (scheme, token) = value.trim().split_once(char::is_whitespace)?;
+    let token = token.trim();
+    
+    (scheme.eq_ignore_ascii_case("bearer") && !token.is_empty()).then_some(token)
+}
+
+/// Header carrying the id that ties together the logs of one request across services
+pub const CORRELATION_ID_HEADER: &str = "X-Correlation-Id";
+
+/// The caller's correlation id from a header map, ignoring blank values
+pub fn correlation_id_from_headers(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(CORRELATION_ID_HEADER)
+        .map(|value| value.trim())
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
 
 /// Request context from Lambda event
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Context {
     pub request_id: String,
+    /// The caller's `X-Correlation-Id`, or a new id when the request starts here
+    #[serde(default)]
+    pub correlation_id: String,
     pub user_id: Option<String>,
     pub email: Option<String>,
     pub auth_token: Option<String>,
     pub custom: HashMap<String, Value>,
+    /// API Gateway WebSocket connection the event came in on; `None` for HTTP requests
+    #[serde(default)]
+    pub connection_id: Option<String>,
+    /// WebSocket route key, e.g. `$connect`, `$disconnect`, `$default` or a custom
+    /// route selected from the message; `None` for HTTP requests
+    #[serde(default)]
+    pub route_key: Option<String>,
+    /// When the router built the context. Not serialized; a deserialized context
+    /// starts its clock at deserialization
+    #[serde(skip, default = "Instant::now")]
+    start: Instant,
+    #[serde(skip, default = "SystemTime::now")]
+    started_at: SystemTime,
 }
 
 impl Context {
     pub fn new(request_id: String) -> Self {
         Self {
+            correlation_id: Uuid::new_v4().to_string(),
             request_id,
             user_id: None,
             email: None,
             auth_token: None,
             custom: HashMap::new(),
+            connection_id: None,
+            route_key: None,
+            start: Instant::now(),
+            started_at: SystemTime::now(),
         }
     }
     
+    /// Time since the request started, for deadline and shed-load decisions
+    pub fn elapsed(&self) -> Duration {
+        self.start.elapsed()
+    }
+    
+    /// Wall-clock time the request started
+    pub fn started_at(&self) -> SystemTime {
+        self.started_at
+    }
+    
     pub fn with_user(mut self, user_id: String, email: Option<String>) -> Self {
         self.user_id = Some(user_id);
         self.email = email;
@@ -33,31 +94,90 @@ impl Context {
         self.custom.insert(key, value);
         self
     }
+    
+    pub fn with_correlation_id(mut self, correlation_id: String) -> Self {
+        self.correlation_id = correlation_id;
+        self
+    }
+    
+    /// Whether the request came from an API Gateway WebSocket route
+    pub fn is_websocket(&self) -> bool {
+        self.connection_id.is_some()
+    }
+}
+
+/// Slot the router fills with the context the handler ran with. Clones share the
+/// slot, so middleware wrapping `next` can read user info that inner middleware (e.g.
+/// auth) attached on the way in
+#[derive(Debug, Clone, Default)]
+pub struct HandlerContext(Arc<Mutex<Option<Context>>>);
+
+impl HandlerContext {
+    /// The handler's context, or `None` if the request never reached a handler
+    pub fn get(&self) -> Option<Context> {
+        self.0
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+    
+    pub(crate) fn set(&self, context: Context) {
+        *self.0.lock().unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(context);
+    }
 }
 
 /// HTTP Request representation
 #[derive(Debug, Clone)]
 pub struct Request {
+    /// Uppercase method, e.g. `GET`; the event type, e.g. `MESSAGE`, for WebSocket events
     pub method: String,
+    /// Request path; the route key, e.g. `$connect`, for WebSocket events
     pub path: String,
-    pub headers: HashMap<String, String>,
+    /// Headers, looked up case-insensitively
+    pub headers: HeaderMap,
     pub query_params: HashMap<String, String>,
     pub path_params: HashMap<String, String>,
+    /// Body text, already decoded when the event was base64-encoded. `None` for bodies
+    /// that aren't UTF-8 (see `body_bytes`)
     pub body: Option<String>,
+    /// Whether the event body arrived base64-encoded
+    pub is_base64_encoded: bool,
     pub context: Context,
+    binary_body: Option<Vec<u8>>,
+    body_error: Option<String>,
+    matched_route: Option<String>,
+    required_permissions: Vec<String>,
+    handler_context: HandlerContext,
     raw_event: Value,
 }
 
 impl Request {
-    /// Create a new Request from Lambda event
+    /// Create a new Request from Lambda event (payload format 2.0, 1.0 via
+    /// `httpMethod` / `path`, or an API Gateway WebSocket event)
     pub fn from_lambda_event(event: Value) -> Self {
+        let websocket = is_websocket_event(&event);
+        let websocket_field = |field: &str| {
+            websocket
+                .then(|| event["requestContext"][field].as_str())
+                .flatten()
+                .map(str::to_string)
+        };
+        let route_key = websocket_field("routeKey");
+        
+        // WebSocket events have no method or path; the event type (CONNECT, MESSAGE,
+        // DISCONNECT) and route key stand in for them so logs stay readable
         let method = event["requestContext"]["http"]["method"]
             .as_str()
-            .unwrap_or("GET")
-            .to_string();
+            .or_else(|| event["httpMethod"].as_str())
+            .map(str::to_string)
+            .or_else(|| websocket_field("eventType"))
+            .unwrap_or_else(|| "GET".to_string())
+            .to_ascii_uppercase();
         
         let path = event["rawPath"]
             .as_str()
+            .or_else(|| event["path"].as_str())
+            .or(route_key.as_deref())
             .unwrap_or("/")
             .to_string();
         
@@ -65,7 +185,7 @@ impl Request {
             .and_then(|v| v.as_object())
             .map(|obj| {
                 obj.iter()
-                    .map(|(k, v)| (k.clone(), v.as_str().unwrap_or("").to_string()))
+                    .map(|(k, v)| (k.as_str(), v.as_str().unwrap_or("")))
                     .collect()
             })
             .unwrap_or_default();
@@ -79,15 +199,24 @@ impl Request {
             })
             .unwrap_or_default();
         
-        let body = event.get("body")
-            .and_then(|v| v.as_str())
-            .map(|s| s.to_string());
+        let is_base64_encoded = event["isBase64Encoded"].as_bool().unwrap_or(false);
+        let (body, binary_body, body_error) = match event.get("body").and_then(|v| v.as_str()) {
+            Some(body) if is_base64_encoded => decode_base64_body(body),
+            body => (body.map(|s| s.to_string()), None, None),
+        };
         
         let request_id = event["requestContext"]["requestId"]
             .as_str()
             .unwrap_or("unknown")
             .to_string();
         
+        let mut context = Context::new(request_id);
+        if let Some(correlation_id) = correlation_id_from_headers(&headers) {
+            context = context.with_correlation_id(correlation_id);
+        }
+        context.connection_id = websocket_field("connectionId");
+        context.route_key = route_key;
+        
         Self {
             method,
             path,
@@ -95,15 +224,53 @@ impl Request {
             query_params,
             path_params: HashMap::new(),
             body,
-            context: Context::new(request_id),
+            is_base64_encoded,
+            context,
+            binary_body,
+            body_error,
+            matched_route: None,
+            required_permissions: Vec::new(),
+            handler_context: HandlerContext::default(),
             raw_event: event,
         }
     }
     
-    /// Get header value
+    /// Get header value; the name is matched case-insensitively
     pub fn header(&self, name: &str) -> Option<&String> {
         self.headers.get(name)
-            .or_else(|| self.headers.get(&name.to_lowercase()))
+    }
+    
+    /// Get the bearer token from the `Authorization` header, matching the header name
+    /// and `Bearer` scheme case-insensitively. Falls back to a `token` query parameter
+    /// for WebSocket-style clients that cannot set headers
+    pub fn bearer_token(&self) -> Option<&str> {
+        match self.header("Authorization") {
+            Some(value) => parse_bearer_token(value),
+            None => self.query("token").map(|token| token.trim()).filter(|token| !token.is_empty()),
+        }
+    }
+    
+    /// Get the `If-Match` header used for optimistic concurrency checks
+    pub fn if_match(&self) -> Option<&str> {
+        self.header("If-Match").map(|value| value.as_str())
+    }
+    
+    /// Get request cookies. Payload format 2.0 moves them out of the headers into a
+    /// `cookies` array, so both that and any `Cookie` header are read
+    pub fn cookies(&self) -> HashMap<String, String> {
+        let mut pairs: Vec<String> = self.raw_event["cookies"]
+            .as_array()
+            .map(|cookies| {
+                cookies
+                    .iter()
+                    .filter_map(|c| c.as_str().map(|s| s.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        
+        pairs.extend(self.header("Cookie").cloned());
+        
+        parse_cookie_header(&pairs.join("; "))
     }
     
     /// Get query parameter
@@ -124,19 +291,105 @@ impl Request {
         }
     }
     
-    /// Get raw body
+    /// Get the body text (decoded if it was sent base64-encoded)
     pub fn body(&self) -> Option<&str> {
         self.body.as_deref()
     }
     
-    /// Get raw Lambda event
+    /// Get the body as bytes, for binary uploads. Empty when there is no body
+    pub fn body_bytes(&self) -> Vec<u8> {
+        match (&self.binary_body, &self.body) {
+            (Some(bytes), _) => bytes.clone(),
+            (None, Some(body)) => body.as_bytes().to_vec(),
+            (None, None) => Vec::new(),
+        }
+    }
+    
+    /// Why the body couldn't be decoded, when `isBase64Encoded` was set on a body that
+    /// isn't valid base64. The router answers such requests with a `400`
+    pub(crate) fn body_error(&self) -> Option<&str> {
+        self.body_error.as_deref()
+    }
+    
+    /// Parse `application/x-www-form-urlencoded` body into key/value pairs.
+    /// Repeated keys keep the last value.
+    pub fn form(&self) -> Result<HashMap<String, String>, RouterError> {
+        let raw = match &self.body {
+            Some(body) => body,
+            None if self.binary_body.is_some() => {
+                return Err(RouterError::BadRequest("Form body is not valid UTF-8".to_string()))
+            }
+            None => return Ok(HashMap::new()),
+        };
+        
+        let mut params = HashMap::new();
+        for pair in raw.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            params.insert(decode_form_component(key)?, decode_form_component(value)?);
+        }
+        Ok(params)
+    }
+    
+    /// Get raw Lambda event. Prefer the typed accessors below for the common
+    /// `requestContext` fields
     pub fn raw_event(&self) -> &Value {
         &self.raw_event
     }
     
+    /// The event's `requestContext` object, if present
+    pub fn request_context(&self) -> Option<&Value> {
+        self.raw_event.get("requestContext").filter(|v| v.is_object())
+    }
+    
+    /// API Gateway's request id (`requestContext.requestId`)
+    pub fn request_id(&self) -> Option<&str> {
+        self.request_context_str(&["requestId"])
+    }
+    
+    /// Caller IP as seen by API Gateway: `requestContext.http.sourceIp` (payload 2.0)
+    /// or `requestContext.identity.sourceIp` (1.0). Not proxy-aware
+    pub fn source_ip(&self) -> Option<&str> {
+        self.request_context_str(&["http", "sourceIp"])
+            .or_else(|| self.request_context_str(&["identity", "sourceIp"]))
+    }
+    
+    /// Deployment stage, e.g. `$default` or `prod`
+    pub fn stage(&self) -> Option<&str> {
+        self.request_context_str(&["stage"])
+    }
+    
+    /// Domain the request was sent to (`requestContext.domainName`)
+    pub fn domain_name(&self) -> Option<&str> {
+        self.request_context_str(&["domainName"])
+    }
+    
+    /// Non-empty string at `path` under `requestContext`
+    fn request_context_str(&self, path: &[&str]) -> Option<&str> {
+        path.iter()
+            .try_fold(self.request_context()?, |value, key| value.get(key))?
+            .as_str()
+            .filter(|value| !value.is_empty())
+    }
+    
     /// Check if request is CORS preflight
     pub fn is_preflight(&self) -> bool {
-        self.method == "OPTIONS"
+        self.method.eq_ignore_ascii_case("OPTIONS")
+    }
+    
+    /// Route pattern the router matched, e.g. `/api/workouts/plans/:planId`
+    pub fn matched_route(&self) -> Option<&str> {
+        self.matched_route.as_deref()
+    }
+    
+    /// Permissions the matched route was registered with (see `Router::post_guarded`).
+    /// Auth middleware enforces these against the caller
+    pub fn required_permissions(&self) -> &[String] {
+        &self.required_permissions
+    }
+    
+    /// Handle to the context the handler runs with, filled in once it has run
+    pub fn handler_context(&self) -> HandlerContext {
+        self.handler_context.clone()
     }
     
     /// Set path parameters (used internally by router)
@@ -144,8 +397,261 @@ impl Request {
         self.path_params = params;
     }
     
+    /// Set the matched route pattern (used internally by router)
+    pub(crate) fn set_matched_route(&mut self, pattern: &str) {
+        self.matched_route = Some(pattern.to_string());
+    }
+    
+    /// Set the matched route's required permissions (used internally by router)
+    pub(crate) fn set_required_permissions(&mut self, permissions: Vec<String>) {
+        self.required_permissions = permissions;
+    }
+    
     /// Set context (used internally by middleware)
     pub fn set_context(&mut self, context: Context) {
         self.context = context;
     }
 }
+
+/// Decode a base64 event body into `(text, bytes, error)`: the text when it is UTF-8,
+/// otherwise the raw bytes, or an error for content that isn't base64 at all
+fn decode_base64_body(body: &str) -> (Option<String>, Option<Vec<u8>>, Option<String>) {
+    match BASE64.decode(body.trim()) {
+        Ok(bytes) => match String::from_utf8(bytes) {
+            Ok(text) => (Some(text), None, None),
+            Err(e) => (None, Some(e.into_bytes()), None),
+        },
+        Err(e) => (None, None, Some(format!("Invalid base64 body: {}", e))),
+    }
+}
+
+/// Decode a single form component (`+` as space, `%XX` escapes)
+fn decode_form_component(component: &str) -> Result<String, RouterError> {
+    let bytes = component.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let valid = bytes.len() > i + 2
+                && bytes[i + 1].is_ascii_hexdigit()
+                && bytes[i + 2].is_ascii_hexdigit();
+            if !valid {
+                return Err(RouterError::BadRequest(format!(
+                    "Malformed percent-encoding in form field: {}",
+                    component
+                )));
+            }
+            i += 3;
+        } else {
+            i += 1;
+        }
+    }
+    
+    urlencoding::decode(&component.replace('+', " "))
+        .map(|s| s.into_owned())
+        .map_err(|_| RouterError::BadRequest("Form field is not valid UTF-8".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn form_request(body: &str, is_base64_encoded: bool) -> Request {
+        Request::from_lambda_event(json!({
+            "rawPath": "/webhook",
+            "requestContext": { "http": { "method": "POST" }, "requestId": "req-1" },
+            "headers": { "content-type": "application/x-www-form-urlencoded" },
+            "body": body,
+            "isBase64Encoded": is_base64_encoded
+        }))
+    }
+
+    #[test]
+    fn test_form_parsing() {
+        let req = form_request("token=abc%2F123&platform=ios&name=John+Doe&empty=", false);
+        let form = req.form().unwrap();
+        assert_eq!(form.get("token"), Some(&"abc/123".to_string()));
+        assert_eq!(form.get("platform"), Some(&"ios".to_string()));
+        assert_eq!(form.get("name"), Some(&"John Doe".to_string()));
+        assert_eq!(form.get("empty"), Some(&"".to_string()));
+    }
+
+    #[test]
+    fn test_form_base64_body() {
+        let encoded = BASE64.encode("token=abc&platform=android");
+        let form = form_request(&encoded, true).form().unwrap();
+        assert_eq!(form.get("platform"), Some(&"android".to_string()));
+    }
+
+    #[test]
+    fn test_base64_json_body_is_decoded() {
+        let encoded = BASE64.encode(r#"{"planId":"p-1"}"#);
+        let req = form_request(&encoded, true);
+        assert_eq!(req.body(), Some(r#"{"planId":"p-1"}"#));
+        assert_eq!(req.json::<Value>().unwrap()["planId"], "p-1");
+        assert_eq!(req.body_bytes(), br#"{"planId":"p-1"}"#.to_vec());
+    }
+
+    #[test]
+    fn test_binary_base64_body() {
+        let photo = vec![0xff, 0xd8, 0xff, 0xe0, 0x00, 0x10];
+        let req = form_request(&BASE64.encode(&photo), true);
+        assert_eq!(req.body(), None);
+        assert_eq!(req.body_bytes(), photo);
+        assert!(req.body_error().is_none());
+        assert!(matches!(req.form(), Err(RouterError::BadRequest(_))));
+    }
+
+    #[test]
+    fn test_base64_flag_on_plain_body() {
+        let req = form_request(r#"{"planId": "p-1"}"#, true);
+        assert!(req.body_error().unwrap().starts_with("Invalid base64 body"));
+        assert_eq!(req.body(), None);
+
+        let plain = form_request("token=abc", false);
+        assert_eq!(plain.body_bytes(), b"token=abc".to_vec());
+        assert!(plain.body_error().is_none());
+    }
+
+    #[test]
+    fn test_form_malformed_encoding() {
+        let req = form_request("token=%zz", false);
+        assert!(matches!(req.form(), Err(RouterError::BadRequest(_))));
+    }
+
+    #[test]
+    fn test_if_match_header() {
+        let req = Request::from_lambda_event(json!({
+            "rawPath": "/api/workouts/plans",
+            "requestContext": { "http": { "method": "PUT" }, "requestId": "req-1" },
+            "headers": { "if-match": "\"3\"" }
+        }));
+        assert_eq!(req.if_match(), Some("\"3\""));
+
+        let req = Request::from_lambda_event(json!({
+            "rawPath": "/api/workouts/plans",
+            "requestContext": { "http": { "method": "PUT" }, "requestId": "req-1" },
+            "headers": {}
+        }));
+        assert_eq!(req.if_match(), None);
+    }
+
+    fn auth_request(headers: serde_json::Value, query: serde_json::Value) -> Request {
+        Request::from_lambda_event(json!({
+            "rawPath": "/api/workouts",
+            "requestContext": { "http": { "method": "GET" }, "requestId": "req-1" },
+            "headers": headers,
+            "queryStringParameters": query
+        }))
+    }
+
+    #[test]
+    fn test_correlation_id_from_header() {
+        let req = auth_request(json!({ "X-Correlation-Id": " corr-1 " }), json!({}));
+        assert_eq!(req.context.correlation_id, "corr-1");
+
+        let blank = auth_request(json!({ "x-correlation-id": "" }), json!({}));
+        let other = auth_request(json!({}), json!({}));
+        assert!(!blank.context.correlation_id.is_empty());
+        assert_ne!(blank.context.correlation_id, other.context.correlation_id);
+    }
+
+    #[test]
+    fn test_headers_and_method_are_normalized() {
+        let req = Request::from_lambda_event(json!({
+            "rawPath": "/api/workouts",
+            "requestContext": { "http": { "method": "patch" }, "requestId": "req-1" },
+            "headers": { "Content-Type": "application/json", "X-API-KEY": "k1" }
+        }));
+
+        assert_eq!(req.method, "PATCH");
+        assert_eq!(req.header("content-type"), Some(&"application/json".to_string()));
+        assert_eq!(req.headers.get("x-api-key"), Some(&"k1".to_string()));
+        assert_eq!(req.headers.get("X-Api-Key"), Some(&"k1".to_string()));
+    }
+
+    #[test]
+    fn test_context_elapsed_survives_clones() {
+        let ctx = Context::new("req-1".to_string());
+        std::thread::sleep(Duration::from_millis(5));
+        let cloned = ctx.clone().with_user("user-1".to_string(), None);
+
+        assert!(cloned.elapsed() >= Duration::from_millis(5));
+        assert_eq!(cloned.started_at(), ctx.started_at());
+        assert!(ctx.started_at() <= SystemTime::now());
+    }
+
+    #[test]
+    fn test_request_context_accessors() {
+        let req = Request::from_lambda_event(json!({
+            "rawPath": "/api/workouts",
+            "requestContext": {
+                "http": { "method": "GET", "sourceIp": "203.0.113.7" },
+                "requestId": "req-1",
+                "stage": "$default",
+                "domainName": "api.gymcoach.ai"
+            }
+        }));
+        assert!(req.request_context().is_some());
+        assert_eq!(req.request_id(), Some("req-1"));
+        assert_eq!(req.source_ip(), Some("203.0.113.7"));
+        assert_eq!(req.stage(), Some("$default"));
+        assert_eq!(req.domain_name(), Some("api.gymcoach.ai"));
+
+        let v1 = Request::from_lambda_event(json!({
+            "httpMethod": "GET",
+            "path": "/api/workouts",
+            "requestContext": { "identity": { "sourceIp": "198.51.100.2" }, "stage": "" }
+        }));
+        assert_eq!(v1.source_ip(), Some("198.51.100.2"));
+        assert_eq!(v1.stage(), None);
+        assert_eq!(v1.request_id(), None);
+
+        let bare = Request::from_lambda_event(json!({ "rawPath": "/" }));
+        assert!(bare.request_context().is_none());
+        assert_eq!(bare.source_ip(), None);
+    }
+
+    #[test]
+    fn test_bearer_token_from_header() {
+        let req = auth_request(json!({ "Authorization": "Bearer abc.def.ghi" }), json!({}));
+        assert_eq!(req.bearer_token(), Some("abc.def.ghi"));
+
+        let req = auth_request(json!({ "AUTHORIZATION": "bearer   abc.def.ghi " }), json!({}));
+        assert_eq!(req.bearer_token(), Some("abc.def.ghi"));
+
+        let req = auth_request(json!({ "authorization": "Basic dXNlcjpwYXNz" }), json!({}));
+        assert_eq!(req.bearer_token(), None);
+    }
+
+    #[test]
+    fn test_cookies_from_event_array_and_header() {
+        let req = Request::from_lambda_event(json!({
+            "rawPath": "/api/auth/refresh",
+            "requestContext": { "http": { "method": "POST" }, "requestId": "req-1" },
+            "cookies": ["refresh_token=abc.def", "theme=\"dark\""],
+            "headers": { "Cookie": "device=ios; refresh_token=ignored" }
+        }));
+
+        let cookies = req.cookies();
+        assert_eq!(cookies.get("refresh_token"), Some(&"abc.def".to_string()));
+        assert_eq!(cookies.get("theme"), Some(&"dark".to_string()));
+        assert_eq!(cookies.get("device"), Some(&"ios".to_string()));
+    }
+
+    #[test]
+    fn test_bearer_token_query_fallback() {
+        let req = auth_request(json!({}), json!({ "token": "abc.def.ghi" }));
+        assert_eq!(req.bearer_token(), Some("abc.def.ghi"));
+
+        // A header always wins over the query parameter
+        let req = auth_request(
+            json!({ "authorization": "Bearer from-header" }),
+            json!({ "token": "from-query" }),
+        );
+        assert_eq!(req.bearer_token(), Some("from-header"));
+
+        let req = auth_request(json!({}), json!({}));
+        assert_eq!(req.bearer_token(), None);
+    }
+}
@@ -1,7 +1,46 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use std::collections::HashMap;
 
+use crate::cookie::CookieOptions;
+use crate::csv::{wants_csv, ToCsv};
+use crate::Request;
+
+/// Lambda proxy payload format of the incoming event, which decides the response shape
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PayloadFormat {
+    /// REST API / ALB events: repeated headers and cookies go in `multiValueHeaders`
+    V1,
+    /// HTTP API / Function URL events: cookies go in a top-level `cookies` array
+    V2,
+    /// API Gateway WebSocket events: status, headers and body only, since there is no
+    /// browser on the other end to set cookies on
+    WebSocket,
+}
+
+impl PayloadFormat {
+    /// Detect the format from the event's `version` field. Anything other than `"1.0"`
+    /// is treated as 2.0, which is what Function URLs send. WebSocket events carry no
+    /// version and are recognized by their `connectionId` and `eventType`
+    pub fn from_event(event: &Value) -> Self {
+        if is_websocket_event(event) {
+            return PayloadFormat::WebSocket;
+        }
+        match event["version"].as_str() {
+            Some("1.0") => PayloadFormat::V1,
+            _ => PayloadFormat::V2,
+        }
+    }
+}
+
+/// Whether `event` came from an API Gateway WebSocket route (`$connect`,
+/// `$disconnect`, `$default` or a custom route key)
+pub(crate) fn is_websocket_event(event: &Value) -> bool {
+    let request_context = &event["requestContext"];
+    request_context["connectionId"].is_string() && request_context["eventType"].is_string()
+}
+
 /// HTTP Response builder
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Response {
@@ -11,6 +50,17 @@ pub struct Response {
     pub body: String,
     #[serde(rename = "isBase64Encoded")]
     pub is_base64_encoded: bool,
+    /// `Set-Cookie` values, returned in the Lambda `cookies` array so several cookies
+    /// survive (a header map can only hold one `Set-Cookie`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cookies: Vec<String>,
+    /// Extra values for headers that repeat (e.g. `Vary`), on top of `headers`
+    #[serde(
+        rename = "multiValueHeaders",
+        default,
+        skip_serializing_if = "HashMap::is_empty"
+    )]
+    pub multi_value_headers: HashMap<String, Vec<String>>,
 }
 
 impl Response {
@@ -24,6 +74,8 @@ impl Response {
             headers,
             body: String::new(),
             is_base64_encoded: false,
+            cookies: Vec::new(),
+            multi_value_headers: HashMap::new(),
         }
     }
 
@@ -51,18 +103,94 @@ impl Response {
         self
     }
 
+    /// Response with an already-serialized body (e.g. cached JSON), skipping another
+    /// round-trip through `serde_json`. Text content types that are valid UTF-8 are
+    /// passed through as-is; anything else is base64-encoded with `isBase64Encoded` set
+    pub fn raw(status_code: u16, content_type: &str, body: impl Into<Vec<u8>>) -> Self {
+        let mut response = Self::new(status_code).header("Content-Type", content_type);
+        let mut bytes = body.into();
+
+        if is_text_content_type(content_type) {
+            match String::from_utf8(bytes) {
+                Ok(text) => {
+                    response.body = text;
+                    return response;
+                }
+                Err(e) => bytes = e.into_bytes(),
+            }
+        }
+
+        response.body = BASE64.encode(bytes);
+        response.is_base64_encoded = true;
+        response
+    }
+
+    /// 200 response in the format the client asked for: CSV when `?format=csv` or an
+    /// `Accept` header preferring `text/csv`, JSON otherwise. Sets `Vary: Accept` so
+    /// caches keep the two apart
+    pub fn negotiated<T>(req: &Request, value: &T) -> Self
+    where
+        T: Serialize + ToCsv + ?Sized,
+    {
+        let response = if wants_csv(req) {
+            Self::raw(200, "text/csv; charset=utf-8", value.to_csv()).with_cors()
+        } else {
+            Self::new(200).json_body(&value).with_cors()
+        };
+        response.append_header("Vary", "Accept")
+    }
+
     /// Add header
     pub fn header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
         self.headers.insert(key.into(), value.into());
         self
     }
 
+    /// Set the `ETag` header. `etag` is used as given, so it should already be quoted
+    /// (e.g. from `compute_etag`)
+    pub fn etag(self, etag: impl Into<String>) -> Self {
+        self.header(ETAG_HEADER, etag)
+    }
+
+    /// Set an `ETag` computed from the body, so the router can answer a matching
+    /// `If-None-Match` with `304 Not Modified`. Only 200 responses get one
+    pub fn with_etag(self) -> Self {
+        if self.status_code != 200 {
+            return self;
+        }
+        let etag = compute_etag(self.body.as_bytes());
+        self.etag(etag)
+    }
+
+    /// The `ETag` header, if set
+    pub fn etag_value(&self) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(ETAG_HEADER))
+            .map(|(_, value)| value.as_str())
+    }
+
     /// Add multiple headers
     pub fn headers(mut self, headers: HashMap<String, String>) -> Self {
         self.headers.extend(headers);
         self
     }
 
+    /// Add another value for a header that may repeat, keeping the values already set
+    pub fn append_header(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.multi_value_headers
+            .entry(key.into())
+            .or_default()
+            .push(value.into());
+        self
+    }
+
+    /// Add a `Set-Cookie`; repeated calls add further cookies
+    pub fn set_cookie(mut self, name: &str, value: &str, options: CookieOptions) -> Self {
+        self.cookies.push(options.to_set_cookie(name, value));
+        self
+    }
+
     /// Add CORS headers
     pub fn with_cors(mut self) -> Self {
         self.headers
@@ -80,14 +208,78 @@ impl Response {
         self
     }
 
-    /// Convert to JSON value
+    /// Convert to JSON value in the payload format 2.0 shape
     pub fn to_json(&self) -> Value {
-        json!({
+        self.to_lambda_json(PayloadFormat::V2)
+    }
+
+    /// Convert to the Lambda proxy response shape for `format`. Format 2.0 has no
+    /// multi-value headers, so repeated values are comma-joined and `Set-Cookie`
+    /// values move to `cookies`; format 1.0 lists every value in `multiValueHeaders`
+    pub fn to_lambda_json(&self, format: PayloadFormat) -> Value {
+        let mut merged: HashMap<String, Vec<String>> = self
+            .headers
+            .iter()
+            .map(|(key, value)| (key.clone(), vec![value.clone()]))
+            .collect();
+        for (key, values) in &self.multi_value_headers {
+            merged
+                .entry(key.clone())
+                .or_default()
+                .extend(values.iter().cloned());
+        }
+
+        let mut value = json!({
             "statusCode": self.status_code,
-            "headers": self.headers,
             "body": self.body,
             "isBase64Encoded": self.is_base64_encoded
-        })
+        });
+
+        match format {
+            PayloadFormat::V2 => {
+                let mut cookies = Vec::new();
+                let mut headers = HashMap::new();
+                for (key, values) in merged {
+                    if key.eq_ignore_ascii_case("set-cookie") {
+                        cookies.extend(values);
+                    } else {
+                        headers.insert(key, values.join(", "));
+                    }
+                }
+                cookies.extend(self.cookies.iter().cloned());
+
+                value["headers"] = json!(headers);
+                if !cookies.is_empty() {
+                    value["cookies"] = json!(cookies);
+                }
+            }
+            PayloadFormat::V1 => {
+                if !self.cookies.is_empty() {
+                    merged
+                        .entry("Set-Cookie".to_string())
+                        .or_default()
+                        .extend(self.cookies.iter().cloned());
+                }
+                let headers: HashMap<&String, &String> = merged
+                    .iter()
+                    .filter_map(|(key, values)| values.first().map(|value| (key, value)))
+                    .collect();
+
+                value["headers"] = json!(headers);
+                value["multiValueHeaders"] = json!(merged);
+            }
+            PayloadFormat::WebSocket => {
+                let headers: HashMap<String, String> = merged
+                    .into_iter()
+                    .filter(|(key, _)| !key.eq_ignore_ascii_case("set-cookie"))
+                    .map(|(key, values)| (key, values.join(", ")))
+                    .collect();
+
+                value["headers"] = json!(headers);
+            }
+        }
+
+        value
     }
 
     /// Create Response from JSON value (for controller compatibility)
@@ -111,11 +303,42 @@ impl Response {
             value["body"].to_string()
         };
 
+        // Repeated values keep the first one in `headers` and the rest as extras
+        let mut multi_value_headers = HashMap::new();
+        if let Some(multi_obj) = value["multiValueHeaders"].as_object() {
+            for (key, vals) in multi_obj {
+                let mut vals = vals
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|v| v.as_str().map(|s| s.to_string()));
+                if let Some(first) = vals.next() {
+                    headers.insert(key.clone(), first);
+                }
+                let rest: Vec<String> = vals.collect();
+                if !rest.is_empty() {
+                    multi_value_headers.insert(key.clone(), rest);
+                }
+            }
+        }
+
+        let cookies = value["cookies"]
+            .as_array()
+            .map(|cookies| {
+                cookies
+                    .iter()
+                    .filter_map(|c| c.as_str().map(|s| s.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
+
         Self {
             status_code,
             headers,
             body,
             is_base64_encoded: value["isBase64Encoded"].as_bool().unwrap_or(false),
+            cookies,
+            multi_value_headers,
         }
     }
 
@@ -136,6 +359,30 @@ impl Response {
         Self::new(204).with_cors()
     }
 
+    /// 302 Found redirect to `location`
+    pub fn redirect(location: &str) -> Self {
+        Self::redirect_with_status(302, location)
+    }
+
+    /// 301 Moved Permanently redirect; clients and caches may remember it
+    pub fn redirect_permanent(location: &str) -> Self {
+        Self::redirect_with_status(301, location)
+    }
+
+    /// 303 See Other redirect: the client follows up with a GET, e.g. after a POST
+    pub fn see_other(location: &str) -> Self {
+        Self::redirect_with_status(303, location)
+    }
+
+    /// A bodiless redirect, so it carries no `Content-Type`
+    fn redirect_with_status(status_code: u16, location: &str) -> Self {
+        let mut response = Self::new(status_code)
+            .header("Location", location)
+            .with_cors();
+        response.headers.remove("Content-Type");
+        response
+    }
+
     /// 400 Bad Request response
     pub fn bad_request(message: &str) -> Self {
         Self::new(400)
@@ -186,6 +433,49 @@ impl Response {
             .with_cors()
     }
 
+    /// 415 Unsupported Media Type response
+    pub fn unsupported_media_type(message: &str) -> Self {
+        Self::new(415)
+            .json(json!({
+                "error": "Unsupported Media Type",
+                "message": message
+            }))
+            .with_cors()
+    }
+
+    /// 409 Conflict response
+    pub fn conflict(message: &str) -> Self {
+        Self::new(409)
+            .json(json!({
+                "error": "Conflict",
+                "message": message
+            }))
+            .with_cors()
+    }
+
+    /// 412 Precondition Failed response, e.g. for a stale `If-Match`
+    pub fn precondition_failed(message: &str) -> Self {
+        Self::new(412)
+            .json(json!({
+                "error": "Precondition Failed",
+                "message": message
+            }))
+            .with_cors()
+    }
+
+    /// 429 Too Many Requests response with a `Retry-After` header (in seconds) so
+    /// clients know when to back off until
+    pub fn too_many_requests(retry_after_secs: u32, message: &str) -> Self {
+        Self::new(429)
+            .json(json!({
+                "error": "Too Many Requests",
+                "message": message,
+                "retry_after": retry_after_secs
+            }))
+            .header("Retry-After", retry_after_secs.to_string())
+            .with_cors()
+    }
+
     /// 500 Internal Server Error response
     pub fn internal_error(message: &str) -> Self {
         Self::new(500)
@@ -196,8 +486,328 @@ impl Response {
             .with_cors()
     }
 
+    /// 501 Not Implemented response
+    pub fn not_implemented(message: &str) -> Self {
+        Self::new(501)
+            .json(json!({
+                "error": "Not Implemented",
+                "message": message
+            }))
+            .with_cors()
+    }
+
+    /// Add `request_id` to an error body (status >= 400 with a JSON object body that has
+    /// an `error` field). Other responses, and bodies that already carry one, are untouched
+    pub fn with_request_id(mut self, request_id: &str) -> Self {
+        if self.status_code < 400 {
+            return self;
+        }
+
+        if let Ok(Value::Object(mut body)) = serde_json::from_str::<Value>(&self.body) {
+            if body.contains_key("error") && !body.contains_key("request_id") {
+                body.insert("request_id".to_string(), json!(request_id));
+                self.body = Value::Object(body).to_string();
+            }
+        }
+        self
+    }
+
     /// CORS preflight response
     pub fn cors_preflight() -> Self {
         Self::new(200).text("").with_cors()
     }
+
+    /// Turn this response into a `304 Not Modified` for a client that already has it.
+    /// Headers (`ETag`, CORS, caching) are kept; the body and its content headers are
+    /// dropped
+    pub fn into_not_modified(mut self) -> Self {
+        self.status_code = 304;
+        self.body = String::new();
+        self.is_base64_encoded = false;
+        self.headers.retain(|key, _| {
+            !key.eq_ignore_ascii_case("content-type") && !key.eq_ignore_ascii_case("content-length")
+        });
+        self
+    }
+}
+
+/// Response header carrying the entity tag
+pub const ETAG_HEADER: &str = "ETag";
+
+/// A strong `ETag` for a response body: the quoted 64-bit FNV-1a hash of its bytes. The
+/// hash is fixed, so every Lambda instance and deployment computes the same tag for
+/// the same body
+pub fn compute_etag(body: &[u8]) -> String {
+    let hash = body.iter().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    format!("\"{:016x}\"", hash)
+}
+
+/// Whether an `If-None-Match` header value matches `etag`. Uses the weak comparison
+/// from RFC 9110, so `W/` prefixes are ignored; `*` matches any tag
+pub fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    let opaque = |tag: &str| tag.trim().trim_start_matches("W/").to_string();
+    let etag = opaque(etag);
+
+    if_none_match
+        .split(',')
+        .any(|candidate| candidate.trim() == "*" || opaque(candidate) == etag)
+}
+
+/// Content types whose bodies can be returned to Lambda as plain strings
+fn is_text_content_type(content_type: &str) -> bool {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+
+    mime.starts_with("text/")
+        || mime.ends_with("/json")
+        || mime.ends_with("+json")
+        || mime.ends_with("/xml")
+        || mime.ends_with("+xml")
+        || mime == "application/javascript"
+        || mime == "application/x-www-form-urlencoded"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_not_implemented() {
+        let response = Response::not_implemented("Device listing is not available yet");
+        assert_eq!(response.status_code, 501);
+
+        let body: Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(body["error"], "Not Implemented");
+        assert_eq!(body["message"], "Device listing is not available yet");
+        assert_eq!(
+            response.headers.get("Access-Control-Allow-Origin"),
+            Some(&"*".to_string())
+        );
+    }
+
+    #[test]
+    fn test_redirects_set_location_without_a_body() {
+        for (response, status) in [
+            (Response::redirect("/login"), 302),
+            (Response::redirect_permanent("/login"), 301),
+            (Response::see_other("/login"), 303),
+        ] {
+            assert_eq!(response.status_code, status);
+            assert_eq!(
+                response.headers.get("Location"),
+                Some(&"/login".to_string())
+            );
+            assert!(response.body.is_empty());
+            assert!(!response.headers.contains_key("Content-Type"));
+        }
+    }
+
+    #[test]
+    fn test_with_request_id_only_touches_error_bodies() {
+        let response = Response::not_found("Plan not found").with_request_id("req-1");
+        let body: Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(body["error"], "Not Found");
+        assert_eq!(body["request_id"], "req-1");
+
+        let response = Response::ok(json!({ "error": "not really" })).with_request_id("req-1");
+        let body: Value = serde_json::from_str(&response.body).unwrap();
+        assert!(body.get("request_id").is_none());
+    }
+
+    #[test]
+    fn test_set_cookie_keeps_every_cookie() {
+        let response = Response::ok(json!({}))
+            .set_cookie("access_token", "a", CookieOptions::new().max_age(900))
+            .set_cookie("refresh_token", "r", CookieOptions::new().path("/api/auth"));
+
+        let value = response.to_json();
+        assert_eq!(value["cookies"].as_array().map(|c| c.len()), Some(2));
+        assert!(value["cookies"][0]
+            .as_str()
+            .unwrap()
+            .starts_with("access_token=a; Max-Age=900"));
+        assert!(!response.headers.contains_key("Set-Cookie"));
+
+        assert_eq!(Response::from_json_value(value).cookies, response.cookies);
+        assert!(Response::ok(json!({})).to_json().get("cookies").is_none());
+    }
+
+    #[test]
+    fn test_v1_shape_uses_multi_value_headers() {
+        let response = Response::ok(json!({}))
+            .header("Vary", "Origin")
+            .append_header("Vary", "Accept-Encoding")
+            .set_cookie("a", "1", CookieOptions::new())
+            .set_cookie("b", "2", CookieOptions::new());
+
+        let value = response.to_lambda_json(PayloadFormat::V1);
+        assert_eq!(
+            value["multiValueHeaders"]["Vary"],
+            json!(["Origin", "Accept-Encoding"])
+        );
+        assert_eq!(
+            value["multiValueHeaders"]["Set-Cookie"]
+                .as_array()
+                .map(|c| c.len()),
+            Some(2)
+        );
+        assert!(value.get("cookies").is_none());
+
+        let value = response.to_lambda_json(PayloadFormat::V2);
+        assert_eq!(value["headers"]["Vary"], "Origin, Accept-Encoding");
+        assert_eq!(value["cookies"].as_array().map(|c| c.len()), Some(2));
+        assert!(value.get("multiValueHeaders").is_none());
+    }
+
+    #[test]
+    fn test_payload_format_from_event() {
+        assert_eq!(
+            PayloadFormat::from_event(&json!({ "version": "1.0" })),
+            PayloadFormat::V1
+        );
+        assert_eq!(
+            PayloadFormat::from_event(&json!({ "version": "2.0" })),
+            PayloadFormat::V2
+        );
+        assert_eq!(PayloadFormat::from_event(&json!({})), PayloadFormat::V2);
+        assert_eq!(
+            PayloadFormat::from_event(&json!({
+                "requestContext": {
+                    "routeKey": "$connect",
+                    "eventType": "CONNECT",
+                    "connectionId": "abc123="
+                }
+            })),
+            PayloadFormat::WebSocket
+        );
+        // HTTP API events have a route key too, but no connection
+        assert_eq!(
+            PayloadFormat::from_event(&json!({
+                "version": "2.0",
+                "requestContext": { "routeKey": "GET /api/plans" }
+            })),
+            PayloadFormat::V2
+        );
+    }
+
+    #[test]
+    fn test_raw_passes_text_through() {
+        let cached = r#"{"labels":["W1","W2"],"values":[72.5,72.1]}"#;
+        let response = Response::raw(200, "application/json; charset=utf-8", cached);
+
+        assert_eq!(response.body, cached);
+        assert!(!response.is_base64_encoded);
+        assert_eq!(
+            response.headers.get("Content-Type"),
+            Some(&"application/json; charset=utf-8".to_string())
+        );
+    }
+
+    #[test]
+    fn test_raw_base64_encodes_binary() {
+        let png_header = vec![0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a];
+        let value = Response::raw(200, "image/png", png_header.clone()).to_json();
+
+        assert_eq!(value["isBase64Encoded"], true);
+        assert_eq!(value["headers"]["Content-Type"], "image/png");
+        assert_eq!(
+            BASE64.decode(value["body"].as_str().unwrap()).unwrap(),
+            png_header
+        );
+
+        // Text content types that aren't valid UTF-8 are encoded rather than mangled
+        let response = Response::raw(200, "text/csv", vec![0xff, 0xfe]);
+        assert!(response.is_base64_encoded);
+    }
+
+    #[test]
+    fn test_etag_is_stable_and_tracks_the_body() {
+        let timeline = Response::ok(json!({ "photos": ["p1", "p2"] })).with_etag();
+        let same = Response::ok(json!({ "photos": ["p1", "p2"] })).with_etag();
+        let changed = Response::ok(json!({ "photos": ["p1", "p2", "p3"] })).with_etag();
+
+        let etag = timeline.etag_value().unwrap();
+        assert!(etag.starts_with('"') && etag.ends_with('"'));
+        assert_eq!(same.etag_value(), Some(etag));
+        assert_ne!(changed.etag_value(), Some(etag));
+        assert_eq!(compute_etag(b""), "\"cbf29ce484222325\"");
+
+        assert!(Response::not_found("Missing")
+            .with_etag()
+            .etag_value()
+            .is_none());
+    }
+
+    #[test]
+    fn test_etag_matches() {
+        assert!(etag_matches("\"abc\"", "\"abc\""));
+        assert!(etag_matches("W/\"abc\"", "\"abc\""));
+        assert!(etag_matches("\"xyz\", \"abc\"", "\"abc\""));
+        assert!(etag_matches("*", "\"abc\""));
+        assert!(!etag_matches("\"xyz\"", "\"abc\""));
+        assert!(!etag_matches("abc", "\"abc\""));
+    }
+
+    #[test]
+    fn test_negotiated_picks_csv_or_json() {
+        struct Weight(f32);
+
+        impl crate::CsvRow for Weight {
+            fn csv_header() -> Vec<&'static str> {
+                vec!["value"]
+            }
+
+            fn csv_row(&self) -> Vec<String> {
+                vec![self.0.to_string()]
+            }
+        }
+
+        impl Serialize for Weight {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                json!({ "value": self.0 }).serialize(serializer)
+            }
+        }
+
+        let weights = vec![Weight(72.5), Weight(72.25)];
+        let request = |accept: &str| {
+            Request::from_lambda_event(json!({
+                "rawPath": "/api/analytics/me/body-measurements",
+                "requestContext": { "http": { "method": "GET" } },
+                "headers": { "accept": accept }
+            }))
+        };
+
+        let csv = Response::negotiated(&request("text/csv"), &weights);
+        assert_eq!(csv.body, "value\r\n72.5\r\n72.25\r\n");
+        assert_eq!(
+            csv.headers.get("Content-Type"),
+            Some(&"text/csv; charset=utf-8".to_string())
+        );
+        assert_eq!(csv.multi_value_headers["Vary"], vec!["Accept".to_string()]);
+
+        let json = Response::negotiated(&request("application/json"), &weights);
+        assert_eq!(json.body, r#"[{"value":72.5},{"value":72.25}]"#);
+        assert_eq!(
+            json.headers.get("Content-Type"),
+            Some(&"application/json".to_string())
+        );
+    }
+
+    #[test]
+    fn test_too_many_requests_sets_retry_after() {
+        let response = Response::too_many_requests(30, "Rate limit exceeded");
+        assert_eq!(response.status_code, 429);
+        assert_eq!(response.headers.get("Retry-After"), Some(&"30".to_string()));
+
+        let body: Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(body["error"], "Too Many Requests");
+        assert_eq!(body["message"], "Rate limit exceeded");
+        assert_eq!(body["retry_after"], 30);
+    }
 }
@@ -2,15 +2,35 @@ use async_trait::async_trait;
 use futures::future::BoxFuture;
 use lambda_runtime::{Error, LambdaEvent};
 use serde_json::Value;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
 use crate::middleware::CorsMiddleware;
-use crate::{Context, Middleware, PathMatcher, Request, Response, Result, RouterError};
+use crate::openapi::{self, RouteInfo};
+use crate::response::etag_matches;
+use crate::server_timing::{time_phase, ServerTiming};
+use crate::xray::{Subsegment, XRayTrace};
+use crate::{
+    Context, Middleware, PathMatcher, PayloadFormat, Request, Response, Result, RouterError,
+    CORRELATION_ID_HEADER,
+};
+
+const JSON_CONTENT_TYPE: &str = "application/json";
 
 /// Handler function type
 pub type HandlerFn =
     Arc<dyn Fn(Request, Context) -> BoxFuture<'static, Result<Response>> + Send + Sync>;
 
+/// Response post-processing hook, run on every outgoing response
+pub type AfterHookFn = Arc<dyn Fn(Response, &Context) -> Response + Send + Sync>;
+
+/// Cleanup run once before the container is reclaimed, e.g. to flush buffered metrics
+pub type ShutdownHookFn = Arc<dyn Fn() -> BoxFuture<'static, ()> + Send + Sync>;
+
+/// Where each request's X-Ray trace comes from; `XRayTrace::from_env` outside tests
+type TraceSourceFn = Arc<dyn Fn() -> Option<XRayTrace> + Send + Sync>;
+
 /// Handler trait for route handlers
 #[async_trait]
 pub trait Handler: Send + Sync {
@@ -21,14 +41,23 @@ pub trait Handler: Send + Sync {
 struct Route {
     method: String,
     matcher: PathMatcher,
+    permissions: Vec<String>,
+    /// Media type request bodies must have, checked before the handler runs
+    content_type: Option<String>,
     handler: HandlerFn,
 }
 
-/// Router for handling Lambda HTTP requests
+/// Router for handling Lambda HTTP requests and API Gateway WebSocket events
 pub struct Router {
     routes: Vec<Route>,
+    websocket_routes: Vec<(String, HandlerFn)>,
     middlewares: Vec<Arc<dyn Middleware>>,
+    after_hooks: Vec<AfterHookFn>,
+    shutdown_hooks: Vec<ShutdownHookFn>,
     not_found_handler: Option<HandlerFn>,
+    server_timing: bool,
+    xray: Option<TraceSourceFn>,
+    strict_trailing_slash: bool,
 }
 
 impl Router {
@@ -36,24 +65,106 @@ impl Router {
     pub fn new() -> Self {
         Self {
             routes: Vec::new(),
+            websocket_routes: Vec::new(),
             middlewares: vec![Arc::new(CorsMiddleware::new())],
+            after_hooks: Vec::new(),
+            shutdown_hooks: Vec::new(),
             not_found_handler: None,
+            server_timing: ServerTiming::enabled_from_env(),
+            xray: None,
+            strict_trailing_slash: true,
         }
     }
 
-    /// Add a middleware
+    /// Add a middleware. Middlewares wrap the handler outer-to-inner in registration
+    /// order: the first registered sees the request first and the response last. The
+    /// built-in CORS middleware is always registered first, so it wraps everything
+    /// added here (auth included) and answers preflights before auth runs
     pub fn use_middleware(&mut self, middleware: impl Middleware + 'static) {
         self.middlewares.push(Arc::new(middleware));
     }
 
-    /// Set custom not found handler
-    pub fn not_found<F>(&mut self, handler: F)
+    /// Add a hook that transforms every outgoing response, including error and
+    /// preflight responses. Hooks run in registration order after the handler and
+    /// middleware, and see the context the handler was called with
+    pub fn after<F>(&mut self, hook: F)
+    where
+        F: Fn(Response, &Context) -> Response + Send + Sync + 'static,
+    {
+        self.after_hooks.push(Arc::new(hook));
+    }
+
+    /// Add a hook run when the container shuts down, to flush metrics and logs
+    /// buffered in memory that would otherwise be lost. Hooks run in registration
+    /// order when the process receives `SIGTERM`, which Lambda sends (with a short
+    /// grace period) only to functions that have at least one extension registered,
+    /// such as the Lambda Insights or ADOT layer
+    pub fn on_shutdown<F, Fut>(&mut self, hook: F)
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.shutdown_hooks.push(Arc::new(move || Box::pin(hook())));
+    }
+
+    /// Run the shutdown hooks. `into_service` calls this on `SIGTERM`
+    pub async fn shutdown(&self) {
+        for hook in &self.shutdown_hooks {
+            hook().await;
+        }
+    }
+
+    /// Emit a `Server-Timing` header with per-phase durations. Defaults to the
+    /// `SERVER_TIMING_DEBUG` environment flag
+    pub fn enable_server_timing(&mut self, enabled: bool) {
+        self.server_timing = enabled;
+    }
+
+    /// Send X-Ray subsegments for sampled invocations (those with `_X_AMZN_TRACE_ID`
+    /// set): one per matched route, annotated with the route, method and status, and
+    /// nested ones for each `time_phase` (handler, auth, db calls)
+    pub fn enable_xray(&mut self) {
+        self.xray = Some(Arc::new(XRayTrace::from_env));
+    }
+
+    /// Whether `/api/plans/` and `/api/plans` are different paths (the default). When
+    /// turned off, trailing slashes are dropped before matching so both reach the same
+    /// route, and handlers see the normalized `req.path`
+    pub fn strict_trailing_slash(&mut self, strict: bool) {
+        self.strict_trailing_slash = strict;
+    }
+
+    /// Set the handler for requests whose path matches no route, in place of the
+    /// default 404. A path that matches but not for the request's method still gets
+    /// a 405 with an `Allow` header
+    pub fn fallback<F>(&mut self, handler: F)
     where
         F: Fn(Request, Context) -> BoxFuture<'static, Result<Response>> + Send + Sync + 'static,
     {
         self.not_found_handler = Some(Arc::new(handler));
     }
 
+    /// Set custom not found handler; same as `fallback`
+    pub fn not_found<F>(&mut self, handler: F)
+    where
+        F: Fn(Request, Context) -> BoxFuture<'static, Result<Response>> + Send + Sync + 'static,
+    {
+        self.fallback(handler);
+    }
+
+    /// Add a handler for an API Gateway WebSocket route key: `$connect`,
+    /// `$disconnect`, `$default` or a custom key from the API's route selection
+    /// expression. Messages with no handler for their key fall back to `$default`.
+    /// Handlers run through the same middleware as HTTP routes and find the connection
+    /// id and route key on the `Context`
+    pub fn websocket<F>(&mut self, route_key: &str, handler: F)
+    where
+        F: Fn(Request, Context) -> BoxFuture<'static, Result<Response>> + Send + Sync + 'static,
+    {
+        self.websocket_routes
+            .push((route_key.to_string(), Arc::new(handler)));
+    }
+
     /// Add a GET route
     pub fn get<F>(&mut self, path: &str, handler: F)
     where
@@ -94,25 +205,237 @@ impl Router {
         self.add_route("PATCH", path, handler);
     }
 
+    /// Add a GET route that requires `permissions` (see `perms!`)
+    pub fn get_guarded<F>(&mut self, path: &str, permissions: Vec<String>, handler: F)
+    where
+        F: Fn(Request, Context) -> BoxFuture<'static, Result<Response>> + Send + Sync + 'static,
+    {
+        self.add_guarded_route("GET", path, permissions, handler);
+    }
+
+    /// Add a POST route that requires `permissions` (see `perms!`)
+    pub fn post_guarded<F>(&mut self, path: &str, permissions: Vec<String>, handler: F)
+    where
+        F: Fn(Request, Context) -> BoxFuture<'static, Result<Response>> + Send + Sync + 'static,
+    {
+        self.add_guarded_route("POST", path, permissions, handler);
+    }
+
+    /// Add a PUT route that requires `permissions` (see `perms!`)
+    pub fn put_guarded<F>(&mut self, path: &str, permissions: Vec<String>, handler: F)
+    where
+        F: Fn(Request, Context) -> BoxFuture<'static, Result<Response>> + Send + Sync + 'static,
+    {
+        self.add_guarded_route("PUT", path, permissions, handler);
+    }
+
+    /// Add a DELETE route that requires `permissions` (see `perms!`)
+    pub fn delete_guarded<F>(&mut self, path: &str, permissions: Vec<String>, handler: F)
+    where
+        F: Fn(Request, Context) -> BoxFuture<'static, Result<Response>> + Send + Sync + 'static,
+    {
+        self.add_guarded_route("DELETE", path, permissions, handler);
+    }
+
+    /// Add a PATCH route that requires `permissions` (see `perms!`)
+    pub fn patch_guarded<F>(&mut self, path: &str, permissions: Vec<String>, handler: F)
+    where
+        F: Fn(Request, Context) -> BoxFuture<'static, Result<Response>> + Send + Sync + 'static,
+    {
+        self.add_guarded_route("PATCH", path, permissions, handler);
+    }
+
     /// Add a route for any method
     pub fn add_route<F>(&mut self, method: &str, path: &str, handler: F)
     where
         F: Fn(Request, Context) -> BoxFuture<'static, Result<Response>> + Send + Sync + 'static,
+    {
+        self.add_guarded_route(method, path, Vec::new(), handler);
+    }
+
+    /// Add a route for any method that requires `permissions`. The router only records
+    /// them on the request (`Request::required_permissions`); the auth middleware
+    /// checks them against the caller
+    pub fn add_guarded_route<F>(
+        &mut self,
+        method: &str,
+        path: &str,
+        permissions: Vec<String>,
+        handler: F,
+    ) where
+        F: Fn(Request, Context) -> BoxFuture<'static, Result<Response>> + Send + Sync + 'static,
     {
         self.routes.push(Route {
             method: method.to_uppercase(),
             matcher: PathMatcher::new(path),
+            permissions,
+            content_type: None,
             handler: Arc::new(handler),
         });
     }
 
-    /// Handle incoming Lambda event
-    async fn handle_request(&self, mut req: Request) -> Result<Response> {
-        // Find matching route
+    /// Add a POST route that only accepts `application/json` bodies
+    pub fn post_json<F>(&mut self, path: &str, handler: F)
+    where
+        F: Fn(Request, Context) -> BoxFuture<'static, Result<Response>> + Send + Sync + 'static,
+    {
+        self.add_route("POST", path, handler);
+        self.require_content_type("POST", path, JSON_CONTENT_TYPE);
+    }
+
+    /// Add a PUT route that only accepts `application/json` bodies
+    pub fn put_json<F>(&mut self, path: &str, handler: F)
+    where
+        F: Fn(Request, Context) -> BoxFuture<'static, Result<Response>> + Send + Sync + 'static,
+    {
+        self.add_route("PUT", path, handler);
+        self.require_content_type("PUT", path, JSON_CONTENT_TYPE);
+    }
+
+    /// Add a PATCH route that only accepts `application/json` bodies
+    pub fn patch_json<F>(&mut self, path: &str, handler: F)
+    where
+        F: Fn(Request, Context) -> BoxFuture<'static, Result<Response>> + Send + Sync + 'static,
+    {
+        self.add_route("PATCH", path, handler);
+        self.require_content_type("PATCH", path, JSON_CONTENT_TYPE);
+    }
+
+    /// Reject requests to an already registered route whose body isn't of
+    /// `content_type` with a `415 Unsupported Media Type`, before middleware or the
+    /// handler run. Parameters such as `; charset=utf-8` are ignored, and requests
+    /// without a body are let through
+    pub fn require_content_type(&mut self, method: &str, path: &str, content_type: &str) {
+        for route in self.routes.iter_mut().filter(|route| {
+            route.method.eq_ignore_ascii_case(method) && route.matcher.pattern() == path
+        }) {
+            route.content_type = Some(content_type.to_ascii_lowercase());
+        }
+    }
+
+    /// Route a request and run the after hooks on whatever response comes out
+    async fn dispatch(&self, req: Request) -> Response {
+        // Handle CORS preflight early
+        if req.is_preflight() {
+            let ctx = req.context.clone();
+            let response = Response::cors_preflight()
+                .header(CORRELATION_ID_HEADER, ctx.correlation_id.clone());
+            return self.run_after_hooks(response, &ctx);
+        }
+
+        // Only safe methods can be answered from the client's cached copy
+        let if_none_match = matches!(req.method.as_str(), "GET" | "HEAD")
+            .then(|| req.header("If-None-Match").cloned())
+            .flatten();
+
+        let timing = self.server_timing.then(ServerTiming::new);
+        let handled = async {
+            match &timing {
+                Some(timing) => timing.scope(self.handle_request(req)).await,
+                None => self.handle_request(req).await,
+            }
+        };
+        let (result, ctx) = match self.xray.as_ref().and_then(|trace_source| trace_source()) {
+            Some(trace) => trace.scope(handled).await,
+            None => handled.await,
+        };
+        let mut response = match result {
+            Ok(resp) => resp,
+            Err(e) => e.to_response(),
+        }
+        .with_request_id(&ctx.request_id)
+        .header(CORRELATION_ID_HEADER, ctx.correlation_id.clone());
+
+        if let (Some(if_none_match), Some(etag)) = (&if_none_match, response.etag_value()) {
+            if response.status_code == 200 && etag_matches(if_none_match, etag) {
+                response = response.into_not_modified();
+            }
+        }
+
+        if let Some(header) = timing.and_then(|timing| timing.header_value()) {
+            response = response.header("Server-Timing", header);
+        }
+
+        self.run_after_hooks(response, &ctx)
+    }
+
+    /// Apply after hooks in registration order
+    fn run_after_hooks(&self, response: Response, ctx: &Context) -> Response {
+        self.after_hooks
+            .iter()
+            .fold(response, |response, hook| hook(response, ctx))
+    }
+
+    /// List registered routes in registration order
+    pub fn routes(&self) -> Vec<RouteInfo> {
+        self.routes
+            .iter()
+            .map(|route| RouteInfo {
+                method: route.method.clone(),
+                pattern: route.matcher.pattern().to_string(),
+            })
+            .collect()
+    }
+
+    /// Pairs of routes with the same method that can match the same path without
+    /// either one being more specific (e.g. `/plans/:planId` and `/plans/:id`). Only
+    /// registration order decides between them, which is almost always a mistake.
+    /// Overlaps that precedence settles, like `/plans/schedule` vs `/plans/:planId`,
+    /// are not reported
+    pub fn check_conflicts(&self) -> Vec<(RouteInfo, RouteInfo)> {
+        let info = |route: &Route| RouteInfo {
+            method: route.method.clone(),
+            pattern: route.matcher.pattern().to_string(),
+        };
+
+        let mut conflicts = Vec::new();
+        for (i, first) in self.routes.iter().enumerate() {
+            for second in &self.routes[i + 1..] {
+                if first.method == second.method
+                    && first.matcher.precedence() == second.matcher.precedence()
+                    && first.matcher.could_overlap(&second.matcher)
+                {
+                    conflicts.push((info(first), info(second)));
+                }
+            }
+        }
+        conflicts
+    }
+
+    /// Build a minimal OpenAPI document (paths, methods and path parameters) for
+    /// the registered routes
+    pub fn openapi(&self, title: &str, version: &str) -> Value {
+        openapi::document(title, version, &self.routes())
+    }
+
+    /// Handle incoming Lambda event, returning the context the handler ran with
+    async fn handle_request(&self, mut req: Request) -> (Result<Response>, Context) {
+        if !self.strict_trailing_slash && req.path.len() > 1 && req.path.ends_with('/') {
+            let trimmed = req.path.trim_end_matches('/');
+            req.path = if trimmed.is_empty() {
+                "/".to_string()
+            } else {
+                trimmed.to_string()
+            };
+        }
+
+        if let Some(error) = req.body_error() {
+            let error = RouterError::BadRequest(error.to_string());
+            return (Err(error), req.context);
+        }
+
+        if let Some(route_key) = req.context.route_key.clone() {
+            return self.handle_websocket(req, &route_key).await;
+        }
+
+        // Find the most specific matching route; registration order only breaks ties
         let route = self
             .routes
             .iter()
-            .find(|r| r.method == req.method && r.matcher.matches(&req.path).is_some());
+            .filter(|r| {
+                r.method.eq_ignore_ascii_case(&req.method) && r.matcher.matches(&req.path).is_some()
+            })
+            .min_by_key(|r| r.matcher.precedence());
 
         match route {
             Some(route) => {
@@ -120,36 +443,133 @@ impl Router {
                 if let Some(params) = route.matcher.matches(&req.path) {
                     req.set_path_params(params);
                 }
+                req.set_matched_route(route.matcher.pattern());
+                req.set_required_permissions(route.permissions.clone());
 
-                // Execute handler with middleware chain
-                let handler = route.handler.clone();
-                let middlewares = self.middlewares.clone();
-
-                // Build middleware chain by wrapping handler in middleware
-                if middlewares.is_empty() {
-                    // No middleware, just execute handler
-                    let ctx = req.context.clone();
-                    (handler)(req, ctx).await
-                } else {
-                    // Execute through middleware chain
-                    self.execute_middleware_chain(req, middlewares, handler)
-                        .await
+                if let Some(expected) = &route.content_type {
+                    if let Err(error) = check_content_type(&req, expected) {
+                        return (Err(error), req.context);
+                    }
                 }
+
+                self.run_route(
+                    req,
+                    route.handler.clone(),
+                    &route.method,
+                    route.matcher.pattern(),
+                )
+                .await
             }
             None => {
-                if let Some(handler) = &self.not_found_handler {
-                    let ctx = req.context.clone();
-                    (handler)(req, ctx).await
+                let allowed = self.allowed_methods(&req.path);
+                if allowed.is_empty() {
+                    self.handle_not_found(req).await
                 } else {
-                    Err(RouterError::RouteNotFound {
+                    let error = RouterError::MethodNotAllowed {
                         method: req.method.clone(),
-                        path: req.path.clone(),
-                    })
+                        allowed,
+                    };
+                    (Err(error), req.context)
                 }
             }
         }
     }
 
+    /// Methods with a route matching `path`, in registration order
+    fn allowed_methods(&self, path: &str) -> Vec<String> {
+        let mut allowed: Vec<String> = Vec::new();
+        for route in &self.routes {
+            if route.matcher.matches(path).is_some() && !allowed.contains(&route.method) {
+                allowed.push(route.method.clone());
+            }
+        }
+        allowed
+    }
+
+    /// Route a WebSocket event by its route key, falling back to `$default`
+    async fn handle_websocket(
+        &self,
+        mut req: Request,
+        route_key: &str,
+    ) -> (Result<Response>, Context) {
+        let route = self
+            .websocket_routes
+            .iter()
+            .find(|(key, _)| key == route_key)
+            .or_else(|| {
+                self.websocket_routes
+                    .iter()
+                    .find(|(key, _)| key == "$default")
+            });
+
+        match route {
+            Some((key, handler)) => {
+                req.set_matched_route(key);
+                let method = req.method.clone();
+                self.run_route(req, handler.clone(), &method, key).await
+            }
+            None => self.handle_not_found(req).await,
+        }
+    }
+
+    /// Run a matched route's handler through the middleware chain, inside an X-Ray
+    /// subsegment when tracing
+    async fn run_route(
+        &self,
+        req: Request,
+        handler: HandlerFn,
+        method: &str,
+        pattern: &str,
+    ) -> (Result<Response>, Context) {
+        let middlewares = self.middlewares.clone();
+
+        // Build middleware chain by wrapping handler in middleware
+        let run = async {
+            if middlewares.is_empty() {
+                // No middleware, just execute handler
+                let ctx = req.context.clone();
+                (
+                    time_phase("handler", (handler)(req, ctx.clone())).await,
+                    ctx,
+                )
+            } else {
+                // Execute through middleware chain
+                self.execute_middleware_chain(req, middlewares, handler)
+                    .await
+            }
+        };
+
+        match Subsegment::begin(&format!("{} {}", method, pattern)) {
+            Some(mut subsegment) => {
+                subsegment.annotate("route", pattern);
+                subsegment.annotate("method", method);
+                let (result, ctx) = subsegment.scope(run).await;
+                let status = match &result {
+                    Ok(response) => response.status_code,
+                    Err(e) => e.to_response().status_code,
+                };
+                subsegment.set_status(status);
+                subsegment.end();
+                (result, ctx)
+            }
+            None => run.await,
+        }
+    }
+
+    /// Answer a request no route matched, with the custom not found handler if set
+    async fn handle_not_found(&self, req: Request) -> (Result<Response>, Context) {
+        let ctx = req.context.clone();
+        if let Some(handler) = &self.not_found_handler {
+            ((handler)(req, ctx.clone()).await, ctx)
+        } else {
+            let error = RouterError::RouteNotFound {
+                method: req.method.clone(),
+                path: req.path.clone(),
+            };
+            (Err(error), ctx)
+        }
+    }
+
     /// Execute request through middleware chain
     async fn execute_middleware_chain(
         &self,
@@ -158,9 +578,13 @@ impl Router {
         handler: Arc<
             dyn Fn(Request, Context) -> BoxFuture<'static, Result<Response>> + Send + Sync,
         >,
-    ) -> Result<Response> {
+    ) -> (Result<Response>, Context) {
         use std::sync::Arc as StdArc;
 
+        // Middleware may replace the context (e.g. auth), so keep the one the handler saw
+        let initial_context = req.context.clone();
+        let handler_context = req.handler_context();
+
         // Build the middleware chain from the end backwards
         let final_handler: StdArc<
             dyn Fn(Request) -> BoxFuture<'static, std::result::Result<Response, Error>>
@@ -169,14 +593,16 @@ impl Router {
         > = StdArc::new(move |req: Request| {
             let handler = handler.clone();
             let ctx = req.context.clone();
+            req.handler_context().set(ctx.clone());
             Box::pin(async move {
-                handler(req, ctx)
+                time_phase("handler", handler(req, ctx))
                     .await
                     .map_err(|e| Box::new(e) as Box<dyn std::error::Error + Send + Sync>)
             })
         });
 
-        // Wrap each middleware around the next
+        // Wrap each middleware around the next, innermost first, so the first one
+        // registered is the outermost layer
         let mut current_handler = final_handler;
 
         for middleware in middlewares.into_iter().rev() {
@@ -184,11 +610,19 @@ impl Router {
             current_handler = StdArc::new(move |req: Request| {
                 let middleware = middleware.clone();
                 let next = next_handler.clone();
+                // Each middleware may pass the request on at most once per request
+                let called = StdArc::new(AtomicBool::new(false));
                 let next_fn: Box<
                     dyn Fn(Request) -> BoxFuture<'static, std::result::Result<Response, Error>>
                         + Send
                         + Sync,
                 > = Box::new(move |req: Request| {
+                    if called.swap(true, Ordering::SeqCst) {
+                        let error: Error = Box::new(RouterError::Internal(
+                            "Middleware called next more than once".to_string(),
+                        ));
+                        return Box::pin(async move { Err(error) });
+                    }
                     let next = next.clone();
                     (next)(req)
                 });
@@ -197,42 +631,96 @@ impl Router {
         }
 
         // Execute the complete chain
-        (current_handler)(req)
-            .await
-            .map_err(|e| RouterError::HandlerError(anyhow::anyhow!("{}", e)))
+        // Errors keep their status if they are RouterErrors (e.g. from a handler)
+        let result = (current_handler)(req).await.map_err(RouterError::from);
+
+        let ctx = handler_context.get().unwrap_or(initial_context);
+        (result, ctx)
     }
 
-    /// Convert router into Lambda service function
+    /// Route one raw Lambda event and return the response JSON in the event's payload
+    /// format. This is all `into_service` does per invocation, so tests can drive the
+    /// full routing stack (middleware, after hooks, error mapping) with a mock event
+    pub async fn handle(
+        &self,
+        event: Value,
+        _ctx: lambda_runtime::Context,
+    ) -> std::result::Result<Value, Error> {
+        let format = PayloadFormat::from_event(&event);
+
+        // Parse request
+        let req = Request::from_lambda_event(event);
+
+        // Route request
+        let response = self.dispatch(req).await;
+
+        Ok(response.to_lambda_json(format))
+    }
+
+    /// Convert router into Lambda service function; a thin wrapper over `handle`.
+    /// When shutdown hooks are registered this also starts listening for `SIGTERM`,
+    /// so it must be called from within the tokio runtime
     pub fn into_service(
         self,
     ) -> impl Fn(LambdaEvent<Value>) -> BoxFuture<'static, std::result::Result<Value, Error>> {
         let router = Arc::new(self);
+        if !router.shutdown_hooks.is_empty() {
+            spawn_shutdown_listener(router.clone());
+        }
 
         move |event: LambdaEvent<Value>| {
             let router = router.clone();
             Box::pin(async move {
-                let (event_payload, _context) = event.into_parts();
+                let (event_payload, context) = event.into_parts();
+                router.handle(event_payload, context).await
+            })
+        }
+    }
+}
 
-                // Parse request
-                let req = Request::from_lambda_event(event_payload);
+/// Check a request body's media type against what its route expects
+fn check_content_type(req: &Request, expected: &str) -> Result<()> {
+    if req.body().is_none_or(str::is_empty) {
+        return Ok(());
+    }
 
-                // Handle CORS preflight early
-                if req.is_preflight() {
-                    return Ok(Response::cors_preflight().to_json());
-                }
+    let actual = req.header("Content-Type");
+    let media_type = actual
+        .and_then(|value| value.split(';').next())
+        .map(|media_type| media_type.trim().to_ascii_lowercase());
+    if media_type.as_deref() == Some(expected) {
+        Ok(())
+    } else {
+        Err(RouterError::UnsupportedMediaType {
+            expected: expected.to_string(),
+            actual: actual.cloned(),
+        })
+    }
+}
 
-                // Route request
-                let response = match router.handle_request(req).await {
-                    Ok(resp) => resp,
-                    Err(e) => e.to_response(),
-                };
+/// Run the router's shutdown hooks on `SIGTERM`, then exit
+#[cfg(unix)]
+fn spawn_shutdown_listener(router: Arc<Router>) {
+    use tokio::signal::unix::{signal, SignalKind};
 
-                Ok(response.to_json())
-            })
+    let mut sigterm = match signal(SignalKind::terminate()) {
+        Ok(sigterm) => sigterm,
+        Err(e) => {
+            eprintln!("Failed to listen for SIGTERM, shutdown hooks won't run: {}", e);
+            return;
         }
-    }
+    };
+    tokio::spawn(async move {
+        if sigterm.recv().await.is_some() {
+            router.shutdown().await;
+            std::process::exit(0);
+        }
+    });
 }
 
+#[cfg(not(unix))]
+fn spawn_shutdown_listener(_router: Arc<Router>) {}
+
 impl Default for Router {
     fn default() -> Self {
         Self::new()
@@ -246,3 +734,802 @@ macro_rules! handler {
         |req: Request, ctx: Context| Box::pin($func(req, ctx))
     };
 }
+
+/// Permissions for a guarded route, e.g. `perms!["write:analytics"]`
+#[macro_export]
+macro_rules! perms {
+    ($($permission:expr),* $(,)?) => {
+        vec![$(::std::string::String::from($permission)),*]
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(method: &str, path: &str) -> Request {
+        Request::from_lambda_event(json!({
+            "rawPath": path,
+            "requestContext": { "http": { "method": method }, "requestId": "req-1" },
+            "headers": {}
+        }))
+    }
+
+    async fn ping(_req: Request, _ctx: Context) -> Result<Response> {
+        Ok(Response::ok(json!({ "pong": true })).header("X-Stage", "handler"))
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_runs_hooks_in_order() {
+        let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut router = Router::new();
+        for name in ["metrics", "logs"] {
+            let calls = calls.clone();
+            router.on_shutdown(move || {
+                let calls = calls.clone();
+                async move { calls.lock().unwrap().push(name) }
+            });
+        }
+
+        router.shutdown().await;
+        assert_eq!(*calls.lock().unwrap(), vec!["metrics", "logs"]);
+    }
+
+    #[tokio::test]
+    async fn test_after_hook_adds_header_to_all_responses() {
+        let mut router = Router::new();
+        router.get("/api/ping", handler!(ping));
+        router.after(|response, ctx| response.header("X-Request-Id", ctx.request_id.clone()));
+
+        let found = router.dispatch(request("GET", "/api/ping")).await;
+        assert_eq!(found.status_code, 200);
+        assert_eq!(
+            found.headers.get("X-Request-Id"),
+            Some(&"req-1".to_string())
+        );
+
+        let missing = router.dispatch(request("GET", "/api/missing")).await;
+        assert_eq!(missing.status_code, 404);
+        assert_eq!(
+            missing.headers.get("X-Request-Id"),
+            Some(&"req-1".to_string())
+        );
+
+        let preflight = router.dispatch(request("OPTIONS", "/api/ping")).await;
+        assert_eq!(
+            preflight.headers.get("X-Request-Id"),
+            Some(&"req-1".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_into_service_answers_in_event_payload_format() {
+        let mut router = Router::new();
+        router.get("/api/ping", handler!(ping));
+        router.after(|response, _ctx| response.set_cookie("seen", "1", Default::default()));
+        let service = router.into_service();
+
+        let v1_event = json!({
+            "version": "1.0",
+            "httpMethod": "GET",
+            "path": "/api/ping",
+            "requestContext": { "requestId": "req-1" },
+            "headers": {}
+        });
+        let v1 = service(LambdaEvent::new(
+            v1_event,
+            lambda_runtime::Context::default(),
+        ))
+        .await
+        .unwrap();
+        assert_eq!(v1["statusCode"], 200);
+        assert_eq!(v1["multiValueHeaders"]["X-Stage"], json!(["handler"]));
+        assert_eq!(
+            v1["multiValueHeaders"]["Set-Cookie"]
+                .as_array()
+                .map(|c| c.len()),
+            Some(1)
+        );
+        assert!(v1.get("cookies").is_none());
+
+        let v2_event = json!({
+            "version": "2.0",
+            "rawPath": "/api/ping",
+            "requestContext": { "http": { "method": "GET" }, "requestId": "req-2" },
+            "headers": {}
+        });
+        let v2 = service(LambdaEvent::new(
+            v2_event,
+            lambda_runtime::Context::default(),
+        ))
+        .await
+        .unwrap();
+        assert_eq!(v2["statusCode"], 200);
+        assert_eq!(v2["cookies"].as_array().map(|c| c.len()), Some(1));
+        assert!(v2.get("multiValueHeaders").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_handle_routes_mock_event() {
+        let mut router = Router::new();
+        router.get("/api/users/:userId", |req: Request, _ctx: Context| {
+            Box::pin(async move {
+                let user_id = req.path_param("userId").cloned().unwrap_or_default();
+                Ok(Response::ok(json!({ "userId": user_id })))
+            })
+        });
+
+        let event = json!({
+            "rawPath": "/api/users/user-123",
+            "requestContext": { "http": { "method": "GET" }, "requestId": "req-1" },
+            "headers": {}
+        });
+        let response = router
+            .handle(event, lambda_runtime::Context::default())
+            .await
+            .unwrap();
+        assert_eq!(response["statusCode"], 200);
+        let body: Value = serde_json::from_str(response["body"].as_str().unwrap()).unwrap();
+        assert_eq!(body["userId"], "user-123");
+
+        let missing = json!({
+            "rawPath": "/api/missing",
+            "requestContext": { "http": { "method": "GET" }, "requestId": "req-2" },
+            "headers": {}
+        });
+        let response = router
+            .handle(missing, lambda_runtime::Context::default())
+            .await
+            .unwrap();
+        assert_eq!(response["statusCode"], 404);
+    }
+
+    /// Records its name on the way in and out of the chain
+    struct Tracing {
+        name: &'static str,
+        log: Arc<std::sync::Mutex<Vec<String>>>,
+    }
+
+    #[async_trait]
+    impl Middleware for Tracing {
+        async fn handle(
+            &self,
+            req: Request,
+            next: crate::Next,
+        ) -> std::result::Result<Response, Error> {
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("{} request", self.name));
+            let response = next(req).await;
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("{} response", self.name));
+            response
+        }
+    }
+
+    #[tokio::test]
+    async fn test_middlewares_run_outer_to_inner_in_registration_order() {
+        let log = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut router = Router::new();
+        for name in ["first", "second", "third"] {
+            router.use_middleware(Tracing {
+                name,
+                log: log.clone(),
+            });
+        }
+        let handler_log = log.clone();
+        router.get("/api/ping", move |_req: Request, _ctx: Context| {
+            handler_log.lock().unwrap().push("handler".to_string());
+            Box::pin(async { Ok(Response::ok(json!({}))) })
+        });
+
+        let response = router.dispatch(request("GET", "/api/ping")).await;
+
+        assert_eq!(response.status_code, 200);
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                "first request",
+                "second request",
+                "third request",
+                "handler",
+                "third response",
+                "second response",
+                "first response",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_next_may_be_called_at_most_once() {
+        let calls = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let mut router = Router::new();
+        router.use_middleware(crate::middleware::MiddlewareFn::new(
+            |req: Request, next: crate::Next| {
+                Box::pin(async move {
+                    let retry = req.clone();
+                    let _ = next(req).await?;
+                    next(retry).await
+                }) as BoxFuture<'static, std::result::Result<Response, Error>>
+            },
+        ));
+        let handler_calls = calls.clone();
+        router.get("/api/ping", move |_req: Request, _ctx: Context| {
+            handler_calls.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async { Ok(Response::ok(json!({}))) })
+        });
+
+        let response = router.dispatch(request("GET", "/api/ping")).await;
+
+        assert_eq!(response.status_code, 500);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_middleware_can_answer_without_calling_next() {
+        let mut router = Router::new();
+        router.use_middleware(crate::middleware::MiddlewareFn::new(
+            |_req: Request, _next: crate::Next| {
+                Box::pin(async { Ok(Response::forbidden("Blocked")) })
+                    as BoxFuture<'static, std::result::Result<Response, Error>>
+            },
+        ));
+        router.get("/api/ping", handler!(ping));
+
+        let response = router.dispatch(request("GET", "/api/ping")).await;
+
+        assert_eq!(response.status_code, 403);
+        assert!(!response.headers.contains_key("X-Stage"));
+        // CORS is registered first, so it still decorates the short-circuited response
+        assert!(response.headers.contains_key("Access-Control-Allow-Origin"));
+    }
+
+    async fn literal(_req: Request, _ctx: Context) -> Result<Response> {
+        Ok(Response::ok(json!({ "route": "literal" })))
+    }
+
+    async fn param(_req: Request, _ctx: Context) -> Result<Response> {
+        Ok(Response::ok(json!({ "route": "param" })))
+    }
+
+    async fn wildcard(_req: Request, _ctx: Context) -> Result<Response> {
+        Ok(Response::ok(json!({ "route": "wildcard" })))
+    }
+
+    #[tokio::test]
+    async fn test_most_specific_route_wins_regardless_of_order() {
+        let mut router = Router::new();
+        router.get("/api/workouts/*rest", handler!(wildcard));
+        router.get("/api/workouts/plans/:planId", handler!(param));
+        router.get("/api/workouts/plans/schedule", handler!(literal));
+
+        let route_for = |response: Response| {
+            let body: Value = serde_json::from_str(&response.body).unwrap();
+            body["route"].as_str().unwrap().to_string()
+        };
+        let schedule = router
+            .dispatch(request("GET", "/api/workouts/plans/schedule"))
+            .await;
+        assert_eq!(route_for(schedule), "literal");
+        let plan = router
+            .dispatch(request("GET", "/api/workouts/plans/plan-1"))
+            .await;
+        assert_eq!(route_for(plan), "param");
+        let other = router
+            .dispatch(request("GET", "/api/workouts/history/2024"))
+            .await;
+        assert_eq!(route_for(other), "wildcard");
+    }
+
+    #[tokio::test]
+    async fn test_trailing_slash_normalization() {
+        let mut router = Router::new();
+        router.get("/api/ping", handler!(ping));
+
+        let slashed = router.dispatch(request("GET", "/api/ping/")).await;
+        assert_eq!(slashed.status_code, 404);
+
+        router.strict_trailing_slash(false);
+        let slashed = router.dispatch(request("GET", "/api/ping/")).await;
+        assert_eq!(slashed.status_code, 200);
+        let exact = router.dispatch(request("GET", "/api/ping")).await;
+        assert_eq!(exact.status_code, 200);
+    }
+
+    #[tokio::test]
+    async fn test_xray_subsegments_for_matched_routes() {
+        let daemon = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+        daemon
+            .set_read_timeout(Some(std::time::Duration::from_secs(1)))
+            .unwrap();
+        let address = daemon.local_addr().unwrap();
+
+        let mut router = Router::new();
+        router.get("/api/plans/:planId", handler!(ping));
+        // What `enable_xray` reads from the environment, without touching the
+        // process-wide variables other tests see
+        router.xray = Some(Arc::new(move || {
+            XRayTrace::from_header(
+                "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1",
+                address,
+            )
+        }));
+        let response = router.dispatch(request("GET", "/api/plans/p1")).await;
+        assert_eq!(response.status_code, 200);
+
+        let mut documents = Vec::new();
+        let mut buf = [0u8; 4096];
+        for _ in 0..2 {
+            let len = daemon.recv(&mut buf).unwrap();
+            let datagram = std::str::from_utf8(&buf[..len]).unwrap();
+            let (_, document) = datagram.split_once('\n').unwrap();
+            documents.push(serde_json::from_str::<Value>(document).unwrap());
+        }
+
+        let (handler, route) = (&documents[0], &documents[1]);
+        assert_eq!(handler["name"], "handler");
+        assert_eq!(handler["parent_id"], route["id"]);
+        assert_eq!(route["name"], "GET /api/plans/:planId");
+        assert_eq!(route["parent_id"], "53995c3f42cd8ad8");
+        assert_eq!(route["annotations"]["route"], "/api/plans/:planId");
+        assert_eq!(route["annotations"]["status"], 200);
+    }
+
+    #[tokio::test]
+    async fn test_methods_match_case_insensitively() {
+        let mut router = Router::new();
+        router.add_route("post", "/api/ping", handler!(ping));
+
+        let lower = router.dispatch(request("post", "/api/ping")).await;
+        assert_eq!(lower.status_code, 200);
+        let upper = router.dispatch(request("POST", "/api/ping")).await;
+        assert_eq!(upper.status_code, 200);
+        let preflight = router.dispatch(request("options", "/api/ping")).await;
+        assert_eq!(preflight.status_code, 200);
+    }
+
+    #[test]
+    fn test_check_conflicts_reports_only_unresolvable_overlaps() {
+        let mut router = Router::new();
+        router.get("/api/workouts/plans/:planId", handler!(param));
+        router.get("/api/workouts/plans/schedule", handler!(literal));
+        router.delete("/api/workouts/plans/:id", handler!(param));
+        router.get("/api/workouts/plans/:id", handler!(param));
+
+        let conflicts = router.check_conflicts();
+
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].0.pattern, "/api/workouts/plans/:planId");
+        assert_eq!(conflicts[0].1.pattern, "/api/workouts/plans/:id");
+        assert_eq!(conflicts[0].1.method, "GET");
+    }
+
+    #[tokio::test]
+    async fn test_after_hooks_run_after_handler_in_order() {
+        let mut router = Router::new();
+        router.get("/api/ping", handler!(ping));
+        router.after(|response, _ctx| {
+            let stage = response.headers.get("X-Stage").cloned().unwrap_or_default();
+            response.header("X-Stage", format!("{},first", stage))
+        });
+        router.after(|response, _ctx| {
+            let stage = response.headers.get("X-Stage").cloned().unwrap_or_default();
+            response.header("X-Stage", format!("{},second", stage))
+        });
+
+        let response = router.dispatch(request("GET", "/api/ping")).await;
+        assert_eq!(
+            response.headers.get("X-Stage"),
+            Some(&"handler,first,second".to_string())
+        );
+    }
+
+    async fn query_then_respond(_req: Request, _ctx: Context) -> Result<Response> {
+        crate::time_phase(
+            "db",
+            tokio::time::sleep(std::time::Duration::from_millis(5)),
+        )
+        .await;
+        Ok(Response::ok(json!({ "ok": true })))
+    }
+
+    fn timing_entries(header: &str) -> Vec<(String, f64)> {
+        header
+            .split(", ")
+            .map(|entry| {
+                let (name, duration) = entry.split_once(";dur=").unwrap();
+                (name.to_string(), duration.parse().unwrap())
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_server_timing_header_when_enabled() {
+        let mut router = Router::new();
+        router.get("/api/ping", handler!(query_then_respond));
+        router.enable_server_timing(true);
+
+        let response = router.dispatch(request("GET", "/api/ping")).await;
+        let header = response
+            .headers
+            .get("Server-Timing")
+            .expect("Server-Timing header");
+        let entries = timing_entries(header);
+
+        let db = entries.iter().find(|(name, _)| name == "db").unwrap().1;
+        let handler = entries
+            .iter()
+            .find(|(name, _)| name == "handler")
+            .unwrap()
+            .1;
+        assert!(db >= 5.0);
+        assert!(handler >= db);
+        assert!(handler < 5_000.0);
+    }
+
+    #[tokio::test]
+    async fn test_server_timing_header_absent_when_disabled() {
+        let mut router = Router::new();
+        router.get("/api/ping", handler!(query_then_respond));
+        router.enable_server_timing(false);
+
+        let response = router.dispatch(request("GET", "/api/ping")).await;
+        assert!(!response.headers.contains_key("Server-Timing"));
+    }
+
+    async fn stale_update(_req: Request, _ctx: Context) -> Result<Response> {
+        let outcome: std::result::Result<(), Box<dyn std::error::Error + Send + Sync>> = Err(
+            Box::new(RouterError::Conflict("Plan was modified".to_string())),
+        );
+        outcome?;
+        Ok(Response::no_content())
+    }
+
+    #[tokio::test]
+    async fn test_error_bodies_include_request_id() {
+        let mut router = Router::new();
+        router.put("/api/plans/:planId", handler!(stale_update));
+
+        let response = router.dispatch(request("PUT", "/api/plans/p1")).await;
+        assert_eq!(response.status_code, 409);
+        let body: Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(body["error"], "Conflict");
+        assert_eq!(body["message"], "Plan was modified");
+        assert_eq!(body["request_id"], "req-1");
+
+        let response = router.dispatch(request("GET", "/api/missing")).await;
+        let body: Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(body["request_id"], "req-1");
+    }
+
+    /// Stands in for a service's auth middleware
+    struct FakeAuth;
+
+    #[async_trait]
+    impl Middleware for FakeAuth {
+        async fn handle(
+            &self,
+            mut req: Request,
+            next: crate::Next,
+        ) -> std::result::Result<Response, Error> {
+            let context = req.context.clone().with_user("user-42".to_string(), None);
+            req.set_context(context);
+            next(req).await
+        }
+    }
+
+    /// Route and handler user id seen by `Probe`
+    type Seen = Arc<std::sync::Mutex<Vec<(Option<String>, Option<String>)>>>;
+
+    /// Records the route and handler user id an outer middleware can see
+    struct Probe(Seen);
+
+    #[async_trait]
+    impl Middleware for Probe {
+        async fn handle(
+            &self,
+            req: Request,
+            next: crate::Next,
+        ) -> std::result::Result<Response, Error> {
+            let route = req.matched_route().map(str::to_string);
+            let handler_context = req.handler_context();
+            let response = next(req).await;
+            let user_id = handler_context.get().and_then(|ctx| ctx.user_id);
+            self.0.lock().unwrap().push((route, user_id));
+            response
+        }
+    }
+
+    #[tokio::test]
+    async fn test_outer_middleware_sees_route_and_handler_context() {
+        let seen = Seen::default();
+        let mut router = Router::new();
+        router.use_middleware(Probe(seen.clone()));
+        router.use_middleware(FakeAuth);
+        router.put("/api/plans/:planId", handler!(stale_update));
+
+        let response = router.dispatch(request("PUT", "/api/plans/p1")).await;
+        assert_eq!(response.status_code, 409);
+        assert_eq!(
+            seen.lock().unwrap().as_slice(),
+            &[(
+                Some("/api/plans/:planId".to_string()),
+                Some("user-42".to_string())
+            )]
+        );
+    }
+
+    async fn echo_correlation_id(_req: Request, ctx: Context) -> Result<Response> {
+        Ok(Response::ok(
+            json!({ "correlation_id": ctx.correlation_id }),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_correlation_id_is_read_or_generated() {
+        let mut router = Router::new();
+        router.get("/api/ping", handler!(echo_correlation_id));
+
+        let incoming = Request::from_lambda_event(json!({
+            "rawPath": "/api/ping",
+            "requestContext": { "http": { "method": "GET" }, "requestId": "req-1" },
+            "headers": { "x-correlation-id": "corr-7" }
+        }));
+        let response = router.dispatch(incoming).await;
+        let body: Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(body["correlation_id"], "corr-7");
+        assert_eq!(
+            response.headers.get(CORRELATION_ID_HEADER),
+            Some(&"corr-7".to_string())
+        );
+
+        let response = router.dispatch(request("GET", "/api/ping")).await;
+        let generated = response.headers.get(CORRELATION_ID_HEADER).unwrap();
+        assert!(!generated.is_empty());
+        assert_ne!(generated, "corr-7");
+    }
+
+    #[tokio::test]
+    async fn test_undecodable_base64_body_is_rejected() {
+        let mut router = Router::new();
+        router.put("/api/plans/:planId", handler!(stale_update));
+
+        let response = router
+            .dispatch(Request::from_lambda_event(json!({
+                "rawPath": "/api/plans/p1",
+                "requestContext": { "http": { "method": "PUT" }, "requestId": "req-1" },
+                "headers": {},
+                "body": "{\"name\": \"Push day\"}",
+                "isBase64Encoded": true
+            })))
+            .await;
+        assert_eq!(response.status_code, 400);
+        let body: Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(body["request_id"], "req-1");
+    }
+
+    #[tokio::test]
+    async fn test_json_route_rejects_other_content_types() {
+        let mut router = Router::new();
+        router.post_json("/api/plans", handler!(ping));
+        let post = |content_type: Option<&str>, body: Option<&str>| {
+            let headers = match content_type {
+                Some(content_type) => json!({ "content-type": content_type }),
+                None => json!({}),
+            };
+            Request::from_lambda_event(json!({
+                "rawPath": "/api/plans",
+                "requestContext": { "http": { "method": "POST" }, "requestId": "req-1" },
+                "headers": headers,
+                "body": body
+            }))
+        };
+
+        let json_body = Some("{\"name\": \"Push day\"}");
+        let accepted = router
+            .dispatch(post(Some("application/json; charset=utf-8"), json_body))
+            .await;
+        assert_eq!(accepted.status_code, 200);
+
+        let rejected = router.dispatch(post(Some("text/plain"), json_body)).await;
+        assert_eq!(rejected.status_code, 415);
+        assert_eq!(router.dispatch(post(None, json_body)).await.status_code, 415);
+
+        // Nothing to mis-parse without a body, and preflights never reach the check
+        assert_eq!(router.dispatch(post(None, None)).await.status_code, 200);
+        assert_eq!(
+            router.dispatch(request("OPTIONS", "/api/plans")).await.status_code,
+            200
+        );
+    }
+
+    async fn progress_timeline(_req: Request, _ctx: Context) -> Result<Response> {
+        Ok(Response::ok(json!({ "photos": ["p1", "p2"] })).with_etag())
+    }
+
+    #[tokio::test]
+    async fn test_matching_if_none_match_returns_304() {
+        let mut router = Router::new();
+        router.get(
+            "/api/analytics/me/progress-photos/timeline",
+            handler!(progress_timeline),
+        );
+        let conditional = |if_none_match: &str| {
+            Request::from_lambda_event(json!({
+                "rawPath": "/api/analytics/me/progress-photos/timeline",
+                "requestContext": { "http": { "method": "GET" }, "requestId": "req-1" },
+                "headers": { "if-none-match": if_none_match }
+            }))
+        };
+
+        let first = router
+            .dispatch(request("GET", "/api/analytics/me/progress-photos/timeline"))
+            .await;
+        assert_eq!(first.status_code, 200);
+        let etag = first.etag_value().unwrap().to_string();
+
+        let cached = router.dispatch(conditional(&etag)).await;
+        assert_eq!(cached.status_code, 304);
+        assert!(cached.body.is_empty());
+        assert_eq!(cached.etag_value(), Some(etag.as_str()));
+        assert!(cached.headers.contains_key("Access-Control-Allow-Origin"));
+        assert!(!cached.headers.contains_key("Content-Type"));
+
+        let stale = router.dispatch(conditional("\"0000000000000000\"")).await;
+        assert_eq!(stale.status_code, 200);
+        assert_eq!(stale.body, first.body);
+    }
+
+    async fn oauth_callback(_req: Request, _ctx: Context) -> Result<Response> {
+        Ok(Response::redirect(
+            "https://app.example.com/settings?connected=1",
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_redirects_pass_through_unchanged() {
+        let mut router = Router::new();
+        router.get("/api/integrations/callback", handler!(oauth_callback));
+
+        let response = router
+            .dispatch(request("GET", "/api/integrations/callback"))
+            .await;
+        assert_eq!(response.status_code, 302);
+        assert_eq!(
+            response.headers.get("Location"),
+            Some(&"https://app.example.com/settings?connected=1".to_string())
+        );
+        assert!(response.body.is_empty());
+    }
+
+    async fn required_permissions(req: Request, _ctx: Context) -> Result<Response> {
+        Ok(Response::ok(
+            json!({ "required": req.required_permissions() }),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_guarded_routes_record_required_permissions() {
+        let mut router = Router::new();
+        router.post_guarded(
+            "/api/analytics/charts",
+            perms!["write:analytics"],
+            handler!(required_permissions),
+        );
+        router.get("/api/analytics/charts", handler!(required_permissions));
+
+        let guarded = router
+            .dispatch(request("POST", "/api/analytics/charts"))
+            .await;
+        let body: Value = serde_json::from_str(&guarded.body).unwrap();
+        assert_eq!(body["required"], json!(["write:analytics"]));
+
+        let open = router
+            .dispatch(request("GET", "/api/analytics/charts"))
+            .await;
+        let body: Value = serde_json::from_str(&open.body).unwrap();
+        assert_eq!(body["required"], json!([]));
+    }
+
+    fn websocket_event(route_key: &str, event_type: &str) -> Value {
+        json!({
+            "requestContext": {
+                "routeKey": route_key,
+                "eventType": event_type,
+                "connectionId": "conn-1",
+                "requestId": "req-1"
+            },
+            "body": "{\"action\":\"ping\"}"
+        })
+    }
+
+    async fn websocket_echo(_req: Request, ctx: Context) -> Result<Response> {
+        Ok(Response::ok(json!({
+            "connectionId": ctx.connection_id,
+            "routeKey": ctx.route_key,
+        })))
+    }
+
+    #[tokio::test]
+    async fn test_websocket_events_route_by_route_key() {
+        let mut router = Router::new();
+        router.get("/api/ping", handler!(ping));
+        router.websocket("$connect", handler!(websocket_echo));
+        router.websocket("$default", handler!(websocket_echo));
+
+        let value = router
+            .handle(
+                websocket_event("$connect", "CONNECT"),
+                lambda_runtime::Context::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(value["statusCode"], 200);
+        assert!(value.get("cookies").is_none());
+        assert!(value.get("multiValueHeaders").is_none());
+        let body: Value = serde_json::from_str(value["body"].as_str().unwrap()).unwrap();
+        assert_eq!(body["connectionId"], "conn-1");
+        assert_eq!(body["routeKey"], "$connect");
+
+        // Keys without a handler fall back to $default, and the context keeps the real key
+        let value = router
+            .handle(
+                websocket_event("sendMessage", "MESSAGE"),
+                lambda_runtime::Context::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(value["statusCode"], 200);
+        let body: Value = serde_json::from_str(value["body"].as_str().unwrap()).unwrap();
+        assert_eq!(body["routeKey"], "sendMessage");
+
+        // HTTP routes on the same router are unaffected
+        let http = router.dispatch(request("GET", "/api/ping")).await;
+        assert_eq!(http.status_code, 200);
+    }
+
+    #[tokio::test]
+    async fn test_unhandled_websocket_route_key_is_not_found() {
+        let mut router = Router::new();
+        router.websocket("$connect", handler!(websocket_echo));
+
+        let value = router
+            .handle(
+                websocket_event("$disconnect", "DISCONNECT"),
+                lambda_runtime::Context::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(value["statusCode"], 404);
+    }
+
+    async fn custom_not_found(req: Request, _ctx: Context) -> Result<Response> {
+        Ok(Response::new(404).json(json!({ "missing": req.path })))
+    }
+
+    #[tokio::test]
+    async fn test_wrong_method_is_405_and_unknown_path_uses_fallback() {
+        let mut router = Router::new();
+        router.get("/api/workouts/plans/:planId", handler!(ping));
+        router.delete("/api/workouts/plans/:planId", handler!(ping));
+        router.fallback(handler!(custom_not_found));
+
+        let wrong_method = router
+            .dispatch(request("POST", "/api/workouts/plans/plan-1"))
+            .await;
+        assert_eq!(wrong_method.status_code, 405);
+        assert_eq!(
+            wrong_method.headers.get("Allow"),
+            Some(&"GET, DELETE".to_string())
+        );
+
+        let missing = router.dispatch(request("GET", "/api/unknown")).await;
+        assert_eq!(missing.status_code, 404);
+        let body: Value = serde_json::from_str(&missing.body).unwrap();
+        assert_eq!(body["missing"], "/api/unknown");
+    }
+}
@@ -0,0 +1,132 @@
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::{Request, Response};
+
+/// One way a request body failed its schema
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldError {
+    /// JSON pointer to the offending value, e.g. `/exercises/0/sets`; empty for the
+    /// body as a whole
+    pub path: String,
+    pub message: String,
+}
+
+/// A compiled JSON Schema for a request payload. Build one per schema at startup
+/// (e.g. in a `Lazy`) and validate each request body against it
+pub struct JsonSchema {
+    validator: jsonschema::Validator,
+}
+
+impl JsonSchema {
+    /// Compile `schema`. Fails when the schema itself is invalid
+    pub fn new(schema: &Value) -> Result<Self, String> {
+        jsonschema::validator_for(schema)
+            .map(|validator| Self { validator })
+            .map_err(|e| format!("Invalid JSON schema: {}", e))
+    }
+
+    /// Every violation in `instance`, or `Ok` when it conforms
+    pub fn validate(&self, instance: &Value) -> Result<(), Vec<FieldError>> {
+        let errors: Vec<FieldError> = self
+            .validator
+            .iter_errors(instance)
+            .map(|error| FieldError {
+                path: error.instance_path.to_string(),
+                message: error.to_string(),
+            })
+            .collect();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+impl Request {
+    /// Parse the JSON body and validate it against `schema`. The error is a ready
+    /// `400` response (boxed, as `Response` is large): a plain bad request when the
+    /// body isn't JSON, otherwise `Response::validation_failed` listing every field
+    /// error
+    pub fn validated_json(&self, schema: &JsonSchema) -> Result<Value, Box<Response>> {
+        let body: Value = self
+            .json()
+            .map_err(|e| Box::new(Response::bad_request(&format!("Invalid JSON body: {}", e))))?;
+
+        schema
+            .validate(&body)
+            .map_err(|errors| Box::new(Response::validation_failed(&errors)))?;
+        Ok(body)
+    }
+}
+
+impl Response {
+    /// 400 Bad Request listing the fields that failed validation, as
+    /// `{"errors": [{"path", "message"}]}`
+    pub fn validation_failed(errors: &[FieldError]) -> Self {
+        Self::new(400)
+            .json(json!({
+                "error": "Bad Request",
+                "message": "Request body failed validation",
+                "errors": errors
+            }))
+            .with_cors()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn plan_schema() -> JsonSchema {
+        JsonSchema::new(&json!({
+            "type": "object",
+            "required": ["name", "durationWeeks"],
+            "properties": {
+                "name": {"type": "string", "minLength": 1},
+                "durationWeeks": {"type": "integer", "minimum": 1},
+                "tags": {"type": "array", "items": {"type": "string"}}
+            }
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_validate_reports_each_field() {
+        let schema = plan_schema();
+
+        assert!(schema.validate(&json!({"name": "Push/Pull", "durationWeeks": 8})).is_ok());
+
+        let errors = schema
+            .validate(&json!({"name": "", "durationWeeks": 0, "tags": ["legs", 3]}))
+            .unwrap_err();
+        let paths: Vec<&str> = errors.iter().map(|error| error.path.as_str()).collect();
+        assert_eq!(errors.len(), 3);
+        assert!(paths.contains(&"/name"));
+        assert!(paths.contains(&"/durationWeeks"));
+        assert!(paths.contains(&"/tags/1"));
+
+        let missing = schema.validate(&json!({})).unwrap_err();
+        assert_eq!(missing.len(), 2);
+        assert!(missing.iter().all(|error| error.path.is_empty()));
+    }
+
+    #[test]
+    fn test_invalid_schema_is_rejected() {
+        assert!(JsonSchema::new(&json!({"type": "not-a-type"})).is_err());
+    }
+
+    #[test]
+    fn test_validation_failed_response() {
+        let response = Response::validation_failed(&[FieldError {
+            path: "/name".to_string(),
+            message: "\"\" is shorter than 1 character".to_string(),
+        }]);
+        assert_eq!(response.status_code, 400);
+
+        let body: Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(body["errors"][0]["path"], "/name");
+    }
+}
@@ -0,0 +1,81 @@
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::xray::trace_subsegment;
+
+/// Environment flag that turns on `Server-Timing` output
+pub const SERVER_TIMING_ENV: &str = "SERVER_TIMING_DEBUG";
+
+tokio::task_local! {
+    static CURRENT_TIMING: ServerTiming;
+}
+
+/// Collects per-phase durations (auth, db, handler, ...) for a single request so they
+/// can be reported in a `Server-Timing` header
+#[derive(Debug, Clone, Default)]
+pub struct ServerTiming {
+    phases: Arc<Mutex<Vec<(String, Duration)>>>,
+}
+
+impl ServerTiming {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether `SERVER_TIMING_DEBUG` is set to `1` or `true`
+    pub fn enabled_from_env() -> bool {
+        std::env::var(SERVER_TIMING_ENV)
+            .map(|value| matches!(value.to_lowercase().as_str(), "1" | "true"))
+            .unwrap_or(false)
+    }
+
+    /// Add time to a phase; repeated phases (e.g. several db calls) accumulate
+    pub fn record(&self, phase: &str, duration: Duration) {
+        if let Ok(mut phases) = self.phases.lock() {
+            match phases.iter_mut().find(|(name, _)| name == phase) {
+                Some((_, total)) => *total += duration,
+                None => phases.push((phase.to_string(), duration)),
+            }
+        }
+    }
+
+    /// Format the recorded phases as a `Server-Timing` header value, in the order
+    /// they were first recorded, or `None` if nothing was recorded
+    pub fn header_value(&self) -> Option<String> {
+        let phases = self.phases.lock().ok()?;
+        if phases.is_empty() {
+            return None;
+        }
+
+        Some(
+            phases
+                .iter()
+                .map(|(name, duration)| {
+                    format!("{};dur={:.2}", name, duration.as_secs_f64() * 1000.0)
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+        )
+    }
+
+    /// Run a future with this collector as the current request's timing
+    pub async fn scope<F: Future>(&self, future: F) -> F::Output {
+        CURRENT_TIMING.scope(self.clone(), future).await
+    }
+}
+
+/// Record a phase against the request currently being timed. Does nothing when
+/// Server-Timing is not enabled for the request
+pub fn record_phase(phase: &str, duration: Duration) {
+    let _ = CURRENT_TIMING.try_with(|timing| timing.record(phase, duration));
+}
+
+/// Await a future and record how long it took as `phase`. When the request is traced
+/// with X-Ray the phase is also sent as a subsegment
+pub async fn time_phase<F: Future>(phase: &str, future: F) -> F::Output {
+    let started = Instant::now();
+    let output = trace_subsegment(phase, future).await;
+    record_phase(phase, started.elapsed());
+    output
+}
@@ -0,0 +1,279 @@
+use serde_json::{json, Map, Value};
+use std::collections::hash_map::RandomState;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::net::{SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Environment variable Lambda sets to the current invocation's trace header
+pub const TRACE_ID_ENV: &str = "_X_AMZN_TRACE_ID";
+
+/// Environment variable with the `host:port` of the X-Ray daemon
+pub const DAEMON_ADDRESS_ENV: &str = "AWS_XRAY_DAEMON_ADDRESS";
+
+const DEFAULT_DAEMON_ADDRESS: &str = "127.0.0.1:2000";
+
+/// Every document sent to the daemon is prefixed with this line
+const DAEMON_HEADER: &str = "{\"format\": \"json\", \"version\": 1}\n";
+
+tokio::task_local! {
+    static CURRENT_TRACE: XRayTrace;
+}
+
+/// The X-Ray trace a request runs under, and the segment that new subsegments are
+/// attached to
+#[derive(Debug, Clone)]
+pub struct XRayTrace {
+    trace_id: String,
+    parent_id: String,
+    daemon: SocketAddr,
+}
+
+impl XRayTrace {
+    /// The trace Lambda is running this invocation under, read from `_X_AMZN_TRACE_ID`.
+    /// `None` outside Lambda or when the invocation isn't sampled
+    pub fn from_env() -> Option<Self> {
+        let header = std::env::var(TRACE_ID_ENV).ok()?;
+        let daemon = std::env::var(DAEMON_ADDRESS_ENV)
+            .ok()
+            .and_then(|address| daemon_address(&address))
+            .or_else(|| DEFAULT_DAEMON_ADDRESS.parse().ok())?;
+        Self::from_header(&header, daemon)
+    }
+
+    /// Parse a `Root=...;Parent=...;Sampled=1` trace header. Unsampled traces give `None`
+    /// since X-Ray would drop their subsegments anyway
+    pub fn from_header(header: &str, daemon: SocketAddr) -> Option<Self> {
+        let mut trace_id = None;
+        let mut parent_id = None;
+        let mut sampled = false;
+        for field in header.split(';') {
+            match field.trim().split_once('=') {
+                Some(("Root", value)) => trace_id = Some(value.to_string()),
+                Some(("Parent", value)) => parent_id = Some(value.to_string()),
+                Some(("Sampled", value)) => sampled = value == "1",
+                _ => {}
+            }
+        }
+
+        sampled.then_some(Self {
+            trace_id: trace_id?,
+            parent_id: parent_id?,
+            daemon,
+        })
+    }
+
+    pub fn trace_id(&self) -> &str {
+        &self.trace_id
+    }
+
+    /// Run a future with this as the current request's trace
+    pub async fn scope<F: Future>(&self, future: F) -> F::Output {
+        CURRENT_TRACE.scope(self.clone(), future).await
+    }
+
+    fn child_of(&self, parent_id: &str) -> Self {
+        Self {
+            parent_id: parent_id.to_string(),
+            ..self.clone()
+        }
+    }
+
+    /// Send a document to the daemon. Tracing is best-effort, so failures are ignored
+    fn emit(&self, document: &Value) {
+        if let Ok(socket) = UdpSocket::bind("0.0.0.0:0") {
+            let _ = socket.send_to(
+                format!("{}{}", DAEMON_HEADER, document).as_bytes(),
+                self.daemon,
+            );
+        }
+    }
+}
+
+/// A timed piece of work within the current request's trace
+#[derive(Debug)]
+pub struct Subsegment {
+    trace: XRayTrace,
+    id: String,
+    name: String,
+    start_time: f64,
+    annotations: Map<String, Value>,
+    error: bool,
+    fault: bool,
+}
+
+impl Subsegment {
+    /// Open a subsegment under the current request's trace, or `None` when the request
+    /// isn't traced
+    pub fn begin(name: &str) -> Option<Self> {
+        let trace = CURRENT_TRACE.try_with(|trace| trace.clone()).ok()?;
+        Some(Self {
+            trace,
+            id: new_id(),
+            name: name.to_string(),
+            start_time: epoch_seconds(),
+            annotations: Map::new(),
+            error: false,
+            fault: false,
+        })
+    }
+
+    /// Add an indexed annotation, searchable in the X-Ray console
+    pub fn annotate(&mut self, key: &str, value: impl Into<Value>) {
+        self.annotations.insert(key.to_string(), value.into());
+    }
+
+    /// Record the response status: 4xx marks the subsegment as an error, 5xx as a fault
+    pub fn set_status(&mut self, status: u16) {
+        self.annotate("status", status);
+        self.error = (400..500).contains(&status);
+        self.fault = status >= 500;
+    }
+
+    /// Run a future with this subsegment as the parent of any subsegments it opens
+    pub async fn scope<F: Future>(&self, future: F) -> F::Output {
+        CURRENT_TRACE
+            .scope(self.trace.child_of(&self.id), future)
+            .await
+    }
+
+    /// Close the subsegment and send it to the daemon
+    pub fn end(self) {
+        let document = self.document(epoch_seconds());
+        self.trace.emit(&document);
+    }
+
+    fn document(&self, end_time: f64) -> Value {
+        json!({
+            "type": "subsegment",
+            "id": self.id,
+            "trace_id": self.trace.trace_id,
+            "parent_id": self.trace.parent_id,
+            "name": self.name,
+            "start_time": self.start_time,
+            "end_time": end_time,
+            "annotations": self.annotations,
+            "error": self.error,
+            "fault": self.fault,
+        })
+    }
+}
+
+/// Await a future inside a subsegment named `name`, e.g. around a repository call.
+/// Just awaits it when the request isn't traced
+pub async fn trace_subsegment<F: Future>(name: &str, future: F) -> F::Output {
+    match Subsegment::begin(name) {
+        Some(subsegment) => {
+            let output = subsegment.scope(future).await;
+            subsegment.end();
+            output
+        }
+        None => future.await,
+    }
+}
+
+fn daemon_address(address: &str) -> Option<SocketAddr> {
+    // The variable may also hold separate `tcp:host:port udp:host:port` addresses
+    let udp = address
+        .split_whitespace()
+        .find_map(|part| part.strip_prefix("udp:"))
+        .unwrap_or(address);
+    udp.trim().parse().ok()
+}
+
+/// A random 64-bit id in the 16 hex digit form X-Ray expects
+fn new_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    format!("{:016x}", hasher.finish())
+}
+
+fn epoch_seconds() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs_f64())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str =
+        "Root=1-5759e988-bd862e3fe1be46a994272793;Parent=53995c3f42cd8ad8;Sampled=1";
+
+    fn daemon() -> (UdpSocket, SocketAddr) {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket
+            .set_read_timeout(Some(std::time::Duration::from_secs(1)))
+            .unwrap();
+        let address = socket.local_addr().unwrap();
+        (socket, address)
+    }
+
+    fn receive(socket: &UdpSocket) -> Value {
+        let mut buf = [0u8; 4096];
+        let len = socket.recv(&mut buf).unwrap();
+        let datagram = std::str::from_utf8(&buf[..len]).unwrap();
+        let document = datagram.strip_prefix(DAEMON_HEADER).unwrap();
+        serde_json::from_str(document).unwrap()
+    }
+
+    #[test]
+    fn test_from_header_requires_a_sampled_trace() {
+        let (_, address) = daemon();
+        let trace = XRayTrace::from_header(HEADER, address).unwrap();
+        assert_eq!(trace.trace_id(), "1-5759e988-bd862e3fe1be46a994272793");
+
+        let unsampled = HEADER.replace("Sampled=1", "Sampled=0");
+        assert!(XRayTrace::from_header(&unsampled, address).is_none());
+        assert!(XRayTrace::from_header("Root=1-abc;Sampled=1", address).is_none());
+    }
+
+    #[test]
+    fn test_daemon_address_formats() {
+        assert_eq!(
+            daemon_address("169.254.79.129:2000"),
+            "169.254.79.129:2000".parse().ok()
+        );
+        assert_eq!(
+            daemon_address("tcp:127.0.0.1:2000 udp:127.0.0.2:2001"),
+            "127.0.0.2:2001".parse().ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_nested_subsegments_are_sent_to_the_daemon() {
+        let (socket, address) = daemon();
+        let trace = XRayTrace::from_header(HEADER, address).unwrap();
+
+        trace
+            .scope(async {
+                let mut outer = Subsegment::begin("GET /api/plans").unwrap();
+                outer.annotate("route", "/api/plans");
+                outer.scope(trace_subsegment("db", async {})).await;
+                outer.set_status(503);
+                outer.end();
+            })
+            .await;
+
+        let inner = receive(&socket);
+        let outer = receive(&socket);
+        assert_eq!(inner["name"], "db");
+        assert_eq!(inner["parent_id"], outer["id"]);
+        assert_eq!(outer["parent_id"], "53995c3f42cd8ad8");
+        assert_eq!(outer["trace_id"], trace.trace_id());
+        assert_eq!(outer["annotations"]["route"], "/api/plans");
+        assert_eq!(outer["annotations"]["status"], 503);
+        assert_eq!(outer["fault"], true);
+        assert_eq!(outer["error"], false);
+    }
+
+    #[tokio::test]
+    async fn test_untraced_requests_open_no_subsegments() {
+        assert!(Subsegment::begin("db").is_none());
+        assert_eq!(trace_subsegment("db", async { 7 }).await, 7);
+    }
+}
@@ -0,0 +1,70 @@
+const { DynamoDBClient } = require('@aws-sdk/client-dynamodb');
+const {
+  DynamoDBDocumentClient,
+  ScanCommand,
+  UpdateCommand,
+} = require('@aws-sdk/lib-dynamodb');
+
+const client = new DynamoDBClient({ region: 'eu-west-1' });
+const docClient = DynamoDBDocumentClient.from(client);
+
+const tableName = process.env.TABLE_NAME || 'gymcoach-ai-main-dev';
+
+// One-off backfill: notification preference items written before the scheduler
+// moved to GSI1 lack GSI1PK/GSI1SK and would be skipped by its query.
+async function backfillNotificationPreferences() {
+  try {
+    console.log('Backfilling GSI1 keys on notification preferences...');
+
+    let updated = 0;
+    let skipped = 0;
+    let lastEvaluatedKey;
+
+    do {
+      const scanResult = await docClient.send(
+        new ScanCommand({
+          TableName: tableName,
+          FilterExpression: 'SK = :sk',
+          ExpressionAttributeValues: {
+            ':sk': 'NOTIFICATION_PREFERENCES',
+          },
+          ExclusiveStartKey: lastEvaluatedKey,
+        })
+      );
+
+      for (const item of scanResult.Items || []) {
+        if (item.GSI1PK === 'NOTIFICATION_PREFERENCES') {
+          skipped++;
+          continue;
+        }
+
+        const userId = item.userId || item.PK.replace('USER#', '');
+        await docClient.send(
+          new UpdateCommand({
+            TableName: tableName,
+            Key: { PK: item.PK, SK: item.SK },
+            UpdateExpression:
+              'SET GSI1PK = :gsi1pk, GSI1SK = :gsi1sk, userId = :userId',
+            ExpressionAttributeValues: {
+              ':gsi1pk': 'NOTIFICATION_PREFERENCES',
+              ':gsi1sk': `USER#${userId}`,
+              ':userId': userId,
+            },
+          })
+        );
+        updated++;
+      }
+
+      lastEvaluatedKey = scanResult.LastEvaluatedKey;
+    } while (lastEvaluatedKey);
+
+    console.log(
+      `\nBackfill complete: ${updated} updated, ${skipped} already indexed`
+    );
+  } catch (error) {
+    console.error('Backfill failed:', error);
+    process.exit(1);
+  }
+}
+
+backfillNotificationPreferences();
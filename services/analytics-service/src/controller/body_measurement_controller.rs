@@ -1,6 +1,7 @@
 use serde_json::Value;
 use anyhow::Result;
 
+use crate::models::BodyMeasurement;
 use crate::service::BodyMeasurementService;
 use crate::utils::ResponseBuilder;
 
@@ -13,20 +14,15 @@ impl BodyMeasurementController {
         Self { service }
     }
 
+    /// Returns the measurements rather than a response, so the handler can serve them as
+    /// JSON or CSV
     pub async fn get_body_measurements(
         &self,
         user_id: &str,
         start_date: Option<&str>,
         end_date: Option<&str>,
-    ) -> Result<Value> {
-        if user_id.is_empty() {
-            return Ok(ResponseBuilder::bad_request("User ID is required"));
-        }
-
-        match self.service.get_body_measurements(user_id, start_date, end_date).await {
-            Ok(measurements) => Ok(ResponseBuilder::ok(measurements)),
-            Err(e) => Ok(ResponseBuilder::internal_server_error(&format!("Failed to retrieve body measurements: {}", e))),
-        }
+    ) -> Result<Vec<BodyMeasurement>> {
+        self.service.get_body_measurements(user_id, start_date, end_date).await
     }
 
     pub async fn create_body_measurement(
@@ -1,6 +1,7 @@
 use serde_json::Value;
 use anyhow::Result;
 
+use crate::models::CreateMilestoneRequest;
 use crate::service::MilestoneService;
 use crate::utils::ResponseBuilder;
 
@@ -24,6 +25,17 @@ impl MilestoneController {
         }
     }
 
+    pub async fn refresh_milestones(&self, user_id: &str) -> Result<Value> {
+        if user_id.is_empty() {
+            return Ok(ResponseBuilder::bad_request("User ID is required"));
+        }
+
+        match self.service.refresh_milestones(user_id).await {
+            Ok(milestones) => Ok(ResponseBuilder::ok(milestones)),
+            Err(e) => Ok(ResponseBuilder::internal_server_error(&format!("Failed to refresh milestones: {}", e))),
+        }
+    }
+
     pub async fn create_milestone(
         &self,
         body: &str,
@@ -36,6 +48,16 @@ impl MilestoneController {
         let description = body["description"].as_str().unwrap_or("").to_string();
         let target_value = body["targetValue"].as_f64().unwrap_or(0.0) as f32;
         let current_value = body["currentValue"].as_f64().map(|v| v as f32);
+        let start_value = body["startValue"].as_f64().map(|v| v as f32);
+        let metric = match body.get("metric").filter(|v| !v.is_null()) {
+            Some(metric) => match serde_json::from_value(metric.clone()) {
+                Ok(metric) => Some(metric),
+                Err(e) => {
+                    return Ok(ResponseBuilder::bad_request(&format!("Invalid metric: {}", e)));
+                }
+            },
+            None => None,
+        };
         let unit = body["unit"].as_str().map(|s| s.to_string());
         let target_date = body["targetDate"].as_str().map(|s| s.to_string());
 
@@ -43,16 +65,20 @@ impl MilestoneController {
             return Ok(ResponseBuilder::bad_request("User ID, milestone type, and title are required"));
         }
 
-        match self.service.create_milestone(
+        let request = CreateMilestoneRequest {
             user_id,
-            milestone_type,
             title,
             description,
             target_value,
             current_value,
+            start_value,
+            metric,
             unit,
             target_date,
-        ).await {
+            milestone_type,
+        };
+
+        match self.service.create_milestone(request).await {
             Ok(milestone) => Ok(ResponseBuilder::created(milestone)),
             Err(e) => Ok(ResponseBuilder::internal_server_error(&format!("Failed to create milestone: {}", e))),
         }
@@ -1,6 +1,9 @@
 use anyhow::Result;
 use serde_json::Value;
 
+use crate::service::progress_photo_service::{
+    InvalidImageError, PhotoComparisonError, PhotoSelector,
+};
 use crate::service::ProgressPhotoService;
 use crate::utils::ResponseBuilder;
 
@@ -44,7 +47,6 @@ impl ProgressPhotoController {
         let user_id = body["userId"].as_str().unwrap_or("").to_string();
         let image_data = body["imageData"].as_str().unwrap_or("").to_string();
         let photo_type = body["photoType"].as_str().map(|s| s.to_string());
-        let content_type = body["contentType"].as_str().map(|s| s.to_string());
         let notes = body["notes"].as_str().map(|s| s.to_string());
         let workout_session_id = body["workoutSessionId"].as_str().map(|s| s.to_string());
         let tags = body["tags"].as_array().map(|arr| {
@@ -73,7 +75,6 @@ impl ProgressPhotoController {
                 user_id,
                 image_data,
                 photo_type,
-                content_type,
                 notes,
                 workout_session_id,
                 tags,
@@ -81,6 +82,9 @@ impl ProgressPhotoController {
             .await
         {
             Ok(photo) => Ok(ResponseBuilder::created(photo)),
+            Err(e) if e.is::<InvalidImageError>() => {
+                Ok(ResponseBuilder::bad_request(&e.to_string()))
+            }
             Err(e) => Ok(ResponseBuilder::internal_server_error(&format!(
                 "Failed to upload progress photo: {}",
                 e
@@ -181,11 +185,45 @@ impl ProgressPhotoController {
             .get_progress_photo_timeline(user_id, start_date, end_date)
             .await
         {
-            Ok(timeline) => Ok(ResponseBuilder::ok(timeline)),
+            Ok(timeline) => Ok(ResponseBuilder::ok_last_modified(
+                timeline.entries,
+                timeline.last_modified,
+            )),
             Err(e) => Ok(ResponseBuilder::internal_server_error(&format!(
                 "Failed to get progress photo timeline: {}",
                 e
             ))),
         }
     }
+
+    pub async fn compare_progress_photos(
+        &self,
+        user_id: &str,
+        before: &PhotoSelector,
+        after: &PhotoSelector,
+    ) -> Result<Value> {
+        if user_id.is_empty() {
+            return Ok(ResponseBuilder::bad_request("User ID is required"));
+        }
+
+        match self
+            .service
+            .compare_progress_photos(user_id, before, after)
+            .await
+        {
+            Ok(comparison) => Ok(ResponseBuilder::ok(comparison)),
+            Err(e) => match e.downcast_ref::<PhotoComparisonError>() {
+                Some(PhotoComparisonError::NotFound(message)) => {
+                    Ok(ResponseBuilder::not_found(message))
+                }
+                Some(PhotoComparisonError::NotOwned) => {
+                    Ok(ResponseBuilder::forbidden(&e.to_string()))
+                }
+                None => Ok(ResponseBuilder::internal_server_error(&format!(
+                    "Failed to compare progress photos: {}",
+                    e
+                ))),
+            },
+        }
+    }
 }
@@ -1,9 +1,36 @@
 use serde_json::Value;
 use anyhow::Result;
+use auth_layer::AuthContext;
 
+use crate::models::StrengthProgress;
 use crate::service::StrengthProgressService;
 use crate::utils::ResponseBuilder;
 
+/// The fields of a create strength progress request body
+#[derive(Debug)]
+struct CreateStrengthProgress {
+    user_id: String,
+    exercise_id: String,
+    exercise_name: String,
+    current_max_weight: f32,
+    previous_max_weight: f32,
+    period: Option<String>,
+}
+
+impl CreateStrengthProgress {
+    fn from_body(body: &Value, auth_context: &AuthContext) -> Self {
+        Self {
+            // The body's userId is only honoured for admins creating on behalf of someone else
+            user_id: auth_context.resolve_owner(body["userId"].as_str()).to_string(),
+            exercise_id: body["exerciseId"].as_str().unwrap_or("").to_string(),
+            exercise_name: body["exerciseName"].as_str().unwrap_or("").to_string(),
+            current_max_weight: body["currentMaxWeight"].as_f64().unwrap_or(0.0) as f32,
+            previous_max_weight: body["previousMaxWeight"].as_f64().unwrap_or(0.0) as f32,
+            period: body["period"].as_str().map(|s| s.to_string()),
+        }
+    }
+}
+
 pub struct StrengthProgressController {
     service: StrengthProgressService,
 }
@@ -13,49 +40,80 @@ impl StrengthProgressController {
         Self { service }
     }
 
+    /// Returns the progress entries rather than a response, so the handler can serve
+    /// them as JSON or CSV
     pub async fn get_strength_progress(
         &self,
         user_id: &str,
         start_date: Option<&str>,
         end_date: Option<&str>,
-    ) -> Result<Value> {
-        if user_id.is_empty() {
-            return Ok(ResponseBuilder::bad_request("User ID is required"));
-        }
-
-        match self.service.get_strength_progress(user_id, start_date, end_date).await {
-            Ok(progress) => Ok(ResponseBuilder::ok(progress)),
-            Err(e) => Ok(ResponseBuilder::internal_server_error(&format!("Failed to retrieve strength progress: {}", e))),
-        }
+    ) -> Result<Vec<StrengthProgress>> {
+        self.service.get_strength_progress(user_id, start_date, end_date).await
     }
 
     pub async fn create_strength_progress(
         &self,
         body: &str,
+        auth_context: &AuthContext,
     ) -> Result<Value> {
         let body: Value = serde_json::from_str(body)?;
-        
-        let user_id = body["userId"].as_str().unwrap_or("").to_string();
-        let exercise_id = body["exerciseId"].as_str().unwrap_or("").to_string();
-        let exercise_name = body["exerciseName"].as_str().unwrap_or("").to_string();
-        let current_max_weight = body["currentMaxWeight"].as_f64().unwrap_or(0.0) as f32;
-        let previous_max_weight = body["previousMaxWeight"].as_f64().unwrap_or(0.0) as f32;
-        let period = body["period"].as_str().map(|s| s.to_string());
-
-        if user_id.is_empty() || exercise_id.is_empty() || exercise_name.is_empty() {
+        let request = CreateStrengthProgress::from_body(&body, auth_context);
+
+        if request.user_id.is_empty() || request.exercise_id.is_empty() || request.exercise_name.is_empty() {
             return Ok(ResponseBuilder::bad_request("User ID, exercise ID, and exercise name are required"));
         }
 
         match self.service.create_strength_progress(
-            user_id,
-            exercise_id,
-            exercise_name,
-            current_max_weight,
-            previous_max_weight,
-            period,
+            request.user_id,
+            request.exercise_id,
+            request.exercise_name,
+            request.current_max_weight,
+            request.previous_max_weight,
+            request.period,
         ).await {
             Ok(progress) => Ok(ResponseBuilder::created(progress)),
             Err(e) => Ok(ResponseBuilder::internal_server_error(&format!("Failed to create strength progress: {}", e))),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn auth_context(user_id: &str, role: &str) -> AuthContext {
+        AuthContext {
+            user_id: user_id.to_string(),
+            email: format!("{}@example.com", user_id),
+            roles: vec![role.to_string()],
+            permissions: vec![],
+            exp: 0,
+            iat: 0,
+        }
+    }
+
+    fn body(user_id: &str) -> Value {
+        json!({
+            "userId": user_id,
+            "exerciseId": "bench-press",
+            "exerciseName": "Bench Press",
+            "currentMaxWeight": 100.0,
+            "previousMaxWeight": 90.0
+        })
+    }
+
+    #[test]
+    fn test_create_ignores_another_user_id_from_a_regular_user() {
+        let request = CreateStrengthProgress::from_body(&body("someone-else"), &auth_context("user-1", "user"));
+        assert_eq!(request.user_id, "user-1");
+        assert_eq!(request.exercise_id, "bench-press");
+        assert_eq!(request.current_max_weight, 100.0);
+    }
+
+    #[test]
+    fn test_create_lets_an_admin_target_another_user() {
+        let request = CreateStrengthProgress::from_body(&body("user-1"), &auth_context("admin-1", "admin"));
+        assert_eq!(request.user_id, "user-1");
+    }
+}
@@ -2,6 +2,7 @@ use auth_layer::AuthContext;
 use lambda_router::{Context, Request, Response, RouterError};
 use tracing::error;
 
+use crate::service::progress_photo_service::PhotoSelector;
 use crate::{
     ACHIEVEMENT_CONTROLLER, BODY_MEASUREMENT_CONTROLLER, MILESTONE_CONTROLLER,
     PERFORMANCE_TREND_CONTROLLER, PROGRESS_CHART_CONTROLLER, PROGRESS_PHOTO_CONTROLLER,
@@ -38,6 +39,9 @@ fn get_user_id(req: &Request, ctx: &Context) -> Result<String, RouterError> {
 
 pub async fn get_strength_progress(req: Request, ctx: Context) -> Result<Response, RouterError> {
     let user_id = get_user_id(&req, &ctx)?;
+    if user_id.is_empty() {
+        return Ok(Response::bad_request("User ID is required"));
+    }
     let start_date = req.query("startDate").map(|s| s.to_string());
     let end_date = req.query("endDate").map(|s| s.to_string());
 
@@ -49,7 +53,8 @@ pub async fn get_strength_progress(req: Request, ctx: Context) -> Result<Respons
         .get_strength_progress(&user_id, start_date.as_deref(), end_date.as_deref())
         .await
     {
-        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        // JSON by default; CSV for exports (`Accept: text/csv` or `?format=csv`)
+        Ok(progress) => Ok(Response::negotiated(&req, &progress).with_etag()),
         Err(e) => {
             error!("Error in get_strength_progress handler: {}", e);
             Ok(Response::internal_error("Failed to process request"))
@@ -59,15 +64,16 @@ pub async fn get_strength_progress(req: Request, ctx: Context) -> Result<Respons
 
 pub async fn create_strength_progress(
     req: Request,
-    _ctx: Context,
+    ctx: Context,
 ) -> Result<Response, RouterError> {
+    let auth_context = get_auth_context(&ctx);
     let body = req.body().ok_or("Missing request body")?;
 
     let controller = STRENGTH_PROGRESS_CONTROLLER
         .get()
         .ok_or("Controller not initialized")?;
 
-    match controller.create_strength_progress(body).await {
+    match controller.create_strength_progress(body, &auth_context).await {
         Ok(response_value) => Ok(Response::from_json_value(response_value)),
         Err(e) => {
             error!("Error in create_strength_progress handler: {}", e);
@@ -80,6 +86,9 @@ pub async fn create_strength_progress(
 
 pub async fn get_body_measurements(req: Request, ctx: Context) -> Result<Response, RouterError> {
     let user_id = get_user_id(&req, &ctx)?;
+    if user_id.is_empty() {
+        return Ok(Response::bad_request("User ID is required"));
+    }
     let start_date = req.query("startDate").map(|s| s.to_string());
     let end_date = req.query("endDate").map(|s| s.to_string());
 
@@ -91,7 +100,8 @@ pub async fn get_body_measurements(req: Request, ctx: Context) -> Result<Respons
         .get_body_measurements(&user_id, start_date.as_deref(), end_date.as_deref())
         .await
     {
-        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        // JSON by default; CSV for exports (`Accept: text/csv` or `?format=csv`)
+        Ok(measurements) => Ok(Response::negotiated(&req, &measurements).with_etag()),
         Err(e) => {
             error!("Error in get_body_measurements handler: {}", e);
             Ok(Response::internal_error("Failed to process request"))
@@ -125,7 +135,7 @@ pub async fn get_progress_charts(req: Request, ctx: Context) -> Result<Response,
         .ok_or("Controller not initialized")?;
 
     match controller.get_progress_charts(&user_id).await {
-        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Ok(response_value) => Ok(Response::from_json_value(response_value).with_etag()),
         Err(e) => {
             error!("Error in get_progress_charts handler: {}", e);
             Ok(Response::internal_error("Failed to process request"))
@@ -159,7 +169,7 @@ pub async fn get_milestones(req: Request, ctx: Context) -> Result<Response, Rout
         .ok_or("Controller not initialized")?;
 
     match controller.get_milestones(&user_id).await {
-        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Ok(response_value) => Ok(Response::from_json_value(response_value).with_etag()),
         Err(e) => {
             error!("Error in get_milestones handler: {}", e);
             Ok(Response::internal_error("Failed to process request"))
@@ -167,6 +177,22 @@ pub async fn get_milestones(req: Request, ctx: Context) -> Result<Response, Rout
     }
 }
 
+pub async fn refresh_milestones(req: Request, ctx: Context) -> Result<Response, RouterError> {
+    let user_id = get_user_id(&req, &ctx)?;
+
+    let controller = MILESTONE_CONTROLLER
+        .get()
+        .ok_or("Controller not initialized")?;
+
+    match controller.refresh_milestones(&user_id).await {
+        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Err(e) => {
+            error!("Error in refresh_milestones handler: {}", e);
+            Ok(Response::internal_error("Failed to process request"))
+        }
+    }
+}
+
 pub async fn create_milestone(req: Request, _ctx: Context) -> Result<Response, RouterError> {
     let body = req.body().ok_or("Missing request body")?;
 
@@ -193,7 +219,7 @@ pub async fn get_achievements(req: Request, ctx: Context) -> Result<Response, Ro
         .ok_or("Controller not initialized")?;
 
     match controller.get_achievements(&user_id).await {
-        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Ok(response_value) => Ok(Response::from_json_value(response_value).with_etag()),
         Err(e) => {
             error!("Error in get_achievements handler: {}", e);
             Ok(Response::internal_error("Failed to process request"))
@@ -232,7 +258,7 @@ pub async fn get_performance_trends(req: Request, ctx: Context) -> Result<Respon
         .get_performance_trends(&user_id, start_date.as_deref(), end_date.as_deref())
         .await
     {
-        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Ok(response_value) => Ok(Response::from_json_value(response_value).with_etag()),
         Err(e) => {
             error!("Error in get_performance_trends handler: {}", e);
             Ok(Response::internal_error("Failed to process request"))
@@ -254,7 +280,7 @@ pub async fn get_workout_analytics(req: Request, ctx: Context) -> Result<Respons
         .get_workout_analytics(&user_id, period.as_deref())
         .await
     {
-        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Ok(response_value) => Ok(Response::from_json_value(response_value).with_etag()),
         Err(e) => {
             error!("Error in get_workout_analytics handler: {}", e);
             Ok(Response::internal_error("Failed to process request"))
@@ -274,7 +300,7 @@ pub async fn get_workout_insights(req: Request, ctx: Context) -> Result<Response
         .get_workout_insights(&user_id, period.as_deref())
         .await
     {
-        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Ok(response_value) => Ok(Response::from_json_value(response_value).with_etag()),
         Err(e) => {
             error!("Error in get_workout_insights handler: {}", e);
             Ok(Response::internal_error("Failed to process request"))
@@ -305,7 +331,7 @@ pub async fn get_progress_photos(req: Request, ctx: Context) -> Result<Response,
         )
         .await
     {
-        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Ok(response_value) => Ok(Response::from_json_value(response_value).with_etag()),
         Err(e) => {
             error!("Error in get_progress_photos handler: {}", e);
             Ok(Response::internal_error("Failed to process request"))
@@ -437,7 +463,7 @@ pub async fn get_progress_photo_analytics(
         .get_progress_photo_analytics(&user_id, Some(&start_date), Some(&end_date))
         .await
     {
-        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Ok(response_value) => Ok(Response::from_json_value(response_value).with_etag()),
         Err(e) => {
             error!("Error in get_progress_photo_analytics handler: {}", e);
             Ok(Response::internal_error("Failed to process request"))
@@ -474,7 +500,14 @@ pub async fn get_progress_photo_timeline(
         .get_progress_photo_timeline(&user_id, Some(&start_date), Some(&end_date))
         .await
     {
-        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Ok(response_value) => {
+            let response = Response::from_json_value(response_value).with_etag();
+            if not_modified_since(&req, &response) {
+                Ok(response.into_not_modified())
+            } else {
+                Ok(response)
+            }
+        }
         Err(e) => {
             error!("Error in get_progress_photo_timeline handler: {}", e);
             Ok(Response::internal_error("Failed to process request"))
@@ -482,6 +515,82 @@ pub async fn get_progress_photo_timeline(
     }
 }
 
+// Whether the client's `If-Modified-Since` covers the response's `Last-Modified`.
+// `If-None-Match` takes precedence when both are sent, and the router answers that
+fn not_modified_since(req: &Request, response: &Response) -> bool {
+    if response.status_code != 200 || req.header("If-None-Match").is_some() {
+        return false;
+    }
+    let parse = |value: &str| chrono::DateTime::parse_from_rfc2822(value.trim()).ok();
+    let since = req
+        .header("If-Modified-Since")
+        .and_then(|value| parse(value));
+    let last_modified = response
+        .headers
+        .get("Last-Modified")
+        .and_then(|value| parse(value));
+
+    // Both are whole seconds, as HTTP dates are
+    matches!((since, last_modified), (Some(since), Some(last_modified)) if last_modified <= since)
+}
+
+pub async fn compare_progress_photos(req: Request, ctx: Context) -> Result<Response, RouterError> {
+    // Only the caller's own photos can be compared, so ignore any userId in the path
+    let auth_context = get_auth_context(&ctx);
+
+    let before = match photo_selector(&req, "before", "beforeDate") {
+        Ok(Some(selector)) => selector,
+        Ok(None) => {
+            return Ok(Response::bad_request(
+                "Either before or beforeDate is required",
+            ))
+        }
+        Err(message) => return Ok(Response::bad_request(&message)),
+    };
+    let after = match photo_selector(&req, "after", "afterDate") {
+        Ok(Some(selector)) => selector,
+        Ok(None) => {
+            return Ok(Response::bad_request(
+                "Either after or afterDate is required",
+            ))
+        }
+        Err(message) => return Ok(Response::bad_request(&message)),
+    };
+
+    let controller = PROGRESS_PHOTO_CONTROLLER
+        .get()
+        .ok_or("Controller not initialized")?;
+
+    match controller
+        .compare_progress_photos(&auth_context.user_id, &before, &after)
+        .await
+    {
+        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Err(e) => {
+            error!("Error in compare_progress_photos handler: {}", e);
+            Ok(Response::internal_error("Failed to process request"))
+        }
+    }
+}
+
+// Helper function to read one side of a photo comparison: a photo id, or a
+// YYYY-MM-DD date picking the first photo taken that day
+fn photo_selector(
+    req: &Request,
+    id_param: &str,
+    date_param: &str,
+) -> Result<Option<PhotoSelector>, String> {
+    if let Some(photo_id) = req.query(id_param).filter(|s| !s.is_empty()) {
+        return Ok(Some(PhotoSelector::Id(photo_id.to_string())));
+    }
+    match req.query(date_param) {
+        Some(date) => chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map(|date| Some(PhotoSelector::Date(date)))
+            .map_err(|_| format!("{} must be a date in YYYY-MM-DD format", date_param)),
+        None => Ok(None),
+    }
+}
+
 // Helper function to convert time range to dates
 fn time_range_to_dates(time_range: &str) -> (String, String) {
     let now = chrono::Utc::now();
@@ -9,14 +9,20 @@ use async_trait::async_trait;
 use aws_config::meta::region::RegionProviderChain;
 use aws_sdk_dynamodb::Client as DynamoDbClient;
 use aws_sdk_s3::Client as S3Client;
-use lambda_router::{handler, Context, Middleware, Next, Request, Response, Router};
+use lambda_router::{
+    handler, perms, time_phase, Context, Middleware, Next, Request, Response, Router,
+};
 use lambda_runtime::service_fn;
 use lambda_runtime::Error as LambdaError;
 use once_cell::sync::{Lazy, OnceCell};
+use security_middleware::SecurityMiddleware;
+use service_config::ServiceConfig;
 use std::sync::Arc;
 use tracing::{error, info};
 
-use auth_layer::{AuthLayer, LambdaEvent as AuthLambdaEvent};
+use auth_layer::{
+    build_request_context, with_token_expiry_header, AuthLayer, LambdaEvent as AuthLambdaEvent,
+};
 use controller::{
     AchievementController, BodyMeasurementController, MilestoneController,
     PerformanceTrendController, ProgressChartController, ProgressPhotoController,
@@ -46,6 +52,7 @@ use handlers::{
     // Strength progress handlers
     create_strength_progress,
     // Progress photo handlers
+    compare_progress_photos,
     delete_progress_photo,
     get_achievements,
     get_body_measurements,
@@ -60,6 +67,7 @@ use handlers::{
     // Workout analytics handlers
     get_workout_analytics,
     get_workout_insights,
+    refresh_milestones,
     update_progress_photo,
     upload_progress_photo,
 };
@@ -95,6 +103,9 @@ async fn main() -> Result<(), LambdaError> {
 
     info!("Starting Analytics Service initialization...");
 
+    // Fail fast on missing configuration, before anything is initialized
+    let service_config = ServiceConfig::from_env()?;
+
     // Initialize global clients
     info!("Initializing AWS clients...");
     if DYNAMODB_CLIENT.get().is_none() || S3_CLIENT.get().is_none() {
@@ -103,7 +114,12 @@ async fn main() -> Result<(), LambdaError> {
             .region(region_provider)
             .load()
             .await;
-        let _ = DYNAMODB_CLIENT.set(Arc::new(DynamoDbClient::new(&config)));
+        let mut dynamodb_config = aws_sdk_dynamodb::config::Builder::from(&config);
+        if let Some(endpoint_url) = &service_config.dynamodb_endpoint_url {
+            info!("Using DynamoDB endpoint {}", endpoint_url);
+            dynamodb_config = dynamodb_config.endpoint_url(endpoint_url);
+        }
+        let _ = DYNAMODB_CLIENT.set(Arc::new(DynamoDbClient::from_conf(dynamodb_config.build())));
         let _ = S3_CLIENT.set(Arc::new(S3Client::new(&config)));
         info!("AWS clients initialized successfully");
     }
@@ -114,13 +130,19 @@ async fn main() -> Result<(), LambdaError> {
 
     // Initialize controllers once
     info!("Initializing controllers...");
-    init_controllers();
+    init_controllers(&service_config);
     info!("Controllers initialized successfully");
 
     // Create router
     info!("Creating router...");
     let mut router = Router::new();
 
+    // Write out auth metrics still buffered when the container shuts down
+    router.on_shutdown(|| async { AUTH_LAYER.flush_metrics() });
+
+    // Reject blocked IPs, injection payloads and oversized bodies before authenticating
+    router.use_middleware(SecurityMiddleware::new());
+
     // Add authentication middleware
     router.use_middleware(AuthMiddleware);
 
@@ -157,7 +179,11 @@ async fn main() -> Result<(), LambdaError> {
         "/api/analytics/charts/:userId",
         handler!(get_progress_charts),
     );
-    router.post("/api/analytics/charts", handler!(create_progress_chart));
+    router.post_guarded(
+        "/api/analytics/charts",
+        perms!["write:analytics"],
+        handler!(create_progress_chart),
+    );
     router.get("/api/analytics/me/charts", handler!(get_progress_charts));
 
     // Milestone routes
@@ -167,13 +193,25 @@ async fn main() -> Result<(), LambdaError> {
     );
     router.post("/api/analytics/milestones", handler!(create_milestone));
     router.get("/api/analytics/me/milestones", handler!(get_milestones));
+    router.post(
+        "/api/analytics/milestones/:userId/refresh",
+        handler!(refresh_milestones),
+    );
+    router.post(
+        "/api/analytics/me/milestones/refresh",
+        handler!(refresh_milestones),
+    );
 
     // Achievement routes
     router.get(
         "/api/analytics/achievements/:userId",
         handler!(get_achievements),
     );
-    router.post("/api/analytics/achievements", handler!(create_achievement));
+    router.post_guarded(
+        "/api/analytics/achievements",
+        perms!["write:analytics"],
+        handler!(create_achievement),
+    );
     router.get("/api/analytics/me/achievements", handler!(get_achievements));
 
     // Performance Trend routes
@@ -249,6 +287,10 @@ async fn main() -> Result<(), LambdaError> {
         "/api/analytics/me/progress-photos/timeline",
         handler!(get_progress_photo_timeline),
     );
+    router.get(
+        "/api/analytics/me/progress-photos/compare",
+        handler!(compare_progress_photos),
+    );
 
     info!("Analytics Service initialized successfully");
     info!("Starting Lambda runtime...");
@@ -263,8 +305,8 @@ async fn main() -> Result<(), LambdaError> {
     result
 }
 
-fn init_controllers() {
-    let table_name = std::env::var("TABLE_NAME").unwrap_or_else(|_| "gymcoach-ai-main".to_string());
+fn init_controllers(config: &ServiceConfig) {
+    let table_name = &config.table_name;
     let bucket_name = std::env::var("PROGRESS_PHOTOS_BUCKET")
         .unwrap_or_else(|_| "gymcoach-ai-progress-photos".to_string());
     let dynamodb_client = DYNAMODB_CLIENT
@@ -297,13 +339,23 @@ fn init_controllers() {
 
     // Initialize services
     let strength_progress_service = StrengthProgressService::new(strength_progress_repository);
-    let body_measurement_service = BodyMeasurementService::new(body_measurement_repository);
+    let body_measurement_service =
+        BodyMeasurementService::new(body_measurement_repository.clone());
     let progress_chart_service = ProgressChartService::new(progress_chart_repository);
-    let milestone_service = MilestoneService::new(milestone_repository);
     let achievement_service = AchievementService::new(achievement_repository);
     let performance_trend_service = PerformanceTrendService::new(performance_trend_repository);
     let workout_session_service = WorkoutSessionService::new(workout_session_repository);
-    let progress_photo_service = ProgressPhotoService::new(progress_photo_repository);
+    let milestone_service = MilestoneService::new(
+        milestone_repository,
+        workout_session_service.clone(),
+        body_measurement_service.clone(),
+    );
+    let keep_original_photos = std::env::var("KEEP_ORIGINAL_PHOTOS")
+        .map(|value| value == "true" || value == "1")
+        .unwrap_or(false);
+    let progress_photo_service =
+        ProgressPhotoService::new(progress_photo_repository, body_measurement_repository)
+            .with_keep_originals(keep_original_photos);
     let analytics_service = AnalyticsService::new(
         strength_progress_service.clone(),
         body_measurement_service.clone(),
@@ -342,19 +394,19 @@ impl Middleware for AuthMiddleware {
 
         // Convert to auth event format
         let auth_event = AuthLambdaEvent {
-            headers: Some(req.headers.clone()),
+            headers: Some(req.headers.to_hash_map()),
             request_context: req
-                .raw_event()
-                .get("requestContext")
+                .request_context()
                 .and_then(|v| serde_json::from_value(v.clone()).ok()),
             path_parameters: Some(req.path_params.clone()),
             query_string_parameters: Some(req.query_params.clone()),
             body: req.body.clone(),
+            required_permissions: req.required_permissions().to_vec(),
         };
 
         // Authenticate request
         info!("Auth middleware: Calling auth layer...");
-        let auth_result = AUTH_LAYER.authenticate(&auth_event).await.map_err(|e| {
+        let auth_result = time_phase("auth", AUTH_LAYER.authenticate(&auth_event)).await.map_err(|e| {
             error!("Auth middleware: Authentication failed: {}", e);
             format!("Auth error: {}", e)
         })?;
@@ -372,27 +424,17 @@ impl Middleware for AuthMiddleware {
         }
 
         // Add user context
-        if let Some(auth_ctx) = auth_result.context {
+        if let Some(auth_ctx) = &auth_result.context {
             info!(
                 "Auth middleware: Adding user context for user: {}",
                 auth_ctx.user_id
             );
-            match serde_json::to_value(&auth_ctx) {
-                Ok(auth_ctx_value) => {
-                    req.set_context(
-                        Context::new(req.context.request_id.clone())
-                            .with_user(auth_ctx.user_id.clone(), Some(auth_ctx.email.clone()))
-                            .with_custom("auth_context".to_string(), auth_ctx_value),
-                    );
-                }
-                Err(e) => {
-                    error!("Failed to serialize auth context: {}", e);
-                    // Continue without auth context
-                }
-            }
+            let context = build_request_context(req.context.clone(), auth_ctx);
+            req.set_context(context);
         }
 
         info!("Auth middleware: Calling next handler...");
-        next(req).await
+        let response = next(req).await?;
+        Ok(with_token_expiry_header(response, auth_result.expires_in))
     }
 }
@@ -1,4 +1,5 @@
 use chrono::Utc;
+use lambda_router::CsvRow;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -46,9 +47,14 @@ pub struct PerformanceTrend {
     pub trend_type: String,
     pub period: String,
     pub data_points: Vec<TrendDataPoint>,
-    pub slope: f32,
+    pub slope: f32, // change per day
     pub r_squared: f32,
     pub prediction: Option<f32>,
+    #[serde(default)]
+    pub direction: String, // "increasing", "decreasing", "plateau"
+    /// Set when there are too few data points for the fit to be trusted
+    #[serde(default)]
+    pub low_confidence: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -82,6 +88,36 @@ pub struct StrengthProgress {
     pub trend: String, // "increasing", "decreasing", "stable"
 }
 
+impl CsvRow for StrengthProgress {
+    fn csv_header() -> Vec<&'static str> {
+        vec![
+            "exercise_id",
+            "exercise_name",
+            "current_max_weight",
+            "previous_max_weight",
+            "weight_increase",
+            "percentage_increase",
+            "period",
+            "measurement_date",
+            "trend",
+        ]
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.exercise_id.clone(),
+            self.exercise_name.clone(),
+            self.current_max_weight.to_string(),
+            self.previous_max_weight.to_string(),
+            self.weight_increase.to_string(),
+            self.percentage_increase.to_string(),
+            self.period.clone(),
+            self.measurement_date.clone(),
+            self.trend.clone(),
+        ]
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct BodyMeasurement {
     pub id: String,
@@ -93,6 +129,23 @@ pub struct BodyMeasurement {
     pub notes: Option<String>,
 }
 
+impl CsvRow for BodyMeasurement {
+    fn csv_header() -> Vec<&'static str> {
+        vec!["id", "measurement_type", "value", "unit", "measured_at", "notes"]
+    }
+
+    fn csv_row(&self) -> Vec<String> {
+        vec![
+            self.id.clone(),
+            self.measurement_type.clone(),
+            self.value.to_string(),
+            self.unit.clone(),
+            self.measured_at.clone(),
+            self.notes.clone().unwrap_or_default(),
+        ]
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ProgressChart {
     pub chart_id: String,
@@ -272,6 +325,90 @@ pub struct Milestone {
     pub achieved: bool,
     pub achieved_at: Option<String>,
     pub metadata: Option<serde_json::Value>,
+    // Where current_value comes from; milestones without one are updated by hand
+    #[serde(default)]
+    pub metric: Option<MilestoneMetric>,
+    // Value progress is measured from, so goals can count down as well as up
+    #[serde(default)]
+    pub start_value: f32,
+}
+
+impl Milestone {
+    /// Record the latest value of the milestone's metric. Progress runs from
+    /// `start_value` to `target_value` in either direction, clamped to 0-100.
+    /// Reaching the target marks the milestone achieved; achieved milestones stay
+    /// achieved even if the value later slips back.
+    pub fn record_progress(&mut self, current_value: f32, now: &str) {
+        self.current_value = current_value;
+        let distance = self.target_value - self.start_value;
+        self.progress_percentage = if distance == 0.0 {
+            if current_value == self.target_value { 100.0 } else { 0.0 }
+        } else {
+            ((current_value - self.start_value) / distance * 100.0).clamp(0.0, 100.0)
+        };
+
+        if self.progress_percentage >= 100.0 && !self.achieved {
+            self.achieved = true;
+            self.achieved_at = Some(now.to_string());
+            self.status = "completed".to_string();
+        }
+    }
+}
+
+/// The analytics figure a milestone tracks
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MilestoneMetric {
+    /// Number of workout sessions logged
+    TotalWorkouts,
+    /// Minutes spent across all workout sessions
+    TotalWorkoutMinutes,
+    /// Heaviest weight lifted on one exercise
+    MaxWeight { exercise_id: String },
+    /// Latest body measurement of a type, e.g. "weight" or "body_fat"
+    BodyMeasurement { measurement_type: String },
+}
+
+impl MilestoneMetric {
+    /// Totals and lifts count up from zero; body measurements start wherever the
+    /// user is when the milestone is set
+    pub fn default_start_value(&self, current_value: f32) -> f32 {
+        match self {
+            Self::BodyMeasurement { .. } => current_value,
+            _ => 0.0,
+        }
+    }
+
+    /// The metric's value given the user's sessions and body measurements, or
+    /// `None` if there is nothing to measure yet
+    pub fn value(&self, sessions: &[WorkoutSession], measurements: &[BodyMeasurement]) -> Option<f32> {
+        match self {
+            Self::TotalWorkouts => Some(sessions.len() as f32),
+            Self::TotalWorkoutMinutes => Some(
+                sessions
+                    .iter()
+                    .filter_map(|session| session.duration_minutes)
+                    .sum::<i32>() as f32,
+            ),
+            Self::MaxWeight { exercise_id } => sessions
+                .iter()
+                .flat_map(|session| &session.exercises)
+                .filter(|exercise| &exercise.exercise_id == exercise_id)
+                .flat_map(|exercise| &exercise.sets)
+                .filter(|set| set.completed)
+                .filter_map(|set| set.weight)
+                .reduce(f32::max),
+            Self::BodyMeasurement { measurement_type } => measurements
+                .iter()
+                .filter(|measurement| &measurement.measurement_type == measurement_type)
+                .max_by(|a, b| a.measured_at.cmp(&b.measured_at))
+                .map(|measurement| measurement.value),
+        }
+    }
+
+    pub fn needs_sessions(&self) -> bool {
+        !matches!(self, Self::BodyMeasurement { .. })
+    }
 }
 
 // Enhanced models for comprehensive analytics
@@ -380,7 +517,10 @@ pub struct CreateMilestoneRequest {
     pub title: String,
     pub description: String,
     pub target_value: f32,
-    pub unit: String,
+    pub current_value: Option<f32>,
+    pub start_value: Option<f32>,
+    pub metric: Option<MilestoneMetric>,
+    pub unit: Option<String>,
     pub target_date: Option<String>,
     pub milestone_type: String,
 }
@@ -544,6 +684,14 @@ pub struct ProgressPhoto {
     pub photo_type: String, // 'before', 'after', 'progress', 'front', 'side', 'back'
     pub photo_url: String,
     pub s3_key: String,
+    // Downscaled copy for lists; missing on photos uploaded before thumbnails existed
+    #[serde(default)]
+    pub thumbnail_url: Option<String>,
+    #[serde(default)]
+    pub thumbnail_s3_key: Option<String>,
+    // Upload as received, EXIF included; only kept when KEEP_ORIGINAL_PHOTOS is set
+    #[serde(default)]
+    pub original_s3_key: Option<String>,
     pub taken_at: String,
     pub notes: Option<String>,
     pub workout_session_id: Option<String>,
@@ -623,9 +771,23 @@ pub struct PhotoComparison {
     pub time_span_days: u32,
     pub comparison_type: String, // 'before_after', 'progress_timeline', 'side_by_side'
     pub insights: Vec<ComparisonInsight>,
+    // Body measurements logged between the two photos, earliest vs latest per type
+    #[serde(default)]
+    pub measurement_deltas: Vec<MeasurementDelta>,
     pub created_at: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MeasurementDelta {
+    pub measurement_type: String,
+    pub unit: String,
+    pub before_value: f32,
+    pub after_value: f32,
+    pub change: f32,
+    pub before_measured_at: String,
+    pub after_measured_at: String,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct ComparisonInsight {
     pub insight_type: String,
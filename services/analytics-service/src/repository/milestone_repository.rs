@@ -49,6 +49,8 @@ impl MilestoneRepository {
                     achieved: *item.get("achieved")?.as_bool().ok()?,
                     achieved_at: item.get("achievedAt").and_then(|v| v.as_s().ok()).map(|s| s.clone()),
                     metadata: None,
+                    metric: item.get("metric").and_then(|v| v.as_s().ok()).and_then(|s| serde_json::from_str(s).ok()),
+                    start_value: item.get("startValue").and_then(|v| v.as_n().ok()).and_then(|n| n.parse().ok()).unwrap_or(0.0),
                 })
             })
             .collect();
@@ -57,6 +59,15 @@ impl MilestoneRepository {
     }
 
     pub async fn create_milestone(&self, milestone: &Milestone) -> Result<Milestone> {
+        self.put_milestone(milestone).await
+    }
+
+    /// Overwrite a stored milestone, e.g. after its progress was recomputed
+    pub async fn update_milestone(&self, milestone: &Milestone) -> Result<Milestone> {
+        self.put_milestone(milestone).await
+    }
+
+    async fn put_milestone(&self, milestone: &Milestone) -> Result<Milestone> {
         let mut item = HashMap::new();
         item.insert("PK".to_string(), AttributeValue::S("MILESTONES".to_string()));
         item.insert("SK".to_string(), AttributeValue::S(format!("MILESTONE#{}", milestone.id)));
@@ -70,6 +81,9 @@ impl MilestoneRepository {
         item.insert("progressPercentage".to_string(), AttributeValue::N(milestone.progress_percentage.to_string()));
         item.insert("achieved".to_string(), AttributeValue::Bool(milestone.achieved));
         item.insert("createdAt".to_string(), AttributeValue::S(milestone.created_at.clone()));
+        item.insert("unit".to_string(), AttributeValue::S(milestone.unit.clone()));
+        item.insert("status".to_string(), AttributeValue::S(milestone.status.clone()));
+        item.insert("startValue".to_string(), AttributeValue::N(milestone.start_value.to_string()));
         
         if let Some(achieved_at) = &milestone.achieved_at {
             item.insert("achievedAt".to_string(), AttributeValue::S(achieved_at.clone()));
@@ -77,6 +91,9 @@ impl MilestoneRepository {
         if let Some(target_date) = &milestone.target_date {
             item.insert("targetDate".to_string(), AttributeValue::S(target_date.clone()));
         }
+        if let Some(metric) = &milestone.metric {
+            item.insert("metric".to_string(), AttributeValue::S(serde_json::to_string(metric)?));
+        }
         
         self.client
             .put_item()
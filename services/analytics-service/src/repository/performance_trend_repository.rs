@@ -52,9 +52,12 @@ impl PerformanceTrendRepository {
                             })
                         }).collect())
                         .unwrap_or_default(),
-                    slope: item.get("slope")?.as_n().ok()?.parse().ok()?,
-                    r_squared: item.get("rSquared")?.as_n().ok()?.parse().ok()?,
+                    // Recomputed from the data points by the service
+                    slope: item.get("slope").and_then(|v| v.as_n().ok()).and_then(|s| s.parse().ok()).unwrap_or(0.0),
+                    r_squared: item.get("rSquared").and_then(|v| v.as_n().ok()).and_then(|s| s.parse().ok()).unwrap_or(0.0),
                     prediction: item.get("prediction").and_then(|v| v.as_n().ok()).and_then(|s| s.parse().ok()),
+                    direction: String::new(),
+                    low_confidence: true,
                 })
             })
             .collect();
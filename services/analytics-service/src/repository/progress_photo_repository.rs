@@ -2,11 +2,15 @@ use anyhow::Result;
 use aws_sdk_dynamodb::types::AttributeValue;
 use aws_sdk_dynamodb::Client as DynamoDbClient;
 use aws_sdk_s3::Client as S3Client;
-use base64::Engine;
+use chrono::{DateTime, Utc};
 use std::collections::HashMap;
 
 use crate::models::ProgressPhoto;
 
+/// Sort key, under `USER#<id>`, of the item holding when the user's photo
+/// timeline last changed other than through a photo's own `updatedAt`
+const PHOTO_TIMELINE_SK: &str = "PROGRESS_PHOTOS_MODIFIED";
+
 #[derive(Clone)]
 pub struct ProgressPhotoRepository {
     dynamodb_client: DynamoDbClient,
@@ -102,6 +106,18 @@ impl ProgressPhotoRepository {
                     photo_type: item.get("photoType")?.as_s().ok()?.clone(),
                     photo_url: item.get("photoUrl")?.as_s().ok()?.clone(),
                     s3_key: item.get("s3Key")?.as_s().ok()?.clone(),
+                    thumbnail_url: item
+                        .get("thumbnailUrl")
+                        .and_then(|v| v.as_s().ok())
+                        .cloned(),
+                    thumbnail_s3_key: item
+                        .get("thumbnailS3Key")
+                        .and_then(|v| v.as_s().ok())
+                        .cloned(),
+                    original_s3_key: item
+                        .get("originalS3Key")
+                        .and_then(|v| v.as_s().ok())
+                        .cloned(),
                     taken_at: item.get("takenAt")?.as_s().ok()?.clone(),
                     notes: item
                         .get("notes")
@@ -172,6 +188,24 @@ impl ProgressPhotoRepository {
             AttributeValue::S(photo.updated_at.clone()),
         );
 
+        if let Some(thumbnail_url) = &photo.thumbnail_url {
+            item.insert(
+                "thumbnailUrl".to_string(),
+                AttributeValue::S(thumbnail_url.clone()),
+            );
+        }
+        if let Some(thumbnail_s3_key) = &photo.thumbnail_s3_key {
+            item.insert(
+                "thumbnailS3Key".to_string(),
+                AttributeValue::S(thumbnail_s3_key.clone()),
+            );
+        }
+        if let Some(original_s3_key) = &photo.original_s3_key {
+            item.insert(
+                "originalS3Key".to_string(),
+                AttributeValue::S(original_s3_key.clone()),
+            );
+        }
         if let Some(notes) = &photo.notes {
             item.insert("notes".to_string(), AttributeValue::S(notes.clone()));
         }
@@ -211,25 +245,47 @@ impl ProgressPhotoRepository {
         user_id: &str,
         photo_id: &str,
         content_type: &str,
-        image_data: &str,
+        file_data: Vec<u8>,
     ) -> Result<String> {
         // FIXED: Use simpler key structure that matches CloudFront path
         // CloudFront is configured for /progress-photos/* path
         let key = format!("{}", photo_id);
-        let full_s3_key = format!("progress-photos/{}", key);
+        self.put_photo_object(&key, content_type, file_data).await
+    }
 
-        // Decode base64 image data
-        let file_data = base64::engine::general_purpose::STANDARD
-            .decode(image_data)
-            .map_err(|e| anyhow::anyhow!("Invalid base64 image data: {}", e))?;
+    /// Stores a thumbnail under `progress-photos/thumbnails/`, so the CloudFront
+    /// /progress-photos/* behaviour serves it too. Returns the URL and S3 key.
+    pub async fn upload_progress_photo_thumbnail_to_s3(
+        &self,
+        photo_id: &str,
+        file_data: Vec<u8>,
+    ) -> Result<(String, String)> {
+        let key = format!("thumbnails/{}.jpg", photo_id);
+        let url = self.put_photo_object(&key, "image/jpeg", file_data).await?;
+        Ok((url, format!("progress-photos/{}", key)))
+    }
 
-        self.s3_client
-            .put_object()
-            .bucket(&self.bucket_name)
-            .key(&full_s3_key)
-            .body(file_data.into())
-            .content_type(content_type)
-            .send()
+    /// Stores an upload exactly as received under `progress-photo-originals/`,
+    /// which CloudFront does not serve. Returns the S3 key.
+    pub async fn upload_progress_photo_original_to_s3(
+        &self,
+        photo_id: &str,
+        content_type: &str,
+        file_data: Vec<u8>,
+    ) -> Result<String> {
+        let key = format!("progress-photo-originals/{}", photo_id);
+        self.put_object(&key, content_type, file_data).await?;
+        Ok(key)
+    }
+
+    async fn put_photo_object(
+        &self,
+        key: &str,
+        content_type: &str,
+        file_data: Vec<u8>,
+    ) -> Result<String> {
+        let full_s3_key = format!("progress-photos/{}", key);
+        self.put_object(&full_s3_key, content_type, file_data)
             .await?;
 
         // Get CloudFront URL from environment variable, or construct permanent URL
@@ -250,6 +306,18 @@ impl ProgressPhotoRepository {
         Ok(photo_url)
     }
 
+    async fn put_object(&self, key: &str, content_type: &str, file_data: Vec<u8>) -> Result<()> {
+        self.s3_client
+            .put_object()
+            .bucket(&self.bucket_name)
+            .key(key)
+            .body(file_data.into())
+            .content_type(content_type)
+            .send()
+            .await?;
+        Ok(())
+    }
+
     pub async fn get_progress_photo_by_id(&self, photo_id: &str) -> Result<ProgressPhoto> {
         let mut attribute_values = std::collections::HashMap::new();
         attribute_values.insert(
@@ -305,6 +373,18 @@ impl ProgressPhotoRepository {
                 .as_s()
                 .map_err(|_| anyhow::anyhow!("Invalid s3Key"))?
                 .clone(),
+            thumbnail_url: item
+                .get("thumbnailUrl")
+                .and_then(|v| v.as_s().ok())
+                .cloned(),
+            thumbnail_s3_key: item
+                .get("thumbnailS3Key")
+                .and_then(|v| v.as_s().ok())
+                .cloned(),
+            original_s3_key: item
+                .get("originalS3Key")
+                .and_then(|v| v.as_s().ok())
+                .cloned(),
             taken_at: item
                 .get("takenAt")
                 .ok_or_else(|| anyhow::anyhow!("Missing takenAt"))?
@@ -387,6 +467,24 @@ impl ProgressPhotoRepository {
             AttributeValue::S(photo.updated_at.clone()),
         );
 
+        if let Some(thumbnail_url) = &photo.thumbnail_url {
+            item.insert(
+                "thumbnailUrl".to_string(),
+                AttributeValue::S(thumbnail_url.clone()),
+            );
+        }
+        if let Some(thumbnail_s3_key) = &photo.thumbnail_s3_key {
+            item.insert(
+                "thumbnailS3Key".to_string(),
+                AttributeValue::S(thumbnail_s3_key.clone()),
+            );
+        }
+        if let Some(original_s3_key) = &photo.original_s3_key {
+            item.insert(
+                "originalS3Key".to_string(),
+                AttributeValue::S(original_s3_key.clone()),
+            );
+        }
         if let Some(notes) = &photo.notes {
             item.insert("notes".to_string(), AttributeValue::S(notes.clone()));
         }
@@ -439,6 +537,44 @@ impl ProgressPhotoRepository {
         Ok(())
     }
 
+    /// Record that a user's photo timeline changed without a photo to show for
+    /// it, as after a delete, so the timeline's `Last-Modified` still moves forward
+    pub async fn touch_photo_timeline(&self, user_id: &str, at: DateTime<Utc>) -> Result<()> {
+        self.dynamodb_client
+            .put_item()
+            .table_name(&self.table_name)
+            .item("PK", AttributeValue::S(format!("USER#{}", user_id)))
+            .item("SK", AttributeValue::S(PHOTO_TIMELINE_SK.to_string()))
+            .item("modifiedAt", AttributeValue::S(at.to_rfc3339()))
+            .send()
+            .await?;
+
+        Ok(())
+    }
+
+    /// When `touch_photo_timeline` last ran for the user, if ever
+    pub async fn get_photo_timeline_modified(
+        &self,
+        user_id: &str,
+    ) -> Result<Option<DateTime<Utc>>> {
+        let result = self
+            .dynamodb_client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(format!("USER#{}", user_id)))
+            .key("SK", AttributeValue::S(PHOTO_TIMELINE_SK.to_string()))
+            .send()
+            .await?;
+
+        Ok(result
+            .item
+            .as_ref()
+            .and_then(|item| item.get("modifiedAt"))
+            .and_then(|value| value.as_s().ok())
+            .and_then(|value| DateTime::parse_from_rfc3339(value).ok())
+            .map(|value| value.with_timezone(&Utc)))
+    }
+
     pub async fn delete_progress_photo_from_s3(&self, s3_key: &str) -> Result<()> {
         // FIXED: s3_key should already be in format "progress-photos/{photo_id}"
         // If it's just the photo_id, add the prefix
@@ -448,6 +584,14 @@ impl ProgressPhotoRepository {
             format!("progress-photos/{}", s3_key)
         };
 
+        self.delete_object(&key).await
+    }
+
+    pub async fn delete_progress_photo_original_from_s3(&self, s3_key: &str) -> Result<()> {
+        self.delete_object(s3_key).await
+    }
+
+    async fn delete_object(&self, key: &str) -> Result<()> {
         tracing::info!(
             "Deleting progress photo from S3: bucket={}, key={}",
             self.bucket_name,
@@ -457,7 +601,7 @@ impl ProgressPhotoRepository {
         self.s3_client
             .delete_object()
             .bucket(&self.bucket_name)
-            .key(&key)
+            .key(key)
             .send()
             .await?;
 
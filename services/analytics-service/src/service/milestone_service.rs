@@ -2,45 +2,124 @@ use anyhow::Result;
 use chrono::Utc;
 use uuid::Uuid;
 
-use crate::models::Milestone;
+use crate::models::{BodyMeasurement, CreateMilestoneRequest, Milestone, MilestoneMetric, WorkoutSession};
 use crate::repository::MilestoneRepository;
+use crate::service::{BodyMeasurementService, WorkoutSessionService};
+
+/// Metrics are measured over the user's whole history
+const ALL_TIME_START: &str = "1970-01-01T00:00:00Z";
 
 #[derive(Clone)]
 pub struct MilestoneService {
     repository: MilestoneRepository,
+    workout_session_service: WorkoutSessionService,
+    body_measurement_service: BodyMeasurementService,
 }
 
 impl MilestoneService {
-    pub fn new(repository: MilestoneRepository) -> Self {
-        Self { repository }
+    pub fn new(
+        repository: MilestoneRepository,
+        workout_session_service: WorkoutSessionService,
+        body_measurement_service: BodyMeasurementService,
+    ) -> Self {
+        Self {
+            repository,
+            workout_session_service,
+            body_measurement_service,
+        }
     }
 
+    /// The user's milestones, with in-flight ones brought up to date from their
+    /// metric. Nothing is saved here; `refresh_milestones` persists the progress.
     pub async fn get_milestones(&self, user_id: &str) -> Result<Vec<Milestone>> {
-        self.repository.get_milestones(user_id).await
+        let mut milestones = self.repository.get_milestones(user_id).await?;
+        self.apply_progress(user_id, &mut milestones).await?;
+        Ok(milestones)
+    }
+
+    /// Bring in-flight milestones up to date and save the ones whose value
+    /// moved, so a milestone that reached its target stays achieved from then on.
+    pub async fn refresh_milestones(&self, user_id: &str) -> Result<Vec<Milestone>> {
+        let mut milestones = self.repository.get_milestones(user_id).await?;
+        for index in self.apply_progress(user_id, &mut milestones).await? {
+            self.repository.update_milestone(&milestones[index]).await?;
+        }
+        Ok(milestones)
     }
 
-    pub async fn create_milestone(
+    /// Record each in-flight milestone's current metric value, returning the
+    /// indexes of the milestones that changed
+    async fn apply_progress(
         &self,
-        user_id: String,
-        milestone_type: String,
-        title: String,
-        description: String,
-        target_value: f32,
-        current_value: Option<f32>,
-        unit: Option<String>,
-        target_date: Option<String>,
-    ) -> Result<Milestone> {
-        let current_value = current_value.unwrap_or(0.0);
-        let progress_percentage = if target_value > 0.0 {
-            (current_value / target_value) * 100.0
-        } else {
-            0.0
-        };
+        user_id: &str,
+        milestones: &mut [Milestone],
+    ) -> Result<Vec<usize>> {
+        let tracked: Vec<&MilestoneMetric> = milestones
+            .iter()
+            .filter(|milestone| !milestone.achieved)
+            .filter_map(|milestone| milestone.metric.as_ref())
+            .collect();
+        if tracked.is_empty() {
+            return Ok(Vec::new());
+        }
+        let (sessions, measurements) = self.load_metric_data(user_id, &tracked).await?;
+
+        let now = Utc::now().to_rfc3339();
+        let mut changed = Vec::new();
+        for (index, milestone) in milestones.iter_mut().enumerate() {
+            if milestone.achieved {
+                continue;
+            }
+            let Some(current_value) = milestone
+                .metric
+                .as_ref()
+                .and_then(|metric| metric.value(&sessions, &measurements))
+            else {
+                continue;
+            };
+
+            if current_value == milestone.current_value {
+                continue;
+            }
+            milestone.record_progress(current_value, &now);
+            changed.push(index);
+        }
+
+        Ok(changed)
+    }
+
+    pub async fn create_milestone(&self, request: CreateMilestoneRequest) -> Result<Milestone> {
+        let CreateMilestoneRequest {
+            user_id,
+            title,
+            description,
+            target_value,
+            current_value,
+            start_value,
+            metric,
+            unit,
+            target_date,
+            milestone_type,
+        } = request;
 
-        let achieved = progress_percentage >= 100.0;
-        let achieved_at = if achieved { Some(Utc::now().to_rfc3339()) } else { None };
+        // A tracked milestone starts from the metric's value, not the client's
+        let current_value = match &metric {
+            Some(metric) => {
+                let (sessions, measurements) =
+                    self.load_metric_data(&user_id, &[metric]).await?;
+                metric.value(&sessions, &measurements).or(current_value)
+            }
+            None => current_value,
+        }
+        .unwrap_or(0.0);
+        let start_value = start_value.unwrap_or_else(|| {
+            metric
+                .as_ref()
+                .map_or(0.0, |metric| metric.default_start_value(current_value))
+        });
+        let now = Utc::now().to_rfc3339();
 
-        let milestone = Milestone {
+        let mut milestone = Milestone {
             id: Uuid::new_v4().to_string(),
             user_id,
             milestone_type,
@@ -50,14 +129,43 @@ impl MilestoneService {
             current_value,
             unit: unit.unwrap_or_else(|| "kg".to_string()),
             target_date,
-            created_at: Utc::now().to_rfc3339(),
+            created_at: now.clone(),
             status: "active".to_string(),
-            progress_percentage,
-            achieved,
-            achieved_at,
+            progress_percentage: 0.0,
+            achieved: false,
+            achieved_at: None,
             metadata: None,
+            metric,
+            start_value,
         };
+        milestone.record_progress(current_value, &now);
 
         self.repository.create_milestone(&milestone).await
     }
+
+    /// Fetch only the data the given metrics are computed from
+    async fn load_metric_data(
+        &self,
+        user_id: &str,
+        metrics: &[&MilestoneMetric],
+    ) -> Result<(Vec<WorkoutSession>, Vec<BodyMeasurement>)> {
+        let now = Utc::now().to_rfc3339();
+
+        let sessions = if metrics.iter().any(|metric| metric.needs_sessions()) {
+            self.workout_session_service
+                .get_workout_sessions_for_analytics(user_id, Some(ALL_TIME_START), Some(&now))
+                .await?
+        } else {
+            Vec::new()
+        };
+        let measurements = if metrics.iter().any(|metric| !metric.needs_sessions()) {
+            self.body_measurement_service
+                .get_body_measurements(user_id, Some(ALL_TIME_START), Some(&now))
+                .await?
+        } else {
+            Vec::new()
+        };
+
+        Ok((sessions, measurements))
+    }
 }
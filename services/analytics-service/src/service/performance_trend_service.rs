@@ -1,8 +1,15 @@
 use anyhow::Result;
+use chrono::{DateTime, NaiveDate, TimeZone, Utc};
 
-use crate::models::PerformanceTrend;
+use crate::models::{PerformanceTrend, TrendDataPoint};
 use crate::repository::PerformanceTrendRepository;
 
+/// Fewer points than this and the fit is flagged as low confidence
+const MIN_CONFIDENT_POINTS: usize = 4;
+/// A trend whose fitted change over the whole series is smaller than this
+/// fraction of the mean value is a plateau
+const PLATEAU_THRESHOLD: f64 = 0.02;
+
 #[derive(Clone)]
 pub struct PerformanceTrendService {
     repository: PerformanceTrendRepository,
@@ -26,6 +33,176 @@ impl PerformanceTrendService {
             chrono::Utc::now().to_rfc3339()
         });
 
-        self.repository.get_performance_trends(user_id, &start, &end).await
+        let mut trends = self.repository.get_performance_trends(user_id, &start, &end).await?;
+        for trend in &mut trends {
+            let regression = TrendRegression::fit(&trend.data_points);
+            trend.slope = regression.slope as f32;
+            trend.r_squared = regression.r_squared as f32;
+            trend.direction = regression.direction.to_string();
+            trend.low_confidence = regression.low_confidence();
+        }
+
+        Ok(trends)
     }
 }
+
+/// Least-squares line through a metric's time series
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrendRegression {
+    /// Change in value per day
+    pub slope: f64,
+    pub r_squared: f64,
+    pub direction: &'static str,
+    /// Number of points with a parseable date that went into the fit
+    pub points: usize,
+}
+
+impl TrendRegression {
+    /// Fit the series using each point's actual timestamp as x, so irregularly
+    /// spaced measurements are weighted by when they were taken rather than by
+    /// their position in the list
+    pub fn fit(data_points: &[TrendDataPoint]) -> Self {
+        let samples: Vec<(DateTime<Utc>, f64)> = data_points
+            .iter()
+            .filter_map(|point| Some((parse_point_date(&point.date)?, point.value as f64)))
+            .collect();
+        let flat = Self {
+            slope: 0.0,
+            r_squared: 0.0,
+            direction: "plateau",
+            points: samples.len(),
+        };
+        let Some(first) = samples.iter().map(|(date, _)| *date).min() else {
+            return flat;
+        };
+
+        let xs: Vec<f64> = samples
+            .iter()
+            .map(|(date, _)| (*date - first).num_seconds() as f64 / 86_400.0)
+            .collect();
+        let ys: Vec<f64> = samples.iter().map(|(_, value)| *value).collect();
+        let n = samples.len() as f64;
+        let mean_x = xs.iter().sum::<f64>() / n;
+        let mean_y = ys.iter().sum::<f64>() / n;
+
+        let sxx: f64 = xs.iter().map(|x| (x - mean_x).powi(2)).sum();
+        // All points on the same instant (or a single point) have no slope
+        if sxx == 0.0 {
+            return flat;
+        }
+        let sxy: f64 = xs.iter().zip(&ys).map(|(x, y)| (x - mean_x) * (y - mean_y)).sum();
+        let slope = sxy / sxx;
+        let intercept = mean_y - slope * mean_x;
+
+        let ss_total: f64 = ys.iter().map(|y| (y - mean_y).powi(2)).sum();
+        let ss_residual: f64 = xs
+            .iter()
+            .zip(&ys)
+            .map(|(x, y)| (y - (intercept + slope * x)).powi(2))
+            .sum();
+        let r_squared = if ss_total == 0.0 { 1.0 } else { (1.0 - ss_residual / ss_total).max(0.0) };
+
+        let span = xs.iter().cloned().fold(f64::MIN, f64::max);
+        let fitted_change = slope * span;
+        let direction = if fitted_change.abs() <= PLATEAU_THRESHOLD * mean_y.abs() {
+            "plateau"
+        } else if slope > 0.0 {
+            "increasing"
+        } else {
+            "decreasing"
+        };
+
+        Self {
+            slope,
+            r_squared,
+            direction,
+            points: samples.len(),
+        }
+    }
+
+    /// Too few points went into the fit for it to be trusted
+    pub fn low_confidence(&self) -> bool {
+        self.points < MIN_CONFIDENT_POINTS
+    }
+}
+
+/// Data point dates are either RFC 3339 timestamps or plain `YYYY-MM-DD` days
+fn parse_point_date(date: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(date)
+        .map(|date| date.with_timezone(&Utc))
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                .ok()
+                .and_then(|day| day.and_hms_opt(0, 0, 0))
+                .map(|midnight| Utc.from_utc_datetime(&midnight))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn series(points: &[(&str, f32)]) -> Vec<TrendDataPoint> {
+        points
+            .iter()
+            .map(|&(date, value)| TrendDataPoint { date: date.to_string(), value, context: None })
+            .collect()
+    }
+
+    #[test]
+    fn test_fit_uses_actual_dates_for_unevenly_spaced_points() {
+        // One unit a day, measured on days 0, 1, 3 and 10
+        let regression = TrendRegression::fit(&series(&[
+            ("2024-03-01", 100.0),
+            ("2024-03-02T00:00:00Z", 101.0),
+            ("2024-03-04", 103.0),
+            ("2024-03-11", 110.0),
+        ]));
+
+        assert!((regression.slope - 1.0).abs() < 1e-9);
+        assert!((regression.r_squared - 1.0).abs() < 1e-9);
+        assert_eq!(regression.direction, "increasing");
+        assert_eq!(regression.points, 4);
+    }
+
+    #[test]
+    fn test_fit_of_a_flat_series_is_a_plateau() {
+        let regression = TrendRegression::fit(&series(&[
+            ("2024-03-01", 80.0),
+            ("2024-03-05", 80.0),
+            ("2024-03-09", 80.0),
+            ("2024-03-20", 80.0),
+        ]));
+
+        assert_eq!(regression.slope, 0.0);
+        assert_eq!(regression.r_squared, 1.0);
+        assert_eq!(regression.direction, "plateau");
+
+        // A small drift relative to the values is still a plateau
+        let drifting = TrendRegression::fit(&series(&[("2024-03-01", 100.0), ("2024-03-31", 101.0)]));
+        assert_eq!(drifting.direction, "plateau");
+    }
+
+    #[test]
+    fn test_fewer_than_four_dated_points_is_low_confidence() {
+        let three = series(&[("2024-03-01", 100.0), ("2024-03-08", 95.0), ("2024-03-15", 90.0)]);
+        let regression = TrendRegression::fit(&three);
+        assert_eq!(regression.direction, "decreasing");
+        assert!(regression.low_confidence());
+
+        // A point without a readable date doesn't count towards the fit
+        let mut four = three.clone();
+        four.push(TrendDataPoint { date: "last week".to_string(), value: 85.0, context: None });
+        assert!(TrendRegression::fit(&four).low_confidence());
+
+        four.pop();
+        four.push(TrendDataPoint { date: "2024-03-22".to_string(), value: 85.0, context: None });
+        let regression = TrendRegression::fit(&four);
+        assert_eq!(regression.points, 4);
+        assert!(!regression.low_confidence());
+
+        assert!(TrendRegression::fit(&[]).low_confidence());
+    }
+}
+
@@ -1,18 +1,112 @@
 use anyhow::Result;
-use chrono::Utc;
+use base64::Engine;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use std::io::Cursor;
 use uuid::Uuid;
 
-use crate::models::ProgressPhoto;
-use crate::repository::ProgressPhotoRepository;
+use crate::models::{BodyMeasurement, MeasurementDelta, PhotoComparison, ProgressPhoto};
+use crate::repository::{BodyMeasurementRepository, ProgressPhotoRepository};
+
+/// Longest edge, in pixels, of the thumbnails shown in photo lists
+const THUMBNAIL_SIZE: u32 = 256;
+
+/// JPEG quality for re-encoded uploads
+const JPEG_QUALITY: u8 = 90;
+
+/// A user's photo timeline and when its newest change was made
+pub struct PhotoTimeline {
+    pub entries: Vec<serde_json::Value>,
+    /// Latest `updated_at` across the photos, or the last delete when that is
+    /// later; `None` when neither is known
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+/// Latest of the photos' `updated_at` and the time the timeline was last touched
+/// by a delete; `None` when neither is known
+fn timeline_last_modified(
+    photos: &[ProgressPhoto],
+    timeline_modified: Option<DateTime<Utc>>,
+) -> Option<DateTime<Utc>> {
+    photos
+        .iter()
+        .filter_map(|photo| DateTime::parse_from_rfc3339(&photo.updated_at).ok())
+        .map(|updated_at| updated_at.with_timezone(&Utc))
+        .chain(timeline_modified)
+        .max()
+}
+
+/// The uploaded data is not an image we can read: bad base64, an unsupported
+/// format or a corrupt file. Controllers turn this into a 400.
+#[derive(Debug)]
+pub struct InvalidImageError(pub String);
+
+impl std::fmt::Display for InvalidImageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Invalid image: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidImageError {}
+
+/// Why a before/after comparison could not be built. Controllers turn
+/// `NotFound` into a 404 and `NotOwned` into a 403.
+#[derive(Debug)]
+pub enum PhotoComparisonError {
+    NotFound(String),
+    NotOwned,
+}
+
+impl std::fmt::Display for PhotoComparisonError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound(what) => write!(f, "{}", what),
+            Self::NotOwned => write!(f, "You can only compare your own progress photos"),
+        }
+    }
+}
+
+impl std::error::Error for PhotoComparisonError {}
+
+/// One side of a comparison: a specific photo, or the user's photo from a day
+pub enum PhotoSelector {
+    Id(String),
+    Date(NaiveDate),
+}
+
+/// An upload after `process_photo`: upright, without metadata, plus its thumbnail
+struct ProcessedPhoto {
+    data: Vec<u8>,
+    content_type: &'static str,
+    original_content_type: &'static str,
+    thumbnail: Vec<u8>,
+}
 
 #[derive(Clone)]
 pub struct ProgressPhotoService {
     repository: ProgressPhotoRepository,
+    body_measurement_repository: BodyMeasurementRepository,
+    keep_originals: bool,
 }
 
 impl ProgressPhotoService {
-    pub fn new(repository: ProgressPhotoRepository) -> Self {
-        Self { repository }
+    pub fn new(
+        repository: ProgressPhotoRepository,
+        body_measurement_repository: BodyMeasurementRepository,
+    ) -> Self {
+        Self {
+            repository,
+            body_measurement_repository,
+            keep_originals: false,
+        }
+    }
+
+    /// Also store the bytes exactly as uploaded, EXIF included, outside the
+    /// CloudFront path. Off by default since the EXIF may hold the user's location.
+    pub fn with_keep_originals(mut self, keep_originals: bool) -> Self {
+        self.keep_originals = keep_originals;
+        self
     }
 
     pub async fn get_progress_photos(
@@ -33,7 +127,6 @@ impl ProgressPhotoService {
         user_id: String,
         image_data: String,
         photo_type: Option<String>,
-        content_type: Option<String>,
         notes: Option<String>,
         workout_session_id: Option<String>,
         tags: Option<Vec<String>>,
@@ -42,15 +135,45 @@ impl ProgressPhotoService {
         let taken_at = Utc::now().to_rfc3339();
         let created_at = taken_at.clone();
 
+        let file_data = base64::engine::general_purpose::STANDARD
+            .decode(image_data)
+            .map_err(|e| InvalidImageError(format!("invalid base64 data: {}", e)))?;
+
+        // Decoding a full-size photo is CPU bound, keep it off the async workers
+        let (file_data, processed) = tokio::task::spawn_blocking(move || {
+            let processed = process_photo(&file_data);
+            (file_data, processed)
+        })
+        .await?;
+        let processed = processed?;
+
+        let original_s3_key = if self.keep_originals {
+            Some(
+                self.repository
+                    .upload_progress_photo_original_to_s3(
+                        &photo_id,
+                        processed.original_content_type,
+                        file_data,
+                    )
+                    .await?,
+            )
+        } else {
+            None
+        };
+
         let photo_url = self
             .repository
             .upload_progress_photo_to_s3(
                 &user_id,
                 &photo_id,
-                &content_type.unwrap_or_else(|| "image/jpeg".to_string()),
-                &image_data,
+                processed.content_type,
+                processed.data,
             )
             .await?;
+        let (thumbnail_url, thumbnail_s3_key) = self
+            .repository
+            .upload_progress_photo_thumbnail_to_s3(&photo_id, processed.thumbnail)
+            .await?;
 
         // FIXED: Store correct S3 key format matching the upload path
         let progress_photo = ProgressPhoto {
@@ -60,6 +183,9 @@ impl ProgressPhotoService {
             photo_type: photo_type.unwrap_or_else(|| "progress".to_string()),
             photo_url: photo_url.clone(),
             s3_key: format!("progress-photos/{}", photo_id), // Match the actual S3 key structure
+            thumbnail_url: Some(thumbnail_url),
+            thumbnail_s3_key: Some(thumbnail_s3_key),
+            original_s3_key,
             taken_at: taken_at.clone(),
             notes,
             created_at: created_at.clone(),
@@ -104,9 +230,24 @@ impl ProgressPhotoService {
         self.repository
             .delete_progress_photo_from_s3(&photo.s3_key)
             .await?;
+        if let Some(thumbnail_s3_key) = &photo.thumbnail_s3_key {
+            self.repository
+                .delete_progress_photo_from_s3(thumbnail_s3_key)
+                .await?;
+        }
+        if let Some(original_s3_key) = &photo.original_s3_key {
+            self.repository
+                .delete_progress_photo_original_from_s3(original_s3_key)
+                .await?;
+        }
 
         // Delete from database
-        self.repository.delete_progress_photo(photo_id).await
+        self.repository.delete_progress_photo(photo_id).await?;
+
+        // The photo is gone, so no remaining `updatedAt` records the change
+        self.repository
+            .touch_photo_timeline(&photo.user_id, Utc::now())
+            .await
     }
 
     pub async fn get_progress_photo_analytics(
@@ -156,18 +297,24 @@ impl ProgressPhotoService {
         user_id: &str,
         start_date: Option<&str>,
         end_date: Option<&str>,
-    ) -> Result<Vec<serde_json::Value>> {
+    ) -> Result<PhotoTimeline> {
         let photos = self
             .repository
             .get_progress_photos(user_id, None, start_date, end_date, None)
             .await?;
+        let timeline_modified = self.repository.get_photo_timeline_modified(user_id).await?;
 
-        let timeline: Vec<serde_json::Value> = photos
+        let last_modified = timeline_last_modified(&photos, timeline_modified);
+
+        let entries: Vec<serde_json::Value> = photos
             .into_iter()
             .map(|photo| {
+                // Lists show thumbnails; the full image is for the detail view.
+                // Older photos have no thumbnail and fall back to the original.
                 serde_json::json!({
                     "id": photo.id,
                     "photo_type": photo.photo_type,
+                    "thumbnail_url": photo.thumbnail_url.as_ref().unwrap_or(&photo.photo_url),
                     "photo_url": photo.photo_url,
                     "taken_at": photo.taken_at,
                     "notes": photo.notes,
@@ -177,6 +324,229 @@ impl ProgressPhotoService {
             })
             .collect();
 
-        Ok(timeline)
+        Ok(PhotoTimeline {
+            entries,
+            last_modified,
+        })
+    }
+
+    /// Builds a before/after view of two of the user's photos: both photos,
+    /// the days between them and how their body measurements moved meanwhile.
+    /// The earlier photo is always `photos[0]`, whichever order they were given in.
+    pub async fn compare_progress_photos(
+        &self,
+        user_id: &str,
+        first: &PhotoSelector,
+        second: &PhotoSelector,
+    ) -> Result<PhotoComparison> {
+        let first = self.resolve_photo(user_id, first).await?;
+        let second = self.resolve_photo(user_id, second).await?;
+        let (before, after) = if first.taken_at <= second.taken_at {
+            (first, second)
+        } else {
+            (second, first)
+        };
+
+        let before_at = DateTime::parse_from_rfc3339(&before.taken_at)?;
+        let after_at = DateTime::parse_from_rfc3339(&after.taken_at)?;
+        let time_span_days = (after_at - before_at).num_days().max(0) as u32;
+
+        // Whole days on both ends, so a weigh-in on the morning of the first
+        // photo still counts as the starting point
+        let start = before_at.date_naive().to_string();
+        let end = (after_at.date_naive() + Duration::days(1)).to_string();
+        let measurements = self
+            .body_measurement_repository
+            .get_body_measurements(user_id, &start, &end)
+            .await?;
+
+        Ok(PhotoComparison {
+            comparison_id: format!("{}_{}", before.id, after.id),
+            time_span_days,
+            comparison_type: "before_after".to_string(),
+            insights: Vec::new(),
+            measurement_deltas: measurement_deltas(&measurements),
+            photos: vec![before, after],
+            created_at: Utc::now().to_rfc3339(),
+        })
+    }
+
+    async fn resolve_photo(
+        &self,
+        user_id: &str,
+        selector: &PhotoSelector,
+    ) -> Result<ProgressPhoto> {
+        match selector {
+            PhotoSelector::Id(photo_id) => {
+                let photo = self
+                    .repository
+                    .get_progress_photo_by_id(photo_id)
+                    .await
+                    .map_err(|_| {
+                        PhotoComparisonError::NotFound(format!(
+                            "Progress photo {} not found",
+                            photo_id
+                        ))
+                    })?;
+                if photo.user_id != user_id {
+                    return Err(PhotoComparisonError::NotOwned.into());
+                }
+                Ok(photo)
+            }
+            PhotoSelector::Date(date) => {
+                // takenAt is an RFC 3339 string, so the next day's bare date
+                // sorts after every timestamp of this one
+                let start = date.to_string();
+                let end = (*date + Duration::days(1)).to_string();
+                self.repository
+                    .get_progress_photos(user_id, None, Some(&start), Some(&end), None)
+                    .await?
+                    .into_iter()
+                    .min_by(|a, b| a.taken_at.cmp(&b.taken_at))
+                    .ok_or_else(|| {
+                        PhotoComparisonError::NotFound(format!(
+                            "No progress photo taken on {}",
+                            date
+                        ))
+                        .into()
+                    })
+            }
+        }
+    }
+}
+
+/// Pairs each measurement type's earliest reading with its latest one. Types
+/// measured only once, or logged in different units, have no meaningful delta.
+fn measurement_deltas(measurements: &[BodyMeasurement]) -> Vec<MeasurementDelta> {
+    let mut by_type: std::collections::BTreeMap<&str, Vec<&BodyMeasurement>> =
+        std::collections::BTreeMap::new();
+    for measurement in measurements {
+        by_type
+            .entry(measurement.measurement_type.as_str())
+            .or_default()
+            .push(measurement);
+    }
+
+    by_type
+        .into_values()
+        .filter_map(|mut readings| {
+            readings.sort_by(|a, b| a.measured_at.cmp(&b.measured_at));
+            let first = *readings.first()?;
+            let last = *readings.last()?;
+            if readings.len() < 2 || first.unit != last.unit {
+                return None;
+            }
+            Some(MeasurementDelta {
+                measurement_type: first.measurement_type.clone(),
+                unit: first.unit.clone(),
+                before_value: first.value,
+                after_value: last.value,
+                change: last.value - first.value,
+                before_measured_at: first.measured_at.clone(),
+                after_measured_at: last.measured_at.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Decodes the upload, turns it upright according to its EXIF orientation and
+/// re-encodes it. The encoders only write pixels, so EXIF (GPS position, camera,
+/// timestamps) is dropped along the way. PNGs stay PNG to keep transparency;
+/// everything else becomes a JPEG. The thumbnail is a JPEG no larger than
+/// `THUMBNAIL_SIZE` on either side.
+fn process_photo(file_data: &[u8]) -> Result<ProcessedPhoto> {
+    let invalid = |e: image::ImageError| InvalidImageError(e.to_string());
+
+    let reader = ImageReader::new(Cursor::new(file_data)).with_guessed_format()?;
+    let format = reader
+        .format()
+        .ok_or_else(|| InvalidImageError("unrecognised image format".to_string()))?;
+    let mut decoder = reader.into_decoder().map_err(invalid)?;
+    let orientation = decoder.orientation().map_err(invalid)?;
+    let mut image = DynamicImage::from_decoder(decoder).map_err(invalid)?;
+    image.apply_orientation(orientation);
+
+    let mut data = Vec::new();
+    let content_type = if format == ImageFormat::Png {
+        image.write_to(&mut Cursor::new(&mut data), ImageFormat::Png)?;
+        "image/png"
+    } else {
+        image
+            .to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY))?;
+        "image/jpeg"
+    };
+
+    let mut thumbnail = Vec::new();
+    image
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .into_rgb8()
+        .write_to(&mut Cursor::new(&mut thumbnail), ImageFormat::Jpeg)?;
+
+    Ok(ProcessedPhoto {
+        data,
+        content_type,
+        original_content_type: format.to_mime_type(),
+        thumbnail,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn photo(id: &str, updated_at: &str) -> ProgressPhoto {
+        ProgressPhoto {
+            id: id.to_string(),
+            user_id: "user-1".to_string(),
+            photo_type: "front".to_string(),
+            photo_url: format!("https://cdn.example.com/progress-photos/{}", id),
+            s3_key: format!("progress-photos/{}", id),
+            thumbnail_url: None,
+            thumbnail_s3_key: None,
+            original_s3_key: None,
+            taken_at: updated_at.to_string(),
+            notes: None,
+            workout_session_id: None,
+            tags: Vec::new(),
+            metadata: None,
+            created_at: updated_at.to_string(),
+            updated_at: updated_at.to_string(),
+        }
+    }
+
+    fn at(value: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(value)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_deleting_the_newest_photo_moves_last_modified_forward() {
+        let older = photo("older", "2026-03-01T08:00:00Z");
+        let newest = photo("newest", "2026-03-05T08:00:00Z");
+
+        // What a polling client holds as `If-Modified-Since`
+        let seen = timeline_last_modified(&[older.clone(), newest], None).unwrap();
+        assert_eq!(seen, at("2026-03-05T08:00:00Z"));
+
+        // Without the delete marker the timeline would look older than before
+        assert!(timeline_last_modified(std::slice::from_ref(&older), None).unwrap() < seen);
+
+        let deleted_at = at("2026-03-06T10:30:00Z");
+        let after_delete = timeline_last_modified(&[older], Some(deleted_at)).unwrap();
+        assert_eq!(after_delete, deleted_at);
+        assert!(after_delete > seen);
+    }
+
+    #[test]
+    fn test_last_modified_of_an_empty_timeline() {
+        assert_eq!(timeline_last_modified(&[], None), None);
+
+        let deleted_at = at("2026-03-06T10:30:00Z");
+        assert_eq!(
+            timeline_last_modified(&[], Some(deleted_at)),
+            Some(deleted_at)
+        );
     }
 }
@@ -1,5 +1,9 @@
+use chrono::{DateTime, Utc};
 use serde_json::{json, Value};
 
+/// `strftime` pattern for HTTP dates (RFC 9110 IMF-fixdate), always in GMT
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
 pub struct ResponseBuilder;
 
 impl ResponseBuilder {
@@ -12,6 +16,20 @@ impl ResponseBuilder {
         })
     }
 
+    /// Build a 200 OK response with a `Last-Modified` header, when known, so clients
+    /// can revalidate with `If-Modified-Since`
+    pub fn ok_last_modified<T: serde::Serialize>(
+        data: T,
+        last_modified: Option<DateTime<Utc>>,
+    ) -> Value {
+        let mut response = Self::ok(data);
+        if let Some(last_modified) = last_modified {
+            response["headers"]["Last-Modified"] =
+                Value::String(last_modified.format(HTTP_DATE_FORMAT).to_string());
+        }
+        response
+    }
+
     /// Build a 201 Created response
     pub fn created<T: serde::Serialize>(data: T) -> Value {
         json!({
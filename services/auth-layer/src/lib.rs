@@ -1,10 +1,30 @@
 use serde::{Deserialize, Serialize};
 use anyhow::{Result, anyhow};
 use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
 use chrono::Utc;
-use base64::{Engine as _, engine::general_purpose};
+use lambda_router::{Context, Response};
+use metrics_collector::MetricsCollector;
 
-#[derive(Debug, Serialize, Deserialize)]
+pub mod providers;
+
+pub use providers::{
+    ApiKeyProvider, AuthProvider, Authenticated, CognitoJwtProvider, API_KEY_HEADER, SERVICE_ROLE,
+};
+
+/// Response header telling the client how many seconds its token has left
+pub const TOKEN_EXPIRING_HEADER: &str = "X-Token-Expiring";
+/// Environment variable setting how close to expiry, in seconds, a token must be
+/// before responses carry `X-Token-Expiring`
+pub const EXPIRY_WARNING_ENV: &str = "TOKEN_EXPIRY_WARNING_SECONDS";
+const DEFAULT_EXPIRY_WARNING_SECONDS: i64 = 300;
+
+const METRICS_NAMESPACE: &str = "GymCoach/Auth";
+/// Buffered auth latency metrics are written out once this many have been recorded
+const METRICS_FLUSH_BATCH: usize = 100;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuthContext {
     pub user_id: String,
     pub email: String,
@@ -14,11 +34,63 @@ pub struct AuthContext {
     pub iat: i64,
 }
 
+impl AuthContext {
+    pub fn is_admin(&self) -> bool {
+        self.roles.iter().any(|role| role == "admin")
+    }
+
+    /// Whether this is a service principal authenticated by API key rather than a user
+    pub fn is_service(&self) -> bool {
+        self.user_id.starts_with("service:") && self.roles.iter().any(|role| role == SERVICE_ROLE)
+    }
+
+    /// Resolve who owns a resource being created. Only admins may create on behalf of
+    /// another user by naming them explicitly; for everyone else any requested user id
+    /// (from the body or path) is ignored and the authenticated user owns the item
+    pub fn resolve_owner<'a>(&'a self, requested_user_id: Option<&'a str>) -> &'a str {
+        match requested_user_id.map(str::trim) {
+            Some(requested) if self.is_admin() && !requested.is_empty() => requested,
+            _ => &self.user_id,
+        }
+    }
+}
+
+/// Populate a router `Context` from a validated `AuthContext`. Every service auth
+/// middleware goes through here so handlers see the same `user_id`, `email` and
+/// `auth_context` custom data; the request id and any other custom data are kept
+pub fn build_request_context(context: Context, auth_context: &AuthContext) -> Context {
+    let context = context.with_user(auth_context.user_id.clone(), Some(auth_context.email.clone()));
+
+    match serde_json::to_value(auth_context) {
+        Ok(auth_context_value) => context.with_custom("auth_context".to_string(), auth_context_value),
+        Err(_) => context,
+    }
+}
+
+/// Add `X-Token-Expiring` to a response when the caller's token is about to expire,
+/// so clients can refresh before a request fails with 401
+pub fn with_token_expiry_header(response: Response, expires_in: Option<i64>) -> Response {
+    match expires_in {
+        Some(seconds) => response.header(TOKEN_EXPIRING_HEADER, seconds.to_string()),
+        None => response,
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AuthResult {
     pub is_authorized: bool,
     pub context: Option<AuthContext>,
     pub error: Option<String>,
+    /// Time spent in `authenticate`, for auth latency metrics
+    #[serde(default)]
+    pub duration_ms: f64,
+    /// Whether the token's claims were served from the memoization cache
+    #[serde(default)]
+    pub cache_hit: bool,
+    /// Seconds until the token expires, set only when that falls inside the expiry
+    /// warning window
+    #[serde(default)]
+    pub expires_in: Option<i64>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -28,6 +100,9 @@ pub struct LambdaEvent {
     pub path_parameters: Option<HashMap<String, String>>,
     pub query_string_parameters: Option<HashMap<String, String>>,
     pub body: Option<String>,
+    /// Permissions the matched route requires, from `lambda_router::Request::required_permissions`
+    #[serde(default)]
+    pub required_permissions: Vec<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -42,128 +117,123 @@ pub struct Authorizer {
 }
 
 pub struct AuthLayer {
-    jwt_secret: String,
-    cognito_region: String,
-    cognito_user_pool_id: String,
+    providers: Vec<Box<dyn AuthProvider>>,
+    expiry_warning_seconds: i64,
+    /// `AuthLatency` per authenticated request, emitted as EMF in batches
+    metrics: Mutex<MetricsCollector>,
 }
 
 impl AuthLayer {
+    /// Cognito JWT authentication configured from the environment
     pub fn new() -> Self {
+        let expiry_warning_seconds = std::env::var(EXPIRY_WARNING_ENV)
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(DEFAULT_EXPIRY_WARNING_SECONDS);
+
         Self {
-            jwt_secret: std::env::var("JWT_SECRET").unwrap_or_else(|_| "default-secret".to_string()),
-            cognito_region: std::env::var("COGNITO_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
-            cognito_user_pool_id: std::env::var("COGNITO_USER_POOL_ID").unwrap_or_else(|_| "".to_string()),
+            providers: vec![Box::new(CognitoJwtProvider::from_env())],
+            expiry_warning_seconds,
+            metrics: Mutex::new(MetricsCollector::new(METRICS_NAMESPACE.to_string(), HashMap::new())),
         }
     }
 
+    /// Report tokens expiring within `seconds` through `AuthResult::expires_in`
+    pub fn with_expiry_warning(mut self, seconds: i64) -> Self {
+        self.expiry_warning_seconds = seconds;
+        self
+    }
+
+    /// Also accept credentials from `provider`, tried after the ones already configured
+    pub fn with_provider(mut self, provider: impl AuthProvider + 'static) -> Self {
+        self.providers.push(Box::new(provider));
+        self
+    }
+
     pub async fn authenticate(&self, event: &LambdaEvent) -> Result<AuthResult> {
-        // Extract token from Authorization header
-        let token = self.extract_token(event)?;
-        
-        // Validate JWT token
-        let claims = self.validate_jwt_token(&token)?;
-        
-        // Extract user context
-        let context = self.extract_user_context(&claims)?;
+        let started = Instant::now();
+        
+        // The first provider that finds its credentials decides who the caller is
+        let mut authenticated = None;
+        for provider in &self.providers {
+            if let Some(found) = provider.authenticate(event)? {
+                authenticated = Some(found);
+                break;
+            }
+        }
+        let Authenticated { context, cache_hit } =
+            authenticated.ok_or_else(|| anyhow!("No authorization header found"))?;
         
-        // Check if user is authorized
+        // Authorization depends on the requested resource, so it is never memoized
         let is_authorized = self.check_authorization(&context, event).await?;
+        let error = (!is_authorized).then(|| self.missing_permissions_error(&context, event));
+        let expires_in = self.expires_in(&context);
+        let duration_ms = started.elapsed().as_secs_f64() * 1000.0;
+        self.record_latency(duration_ms, cache_hit);
         
         Ok(AuthResult {
             is_authorized,
             context: Some(context),
-            error: None,
+            error,
+            duration_ms,
+            cache_hit,
+            expires_in,
         })
     }
 
-    fn extract_token(&self, event: &LambdaEvent) -> Result<String> {
-        let headers = event.headers.as_ref()
-            .ok_or_else(|| anyhow!("No headers found"))?;
-        
-        let auth_header = headers.get("authorization")
-            .or_else(|| headers.get("Authorization"))
-            .ok_or_else(|| anyhow!("No authorization header found"))?;
-        
-        if !auth_header.starts_with("Bearer ") {
-            return Err(anyhow!("Invalid authorization header format"));
-        }
-        
-        Ok(auth_header[7..].to_string())
+    /// Write the buffered auth latency metrics to stdout as EMF. Services register this
+    /// as a `Router::on_shutdown` hook so the last partial batch isn't lost
+    pub fn flush_metrics(&self) {
+        // Metrics are best-effort; a failed write only loses the batch
+        let _ = self.lock_metrics().emit_emf();
     }
 
-    fn validate_jwt_token(&self, token: &str) -> Result<HashMap<String, serde_json::Value>> {
-        // For development, we'll use a simple JWT decode without verification
-        // In production, you should fetch the public key from Cognito and verify properly
-        
-        // Split JWT into parts
-        let parts: Vec<&str> = token.split('.').collect();
-        if parts.len() != 3 {
-            return Err(anyhow!("Invalid JWT format"));
-        }
-        
-        // Decode the payload (middle part) - this is just for development
-        let payload = parts[1];
-        // Add padding if needed
-        let mut payload = payload.to_string();
-        while payload.len() % 4 != 0 {
-            payload.push('=');
+    fn record_latency(&self, duration_ms: f64, cache_hit: bool) {
+        let mut metrics = self.lock_metrics();
+        metrics.record_auth_latency(duration_ms, cache_hit);
+        if metrics.get_metrics().len() >= METRICS_FLUSH_BATCH {
+            let _ = metrics.emit_emf();
         }
-        
-        let decoded = general_purpose::STANDARD.decode(&payload)?;
-        let claims: HashMap<String, serde_json::Value> = serde_json::from_slice(&decoded)?;
-        
-        // Check if token is expired
-        if let Some(exp) = claims.get("exp") {
-            if let Some(exp_timestamp) = exp.as_i64() {
-                let now = Utc::now().timestamp();
-                if now > exp_timestamp {
-                    return Err(anyhow!("Token has expired"));
-                }
-            }
+    }
+
+    fn lock_metrics(&self) -> std::sync::MutexGuard<'_, MetricsCollector> {
+        self.metrics
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Seconds left on the token when it's within the warning window. Expired tokens
+    /// are rejected by the providers, and API key principals (`exp` 0) never expire
+    fn expires_in(&self, context: &AuthContext) -> Option<i64> {
+        if context.exp == 0 {
+            return None;
         }
-        
-        Ok(claims)
+        let remaining = context.exp - Utc::now().timestamp();
+        (0..=self.expiry_warning_seconds).contains(&remaining).then_some(remaining)
     }
 
-    fn extract_user_context(&self, claims: &HashMap<String, serde_json::Value>) -> Result<AuthContext> {
-        let user_id = claims.get("sub")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| anyhow!("No user ID found in token"))?;
-        
-        let email = claims.get("email")
-            .and_then(|v| v.as_str())
-            .ok_or_else(|| anyhow!("No email found in token"))?;
-        
-        let roles = claims.get("cognito:groups")
-            .and_then(|v| v.as_array())
-            .map(|arr| arr.iter().filter_map(|v| v.as_str()).map(|s| s.to_string()).collect())
-            .unwrap_or_default();
-        
-        let permissions = claims.get("permissions")
-            .and_then(|v| v.as_array())
-            .map(|arr| arr.iter().filter_map(|v| v.as_str()).map(|s| s.to_string()).collect())
-            .unwrap_or_default();
-        
-        let exp = claims.get("exp")
-            .and_then(|v| v.as_i64())
-            .unwrap_or(0);
-        
-        let iat = claims.get("iat")
-            .and_then(|v| v.as_i64())
-            .unwrap_or(0);
-        
-        Ok(AuthContext {
-            user_id: user_id.to_string(),
-            email: email.to_string(),
-            roles,
-            permissions,
-            exp,
-            iat,
-        })
+    fn missing_permissions_error(&self, context: &AuthContext, event: &LambdaEvent) -> String {
+        let missing: Vec<&str> = event.required_permissions.iter()
+            .filter(|perm| !context.permissions.contains(perm))
+            .map(|perm| perm.as_str())
+            .collect();
+        
+        if missing.is_empty() {
+            "Access denied".to_string()
+        } else {
+            format!("Missing required permissions: {}", missing.join(", "))
+        }
     }
 
+    /// Permissions the route declares are required of everyone but admins, whatever
+    /// their role or the resource's owner; past that, any one of role access, the
+    /// declared permissions or resource ownership grants access
     async fn check_authorization(&self, context: &AuthContext, event: &LambdaEvent) -> Result<bool> {
-        // Check if user has required permissions for the requested resource
+        if !context.is_admin() && !self.check_permission_access(context, &event.required_permissions) {
+            return Ok(false);
+        }
+
+        // Check if user has access to the requested resource
         let resource = self.extract_resource(event)?;
         let action = self.extract_action(event)?;
         
@@ -173,7 +243,7 @@ impl AuthLayer {
         }
         
         // Check permission-based access
-        if self.check_permission_access(context, &resource, &action) {
+        if self.check_permission_access(context, &event.required_permissions) {
             return Ok(true);
         }
         
@@ -212,7 +282,7 @@ impl AuthLayer {
         let admin_resources = ["/api/admin", "/api/analytics", "/api/coaching"];
         let user_resources = ["/api/user-profiles", "/api/workouts", "/api/nutrition", "/api/analytics"];
         
-        if context.roles.contains(&"admin".to_string()) {
+        if context.is_admin() {
             return true; // Admin has access to everything
         }
         
@@ -223,20 +293,9 @@ impl AuthLayer {
         false
     }
 
-    fn check_permission_access(&self, context: &AuthContext, resource: &str, action: &str) -> bool {
-        // Define permission-based access rules
-        let required_permissions = match (resource, action) {
-            (r, "GET") if r.starts_with("/api/user-profiles") => vec!["read:profile"],
-            (r, "PUT") if r.starts_with("/api/user-profiles") => vec!["write:profile"],
-            (r, "POST") if r.starts_with("/api/workouts") => vec!["write:workout"],
-            (r, "GET") if r.starts_with("/api/workouts") => vec!["read:workout"],
-            // Allow analytics reads for authenticated users via role check above; only POST requires permission
-            (r, "POST") if r.starts_with("/api/analytics") => vec!["write:analytics"],
-            (r, "GET") if r.starts_with("/api/analytics") => vec![],
-            _ => vec![],
-        };
-        
-        required_permissions.iter().all(|perm| context.permissions.contains(&perm.to_string()))
+    fn check_permission_access(&self, context: &AuthContext, required_permissions: &[String]) -> bool {
+        // Routes declare what they need (`Router::post_guarded` and friends)
+        required_permissions.iter().all(|perm| context.permissions.contains(perm))
     }
 
     fn check_resource_ownership(&self, context: &AuthContext, event: &LambdaEvent) -> bool {
@@ -254,30 +313,120 @@ impl AuthLayer {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::collections::HashMap;
 
     #[test]
-    fn test_extract_token() {
-        let mut headers = HashMap::new();
-        headers.insert("authorization".to_string(), "Bearer test-token".to_string());
+    fn test_check_role_access() {
+        let context = AuthContext {
+            user_id: "user123".to_string(),
+            email: "test@example.com".to_string(),
+            roles: vec!["user".to_string()],
+            permissions: vec![],
+            exp: 0,
+            iat: 0,
+        };
         
-        let event = LambdaEvent {
-            headers: Some(headers),
+        let auth_layer = AuthLayer::new();
+        assert!(auth_layer.check_role_access(&context, "/api/user-profiles", "GET"));
+        assert!(!auth_layer.check_role_access(&context, "/api/admin", "GET"));
+    }
+
+    #[test]
+    fn test_check_permission_access() {
+        let context = AuthContext {
+            user_id: "user123".to_string(),
+            email: "test@example.com".to_string(),
+            roles: vec![],
+            permissions: vec!["read:profile".to_string(), "write:workout".to_string()],
+            exp: 0,
+            iat: 0,
+        };
+        
+        let auth_layer = AuthLayer::new();
+        assert!(auth_layer.check_permission_access(&context, &[]));
+        assert!(auth_layer.check_permission_access(&context, &["read:profile".to_string()]));
+        assert!(auth_layer.check_permission_access(&context, &["read:profile".to_string(), "write:workout".to_string()]));
+        assert!(!auth_layer.check_permission_access(&context, &["write:analytics".to_string()]));
+    }
+
+    #[tokio::test]
+    async fn test_route_permissions_are_enforced() {
+        let auth_layer = AuthLayer::new()
+            .with_provider(ApiKeyProvider::new().with_key("reports", "reports-key"));
+        let event = |required_permissions: Vec<String>| LambdaEvent {
+            headers: Some(HashMap::from([("X-Api-Key".to_string(), "reports-key".to_string())])),
             request_context: None,
             path_parameters: None,
             query_string_parameters: None,
             body: None,
+            required_permissions,
         };
         
+        let open = auth_layer.authenticate(&event(vec![])).await.unwrap();
+        assert!(open.is_authorized);
+        assert!(open.error.is_none());
+        
+        let guarded = auth_layer.authenticate(&event(vec!["write:analytics".to_string()])).await.unwrap();
+        assert!(!guarded.is_authorized);
+        assert_eq!(guarded.error.as_deref(), Some("Missing required permissions: write:analytics"));
+    }
+
+    #[tokio::test]
+    async fn test_required_permissions_gate_owners_and_roles() {
         let auth_layer = AuthLayer::new();
-        let result = auth_layer.extract_token(&event);
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "test-token");
+        let context = |roles: &[&str], permissions: &[&str]| AuthContext {
+            user_id: "user123".to_string(),
+            email: "test@example.com".to_string(),
+            roles: roles.iter().map(|role| role.to_string()).collect(),
+            permissions: permissions.iter().map(|perm| perm.to_string()).collect(),
+            exp: 0,
+            iat: 0,
+        };
+        // The caller's own resource, which ownership alone would grant
+        let event = LambdaEvent {
+            headers: None,
+            request_context: None,
+            path_parameters: Some(HashMap::from([("userId".to_string(), "user123".to_string())])),
+            query_string_parameters: None,
+            body: None,
+            required_permissions: vec!["write:analytics".to_string()],
+        };
+        
+        let owner = context(&["user"], &[]);
+        assert!(!auth_layer.check_authorization(&owner, &event).await.unwrap());
+        let permitted = context(&["user"], &["write:analytics"]);
+        assert!(auth_layer.check_authorization(&permitted, &event).await.unwrap());
+        let admin = context(&["admin"], &[]);
+        assert!(auth_layer.check_authorization(&admin, &event).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_auth_latency_is_buffered_until_flushed() {
+        let auth_layer = AuthLayer::new()
+            .with_provider(ApiKeyProvider::new().with_key("reports", "reports-key"));
+        let event = LambdaEvent {
+            headers: Some(HashMap::from([("X-Api-Key".to_string(), "reports-key".to_string())])),
+            request_context: None,
+            path_parameters: None,
+            query_string_parameters: None,
+            body: None,
+            required_permissions: vec![],
+        };
+        
+        auth_layer.authenticate(&event).await.unwrap();
+        auth_layer.authenticate(&event).await.unwrap();
+        {
+            let metrics = auth_layer.lock_metrics();
+            assert_eq!(metrics.get_metrics().len(), 2);
+            assert_eq!(metrics.get_metrics()[0].metric_name, "AuthLatency");
+        }
+        
+        auth_layer.flush_metrics();
+        assert!(auth_layer.lock_metrics().get_metrics().is_empty());
     }
 
     #[test]
-    fn test_check_role_access() {
-        let context = AuthContext {
+    fn test_build_request_context_populates_user_and_auth_context() {
+        let auth_context = AuthContext {
             user_id: "user123".to_string(),
             email: "test@example.com".to_string(),
             roles: vec!["user".to_string()],
@@ -286,25 +435,67 @@ mod tests {
             iat: 0,
         };
         
-        let auth_layer = AuthLayer::new();
-        assert!(auth_layer.check_role_access(&context, "/api/user-profiles", "GET"));
-        assert!(!auth_layer.check_role_access(&context, "/api/admin", "GET"));
+        let context = build_request_context(Context::new("req-1".to_string()), &auth_context);
+        
+        assert_eq!(context.request_id, "req-1");
+        assert_eq!(context.user_id.as_deref(), Some("user123"));
+        assert_eq!(context.email.as_deref(), Some("test@example.com"));
+        
+        let stored: AuthContext = serde_json::from_value(context.custom["auth_context"].clone()).unwrap();
+        assert_eq!(stored.user_id, "user123");
+        assert_eq!(stored.roles, vec!["user".to_string()]);
     }
 
     #[test]
-    fn test_check_permission_access() {
-        let context = AuthContext {
+    fn test_build_request_context_keeps_existing_custom_data() {
+        let auth_context = AuthContext {
             user_id: "user123".to_string(),
             email: "test@example.com".to_string(),
             roles: vec![],
-            permissions: vec!["read:profile".to_string(), "write:workout".to_string()],
+            permissions: vec![],
             exp: 0,
             iat: 0,
         };
+        let context = Context::new("req-2".to_string())
+            .with_custom("trace_id".to_string(), serde_json::json!("abc"));
         
-        let auth_layer = AuthLayer::new();
-        assert!(auth_layer.check_permission_access(&context, "/api/user-profiles", "GET"));
-        assert!(auth_layer.check_permission_access(&context, "/api/workouts", "POST"));
-        assert!(!auth_layer.check_permission_access(&context, "/api/analytics", "GET"));
+        let context = build_request_context(context, &auth_context);
+        
+        assert_eq!(context.custom["trace_id"], serde_json::json!("abc"));
+        assert!(context.custom.contains_key("auth_context"));
+    }
+
+    #[test]
+    fn test_resolve_owner_ignores_body_user_id_for_regular_users() {
+        let context = AuthContext {
+            user_id: "user123".to_string(),
+            email: "test@example.com".to_string(),
+            roles: vec!["user".to_string()],
+            permissions: vec![],
+            exp: 0,
+            iat: 0,
+        };
+        
+        assert!(!context.is_admin());
+        assert_eq!(context.resolve_owner(Some("someone-else")), "user123");
+        assert_eq!(context.resolve_owner(Some("")), "user123");
+        assert_eq!(context.resolve_owner(None), "user123");
+    }
+
+    #[test]
+    fn test_resolve_owner_lets_admin_target_another_user() {
+        let context = AuthContext {
+            user_id: "admin1".to_string(),
+            email: "admin@example.com".to_string(),
+            roles: vec!["admin".to_string()],
+            permissions: vec![],
+            exp: 0,
+            iat: 0,
+        };
+        
+        assert!(context.is_admin());
+        assert_eq!(context.resolve_owner(Some("user123")), "user123");
+        assert_eq!(context.resolve_owner(Some("  ")), "admin1");
+        assert_eq!(context.resolve_owner(None), "admin1");
     }
 }
@@ -38,6 +38,7 @@ async fn handler(event: LambdaEvent<Value>) -> Result<Value, Error> {
         body: event.payload.get("body")
             .and_then(|v| v.as_str())
             .map(|s| s.to_string()),
+        required_permissions: Vec::new(),
     };
     
     match auth_layer.authenticate(&auth_event).await {
@@ -0,0 +1,449 @@
+use anyhow::{Result, anyhow};
+use base64::{Engine as _, engine::general_purpose};
+use chrono::Utc;
+use lambda_router::parse_bearer_token;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::{AuthContext, LambdaEvent};
+
+/// Maximum number of distinct tokens whose decoded context is memoized
+const CLAIMS_CACHE_CAPACITY: usize = 8;
+/// How long a decoded context is reused. The cache lives on the provider, which services
+/// keep in a static `AuthLayer`, so it spans invocations of a warm container: the same
+/// token seen again within this window skips validation even on a later request
+const CLAIMS_CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// Header carrying a service API key
+pub const API_KEY_HEADER: &str = "X-Api-Key";
+/// Comma-separated `service=key` pairs accepted by `ApiKeyProvider::from_env`
+const SERVICE_API_KEYS_ENV: &str = "SERVICE_API_KEYS";
+/// Role given to principals authenticated with a service API key
+pub const SERVICE_ROLE: &str = "service";
+
+/// A caller identified by a provider
+#[derive(Debug, Clone)]
+pub struct Authenticated {
+    pub context: AuthContext,
+    /// Whether the context was served from the provider's memoization cache
+    pub cache_hit: bool,
+}
+
+/// One way of identifying the caller. `AuthLayer` tries its providers in order: a
+/// provider returns `Ok(None)` when the request carries none of its credentials so the
+/// next one gets a turn, and `Err` when it carries credentials that don't check out,
+/// which fails the request without trying the rest
+pub trait AuthProvider: Send + Sync {
+    fn authenticate(&self, event: &LambdaEvent) -> Result<Option<Authenticated>>;
+}
+
+struct CachedContext {
+    context: AuthContext,
+    cached_at: Instant,
+}
+
+impl CachedContext {
+    fn is_fresh(&self) -> bool {
+        let not_expired = self.context.exp == 0 || Utc::now().timestamp() < self.context.exp;
+        not_expired && self.cached_at.elapsed() < CLAIMS_CACHE_TTL
+    }
+}
+
+/// Cognito user tokens, from `Authorization: Bearer` or a `token` query parameter
+pub struct CognitoJwtProvider {
+    jwt_secret: String,
+    cognito_region: String,
+    cognito_user_pool_id: String,
+    /// Decoded contexts by token, kept for `CLAIMS_CACHE_TTL` across invocations
+    context_cache: Mutex<HashMap<String, CachedContext>>,
+}
+
+impl CognitoJwtProvider {
+    pub fn from_env() -> Self {
+        Self {
+            jwt_secret: std::env::var("JWT_SECRET").unwrap_or_else(|_| "default-secret".to_string()),
+            cognito_region: std::env::var("COGNITO_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+            cognito_user_pool_id: std::env::var("COGNITO_USER_POOL_ID").unwrap_or_else(|_| "".to_string()),
+            context_cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn cached_context(&self, token: &str) -> Option<AuthContext> {
+        let mut cache = self.context_cache.lock().ok()?;
+        match cache.get(token) {
+            Some(entry) if entry.is_fresh() => Some(entry.context.clone()),
+            Some(_) => {
+                cache.remove(token);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn cache_context(&self, token: &str, context: &AuthContext) {
+        let Ok(mut cache) = self.context_cache.lock() else {
+            return;
+        };
+
+        cache.retain(|_, entry| entry.is_fresh());
+        if cache.len() >= CLAIMS_CACHE_CAPACITY {
+            let oldest = cache
+                .iter()
+                .min_by_key(|(_, entry)| entry.cached_at)
+                .map(|(key, _)| key.clone());
+            if let Some(key) = oldest {
+                cache.remove(&key);
+            }
+        }
+
+        cache.insert(
+            token.to_string(),
+            CachedContext {
+                context: context.clone(),
+                cached_at: Instant::now(),
+            },
+        );
+    }
+
+    /// The bearer token, or `None` when the request doesn't carry one
+    fn extract_token(&self, event: &LambdaEvent) -> Result<Option<String>> {
+        // Same rules as lambda_router::Request::bearer_token
+        let auth_header = event.headers.as_ref().and_then(|headers| {
+            headers.iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case("authorization"))
+                .map(|(_, value)| value)
+        });
+
+        match auth_header {
+            Some(value) => parse_bearer_token(value)
+                .map(|token| Some(token.to_string()))
+                .ok_or_else(|| anyhow!("Invalid authorization header format")),
+            None => Ok(event.query_string_parameters.as_ref()
+                .and_then(|params| params.get("token"))
+                .map(|token| token.trim())
+                .filter(|token| !token.is_empty())
+                .map(|token| token.to_string())),
+        }
+    }
+
+    fn validate_jwt_token(&self, token: &str) -> Result<HashMap<String, serde_json::Value>> {
+        // For development, we'll use a simple JWT decode without verification
+        // In production, you should fetch the public key from Cognito and verify properly
+
+        // Split JWT into parts
+        let parts: Vec<&str> = token.split('.').collect();
+        if parts.len() != 3 {
+            return Err(anyhow!("Invalid JWT format"));
+        }
+
+        // Decode the payload (middle part) - this is just for development
+        let payload = parts[1];
+        // Add padding if needed
+        let mut payload = payload.to_string();
+        while payload.len() % 4 != 0 {
+            payload.push('=');
+        }
+
+        let decoded = general_purpose::STANDARD.decode(&payload)?;
+        let claims: HashMap<String, serde_json::Value> = serde_json::from_slice(&decoded)?;
+
+        // Check if token is expired
+        if let Some(exp) = claims.get("exp") {
+            if let Some(exp_timestamp) = exp.as_i64() {
+                let now = Utc::now().timestamp();
+                if now > exp_timestamp {
+                    return Err(anyhow!("Token has expired"));
+                }
+            }
+        }
+
+        Ok(claims)
+    }
+
+    fn extract_user_context(&self, claims: &HashMap<String, serde_json::Value>) -> Result<AuthContext> {
+        let user_id = claims.get("sub")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow!("No user ID found in token"))?;
+
+        let email = claims.get("email")
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| anyhow!("No email found in token"))?;
+
+        let roles = claims.get("cognito:groups")
+            .and_then(|v| v.as_array())
+            .map(|arr| arr.iter().filter_map(|v| v.as_str()).map(|s| s.to_string()).collect())
+            .unwrap_or_default();
+
+        let permissions = claims.get("permissions")
+            .and_then(|v| v.as_array())
+            .map(|arr| arr.iter().filter_map(|v| v.as_str()).map(|s| s.to_string()).collect())
+            .unwrap_or_default();
+
+        let exp = claims.get("exp")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(0);
+
+        let iat = claims.get("iat")
+            .and_then(|v| v.as_i64())
+            .unwrap_or(0);
+
+        Ok(AuthContext {
+            user_id: user_id.to_string(),
+            email: email.to_string(),
+            roles,
+            permissions,
+            exp,
+            iat,
+        })
+    }
+}
+
+impl AuthProvider for CognitoJwtProvider {
+    fn authenticate(&self, event: &LambdaEvent) -> Result<Option<Authenticated>> {
+        let Some(token) = self.extract_token(event)? else {
+            return Ok(None);
+        };
+
+        // Reuse the validated context if this token was decoded in the last few seconds,
+        // in this invocation or an earlier one on the same container
+        if let Some(context) = self.cached_context(&token) {
+            return Ok(Some(Authenticated { context, cache_hit: true }));
+        }
+
+        let claims = self.validate_jwt_token(&token)?;
+        let context = self.extract_user_context(&claims)?;
+        self.cache_context(&token, &context);
+
+        Ok(Some(Authenticated { context, cache_hit: false }))
+    }
+}
+
+/// Static API keys for server-to-server callers, sent in `X-Api-Key`. Each key belongs
+/// to a named service, which is authenticated as the principal `service:<name>` with the
+/// `service` role
+#[derive(Default)]
+pub struct ApiKeyProvider {
+    /// `(service, key)` pairs
+    keys: Vec<(String, String)>,
+}
+
+impl ApiKeyProvider {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keys from `SERVICE_API_KEYS`, e.g. `notification-scheduler=<key>,other=<key>`.
+    /// Malformed pairs are skipped, so an unset variable accepts no keys
+    pub fn from_env() -> Self {
+        let configured = std::env::var(SERVICE_API_KEYS_ENV).unwrap_or_default();
+
+        configured
+            .split(',')
+            .filter_map(|pair| pair.split_once('='))
+            .fold(Self::new(), |provider, (service, key)| provider.with_key(service.trim(), key.trim()))
+    }
+
+    /// Accept `key` for `service`. Blank services or keys are ignored
+    pub fn with_key(mut self, service: impl Into<String>, key: impl Into<String>) -> Self {
+        let (service, key) = (service.into(), key.into());
+        if !service.is_empty() && !key.is_empty() {
+            self.keys.push((service, key));
+        }
+        self
+    }
+}
+
+impl AuthProvider for ApiKeyProvider {
+    fn authenticate(&self, event: &LambdaEvent) -> Result<Option<Authenticated>> {
+        let api_key = event.headers.as_ref().and_then(|headers| {
+            headers.iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(API_KEY_HEADER))
+                .map(|(_, value)| value.trim())
+        });
+        let Some(api_key) = api_key.filter(|key| !key.is_empty()) else {
+            return Ok(None);
+        };
+
+        // Compare against every key so the time taken doesn't reveal which one matched
+        let service = self.keys.iter().fold(None, |found, (service, key)| {
+            if constant_time_eq(key.as_bytes(), api_key.as_bytes()) {
+                Some(service)
+            } else {
+                found
+            }
+        });
+        let service = service.ok_or_else(|| anyhow!("Invalid API key"))?;
+
+        Ok(Some(Authenticated {
+            context: service_context(service),
+            cache_hit: false,
+        }))
+    }
+}
+
+/// Synthetic principal for a service authenticated by API key
+fn service_context(service: &str) -> AuthContext {
+    AuthContext {
+        user_id: format!("service:{}", service),
+        email: String::new(),
+        roles: vec![SERVICE_ROLE.to_string()],
+        permissions: vec![],
+        exp: 0,
+        iat: Utc::now().timestamp(),
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AuthLayer;
+
+    fn test_token(user_id: &str) -> String {
+        token_expiring_in(user_id, 3600)
+    }
+
+    fn token_expiring_in(user_id: &str, seconds: i64) -> String {
+        let payload = serde_json::json!({
+            "sub": user_id,
+            "email": format!("{}@example.com", user_id),
+            "exp": Utc::now().timestamp() + seconds,
+            "iat": Utc::now().timestamp(),
+        });
+        format!(
+            "header.{}.signature",
+            general_purpose::STANDARD.encode(payload.to_string())
+        )
+    }
+
+    fn event_with_headers(headers: &[(&str, &str)]) -> LambdaEvent {
+        LambdaEvent {
+            headers: Some(headers.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()),
+            request_context: None,
+            path_parameters: None,
+            query_string_parameters: None,
+            body: None,
+            required_permissions: Vec::new(),
+        }
+    }
+
+    fn bearer_event(token: &str) -> LambdaEvent {
+        event_with_headers(&[("authorization", &format!("Bearer {}", token))])
+    }
+
+    #[tokio::test]
+    async fn test_authenticate_memoizes_context_per_token() {
+        let auth_layer = AuthLayer::new();
+        let event = bearer_event(&test_token("user123"));
+
+        let first = auth_layer.authenticate(&event).await.unwrap();
+        assert!(!first.cache_hit);
+
+        let second = auth_layer.authenticate(&event).await.unwrap();
+        assert!(second.cache_hit);
+        assert_eq!(second.context.unwrap().user_id, "user123");
+
+        // A different token must never be served another token's context
+        let other = auth_layer.authenticate(&bearer_event(&test_token("user456"))).await.unwrap();
+        assert!(!other.cache_hit);
+        assert_eq!(other.context.unwrap().user_id, "user456");
+    }
+
+    #[tokio::test]
+    async fn test_authenticate_reports_tokens_about_to_expire() {
+        let auth_layer = AuthLayer::new().with_expiry_warning(300);
+
+        let fresh = auth_layer.authenticate(&bearer_event(&test_token("user123"))).await.unwrap();
+        assert_eq!(fresh.expires_in, None);
+
+        let expiring = auth_layer.authenticate(&bearer_event(&token_expiring_in("user123", 120))).await.unwrap();
+        assert!(matches!(expiring.expires_in, Some(seconds) if seconds > 0 && seconds <= 120));
+
+        assert!(auth_layer.authenticate(&bearer_event(&token_expiring_in("user123", -10))).await.is_err());
+    }
+
+    #[test]
+    fn test_context_cache_is_bounded() {
+        let provider = CognitoJwtProvider::from_env();
+        for i in 0..(CLAIMS_CACHE_CAPACITY + 4) {
+            let event = bearer_event(&test_token(&format!("user{}", i)));
+            provider.authenticate(&event).unwrap();
+        }
+
+        assert_eq!(provider.context_cache.lock().unwrap().len(), CLAIMS_CACHE_CAPACITY);
+    }
+
+    #[test]
+    fn test_extract_token() {
+        let event = event_with_headers(&[("authorization", "Bearer test-token")]);
+
+        let provider = CognitoJwtProvider::from_env();
+        let result = provider.extract_token(&event);
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().as_deref(), Some("test-token"));
+
+        // No credentials at all lets the next provider try
+        assert_eq!(provider.extract_token(&event_with_headers(&[])).unwrap(), None);
+        assert!(provider.extract_token(&event_with_headers(&[("Authorization", "Basic abc")])).is_err());
+    }
+
+    #[test]
+    fn test_extract_token_from_query_param() {
+        let mut query = HashMap::new();
+        query.insert("token".to_string(), "socket-token".to_string());
+
+        let event = LambdaEvent {
+            headers: Some(HashMap::new()),
+            request_context: None,
+            path_parameters: None,
+            query_string_parameters: Some(query),
+            body: None,
+            required_permissions: Vec::new(),
+        };
+
+        let provider = CognitoJwtProvider::from_env();
+        assert_eq!(provider.extract_token(&event).unwrap().as_deref(), Some("socket-token"));
+    }
+
+    #[test]
+    fn test_api_key_provider() {
+        let provider = ApiKeyProvider::new()
+            .with_key("notification-scheduler", "sched-key")
+            .with_key("reports", "reports-key")
+            .with_key("blank", "");
+
+        let found = provider.authenticate(&event_with_headers(&[("x-api-key", "reports-key")])).unwrap().unwrap();
+        assert_eq!(found.context.user_id, "service:reports");
+        assert!(found.context.is_service());
+        assert!(!found.context.is_admin());
+
+        assert!(provider.authenticate(&event_with_headers(&[("X-Api-Key", "wrong")])).is_err());
+        assert!(provider.authenticate(&event_with_headers(&[("X-Api-Key", "")])).unwrap().is_none());
+        assert!(provider.authenticate(&bearer_event("token")).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_providers_are_tried_in_order() {
+        let auth_layer = AuthLayer::new()
+            .with_provider(ApiKeyProvider::new().with_key("notification-scheduler", "sched-key"));
+
+        let service = auth_layer.authenticate(&event_with_headers(&[("X-Api-Key", "sched-key")])).await.unwrap();
+        assert_eq!(service.context.unwrap().user_id, "service:notification-scheduler");
+
+        let user = auth_layer.authenticate(&bearer_event(&test_token("user123"))).await.unwrap();
+        assert_eq!(user.context.unwrap().user_id, "user123");
+
+        // An invalid JWT fails the request rather than falling through to the API key
+        let invalid = event_with_headers(&[("Authorization", "Bearer not-a-jwt"), ("X-Api-Key", "sched-key")]);
+        assert!(auth_layer.authenticate(&invalid).await.is_err());
+
+        assert!(auth_layer.authenticate(&event_with_headers(&[])).await.is_err());
+
+        // Without the API key provider the key isn't accepted
+        assert!(AuthLayer::new().authenticate(&event_with_headers(&[("X-Api-Key", "sched-key")])).await.is_err());
+    }
+}
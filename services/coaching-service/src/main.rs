@@ -73,6 +73,7 @@ async fn handler(event: LambdaEvent<Value>) -> Result<Value, Error> {
         body: event.get("body")
             .and_then(|v| v.as_str())
             .map(|s| s.to_string()),
+        required_permissions: Vec::new(),
     };
 
     // Authenticate request
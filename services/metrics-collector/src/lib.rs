@@ -1,7 +1,12 @@
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
 use chrono::{DateTime, Utc};
 
+/// CloudWatch accepts at most this many metrics per EMF directive
+const EMF_MAX_METRICS: usize = 100;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Metric {
     pub namespace: String,
@@ -70,17 +75,40 @@ impl CustomMetric {
 
 pub struct MetricsCollector {
     namespace: String,
+    default_dimensions: HashMap<String, String>,
     metrics: Vec<CustomMetric>,
 }
 
 impl MetricsCollector {
-    pub fn new(namespace: String) -> Self {
+    /// `default_dimensions` (e.g. service, environment, region) are added to every
+    /// recorded metric that doesn't set the same dimension itself
+    pub fn new(namespace: String, default_dimensions: HashMap<String, String>) -> Self {
         Self {
             namespace,
+            default_dimensions,
             metrics: Vec::new(),
         }
     }
 
+    pub fn with_default_dimension(mut self, key: String, value: String) -> Self {
+        self.default_dimensions.insert(key, value);
+        self
+    }
+
+    pub fn default_dimensions(&self) -> &HashMap<String, String> {
+        &self.default_dimensions
+    }
+
+    fn push(&mut self, mut metric: CustomMetric) {
+        for (key, value) in &self.default_dimensions {
+            metric
+                .dimensions
+                .entry(key.clone())
+                .or_insert_with(|| value.clone());
+        }
+        self.metrics.push(metric);
+    }
+
     pub fn increment_counter(&mut self, metric_name: String, value: f64) {
         let metric = CustomMetric::new(
             self.namespace.clone(),
@@ -88,7 +116,7 @@ impl MetricsCollector {
             value,
             MetricUnit::Count,
         );
-        self.metrics.push(metric);
+        self.push(metric);
     }
 
     pub fn record_duration(&mut self, metric_name: String, duration_ms: f64) {
@@ -98,7 +126,7 @@ impl MetricsCollector {
             duration_ms,
             MetricUnit::Duration,
         );
-        self.metrics.push(metric);
+        self.push(metric);
     }
 
     pub fn record_percentage(&mut self, metric_name: String, percentage: f64) {
@@ -108,7 +136,7 @@ impl MetricsCollector {
             percentage,
             MetricUnit::Percentage,
         );
-        self.metrics.push(metric);
+        self.push(metric);
     }
 
     pub fn record_bytes(&mut self, metric_name: String, bytes: f64) {
@@ -118,7 +146,7 @@ impl MetricsCollector {
             bytes,
             MetricUnit::Bytes,
         );
-        self.metrics.push(metric);
+        self.push(metric);
     }
 
     pub fn record_requests(&mut self, metric_name: String, count: f64) {
@@ -128,7 +156,7 @@ impl MetricsCollector {
             count,
             MetricUnit::Requests,
         );
-        self.metrics.push(metric);
+        self.push(metric);
     }
 
     pub fn record_errors(&mut self, metric_name: String, count: f64) {
@@ -138,11 +166,22 @@ impl MetricsCollector {
             count,
             MetricUnit::Errors,
         );
-        self.metrics.push(metric);
+        self.push(metric);
+    }
+
+    pub fn record_auth_latency(&mut self, duration_ms: f64, cache_hit: bool) {
+        let metric = CustomMetric::new(
+            self.namespace.clone(),
+            "AuthLatency".to_string(),
+            duration_ms,
+            MetricUnit::Duration,
+        )
+        .with_dimension("CacheHit".to_string(), cache_hit.to_string());
+        self.push(metric);
     }
 
     pub fn record_custom_metric(&mut self, metric: CustomMetric) {
-        self.metrics.push(metric);
+        self.push(metric);
     }
 
     pub fn get_metrics(&self) -> &[CustomMetric] {
@@ -153,6 +192,94 @@ impl MetricsCollector {
         self.metrics.clear();
     }
 
+    /// Remove and return the recorded metrics, e.g. after publishing them
+    pub fn take_metrics(&mut self) -> Vec<CustomMetric> {
+        std::mem::take(&mut self.metrics)
+    }
+
+    /// Write the recorded metrics to stdout as CloudWatch embedded metric format
+    /// documents, one JSON object per line, and clear them. The Lambda log
+    /// pipeline extracts the metrics asynchronously, so unlike a `PutMetricData`
+    /// flush this adds no network round trip to the request
+    pub fn emit_emf(&mut self) -> std::io::Result<()> {
+        let documents = self.to_emf_documents();
+        let mut stdout = std::io::stdout().lock();
+        for document in &documents {
+            writeln!(stdout, "{}", document)?;
+        }
+        stdout.flush()?;
+        self.metrics.clear();
+        Ok(())
+    }
+
+    /// Build the EMF documents for the recorded metrics. Metrics sharing a
+    /// namespace and dimension set go in the same document, with repeated
+    /// values of a metric reported as an array
+    pub fn to_emf_documents(&self) -> Vec<Value> {
+        let mut groups: BTreeMap<(&str, BTreeMap<&str, &str>), Vec<&CustomMetric>> = BTreeMap::new();
+        for metric in &self.metrics {
+            let dimensions = metric
+                .dimensions
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str()))
+                .collect();
+            groups
+                .entry((metric.namespace.as_str(), dimensions))
+                .or_default()
+                .push(metric);
+        }
+
+        let mut documents = Vec::new();
+        for ((namespace, dimensions), metrics) in groups {
+            // Values per metric name, keeping first-recorded order
+            let mut names: Vec<(&str, &'static str)> = Vec::new();
+            let mut values: HashMap<&str, Vec<f64>> = HashMap::new();
+            for metric in &metrics {
+                let recorded = values.entry(metric.metric_name.as_str()).or_default();
+                if recorded.is_empty() {
+                    names.push((metric.metric_name.as_str(), metric.unit.as_str()));
+                }
+                recorded.push(metric.value);
+            }
+            let timestamp = metrics
+                .iter()
+                .map(|metric| metric.timestamp.timestamp_millis())
+                .min()
+                .unwrap_or_else(|| Utc::now().timestamp_millis());
+
+            for chunk in names.chunks(EMF_MAX_METRICS) {
+                let definitions: Vec<Value> = chunk
+                    .iter()
+                    .map(|(name, unit)| json!({ "Name": name, "Unit": unit }))
+                    .collect();
+
+                let mut document = Map::new();
+                document.insert(
+                    "_aws".to_string(),
+                    json!({
+                        "Timestamp": timestamp,
+                        "CloudWatchMetrics": [{
+                            "Namespace": namespace,
+                            "Dimensions": [dimensions.keys().collect::<Vec<_>>()],
+                            "Metrics": definitions,
+                        }],
+                    }),
+                );
+                for (key, value) in &dimensions {
+                    document.insert(key.to_string(), json!(value));
+                }
+                for (name, _) in chunk {
+                    let recorded = &values[name];
+                    let value = if recorded.len() == 1 { json!(recorded[0]) } else { json!(recorded) };
+                    document.insert(name.to_string(), value);
+                }
+                documents.push(Value::Object(document));
+            }
+        }
+
+        documents
+    }
+
     pub fn get_metric_summary(&self) -> HashMap<String, f64> {
         let mut summary = HashMap::new();
         
@@ -287,14 +414,14 @@ mod tests {
 
     #[test]
     fn test_metrics_collector_creation() {
-        let collector = MetricsCollector::new("TestNamespace".to_string());
+        let collector = MetricsCollector::new("TestNamespace".to_string(), HashMap::new());
         assert_eq!(collector.namespace, "TestNamespace");
         assert_eq!(collector.metrics.len(), 0);
     }
 
     #[test]
     fn test_increment_counter() {
-        let mut collector = MetricsCollector::new("TestNamespace".to_string());
+        let mut collector = MetricsCollector::new("TestNamespace".to_string(), HashMap::new());
         collector.increment_counter("test_metric".to_string(), 1.0);
         
         assert_eq!(collector.metrics.len(), 1);
@@ -304,7 +431,7 @@ mod tests {
 
     #[test]
     fn test_record_duration() {
-        let mut collector = MetricsCollector::new("TestNamespace".to_string());
+        let mut collector = MetricsCollector::new("TestNamespace".to_string(), HashMap::new());
         collector.record_duration("test_duration".to_string(), 100.0);
         
         assert_eq!(collector.metrics.len(), 1);
@@ -315,11 +442,66 @@ mod tests {
 
     #[test]
     fn test_get_metric_summary() {
-        let mut collector = MetricsCollector::new("TestNamespace".to_string());
+        let mut collector = MetricsCollector::new("TestNamespace".to_string(), HashMap::new());
         collector.increment_counter("test_metric".to_string(), 1.0);
         collector.increment_counter("test_metric".to_string(), 2.0);
         
         let summary = collector.get_metric_summary();
         assert_eq!(summary.get("TestNamespace.test_metric"), Some(&3.0));
     }
+
+    #[test]
+    fn test_default_dimensions_are_applied() {
+        let mut collector = MetricsCollector::new(
+            "TestNamespace".to_string(),
+            HashMap::from([("Service".to_string(), "workout-service".to_string())]),
+        )
+        .with_default_dimension("Environment".to_string(), "prod".to_string());
+
+        collector.increment_counter("test_metric".to_string(), 1.0);
+        collector.record_custom_metric(
+            CustomMetric::new("TestNamespace".to_string(), "custom".to_string(), 1.0, MetricUnit::Count)
+                .with_dimension("Environment".to_string(), "dev".to_string()),
+        );
+
+        let metrics = collector.get_metrics();
+        assert_eq!(metrics[0].dimensions.get("Service").map(String::as_str), Some("workout-service"));
+        assert_eq!(metrics[0].dimensions.get("Environment").map(String::as_str), Some("prod"));
+        // A metric's own dimension wins over the default
+        assert_eq!(metrics[1].dimensions.get("Environment").map(String::as_str), Some("dev"));
+        assert_eq!(metrics[1].dimensions.get("Service").map(String::as_str), Some("workout-service"));
+    }
+
+    #[test]
+    fn test_to_emf_documents() {
+        let mut collector = MetricsCollector::new("TestNamespace".to_string(), HashMap::new());
+        collector.increment_counter("requests".to_string(), 1.0);
+        collector.increment_counter("requests".to_string(), 2.0);
+        collector.record_auth_latency(12.5, true);
+
+        let documents = collector.to_emf_documents();
+        assert_eq!(documents.len(), 2);
+
+        let counters = &documents[0];
+        let directive = &counters["_aws"]["CloudWatchMetrics"][0];
+        assert_eq!(directive["Namespace"], "TestNamespace");
+        assert_eq!(directive["Dimensions"], json!([[]]));
+        assert_eq!(directive["Metrics"], json!([{ "Name": "requests", "Unit": "Count" }]));
+        assert_eq!(counters["requests"], json!([1.0, 2.0]));
+
+        let latency = &documents[1];
+        assert_eq!(latency["_aws"]["CloudWatchMetrics"][0]["Dimensions"], json!([["CacheHit"]]));
+        assert_eq!(latency["CacheHit"], "true");
+        assert_eq!(latency["AuthLatency"], 12.5);
+        assert!(latency["_aws"]["Timestamp"].is_i64());
+    }
+
+    #[test]
+    fn test_emit_emf_clears_metrics() {
+        let mut collector = MetricsCollector::new("TestNamespace".to_string(), HashMap::new());
+        collector.increment_counter("test_metric".to_string(), 1.0);
+
+        collector.emit_emf().unwrap();
+        assert!(collector.get_metrics().is_empty());
+    }
 }
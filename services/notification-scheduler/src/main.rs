@@ -1,12 +1,18 @@
 use lambda_runtime::{run, service_fn, Error, LambdaEvent};
+use futures::stream::{self, StreamExt};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
-use tracing::{info, error};
-use aws_sdk_dynamodb::{Client as DynamoDbClient, types::AttributeValue};
+use tracing::{info, error, warn, info_span, Instrument};
+use aws_sdk_dynamodb::{Client as DynamoDbClient, types::AttributeValue, operation::put_item::PutItemError};
 use aws_sdk_lambda::{Client as LambdaClient};
 use std::collections::HashMap;
+use std::future::Future;
 use chrono::{DateTime, Utc, Timelike, Datelike};
 use chrono_tz::Tz;
+use cron::Schedule;
+use std::str::FromStr;
+use once_cell::sync::Lazy;
+use shared_logging::{correlation_id_from_payload, inject_correlation_id, new_correlation_id, CircuitBreaker};
 
 #[derive(Deserialize)]
 struct EventBridgeEvent {
@@ -17,6 +23,21 @@ struct EventBridgeEvent {
     detail: Value,
 }
 
+/// Environment variable bounding how many users are processed at once
+const INVOKE_CONCURRENCY_ENV: &str = "NOTIFICATION_INVOKE_CONCURRENCY";
+const DEFAULT_INVOKE_CONCURRENCY: usize = 10;
+
+/// Environment variable setting the de-duplication bucket size in minutes
+const DEDUP_WINDOW_ENV: &str = "NOTIFICATION_DEDUP_WINDOW_MINUTES";
+const DEFAULT_DEDUP_WINDOW_MINUTES: i64 = 5;
+
+/// Environment variable holding the API key the notification service accepts from us
+const NOTIFICATION_SERVICE_API_KEY_ENV: &str = "NOTIFICATION_SERVICE_API_KEY";
+
+/// Circuit state per target function, kept across warm invocations so a failing
+/// notification service isn't invoked for every due user
+static CIRCUIT_BREAKER: Lazy<CircuitBreaker> = Lazy::new(CircuitBreaker::from_env);
+
 #[derive(Serialize)]
 struct NotificationRequest {
     user_id: String,
@@ -26,11 +47,126 @@ struct NotificationRequest {
     data: Option<Value>,
 }
 
+/// Outcome of a scheduler run. Failures are reported per user instead of aborting the run
+#[derive(Debug, Default, Serialize)]
+struct ProcessingSummary {
+    processed: usize,
+    failed: usize,
+    errors: Vec<UserFailure>,
+}
+
+#[derive(Debug, Serialize)]
+struct UserFailure {
+    user_id: String,
+    error: String,
+}
+
+impl ProcessingSummary {
+    fn record_failure(&mut self, user_id: &str, error: impl ToString) {
+        self.failed += 1;
+        self.errors.push(UserFailure {
+            user_id: user_id.to_string(),
+            error: error.to_string(),
+        });
+    }
+
+    fn merge(mut self, other: ProcessingSummary) -> Self {
+        self.processed += other.processed;
+        self.failed += other.failed;
+        self.errors.extend(other.errors);
+        self
+    }
+}
+
+/// Read the invoke concurrency, falling back to the default for missing, zero or
+/// unparseable values
+fn invoke_concurrency(value: Option<String>) -> usize {
+    value
+        .and_then(|v| v.trim().parse::<usize>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(DEFAULT_INVOKE_CONCURRENCY)
+}
+
+/// Read the de-duplication window, falling back to the default for missing, zero or
+/// unparseable values
+fn dedup_window_minutes(value: Option<String>) -> i64 {
+    value
+        .and_then(|v| v.trim().parse::<i64>().ok())
+        .filter(|&n| n > 0)
+        .unwrap_or(DEFAULT_DEDUP_WINDOW_MINUTES)
+}
+
+/// The time bucket `now` falls into; runs within the same bucket share one de-dup record
+fn dedup_bucket(now: &DateTime<Utc>, window_minutes: i64) -> i64 {
+    now.timestamp().div_euclid(window_minutes * 60)
+}
+
+/// Sort key of the de-dup record for one notification type in one bucket
+fn dedup_sort_key(notification_type: &str, bucket: i64) -> String {
+    format!("NOTIFICATION_SENT#{}#{}", notification_type, bucket)
+}
+
+/// Parse a stored cron expression. Standard 5-field expressions (`min hour dom month dow`)
+/// get a leading seconds field since the parser expects 6 or 7 fields. Day-of-week
+/// numbers run 1-7 from Sunday, so names (`SUN`, `MON-FRI`) are the safer choice
+fn parse_cron(expression: &str) -> Result<Schedule, cron::error::Error> {
+    let expression = expression.trim();
+    if expression.split_whitespace().count() == 5 {
+        Schedule::from_str(&format!("0 {}", expression))
+    } else {
+        Schedule::from_str(expression)
+    }
+}
+
+/// Whether the schedule fires on the minute tick containing `now`, evaluated in `tz`.
+/// EventBridge invokes the scheduler once a minute, so seconds are ignored
+fn cron_matches(schedule: &Schedule, now: &DateTime<Utc>, tz: Tz) -> bool {
+    now.with_timezone(&tz)
+        .with_second(0)
+        .and_then(|tick| tick.with_nanosecond(0))
+        .map(|tick| schedule.includes(tick))
+        .unwrap_or(false)
+}
+
+/// Deliver one notification under its de-dup claim. The claim is written first so
+/// overlapping runs don't both send; when the send fails (circuit open, invoke error)
+/// the claim is released so a later run in the same window retries instead of the
+/// reminder being dropped. Returns `false` when another run already holds the claim
+async fn deliver_claimed<ClaimFut, SendFn, SendFut, ReleaseFn, ReleaseFut>(
+    claim: ClaimFut,
+    send: SendFn,
+    release: ReleaseFn,
+) -> Result<bool, Error>
+where
+    ClaimFut: Future<Output = Result<bool, Error>>,
+    SendFn: FnOnce() -> SendFut,
+    SendFut: Future<Output = Result<(), Error>>,
+    ReleaseFn: FnOnce() -> ReleaseFut,
+    ReleaseFut: Future<Output = Result<(), Error>>,
+{
+    if !claim.await? {
+        return Ok(false);
+    }
+    
+    if let Err(e) = send().await {
+        if let Err(release_error) = release().await {
+            // The record expires with its window, so the worst case is one skipped retry
+            warn!("Failed to release de-dup claim after a failed send: {}", release_error);
+        }
+        return Err(e);
+    }
+    
+    Ok(true)
+}
+
 struct SchedulerService {
     dynamodb: DynamoDbClient,
     lambda: LambdaClient,
     table_name: String,
     notification_service_function_arn: String,
+    notification_service_api_key: Option<String>,
+    invoke_concurrency: usize,
+    dedup_window_minutes: i64,
 }
 
 impl SchedulerService {
@@ -45,88 +181,192 @@ impl SchedulerService {
         let notification_service_function_arn = std::env::var("NOTIFICATION_SERVICE_FUNCTION_ARN")
             .map_err(|e| Error::from(format!("NOTIFICATION_SERVICE_FUNCTION_ARN environment variable not set: {}", e)))?;
         
+        let notification_service_api_key = std::env::var(NOTIFICATION_SERVICE_API_KEY_ENV).ok()
+            .filter(|key| !key.is_empty());
+        
+        let invoke_concurrency = invoke_concurrency(std::env::var(INVOKE_CONCURRENCY_ENV).ok());
+        let dedup_window_minutes = dedup_window_minutes(std::env::var(DEDUP_WINDOW_ENV).ok());
+        
         Ok(Self {
             dynamodb,
             lambda,
             table_name,
             notification_service_function_arn,
+            notification_service_api_key,
+            invoke_concurrency,
+            dedup_window_minutes,
         })
     }
     
-    async fn process_scheduled_notifications(&self) -> Result<usize, Error> {
+    async fn process_scheduled_notifications(&self, correlation_id: &str) -> Result<ProcessingSummary, Error> {
         let now = Utc::now();
-        let mut processed_count = 0;
         
         info!("Processing scheduled notifications at: {}", now);
         
         // Get all users with notification preferences
         let users = self.get_users_with_preferences().await?;
         
-        for user in users {
-            let preferences = self.get_user_preferences(&user.user_id).await?;
+        // Process users with bounded concurrency; one user's failure doesn't stop the rest
+        let summary = stream::iter(users)
+            .map(|user| self.process_user(user, now, correlation_id))
+            .buffer_unordered(self.invoke_concurrency)
+            .fold(ProcessingSummary::default(), |summary, user_summary| async move {
+                summary.merge(user_summary)
+            })
+            .await;
+        
+        info!(
+            "Processed {} scheduled notifications ({} failed)",
+            summary.processed, summary.failed
+        );
+        
+        for metric in CIRCUIT_BREAKER.take_metrics() {
+            info!("Circuit breaker metric: {}", serde_json::to_string(&metric).unwrap_or_default());
+        }
+        
+        Ok(summary)
+    }
+    
+    async fn process_user(&self, user: User, now: DateTime<Utc>, correlation_id: &str) -> ProcessingSummary {
+        let mut summary = ProcessingSummary::default();
+        
+        let preferences = match self.get_user_preferences(&user.user_id).await {
+            Ok(preferences) => preferences,
+            Err(e) => {
+                error!("Failed to load preferences for user {}: {}", user.user_id, e);
+                summary.record_failure(&user.user_id, format!("Failed to load preferences: {}", e));
+                return summary;
+            }
+        };
+        
+        for request in self.due_notifications(&user.user_id, &preferences, &now) {
+            let notification_type = request.notification_type.clone();
             
-            // Check if it's time for workout reminders
-            if preferences.workout_reminders {
-                if let Some(reminder_time) = &preferences.workout_reminder_time {
-                    if self.is_time_for_reminder(&now, reminder_time, &preferences.timezone) {
-                        self.send_workout_reminder(&user.user_id).await?;
-                        processed_count += 1;
-                    }
+            let delivered = deliver_claimed(
+                self.claim_notification(&request, &now),
+                || self.invoke_notification_service(&request, correlation_id),
+                || self.release_notification(&request, &now),
+            )
+            .await;
+            
+            match delivered {
+                Ok(true) => summary.processed += 1,
+                Ok(false) => {
+                    info!("Skipping duplicate {} for user {}", notification_type, user.user_id);
+                }
+                Err(e) => {
+                    error!("Failed to send {} to user {}: {}", notification_type, user.user_id, e);
+                    summary.record_failure(&user.user_id, format!("{}: {}", notification_type, e));
                 }
             }
-            
-            // Check if it's time for nutrition reminders
-            if preferences.nutrition_reminders {
-                if let Some(reminder_times) = &preferences.nutrition_reminder_times {
-                    for reminder_time in reminder_times {
-                        if self.is_time_for_reminder(&now, reminder_time, &preferences.timezone) {
-                            self.send_nutrition_reminder(&user.user_id, reminder_time).await?;
-                            processed_count += 1;
+        }
+        
+        summary
+    }
+    
+    /// Notifications that are due for a user at `now`
+    fn due_notifications(&self, user_id: &str, preferences: &NotificationPreferences, now: &DateTime<Utc>) -> Vec<NotificationRequest> {
+        let mut requests = Vec::new();
+        
+        // Check if it's time for workout reminders
+        if preferences.workout_reminders {
+            let due = self.cron_due("workout_reminder", preferences, now).unwrap_or_else(|| {
+                preferences.workout_reminder_time.as_ref()
+                    .map(|reminder_time| self.is_time_for_reminder(now, reminder_time, &preferences.timezone))
+                    .unwrap_or(false)
+            });
+            if due {
+                requests.push(workout_reminder(user_id));
+            }
+        }
+        
+        // Check if it's time for nutrition reminders
+        if preferences.nutrition_reminders {
+            match self.cron_due("nutrition_reminder", preferences, now) {
+                Some(due) => {
+                    if due {
+                        let local_time = now.with_timezone(&user_timezone(&preferences.timezone)).format("%H:%M").to_string();
+                        requests.push(nutrition_reminder(user_id, &local_time));
+                    }
+                }
+                None => {
+                    if let Some(reminder_times) = &preferences.nutrition_reminder_times {
+                        for reminder_time in reminder_times {
+                            if self.is_time_for_reminder(now, reminder_time, &preferences.timezone) {
+                                requests.push(nutrition_reminder(user_id, reminder_time));
+                            }
                         }
                     }
                 }
             }
-            
-            // Check if it's time for water reminders (every 2 hours)
-            if preferences.water_reminders {
-                if self.is_time_for_water_reminder(&now, &preferences.timezone) {
-                    self.send_water_reminder(&user.user_id).await?;
-                    processed_count += 1;
-                }
+        }
+        
+        // Check if it's time for water reminders (every 2 hours unless scheduled)
+        if preferences.water_reminders {
+            let due = self.cron_due("water_reminder", preferences, now)
+                .unwrap_or_else(|| self.is_time_for_water_reminder(now, &preferences.timezone));
+            if due {
+                requests.push(water_reminder(user_id));
             }
-            
-            // Check if it's time for progress photo reminders (weekly)
-            if preferences.progress_photos {
-                if self.is_time_for_progress_photo(&now, &preferences.timezone) {
-                    self.send_progress_photo_reminder(&user.user_id).await?;
-                    processed_count += 1;
-                }
+        }
+        
+        // Check if it's time for progress photo reminders (weekly unless scheduled)
+        if preferences.progress_photos {
+            let due = self.cron_due("progress_photo", preferences, now)
+                .unwrap_or_else(|| self.is_time_for_progress_photo(now, &preferences.timezone));
+            if due {
+                requests.push(progress_photo_reminder(user_id));
             }
         }
         
-        info!("Processed {} scheduled notifications", processed_count);
-        Ok(processed_count)
+        requests
+    }
+    
+    /// Evaluate the user's cron schedule for a reminder type against the current tick.
+    /// `None` when no schedule is stored (or it doesn't parse), so the caller falls
+    /// back to the built-in cadence
+    fn cron_due(&self, notification_type: &str, preferences: &NotificationPreferences, now: &DateTime<Utc>) -> Option<bool> {
+        let expression = preferences.reminder_schedules.get(notification_type)?;
+        
+        match parse_cron(expression) {
+            Ok(schedule) => Some(cron_matches(&schedule, now, user_timezone(&preferences.timezone))),
+            Err(e) => {
+                warn!("Ignoring invalid {} schedule '{}': {}", notification_type, expression, e);
+                None
+            }
+        }
     }
     
     async fn get_users_with_preferences(&self) -> Result<Vec<User>, Error> {
-        // Query all users who have notification preferences
-        let result = self.dynamodb
-            .scan()
-            .table_name(&self.table_name)
-            .filter_expression("SK = :sk")
-            .expression_attribute_values(":sk", AttributeValue::S("NOTIFICATION_PREFERENCES".to_string()))
-            .send()
-            .await?;
-        
+        // Preference items are indexed on GSI1 under GSI1PK = NOTIFICATION_PREFERENCES,
+        // so only those items are read; page through until the index is exhausted
         let mut users = Vec::new();
-        if let Some(items) = result.items {
-            for item in items {
+        let mut exclusive_start_key = None;
+        
+        loop {
+            let result = self.dynamodb
+                .query()
+                .table_name(&self.table_name)
+                .index_name("GSI1")
+                .key_condition_expression("GSI1PK = :gsi1pk")
+                .expression_attribute_values(":gsi1pk", AttributeValue::S("NOTIFICATION_PREFERENCES".to_string()))
+                .projection_expression("userId")
+                .set_exclusive_start_key(exclusive_start_key)
+                .send()
+                .await?;
+            
+            for item in result.items.unwrap_or_default() {
                 if let Some(user_id) = item.get("userId").and_then(|v| v.as_s().ok()) {
                     users.push(User {
                         user_id: user_id.to_string(),
                     });
                 }
             }
+            
+            exclusive_start_key = result.last_evaluated_key;
+            if exclusive_start_key.is_none() {
+                break;
+            }
         }
         
         info!("Found {} users with notification preferences", users.len());
@@ -192,84 +432,80 @@ impl SchedulerService {
         false
     }
     
-    async fn send_workout_reminder(&self, user_id: &str) -> Result<(), Error> {
-        let request = NotificationRequest {
-            user_id: user_id.to_string(),
-            notification_type: "workout_reminder".to_string(),
-            title: "Time for your workout! 💪".to_string(),
-            body: "Your scheduled workout is ready. Let's get moving!".to_string(),
-            data: Some(json!({
-                "action": "start_workout",
-                "category": "workout"
-            })),
-        };
-        
-        self.invoke_notification_service(request).await
-    }
-    
-    async fn send_nutrition_reminder(&self, user_id: &str, reminder_time: &str) -> Result<(), Error> {
-        let meal_type = match reminder_time {
-            "08:00" => "breakfast",
-            "13:00" => "lunch",
-            "19:00" => "dinner",
-            _ => "meal",
-        };
-        
-        let request = NotificationRequest {
-            user_id: user_id.to_string(),
-            notification_type: "nutrition_reminder".to_string(),
-            title: "Time to log your meal! 🍽️".to_string(),
-            body: format!("Don't forget to log your {} to track your nutrition goals.", meal_type),
-            data: Some(json!({
-                "action": "log_meal",
-                "category": "nutrition",
-                "meal_type": meal_type
-            })),
-        };
+    /// Write the de-dup record for this notification's time bucket. Returns `false` when
+    /// the record already exists, i.e. an identical reminder was sent in this window.
+    /// Records carry a `ttl` so DynamoDB expires them shortly after the window closes
+    async fn claim_notification(&self, request: &NotificationRequest, now: &DateTime<Utc>) -> Result<bool, Error> {
+        let window_secs = self.dedup_window_minutes * 60;
+        let bucket = dedup_bucket(now, self.dedup_window_minutes);
+        let ttl = (bucket + 2) * window_secs;
         
-        self.invoke_notification_service(request).await
-    }
-    
-    async fn send_water_reminder(&self, user_id: &str) -> Result<(), Error> {
-        let request = NotificationRequest {
-            user_id: user_id.to_string(),
-            notification_type: "water_reminder".to_string(),
-            title: "Stay hydrated! 💧".to_string(),
-            body: "Time for a glass of water. Keep up the great work!".to_string(),
-            data: Some(json!({
-                "action": "log_water",
-                "category": "hydration"
-            })),
-        };
+        let result = self.dynamodb
+            .put_item()
+            .table_name(&self.table_name)
+            .item("PK", AttributeValue::S(format!("USER#{}", request.user_id)))
+            .item("SK", AttributeValue::S(dedup_sort_key(&request.notification_type, bucket)))
+            .item("notificationType", AttributeValue::S(request.notification_type.clone()))
+            .item("sentAt", AttributeValue::S(now.to_rfc3339()))
+            .item("ttl", AttributeValue::N(ttl.to_string()))
+            .condition_expression("attribute_not_exists(PK)")
+            .send()
+            .await;
         
-        self.invoke_notification_service(request).await
+        match result {
+            Ok(_) => Ok(true),
+            Err(e) if matches!(e.as_service_error(), Some(PutItemError::ConditionalCheckFailedException(_))) => Ok(false),
+            Err(e) => Err(Error::from(e)),
+        }
     }
     
-    async fn send_progress_photo_reminder(&self, user_id: &str) -> Result<(), Error> {
-        let request = NotificationRequest {
-            user_id: user_id.to_string(),
-            notification_type: "progress_photo".to_string(),
-            title: "Weekly Progress Check! 📸".to_string(),
-            body: "Time to take your weekly progress photo and track your transformation!".to_string(),
-            data: Some(json!({
-                "action": "take_progress_photo",
-                "category": "progress"
-            })),
-        };
+    /// Delete the de-dup record `claim_notification` wrote for this window, so the
+    /// notification can be claimed and sent again
+    async fn release_notification(&self, request: &NotificationRequest, now: &DateTime<Utc>) -> Result<(), Error> {
+        let bucket = dedup_bucket(now, self.dedup_window_minutes);
         
-        self.invoke_notification_service(request).await
+        self.dynamodb
+            .delete_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(format!("USER#{}", request.user_id)))
+            .key("SK", AttributeValue::S(dedup_sort_key(&request.notification_type, bucket)))
+            .send()
+            .await?;
+        Ok(())
     }
     
-    async fn invoke_notification_service(&self, request: NotificationRequest) -> Result<(), Error> {
-        let payload = serde_json::to_string(&request)
+    /// Invoke the notification service, passing this run's correlation id on so its
+    /// logs can be tied back to the scheduler run
+    async fn invoke_notification_service(&self, request: &NotificationRequest, correlation_id: &str) -> Result<(), Error> {
+        let mut payload = serde_json::to_value(request)
             .map_err(|e| Error::from(format!("Failed to serialize request: {}", e)))?;
+        inject_correlation_id(&mut payload, correlation_id);
+        // The notification service authenticates us as a service principal by this key
+        if let (Some(api_key), Some(headers)) = (
+            &self.notification_service_api_key,
+            payload.get_mut("headers").and_then(Value::as_object_mut),
+        ) {
+            headers.insert("X-Api-Key".to_string(), Value::String(api_key.clone()));
+        }
+        let payload = payload.to_string();
+        
+        let target = &self.notification_service_function_arn;
+        CIRCUIT_BREAKER.try_acquire(target)?;
         
-        self.lambda
+        let result = self.lambda
             .invoke()
-            .function_name(&self.notification_service_function_arn)
+            .function_name(target)
             .payload(payload.as_bytes().into())
             .send()
-            .await?;
+            .await;
+        
+        match result {
+            Ok(_) => CIRCUIT_BREAKER.record_success(target),
+            Err(e) => {
+                CIRCUIT_BREAKER.record_failure(target);
+                return Err(e.into());
+            }
+        }
         
         info!("Invoked notification service for user: {}", request.user_id);
         Ok(())
@@ -315,6 +551,13 @@ impl SchedulerService {
             .and_then(|v| v.as_s().ok())
             .map(|s| s.to_string());
         
+        let reminder_schedules = item.get("reminderSchedules")
+            .and_then(|v| v.as_m().ok())
+            .map(|map| map.iter()
+                .filter_map(|(notification_type, v)| Some((notification_type.clone(), v.as_s().ok()?.clone())))
+                .collect())
+            .unwrap_or_default();
+        
         Ok(NotificationPreferences {
             workout_reminders: *workout_reminders,
             nutrition_reminders: *nutrition_reminders,
@@ -325,10 +568,71 @@ impl SchedulerService {
             workout_reminder_time,
             nutrition_reminder_times,
             timezone,
+            reminder_schedules,
         })
     }
 }
 
+fn workout_reminder(user_id: &str) -> NotificationRequest {
+    NotificationRequest {
+        user_id: user_id.to_string(),
+        notification_type: "workout_reminder".to_string(),
+        title: "Time for your workout! 💪".to_string(),
+        body: "Your scheduled workout is ready. Let's get moving!".to_string(),
+        data: Some(json!({
+            "action": "start_workout",
+            "category": "workout"
+        })),
+    }
+}
+
+fn nutrition_reminder(user_id: &str, reminder_time: &str) -> NotificationRequest {
+    let meal_type = match reminder_time {
+        "08:00" => "breakfast",
+        "13:00" => "lunch",
+        "19:00" => "dinner",
+        _ => "meal",
+    };
+
+    NotificationRequest {
+        user_id: user_id.to_string(),
+        notification_type: "nutrition_reminder".to_string(),
+        title: "Time to log your meal! 🍽️".to_string(),
+        body: format!("Don't forget to log your {} to track your nutrition goals.", meal_type),
+        data: Some(json!({
+            "action": "log_meal",
+            "category": "nutrition",
+            "meal_type": meal_type
+        })),
+    }
+}
+
+fn water_reminder(user_id: &str) -> NotificationRequest {
+    NotificationRequest {
+        user_id: user_id.to_string(),
+        notification_type: "water_reminder".to_string(),
+        title: "Stay hydrated! 💧".to_string(),
+        body: "Time for a glass of water. Keep up the great work!".to_string(),
+        data: Some(json!({
+            "action": "log_water",
+            "category": "hydration"
+        })),
+    }
+}
+
+fn progress_photo_reminder(user_id: &str) -> NotificationRequest {
+    NotificationRequest {
+        user_id: user_id.to_string(),
+        notification_type: "progress_photo".to_string(),
+        title: "Weekly Progress Check! 📸".to_string(),
+        body: "Time to take your weekly progress photo and track your transformation!".to_string(),
+        data: Some(json!({
+            "action": "take_progress_photo",
+            "category": "progress"
+        })),
+    }
+}
+
 #[derive(Debug, Clone)]
 struct User {
     user_id: String,
@@ -345,6 +649,8 @@ struct NotificationPreferences {
     workout_reminder_time: Option<String>,
     nutrition_reminder_times: Option<Vec<String>>,
     timezone: Option<String>,
+    /// Cron expressions keyed by notification type, overriding the built-in cadence
+    reminder_schedules: HashMap<String, String>,
 }
 
 impl Default for NotificationPreferences {
@@ -359,10 +665,26 @@ impl Default for NotificationPreferences {
             workout_reminder_time: Some("08:00".to_string()),
             nutrition_reminder_times: Some(vec!["08:00".to_string(), "13:00".to_string(), "19:00".to_string()]),
             timezone: Some("UTC".to_string()),
+            reminder_schedules: HashMap::new(),
         }
     }
 }
 
+/// The user's timezone, falling back to UTC when unset or unrecognised
+fn user_timezone(timezone: &Option<String>) -> Tz {
+    timezone.as_deref()
+        .and_then(|tz| tz.parse::<Tz>().ok())
+        .unwrap_or(Tz::UTC)
+}
+
+/// Correlation id for a scheduler run: the caller's when invoked with one, otherwise the
+/// EventBridge event id, otherwise a new id
+fn run_correlation_id(payload: &Value) -> String {
+    correlation_id_from_payload(payload)
+        .or_else(|| payload["id"].as_str().map(str::to_string))
+        .unwrap_or_else(new_correlation_id)
+}
+
 async fn handler(event: LambdaEvent<Value>) -> Result<Value, Error> {
     tracing_subscriber::fmt()
         .with_max_level(tracing::Level::INFO)
@@ -371,23 +693,29 @@ async fn handler(event: LambdaEvent<Value>) -> Result<Value, Error> {
         .init();
     
     let (payload, context) = event.into_parts();
+    let correlation_id = run_correlation_id(&payload);
     
-    info!("Received event: {:?}", payload);
+    info!("Received event (correlation id {}): {:?}", correlation_id, payload);
     info!("Context: {:?}", context);
     
     let scheduler_service = SchedulerService::new().await?;
     
-    let result = scheduler_service.process_scheduled_notifications().await;
+    let result = scheduler_service
+        .process_scheduled_notifications(&correlation_id)
+        .instrument(info_span!("scheduler_run", correlation_id = %correlation_id))
+        .await;
     
     match result {
-        Ok(processed_count) => {
-            info!("Processed {} scheduled notifications", processed_count);
+        Ok(summary) => {
+            info!("Processed {} scheduled notifications ({} failed)", summary.processed, summary.failed);
             Ok(json!({
                 "statusCode": 200,
                 "body": json!({
-                    "success": true,
-                    "message": format!("Processed {} scheduled notifications", processed_count),
-                    "processed_count": processed_count
+                    "success": summary.failed == 0,
+                    "message": format!("Processed {} scheduled notifications", summary.processed),
+                    "processed": summary.processed,
+                    "failed": summary.failed,
+                    "errors": summary.errors
                 }).to_string()
             }))
         },
@@ -408,3 +736,129 @@ async fn handler(event: LambdaEvent<Value>) -> Result<Value, Error> {
 async fn main() -> Result<(), Error> {
     run(service_fn(handler)).await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invoke_concurrency_from_env_value() {
+        assert_eq!(invoke_concurrency(Some("25".to_string())), 25);
+        assert_eq!(invoke_concurrency(Some("0".to_string())), DEFAULT_INVOKE_CONCURRENCY);
+        assert_eq!(invoke_concurrency(Some("lots".to_string())), DEFAULT_INVOKE_CONCURRENCY);
+        assert_eq!(invoke_concurrency(None), DEFAULT_INVOKE_CONCURRENCY);
+    }
+
+    #[test]
+    fn test_dedup_window_from_env_value() {
+        assert_eq!(dedup_window_minutes(Some("15".to_string())), 15);
+        assert_eq!(dedup_window_minutes(Some("0".to_string())), DEFAULT_DEDUP_WINDOW_MINUTES);
+        assert_eq!(dedup_window_minutes(Some("-5".to_string())), DEFAULT_DEDUP_WINDOW_MINUTES);
+        assert_eq!(dedup_window_minutes(None), DEFAULT_DEDUP_WINDOW_MINUTES);
+    }
+
+    #[test]
+    fn test_run_correlation_id() {
+        let forwarded = json!({ "headers": { "X-Correlation-Id": "corr-1" }, "id": "event-1" });
+        assert_eq!(run_correlation_id(&forwarded), "corr-1");
+
+        let scheduled = json!({ "id": "event-1", "source": "aws.events", "detail-type": "Scheduled Event" });
+        assert_eq!(run_correlation_id(&scheduled), "event-1");
+
+        assert!(!run_correlation_id(&json!({})).is_empty());
+    }
+
+    #[test]
+    fn test_overlapping_runs_share_a_dedup_key() {
+        let at = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+
+        let first = dedup_bucket(&at("2024-03-10T08:00:00Z"), 5);
+        let overlapping = dedup_bucket(&at("2024-03-10T08:04:59Z"), 5);
+        let next = dedup_bucket(&at("2024-03-10T08:05:00Z"), 5);
+
+        assert_eq!(first, overlapping);
+        assert_ne!(first, next);
+        assert_eq!(
+            dedup_sort_key("water_reminder", first),
+            dedup_sort_key("water_reminder", overlapping)
+        );
+        assert_ne!(
+            dedup_sort_key("water_reminder", first),
+            dedup_sort_key("workout_reminder", first)
+        );
+    }
+
+    #[test]
+    fn test_cron_matches_in_user_timezone() {
+        let at = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc);
+        let every_three_hours = parse_cron("30 7-19/3 * * MON-FRI").unwrap();
+        let new_york: Tz = "America/New_York".parse().unwrap();
+
+        // Monday 10:30 in New York, mid-way through the minute tick
+        assert!(cron_matches(&every_three_hours, &at("2024-03-11T14:30:42Z"), new_york));
+        // Same instant evaluated in UTC is 14:30, outside the 7,10,13,16,19 hours
+        assert!(!cron_matches(&every_three_hours, &at("2024-03-11T14:30:42Z"), Tz::UTC));
+        // Saturday
+        assert!(!cron_matches(&every_three_hours, &at("2024-03-16T14:30:00Z"), new_york));
+    }
+
+    #[test]
+    fn test_parse_cron_accepts_six_fields_and_rejects_garbage() {
+        assert!(parse_cron("0 0 18 * * SUN").is_ok());
+        assert!(parse_cron("every two hours").is_err());
+    }
+
+    #[test]
+    fn test_summary_collects_failures_without_losing_successes() {
+        let mut failed_user = ProcessingSummary::default();
+        failed_user.record_failure("user-2", "workout_reminder: throttled");
+
+        let summary = ProcessingSummary { processed: 2, ..Default::default() }
+            .merge(failed_user)
+            .merge(ProcessingSummary { processed: 1, ..Default::default() });
+
+        assert_eq!(summary.processed, 3);
+        assert_eq!(summary.failed, 1);
+        assert_eq!(summary.errors[0].user_id, "user-2");
+        assert_eq!(summary.errors[0].error, "workout_reminder: throttled");
+    }
+
+    #[tokio::test]
+    async fn test_failed_send_releases_the_claim() {
+        let released = std::sync::atomic::AtomicBool::new(false);
+
+        let result = deliver_claimed(
+            async { Ok(true) },
+            || async { Err(Error::from("Circuit open for notification-service; retry in 30s")) },
+            || async {
+                released.store(true, std::sync::atomic::Ordering::SeqCst);
+                Ok(())
+            },
+        )
+        .await;
+
+        assert!(result.is_err());
+        assert!(released.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_claimed_sends_keep_the_claim_and_duplicates_skip_the_send() {
+        let sent = std::sync::atomic::AtomicUsize::new(0);
+        let released = std::sync::atomic::AtomicBool::new(false);
+        let send = || async {
+            sent.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(())
+        };
+        let release = || async {
+            released.store(true, std::sync::atomic::Ordering::SeqCst);
+            Ok(())
+        };
+
+        assert!(deliver_claimed(async { Ok(true) }, send, release).await.unwrap());
+        assert!(!deliver_claimed(async { Ok(false) }, send, release).await.unwrap());
+
+        assert_eq!(sent.load(std::sync::atomic::Ordering::SeqCst), 1);
+        assert!(!released.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+}
@@ -0,0 +1,313 @@
+use async_trait::async_trait;
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use lambda_runtime::Error;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+const MESSAGING_SCOPE: &str = "https://www.googleapis.com/auth/firebase.messaging";
+/// Refresh access tokens this long before Google expires them
+const TOKEN_REFRESH_MARGIN: Duration = Duration::from_secs(300);
+
+/// Access tokens are reused across warm invocations until close to expiry
+static ACCESS_TOKEN: Mutex<Option<CachedToken>> = Mutex::new(None);
+
+#[derive(Clone)]
+struct CachedToken {
+    client_email: String,
+    access_token: String,
+    refresh_at: Instant,
+}
+
+/// Fields we need from a Firebase service account key file
+#[derive(Deserialize)]
+pub struct ServiceAccount {
+    pub client_email: String,
+    pub private_key: String,
+    pub project_id: Option<String>,
+}
+
+impl ServiceAccount {
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        serde_json::from_str(json)
+            .map_err(|e| Error::from(format!("Invalid service account JSON: {}", e)))
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+/// Result of sending one message to one device
+#[derive(Debug, PartialEq)]
+pub enum Delivery {
+    Delivered,
+    /// FCM reported the token as unregistered or invalid; the device should be removed
+    DeadToken,
+    Failed(String),
+}
+
+/// The HTTP call to FCM, kept behind a trait so sends can be tested without the network
+#[async_trait]
+pub trait FcmHttp: Send + Sync {
+    /// POST a JSON message and return the status code and response body
+    async fn post_message(
+        &self,
+        url: &str,
+        access_token: &str,
+        payload: &Value,
+    ) -> Result<(u16, String), Error>;
+}
+
+#[async_trait]
+impl FcmHttp for reqwest::Client {
+    async fn post_message(
+        &self,
+        url: &str,
+        access_token: &str,
+        payload: &Value,
+    ) -> Result<(u16, String), Error> {
+        let response = self
+            .post(url)
+            .bearer_auth(access_token)
+            .header("Content-Type", "application/json")
+            .json(payload)
+            .send()
+            .await?;
+
+        let status = response.status().as_u16();
+        Ok((status, response.text().await?))
+    }
+}
+
+/// Get an OAuth2 access token for FCM, minting a new one from the service account
+/// when there is no cached token or it is about to expire
+pub async fn access_token(
+    client: &reqwest::Client,
+    service_account: &ServiceAccount,
+) -> Result<String, Error> {
+    if let Some(cached) = ACCESS_TOKEN.lock().ok().and_then(|cache| cache.clone()) {
+        if cached.client_email == service_account.client_email && Instant::now() < cached.refresh_at
+        {
+            return Ok(cached.access_token);
+        }
+    }
+
+    let assertion = signed_assertion(service_account)?;
+    let response = client
+        .post(TOKEN_URL)
+        .form(&[
+            ("grant_type", "urn:ietf:params:oauth:grant-type:jwt-bearer"),
+            ("assertion", assertion.as_str()),
+        ])
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(Error::from(format!(
+            "OAuth token request failed: {}",
+            response.text().await?
+        )));
+    }
+
+    let token: TokenResponse = response.json().await?;
+    let lifetime = Duration::from_secs(token.expires_in).saturating_sub(TOKEN_REFRESH_MARGIN);
+    if let Ok(mut cache) = ACCESS_TOKEN.lock() {
+        *cache = Some(CachedToken {
+            client_email: service_account.client_email.clone(),
+            access_token: token.access_token.clone(),
+            refresh_at: Instant::now() + lifetime,
+        });
+    }
+
+    Ok(token.access_token)
+}
+
+/// Self-signed JWT exchanged for an access token (RFC 7523)
+fn signed_assertion(service_account: &ServiceAccount) -> Result<String, Error> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let claims = json!({
+        "iss": service_account.client_email,
+        "scope": MESSAGING_SCOPE,
+        "aud": TOKEN_URL,
+        "iat": now,
+        "exp": now + 3600,
+    });
+
+    let key = EncodingKey::from_rsa_pem(service_account.private_key.as_bytes())
+        .map_err(|e| Error::from(format!("Invalid service account private key: {}", e)))?;
+    encode(&Header::new(Algorithm::RS256), &claims, &key)
+        .map_err(|e| Error::from(format!("Failed to sign token assertion: {}", e)))
+}
+
+/// The HTTP v1 message envelope. FCM requires data values to be strings
+pub fn v1_message(token: &str, title: &str, body: &str, data: Option<Value>) -> Value {
+    let data_map: HashMap<String, String> = match data {
+        Some(Value::Object(obj)) => obj
+            .into_iter()
+            .map(|(k, v)| {
+                let value = match v {
+                    Value::String(s) => s,
+                    other => other.to_string(),
+                };
+                (k, value)
+            })
+            .collect(),
+        _ => HashMap::new(),
+    };
+
+    json!({
+        "message": {
+            "token": token,
+            "notification": {
+                "title": title,
+                "body": body,
+            },
+            "data": data_map,
+            "android": {
+                "priority": "high"
+            },
+            "apns": {
+                "headers": {
+                    "apns-priority": "10"
+                }
+            }
+        }
+    })
+}
+
+/// Legacy API error codes meaning the token will never work again
+const LEGACY_DEAD_TOKEN_ERRORS: [&str; 2] = ["NotRegistered", "InvalidRegistration"];
+
+/// Classify an FCM response. Dead tokens are recognised from HTTP v1 errors
+/// (`UNREGISTERED`, or `INVALID_ARGUMENT` about the registration token) as well as
+/// legacy `results[].error` values (`NotRegistered`, `InvalidRegistration`)
+pub fn classify_response(status: u16, body: &str) -> Delivery {
+    let parsed: Value = serde_json::from_str(body).unwrap_or(Value::Null);
+    if is_dead_token(&parsed) {
+        return Delivery::DeadToken;
+    }
+
+    if (200..300).contains(&status) {
+        Delivery::Delivered
+    } else {
+        Delivery::Failed(format!("FCM returned {}: {}", status, body))
+    }
+}
+
+fn is_dead_token(body: &Value) -> bool {
+    let error = &body["error"];
+    let v1_unregistered = error["details"]
+        .as_array()
+        .map(|details| {
+            details
+                .iter()
+                .any(|detail| detail["errorCode"] == "UNREGISTERED")
+        })
+        .unwrap_or(false);
+    let v1_invalid_token = error["status"] == "INVALID_ARGUMENT"
+        && error["message"]
+            .as_str()
+            .map(|message| message.contains("registration token"))
+            .unwrap_or(false);
+    let legacy = body["results"]
+        .as_array()
+        .map(|results| {
+            results.iter().any(|result| {
+                result["error"]
+                    .as_str()
+                    .map(|code| LEGACY_DEAD_TOKEN_ERRORS.contains(&code))
+                    .unwrap_or(false)
+            })
+        })
+        .unwrap_or(false);
+
+    v1_unregistered || v1_invalid_token || legacy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_v1_message_envelope() {
+        let message = v1_message(
+            "device-token",
+            "Time for your workout!",
+            "Let's go",
+            Some(json!({ "action": "start_workout", "count": 3 })),
+        );
+
+        assert_eq!(message["message"]["token"], "device-token");
+        assert_eq!(
+            message["message"]["notification"]["title"],
+            "Time for your workout!"
+        );
+        assert_eq!(message["message"]["data"]["action"], "start_workout");
+        assert_eq!(message["message"]["data"]["count"], "3");
+    }
+
+    #[test]
+    fn test_classify_dead_tokens() {
+        let unregistered = json!({
+            "error": {
+                "code": 404,
+                "status": "NOT_FOUND",
+                "details": [{
+                    "@type": "type.googleapis.com/google.firebase.fcm.v1.FcmError",
+                    "errorCode": "UNREGISTERED"
+                }]
+            }
+        });
+        assert_eq!(
+            classify_response(404, &unregistered.to_string()),
+            Delivery::DeadToken
+        );
+
+        let invalid = json!({
+            "error": {
+                "code": 400,
+                "status": "INVALID_ARGUMENT",
+                "message": "The registration token is not a valid FCM registration token"
+            }
+        });
+        assert_eq!(
+            classify_response(400, &invalid.to_string()),
+            Delivery::DeadToken
+        );
+
+        let legacy = json!({ "failure": 1, "results": [{ "error": "InvalidRegistration" }] });
+        assert_eq!(
+            classify_response(200, &legacy.to_string()),
+            Delivery::DeadToken
+        );
+    }
+
+    #[test]
+    fn test_classify_other_responses() {
+        assert_eq!(
+            classify_response(200, r#"{"name":"projects/p/messages/1"}"#),
+            Delivery::Delivered
+        );
+
+        let quota = json!({
+            "error": {
+                "code": 429,
+                "status": "RESOURCE_EXHAUSTED",
+                "details": [{ "errorCode": "QUOTA_EXCEEDED" }]
+            }
+        });
+        assert!(matches!(
+            classify_response(429, &quota.to_string()),
+            Delivery::Failed(_)
+        ));
+    }
+}
@@ -1,8 +1,14 @@
 use lambda_runtime::{run, service_fn, Error, LambdaEvent};
-use serde::{Deserialize, Serialize};
+use serde::Deserialize;
 use serde_json::json;
 use aws_sdk_dynamodb::{Client as DynamoDbClient, types::AttributeValue};
-use std::collections::HashMap;
+use async_trait::async_trait;
+use shared_logging::correlation_id_from_payload;
+use tracing::Instrument;
+
+mod fcm;
+
+use fcm::{Delivery, FcmHttp, ServiceAccount};
 
 #[derive(Deserialize)]
 struct NotificationRequest {
@@ -13,44 +19,107 @@ struct NotificationRequest {
     data: Option<serde_json::Value>,
 }
 
-#[derive(Serialize)]
-struct FcmMessage {
+/// A registered device and the sort key of its DynamoDB item
+struct DeviceToken {
+    sk: String,
     token: String,
-    notification: FcmNotification,
-    data: Option<HashMap<String, String>>,
 }
 
-#[derive(Serialize)]
-struct FcmNotification {
-    title: String,
-    body: String,
+/// Removal of `DEVICE#` items whose FCM token is dead
+#[async_trait]
+trait DeviceStore: Send + Sync {
+    async fn delete_device(&self, user_id: &str, sk: &str) -> Result<(), Error>;
+}
+
+#[async_trait]
+impl DeviceStore for DynamoDbClient {
+    async fn delete_device(&self, user_id: &str, sk: &str) -> Result<(), Error> {
+        let table_name = std::env::var("TABLE_NAME")?;
+        
+        self.delete_item()
+            .table_name(table_name)
+            .key("PK", AttributeValue::S(format!("USER#{}", user_id)))
+            .key("SK", AttributeValue::S(sk.to_string()))
+            .send()
+            .await?;
+        
+        Ok(())
+    }
 }
 
+/// Send to every device of the user and return how many dead tokens were removed
 async fn send_notification(
     event: NotificationRequest,
     dynamodb: &DynamoDbClient,
     fcm_key: &str,
-) -> Result<(), Error> {
+) -> Result<usize, Error> {
     // Get user's device tokens from DynamoDB
     let tokens = get_user_device_tokens(dynamodb, &event.user_id).await?;
     
     if tokens.is_empty() {
         tracing::warn!("No device tokens found for user: {}", event.user_id);
-        return Ok(());
+        return Ok(0);
     }
     
-    // Send to each device
-    for token in tokens {
-        send_fcm_notification(&token, &event.title, &event.body, event.data.clone(), fcm_key).await?;
+    let client = reqwest::Client::new();
+    let service_account = ServiceAccount::from_json(fcm_key)?;
+    let project_id = std::env::var("FIREBASE_PROJECT_ID")
+        .ok()
+        .or_else(|| service_account.project_id.clone())
+        .ok_or("FIREBASE_PROJECT_ID not set and missing from service account")?;
+    let access_token = fcm::access_token(&client, &service_account).await?;
+    let url = format!("https://fcm.googleapis.com/v1/projects/{}/messages:send", project_id);
+    
+    deliver_to_devices(&event, &tokens, &client, &url, &access_token, dynamodb).await
+}
+
+/// Send to each device, deleting the `DEVICE#` item of every token FCM reports as
+/// unregistered or invalid. Other failures are logged and do not stop the loop
+async fn deliver_to_devices(
+    event: &NotificationRequest,
+    devices: &[DeviceToken],
+    http: &dyn FcmHttp,
+    url: &str,
+    access_token: &str,
+    store: &dyn DeviceStore,
+) -> Result<usize, Error> {
+    let mut removed = 0;
+    
+    for device in devices {
+        let delivery = send_fcm_notification(
+            http,
+            url,
+            access_token,
+            &device.token,
+            &event.title,
+            &event.body,
+            event.data.clone(),
+        )
+        .await?;
+        
+        match delivery {
+            Delivery::Delivered => {}
+            Delivery::DeadToken => {
+                store.delete_device(&event.user_id, &device.sk).await?;
+                removed += 1;
+            }
+            Delivery::Failed(error) => {
+                tracing::error!("FCM request failed for user {}: {}", event.user_id, error);
+            }
+        }
     }
     
-    Ok(())
+    if removed > 0 {
+        tracing::info!("Removed {} dead device tokens for user: {}", removed, event.user_id);
+    }
+    
+    Ok(removed)
 }
 
 async fn get_user_device_tokens(
     dynamodb: &DynamoDbClient,
     user_id: &str,
-) -> Result<Vec<String>, Error> {
+) -> Result<Vec<DeviceToken>, Error> {
     let table_name = std::env::var("TABLE_NAME")?;
     
     let result = dynamodb
@@ -65,8 +134,13 @@ async fn get_user_device_tokens(
     let mut tokens = Vec::new();
     if let Some(items) = result.items {
         for item in items {
-            if let Some(token) = item.get("fcmToken").and_then(|v| v.as_s().ok()) {
-                tokens.push(token.to_string());
+            let sk = item.get("SK").and_then(|v| v.as_s().ok());
+            let token = item.get("fcmToken").and_then(|v| v.as_s().ok());
+            if let (Some(sk), Some(token)) = (sk, token) {
+                tokens.push(DeviceToken {
+                    sk: sk.to_string(),
+                    token: token.to_string(),
+                });
             }
         }
     }
@@ -74,50 +148,23 @@ async fn get_user_device_tokens(
     Ok(tokens)
 }
 
+/// Send one message through the FCM HTTP v1 API and classify the response
 async fn send_fcm_notification(
+    http: &dyn FcmHttp,
+    url: &str,
+    access_token: &str,
     token: &str,
     title: &str,
     body: &str,
     data: Option<serde_json::Value>,
-    fcm_key: &str,
-) -> Result<(), Error> {
-    let client = reqwest::Client::new();
-    
-    let mut data_map = HashMap::new();
-    if let Some(d) = data {
-        if let serde_json::Value::Object(obj) = d {
-            for (k, v) in obj {
-                data_map.insert(k, v.to_string());
-            }
-        }
-    }
-    
-    let payload = json!({
-        "to": token,
-        "notification": {
-            "title": title,
-            "body": body,
-        },
-        "data": data_map,
-        "priority": "high",
-    });
+) -> Result<Delivery, Error> {
+    let payload = fcm::v1_message(token, title, body, data);
+    let (status, response_body) = http.post_message(url, access_token, &payload).await?;
     
-    let response = client
-        .post("https://fcm.googleapis.com/fcm/send")
-        .header("Authorization", format!("key={}", fcm_key))
-        .header("Content-Type", "application/json")
-        .json(&payload)
-        .send()
-        .await?;
-    
-    if !response.status().is_success() {
-        tracing::error!("FCM request failed: {:?}", response.text().await?);
-    }
-    
-    Ok(())
+    Ok(fcm::classify_response(status, &response_body))
 }
 
-async fn handler(event: LambdaEvent<NotificationRequest>) -> Result<serde_json::Value, Error> {
+async fn handler(event: LambdaEvent<serde_json::Value>) -> Result<serde_json::Value, Error> {
     tracing_subscriber::fmt()
         .with_max_level(tracing::Level::INFO)
         .json()
@@ -125,17 +172,108 @@ async fn handler(event: LambdaEvent<NotificationRequest>) -> Result<serde_json::
         .without_time()
         .init();
     
-    let (payload, _context) = event.into_parts();
+    let (payload, context) = event.into_parts();
+    // Keep the caller's correlation id so these logs join the scheduler run's
+    let correlation_id = correlation_id_from_payload(&payload).unwrap_or(context.request_id);
+    let request: NotificationRequest = serde_json::from_value(payload)?;
     let config = aws_config::load_from_env().await;
     let dynamodb = DynamoDbClient::new(&config);
     let fcm_key = std::env::var("FCM_SERVER_KEY")?;
     
-    send_notification(payload, &dynamodb, &fcm_key).await?;
+    let removed_devices = send_notification(request, &dynamodb, &fcm_key)
+        .instrument(tracing::info_span!("send_notification", correlation_id = %correlation_id))
+        .await?;
     
-    Ok(json!({ "success": true }))
+    Ok(json!({ "success": true, "removed_devices": removed_devices }))
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
     run(service_fn(handler)).await
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Returns a canned response for every device token it is asked about
+    struct MockFcm {
+        responses: Vec<(&'static str, u16, serde_json::Value)>,
+    }
+
+    #[async_trait]
+    impl FcmHttp for MockFcm {
+        async fn post_message(
+            &self,
+            _url: &str,
+            _access_token: &str,
+            payload: &serde_json::Value,
+        ) -> Result<(u16, String), Error> {
+            let token = payload["message"]["token"].as_str().unwrap_or_default();
+            let (_, status, body) = self
+                .responses
+                .iter()
+                .find(|(t, _, _)| *t == token)
+                .ok_or("unexpected token")?;
+            Ok((*status, body.to_string()))
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingStore {
+        deleted: Mutex<Vec<(String, String)>>,
+    }
+
+    #[async_trait]
+    impl DeviceStore for RecordingStore {
+        async fn delete_device(&self, user_id: &str, sk: &str) -> Result<(), Error> {
+            self.deleted
+                .lock()
+                .unwrap()
+                .push((user_id.to_string(), sk.to_string()));
+            Ok(())
+        }
+    }
+
+    fn device(sk: &str, token: &str) -> DeviceToken {
+        DeviceToken {
+            sk: sk.to_string(),
+            token: token.to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_not_registered_token_is_deleted() {
+        let http = MockFcm {
+            responses: vec![
+                ("live-token", 200, json!({ "name": "projects/p/messages/1" })),
+                ("dead-token", 200, json!({ "failure": 1, "results": [{ "error": "NotRegistered" }] })),
+                ("flaky-token", 503, json!({ "error": { "code": 503, "status": "UNAVAILABLE" } })),
+            ],
+        };
+        let store = RecordingStore::default();
+        let event = NotificationRequest {
+            user_id: "user-1".to_string(),
+            notification_type: "workout_reminder".to_string(),
+            title: "Time for your workout!".to_string(),
+            body: "Let's go".to_string(),
+            data: None,
+        };
+        let devices = vec![
+            device("DEVICE#live", "live-token"),
+            device("DEVICE#dead", "dead-token"),
+            device("DEVICE#flaky", "flaky-token"),
+        ];
+
+        let removed = deliver_to_devices(&event, &devices, &http, "https://fcm.test", "access", &store)
+            .await
+            .unwrap();
+
+        assert_eq!(removed, 1);
+        assert_eq!(
+            *store.deleted.lock().unwrap(),
+            vec![("user-1".to_string(), "DEVICE#dead".to_string())]
+        );
+    }
+}
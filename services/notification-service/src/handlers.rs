@@ -98,13 +98,10 @@ pub async fn get_notifications(_req: Request, ctx: Context) -> Result<Response,
 
     info!("Getting notifications for user: {}", user_id);
 
-    // For now, return a placeholder response since the method doesn't exist yet
-    // This would need to be implemented in the NotificationService
-    Ok(Response::ok(serde_json::json!({
-        "success": true,
-        "notifications": [],
-        "message": "Notification history retrieval not yet implemented"
-    })))
+    // Notification history is not persisted by NotificationService yet
+    Ok(Response::not_implemented(
+        "Notification history retrieval not yet implemented",
+    ))
 }
 
 pub async fn mark_notification_read(_req: Request, ctx: Context) -> Result<Response, RouterError> {
@@ -113,14 +110,15 @@ pub async fn mark_notification_read(_req: Request, ctx: Context) -> Result<Respo
         .path_param("notificationId")
         .ok_or("Missing notificationId")?;
 
-    info!("Marking notification as read: {}", notification_id);
+    info!(
+        "Marking notification as read: {} for user: {}",
+        notification_id, user_id
+    );
 
-    // For now, return a placeholder response since the method doesn't exist yet
-    // This would need to be implemented in the NotificationService
-    Ok(Response::ok(serde_json::json!({
-        "success": true,
-        "message": "Notification read status update not yet implemented"
-    })))
+    // Read status is not tracked by NotificationService yet
+    Ok(Response::not_implemented(
+        "Notification read status update not yet implemented",
+    ))
 }
 
 // ==================== DEVICE HANDLERS ====================
@@ -203,13 +201,10 @@ pub async fn get_user_devices(_req: Request, ctx: Context) -> Result<Response, R
 
     info!("Getting devices for user: {}", user_id);
 
-    // For now, return a placeholder response since the method doesn't exist yet
-    // This would need to be implemented in the DeviceService
-    Ok(Response::ok(serde_json::json!({
-        "success": true,
-        "devices": [],
-        "message": "Device listing not yet implemented"
-    })))
+    // DeviceService does not expose a listing query yet
+    Ok(Response::not_implemented(
+        "Device listing not yet implemented",
+    ))
 }
 
 // ==================== PREFERENCES HANDLERS ====================
@@ -249,6 +244,12 @@ pub async fn update_preferences(req: Request, ctx: Context) -> Result<Response,
     let preferences: NotificationPreferences =
         serde_json::from_str(body).map_err(|e| format!("Invalid request body: {}", e))?;
 
+    if let Some(schedules) = &preferences.reminder_schedules {
+        if let Err(e) = crate::utils::validate_reminder_schedules(schedules) {
+            return Ok(Response::bad_request(&e.to_string()));
+        }
+    }
+
     info!("Updating notification preferences for user: {}", user_id);
 
     let preferences_service = match PreferencesService::new().await {
@@ -281,8 +282,15 @@ pub async fn update_preferences(req: Request, ctx: Context) -> Result<Response,
 
 pub async fn process_scheduled_notifications(
     _req: Request,
-    _ctx: Context,
+    ctx: Context,
 ) -> Result<Response, RouterError> {
+    // Only internal callers holding a service API key may trigger a run
+    if !get_auth_context(&ctx).is_some_and(|auth| auth.is_service()) {
+        return Ok(Response::forbidden(
+            "Scheduled processing requires service credentials",
+        ));
+    }
+
     info!("Processing scheduled notifications");
 
     let scheduler_service = match SchedulerService::new().await {
@@ -1,6 +1,7 @@
 use async_trait::async_trait;
-use lambda_router::{handler, Context, Middleware, Next, Request, Response, Router};
+use lambda_router::{handler, time_phase, Context, Middleware, Next, Request, Response, Router};
 use lambda_runtime::service_fn;
+use security_middleware::SecurityMiddleware;
 use lambda_runtime::Error as LambdaError;
 use once_cell::sync::Lazy;
 use tracing::{error, info};
@@ -10,11 +11,16 @@ mod models;
 mod services;
 mod utils;
 
-use auth_layer::{AuthLayer, LambdaEvent as AuthLambdaEvent};
+use auth_layer::{
+    build_request_context, with_token_expiry_header, ApiKeyProvider, AuthLayer,
+    LambdaEvent as AuthLambdaEvent,
+};
 use handlers::*;
 
-// Global auth layer
-static AUTH_LAYER: Lazy<AuthLayer> = Lazy::new(|| AuthLayer::new());
+// Global auth layer. Users sign in with Cognito; the scheduler and EventBridge call in
+// with a service API key (`SERVICE_API_KEYS`)
+static AUTH_LAYER: Lazy<AuthLayer> =
+    Lazy::new(|| AuthLayer::new().with_provider(ApiKeyProvider::from_env()));
 
 #[tokio::main]
 async fn main() -> Result<(), LambdaError> {
@@ -36,6 +42,12 @@ async fn main() -> Result<(), LambdaError> {
     info!("Creating router...");
     let mut router = Router::new();
 
+    // Write out auth metrics still buffered when the container shuts down
+    router.on_shutdown(|| async { AUTH_LAYER.flush_metrics() });
+
+    // Reject blocked IPs, injection payloads and oversized bodies before authenticating
+    router.use_middleware(SecurityMiddleware::new());
+
     // Add authentication middleware
     router.use_middleware(AuthMiddleware);
 
@@ -88,19 +100,14 @@ struct AuthMiddleware;
 impl Middleware for AuthMiddleware {
     async fn handle(&self, req: Request, next: Next) -> Result<Response, lambda_runtime::Error> {
         // Skip auth for OPTIONS requests (CORS preflight)
-        if req.method == "OPTIONS" {
+        if req.is_preflight() {
             return Ok(Response::cors_preflight());
         }
 
-        // Skip auth for scheduled notifications endpoint (triggered by EventBridge)
-        if req.path == "/api/notifications/scheduled/process" {
-            return next(req).await;
-        }
-
         // Convert to auth event format
         let auth_event = AuthLambdaEvent {
             headers: if !req.headers.is_empty() {
-                Some(req.headers.clone())
+                Some(req.headers.to_hash_map())
             } else {
                 None
             },
@@ -112,10 +119,11 @@ impl Middleware for AuthMiddleware {
                 None
             },
             body: req.body.clone(),
+            required_permissions: req.required_permissions().to_vec(),
         };
 
         // Authenticate request
-        match AUTH_LAYER.authenticate(&auth_event).await {
+        match time_phase("auth", AUTH_LAYER.authenticate(&auth_event)).await {
             Ok(auth_result) => {
                 if !auth_result.is_authorized {
                     return Ok(Response::forbidden(
@@ -126,18 +134,10 @@ impl Middleware for AuthMiddleware {
                 if let Some(auth_context) = auth_result.context {
                     // Create new request with auth info
                     let mut new_req = req.clone();
-                    new_req.context.user_id = Some(auth_context.user_id.clone());
-                    new_req.context.email = Some(auth_context.email.clone());
-
-                    // Store auth_context in custom data
-                    if let Ok(auth_json) = serde_json::to_value(&auth_context) {
-                        new_req
-                            .context
-                            .custom
-                            .insert("auth_context".to_string(), auth_json);
-                    }
-
-                    next(new_req).await
+                    new_req.set_context(build_request_context(req.context, &auth_context));
+
+                    let response = next(new_req).await?;
+                    Ok(with_token_expiry_header(response, auth_result.expires_in))
                 } else {
                     Ok(Response::unauthorized("Authentication required"))
                 }
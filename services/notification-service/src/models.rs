@@ -1,5 +1,6 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use validator::Validate;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Validate)]
@@ -70,6 +71,11 @@ pub struct NotificationPreferences {
 
     #[validate(length(min = 1, max = 50))]
     pub timezone: Option<String>,
+
+    /// Cron expressions keyed by notification type (e.g. "water_reminder"); types
+    /// without one keep the scheduler's built-in cadence
+    #[serde(default)]
+    pub reminder_schedules: Option<HashMap<String, String>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -107,6 +113,7 @@ impl Default for NotificationPreferences {
                 "19:00".to_string(),
             ]),
             timezone: Some("UTC".to_string()),
+            reminder_schedules: None,
         }
     }
 }
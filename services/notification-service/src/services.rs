@@ -608,6 +608,15 @@ impl PreferencesService {
             AttributeValue::S("NOTIFICATION_PREFERENCES".to_string()),
         );
         item.insert("userId".to_string(), AttributeValue::S(user_id.to_string()));
+        // Lets the scheduler query preference items on GSI1 instead of scanning the table
+        item.insert(
+            "GSI1PK".to_string(),
+            AttributeValue::S("NOTIFICATION_PREFERENCES".to_string()),
+        );
+        item.insert(
+            "GSI1SK".to_string(),
+            AttributeValue::S(format!("USER#{}", user_id)),
+        );
         item.insert(
             "workoutReminders".to_string(),
             AttributeValue::Bool(preferences.workout_reminders),
@@ -654,6 +663,19 @@ impl PreferencesService {
             item.insert("timezone".to_string(), AttributeValue::S(tz.clone()));
         }
 
+        if let Some(schedules) = &preferences.reminder_schedules {
+            let schedules_attr: HashMap<String, AttributeValue> = schedules
+                .iter()
+                .map(|(notification_type, cron)| {
+                    (notification_type.clone(), AttributeValue::S(cron.clone()))
+                })
+                .collect();
+            item.insert(
+                "reminderSchedules".to_string(),
+                AttributeValue::M(schedules_attr),
+            );
+        }
+
         self.dynamodb
             .put_item()
             .table_name(&self.table_name)
@@ -739,6 +761,17 @@ impl PreferencesService {
             .and_then(|v| v.as_s().ok())
             .map(|s| s.to_string());
 
+        let reminder_schedules = item
+            .get("reminderSchedules")
+            .and_then(|v| v.as_m().ok())
+            .map(|map| {
+                map.iter()
+                    .filter_map(|(notification_type, v)| {
+                        Some((notification_type.clone(), v.as_s().ok()?.clone()))
+                    })
+                    .collect()
+            });
+
         Ok(NotificationPreferences {
             workout_reminders: *workout_reminders,
             nutrition_reminders: *nutrition_reminders,
@@ -749,6 +782,7 @@ impl PreferencesService {
             workout_reminder_time,
             nutrition_reminder_times,
             timezone,
+            reminder_schedules,
         })
     }
 }
@@ -2,6 +2,7 @@ use anyhow::{anyhow, Result};
 use chrono::{DateTime, Utc, TimeZone, Timelike};
 use serde_json::Value;
 use std::collections::HashMap;
+use std::str::FromStr;
 
 pub fn validate_notification_type(notification_type: &str) -> Result<()> {
     let valid_types = [
@@ -69,6 +70,30 @@ pub fn validate_timezone(timezone: &str) -> Result<()> {
     }
 }
 
+/// Check a reminder cron expression the way the scheduler will parse it: standard
+/// 5-field expressions get a leading seconds field, 6 and 7 fields are used as-is
+pub fn validate_cron_expression(expression: &str) -> Result<()> {
+    let expression = expression.trim();
+    let normalized = if expression.split_whitespace().count() == 5 {
+        format!("0 {}", expression)
+    } else {
+        expression.to_string()
+    };
+    
+    cron::Schedule::from_str(&normalized)
+        .map(|_| ())
+        .map_err(|e| anyhow!("Invalid cron expression '{}': {}", expression, e))
+}
+
+pub fn validate_reminder_schedules(schedules: &HashMap<String, String>) -> Result<()> {
+    for (notification_type, expression) in schedules {
+        validate_notification_type(notification_type)?;
+        validate_cron_expression(expression)?;
+    }
+    
+    Ok(())
+}
+
 pub fn parse_timezone(timezone: &str) -> Result<chrono_tz::Tz> {
     timezone.parse()
         .map_err(|_| anyhow!("Invalid timezone: {}", timezone))
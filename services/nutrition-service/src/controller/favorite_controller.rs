@@ -2,8 +2,9 @@ use serde_json::{json, Value};
 use anyhow::Result;
 use tracing::error;
 
+use crate::models::*;
 use crate::service::FavoriteService;
-use crate::utils::ResponseBuilder;
+use crate::utils::{ResponseBuilder, DataHelper};
 use auth_layer::AuthContext;
 
 #[derive(Clone)]
@@ -16,8 +17,17 @@ impl FavoriteController {
         Self { favorite_service }
     }
 
-    pub async fn add_favorite_food(&self, user_id: &str, food_id: &str, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-        match self.favorite_service.add_favorite_food(user_id, food_id, auth_context).await {
+    pub async fn add_favorite_food(&self, user_id: &str, food_id: &str, body: Option<&str>, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        // Position and tags are optional, so the body may be absent
+        let request = match body.filter(|body| !body.trim().is_empty()) {
+            Some(body) => match DataHelper::parse_json_to_type::<FavoriteFoodRequest>(body) {
+                Ok(request) => request,
+                Err(_) => return Ok(ResponseBuilder::bad_request("Invalid favorite request")),
+            },
+            None => FavoriteFoodRequest::default(),
+        };
+
+        match self.favorite_service.add_favorite_food(user_id, food_id, &request, auth_context).await {
             Ok(_) => Ok(ResponseBuilder::ok(json!({
                 "message": "Favorite added"
             }))),
@@ -52,6 +62,54 @@ impl FavoriteController {
         }
     }
 
+    pub async fn update_favorite_food(&self, user_id: &str, food_id: &str, body: &str, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        let request: FavoriteFoodRequest = match DataHelper::parse_json_to_type(body) {
+            Ok(request) => request,
+            Err(_) => return Ok(ResponseBuilder::bad_request("Invalid favorite request")),
+        };
+
+        match self.favorite_service.update_favorite_food(user_id, food_id, &request, auth_context).await {
+            Ok(_) => Ok(ResponseBuilder::ok(json!({
+                "message": "Favorite updated"
+            }))),
+            Err(e) => {
+                error!("Error updating favorite: {}", e);
+                let msg = e.to_string();
+                if msg.contains("You can only") {
+                    Ok(ResponseBuilder::forbidden(&msg))
+                } else if msg.contains("not found") {
+                    Ok(ResponseBuilder::not_found(&msg))
+                } else {
+                    Ok(ResponseBuilder::internal_server_error("Failed to update favorite"))
+                }
+            }
+        }
+    }
+
+    pub async fn reorder_favorite_foods(&self, user_id: &str, body: &str, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        let request: ReorderFavoritesRequest = match DataHelper::parse_json_to_type(body) {
+            Ok(request) => request,
+            Err(_) => return Ok(ResponseBuilder::bad_request("Expected a food_ids array")),
+        };
+
+        match self.favorite_service.reorder_favorite_foods(user_id, &request.food_ids, auth_context).await {
+            Ok(_) => Ok(ResponseBuilder::ok(json!({
+                "message": "Favorites reordered"
+            }))),
+            Err(e) => {
+                error!("Error reordering favorites: {}", e);
+                let msg = e.to_string();
+                if msg.contains("You can only") {
+                    Ok(ResponseBuilder::forbidden(&msg))
+                } else if msg.contains("not found") {
+                    Ok(ResponseBuilder::bad_request(&msg))
+                } else {
+                    Ok(ResponseBuilder::internal_server_error("Failed to reorder favorites"))
+                }
+            }
+        }
+    }
+
     pub async fn list_favorite_foods(&self, user_id: &str, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
         match self.favorite_service.list_favorite_foods(user_id, auth_context).await {
             Ok(foods) => Ok(ResponseBuilder::ok(json!({
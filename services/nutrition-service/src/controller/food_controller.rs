@@ -5,6 +5,7 @@ use tracing::error;
 use crate::models::*;
 use crate::service::FoodService;
 use crate::utils::{ResponseBuilder, DataHelper};
+use lambda_router::PageParams;
 
 #[derive(Clone)]
 pub struct FoodController {
@@ -57,15 +58,9 @@ impl FoodController {
         }
     }
 
-    pub async fn search_foods(&self, query: &str, limit: Option<u32>, cursor: Option<String>) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-        match self.food_service.search_foods(query, limit, cursor).await {
-            Ok((foods, next_cursor)) => Ok(ResponseBuilder::ok(json!({
-                "foods": foods,
-                "query": query,
-                "count": foods.len(),
-                "limit": limit.unwrap_or(20),
-                "next_cursor": next_cursor
-            }))),
+    pub async fn search_foods(&self, query: &str, page: &PageParams) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        match self.food_service.search_foods(query, page).await {
+            Ok(results) => Ok(ResponseBuilder::ok(results)),
             Err(e) => {
                 error!("Error searching foods: {}", e);
                 Ok(ResponseBuilder::internal_server_error("Failed to search foods"))
@@ -6,6 +6,7 @@ use crate::models::*;
 use crate::service::MealService;
 use crate::utils::{ResponseBuilder, DataHelper};
 use auth_layer::AuthContext;
+use lambda_router::PageParams;
 
 #[derive(Clone)]
 pub struct MealController {
@@ -98,12 +99,59 @@ impl MealController {
         }
     }
 
-    pub async fn get_user_meals(&self, user_id: &str, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-        match self.meal_service.get_user_meals(user_id, auth_context).await {
-            Ok(meals) => Ok(ResponseBuilder::ok(json!({
-                "meals": meals,
-                "count": meals.len()
-            }))),
+    pub async fn get_meals_by_range(&self, user_id: &str, start: &str, end: &str, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        match self.meal_service.get_meals_by_range(user_id, start, end, auth_context).await {
+            Ok(meals_by_date) => {
+                let count: usize = meals_by_date.values().map(Vec::len).sum();
+                Ok(ResponseBuilder::ok(json!({
+                    "start": start,
+                    "end": end,
+                    "days": meals_by_date,
+                    "count": count
+                })))
+            }
+            Err(e) => {
+                error!("Error fetching meals by range: {}", e);
+                let msg = e.to_string();
+                if msg.contains("You can only access") {
+                    Ok(ResponseBuilder::forbidden(&msg))
+                } else if msg.contains("Invalid date") {
+                    Ok(ResponseBuilder::bad_request(&msg))
+                } else {
+                    Ok(ResponseBuilder::internal_server_error("Failed to retrieve meals"))
+                }
+            }
+        }
+    }
+
+    pub async fn migrate_meal_keys(&self, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        match self.meal_service.migrate_meal_keys(auth_context).await {
+            Ok(migrated) => Ok(ResponseBuilder::ok(json!({ "migrated": migrated }))),
+            Err(e) => {
+                error!("Error migrating meal keys: {}", e);
+                Ok(ResponseBuilder::internal_server_error("Failed to migrate meal keys"))
+            }
+        }
+    }
+
+    /// `{meals, count}` with every meal, or a `ListResponse` page when `page` is given
+    pub async fn get_user_meals(&self, user_id: &str, page: Option<PageParams>, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        let result = match page {
+            Some(params) => self.meal_service
+                .get_user_meals_page(user_id, &params, auth_context)
+                .await
+                .map(|page| json!(page)),
+            None => self.meal_service
+                .get_user_meals(user_id, auth_context)
+                .await
+                .map(|meals| json!({
+                    "meals": meals,
+                    "count": meals.len()
+                })),
+        };
+
+        match result {
+            Ok(body) => Ok(ResponseBuilder::ok(body)),
             Err(e) => {
                 error!("Error fetching user meals: {}", e);
                 let msg = e.to_string();
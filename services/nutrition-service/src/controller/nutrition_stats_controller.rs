@@ -30,4 +30,21 @@ impl NutritionStatsController {
             }
         }
     }
+
+    pub async fn get_nutrition_stats_range(&self, user_id: &str, start: &str, end: &str, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        match self.nutrition_stats_service.get_nutrition_stats_range(user_id, start, end, auth_context).await {
+            Ok(stats) => Ok(ResponseBuilder::ok(stats)),
+            Err(e) => {
+                error!("Error fetching nutrition stats range: {}", e);
+                let msg = e.to_string();
+                if msg.contains("You can only access") {
+                    Ok(ResponseBuilder::forbidden(&msg))
+                } else if msg.contains("Invalid date") {
+                    Ok(ResponseBuilder::bad_request(&msg))
+                } else {
+                    Ok(ResponseBuilder::internal_server_error("Failed to get nutrition statistics"))
+                }
+            }
+        }
+    }
 }
@@ -18,14 +18,7 @@ impl WaterController {
 
     pub async fn get_water(&self, user_id: &str, date: &str, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
         match self.water_service.get_water(user_id, date, auth_context).await {
-            Ok(Some(glasses)) => Ok(ResponseBuilder::ok(json!({
-                "date": date,
-                "glasses": glasses
-            }))),
-            Ok(None) => Ok(ResponseBuilder::ok(json!({
-                "date": date,
-                "glasses": 0
-            }))),
+            Ok(progress) => Ok(ResponseBuilder::ok(json!(progress))),
             Err(e) => {
                 error!("Error fetching water intake: {}", e);
                 let msg = e.to_string();
@@ -63,4 +56,42 @@ impl WaterController {
             }
         }
     }
+
+    pub async fn add_water(&self, user_id: &str, date: &str, body: &str, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        let parsed: Result<Value, _> = DataHelper::parse_json_safe(body);
+        let glasses = match parsed {
+            Ok(json) => json.get("glasses")
+                .and_then(|v| v.as_u64())
+                .unwrap_or(1) as u32,
+            Err(_) => 1,
+        };
+
+        match self.water_service.add_water(user_id, date, glasses, auth_context).await {
+            Ok(progress) => Ok(ResponseBuilder::ok(json!(progress))),
+            Err(e) => {
+                error!("Error adding water intake: {}", e);
+                let msg = e.to_string();
+                if msg.contains("You can only") {
+                    Ok(ResponseBuilder::forbidden(&msg))
+                } else {
+                    Ok(ResponseBuilder::internal_server_error("Failed to add water intake"))
+                }
+            }
+        }
+    }
+
+    pub async fn reset_water(&self, user_id: &str, date: &str, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        match self.water_service.reset_water(user_id, date, auth_context).await {
+            Ok(progress) => Ok(ResponseBuilder::ok(json!(progress))),
+            Err(e) => {
+                error!("Error resetting water intake: {}", e);
+                let msg = e.to_string();
+                if msg.contains("You can only") {
+                    Ok(ResponseBuilder::forbidden(&msg))
+                } else {
+                    Ok(ResponseBuilder::internal_server_error("Failed to reset water intake"))
+                }
+            }
+        }
+    }
 }
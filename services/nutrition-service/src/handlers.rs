@@ -1,4 +1,5 @@
-use lambda_router::{Request, Response, Context, RouterError};
+use lambda_router::{Request, Response, Context, RouterError, PageParams};
+use lambda_router::pagination::DEFAULT_PAGE_LIMIT;
 use tracing::error;
 use auth_layer::AuthContext;
 
@@ -91,11 +92,15 @@ pub async fn get_meals_by_date(req: Request, ctx: Context) -> Result<Response, R
 
 pub async fn get_user_meals(req: Request, ctx: Context) -> Result<Response, RouterError> {
     let user_id = req.path_param("userId").ok_or("Missing userId")?;
+    let page = match req.page_params() {
+        Ok(page) => page,
+        Err(response) => return Ok(*response),
+    };
     let auth_context = get_auth_context(&ctx);
     
     let controller = MEAL_CONTROLLER.get().ok_or("Controller not initialized")?;
     
-    match controller.get_user_meals(user_id, &auth_context).await {
+    match controller.get_user_meals(user_id, page, &auth_context).await {
         Ok(response_value) => Ok(Response::from_json_value(response_value)),
         Err(e) => {
             error!("Error in get_user_meals handler: {}", e);
@@ -106,11 +111,15 @@ pub async fn get_user_meals(req: Request, ctx: Context) -> Result<Response, Rout
 
 pub async fn get_user_meals_me(req: Request, ctx: Context) -> Result<Response, RouterError> {
     let user_id = ctx.user_id.as_ref().ok_or("Unauthorized")?.clone();
+    let page = match req.page_params() {
+        Ok(page) => page,
+        Err(response) => return Ok(*response),
+    };
     let auth_context = get_auth_context(&ctx);
     
     let controller = MEAL_CONTROLLER.get().ok_or("Controller not initialized")?;
     
-    match controller.get_user_meals(&user_id, &auth_context).await {
+    match controller.get_user_meals(&user_id, page, &auth_context).await {
         Ok(response_value) => Ok(Response::from_json_value(response_value)),
         Err(e) => {
             error!("Error in get_user_meals_me handler: {}", e);
@@ -135,6 +144,37 @@ pub async fn get_meals_by_date_me(req: Request, ctx: Context) -> Result<Response
     }
 }
 
+pub async fn get_meals_by_range_me(req: Request, ctx: Context) -> Result<Response, RouterError> {
+    let user_id = ctx.user_id.as_ref().ok_or("Unauthorized")?.clone();
+    let start = req.query("start").ok_or("Missing query parameter 'start'")?;
+    let end = req.query("end").ok_or("Missing query parameter 'end'")?;
+    let auth_context = get_auth_context(&ctx);
+    
+    let controller = MEAL_CONTROLLER.get().ok_or("Controller not initialized")?;
+    
+    match controller.get_meals_by_range(&user_id, start, end, &auth_context).await {
+        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Err(e) => {
+            error!("Error in get_meals_by_range_me handler: {}", e);
+            Ok(Response::internal_error("Failed to process request"))
+        }
+    }
+}
+
+pub async fn migrate_meal_keys_me(_req: Request, ctx: Context) -> Result<Response, RouterError> {
+    let auth_context = get_auth_context(&ctx);
+    
+    let controller = MEAL_CONTROLLER.get().ok_or("Controller not initialized")?;
+    
+    match controller.migrate_meal_keys(&auth_context).await {
+        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Err(e) => {
+            error!("Error in migrate_meal_keys_me handler: {}", e);
+            Ok(Response::internal_error("Failed to process request"))
+        }
+    }
+}
+
 pub async fn update_meal(req: Request, ctx: Context) -> Result<Response, RouterError> {
     let user_id = req.path_param("userId").ok_or("Missing userId")?;
     let meal_id = req.path_param("mealId").ok_or("Missing mealId")?;
@@ -200,12 +240,15 @@ pub async fn get_food(req: Request, _ctx: Context) -> Result<Response, RouterErr
 
 pub async fn search_foods(req: Request, _ctx: Context) -> Result<Response, RouterError> {
     let query = req.query("q").ok_or("Missing query parameter 'q'")?;
-    let limit = req.query("limit").and_then(|s| s.parse::<u32>().ok());
-    let cursor = req.query("cursor").cloned();
+    // Search results are always paged
+    let page = match req.page_params() {
+        Ok(page) => page.unwrap_or(PageParams { limit: DEFAULT_PAGE_LIMIT, cursor: None, include_total: false }),
+        Err(response) => return Ok(*response),
+    };
     
     let controller = FOOD_CONTROLLER.get().ok_or("Controller not initialized")?;
     
-    match controller.search_foods(query, limit, cursor).await {
+    match controller.search_foods(query, &page).await {
         Ok(response_value) => Ok(Response::from_json_value(response_value)),
         Err(e) => {
             error!("Error in search_foods handler: {}", e);
@@ -223,7 +266,7 @@ pub async fn add_favorite_food(req: Request, ctx: Context) -> Result<Response, R
     
     let controller = FAVORITE_CONTROLLER.get().ok_or("Controller not initialized")?;
     
-    match controller.add_favorite_food(user_id, food_id, &auth_context).await {
+    match controller.add_favorite_food(user_id, food_id, req.body(), &auth_context).await {
         Ok(response_value) => Ok(Response::from_json_value(response_value)),
         Err(e) => {
             error!("Error in add_favorite_food handler: {}", e);
@@ -285,7 +328,7 @@ pub async fn add_favorite_food_me(req: Request, ctx: Context) -> Result<Response
     
     let controller = FAVORITE_CONTROLLER.get().ok_or("Controller not initialized")?;
     
-    match controller.add_favorite_food(user_id, food_id, &auth_context).await {
+    match controller.add_favorite_food(user_id, food_id, req.body(), &auth_context).await {
         Ok(response_value) => Ok(Response::from_json_value(response_value)),
         Err(e) => {
             error!("Error in add_favorite_food_me handler: {}", e);
@@ -310,6 +353,72 @@ pub async fn remove_favorite_food_me(req: Request, ctx: Context) -> Result<Respo
     }
 }
 
+pub async fn update_favorite_food(req: Request, ctx: Context) -> Result<Response, RouterError> {
+    let user_id = req.path_param("userId").ok_or("Missing userId")?;
+    let food_id = req.path_param("foodId").ok_or("Missing foodId")?;
+    let auth_context = get_auth_context(&ctx);
+    let body = req.body().ok_or("Missing request body")?;
+    
+    let controller = FAVORITE_CONTROLLER.get().ok_or("Controller not initialized")?;
+    
+    match controller.update_favorite_food(user_id, food_id, body, &auth_context).await {
+        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Err(e) => {
+            error!("Error in update_favorite_food handler: {}", e);
+            Ok(Response::internal_error("Failed to process request"))
+        }
+    }
+}
+
+pub async fn reorder_favorite_foods(req: Request, ctx: Context) -> Result<Response, RouterError> {
+    let user_id = req.path_param("userId").ok_or("Missing userId")?;
+    let auth_context = get_auth_context(&ctx);
+    let body = req.body().ok_or("Missing request body")?;
+    
+    let controller = FAVORITE_CONTROLLER.get().ok_or("Controller not initialized")?;
+    
+    match controller.reorder_favorite_foods(user_id, body, &auth_context).await {
+        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Err(e) => {
+            error!("Error in reorder_favorite_foods handler: {}", e);
+            Ok(Response::internal_error("Failed to process request"))
+        }
+    }
+}
+
+pub async fn update_favorite_food_me(req: Request, ctx: Context) -> Result<Response, RouterError> {
+    let auth_context = get_auth_context(&ctx);
+    let user_id = &auth_context.user_id;
+    let food_id = req.path_param("foodId").ok_or("Missing foodId")?;
+    let body = req.body().ok_or("Missing request body")?;
+    
+    let controller = FAVORITE_CONTROLLER.get().ok_or("Controller not initialized")?;
+    
+    match controller.update_favorite_food(user_id, food_id, body, &auth_context).await {
+        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Err(e) => {
+            error!("Error in update_favorite_food_me handler: {}", e);
+            Ok(Response::internal_error("Failed to process request"))
+        }
+    }
+}
+
+pub async fn reorder_favorite_foods_me(req: Request, ctx: Context) -> Result<Response, RouterError> {
+    let auth_context = get_auth_context(&ctx);
+    let user_id = &auth_context.user_id;
+    let body = req.body().ok_or("Missing request body")?;
+    
+    let controller = FAVORITE_CONTROLLER.get().ok_or("Controller not initialized")?;
+    
+    match controller.reorder_favorite_foods(user_id, body, &auth_context).await {
+        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Err(e) => {
+            error!("Error in reorder_favorite_foods_me handler: {}", e);
+            Ok(Response::internal_error("Failed to process request"))
+        }
+    }
+}
+
 // ==================== NUTRITION PLAN HANDLERS ====================
 
 pub async fn create_nutrition_plan(req: Request, ctx: Context) -> Result<Response, RouterError> {
@@ -361,6 +470,23 @@ pub async fn get_nutrition_stats(req: Request, ctx: Context) -> Result<Response,
     }
 }
 
+pub async fn get_nutrition_stats_range_me(req: Request, ctx: Context) -> Result<Response, RouterError> {
+    let user_id = ctx.user_id.as_ref().ok_or("Unauthorized")?.clone();
+    let start = req.query("start").ok_or("Missing query parameter 'start'")?;
+    let end = req.query("end").ok_or("Missing query parameter 'end'")?;
+    let auth_context = get_auth_context(&ctx);
+    
+    let controller = NUTRITION_STATS_CONTROLLER.get().ok_or("Controller not initialized")?;
+    
+    match controller.get_nutrition_stats_range(&user_id, start, end, &auth_context).await {
+        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Err(e) => {
+            error!("Error in get_nutrition_stats_range_me handler: {}", e);
+            Ok(Response::internal_error("Failed to process request"))
+        }
+    }
+}
+
 // ==================== WATER HANDLERS ====================
 
 pub async fn get_water(req: Request, ctx: Context) -> Result<Response, RouterError> {
@@ -428,3 +554,69 @@ pub async fn set_water_me(req: Request, ctx: Context) -> Result<Response, Router
         }
     }
 }
+
+pub async fn add_water(req: Request, ctx: Context) -> Result<Response, RouterError> {
+    let user_id = req.path_param("userId").ok_or("Missing userId")?;
+    let date = req.path_param("date").ok_or("Missing date")?;
+    let auth_context = get_auth_context(&ctx);
+    let body = req.body().unwrap_or("{}");
+    
+    let controller = WATER_CONTROLLER.get().ok_or("Controller not initialized")?;
+    
+    match controller.add_water(user_id, date, body, &auth_context).await {
+        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Err(e) => {
+            error!("Error in add_water handler: {}", e);
+            Ok(Response::internal_error("Failed to process request"))
+        }
+    }
+}
+
+pub async fn add_water_me(req: Request, ctx: Context) -> Result<Response, RouterError> {
+    let date = req.path_param("date").ok_or("Missing date")?;
+    let auth_context = get_auth_context(&ctx);
+    let user_id = &auth_context.user_id;
+    let body = req.body().unwrap_or("{}");
+    
+    let controller = WATER_CONTROLLER.get().ok_or("Controller not initialized")?;
+    
+    match controller.add_water(user_id, date, body, &auth_context).await {
+        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Err(e) => {
+            error!("Error in add_water_me handler: {}", e);
+            Ok(Response::internal_error("Failed to process request"))
+        }
+    }
+}
+
+pub async fn reset_water(req: Request, ctx: Context) -> Result<Response, RouterError> {
+    let user_id = req.path_param("userId").ok_or("Missing userId")?;
+    let date = req.path_param("date").ok_or("Missing date")?;
+    let auth_context = get_auth_context(&ctx);
+    
+    let controller = WATER_CONTROLLER.get().ok_or("Controller not initialized")?;
+    
+    match controller.reset_water(user_id, date, &auth_context).await {
+        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Err(e) => {
+            error!("Error in reset_water handler: {}", e);
+            Ok(Response::internal_error("Failed to process request"))
+        }
+    }
+}
+
+pub async fn reset_water_me(req: Request, ctx: Context) -> Result<Response, RouterError> {
+    let date = req.path_param("date").ok_or("Missing date")?;
+    let auth_context = get_auth_context(&ctx);
+    let user_id = &auth_context.user_id;
+    
+    let controller = WATER_CONTROLLER.get().ok_or("Controller not initialized")?;
+    
+    match controller.reset_water(user_id, date, &auth_context).await {
+        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Err(e) => {
+            error!("Error in reset_water_me handler: {}", e);
+            Ok(Response::internal_error("Failed to process request"))
+        }
+    }
+}
@@ -2,10 +2,12 @@ use async_trait::async_trait;
 use aws_config::meta::region::RegionProviderChain;
 use aws_sdk_dynamodb::Client as DynamoDbClient;
 use aws_sdk_s3::Client as S3Client;
-use lambda_router::{handler, Context, Middleware, Next, Request, Response, Router, RouterError};
+use lambda_router::{handler, time_phase, CacheMiddleware, Context, Middleware, Next, Request, Response, Router, RouterError};
 use lambda_runtime::service_fn;
 use lambda_runtime::Error as LambdaError;
 use once_cell::sync::{Lazy, OnceCell};
+use security_middleware::SecurityMiddleware;
+use service_config::ServiceConfig;
 use std::sync::Arc;
 use tracing::{error, info};
 
@@ -16,7 +18,9 @@ mod repository;
 mod service;
 mod utils;
 
-use auth_layer::{AuthLayer, LambdaEvent as AuthLambdaEvent};
+use auth_layer::{
+    build_request_context, with_token_expiry_header, AuthLayer, LambdaEvent as AuthLambdaEvent,
+};
 use controller::{
     FavoriteController, FoodController, MealController, NutritionPlanController,
     NutritionStatsController, WaterController,
@@ -28,12 +32,15 @@ use service::{
     FavoriteService, FoodService, MealService, NutritionPlanService, NutritionStatsService,
     WaterService,
 };
+use utils::constants::{DEFAULT_MEAL_RANGE_MAX_DAYS, MEAL_RANGE_MAX_DAYS_ENV};
 
 // Import all handler functions
 use handlers::{
     // Favorite handlers
     add_favorite_food,
     add_favorite_food_me,
+    add_water,
+    add_water_me,
     // Food handlers
     create_food,
     // Meal handlers
@@ -46,21 +53,30 @@ use handlers::{
     get_meal,
     get_meals_by_date,
     get_meals_by_date_me,
+    get_meals_by_range_me,
     get_nutrition_plan,
     // Nutrition stats handlers
     get_nutrition_stats,
+    get_nutrition_stats_range_me,
     get_user_meals,
     get_user_meals_me,
     // Water handlers
     get_water,
     get_water_me,
     list_favorite_foods,
+    migrate_meal_keys_me,
     list_favorite_foods_me,
     remove_favorite_food,
     remove_favorite_food_me,
+    reorder_favorite_foods,
+    reorder_favorite_foods_me,
+    reset_water,
+    reset_water_me,
     search_foods,
     set_water,
     set_water_me,
+    update_favorite_food,
+    update_favorite_food_me,
     update_meal,
 };
 
@@ -93,12 +109,20 @@ async fn main() -> Result<(), LambdaError> {
 
     info!("Starting Nutrition Service initialization...");
 
+    // Fail fast on missing configuration, before anything is initialized
+    let service_config = ServiceConfig::from_env()?;
+
     // Initialize global clients
     info!("Initializing AWS clients...");
     if DYNAMODB_CLIENT.get().is_none() || S3_CLIENT.get().is_none() {
         let region_provider = RegionProviderChain::default_provider();
         let config = aws_config::from_env().region(region_provider).load().await;
-        let _ = DYNAMODB_CLIENT.set(Arc::new(DynamoDbClient::new(&config)));
+        let mut dynamodb_config = aws_sdk_dynamodb::config::Builder::from(&config);
+        if let Some(endpoint_url) = &service_config.dynamodb_endpoint_url {
+            info!("Using DynamoDB endpoint {}", endpoint_url);
+            dynamodb_config = dynamodb_config.endpoint_url(endpoint_url);
+        }
+        let _ = DYNAMODB_CLIENT.set(Arc::new(DynamoDbClient::from_conf(dynamodb_config.build())));
         let _ = S3_CLIENT.set(Arc::new(S3Client::new(&config)));
         info!("AWS clients initialized successfully");
     }
@@ -109,16 +133,25 @@ async fn main() -> Result<(), LambdaError> {
 
     // Initialize controllers once
     info!("Initializing controllers...");
-    init_controllers();
+    init_controllers(&service_config);
     info!("Controllers initialized successfully");
 
     // Create router
     info!("Creating router...");
     let mut router = Router::new();
 
+    // Write out auth metrics still buffered when the container shuts down
+    router.on_shutdown(|| async { AUTH_LAYER.flush_metrics() });
+
+    // Reject blocked IPs, injection payloads and oversized bodies before authenticating
+    router.use_middleware(SecurityMiddleware::new());
+
     // Add authentication middleware
     router.use_middleware(AuthMiddleware);
 
+    // Food search hits the same few queries over and over
+    router.use_middleware(CacheMiddleware::new().route("/api/nutrition/foods/search", 300));
+
     // Meal routes
     router.post("/api/nutrition/users/:userId/meals", handler!(create_meal));
     router.get(
@@ -160,6 +193,14 @@ async fn main() -> Result<(), LambdaError> {
         "/api/nutrition/me/meals/date/:date",
         handler!(get_meals_by_date_me),
     );
+    router.get(
+        "/api/nutrition/me/meals/range",
+        handler!(get_meals_by_range_me),
+    );
+    router.post(
+        "/api/nutrition/me/meals/migrate-keys",
+        handler!(migrate_meal_keys_me),
+    );
     router.get("/nutrition/me/meals", handler!(get_user_meals_me));
     router.post("/me/meals", handler!(create_meal_me));
 
@@ -181,6 +222,14 @@ async fn main() -> Result<(), LambdaError> {
         "/api/nutrition/users/:userId/favorites/foods",
         handler!(list_favorite_foods),
     );
+    router.put(
+        "/api/nutrition/users/:userId/favorites/foods/:foodId",
+        handler!(update_favorite_food),
+    );
+    router.put(
+        "/api/nutrition/users/:userId/favorites/foods",
+        handler!(reorder_favorite_foods),
+    );
 
     // Alternative paths
     router.post(
@@ -209,6 +258,14 @@ async fn main() -> Result<(), LambdaError> {
         "/api/nutrition/me/favorites/foods/:foodId",
         handler!(remove_favorite_food_me),
     );
+    router.put(
+        "/api/nutrition/me/favorites/foods/:foodId",
+        handler!(update_favorite_food_me),
+    );
+    router.put(
+        "/api/nutrition/me/favorites/foods",
+        handler!(reorder_favorite_foods_me),
+    );
 
     // Nutrition plan routes
     router.post(
@@ -226,6 +283,10 @@ async fn main() -> Result<(), LambdaError> {
         handler!(get_nutrition_stats),
     );
     router.get("/api/users/:userId/stats", handler!(get_nutrition_stats));
+    router.get(
+        "/api/nutrition/me/stats/range",
+        handler!(get_nutrition_stats_range_me),
+    );
 
     // Water intake routes
     router.get(
@@ -238,10 +299,26 @@ async fn main() -> Result<(), LambdaError> {
     );
     router.get("/api/users/:userId/water/date/:date", handler!(get_water));
     router.post("/api/users/:userId/water/date/:date", handler!(set_water));
+    router.post(
+        "/api/nutrition/users/:userId/water/date/:date/add",
+        handler!(add_water),
+    );
+    router.delete(
+        "/api/nutrition/users/:userId/water/date/:date",
+        handler!(reset_water),
+    );
 
     // Water intake /me routes
     router.get("/api/nutrition/me/water/date/:date", handler!(get_water_me));
     router.post("/api/nutrition/me/water/date/:date", handler!(set_water_me));
+    router.post(
+        "/api/nutrition/me/water/date/:date/add",
+        handler!(add_water_me),
+    );
+    router.delete(
+        "/api/nutrition/me/water/date/:date",
+        handler!(reset_water_me),
+    );
 
     info!("Nutrition Service initialized successfully");
     info!("Starting Lambda runtime...");
@@ -256,8 +333,8 @@ async fn main() -> Result<(), LambdaError> {
     result
 }
 
-fn init_controllers() {
-    let table_name = std::env::var("DYNAMODB_TABLE").unwrap_or_else(|_| "gymcoach-ai".to_string());
+fn init_controllers(config: &ServiceConfig) {
+    let table_name = &config.table_name;
     let dynamodb_client = DYNAMODB_CLIENT
         .get()
         .expect("DynamoDB not initialized")
@@ -270,12 +347,21 @@ fn init_controllers() {
     let water_repository = WaterRepository::new(dynamodb_client.clone(), table_name.clone());
     let favorite_repository = FavoriteRepository::new(dynamodb_client.clone(), table_name.clone());
 
-    let meal_service = MealService::new(meal_repository.clone(), food_repository.clone());
+    let max_range_days = std::env::var(MEAL_RANGE_MAX_DAYS_ENV)
+        .ok()
+        .and_then(|v| v.trim().parse::<i64>().ok())
+        .filter(|&days| days > 0)
+        .unwrap_or(DEFAULT_MEAL_RANGE_MAX_DAYS);
+
+    let meal_service = MealService::new(meal_repository.clone(), food_repository.clone())
+        .with_max_range_days(max_range_days);
     let food_service = FoodService::new(food_repository.clone());
     let nutrition_plan_service = NutritionPlanService::new(nutrition_plan_repository);
     let water_service = WaterService::new(water_repository.clone());
     let favorite_service = FavoriteService::new(favorite_repository, food_repository.clone());
-    let nutrition_stats_service = NutritionStatsService::new(meal_repository, water_repository);
+    let nutrition_stats_service =
+        NutritionStatsService::new(meal_repository, water_repository, food_repository.clone())
+            .with_max_range_days(max_range_days);
 
     let _ = MEAL_CONTROLLER.set(MealController::new(meal_service));
     let _ = FOOD_CONTROLLER.set(FoodController::new(food_service));
@@ -298,19 +384,19 @@ impl Middleware for AuthMiddleware {
 
         // Convert to auth event format
         let auth_event = AuthLambdaEvent {
-            headers: Some(req.headers.clone()),
+            headers: Some(req.headers.to_hash_map()),
             request_context: req
-                .raw_event()
-                .get("requestContext")
+                .request_context()
                 .and_then(|v| serde_json::from_value(v.clone()).ok()),
             path_parameters: Some(req.path_params.clone()),
             query_string_parameters: Some(req.query_params.clone()),
             body: req.body.clone(),
+            required_permissions: req.required_permissions().to_vec(),
         };
 
         // Authenticate request
         info!("Auth middleware: Calling auth layer...");
-        let auth_result = AUTH_LAYER.authenticate(&auth_event).await.map_err(|e| {
+        let auth_result = time_phase("auth", AUTH_LAYER.authenticate(&auth_event)).await.map_err(|e| {
             error!("Auth middleware: Authentication failed: {}", e);
             format!("Auth error: {}", e)
         })?;
@@ -328,27 +414,17 @@ impl Middleware for AuthMiddleware {
         }
 
         // Add user context
-        if let Some(auth_ctx) = auth_result.context {
+        if let Some(auth_ctx) = &auth_result.context {
             info!(
                 "Auth middleware: Adding user context for user: {}",
                 auth_ctx.user_id
             );
-            match serde_json::to_value(&auth_ctx) {
-                Ok(auth_ctx_value) => {
-                    req.set_context(
-                        Context::new(req.context.request_id.clone())
-                            .with_user(auth_ctx.user_id.clone(), Some(auth_ctx.email.clone()))
-                            .with_custom("auth_context".to_string(), auth_ctx_value),
-                    );
-                }
-                Err(e) => {
-                    error!("Failed to serialize auth context: {}", e);
-                    // Continue without auth context
-                }
-            }
+            let context = build_request_context(req.context.clone(), auth_ctx);
+            req.set_context(context);
         }
 
         info!("Auth middleware: Calling next handler...");
-        next(req).await
+        let response = next(req).await?;
+        Ok(with_token_expiry_header(response, auth_result.expires_in))
     }
 }
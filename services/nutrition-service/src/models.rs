@@ -172,6 +172,10 @@ pub struct Food {
     pub verified_by: Option<String>,
     pub verified_at: Option<DateTime<Utc>>,
     
+    /// How many times the food has been logged in a meal; breaks search ranking ties
+    #[serde(default)]
+    pub usage_count: u64,
+    
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -605,3 +609,60 @@ pub struct PaginationInfo {
     pub has_next: bool,
     pub has_prev: bool,
 }
+
+/// A day's water intake measured against the user's daily goal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WaterProgress {
+    pub date: String,
+    pub glasses: u32,
+    pub goal: u32,
+    pub percentage: f64,
+}
+
+impl WaterProgress {
+    pub fn new(date: &str, glasses: u32, goal: u32) -> Self {
+        let percentage = if goal > 0 {
+            (glasses as f64 / goal as f64 * 100.0).round()
+        } else {
+            0.0
+        };
+
+        Self {
+            date: date.to_string(),
+            glasses,
+            goal,
+            percentage,
+        }
+    }
+}
+
+/// A user's favorite food record: the food it points at plus the user's own ordering
+/// and tags
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FavoriteFood {
+    pub food_id: String,
+    pub position: Option<u32>,
+    pub tags: Vec<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A favorite as listed to the client: the food's fields with the favorite's annotations
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FavoriteFoodEntry {
+    #[serde(flatten)]
+    pub food: Food,
+    pub position: Option<u32>,
+    pub tags: Vec<String>,
+    pub favorited_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FavoriteFoodRequest {
+    pub position: Option<u32>,
+    pub tags: Option<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReorderFavoritesRequest {
+    pub food_ids: Vec<String>,
+}
@@ -1,8 +1,9 @@
-use aws_sdk_dynamodb::{Client as DynamoDbClient, types::AttributeValue};
+use aws_sdk_dynamodb::{Client as DynamoDbClient, types::AttributeValue, operation::update_item::UpdateItemError};
 use anyhow::Result;
 use tracing::error;
 use chrono::Utc;
 
+use crate::models::FavoriteFood;
 
 #[derive(Clone)]
 pub struct FavoriteRepository {
@@ -15,13 +16,17 @@ impl FavoriteRepository {
         Self { client, table_name }
     }
 
-    pub async fn add_favorite_food(&self, user_id: &str, food_id: &str) -> Result<()> {
+    pub async fn add_favorite_food(&self, user_id: &str, food_id: &str, position: Option<u32>, tags: &[String]) -> Result<()> {
         let mut item = std::collections::HashMap::new();
         item.insert("PK".to_string(), AttributeValue::S(format!("USER#{}", user_id)));
         item.insert("SK".to_string(), AttributeValue::S(format!("FAVORITE#FOOD#{}", food_id)));
         item.insert("EntityType".to_string(), AttributeValue::S("FAVORITE".to_string()));
         item.insert("UserId".to_string(), AttributeValue::S(user_id.to_string()));
         item.insert("FoodId".to_string(), AttributeValue::S(food_id.to_string()));
+        if let Some(position) = position {
+            item.insert("Position".to_string(), AttributeValue::N(position.to_string()));
+        }
+        item.insert("Tags".to_string(), tags_attribute(tags));
         item.insert("CreatedAt".to_string(), AttributeValue::S(Utc::now().to_rfc3339()));
 
         let request = self.client
@@ -55,7 +60,7 @@ impl FavoriteRepository {
         }
     }
 
-    pub async fn list_favorite_food_ids(&self, user_id: &str) -> Result<Vec<String>> {
+    pub async fn list_favorite_foods(&self, user_id: &str) -> Result<Vec<FavoriteFood>> {
         let request = self.client
             .query()
             .table_name(&self.table_name)
@@ -65,22 +70,93 @@ impl FavoriteRepository {
 
         match request.send().await {
             Ok(response) => {
-                let mut ids = Vec::new();
+                let mut favorites = Vec::new();
                 for item in response.items() {
-                    if let Some(fid) = item.get("FoodId").and_then(|v| v.as_s().ok()) {
-                        ids.push(fid.to_string());
-                    } else if let Some(sk) = item.get("SK").and_then(|v| v.as_s().ok()) {
-                        if let Some(fid) = sk.strip_prefix("FAVORITE#FOOD#") { 
-                            ids.push(fid.to_string()); 
-                        }
-                    }
+                    let food_id = item.get("FoodId")
+                        .and_then(|v| v.as_s().ok())
+                        .map(|fid| fid.as_str())
+                        .or_else(|| item.get("SK")
+                            .and_then(|v| v.as_s().ok())
+                            .and_then(|sk| sk.strip_prefix("FAVORITE#FOOD#")));
+                    let Some(food_id) = food_id else {
+                        continue;
+                    };
+
+                    favorites.push(FavoriteFood {
+                        food_id: food_id.to_string(),
+                        position: item.get("Position")
+                            .and_then(|v| v.as_n().ok())
+                            .and_then(|s| s.parse::<u32>().ok()),
+                        tags: item.get("Tags")
+                            .and_then(|v| v.as_l().ok())
+                            .map(|tags| tags.iter()
+                                .filter_map(|tag| tag.as_s().ok().cloned())
+                                .collect())
+                            .unwrap_or_default(),
+                        created_at: item.get("CreatedAt")
+                            .and_then(|v| v.as_s().ok())
+                            .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+                            .map(|dt| dt.with_timezone(&Utc))
+                            .unwrap_or_default(),
+                    });
                 }
-                Ok(ids)
+                Ok(favorites)
             }
             Err(e) => {
-                error!("Failed to list favorite ids: {}", e);
+                error!("Failed to list favorites: {}", e);
                 Err(anyhow::anyhow!("Failed to list favorites: {}", e))
             }
         }
     }
+
+    /// Replace a favorite's tags. Fails with "Favorite not found" if the food isn't a favorite
+    pub async fn update_favorite_tags(&self, user_id: &str, food_id: &str, tags: &[String]) -> Result<()> {
+        let request = self.client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(format!("USER#{}", user_id)))
+            .key("SK", AttributeValue::S(format!("FAVORITE#FOOD#{}", food_id)))
+            .update_expression("SET Tags = :tags")
+            .condition_expression("attribute_exists(SK)")
+            .expression_attribute_values(":tags", tags_attribute(tags));
+
+        match request.send().await {
+            Ok(_) => Ok(()),
+            Err(e) if matches!(e.as_service_error(), Some(UpdateItemError::ConditionalCheckFailedException(_))) => Err(anyhow::anyhow!("Favorite not found")),
+            Err(e) => {
+                error!("Failed to update favorite tags: {}", e);
+                Err(anyhow::anyhow!("Failed to update favorite: {}", e))
+            }
+        }
+    }
+
+    /// Set a favorite's manual position, or clear it with `None` so it sorts by recency
+    pub async fn set_favorite_position(&self, user_id: &str, food_id: &str, position: Option<u32>) -> Result<()> {
+        let mut request = self.client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(format!("USER#{}", user_id)))
+            .key("SK", AttributeValue::S(format!("FAVORITE#FOOD#{}", food_id)))
+            .condition_expression("attribute_exists(SK)");
+        request = match position {
+            Some(position) => request
+                .update_expression("SET #position = :position")
+                .expression_attribute_values(":position", AttributeValue::N(position.to_string())),
+            None => request.update_expression("REMOVE #position"),
+        };
+        let request = request.expression_attribute_names("#position", "Position");
+
+        match request.send().await {
+            Ok(_) => Ok(()),
+            Err(e) if matches!(e.as_service_error(), Some(UpdateItemError::ConditionalCheckFailedException(_))) => Err(anyhow::anyhow!("Favorite not found")),
+            Err(e) => {
+                error!("Failed to set favorite position: {}", e);
+                Err(anyhow::anyhow!("Failed to update favorite: {}", e))
+            }
+        }
+    }
+}
+
+fn tags_attribute(tags: &[String]) -> AttributeValue {
+    AttributeValue::L(tags.iter().map(|tag| AttributeValue::S(tag.clone())).collect())
 }
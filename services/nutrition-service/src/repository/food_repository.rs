@@ -1,9 +1,10 @@
 use anyhow::Result;
 use aws_sdk_dynamodb::{types::AttributeValue, Client as DynamoDbClient};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use tracing::{error, info};
 
 use crate::models::*;
+use crate::repository::batch_get;
 
 #[derive(Clone)]
 pub struct FoodRepository {
@@ -103,6 +104,10 @@ impl FoodRepository {
         );
 
         item.insert("Verified".to_string(), AttributeValue::Bool(food.verified));
+        item.insert(
+            "UsageCount".to_string(),
+            AttributeValue::N(food.usage_count.to_string()),
+        );
 
         if let Some(verified_by) = &food.verified_by {
             item.insert(
@@ -169,61 +174,96 @@ impl FoodRepository {
         }
     }
 
-    pub async fn search_foods(
-        &self,
-        query: &str,
-        limit: u32,
-        cursor: Option<String>,
-    ) -> Result<(Vec<Food>, Option<String>)> {
-        // Use GSI1 with fixed PK and nameLower prefix in SK
-        let prefix = query.to_lowercase();
-        info!("Querying foods on GSI1 begins_with: {}", prefix);
-
-        let mut request = self
-            .client
-            .query()
-            .table_name(&self.table_name)
-            .index_name("GSI1")
-            .key_condition_expression("GSI1PK = :pk AND begins_with(GSI1SK, :sk)")
-            .expression_attribute_values(":pk", AttributeValue::S("FOOD".to_string()))
-            .expression_attribute_values(":sk", AttributeValue::S(prefix))
-            .limit(limit as i32);
-
-        // Pagination support via ExclusiveStartKey using table PK/SK
-        if let Some(last_food_id) = cursor {
-            let mut eks = std::collections::HashMap::new();
-            eks.insert(
-                "PK".to_string(),
-                AttributeValue::S(format!("FOOD#{}", last_food_id)),
-            );
-            eks.insert(
-                "SK".to_string(),
-                AttributeValue::S(format!("FOOD#{}", last_food_id)),
-            );
-            request = request.set_exclusive_start_key(Some(eks));
-        }
+    /// The foods with the given ids, keyed by id, fetched with BatchGetItem rather than
+    /// one read each. Ids with no food are left out of the map
+    pub async fn get_foods_by_ids(&self, food_ids: &[String]) -> Result<HashMap<String, Food>> {
+        let unique_ids: HashSet<&String> = food_ids.iter().collect();
+        let keys = unique_ids
+            .into_iter()
+            .map(|food_id| {
+                HashMap::from([
+                    ("PK".to_string(), AttributeValue::S(format!("FOOD#{}", food_id))),
+                    ("SK".to_string(), AttributeValue::S(format!("FOOD#{}", food_id))),
+                ])
+            })
+            .collect();
+
+        batch_get(&self.client, &self.table_name, keys)
+            .await?
+            .iter()
+            .map(|item| self.item_to_food(item).map(|food| (food.id.clone(), food)))
+            .collect()
+    }
 
-        match request.send().await {
-            Ok(response) => {
-                info!("Query (GSI1) successful, found {} items", response.count());
-                let mut foods = Vec::new();
-                for (i, item) in response.items().iter().enumerate() {
-                    match self.item_to_food(item) {
-                        Ok(food) => foods.push(food),
-                        Err(e) => error!("Failed to parse food item {}: {}", i, e),
+    /// Call `on_match` with every food whose lowercase name contains `query`, unranked.
+    /// Reads the `FOOD` partition of GSI1 (`GSI1SK` is `nameLower#id`) with a `contains`
+    /// filter one page at a time, so the caller decides what to keep. A failed read fails
+    /// the whole search rather than returning the matches found before it
+    pub async fn for_each_search_match(&self, query: &str, mut on_match: impl FnMut(Food) + Send) -> Result<()> {
+        info!("Querying foods on GSI1 containing: {}", query);
+
+        let mut matched = 0;
+        let mut exclusive_start_key = None;
+
+        loop {
+            let mut request = self
+                .client
+                .query()
+                .table_name(&self.table_name)
+                .index_name("GSI1")
+                .key_condition_expression("GSI1PK = :pk")
+                .expression_attribute_values(":pk", AttributeValue::S("FOOD".to_string()))
+                .set_exclusive_start_key(exclusive_start_key);
+            if !query.is_empty() {
+                request = request
+                    .filter_expression("contains(GSI1SK, :query)")
+                    .expression_attribute_values(":query", AttributeValue::S(query.to_string()));
+            }
+
+            let response = request.send().await.map_err(|e| {
+                error!("Failed to search foods (query GSI1): {:?}", e);
+                anyhow::anyhow!("Failed to search foods: {}", e)
+            })?;
+            for (i, item) in response.items().iter().enumerate() {
+                match self.item_to_food(item) {
+                    Ok(food) => {
+                        matched += 1;
+                        on_match(food);
                     }
+                    Err(e) => error!("Failed to parse food item {}: {}", i, e),
                 }
-                // Build next cursor from LastEvaluatedKey (derive FoodId from PK)
-                let next_cursor = response.last_evaluated_key().and_then(|k| {
-                    k.get("PK")
-                        .and_then(|v| v.as_s().ok())
-                        .and_then(|s| s.strip_prefix("FOOD#").map(|v| v.to_string()))
-                });
-                Ok((foods, next_cursor))
             }
+
+            exclusive_start_key = response.last_evaluated_key().cloned();
+            if exclusive_start_key.is_none() {
+                break;
+            }
+        }
+
+        info!("Query (GSI1) found {} candidate foods", matched);
+        Ok(())
+    }
+
+    /// Count one more use of a food, atomically. Foods that no longer exist are left alone
+    pub async fn increment_usage_count(&self, food_id: &str) -> Result<()> {
+        let request = self
+            .client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(format!("FOOD#{}", food_id)))
+            .key("SK", AttributeValue::S(format!("FOOD#{}", food_id)))
+            .update_expression("ADD UsageCount :one")
+            .condition_expression("attribute_exists(PK)")
+            .expression_attribute_values(":one", AttributeValue::N("1".to_string()));
+
+        match request.send().await {
+            Ok(_) => Ok(()),
             Err(e) => {
-                error!("Failed to search foods (query GSI1): {:?}", e);
-                Ok((Vec::new(), None))
+                error!("Failed to increment food usage count: {}", e);
+                Err(anyhow::anyhow!(
+                    "Failed to increment food usage count: {}",
+                    e
+                ))
             }
         }
     }
@@ -363,6 +403,12 @@ impl FoodRepository {
             .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
             .map(|dt| dt.with_timezone(&chrono::Utc));
 
+        let usage_count = item
+            .get("UsageCount")
+            .and_then(|v| v.as_n().ok())
+            .and_then(|s| s.parse::<u64>().ok())
+            .unwrap_or(0);
+
         let created_at = item
             .get("CreatedAt")
             .and_then(|v| v.as_s().ok())
@@ -395,6 +441,7 @@ impl FoodRepository {
             verified,
             verified_by,
             verified_at,
+            usage_count,
             created_at,
             updated_at,
         })
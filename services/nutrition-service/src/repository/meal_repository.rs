@@ -1,11 +1,58 @@
 use anyhow::Result;
-use aws_sdk_dynamodb::{types::AttributeValue, types::ReturnValue, Client as DynamoDbClient};
-use chrono::{DateTime, Utc};
+use aws_sdk_dynamodb::operation::transact_write_items::TransactWriteItemsError;
+use aws_sdk_dynamodb::types::{AttributeValue, Delete, Put, ReturnValue, Select, TransactWriteItem};
+use aws_sdk_dynamodb::Client as DynamoDbClient;
+use chrono::{DateTime, NaiveDate, SecondsFormat, Utc};
 use std::collections::HashMap;
 use tracing::{error, info};
 
 use crate::models::*;
 
+/// Sort-key prefix shared by both meal key forms
+const MEAL_SK_PREFIX: &str = "MEAL#";
+/// Sort-key prefix of the items pointing a meal id at its time-prefixed key
+const MEAL_POINTER_SK_PREFIX: &str = "MEALID#";
+/// Attribute of a pointer item holding the meal's sort key
+const MEAL_SK_ATTRIBUTE: &str = "MealSK";
+/// Sort key of the marker `migrate_legacy_meals` leaves once none of a user's meals are
+/// under legacy keys any more; until then date-range reads also look for legacy meals
+const LEGACY_MEALS_MIGRATED_SK: &str = "MEALKEYS#MIGRATED";
+
+/// `MEAL#{meal_date}#{id}`, with the date in UTC at millisecond precision so keys sort
+/// chronologically and a newest-first query with a limit returns the latest meals.
+/// Meals written before this used `MEAL#{id}`; lookups by id read the meal's pointer item
+/// (see `meal_pointer_key`) and fall back to that legacy key
+pub fn meal_sort_key(meal_date: &DateTime<Utc>, meal_id: &str) -> String {
+    format!(
+        "{}{}#{}",
+        MEAL_SK_PREFIX,
+        meal_date.to_rfc3339_opts(SecondsFormat::Millis, true),
+        meal_id
+    )
+}
+
+/// `MEALID#{id}`, the key of the item recording which sort key a time-keyed meal is stored
+/// under. It sorts after every `MEAL#...` key and doesn't begin with `MEAL#`, so meal
+/// queries and date ranges never read it
+fn meal_pointer_key(meal_id: &str) -> String {
+    format!("{}{}", MEAL_POINTER_SK_PREFIX, meal_id)
+}
+
+fn is_legacy_meal_key(sk: &str) -> bool {
+    sk.strip_prefix(MEAL_SK_PREFIX)
+        .is_some_and(|rest| !rest.contains('#'))
+}
+
+/// The inclusive sort-key bounds of meals dated `start` through `end`: `MEAL#{start}` sorts
+/// before any meal on that day and `MEAL#{end + 1}` before any meal on the day after
+fn meal_key_range(start: NaiveDate, end: NaiveDate) -> (String, String) {
+    let after_end = end.succ_opt().unwrap_or(end);
+    (
+        format!("{}{}", MEAL_SK_PREFIX, start.format("%Y-%m-%d")),
+        format!("{}{}", MEAL_SK_PREFIX, after_end.format("%Y-%m-%d")),
+    )
+}
+
 #[derive(Clone)]
 pub struct MealRepository {
     client: DynamoDbClient,
@@ -27,7 +74,7 @@ impl MealRepository {
         );
         item.insert(
             "SK".to_string(),
-            AttributeValue::S(format!("MEAL#{}", meal.id)),
+            AttributeValue::S(meal_sort_key(&meal.meal_date, &meal.id)),
         );
         item.insert(
             "GSI1PK".to_string(),
@@ -122,12 +169,17 @@ impl MealRepository {
             AttributeValue::S(meal.updated_at.to_rfc3339()),
         );
 
-        let request = self
-            .client
-            .put_item()
+        let put = Put::builder()
             .table_name(&self.table_name)
             .set_item(Some(item))
-            .condition_expression("attribute_not_exists(PK)");
+            .condition_expression("attribute_not_exists(PK)")
+            .build()?;
+        let key = meal_sort_key(&meal.meal_date, &meal.id);
+        let request = self
+            .client
+            .transact_write_items()
+            .transact_items(TransactWriteItem::builder().put(put).build())
+            .transact_items(self.meal_pointer_write(&meal.user_id, &meal.id, &key)?);
 
         match request.send().await {
             Ok(_) => {
@@ -141,29 +193,64 @@ impl MealRepository {
         }
     }
 
-    pub async fn get_meal_by_id(&self, user_id: &str, meal_id: &str) -> Result<Option<Meal>> {
+    /// The item for `meal_id` in `user_id`'s partition, in either key form. Both reads are
+    /// strongly consistent key lookups, so a meal is found as soon as it's written: the
+    /// pointer item gives a time-keyed meal's sort key, and a meal without one is read
+    /// under its legacy `MEAL#{id}` key
+    async fn find_meal_item(&self, user_id: &str, meal_id: &str) -> Result<Option<HashMap<String, AttributeValue>>> {
+        let pointer = self.get_user_item(user_id, meal_pointer_key(meal_id)).await?;
+        let sk = pointer
+            .as_ref()
+            .and_then(|pointer| pointer.get(MEAL_SK_ATTRIBUTE))
+            .and_then(|v| v.as_s().ok())
+            .cloned()
+            .unwrap_or_else(|| format!("{}{}", MEAL_SK_PREFIX, meal_id));
+        self.get_user_item(user_id, sk).await
+    }
+
+    /// The item under `sk` in `user_id`'s partition, read strongly consistent
+    async fn get_user_item(&self, user_id: &str, sk: String) -> Result<Option<HashMap<String, AttributeValue>>> {
         let request = self
             .client
             .get_item()
             .table_name(&self.table_name)
             .key("PK", AttributeValue::S(format!("USER#{}", user_id)))
-            .key("SK", AttributeValue::S(format!("MEAL#{}", meal_id)));
+            .key("SK", AttributeValue::S(sk))
+            .consistent_read(true);
 
         match request.send().await {
-            Ok(response) => {
-                if let Some(item) = response.item() {
-                    Ok(Some(self.item_to_meal(item)?))
-                } else {
-                    Ok(None)
-                }
-            }
+            Ok(response) => Ok(response.item().cloned()),
             Err(e) => {
-                error!("Failed to get meal by ID: {}", e);
+                error!("Failed to get meal: {}", e);
                 Err(anyhow::anyhow!("Failed to get meal: {}", e))
             }
         }
     }
 
+    /// The sort key `meal_id` is stored under in `user_id`'s partition, in either key form
+    async fn find_meal_key(&self, user_id: &str, meal_id: &str) -> Result<Option<String>> {
+        let item = self.find_meal_item(user_id, meal_id).await?;
+        Ok(item.and_then(|item| item.get("SK").and_then(|v| v.as_s().ok()).cloned()))
+    }
+
+    /// A transaction write pointing `meal_id` at `sk` (see `meal_pointer_key`)
+    fn meal_pointer_write(&self, user_id: &str, meal_id: &str, sk: &str) -> Result<TransactWriteItem> {
+        let put = Put::builder()
+            .table_name(&self.table_name)
+            .item("PK", AttributeValue::S(format!("USER#{}", user_id)))
+            .item("SK", AttributeValue::S(meal_pointer_key(meal_id)))
+            .item(MEAL_SK_ATTRIBUTE, AttributeValue::S(sk.to_string()))
+            .build()?;
+        Ok(TransactWriteItem::builder().put(put).build())
+    }
+
+    pub async fn get_meal_by_id(&self, user_id: &str, meal_id: &str) -> Result<Option<Meal>> {
+        self.find_meal_item(user_id, meal_id)
+            .await?
+            .map(|item| self.item_to_meal(&item))
+            .transpose()
+    }
+
     pub async fn get_meals_by_date(
         &self,
         user_id: &str,
@@ -211,32 +298,393 @@ impl MealRepository {
         }
     }
 
-    pub async fn get_user_meals(&self, user_id: &str) -> Result<Vec<Meal>> {
-        let request = self
+    /// Meals dated from `start` through `end` (inclusive), oldest first, read as a range of
+    /// time-prefixed sort keys (see `meal_key_range`). Until `migrate_legacy_meals` has
+    /// moved all of the user's meals, ones still under legacy `MEAL#{id}` keys are read
+    /// with a second query over all of their meals
+    pub async fn get_meals_by_range(
+        &self,
+        user_id: &str,
+        start: NaiveDate,
+        end: NaiveDate,
+    ) -> Result<Vec<Meal>> {
+        let pk = format!("USER#{}", user_id);
+        let (sk_start, sk_end) = meal_key_range(start, end);
+        info!(
+            "Querying meals for user: {} from {} to {}",
+            user_id, start, end
+        );
+
+        let mut meals = Vec::new();
+        let mut exclusive_start_key = None;
+
+        loop {
+            let request = self
+                .client
+                .query()
+                .table_name(&self.table_name)
+                .key_condition_expression("PK = :pk AND SK BETWEEN :sk_start AND :sk_end")
+                .expression_attribute_values(":pk", AttributeValue::S(pk.clone()))
+                .expression_attribute_values(":sk_start", AttributeValue::S(sk_start.clone()))
+                .expression_attribute_values(":sk_end", AttributeValue::S(sk_end.clone()))
+                .set_exclusive_start_key(exclusive_start_key);
+
+            match request.send().await {
+                Ok(response) => {
+                    for item in response.items() {
+                        match self.item_to_meal(item) {
+                            Ok(meal) if (start..=end).contains(&meal.meal_date.date_naive()) => meals.push(meal),
+                            Ok(_) => {}
+                            Err(e) => {
+                                error!("Failed to parse meal item: {}", e);
+                            }
+                        }
+                    }
+
+                    exclusive_start_key = response.last_evaluated_key().cloned();
+                    if exclusive_start_key.is_none() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to get meals by range: {}", e);
+                    return Err(anyhow::anyhow!("Failed to get meals: {}", e));
+                }
+            }
+        }
+
+        if !self.legacy_meals_migrated(user_id).await? {
+            meals.extend(self.get_legacy_meals_by_range(user_id, start, end).await?);
+        }
+
+        // Legacy meals were read separately, so put everything in date order
+        meals.sort_by_key(|meal| meal.meal_date);
+        info!("Returning {} meals", meals.len());
+        Ok(meals)
+    }
+
+    /// Whether `migrate_legacy_meals` has left its marker for `user_id`
+    async fn legacy_meals_migrated(&self, user_id: &str) -> Result<bool> {
+        Ok(self
+            .get_user_item(user_id, LEGACY_MEALS_MIGRATED_SK.to_string())
+            .await?
+            .is_some())
+    }
+
+    /// `user_id`'s meals under legacy `MEAL#{id}` keys dated `start` through `end`. Those
+    /// keys don't sort by date, so this reads every meal the user has; the filter on
+    /// `MealDate` only trims what comes back
+    async fn get_legacy_meals_by_range(&self, user_id: &str, start: NaiveDate, end: NaiveDate) -> Result<Vec<Meal>> {
+        let after_end = end.succ_opt().unwrap_or(end);
+        let mut items = Vec::new();
+        let mut exclusive_start_key = None;
+
+        loop {
+            let response = self
+                .client
+                .query()
+                .table_name(&self.table_name)
+                .key_condition_expression("PK = :pk AND begins_with(SK, :sk)")
+                .filter_expression("MealDate >= :date_start AND MealDate < :date_end")
+                .expression_attribute_values(":pk", AttributeValue::S(format!("USER#{}", user_id)))
+                .expression_attribute_values(":sk", AttributeValue::S(MEAL_SK_PREFIX.to_string()))
+                .expression_attribute_values(":date_start", AttributeValue::S(start.format("%Y-%m-%d").to_string()))
+                .expression_attribute_values(":date_end", AttributeValue::S(after_end.format("%Y-%m-%d").to_string()))
+                .set_exclusive_start_key(exclusive_start_key)
+                .send()
+                .await
+                .map_err(|e| {
+                    error!("Failed to get legacy meals by range: {}", e);
+                    anyhow::anyhow!("Failed to get meals: {}", e)
+                })?;
+
+            items.extend(response.items().iter().cloned());
+            exclusive_start_key = response.last_evaluated_key().cloned();
+            if exclusive_start_key.is_none() {
+                break;
+            }
+        }
+
+        Ok(self.legacy_meals_in_range(&items, start, end))
+    }
+
+    /// The meals among `items` stored under legacy keys and dated `start` through `end`.
+    /// Time-keyed meals are skipped, as the key range query already read them
+    fn legacy_meals_in_range(&self, items: &[HashMap<String, AttributeValue>], start: NaiveDate, end: NaiveDate) -> Vec<Meal> {
+        items
+            .iter()
+            .filter(|item| {
+                item.get("SK")
+                    .and_then(|v| v.as_s().ok())
+                    .is_some_and(|sk| is_legacy_meal_key(sk))
+            })
+            .filter_map(|item| match self.item_to_meal(item) {
+                Ok(meal) => Some(meal),
+                Err(e) => {
+                    error!("Failed to parse meal item: {}", e);
+                    None
+                }
+            })
+            .filter(|meal| (start..=end).contains(&meal.meal_date.date_naive()))
+            .collect()
+    }
+
+    /// A user's meals, most recent first. With `limit`, pages are read in sort-key order
+    /// until that many time-keyed meals are found; meals still under legacy keys only show
+    /// up if they were read along the way (see `migrate_legacy_meals`)
+    pub async fn get_user_meals(&self, user_id: &str, limit: Option<usize>) -> Result<Vec<Meal>> {
+        let mut meals = Vec::new();
+        let mut keyed_meals = 0;
+        let mut exclusive_start_key = None;
+
+        loop {
+            let request = self
+                .client
+                .query()
+                .table_name(&self.table_name)
+                .key_condition_expression("PK = :pk AND begins_with(SK, :sk)")
+                .expression_attribute_values(":pk", AttributeValue::S(format!("USER#{}", user_id)))
+                .expression_attribute_values(":sk", AttributeValue::S(MEAL_SK_PREFIX.to_string()))
+                .scan_index_forward(false)
+                .set_limit(limit.map(|limit| limit as i32))
+                .set_exclusive_start_key(exclusive_start_key);
+
+            match request.send().await {
+                Ok(response) => {
+                    for item in response.items() {
+                        let legacy = item
+                            .get("SK")
+                            .and_then(|v| v.as_s().ok())
+                            .is_some_and(|sk| is_legacy_meal_key(sk));
+                        if let Ok(meal) = self.item_to_meal(item) {
+                            meals.push(meal);
+                            if !legacy {
+                                keyed_meals += 1;
+                            }
+                        }
+                    }
+
+                    exclusive_start_key = response.last_evaluated_key().cloned();
+                    let enough = limit.is_some_and(|limit| keyed_meals >= limit);
+                    if enough || exclusive_start_key.is_none() {
+                        break;
+                    }
+                }
+                Err(e) => {
+                    error!("Failed to get user meals: {}", e);
+                    return Err(anyhow::anyhow!("Failed to get user meals: {}", e));
+                }
+            }
+        }
+
+        // Legacy keys don't sort by date, so order what was read by meal date
+        meals.sort_by(|a, b| b.meal_date.cmp(&a.meal_date));
+        if let Some(limit) = limit {
+            meals.truncate(limit);
+        }
+
+        Ok(meals)
+    }
+
+    /// One page of a user's meals in sort-key order, newest first, starting after the
+    /// `start_after` sort key. Returns the meals and, when more remain, the sort key of
+    /// the last one read to pass back as the next page's start
+    pub async fn get_user_meals_page(&self, user_id: &str, limit: usize, start_after: Option<&str>) -> Result<(Vec<Meal>, Option<String>)> {
+        let exclusive_start_key = start_after.map(|sk| {
+            HashMap::from([
+                ("PK".to_string(), AttributeValue::S(format!("USER#{}", user_id))),
+                ("SK".to_string(), AttributeValue::S(sk.to_string())),
+            ])
+        });
+
+        let response = self
             .client
             .query()
             .table_name(&self.table_name)
             .key_condition_expression("PK = :pk AND begins_with(SK, :sk)")
             .expression_attribute_values(":pk", AttributeValue::S(format!("USER#{}", user_id)))
-            .expression_attribute_values(":sk", AttributeValue::S("MEAL#".to_string()));
+            .expression_attribute_values(":sk", AttributeValue::S(MEAL_SK_PREFIX.to_string()))
+            .scan_index_forward(false)
+            .limit(limit as i32)
+            .set_exclusive_start_key(exclusive_start_key)
+            .send()
+            .await
+            .map_err(|e| {
+                error!("Failed to get user meals page: {}", e);
+                anyhow::anyhow!("Failed to get user meals: {}", e)
+            })?;
+
+        let meals = response
+            .items()
+            .iter()
+            .filter_map(|item| self.item_to_meal(item).ok())
+            .collect();
+        let next_start = response
+            .last_evaluated_key()
+            .and_then(|key| key.get("SK"))
+            .and_then(|v| v.as_s().ok())
+            .cloned();
+        Ok((meals, next_start))
+    }
 
-        match request.send().await {
-            Ok(response) => {
-                let mut meals = Vec::new();
-                for item in response.items() {
-                    if let Ok(meal) = self.item_to_meal(item) {
-                        meals.push(meal);
-                    }
-                }
-                // Sort by meal date descending (most recent first)
-                meals.sort_by(|a, b| b.meal_date.cmp(&a.meal_date));
-                Ok(meals)
+    /// How many meals a user has logged, counted with `Select::Count` so no items are
+    /// read back
+    pub async fn count_user_meals(&self, user_id: &str) -> Result<u64> {
+        let mut total = 0;
+        let mut exclusive_start_key = None;
+
+        loop {
+            let response = self
+                .client
+                .query()
+                .table_name(&self.table_name)
+                .key_condition_expression("PK = :pk AND begins_with(SK, :sk)")
+                .expression_attribute_values(":pk", AttributeValue::S(format!("USER#{}", user_id)))
+                .expression_attribute_values(":sk", AttributeValue::S(MEAL_SK_PREFIX.to_string()))
+                .select(Select::Count)
+                .set_exclusive_start_key(exclusive_start_key)
+                .send()
+                .await
+                .map_err(|e| {
+                    error!("Failed to count user meals: {}", e);
+                    anyhow::anyhow!("Failed to count user meals: {}", e)
+                })?;
+
+            total += response.count().max(0) as u64;
+            exclusive_start_key = response.last_evaluated_key().cloned();
+            if exclusive_start_key.is_none() {
+                return Ok(total);
             }
-            Err(e) => {
-                error!("Failed to get user meals: {}", e);
-                Err(anyhow::anyhow!("Failed to get user meals: {}", e))
+        }
+    }
+
+    /// Move a user's meals still under legacy `MEAL#{id}` keys to time-prefixed keys and
+    /// return how many moved. A meal that changes while it's being moved is left under its
+    /// legacy key for the next run; once none are left, the user's marker is written so
+    /// date-range reads stop looking for legacy meals
+    pub async fn migrate_legacy_meals(&self, user_id: &str) -> Result<usize> {
+        let mut legacy_items = Vec::new();
+        let mut exclusive_start_key = None;
+
+        loop {
+            let response = self
+                .client
+                .query()
+                .table_name(&self.table_name)
+                .key_condition_expression("PK = :pk AND begins_with(SK, :sk)")
+                .expression_attribute_values(":pk", AttributeValue::S(format!("USER#{}", user_id)))
+                .expression_attribute_values(":sk", AttributeValue::S(MEAL_SK_PREFIX.to_string()))
+                .consistent_read(true)
+                .set_exclusive_start_key(exclusive_start_key)
+                .send()
+                .await
+                .map_err(|e| {
+                    error!("Failed to read legacy meals: {}", e);
+                    anyhow::anyhow!("Failed to read legacy meals: {}", e)
+                })?;
+
+            legacy_items.extend(
+                response
+                    .items()
+                    .iter()
+                    .filter(|item| {
+                        item.get("SK")
+                            .and_then(|v| v.as_s().ok())
+                            .is_some_and(|sk| is_legacy_meal_key(sk))
+                    })
+                    .cloned(),
+            );
+            exclusive_start_key = response.last_evaluated_key().cloned();
+            if exclusive_start_key.is_none() {
+                break;
             }
         }
+
+        let legacy_count = legacy_items.len();
+        let mut migrated = 0;
+        for item in legacy_items {
+            if self.migrate_legacy_meal(item).await? {
+                migrated += 1;
+            }
+        }
+
+        if migrated == legacy_count {
+            self.client
+                .put_item()
+                .table_name(&self.table_name)
+                .item("PK", AttributeValue::S(format!("USER#{}", user_id)))
+                .item("SK", AttributeValue::S(LEGACY_MEALS_MIGRATED_SK.to_string()))
+                .item("MigratedAt", AttributeValue::S(Utc::now().to_rfc3339()))
+                .send()
+                .await
+                .map_err(|e| {
+                    error!("Failed to mark meal keys migrated: {}", e);
+                    anyhow::anyhow!("Failed to mark meal keys migrated: {}", e)
+                })?;
+        }
+        Ok(migrated)
+    }
+
+    /// Rewrite a `MEAL#{id}` item under its time-prefixed key, along with its pointer item,
+    /// in one transaction: the put
+    /// requires the new key to be free and the delete requires the legacy item to be
+    /// unchanged since it was read. Returns false when either condition fails
+    async fn migrate_legacy_meal(&self, mut item: HashMap<String, AttributeValue>) -> Result<bool> {
+        if !item.contains_key("MealDate") {
+            // Without a stored date there's no key to move the meal to
+            return Ok(false);
+        }
+        let meal = self.item_to_meal(&item)?;
+        let legacy_key = item
+            .get("SK")
+            .and_then(|v| v.as_s().ok())
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("Missing SK"))?;
+
+        let delete = Delete::builder()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(format!("USER#{}", meal.user_id)))
+            .key("SK", AttributeValue::S(legacy_key));
+        let delete = match item.get("UpdatedAt") {
+            Some(updated_at) => delete
+                .condition_expression("attribute_exists(PK) AND UpdatedAt = :updated_at")
+                .expression_attribute_values(":updated_at", updated_at.clone()),
+            None => delete.condition_expression("attribute_exists(PK) AND attribute_not_exists(UpdatedAt)"),
+        };
+
+        let key = meal_sort_key(&meal.meal_date, &meal.id);
+        item.insert("SK".to_string(), AttributeValue::S(key.clone()));
+        let put = Put::builder()
+            .table_name(&self.table_name)
+            .set_item(Some(item))
+            .condition_expression("attribute_not_exists(PK)")
+            .build()?;
+
+        let result = self
+            .client
+            .transact_write_items()
+            .transact_items(TransactWriteItem::builder().put(put).build())
+            .transact_items(TransactWriteItem::builder().delete(delete.build()?).build())
+            .transact_items(self.meal_pointer_write(&meal.user_id, &meal.id, &key)?)
+            .send()
+            .await;
+
+        match result {
+            Ok(_) => {
+                info!("Migrated meal {} to a time-prefixed key", meal.id);
+                Ok(true)
+            }
+            Err(e) => match e.as_service_error() {
+                Some(TransactWriteItemsError::TransactionCanceledException(_)) => {
+                    info!("Skipped migrating meal {}, which changed while being moved", meal.id);
+                    Ok(false)
+                }
+                _ => {
+                    error!("Failed to migrate meal {}: {}", meal.id, e);
+                    Err(anyhow::anyhow!("Failed to migrate meal: {}", e))
+                }
+            },
+        }
     }
 
     pub async fn update_meal(
@@ -299,12 +747,17 @@ impl MealRepository {
                 .insert(":notes".to_string(), AttributeValue::S(notes.clone()));
         }
 
+        let sk = self
+            .find_meal_key(user_id, meal_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Meal not found"))?;
+
         let mut request = self
             .client
             .update_item()
             .table_name(&self.table_name)
             .key("PK", AttributeValue::S(format!("USER#{}", user_id)))
-            .key("SK", AttributeValue::S(format!("MEAL#{}", meal_id)))
+            .key("SK", AttributeValue::S(sk))
             .update_expression(update_expression)
             .set_expression_attribute_values(Some(expression_attribute_values))
             .return_values(ReturnValue::AllNew);
@@ -328,13 +781,26 @@ impl MealRepository {
         }
     }
 
+    /// Delete a meal and its pointer item together
     pub async fn delete_meal(&self, user_id: &str, meal_id: &str) -> Result<()> {
+        let sk = self
+            .find_meal_key(user_id, meal_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Meal not found"))?;
+
+        let delete = |sk: String| -> Result<TransactWriteItem> {
+            let delete = Delete::builder()
+                .table_name(&self.table_name)
+                .key("PK", AttributeValue::S(format!("USER#{}", user_id)))
+                .key("SK", AttributeValue::S(sk))
+                .build()?;
+            Ok(TransactWriteItem::builder().delete(delete).build())
+        };
         let request = self
             .client
-            .delete_item()
-            .table_name(&self.table_name)
-            .key("PK", AttributeValue::S(format!("USER#{}", user_id)))
-            .key("SK", AttributeValue::S(format!("MEAL#{}", meal_id)));
+            .transact_write_items()
+            .transact_items(delete(sk)?)
+            .transact_items(delete(meal_pointer_key(meal_id))?);
 
         match request.send().await {
             Ok(_) => {
@@ -481,3 +947,72 @@ impl MealRepository {
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_meal_key_range_covers_whole_days() {
+        let date = |day| NaiveDate::from_ymd_opt(2024, 3, day).unwrap();
+        let key = |time: &str| meal_sort_key(&time.parse::<DateTime<Utc>>().unwrap(), "meal-1");
+        let (start, end) = meal_key_range(date(1), date(7));
+        let in_range = |sk: &String| start <= *sk && *sk <= end;
+
+        assert!(in_range(&key("2024-03-01T00:00:00Z")));
+        assert!(in_range(&key("2024-03-07T23:59:59.999Z")));
+        assert!(!in_range(&key("2024-02-29T23:59:59.999Z")));
+        assert!(!in_range(&key("2024-03-08T00:00:00Z")));
+    }
+
+    #[test]
+    fn test_meal_pointer_keys_stay_out_of_meal_queries() {
+        let pointer = meal_pointer_key("meal-1");
+        let (start, end) = meal_key_range(
+            NaiveDate::from_ymd_opt(1970, 1, 1).unwrap(),
+            NaiveDate::from_ymd_opt(9998, 12, 31).unwrap(),
+        );
+
+        assert!(!pointer.starts_with(MEAL_SK_PREFIX));
+        assert!(!(start <= pointer && pointer <= end));
+        assert!(!is_legacy_meal_key(&pointer));
+        assert!(!LEGACY_MEALS_MIGRATED_SK.starts_with(MEAL_SK_PREFIX));
+        assert!(!(start.as_str() <= LEGACY_MEALS_MIGRATED_SK && LEGACY_MEALS_MIGRATED_SK <= end.as_str()));
+    }
+
+    fn meal_item(sk: &str, meal_id: &str, meal_date: &str) -> HashMap<String, AttributeValue> {
+        [
+            ("PK", "USER#user-1"),
+            ("SK", sk),
+            ("MealId", meal_id),
+            ("UserId", "user-1"),
+            ("Name", "Oats"),
+            ("MealType", "\"breakfast\""),
+            ("MealDate", meal_date),
+            ("Foods", "[]"),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), AttributeValue::S(value.to_string())))
+        .collect()
+    }
+
+    #[test]
+    fn test_legacy_meals_are_read_by_date_alongside_keyed_ones() {
+        let config = aws_sdk_dynamodb::Config::builder()
+            .behavior_version(aws_sdk_dynamodb::config::BehaviorVersion::latest())
+            .region(aws_sdk_dynamodb::config::Region::new("us-east-1"))
+            .build();
+        let repository = MealRepository::new(DynamoDbClient::from_conf(config), "test".to_string());
+        let date = |day| NaiveDate::from_ymd_opt(2024, 3, day).unwrap();
+        let keyed_date = "2024-03-02T08:00:00+00:00".parse::<DateTime<Utc>>().unwrap();
+        let items = [
+            meal_item("MEAL#legacy-in", "legacy-in", "2024-03-03T08:00:00+00:00"),
+            meal_item("MEAL#legacy-out", "legacy-out", "2024-03-09T08:00:00+00:00"),
+            meal_item(&meal_sort_key(&keyed_date, "keyed"), "keyed", "2024-03-02T08:00:00+00:00"),
+        ];
+
+        let meals = repository.legacy_meals_in_range(&items, date(1), date(7));
+        let ids: Vec<&str> = meals.iter().map(|meal| meal.id.as_str()).collect();
+        assert_eq!(ids, vec!["legacy-in"]);
+    }
+}
@@ -9,3 +9,61 @@ pub use food_repository::FoodRepository;
 pub use nutrition_plan_repository::NutritionPlanRepository;
 pub use water_repository::WaterRepository;
 pub use favorite_repository::FavoriteRepository;
+
+use anyhow::Result;
+use aws_sdk_dynamodb::{types::{AttributeValue, KeysAndAttributes}, Client as DynamoDbClient};
+use std::collections::HashMap;
+use std::time::Duration;
+use tracing::error;
+
+pub type Item = HashMap<String, AttributeValue>;
+
+/// Most keys a single BatchGetItem call accepts
+pub const BATCH_GET_LIMIT: usize = 100;
+/// Calls made per chunk before giving up on keys DynamoDB keeps leaving unprocessed
+const BATCH_GET_MAX_ATTEMPTS: u32 = 5;
+
+/// Read the items at `keys` with BatchGetItem, in chunks of 100 (the BatchGetItem
+/// limit), retrying whatever DynamoDB reports as unprocessed with a short backoff. Keys
+/// with no item are skipped, and items come back in no particular order. `keys` must not
+/// repeat a key
+pub async fn batch_get(client: &DynamoDbClient, table_name: &str, keys: Vec<Item>) -> Result<Vec<Item>> {
+    let mut items = Vec::new();
+
+    for chunk in keys.chunks(BATCH_GET_LIMIT) {
+        let mut pending = chunk.to_vec();
+        let mut attempt = 0;
+        while !pending.is_empty() {
+            if attempt == BATCH_GET_MAX_ATTEMPTS {
+                return Err(anyhow::anyhow!("{} item reads were left unprocessed", pending.len()));
+            }
+            if attempt > 0 {
+                tokio::time::sleep(Duration::from_millis(50 << attempt)).await;
+            }
+            attempt += 1;
+
+            let response = client
+                .batch_get_item()
+                .request_items(table_name, KeysAndAttributes::builder().set_keys(Some(pending)).build()?)
+                .send()
+                .await
+                .map_err(|e| {
+                    error!("Failed to batch get items: {}", e);
+                    anyhow::anyhow!("Failed to batch get items: {}", e)
+                })?;
+
+            items.extend(
+                response
+                    .responses
+                    .and_then(|mut responses| responses.remove(table_name))
+                    .unwrap_or_default(),
+            );
+            pending = response
+                .unprocessed_keys
+                .and_then(|mut unprocessed| unprocessed.remove(table_name))
+                .map(|unprocessed| unprocessed.keys)
+                .unwrap_or_default();
+        }
+    }
+    Ok(items)
+}
@@ -1,4 +1,4 @@
-use aws_sdk_dynamodb::{Client as DynamoDbClient, types::AttributeValue};
+use aws_sdk_dynamodb::{Client as DynamoDbClient, types::{AttributeValue, ReturnValue}};
 use std::collections::HashMap;
 use anyhow::Result;
 use tracing::error;
@@ -64,4 +64,76 @@ impl WaterRepository {
             }
         }
     }
+
+    /// Atomically add `glasses` to the day's total, creating the record if needed, and
+    /// return the new total. Concurrent increments all count, unlike `set_water_by_date`
+    pub async fn add_water_by_date(&self, user_id: &str, date: &str, glasses: u32) -> Result<u32> {
+        let request = self.client
+            .update_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(format!("USER#{}", user_id)))
+            .key("SK", AttributeValue::S(format!("WATER#{}", date)))
+            .update_expression("ADD WaterGlasses :glasses SET EntityType = :entity_type, UserId = :user_id, WaterDate = :date, UpdatedAt = :updated_at")
+            .expression_attribute_values(":glasses", AttributeValue::N(glasses.to_string()))
+            .expression_attribute_values(":entity_type", AttributeValue::S("WATER".to_string()))
+            .expression_attribute_values(":user_id", AttributeValue::S(user_id.to_string()))
+            .expression_attribute_values(":date", AttributeValue::S(date.to_string()))
+            .expression_attribute_values(":updated_at", AttributeValue::S(Utc::now().to_rfc3339()))
+            .return_values(ReturnValue::UpdatedNew);
+
+        match request.send().await {
+            Ok(response) => {
+                let total = response.attributes()
+                    .and_then(|attributes| attributes.get("WaterGlasses"))
+                    .and_then(|v| v.as_n().ok())
+                    .and_then(|s| s.parse::<u32>().ok())
+                    .unwrap_or(glasses);
+                Ok(total)
+            }
+            Err(e) => {
+                error!("Failed to add water by date: {}", e);
+                Err(anyhow::anyhow!("Failed to add water: {}", e))
+            }
+        }
+    }
+
+    pub async fn delete_water_by_date(&self, user_id: &str, date: &str) -> Result<()> {
+        let request = self.client
+            .delete_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(format!("USER#{}", user_id)))
+            .key("SK", AttributeValue::S(format!("WATER#{}", date)));
+
+        match request.send().await {
+            Ok(_) => Ok(()),
+            Err(e) => {
+                error!("Failed to delete water by date: {}", e);
+                Err(anyhow::anyhow!("Failed to reset water: {}", e))
+            }
+        }
+    }
+
+    /// The daily water goal (glasses) from the user's profile preferences, if they set one
+    pub async fn get_daily_water_goal(&self, user_id: &str) -> Result<Option<u32>> {
+        let request = self.client
+            .get_item()
+            .table_name(&self.table_name)
+            .key("PK", AttributeValue::S(format!("USER#{}", user_id)))
+            .key("SK", AttributeValue::S("PREFERENCES".to_string()))
+            .projection_expression("dailyGoals");
+
+        match request.send().await {
+            Ok(response) => Ok(response.item()
+                .and_then(|item| item.get("dailyGoals"))
+                .and_then(|v| v.as_m().ok())
+                .and_then(|goals| goals.get("water"))
+                .and_then(|v| v.as_n().ok())
+                .and_then(|s| s.parse::<u32>().ok())
+                .filter(|&goal| goal > 0)),
+            Err(e) => {
+                error!("Failed to get daily water goal: {}", e);
+                Err(anyhow::anyhow!("Failed to get water goal: {}", e))
+            }
+        }
+    }
 }
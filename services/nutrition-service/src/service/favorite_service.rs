@@ -18,7 +18,7 @@ impl FavoriteService {
         }
     }
 
-    pub async fn add_favorite_food(&self, user_id: &str, food_id: &str, auth_context: &AuthContext) -> Result<()> {
+    pub async fn add_favorite_food(&self, user_id: &str, food_id: &str, request: &FavoriteFoodRequest, auth_context: &AuthContext) -> Result<()> {
         // Authorization check
         if auth_context.user_id != user_id {
             return Err(anyhow::anyhow!("You can only manage your own favorites"));
@@ -30,7 +30,8 @@ impl FavoriteService {
             return Err(anyhow::anyhow!("Food not found"));
         }
 
-        self.favorite_repository.add_favorite_food(user_id, food_id).await
+        let tags = normalize_tags(request.tags.as_deref().unwrap_or_default());
+        self.favorite_repository.add_favorite_food(user_id, food_id, request.position, &tags).await
     }
 
     pub async fn remove_favorite_food(&self, user_id: &str, food_id: &str, auth_context: &AuthContext) -> Result<()> {
@@ -42,19 +43,86 @@ impl FavoriteService {
         self.favorite_repository.remove_favorite_food(user_id, food_id).await
     }
 
-    pub async fn list_favorite_foods(&self, user_id: &str, auth_context: &AuthContext) -> Result<Vec<Food>> {
+    pub async fn update_favorite_food(&self, user_id: &str, food_id: &str, request: &FavoriteFoodRequest, auth_context: &AuthContext) -> Result<()> {
+        // Authorization check
+        if auth_context.user_id != user_id {
+            return Err(anyhow::anyhow!("You can only manage your own favorites"));
+        }
+
+        if let Some(tags) = &request.tags {
+            self.favorite_repository.update_favorite_tags(user_id, food_id, &normalize_tags(tags)).await?;
+        }
+        if let Some(position) = request.position {
+            self.favorite_repository.set_favorite_position(user_id, food_id, Some(position)).await?;
+        }
+        Ok(())
+    }
+
+    /// Put favorites in the order given. Listed foods take positions 0, 1, ...; any
+    /// favorites left out lose their position and follow, most recent first
+    pub async fn reorder_favorite_foods(&self, user_id: &str, food_ids: &[String], auth_context: &AuthContext) -> Result<()> {
+        // Authorization check
+        if auth_context.user_id != user_id {
+            return Err(anyhow::anyhow!("You can only manage your own favorites"));
+        }
+
+        let favorites = self.favorite_repository.list_favorite_foods(user_id).await?;
+        if let Some(unknown) = food_ids.iter().find(|id| !favorites.iter().any(|f| &f.food_id == *id)) {
+            return Err(anyhow::anyhow!("Favorite not found: {}", unknown));
+        }
+
+        for favorite in &favorites {
+            let position = food_ids.iter().position(|id| *id == favorite.food_id).map(|p| p as u32);
+            if position != favorite.position {
+                self.favorite_repository.set_favorite_position(user_id, &favorite.food_id, position).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Favorites sorted by position, then most recently added first
+    pub async fn list_favorite_foods(&self, user_id: &str, auth_context: &AuthContext) -> Result<Vec<FavoriteFoodEntry>> {
         // Authorization check
         if auth_context.user_id != user_id {
             return Err(anyhow::anyhow!("You can only access your own favorites"));
         }
 
-        let ids = self.favorite_repository.list_favorite_food_ids(user_id).await?;
-        let mut foods = Vec::new();
-        for food_id in ids {
-            if let Some(food) = self.food_repository.get_food_by_id(&food_id).await? {
-                foods.push(food);
+        let mut favorites = self.favorite_repository.list_favorite_foods(user_id).await?;
+        favorites.sort_by(|a, b| {
+            match (a.position, b.position) {
+                (Some(a), Some(b)) => a.cmp(&b),
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (None, None) => std::cmp::Ordering::Equal,
             }
+            .then_with(|| b.created_at.cmp(&a.created_at))
+        });
+
+        let food_ids: Vec<String> = favorites.iter().map(|favorite| favorite.food_id.clone()).collect();
+        let mut foods = self.food_repository.get_foods_by_ids(&food_ids).await?;
+
+        // Favorites whose food has since been deleted are left out
+        Ok(favorites
+            .into_iter()
+            .filter_map(|favorite| {
+                foods.remove(&favorite.food_id).map(|food| FavoriteFoodEntry {
+                    food,
+                    position: favorite.position,
+                    tags: favorite.tags,
+                    favorited_at: favorite.created_at,
+                })
+            })
+            .collect())
+    }
+}
+
+/// Trimmed, non-empty tags with duplicates (ignoring case) dropped, in their given order
+fn normalize_tags(tags: &[String]) -> Vec<String> {
+    let mut normalized: Vec<String> = Vec::new();
+    for tag in tags.iter().map(|tag| tag.trim()).filter(|tag| !tag.is_empty()) {
+        if !normalized.iter().any(|existing| existing.eq_ignore_ascii_case(tag)) {
+            normalized.push(tag.to_string());
         }
-        Ok(foods)
     }
+    normalized
 }
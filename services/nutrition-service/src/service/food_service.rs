@@ -2,9 +2,12 @@ use validator::Validate;
 use anyhow::Result;
 use uuid::Uuid;
 use chrono::Utc;
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
 
 use crate::models::*;
 use crate::repository::FoodRepository;
+use lambda_router::{ListResponse, PageParams};
 
 #[derive(Clone)]
 pub struct FoodService {
@@ -41,6 +44,7 @@ impl FoodService {
             verified: false,
             verified_by: None,
             verified_at: None,
+            usage_count: 0,
             created_at: now,
             updated_at: now,
         };
@@ -52,8 +56,187 @@ impl FoodService {
         self.food_repository.get_food_by_id(food_id).await
     }
 
-    pub async fn search_foods(&self, query: &str, limit: Option<u32>, cursor: Option<String>) -> Result<(Vec<Food>, Option<String>)> {
-        let limit = limit.unwrap_or(20).min(100); // Max 100 results
-        self.food_repository.search_foods(query, limit, cursor).await
+    /// Foods whose name matches `query`, most relevant first: exact name, then prefix,
+    /// then substring matches, each ordered by how often the food is logged. Every match
+    /// is ranked, but only the requested page is held in memory. The cursor is the ranking
+    /// key of the page's last food
+    pub async fn search_foods(&self, query: &str, params: &PageParams) -> Result<ListResponse<Food>> {
+        let query = query.trim().to_lowercase();
+        let mut page = SearchPage::new(params.limit, params.start_key());
+        self.food_repository
+            .for_each_search_match(&query, |food| page.offer(&query, food))
+            .await?;
+
+        let (foods, last_key, matched) = page.finish();
+        let total = params.include_total.then_some(matched);
+        Ok(ListResponse::new(foods, params, last_key, total))
+    }
+}
+
+/// Where a food sorts in search results, as a string so it can resume a search: match
+/// rank, then most logged first, then name, then id to break ties
+fn search_sort_key(rank: u8, food: &Food) -> String {
+    format!("{}#{:020}#{}#{}", rank, u64::MAX - food.usage_count, food.name.to_lowercase(), food.id)
+}
+
+struct RankedFood {
+    key: String,
+    food: Food,
+}
+
+impl PartialEq for RankedFood {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for RankedFood {}
+
+impl PartialOrd for RankedFood {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RankedFood {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+/// The best `limit` matches ranked after `after`, collected while the search streams.
+/// The heap holds one food more than the page, to tell whether another page follows,
+/// with the worst one on top to be dropped first
+struct SearchPage<'a> {
+    limit: usize,
+    after: Option<&'a str>,
+    best: BinaryHeap<RankedFood>,
+    matched: u64,
+}
+
+impl<'a> SearchPage<'a> {
+    fn new(limit: usize, after: Option<&'a str>) -> Self {
+        Self { limit, after, best: BinaryHeap::with_capacity(limit + 1), matched: 0 }
+    }
+
+    fn offer(&mut self, query: &str, food: Food) {
+        let Some(rank) = match_rank(&food.name, query) else {
+            return;
+        };
+        self.matched += 1;
+
+        let key = search_sort_key(rank, &food);
+        if self.after.is_some_and(|after| key.as_str() <= after) {
+            return;
+        }
+        self.best.push(RankedFood { key, food });
+        if self.best.len() > self.limit + 1 {
+            self.best.pop();
+        }
+    }
+
+    /// The page in ranking order, the key to resume after when more matches follow, and
+    /// how many foods matched in all
+    fn finish(self) -> (Vec<Food>, Option<String>, u64) {
+        let mut ranked = self.best.into_sorted_vec();
+        let more = ranked.len() > self.limit;
+        ranked.truncate(self.limit);
+        let last_key = if more { ranked.last().map(|ranked| ranked.key.clone()) } else { None };
+        (ranked.into_iter().map(|ranked| ranked.food).collect(), last_key, self.matched)
+    }
+}
+
+/// 0 for an exact (case-insensitive) name match, 1 for a prefix, 2 for a substring,
+/// `None` when the name doesn't contain `query` at all. `query` is already lowercase
+fn match_rank(name: &str, query: &str) -> Option<u8> {
+    let name = name.to_lowercase();
+    if name == query {
+        Some(0)
+    } else if name.starts_with(query) {
+        Some(1)
+    } else if name.contains(query) {
+        Some(2)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn food(id: &str, name: &str, usage_count: u64) -> Food {
+        serde_json::from_value(json!({
+            "id": id,
+            "name": name,
+            "category": "Other",
+            "nutritionFacts": {
+                "calories": 0.0, "protein": 0.0, "total_carbs": 0.0, "dietary_fiber": 0.0,
+                "total_sugars": 0.0, "total_fat": 0.0, "sodium": 0.0
+            },
+            "serving_size": 100.0,
+            "serving_unit": "g",
+            "commonServings": [],
+            "allergens": [],
+            "dietary_tags": [],
+            "verified": false,
+            "usage_count": usage_count,
+            "created_at": "2024-01-01T00:00:00Z",
+            "updated_at": "2024-01-01T00:00:00Z"
+        }))
+        .unwrap()
+    }
+
+    /// Streams `foods` through one page at a time, the way a search reads them
+    fn search_pages(foods: &[Food], query: &str, limit: usize) -> (Vec<Vec<String>>, u64) {
+        let mut pages = Vec::new();
+        let mut after: Option<String> = None;
+        loop {
+            let mut page = SearchPage::new(limit, after.as_deref());
+            for food in foods {
+                page.offer(query, food.clone());
+            }
+            let (ranked, last_key, matched) = page.finish();
+            pages.push(ranked.into_iter().map(|food| food.id).collect());
+            if last_key.is_none() {
+                return (pages, matched);
+            }
+            after = last_key;
+        }
+    }
+
+    #[test]
+    fn test_search_ranks_exact_then_prefix_then_substring_then_usage() {
+        let foods = [
+            food("1", "Brown Rice", 50),
+            food("2", "Rice Cake", 5),
+            food("3", "Rice", 1),
+            food("4", "Rice Pudding", 40),
+            food("5", "Wild Rice", 2),
+            food("6", "Chicken", 100),
+        ];
+
+        let (pages, matched) = search_pages(&foods, "rice", 10);
+        assert_eq!(pages, vec![vec!["3", "4", "2", "1", "5"]]);
+        assert_eq!(matched, 5);
+    }
+
+    #[test]
+    fn test_search_pages_past_any_number_of_matches() {
+        // More matches than any page holds, fed in alphabetical (index) order
+        let foods: Vec<Food> = (0..250)
+            .map(|i| food(&format!("{:03}", i), &format!("Apple {:03}", i), i % 7))
+            .collect();
+
+        let (pages, matched) = search_pages(&foods, "apple", 20);
+        assert_eq!(matched, 250);
+        assert_eq!(pages.len(), 13);
+        assert!(pages[..12].iter().all(|page| page.len() == 20));
+
+        let mut expected = foods.clone();
+        expected.sort_by(|a, b| b.usage_count.cmp(&a.usage_count).then_with(|| a.name.cmp(&b.name)));
+        let expected: Vec<String> = expected.into_iter().map(|food| food.id).collect();
+        assert_eq!(pages.concat(), expected);
     }
 }
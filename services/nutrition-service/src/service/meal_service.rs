@@ -1,17 +1,21 @@
 use validator::Validate;
 use anyhow::Result;
-use tracing::{info, error};
+use tracing::{info, error, warn};
 use uuid::Uuid;
-use chrono::Utc;
+use chrono::{NaiveDate, Utc};
+use std::collections::BTreeMap;
 
 use crate::models::*;
 use crate::repository::{MealRepository, FoodRepository};
+use crate::utils::constants::DEFAULT_MEAL_RANGE_MAX_DAYS;
 use auth_layer::AuthContext;
+use lambda_router::{ListResponse, PageParams};
 
 #[derive(Clone)]
 pub struct MealService {
     meal_repository: MealRepository,
     food_repository: FoodRepository,
+    max_range_days: i64,
 }
 
 impl MealService {
@@ -19,13 +23,23 @@ impl MealService {
         Self {
             meal_repository,
             food_repository,
+            max_range_days: DEFAULT_MEAL_RANGE_MAX_DAYS,
         }
     }
 
+    /// Cap how many days `get_meals_by_range` may span, to bound the cost of one query
+    pub fn with_max_range_days(mut self, max_range_days: i64) -> Self {
+        self.max_range_days = max_range_days;
+        self
+    }
+
     pub async fn create_meal(&self, user_id: &str, create_request: &CreateMealRequest, auth_context: &AuthContext) -> Result<Meal> {
         // Validate the request
         create_request.validate()?;
 
+        // Meals are always owned by the caller unless an admin targets another user
+        let owner_id = auth_context.resolve_owner(Some(user_id));
+
         // Create meal ID
         let meal_id = Uuid::new_v4().to_string();
         let now = Utc::now();
@@ -106,7 +120,7 @@ impl MealService {
 
         let meal = Meal {
             id: meal_id.clone(),
-            user_id: user_id.to_string(),
+            user_id: owner_id.to_string(),
             name: create_request.name.clone(),
             description: create_request.description.clone(),
             meal_type: create_request.meal_type.clone(),
@@ -125,7 +139,16 @@ impl MealService {
             updated_at: now,
         };
 
-        self.meal_repository.create_meal(&meal).await
+        let meal = self.meal_repository.create_meal(&meal).await?;
+
+        // Logged foods rank higher in search; a failed bump shouldn't fail the meal
+        for food in &meal.foods {
+            if let Err(e) = self.food_repository.increment_usage_count(&food.food_id).await {
+                warn!("Failed to count usage of food {}: {}", food.food_id, e);
+            }
+        }
+
+        Ok(meal)
     }
 
     pub async fn get_meal(&self, user_id: &str, meal_id: &str, auth_context: &AuthContext) -> Result<Option<Meal>> {
@@ -169,13 +192,65 @@ impl MealService {
         self.meal_repository.get_meals_by_date(user_id, &meal_date).await
     }
 
+    /// Meals from `start` through `end` (both `YYYY-MM-DD`, inclusive) keyed by date.
+    /// Every day in the range gets an entry, empty when nothing was logged
+    pub async fn get_meals_by_range(&self, user_id: &str, start: &str, end: &str, auth_context: &AuthContext) -> Result<BTreeMap<String, Vec<Meal>>> {
+        // Authorization check
+        if auth_context.user_id != user_id {
+            return Err(anyhow::anyhow!("You can only access your own meals"));
+        }
+
+        let (start_date, end_date) = parse_date_range(start, end, self.max_range_days)?;
+        let days = (end_date - start_date).num_days() + 1;
+
+        let mut meals_by_date: BTreeMap<String, Vec<Meal>> = start_date
+            .iter_days()
+            .take(days as usize)
+            .map(|date| (date.format("%Y-%m-%d").to_string(), Vec::new()))
+            .collect();
+
+        for meal in self.meal_repository.get_meals_by_range(user_id, start_date, end_date).await? {
+            if let Some(meals) = meals_by_date.get_mut(&meal.meal_date.format("%Y-%m-%d").to_string()) {
+                meals.push(meal);
+            }
+        }
+
+        Ok(meals_by_date)
+    }
+
+    /// Move the caller's meals still under legacy `MEAL#{id}` keys to time-prefixed ones,
+    /// returning how many moved. Until this has run, date-range reads may miss legacy meals
+    pub async fn migrate_meal_keys(&self, auth_context: &AuthContext) -> Result<usize> {
+        self.meal_repository.migrate_legacy_meals(&auth_context.user_id).await
+    }
+
     pub async fn get_user_meals(&self, user_id: &str, auth_context: &AuthContext) -> Result<Vec<Meal>> {
         // Authorization check
         if auth_context.user_id != user_id {
             return Err(anyhow::anyhow!("You can only access your own meals"));
         }
 
-        self.meal_repository.get_user_meals(user_id).await
+        self.meal_repository.get_user_meals(user_id, None).await
+    }
+
+    /// One page of the user's meals, newest first, with the total count when it was
+    /// asked for
+    pub async fn get_user_meals_page(&self, user_id: &str, params: &PageParams, auth_context: &AuthContext) -> Result<ListResponse<Meal>> {
+        // Authorization check
+        if auth_context.user_id != user_id {
+            return Err(anyhow::anyhow!("You can only access your own meals"));
+        }
+
+        let (meals, last_key) = self.meal_repository
+            .get_user_meals_page(user_id, params.limit, params.start_key())
+            .await?;
+        let total = if params.include_total {
+            Some(self.meal_repository.count_user_meals(user_id).await?)
+        } else {
+            None
+        };
+
+        Ok(ListResponse::new(meals, params, last_key, total))
     }
 
     pub async fn update_meal(&self, user_id: &str, meal_id: &str, updates: &UpdateMealRequest, auth_context: &AuthContext) -> Result<Meal> {
@@ -227,3 +302,57 @@ impl MealService {
         self.meal_repository.delete_meal(user_id, meal_id).await
     }
 }
+
+/// Parse an inclusive `YYYY-MM-DD` range, rejecting ones that run backwards or span
+/// more than `max_days` days
+pub fn parse_date_range(start: &str, end: &str, max_days: i64) -> Result<(NaiveDate, NaiveDate)> {
+    let parse = |date: &str| NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| anyhow::anyhow!("Invalid date format. Use YYYY-MM-DD for start and end"));
+    let start_date = parse(start)?;
+    let end_date = parse(end)?;
+
+    if end_date < start_date {
+        return Err(anyhow::anyhow!("Invalid date range: end is before start"));
+    }
+    if (end_date - start_date).num_days() + 1 > max_days {
+        return Err(anyhow::anyhow!("Invalid date range: at most {} days can be requested at once", max_days));
+    }
+
+    Ok((start_date, end_date))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_date_range_accepts_a_range_up_to_max_days() {
+        let (start, end) = parse_date_range("2024-03-01", "2024-03-31", 31).unwrap();
+        assert_eq!(start, NaiveDate::from_ymd_opt(2024, 3, 1).unwrap());
+        assert_eq!(end, NaiveDate::from_ymd_opt(2024, 3, 31).unwrap());
+
+        // A single day is a one-day range
+        assert!(parse_date_range("2024-03-01", "2024-03-01", 1).is_ok());
+    }
+
+    #[test]
+    fn test_parse_date_range_rejects_reversed_ranges() {
+        let error = parse_date_range("2024-03-02", "2024-03-01", 31).unwrap_err();
+        assert!(error.to_string().contains("end is before start"));
+    }
+
+    #[test]
+    fn test_parse_date_range_rejects_oversized_ranges() {
+        let error = parse_date_range("2024-03-01", "2024-04-01", 31).unwrap_err();
+        assert!(error.to_string().contains("at most 31 days"));
+
+        let error = parse_date_range("2024-01-01", "2024-12-31", 90).unwrap_err();
+        assert!(error.to_string().contains("at most 90 days"));
+    }
+
+    #[test]
+    fn test_parse_date_range_rejects_malformed_dates() {
+        assert!(parse_date_range("03/01/2024", "2024-03-31", 31).is_err());
+        assert!(parse_date_range("2024-02-30", "2024-03-01", 31).is_err());
+    }
+}
@@ -1,22 +1,147 @@
 use serde_json::{json, Value};
 use anyhow::Result;
 use chrono::{Utc, Datelike};
+use std::collections::BTreeMap;
+use tracing::warn;
 
-use crate::repository::{MealRepository, WaterRepository};
+use crate::repository::{FoodRepository, MealRepository, WaterRepository};
+use crate::service::meal_service::parse_date_range;
+use crate::utils::constants::DEFAULT_MEAL_RANGE_MAX_DAYS;
 use auth_layer::AuthContext;
 
 #[derive(Clone)]
 pub struct NutritionStatsService {
     meal_repository: MealRepository,
     water_repository: WaterRepository,
+    food_repository: FoodRepository,
+    max_range_days: i64,
+}
+
+/// Calories and macros summed over one day or a whole period
+#[derive(Debug, Clone, Copy, Default)]
+struct MacroTotals {
+    calories: f32,
+    protein: f32,
+    carbs: f32,
+    fat: f32,
+    meals: usize,
+}
+
+impl MacroTotals {
+    fn add(&mut self, other: &MacroTotals) {
+        self.calories += other.calories;
+        self.protein += other.protein;
+        self.carbs += other.carbs;
+        self.fat += other.fat;
+        self.meals += other.meals;
+    }
+
+    fn to_json(self) -> Value {
+        json!({
+            "calories": self.calories.round(),
+            "protein": self.protein.round(),
+            "carbs": self.carbs.round(),
+            "fat": self.fat.round(),
+            "meals": self.meals
+        })
+    }
 }
 
 impl NutritionStatsService {
-    pub fn new(meal_repository: MealRepository, water_repository: WaterRepository) -> Self {
+    pub fn new(meal_repository: MealRepository, water_repository: WaterRepository, food_repository: FoodRepository) -> Self {
         Self {
             meal_repository,
             water_repository,
+            food_repository,
+            max_range_days: DEFAULT_MEAL_RANGE_MAX_DAYS,
+        }
+    }
+
+    /// Cap how many days `get_nutrition_stats_range` may span
+    pub fn with_max_range_days(mut self, max_range_days: i64) -> Self {
+        self.max_range_days = max_range_days;
+        self
+    }
+
+    /// Per-day calorie and macro totals from `start` through `end` (`YYYY-MM-DD`,
+    /// inclusive) for charting, plus totals and per-day averages over the period.
+    /// Macros come from each meal's food items; items whose food has since been deleted
+    /// are left out. Meals logged with custom nutrition (no food items) use their totals
+    pub async fn get_nutrition_stats_range(&self, user_id: &str, start: &str, end: &str, auth_context: &AuthContext) -> Result<Value> {
+        // Authorization check
+        if auth_context.user_id != user_id {
+            return Err(anyhow::anyhow!("You can only access your own nutrition statistics"));
         }
+
+        let (start_date, end_date) = parse_date_range(start, end, self.max_range_days)?;
+        let days = (end_date - start_date).num_days() + 1;
+
+        let mut daily: BTreeMap<String, MacroTotals> = start_date
+            .iter_days()
+            .take(days as usize)
+            .map(|date| (date.format("%Y-%m-%d").to_string(), MacroTotals::default()))
+            .collect();
+
+        let meals = self.meal_repository.get_meals_by_range(user_id, start_date, end_date).await?;
+
+        // Look every referenced food up in one batch, however many meals use it
+        let food_ids: Vec<String> = meals
+            .iter()
+            .flat_map(|meal| meal.foods.iter().map(|item| item.food_id.clone()))
+            .collect();
+        let known_foods = self.food_repository.get_foods_by_ids(&food_ids).await?;
+
+        for meal in &meals {
+            let Some(day) = daily.get_mut(&meal.meal_date.format("%Y-%m-%d").to_string()) else {
+                continue;
+            };
+
+            let mut meal_totals = MacroTotals { meals: 1, ..MacroTotals::default() };
+            if meal.foods.is_empty() {
+                meal_totals.calories = meal.total_calories;
+                meal_totals.protein = meal.total_protein;
+                meal_totals.carbs = meal.total_carbs;
+                meal_totals.fat = meal.total_fat;
+            }
+            for item in &meal.foods {
+                if !known_foods.contains_key(&item.food_id) {
+                    warn!("Skipping food {} in meal {}: the food no longer exists", item.food_id, meal.id);
+                    continue;
+                }
+                meal_totals.calories += item.calories;
+                meal_totals.protein += item.protein;
+                meal_totals.carbs += item.total_carbs;
+                meal_totals.fat += item.total_fat;
+            }
+
+            day.add(&meal_totals);
+        }
+
+        let mut totals = MacroTotals::default();
+        let series: Vec<Value> = daily
+            .iter()
+            .map(|(date, day)| {
+                totals.add(day);
+                let mut point = day.to_json();
+                point["date"] = json!(date);
+                point
+            })
+            .collect();
+        let average = |value: f32| (value as f64 / days as f64).round();
+
+        Ok(json!({
+            "start": start,
+            "end": end,
+            "days": days,
+            "series": series,
+            "totals": totals.to_json(),
+            "averages": {
+                "calories": average(totals.calories),
+                "protein": average(totals.protein),
+                "carbs": average(totals.carbs),
+                "fat": average(totals.fat)
+            }
+        }))
     }
 
     pub async fn get_nutrition_stats(&self, user_id: &str, auth_context: &AuthContext) -> Result<Value> {
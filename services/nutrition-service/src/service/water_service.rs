@@ -1,6 +1,9 @@
 use anyhow::Result;
+use tracing::warn;
 
+use crate::models::WaterProgress;
 use crate::repository::WaterRepository;
+use crate::utils::constants::DEFAULT_WATER_GOAL_GLASSES;
 use auth_layer::AuthContext;
 
 #[derive(Clone)]
@@ -13,13 +16,14 @@ impl WaterService {
         Self { water_repository }
     }
 
-    pub async fn get_water(&self, user_id: &str, date: &str, auth_context: &AuthContext) -> Result<Option<u32>> {
+    pub async fn get_water(&self, user_id: &str, date: &str, auth_context: &AuthContext) -> Result<WaterProgress> {
         // Authorization check
         if auth_context.user_id != user_id {
             return Err(anyhow::anyhow!("You can only access your own water intake data"));
         }
 
-        self.water_repository.get_water_by_date(user_id, date).await
+        let glasses = self.water_repository.get_water_by_date(user_id, date).await?.unwrap_or(0);
+        Ok(WaterProgress::new(date, glasses, self.daily_goal(user_id).await))
     }
 
     pub async fn set_water(&self, user_id: &str, date: &str, glasses: u32, auth_context: &AuthContext) -> Result<()> {
@@ -30,4 +34,38 @@ impl WaterService {
 
         self.water_repository.set_water_by_date(user_id, date, glasses).await
     }
+
+    /// Add to the day's intake atomically, so concurrent increments from the client are
+    /// never lost
+    pub async fn add_water(&self, user_id: &str, date: &str, glasses: u32, auth_context: &AuthContext) -> Result<WaterProgress> {
+        // Authorization check
+        if auth_context.user_id != user_id {
+            return Err(anyhow::anyhow!("You can only update your own water intake data"));
+        }
+
+        let total = self.water_repository.add_water_by_date(user_id, date, glasses).await?;
+        Ok(WaterProgress::new(date, total, self.daily_goal(user_id).await))
+    }
+
+    /// Clear the day's intake back to zero, for correcting mistaken entries
+    pub async fn reset_water(&self, user_id: &str, date: &str, auth_context: &AuthContext) -> Result<WaterProgress> {
+        // Authorization check
+        if auth_context.user_id != user_id {
+            return Err(anyhow::anyhow!("You can only update your own water intake data"));
+        }
+
+        self.water_repository.delete_water_by_date(user_id, date).await?;
+        Ok(WaterProgress::new(date, 0, self.daily_goal(user_id).await))
+    }
+
+    /// The user's configured goal, falling back to the default when unset or unreadable
+    async fn daily_goal(&self, user_id: &str) -> u32 {
+        match self.water_repository.get_daily_water_goal(user_id).await {
+            Ok(goal) => goal.unwrap_or(DEFAULT_WATER_GOAL_GLASSES),
+            Err(e) => {
+                warn!("Using the default water goal: {}", e);
+                DEFAULT_WATER_GOAL_GLASSES
+            }
+        }
+    }
 }
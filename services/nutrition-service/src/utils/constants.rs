@@ -29,5 +29,11 @@ pub const DEFAULT_SLEEP_PERIOD: &str = "month";
 pub const DEFAULT_PROFILE_VISIBILITY: &str = "private";
 pub const DEFAULT_UNITS: &str = "metric";
 pub const DEFAULT_TIMEZONE: &str = "UTC";
-pub const DEFAULT_TABLE_NAME: &str = "gymcoach-ai-main";
 pub const DEFAULT_S3_BUCKET: &str = "gymcoach-ai-user-uploads";
+
+// Meal range queries
+pub const MEAL_RANGE_MAX_DAYS_ENV: &str = "MEAL_RANGE_MAX_DAYS";
+pub const DEFAULT_MEAL_RANGE_MAX_DAYS: i64 = 31;
+
+// Water intake
+pub const DEFAULT_WATER_GOAL_GLASSES: u32 = 8;
@@ -0,0 +1,55 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+/// Set once the container has served its first request
+static INVOKED: AtomicBool = AtomicBool::new(false);
+/// When container initialization started, as seen by this process
+static INIT_STARTED: OnceLock<Instant> = OnceLock::new();
+
+/// Whether the container has not served a request yet. Stays `true` until the first
+/// request is recorded with `take_cold_start`, so handlers can tag first-request metrics
+pub fn is_cold_start() -> bool {
+    !INVOKED.load(Ordering::Acquire)
+}
+
+/// Returns `true` for exactly one caller per container lifetime: the first request
+pub fn take_cold_start() -> bool {
+    first_invocation(&INVOKED)
+}
+
+fn first_invocation(invoked: &AtomicBool) -> bool {
+    !invoked.swap(true, Ordering::AcqRel)
+}
+
+/// Note the start of container initialization. Call it first thing in `main` for an
+/// accurate `InitDurationMs`; otherwise the first `PerformanceOptimizer` marks it.
+/// Later calls have no effect
+pub fn mark_init_start() {
+    INIT_STARTED.get_or_init(Instant::now);
+}
+
+/// Time between the start of initialization and `now`
+pub fn init_duration(now: Instant) -> Duration {
+    INIT_STARTED
+        .get()
+        .map(|started| now.saturating_duration_since(*started))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cold_start_flips_once() {
+        mark_init_start();
+        assert!(init_duration(Instant::now()) < Duration::from_secs(60));
+
+        // The process-wide flag is shared with other tests, so exercise a local one
+        let invoked = AtomicBool::new(false);
+        assert!(first_invocation(&invoked));
+        assert!(!first_invocation(&invoked));
+        assert!(!first_invocation(&invoked));
+    }
+}
@@ -4,6 +4,14 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::time::Instant;
 
+pub mod cold_start;
+pub mod request_stats;
+pub mod resource_usage;
+
+pub use cold_start::{is_cold_start, mark_init_start};
+pub use request_stats::{record_database_query, RequestStats};
+pub use resource_usage::ResourceSample;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PerformanceMetrics {
     pub request_id: String,
@@ -19,12 +27,20 @@ pub struct PerformanceMetrics {
     pub cache_misses: u32,
     pub error_count: u32,
     pub status_code: u16,
+    /// First request served by this container
+    pub cold_start: bool,
+    /// Time from container initialization to the first request; cold starts only
+    pub init_duration_ms: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OptimizationConfig {
     pub enable_caching: bool,
     pub cache_ttl_seconds: u64,
+    /// Spread each entry's TTL by up to ± this percentage so entries written together
+    /// don't all expire together; `0` disables jitter
+    #[serde(default)]
+    pub cache_ttl_jitter_percent: u32,
     pub max_cache_size: usize,
     pub enable_compression: bool,
     pub compression_level: u32,
@@ -41,6 +57,7 @@ impl Default for OptimizationConfig {
         Self {
             enable_caching: true,
             cache_ttl_seconds: 300, // 5 minutes
+            cache_ttl_jitter_percent: 10,
             max_cache_size: 1000,
             enable_compression: true,
             compression_level: 6,
@@ -57,6 +74,8 @@ impl Default for OptimizationConfig {
 pub struct PerformanceOptimizer {
     config: OptimizationConfig,
     cache: HashMap<String, CacheEntry>,
+    /// Monotonic counter stamped on entries when they are written or read
+    cache_clock: u64,
     connection_pool: Option<ConnectionPool>,
     metrics: Vec<PerformanceMetrics>,
 }
@@ -67,6 +86,8 @@ struct CacheEntry {
     expires_at: DateTime<Utc>,
     access_count: u64,
     last_accessed: DateTime<Utc>,
+    /// `cache_clock` value of the last write or read; orders entries for LRU eviction
+    last_used: u64,
 }
 
 struct ConnectionPool {
@@ -77,12 +98,14 @@ struct ConnectionPool {
 
 impl PerformanceOptimizer {
     pub fn new(config: OptimizationConfig) -> Self {
+        mark_init_start();
         let enable_connection_pooling = config.enable_connection_pooling;
         let max_connections = config.max_connections;
 
         Self {
             config,
             cache: HashMap::new(),
+            cache_clock: 0,
             connection_pool: if enable_connection_pooling {
                 Some(ConnectionPool {
                     max_connections,
@@ -108,15 +131,17 @@ impl PerformanceOptimizer {
     {
         let start_time = Utc::now();
         let start_instant = Instant::now();
-        let memory_before = self.get_memory_usage();
-        let cpu_before = self.get_cpu_usage();
+        let cold_start = cold_start::take_cold_start();
+        let init_duration_ms =
+            cold_start.then(|| cold_start::init_duration(start_instant).as_millis() as u64);
+        let usage_before = ResourceSample::now();
 
-        let result = handler.await;
+        let stats = RequestStats::new();
+        let result = stats.scope(handler).await;
 
         let end_time = Utc::now();
         let duration = start_instant.elapsed();
-        let memory_after = self.get_memory_usage();
-        let cpu_after = self.get_cpu_usage();
+        let usage_after = ResourceSample::now();
 
         let metrics = PerformanceMetrics {
             request_id: request_id.clone(),
@@ -125,13 +150,16 @@ impl PerformanceOptimizer {
             start_time,
             end_time,
             duration_ms: duration.as_millis() as u64,
-            memory_usage_mb: memory_after - memory_before,
-            cpu_usage_percent: cpu_after - cpu_before,
-            database_queries: 0, // This would be tracked by the database layer
-            cache_hits: 0,       // This would be tracked by the cache layer
-            cache_misses: 0,     // This would be tracked by the cache layer
+            // Zero where /proc is unavailable
+            memory_usage_mb: usage_after.memory_delta_mb(&usage_before).unwrap_or(0.0),
+            cpu_usage_percent: usage_after.cpu_percent_since(&usage_before).unwrap_or(0.0),
+            database_queries: stats.database_queries(),
+            cache_hits: stats.cache_hits(),
+            cache_misses: stats.cache_misses(),
             error_count: if result.is_err() { 1 } else { 0 },
             status_code: if result.is_ok() { 200 } else { 500 },
+            cold_start,
+            init_duration_ms,
         };
 
         self.metrics.push(metrics);
@@ -140,7 +168,7 @@ impl PerformanceOptimizer {
         result
     }
 
-    pub async fn get_cached<T>(&self, key: &str) -> Option<T>
+    pub async fn get_cached<T>(&mut self, key: &str) -> Option<T>
     where
         T: serde::de::DeserializeOwned,
     {
@@ -148,12 +176,18 @@ impl PerformanceOptimizer {
             return None;
         }
 
-        if let Some(entry) = self.cache.get(key) {
+        let tick = self.next_cache_tick();
+        if let Some(entry) = self.cache.get_mut(key) {
             if entry.expires_at > Utc::now() {
+                entry.access_count += 1;
+                entry.last_accessed = Utc::now();
+                entry.last_used = tick;
+                request_stats::record_cache_hit();
                 return serde_json::from_str(&entry.value).ok();
             }
         }
 
+        request_stats::record_cache_miss();
         None
     }
 
@@ -171,7 +205,11 @@ impl PerformanceOptimizer {
         }
 
         let serialized = serde_json::to_string(value)?;
-        let ttl = ttl_seconds.unwrap_or(self.config.cache_ttl_seconds);
+        let ttl = jittered_ttl(
+            ttl_seconds.unwrap_or(self.config.cache_ttl_seconds),
+            self.config.cache_ttl_jitter_percent,
+            random_sample(key),
+        );
         let expires_at = Utc::now() + chrono::Duration::seconds(ttl as i64);
 
         let entry = CacheEntry {
@@ -179,6 +217,7 @@ impl PerformanceOptimizer {
             expires_at,
             access_count: 0,
             last_accessed: Utc::now(),
+            last_used: self.next_cache_tick(),
         };
 
         self.cache.insert(key.to_string(), entry);
@@ -191,6 +230,13 @@ impl PerformanceOptimizer {
         Ok(())
     }
 
+    /// Drop every entry whose key starts with `prefix`, returning how many went
+    pub fn remove_cached_prefix(&mut self, prefix: &str) -> usize {
+        let before = self.cache.len();
+        self.cache.retain(|key, _| !key.starts_with(prefix));
+        before - self.cache.len()
+    }
+
     pub async fn batch_process<T, F>(&self, items: Vec<T>, processor: F) -> Result<Vec<Result<T>>>
     where
         F: Fn(T) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<T>> + Send>>
@@ -287,6 +333,10 @@ impl PerformanceOptimizer {
         } else {
             0.0
         };
+        let mut durations: Vec<u64> = self.metrics.iter().map(|m| m.duration_ms).collect();
+        durations.sort_unstable();
+        let cache_hits = self.metrics.iter().map(|m| m.cache_hits).sum::<u32>();
+        let cache_lookups = cache_hits + self.metrics.iter().map(|m| m.cache_misses).sum::<u32>();
         let average_cpu = if total_requests > 0 {
             self.metrics
                 .iter()
@@ -307,13 +357,15 @@ impl PerformanceOptimizer {
                 0.0
             },
             average_duration_ms: average_duration,
+            min_duration_ms: durations.first().copied().unwrap_or(0),
+            max_duration_ms: durations.last().copied().unwrap_or(0),
+            p50_duration_ms: percentile(&durations, 50.0),
+            p90_duration_ms: percentile(&durations, 90.0),
+            p99_duration_ms: percentile(&durations, 99.0),
             average_memory_mb: average_memory,
             average_cpu_percent: average_cpu,
-            cache_hit_rate: if total_requests > 0 {
-                self.metrics.iter().map(|m| m.cache_hits).sum::<u32>() as f64
-                    / (self.metrics.iter().map(|m| m.cache_hits).sum::<u32>()
-                        + self.metrics.iter().map(|m| m.cache_misses).sum::<u32>())
-                        as f64
+            cache_hit_rate: if cache_lookups > 0 {
+                cache_hits as f64 / cache_lookups as f64
             } else {
                 0.0
             },
@@ -325,31 +377,57 @@ impl PerformanceOptimizer {
         self.cache.retain(|_, entry| entry.expires_at > now);
     }
 
+    /// Evict least recently used entries until the cache is back within `max_cache_size`
     fn cleanup_oldest_cache_entries(&mut self) {
+        let to_remove = self.cache.len().saturating_sub(self.config.max_cache_size);
+        if to_remove == 0 {
+            return;
+        }
+
         let mut entries: Vec<_> = self
             .cache
             .iter()
-            .map(|(k, v)| (k.clone(), v.clone()))
+            .map(|(key, entry)| (entry.last_used, key.clone()))
             .collect();
-        entries.sort_by_key(|(_, entry)| entry.last_accessed);
+        entries.sort_unstable();
 
-        let to_remove = entries.len() - self.config.max_cache_size;
-        for (key, _) in entries.iter().take(to_remove) {
-            self.cache.remove(key);
+        for (_, key) in entries.into_iter().take(to_remove) {
+            self.cache.remove(&key);
         }
     }
 
-    fn get_memory_usage(&self) -> f64 {
-        // In a real implementation, this would get actual memory usage
-        // For now, we'll simulate it
-        std::process::id() as f64 / 1000.0
+    fn next_cache_tick(&mut self) -> u64 {
+        self.cache_clock += 1;
+        self.cache_clock
     }
+}
 
-    fn get_cpu_usage(&self) -> f64 {
-        // In a real implementation, this would get actual CPU usage
-        // For now, we'll simulate it
-        std::process::id() as f64 / 100.0
+/// Shift `ttl` by up to ± `jitter_percent` of itself, choosing the offset from `sample`
+/// (uniform over `u64`). Never returns less than one second for a non-zero TTL
+fn jittered_ttl(ttl: u64, jitter_percent: u32, sample: u64) -> u64 {
+    let spread = ttl * u64::from(jitter_percent.min(100)) / 100;
+    if spread == 0 {
+        return ttl;
     }
+
+    let offset = sample % (2 * spread + 1);
+    (ttl + offset).saturating_sub(spread).max(1)
+}
+
+/// A random value per call, from the std hasher's randomly seeded keys
+fn random_sample(key: &str) -> u64 {
+    use std::hash::BuildHasher;
+
+    std::collections::hash_map::RandomState::new().hash_one(key)
+}
+
+/// Nearest-rank percentile of sorted values; 0 when there are none
+fn percentile(sorted: &[u64], percent: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (percent / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -359,6 +437,11 @@ pub struct PerformanceSummary {
     pub failed_requests: usize,
     pub success_rate: f64,
     pub average_duration_ms: f64,
+    pub min_duration_ms: u64,
+    pub max_duration_ms: u64,
+    pub p50_duration_ms: u64,
+    pub p90_duration_ms: u64,
+    pub p99_duration_ms: u64,
     pub average_memory_mb: f64,
     pub average_cpu_percent: f64,
     pub cache_hit_rate: f64,
@@ -429,6 +512,7 @@ impl DatabaseOptimizer {
 
     pub async fn execute_prepared(&self, name: &str, params: &[String]) -> Result<String> {
         if let Some(query) = self.prepared_statements.get(name) {
+            record_database_query();
             // In a real implementation, this would execute the prepared statement
             // with the provided parameters
             Ok(format!(
@@ -453,38 +537,134 @@ mod tests {
         assert!(optimizer.metrics.is_empty());
     }
 
-    #[test]
-    // async fn test_cache_operations() {
-    //     let mut optimizer = PerformanceOptimizer::new(OptimizationConfig::default());
+    #[tokio::test]
+    async fn test_cache_operations() {
+        let mut optimizer = PerformanceOptimizer::new(OptimizationConfig::default());
+
+        // Test cache set
+        let result = optimizer.set_cached("test_key", &"test_value", None).await;
+        assert!(result.is_ok());
+
+        // Test cache get
+        let cached_value: Option<String> = optimizer.get_cached("test_key").await;
+        assert_eq!(cached_value, Some("test_value".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_remove_cached_prefix() {
+        let mut optimizer = PerformanceOptimizer::new(OptimizationConfig::default());
+        optimizer.set_cached("user-1:a", &1, None).await.unwrap();
+        optimizer.set_cached("user-1:b", &2, None).await.unwrap();
+        optimizer.set_cached("user-2:a", &3, None).await.unwrap();
+
+        assert_eq!(optimizer.remove_cached_prefix("user-1:"), 2);
+        assert_eq!(optimizer.get_cached::<i32>("user-1:a").await, None);
+        assert_eq!(optimizer.get_cached::<i32>("user-2:a").await, Some(3));
+    }
+
+    fn optimizer_with_capacity(max_cache_size: usize) -> PerformanceOptimizer {
+        PerformanceOptimizer::new(OptimizationConfig {
+            max_cache_size,
+            ..OptimizationConfig::default()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_cache_evicts_oldest_at_capacity() {
+        let mut optimizer = optimizer_with_capacity(2);
+        optimizer.set_cached("a", &1, None).await.unwrap();
+        optimizer.set_cached("b", &2, None).await.unwrap();
+        // At capacity nothing is evicted
+        optimizer.cleanup_oldest_cache_entries();
+        assert_eq!(optimizer.cache.len(), 2);
+
+        optimizer.set_cached("c", &3, None).await.unwrap();
+        assert_eq!(optimizer.cache.len(), 2);
+        assert!(!optimizer.cache.contains_key("a"));
+        assert!(optimizer.cache.contains_key("b") && optimizer.cache.contains_key("c"));
+    }
+
+    #[tokio::test]
+    async fn test_cache_eviction_follows_access_recency() {
+        let mut optimizer = optimizer_with_capacity(2);
+        optimizer.set_cached("a", &1, None).await.unwrap();
+        optimizer.set_cached("b", &2, None).await.unwrap();
 
-    //     // Test cache set
-    //     let result = optimizer.set_cached("test_key", &"test_value", None);
-    //     assert!(result.is_ok());
+        // Reading "a" makes "b" the least recently used entry
+        assert_eq!(optimizer.get_cached::<i32>("a").await, Some(1));
+        optimizer.set_cached("c", &3, None).await.unwrap();
+
+        assert!(optimizer.cache.contains_key("a"));
+        assert!(!optimizer.cache.contains_key("b"));
+        assert_eq!(optimizer.cache["a"].access_count, 1);
+    }
 
-    //     // Test cache get
-    //     let cached_value: Option<String> = optimizer.get_cached("test_key").await;
-    //     assert_eq!(cached_value, Some("test_value".to_string()));
-    // }
     #[test]
-    fn test_database_optimizer() {
+    fn test_ttl_jitter_stays_within_bounds() {
+        assert_eq!(jittered_ttl(300, 0, 12345), 300);
+        assert_eq!(jittered_ttl(300, 10, 0), 270);
+        assert_eq!(jittered_ttl(300, 10, 60), 330);
+        assert_eq!(jittered_ttl(300, 10, 30), 300);
+        assert_eq!(jittered_ttl(1, 100, 0), 1);
+
+        let ttls: std::collections::HashSet<u64> = (0..50)
+            .map(|i| jittered_ttl(300, 10, random_sample(&format!("key{}", i))))
+            .collect();
+        assert!(ttls.iter().all(|ttl| (270..=330).contains(ttl)));
+        assert!(ttls.len() > 1);
+    }
+
+    #[tokio::test]
+    async fn test_database_optimizer() {
         let mut db_optimizer = DatabaseOptimizer::new(true, 10);
 
         // Test query optimization
         let query = "SELECT * FROM users WHERE id = 1";
-        let optimized = db_optimizer.optimize_query(query);
+        let optimized = db_optimizer.optimize_query(query).await;
         assert!(optimized.is_ok());
 
         // Test prepared statement
-        let result = db_optimizer.prepare_statement("get_user", query);
+        let result = db_optimizer.prepare_statement("get_user", query).await;
         assert!(result.is_ok());
     }
 
-    #[test]
-    fn test_performance_summary() {
+    #[tokio::test]
+    async fn test_request_stats_feed_cache_hit_rate() {
+        let mut cache = PerformanceOptimizer::new(OptimizationConfig::default());
+        cache
+            .set_cached("workout:1", &"leg day", None)
+            .await
+            .unwrap();
+
         let mut optimizer = PerformanceOptimizer::new(OptimizationConfig::default());
+        optimizer
+            .optimize_request(
+                "req1".to_string(),
+                "workout-service".to_string(),
+                "get_workout".to_string(),
+                async {
+                    let hit: Option<String> = cache.get_cached("workout:1").await;
+                    let miss: Option<String> = cache.get_cached("workout:2").await;
+                    assert!(hit.is_some() && miss.is_none());
+                    Ok(())
+                },
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(optimizer.metrics[0].cache_hits, 1);
+        assert_eq!(optimizer.metrics[0].cache_misses, 1);
+        assert_eq!(optimizer.get_performance_summary().cache_hit_rate, 0.5);
+    }
 
-        // Add some test metrics
-        optimizer.metrics.push(PerformanceMetrics {
+    #[test]
+    fn test_cache_hit_rate_without_lookups() {
+        let optimizer = PerformanceOptimizer::new(OptimizationConfig::default());
+        assert_eq!(optimizer.get_performance_summary().cache_hit_rate, 0.0);
+    }
+
+    fn test_metrics() -> PerformanceMetrics {
+        PerformanceMetrics {
             request_id: "req1".to_string(),
             service: "test".to_string(),
             operation: "test".to_string(),
@@ -498,7 +678,17 @@ mod tests {
             cache_misses: 0,
             error_count: 0,
             status_code: 200,
-        });
+            cold_start: true,
+            init_duration_ms: Some(250),
+        }
+    }
+
+    #[test]
+    fn test_performance_summary() {
+        let mut optimizer = PerformanceOptimizer::new(OptimizationConfig::default());
+
+        // Add some test metrics
+        optimizer.metrics.push(test_metrics());
 
         let summary = optimizer.get_performance_summary();
         assert_eq!(summary.total_requests, 1);
@@ -509,5 +699,34 @@ mod tests {
         assert_eq!(summary.average_memory_mb, 10.0);
         assert_eq!(summary.average_cpu_percent, 5.0);
         assert_eq!(summary.cache_hit_rate, 1.0);
+        assert_eq!(summary.min_duration_ms, 100);
+        assert_eq!(summary.max_duration_ms, 100);
+        assert_eq!(summary.p99_duration_ms, 100);
+    }
+
+    #[test]
+    fn test_duration_percentiles() {
+        let mut optimizer = PerformanceOptimizer::new(OptimizationConfig::default());
+        // 1..=100 ms in shuffled order, plus one slow outlier
+        for i in 0..100u64 {
+            let mut metrics = test_metrics();
+            metrics.duration_ms = (i * 37) % 100 + 1;
+            optimizer.metrics.push(metrics);
+        }
+        let mut outlier = test_metrics();
+        outlier.duration_ms = 5_000;
+        optimizer.metrics.push(outlier);
+
+        let summary = optimizer.get_performance_summary();
+        assert_eq!(summary.min_duration_ms, 1);
+        assert_eq!(summary.max_duration_ms, 5_000);
+        assert_eq!(summary.p50_duration_ms, 51);
+        assert_eq!(summary.p90_duration_ms, 91);
+        assert_eq!(summary.p99_duration_ms, 100);
+
+        let empty = PerformanceOptimizer::new(OptimizationConfig::default());
+        let summary = empty.get_performance_summary();
+        assert_eq!(summary.p50_duration_ms, 0);
+        assert_eq!(summary.max_duration_ms, 0);
     }
 }
@@ -0,0 +1,58 @@
+use std::future::Future;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+
+tokio::task_local! {
+    static CURRENT_STATS: RequestStats;
+}
+
+/// Cache and database counters for a single request. `PerformanceOptimizer::optimize_request`
+/// scopes one around the handler and copies the totals into its `PerformanceMetrics`
+#[derive(Debug, Clone, Default)]
+pub struct RequestStats {
+    cache_hits: Arc<AtomicU32>,
+    cache_misses: Arc<AtomicU32>,
+    database_queries: Arc<AtomicU32>,
+}
+
+impl RequestStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cache_hits(&self) -> u32 {
+        self.cache_hits.load(Ordering::Relaxed)
+    }
+
+    pub fn cache_misses(&self) -> u32 {
+        self.cache_misses.load(Ordering::Relaxed)
+    }
+
+    pub fn database_queries(&self) -> u32 {
+        self.database_queries.load(Ordering::Relaxed)
+    }
+
+    /// Run a future with these counters as the current request's stats
+    pub async fn scope<F: Future>(&self, future: F) -> F::Output {
+        CURRENT_STATS.scope(self.clone(), future).await
+    }
+}
+
+/// Count a cache hit against the current request, if any
+pub fn record_cache_hit() {
+    increment(|stats| &stats.cache_hits);
+}
+
+/// Count a cache miss against the current request, if any
+pub fn record_cache_miss() {
+    increment(|stats| &stats.cache_misses);
+}
+
+/// Count a database query against the current request, if any
+pub fn record_database_query() {
+    increment(|stats| &stats.database_queries);
+}
+
+fn increment(counter: impl FnOnce(&RequestStats) -> &Arc<AtomicU32>) {
+    let _ = CURRENT_STATS.try_with(|stats| counter(stats).fetch_add(1, Ordering::Relaxed));
+}
@@ -0,0 +1,113 @@
+use std::time::Instant;
+
+/// Page size used to convert `statm` pages to bytes (4 KiB on Lambda x86_64 and arm64)
+const PAGE_SIZE_BYTES: u64 = 4096;
+/// Kernel clock ticks per second for `utime`/`stime` (`USER_HZ`, 100 on Linux)
+const CLOCK_TICKS_PER_SECOND: f64 = 100.0;
+
+/// Memory and CPU counters of this process at one point in time. Counters are `None`
+/// where `/proc` is unavailable (non-Linux), and so are the deltas computed from them
+#[derive(Debug, Clone, Copy)]
+pub struct ResourceSample {
+    pub rss_bytes: Option<u64>,
+    pub cpu_ticks: Option<u64>,
+    pub taken_at: Instant,
+}
+
+impl ResourceSample {
+    pub fn now() -> Self {
+        Self {
+            rss_bytes: std::fs::read_to_string("/proc/self/statm")
+                .ok()
+                .and_then(|statm| parse_statm_rss_pages(&statm))
+                .map(|pages| pages * PAGE_SIZE_BYTES),
+            cpu_ticks: std::fs::read_to_string("/proc/self/stat")
+                .ok()
+                .and_then(|stat| parse_stat_cpu_ticks(&stat)),
+            taken_at: Instant::now(),
+        }
+    }
+
+    /// Change in resident memory since `earlier`, in megabytes
+    pub fn memory_delta_mb(&self, earlier: &ResourceSample) -> Option<f64> {
+        let (now, before) = (self.rss_bytes?, earlier.rss_bytes?);
+        Some((now as f64 - before as f64) / (1024.0 * 1024.0))
+    }
+
+    /// CPU time used since `earlier` as a percentage of the wall time in between.
+    /// Can exceed 100 when several threads were busy
+    pub fn cpu_percent_since(&self, earlier: &ResourceSample) -> Option<f64> {
+        let ticks = self.cpu_ticks?.saturating_sub(earlier.cpu_ticks?);
+        let wall_seconds = self
+            .taken_at
+            .saturating_duration_since(earlier.taken_at)
+            .as_secs_f64();
+        if wall_seconds <= 0.0 {
+            return Some(0.0);
+        }
+
+        Some(ticks as f64 / CLOCK_TICKS_PER_SECOND / wall_seconds * 100.0)
+    }
+}
+
+/// Resident set size in pages: the second field of `/proc/self/statm`
+fn parse_statm_rss_pages(statm: &str) -> Option<u64> {
+    statm.split_whitespace().nth(1)?.parse().ok()
+}
+
+/// `utime + stime` from `/proc/self/stat`. Fields are counted after the `comm` field,
+/// which is parenthesised and may itself contain spaces
+fn parse_stat_cpu_ticks(stat: &str) -> Option<u64> {
+    let (_, rest) = stat.rsplit_once(')')?;
+    let mut fields = rest.split_whitespace();
+    // `rest` starts at field 3 (state); utime and stime are fields 14 and 15
+    let utime: u64 = fields.nth(11)?.parse().ok()?;
+    let stime: u64 = fields.next()?.parse().ok()?;
+    Some(utime + stime)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_parse_proc_files() {
+        assert_eq!(
+            parse_statm_rss_pages("10925 2571 1854 2 0 1203 0\n"),
+            Some(2571)
+        );
+        assert_eq!(parse_statm_rss_pages(""), None);
+
+        let stat = "4242 (my service) S 1 4242 4242 0 -1 4194560 1200 0 0 0 \
+                    150 37 0 0 20 0 4 0 5321 45600768 2571 18446744073709551615";
+        assert_eq!(parse_stat_cpu_ticks(stat), Some(187));
+        assert_eq!(parse_stat_cpu_ticks("4242 (truncated"), None);
+    }
+
+    #[test]
+    fn test_deltas_between_samples() {
+        let start = Instant::now();
+        let before = ResourceSample {
+            rss_bytes: Some(64 * 1024 * 1024),
+            cpu_ticks: Some(100),
+            taken_at: start,
+        };
+        let after = ResourceSample {
+            rss_bytes: Some(80 * 1024 * 1024),
+            cpu_ticks: Some(150),
+            taken_at: start + Duration::from_secs(1),
+        };
+
+        assert_eq!(after.memory_delta_mb(&before), Some(16.0));
+        assert_eq!(after.cpu_percent_since(&before), Some(50.0));
+
+        let unavailable = ResourceSample {
+            rss_bytes: None,
+            cpu_ticks: None,
+            taken_at: start,
+        };
+        assert_eq!(after.memory_delta_mb(&unavailable), None);
+        assert_eq!(after.cpu_percent_since(&unavailable), None);
+    }
+}
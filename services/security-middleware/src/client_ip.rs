@@ -0,0 +1,189 @@
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+
+/// Environment variable holding a comma-separated list of trusted proxy IPs
+pub const TRUSTED_PROXIES_ENV: &str = "TRUSTED_PROXY_IPS";
+
+const CLOUDFRONT_VIEWER_ADDRESS: &str = "cloudfront-viewer-address";
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// Resolves the real client IP for requests arriving through CloudFront / API Gateway.
+///
+/// Forwarding headers are client-controlled, so they are only honored when the
+/// request's source IP is a trusted proxy. In that case, in order:
+/// 1. `CloudFront-Viewer-Address` (`ip:port`, set by CloudFront itself)
+/// 2. The right-most `X-Forwarded-For` hop that is not a trusted proxy
+///
+/// Otherwise, or if neither header gives an address, the source IP is the client
+#[derive(Debug, Clone, Default)]
+pub struct ClientIpResolver {
+    trusted_proxies: HashSet<IpAddr>,
+}
+
+impl ClientIpResolver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build a resolver trusting the proxies listed in `TRUSTED_PROXY_IPS`
+    pub fn from_env() -> Self {
+        let trusted = std::env::var(TRUSTED_PROXIES_ENV).unwrap_or_default();
+        Self::new().with_trusted_proxies(trusted.split(',').filter_map(parse_ip))
+    }
+
+    pub fn with_trusted_proxies<I: IntoIterator<Item = IpAddr>>(mut self, proxies: I) -> Self {
+        self.trusted_proxies
+            .extend(proxies.into_iter().map(|ip| ip.to_canonical()));
+        self
+    }
+
+    pub fn is_trusted(&self, ip: &IpAddr) -> bool {
+        self.trusted_proxies.contains(ip)
+    }
+
+    /// Resolve the client IP from `source_ip` and, when that is a trusted proxy, the
+    /// request headers (matched case-insensitively). Returns `None` if the source IP
+    /// does not parse as an address
+    pub fn resolve(&self, headers: &HashMap<String, String>, source_ip: &str) -> Option<IpAddr> {
+        let source = parse_ip(source_ip)?;
+        if !self.is_trusted(&source) {
+            return Some(source);
+        }
+
+        header(headers, CLOUDFRONT_VIEWER_ADDRESS)
+            .and_then(parse_viewer_address)
+            .or_else(|| {
+                header(headers, X_FORWARDED_FOR).and_then(|value| self.forwarded_client(value))
+            })
+            .or(Some(source))
+    }
+
+    /// Each proxy appends the address it received from, so only the hops right of
+    /// the first untrusted one were written by proxies we trust
+    fn forwarded_client(&self, forwarded_for: &str) -> Option<IpAddr> {
+        forwarded_for
+            .rsplit(',')
+            .filter_map(parse_ip)
+            .find(|ip| !self.is_trusted(ip))
+    }
+}
+
+fn header<'a>(headers: &'a HashMap<String, String>, name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// Parse a single address, accepting bare IPv4/IPv6 as well as `1.2.3.4:port` and
+/// `[::1]:port`. IPv4-mapped IPv6 addresses are normalized to IPv4
+pub fn parse_ip(value: &str) -> Option<IpAddr> {
+    let value = value.trim();
+    value
+        .parse::<IpAddr>()
+        .or_else(|_| value.parse::<SocketAddr>().map(|addr| addr.ip()))
+        .or_else(|_| value.trim_start_matches('[').trim_end_matches(']').parse())
+        .ok()
+        .map(|ip| ip.to_canonical())
+}
+
+/// `CloudFront-Viewer-Address` always carries a port, and IPv6 addresses are not
+/// bracketed (`2001:db8::1:443`), so the port is stripped from the last colon
+fn parse_viewer_address(value: &str) -> Option<IpAddr> {
+    let value = value.trim();
+    value
+        .parse::<SocketAddr>()
+        .map(|addr| addr.ip().to_canonical())
+        .ok()
+        .or_else(|| {
+            let (ip, port) = value.rsplit_once(':')?;
+            port.parse::<u16>().ok()?;
+            parse_ip(ip)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_forwarded_for_skips_trusted_hops() {
+        let resolver = ClientIpResolver::new()
+            .with_trusted_proxies(["10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap()]);
+
+        let h = headers(&[("x-forwarded-for", "10.0.0.1, 198.51.100.23, 10.0.0.2")]);
+        assert_eq!(
+            resolver.resolve(&h, "10.0.0.2"),
+            Some("198.51.100.23".parse().unwrap())
+        );
+
+        // Unparseable hops are ignored rather than failing the whole header
+        let h = headers(&[("X-Forwarded-For", "unknown, [2001:db8::5]:8443")]);
+        assert_eq!(
+            resolver.resolve(&h, "10.0.0.2"),
+            Some("2001:db8::5".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_viewer_address_with_port() {
+        let resolver =
+            ClientIpResolver::new().with_trusted_proxies(["130.176.0.10".parse().unwrap()]);
+
+        let h = headers(&[("CloudFront-Viewer-Address", "198.51.100.10:46532")]);
+        assert_eq!(
+            resolver.resolve(&h, "130.176.0.10"),
+            Some("198.51.100.10".parse().unwrap())
+        );
+
+        let h = headers(&[(
+            "cloudfront-viewer-address",
+            "2001:0db8:85a3:0000:0000:8a2e:0370:7334:443",
+        )]);
+        assert_eq!(
+            resolver.resolve(&h, "130.176.0.10"),
+            Some("2001:db8:85a3::8a2e:370:7334".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn test_falls_back_to_source_ip() {
+        let resolver = ClientIpResolver::new().with_trusted_proxies(["10.0.0.1".parse().unwrap()]);
+
+        let h = headers(&[("x-forwarded-for", "10.0.0.1")]);
+        assert_eq!(
+            resolver.resolve(&h, "::ffff:192.0.2.1"),
+            Some("192.0.2.1".parse().unwrap())
+        );
+        assert_eq!(resolver.resolve(&HashMap::new(), "not-an-ip"), None);
+    }
+
+    #[test]
+    fn test_forwarding_headers_from_untrusted_sources_are_ignored() {
+        let resolver = ClientIpResolver::new().with_trusted_proxies(["10.0.0.1".parse().unwrap()]);
+
+        // A direct caller can put anything in these headers
+        let h = headers(&[
+            ("X-Forwarded-For", "192.0.2.99"),
+            ("CloudFront-Viewer-Address", "192.0.2.98:443"),
+        ]);
+        assert_eq!(
+            resolver.resolve(&h, "203.0.113.7"),
+            Some("203.0.113.7".parse().unwrap())
+        );
+
+        // Through a trusted proxy, spoofed hops left of the real client are skipped
+        let h = headers(&[("X-Forwarded-For", "192.0.2.99, 203.0.113.7")]);
+        assert_eq!(
+            resolver.resolve(&h, "10.0.0.1"),
+            Some("203.0.113.7".parse().unwrap())
+        );
+    }
+}
@@ -1,8 +1,21 @@
 use anyhow::{anyhow, Result};
 use chrono::{DateTime, Utc};
+use lambda_router::Response;
 use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use std::collections::HashMap;
 
+pub mod client_ip;
+pub mod nosql;
+pub mod rate_limit;
+pub mod sanitize;
+mod validation;
+
+pub use client_ip::ClientIpResolver;
+pub use nosql::NoSqlInjectionDetector;
+pub use rate_limit::RateLimitMiddleware;
+pub use sanitize::{sanitize, SanitizePolicy};
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SecurityContext {
     pub request_id: String,
@@ -14,6 +27,13 @@ pub struct SecurityContext {
     pub security_headers: HashMap<String, String>,
 }
 
+/// Requests allowed per rate-limit window
+pub const RATE_LIMIT_LIMIT_HEADER: &str = "X-RateLimit-Limit";
+/// Requests left in the current window
+pub const RATE_LIMIT_REMAINING_HEADER: &str = "X-RateLimit-Remaining";
+/// When the current window resets, in Unix epoch seconds
+pub const RATE_LIMIT_RESET_HEADER: &str = "X-RateLimit-Reset";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RateLimitInfo {
     pub key: String,
@@ -23,6 +43,39 @@ pub struct RateLimitInfo {
     pub retry_after: Option<u32>,
 }
 
+impl RateLimitInfo {
+    /// Seconds until the client may retry: `retry_after` when the limiter set one,
+    /// otherwise the time left until the window resets (at least one second)
+    pub fn retry_after_secs(&self) -> u32 {
+        self.retry_after.unwrap_or_else(|| {
+            let seconds = (self.reset_time - Utc::now()).num_seconds();
+            seconds.clamp(1, u32::MAX as i64) as u32
+        })
+    }
+
+    /// A `429 Too Many Requests` response with `Retry-After` and the limit details
+    pub fn to_response(&self, message: &str) -> Response {
+        let response = Response::too_many_requests(self.retry_after_secs(), message);
+        let mut body: Value = serde_json::from_str(&response.body).unwrap_or_else(|_| json!({}));
+        body["limit"] = json!(self.limit);
+        body["remaining"] = json!(self.remaining);
+        body["reset_time"] = json!(self.reset_time);
+        self.apply_headers(response.json(body))
+    }
+
+    /// Add the `X-RateLimit-*` headers so clients can throttle themselves before
+    /// they are rejected
+    pub fn apply_headers(&self, response: Response) -> Response {
+        response
+            .header(RATE_LIMIT_LIMIT_HEADER, self.limit.to_string())
+            .header(RATE_LIMIT_REMAINING_HEADER, self.remaining.to_string())
+            .header(
+                RATE_LIMIT_RESET_HEADER,
+                self.reset_time.timestamp().to_string(),
+            )
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct SecurityValidationResult {
     pub is_valid: bool,
@@ -35,6 +88,11 @@ pub struct SecurityMiddleware {
     rate_limits: HashMap<String, RateLimitConfig>,
     blocked_ips: std::collections::HashSet<String>,
     suspicious_patterns: Vec<String>,
+    sql_injection_patterns: Vec<String>,
+    suspicious_user_agents: Vec<String>,
+    max_body_size: usize,
+    client_ip_resolver: ClientIpResolver,
+    nosql_detector: NoSqlInjectionDetector,
 }
 
 #[derive(Debug, Clone)]
@@ -45,8 +103,166 @@ pub struct RateLimitConfig {
     pub burst_limit: u32,
 }
 
-impl SecurityMiddleware {
-    pub fn new() -> Self {
+/// Default request body limit (10MB)
+pub const DEFAULT_MAX_BODY_SIZE: usize = 10 * 1024 * 1024;
+
+/// Markup-shaped XSS patterns: opening tags, script URLs and inline event-handler
+/// attributes. Bare words such as "script" or "eval" would also reject ordinary
+/// workout notes ("follow the script", "evaluation week")
+#[cfg(feature = "default-patterns")]
+const DEFAULT_XSS_PATTERNS: [&str; 51] = [
+    "<script",
+    "<iframe",
+    "<object",
+    "<embed",
+    "<svg",
+    "<math",
+    "<meta",
+    "<base",
+    "javascript:",
+    "vbscript:",
+    "data:text/html",
+    "eval(",
+    "expression(",
+    "onload=",
+    "onerror=",
+    "onclick=",
+    "onmouseover=",
+    "onfocus=",
+    "onblur=",
+    "onchange=",
+    "onsubmit=",
+    "onreset=",
+    "onselect=",
+    "onkeydown=",
+    "onkeyup=",
+    "onkeypress=",
+    "onmousedown=",
+    "onmouseup=",
+    "onmousemove=",
+    "onmouseout=",
+    "onmouseenter=",
+    "onmouseleave=",
+    "oncontextmenu=",
+    "ondblclick=",
+    "onwheel=",
+    "ontouchstart=",
+    "ontouchend=",
+    "ontouchmove=",
+    "ontouchcancel=",
+    "onpointerdown=",
+    "onpointerup=",
+    "onpointermove=",
+    "onpointercancel=",
+    "onpointerenter=",
+    "onpointerleave=",
+    "onpointerover=",
+    "onpointerout=",
+    "onpointerlockchange=",
+    "onpointerlockerror=",
+    "ongotpointercapture=",
+    "onlostpointercapture=",
+];
+
+/// Injection-shaped SQL patterns: a quote breaking out into a tautology, comment or
+/// stacked statement, UNION-based reads and functions only an attacker probing a SQL
+/// database would call. Bare keywords such as "update set", "delete from" or "sleep("
+/// would also reject ordinary notes ("update sets to 4x8", "delete from plan"). Written
+/// in the form `collapse_sql_whitespace` leaves input in
+#[cfg(feature = "default-patterns")]
+const DEFAULT_SQL_INJECTION_PATTERNS: [&str; 24] = [
+    "'or'1'='1",
+    "'or 1=1",
+    "\"or\"1\"=\"1",
+    "\"or 1=1",
+    "'and 1=1",
+    "'and 1=2",
+    "'--",
+    "';--",
+    "'/*",
+    "';drop",
+    "';delete",
+    "';insert",
+    "';update",
+    "';exec",
+    "';shutdown",
+    ";drop table",
+    ";truncate table",
+    "union select",
+    "union all select",
+    "waitfor delay'",
+    "pg_sleep(",
+    "load_file(",
+    "into outfile",
+    "xp_cmdshell",
+];
+
+#[cfg(feature = "default-patterns")]
+const DEFAULT_SUSPICIOUS_USER_AGENTS: [&str; 28] = [
+    "sqlmap",
+    "nikto",
+    "nmap",
+    "masscan",
+    "zap",
+    "burp",
+    "w3af",
+    "acunetix",
+    "nessus",
+    "openvas",
+    "qualys",
+    "rapid7",
+    "tenable",
+    "veracode",
+    "checkmarx",
+    "fortify",
+    "appscan",
+    "webinspect",
+    "paros",
+    "wget",
+    "curl",
+    "python-requests",
+    "go-http-client",
+    "java-http-client",
+    "okhttp",
+    "apache-httpclient",
+    "libwww-perl",
+    "lwp-trivial",
+];
+
+/// Tunables for `SecurityMiddleware::with_config`. Patterns are matched
+/// case-insensitively as substrings, SQL ones ignoring whitespace next to quotes, `;`
+/// and `=`; `Default` gives the built-in lists (empty when the `default-patterns`
+/// feature is off) and `empty` none at all
+#[derive(Debug, Clone)]
+pub struct SecurityConfig {
+    pub rate_limits: HashMap<String, RateLimitConfig>,
+    pub xss_patterns: Vec<String>,
+    pub sql_injection_patterns: Vec<String>,
+    pub suspicious_user_agents: Vec<String>,
+    pub max_body_size: usize,
+    pub nosql_detector: NoSqlInjectionDetector,
+}
+
+impl SecurityConfig {
+    /// Stop warning about user agents containing `agent`, e.g. `curl` for
+    /// server-to-server callers
+    pub fn allow_user_agent(mut self, agent: &str) -> Self {
+        let agent = agent.to_lowercase();
+        self.suspicious_user_agents
+            .retain(|pattern| !agent.contains(pattern.as_str()));
+        self
+    }
+
+    pub fn with_max_body_size(mut self, max_body_size: usize) -> Self {
+        self.max_body_size = max_body_size;
+        self
+    }
+}
+
+impl SecurityConfig {
+    /// The default rate limits and body size with no XSS, SQL injection or user-agent
+    /// patterns
+    pub fn empty() -> Self {
         let mut rate_limits = HashMap::new();
 
         // Default rate limits for different endpoints
@@ -82,66 +298,94 @@ impl SecurityMiddleware {
 
         Self {
             rate_limits,
+            xss_patterns: Vec::new(),
+            sql_injection_patterns: Vec::new(),
+            suspicious_user_agents: Vec::new(),
+            max_body_size: DEFAULT_MAX_BODY_SIZE,
+            nosql_detector: NoSqlInjectionDetector::from_env(),
+        }
+    }
+}
+
+impl Default for SecurityConfig {
+    #[cfg(feature = "default-patterns")]
+    fn default() -> Self {
+        Self {
+            xss_patterns: to_strings(&DEFAULT_XSS_PATTERNS),
+            sql_injection_patterns: to_strings(&DEFAULT_SQL_INJECTION_PATTERNS),
+            suspicious_user_agents: to_strings(&DEFAULT_SUSPICIOUS_USER_AGENTS),
+            ..Self::empty()
+        }
+    }
+
+    #[cfg(not(feature = "default-patterns"))]
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+#[cfg(feature = "default-patterns")]
+fn to_strings(patterns: &[&str]) -> Vec<String> {
+    patterns.iter().map(|p| p.to_string()).collect()
+}
+
+/// Lowercase and drop duplicates, keeping the first occurrence
+fn normalize_patterns(patterns: Vec<String>) -> Vec<String> {
+    let mut normalized = Vec::with_capacity(patterns.len());
+    for pattern in patterns {
+        add_pattern(&mut normalized, &pattern);
+    }
+    normalized
+}
+
+fn add_pattern(patterns: &mut Vec<String>, pattern: &str) {
+    let pattern = pattern.trim().to_lowercase();
+    if !pattern.is_empty() && !patterns.contains(&pattern) {
+        patterns.push(pattern);
+    }
+}
+
+impl Default for SecurityMiddleware {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SecurityMiddleware {
+    pub fn new() -> Self {
+        Self::with_config(SecurityConfig::default())
+    }
+
+    /// A middleware without any XSS, SQL injection or user-agent patterns, for services
+    /// that only want IP blocking, rate limits and the body-size and NoSQL checks
+    pub fn empty() -> Self {
+        Self::with_config(SecurityConfig::empty())
+    }
+
+    pub fn with_config(config: SecurityConfig) -> Self {
+        Self {
+            rate_limits: config.rate_limits,
             blocked_ips: std::collections::HashSet::new(),
-            suspicious_patterns: vec![
-                "script".to_string(),
-                "javascript".to_string(),
-                "eval".to_string(),
-                "expression".to_string(),
-                "onload".to_string(),
-                "onerror".to_string(),
-                "onclick".to_string(),
-                "onmouseover".to_string(),
-                "onfocus".to_string(),
-                "onblur".to_string(),
-                "onchange".to_string(),
-                "onsubmit".to_string(),
-                "onreset".to_string(),
-                "onselect".to_string(),
-                "onkeydown".to_string(),
-                "onkeyup".to_string(),
-                "onkeypress".to_string(),
-                "onmousedown".to_string(),
-                "onmouseup".to_string(),
-                "onmousemove".to_string(),
-                "onmouseout".to_string(),
-                "onmouseenter".to_string(),
-                "onmouseleave".to_string(),
-                "oncontextmenu".to_string(),
-                "ondblclick".to_string(),
-                "onwheel".to_string(),
-                "ontouchstart".to_string(),
-                "ontouchend".to_string(),
-                "ontouchmove".to_string(),
-                "ontouchcancel".to_string(),
-                "onpointerdown".to_string(),
-                "onpointerup".to_string(),
-                "onpointermove".to_string(),
-                "onpointercancel".to_string(),
-                "onpointerenter".to_string(),
-                "onpointerleave".to_string(),
-                "onpointerover".to_string(),
-                "onpointerout".to_string(),
-                "onpointerlockchange".to_string(),
-                "onpointerlockerror".to_string(),
-                "ongotpointercapture".to_string(),
-                "onlostpointercapture".to_string(),
-                "onpointerdown".to_string(),
-                "onpointerup".to_string(),
-                "onpointermove".to_string(),
-                "onpointercancel".to_string(),
-                "onpointerenter".to_string(),
-                "onpointerleave".to_string(),
-                "onpointerover".to_string(),
-                "onpointerout".to_string(),
-                "onpointerlockchange".to_string(),
-                "onpointerlockerror".to_string(),
-                "ongotpointercapture".to_string(),
-                "onlostpointercapture".to_string(),
-            ],
+            suspicious_patterns: normalize_patterns(config.xss_patterns),
+            sql_injection_patterns: normalize_patterns(config.sql_injection_patterns)
+                .iter()
+                .map(|pattern| collapse_sql_whitespace(pattern))
+                .collect(),
+            suspicious_user_agents: normalize_patterns(config.suspicious_user_agents),
+            max_body_size: config.max_body_size,
+            client_ip_resolver: ClientIpResolver::from_env(),
+            nosql_detector: config.nosql_detector,
         }
     }
 
+    pub fn add_suspicious_pattern(&mut self, pattern: &str) {
+        add_pattern(&mut self.suspicious_patterns, pattern);
+    }
+
+    pub fn add_suspicious_user_agent(&mut self, agent: &str) {
+        add_pattern(&mut self.suspicious_user_agents, agent);
+    }
+
     pub async fn validate_request(
         &self,
         context: &SecurityContext,
@@ -150,8 +394,10 @@ impl SecurityMiddleware {
         let mut warnings = Vec::new();
         let mut rate_limit_info = None;
 
+        let client_ip = self.resolve_client_ip(context);
+
         // Check if IP is blocked
-        if self.blocked_ips.contains(&context.ip_address) {
+        if self.is_ip_blocked(&client_ip) {
             errors.push("IP address is blocked".to_string());
             return Ok(SecurityValidationResult {
                 is_valid: false,
@@ -162,7 +408,7 @@ impl SecurityMiddleware {
         }
 
         // Validate rate limiting
-        if let Some(rate_limit) = self.check_rate_limit(context).await? {
+        if let Some(rate_limit) = self.check_rate_limit(context, &client_ip).await? {
             if rate_limit.remaining == 0 {
                 errors.push("Rate limit exceeded".to_string());
                 return Ok(SecurityValidationResult {
@@ -203,8 +449,7 @@ impl SecurityMiddleware {
 
         // Validate request size
         if let Some(body) = context.security_headers.get("body") {
-            if body.len() > 10 * 1024 * 1024 {
-                // 10MB limit
+            if body.len() > self.max_body_size {
                 errors.push("Request body too large".to_string());
             }
         }
@@ -222,7 +467,28 @@ impl SecurityMiddleware {
         })
     }
 
-    async fn check_rate_limit(&self, context: &SecurityContext) -> Result<Option<RateLimitInfo>> {
+    /// The real client IP behind CloudFront, as a normalized string. Falls back to the
+    /// raw `ip_address` when it cannot be parsed
+    pub fn resolve_client_ip(&self, context: &SecurityContext) -> String {
+        self.client_ip_resolver
+            .resolve(&context.security_headers, &context.ip_address)
+            .map(|ip| ip.to_string())
+            .unwrap_or_else(|| context.ip_address.clone())
+    }
+
+    pub fn set_client_ip_resolver(&mut self, resolver: ClientIpResolver) {
+        self.client_ip_resolver = resolver;
+    }
+
+    pub fn set_nosql_detector(&mut self, detector: NoSqlInjectionDetector) {
+        self.nosql_detector = detector;
+    }
+
+    pub async fn check_rate_limit(
+        &self,
+        context: &SecurityContext,
+        client_ip: &str,
+    ) -> Result<Option<RateLimitInfo>> {
         let endpoint_type = self.get_endpoint_type(&context.security_headers);
         let config = self.rate_limits.get(&endpoint_type).ok_or_else(|| {
             anyhow!(
@@ -233,17 +499,26 @@ impl SecurityMiddleware {
 
         // In a real implementation, you would check against a cache like Redis
         // For now, we'll simulate the rate limit check
+        let remaining = config.requests_per_minute - 1; // Simulate some usage
+        let reset_time = Utc::now() + chrono::Duration::minutes(1);
         let rate_limit_info = RateLimitInfo {
-            key: context.rate_limit_key.clone(),
+            key: Self::rate_limit_key(&endpoint_type, client_ip),
             limit: config.requests_per_minute,
-            remaining: config.requests_per_minute - 1, // Simulate some usage
-            reset_time: Utc::now() + chrono::Duration::minutes(1),
-            retry_after: None,
+            remaining,
+            reset_time,
+            // Exhausted clients must wait for the window to reset
+            retry_after: (remaining == 0)
+                .then(|| (reset_time - Utc::now()).num_seconds().max(1) as u32),
         };
 
         Ok(Some(rate_limit_info))
     }
 
+    /// Rate limits are tracked per endpoint type and resolved client IP
+    fn rate_limit_key(endpoint_type: &str, client_ip: &str) -> String {
+        format!("{}:{}", endpoint_type, client_ip)
+    }
+
     fn get_endpoint_type(&self, headers: &HashMap<String, String>) -> String {
         if let Some(path) = headers.get("path") {
             if path.contains("/auth/") {
@@ -259,171 +534,40 @@ impl SecurityMiddleware {
     }
 
     fn contains_xss_patterns(&self, input: &str) -> bool {
-        let input_lower = input.to_lowercase();
+        let input_lower = collapse_markup_whitespace(&input.to_lowercase());
         self.suspicious_patterns
             .iter()
-            .any(|pattern| input_lower.contains(pattern))
+            .any(|pattern| input_lower.contains(pattern.as_str()))
     }
 
     fn contains_sql_injection_patterns(&self, input: &str) -> bool {
-        let sql_patterns = vec![
-            "union select",
-            "drop table",
-            "delete from",
-            "insert into",
-            "update set",
-            "create table",
-            "alter table",
-            "exec(",
-            "execute(",
-            "sp_",
-            "xp_",
-            "waitfor delay",
-            "benchmark(",
-            "sleep(",
-            "pg_sleep(",
-            "load_file(",
-            "into outfile",
-            "into dumpfile",
-            "char(",
-            "ascii(",
-            "ord(",
-            "hex(",
-            "unhex(",
-            "concat(",
-            "group_concat(",
-            "version()",
-            "database()",
-            "user()",
-            "current_user",
-            "current_database",
-            "information_schema",
-            "sys.tables",
-            "sys.columns",
-            "sys.databases",
-            "sys.users",
-            "sys.schemas",
-            "sys.objects",
-            "sys.tables",
-            "sys.columns",
-            "sys.databases",
-            "sys.users",
-            "sys.schemas",
-            "sys.objects",
-        ];
-
-        let input_lower = input.to_lowercase();
-        sql_patterns
+        let input_lower = collapse_sql_whitespace(&input.to_lowercase());
+        self.sql_injection_patterns
             .iter()
-            .any(|pattern| input_lower.contains(pattern))
+            .any(|pattern| input_lower.contains(pattern.as_str()))
     }
 
     fn contains_nosql_injection_patterns(&self, input: &str) -> bool {
-        let nosql_patterns = vec![
-            "$where",
-            "$ne",
-            "$gt",
-            "$lt",
-            "$gte",
-            "$lte",
-            "$in",
-            "$nin",
-            "$exists",
-            "$regex",
-            "$text",
-            "$search",
-            "$geoWithin",
-            "$geoIntersects",
-            "$near",
-            "$nearSphere",
-            "$center",
-            "$centerSphere",
-            "$box",
-            "$polygon",
-            "$geometry",
-            "$maxDistance",
-            "$minDistance",
-            "$all",
-            "$elemMatch",
-            "$size",
-            "$type",
-            "$mod",
-            "$bitsAllSet",
-            "$bitsAnySet",
-            "$bitsAllClear",
-            "$bitsAnyClear",
-            "$rand",
-            "$expr",
-            "$jsonSchema",
-            "$or",
-            "$and",
-            "$not",
-            "$nor",
-        ];
-
-        let input_lower = input.to_lowercase();
-        nosql_patterns
-            .iter()
-            .any(|pattern| input_lower.contains(pattern))
+        self.nosql_detector.is_injection(input)
     }
 
     fn is_suspicious_user_agent(&self, user_agent: &str) -> bool {
-        let suspicious_agents = vec![
-            "sqlmap",
-            "nikto",
-            "nmap",
-            "masscan",
-            "zap",
-            "burp",
-            "w3af",
-            "acunetix",
-            "nessus",
-            "openvas",
-            "qualys",
-            "rapid7",
-            "tenable",
-            "veracode",
-            "checkmarx",
-            "fortify",
-            "appscan",
-            "webinspect",
-            "paros",
-            "wget",
-            "curl",
-            "python-requests",
-            "go-http-client",
-            "java-http-client",
-            "okhttp",
-            "apache-httpclient",
-            "libwww-perl",
-            "lwp-trivial",
-            "wget",
-            "curl",
-            "python-requests",
-            "go-http-client",
-            "java-http-client",
-            "okhttp",
-            "apache-httpclient",
-            "libwww-perl",
-            "lwp-trivial",
-        ];
-
         let user_agent_lower = user_agent.to_lowercase();
-        suspicious_agents
+        self.suspicious_user_agents
             .iter()
-            .any(|agent| user_agent_lower.contains(agent))
+            .any(|agent| user_agent_lower.contains(agent.as_str()))
     }
 
     pub fn add_blocked_ip(&mut self, ip: String) {
-        self.blocked_ips.insert(ip);
+        self.blocked_ips.insert(normalize_ip(&ip));
     }
 
     pub fn remove_blocked_ip(&mut self, ip: &str) {
-        self.blocked_ips.remove(ip);
+        self.blocked_ips.remove(&normalize_ip(ip));
     }
 
     pub fn is_ip_blocked(&self, ip: &str) -> bool {
-        self.blocked_ips.contains(ip)
+        self.blocked_ips.contains(&normalize_ip(ip))
     }
 
     pub fn get_security_headers() -> HashMap<String, String> {
@@ -451,6 +595,54 @@ impl SecurityMiddleware {
     }
 }
 
+/// Drop whitespace after `<` and before `=` or `(`, so `< script`, `onload = ...` and
+/// `eval (...)` still match the markup-shaped patterns
+fn collapse_markup_whitespace(input: &str) -> String {
+    let mut collapsed = String::with_capacity(input.len());
+    let mut pending = String::new();
+    for c in input.chars() {
+        if c.is_whitespace() {
+            pending.push(c);
+            continue;
+        }
+        if !(collapsed.ends_with('<') || c == '=' || c == '(') {
+            collapsed.push_str(&pending);
+        }
+        pending.clear();
+        collapsed.push(c);
+    }
+    collapsed.push_str(&pending);
+    collapsed
+}
+
+/// Drop whitespace next to quotes, `;` and `=` and shrink other runs of it to one space,
+/// so `' OR 1 = 1` and `';  DROP` match the same patterns as `'or 1=1` and `';drop`
+fn collapse_sql_whitespace(input: &str) -> String {
+    let tight = |c: char| matches!(c, '\'' | '"' | ';' | '=');
+    let mut collapsed = String::with_capacity(input.len());
+    let mut pending = false;
+    for c in input.chars() {
+        if c.is_whitespace() {
+            pending = true;
+            continue;
+        }
+        if pending && !collapsed.is_empty() && !tight(c) && !collapsed.ends_with(tight) {
+            collapsed.push(' ');
+        }
+        pending = false;
+        collapsed.push(c);
+    }
+    collapsed
+}
+
+/// Canonical string form of an IP so blocked entries match however the address was
+/// written (`::ffff:1.2.3.4`, `1.2.3.4:443`, ...)
+fn normalize_ip(ip: &str) -> String {
+    client_ip::parse_ip(ip)
+        .map(|ip| ip.to_string())
+        .unwrap_or_else(|| ip.trim().to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -462,22 +654,70 @@ mod tests {
         assert!(middleware.blocked_ips.is_empty());
     }
 
+    #[cfg(feature = "default-patterns")]
     #[test]
     fn test_xss_detection() {
         let middleware = SecurityMiddleware::new();
         assert!(middleware.contains_xss_patterns("<script>alert('xss')</script>"));
         assert!(middleware.contains_xss_patterns("onclick=\"alert('xss')\""));
+        assert!(middleware.contains_xss_patterns("<img src=x OnError = alert(1)>"));
+        assert!(middleware.contains_xss_patterns("< SCRIPT src=//evil.example>"));
+        assert!(middleware.contains_xss_patterns("<a href=\"JavaScript:alert(1)\">"));
+        assert!(middleware.contains_xss_patterns("eval (atob('YWxlcnQoMSk='))"));
         assert!(!middleware.contains_xss_patterns("normal text"));
     }
 
+    #[cfg(feature = "default-patterns")]
+    #[test]
+    fn test_ordinary_notes_are_not_xss() {
+        let middleware = SecurityMiddleware::new();
+        for note in [
+            "Stick to the script: 5x5 squats, then evaluate the deload",
+            "Form expression felt off on the last set",
+            "Online coaching session on load management",
+            "Read up on JavaScript before the hackathon",
+            "RPE < 8 on all sets",
+        ] {
+            assert!(!middleware.contains_xss_patterns(note), "{}", note);
+        }
+    }
+
+    #[cfg(feature = "default-patterns")]
     #[test]
     fn test_sql_injection_detection() {
         let middleware = SecurityMiddleware::new();
         assert!(middleware.contains_sql_injection_patterns("'; DROP TABLE users; --"));
         assert!(middleware.contains_sql_injection_patterns("UNION SELECT * FROM users"));
+        assert!(middleware.contains_sql_injection_patterns("admin' OR '1' = '1"));
+        assert!(middleware.contains_sql_injection_patterns("x' or 1 = 1 --"));
+        assert!(middleware.contains_sql_injection_patterns("1;  drop  table users"));
         assert!(!middleware.contains_sql_injection_patterns("normal query"));
     }
 
+    #[cfg(feature = "default-patterns")]
+    #[test]
+    fn test_sql_keywords_in_ordinary_text_are_allowed() {
+        let middleware = SecurityMiddleware::new();
+        for note in [
+            "update sets to 4x8 next week",
+            "new personal record(s) on squat",
+            "delete from plan after this block",
+            "Update set 3 with the new weight",
+            "insert into the warm-up: band pull-aparts",
+            "sleep(8h) made a difference",
+            "Felt the ord(inary) pump",
+            "sp_ sprint intervals",
+            "{\"notes\":\"Mike's 5x5, select weights carefully\"}",
+            "drop table height one notch",
+        ] {
+            assert!(
+                !middleware.contains_sql_injection_patterns(note),
+                "{}",
+                note
+            );
+        }
+    }
+
     #[test]
     fn test_nosql_injection_detection() {
         let middleware = SecurityMiddleware::new();
@@ -485,8 +725,10 @@ mod tests {
             .contains_nosql_injection_patterns("{\"$where\": \"this.password == this.username\"}"));
         assert!(middleware.contains_nosql_injection_patterns("{\"$ne\": null}"));
         assert!(!middleware.contains_nosql_injection_patterns("{\"username\": \"test\"}"));
+        assert!(!middleware.contains_nosql_injection_patterns("I read about $where clauses"));
     }
 
+    #[cfg(feature = "default-patterns")]
     #[test]
     fn test_suspicious_user_agent_detection() {
         let middleware = SecurityMiddleware::new();
@@ -497,6 +739,66 @@ mod tests {
         ));
     }
 
+    #[cfg(feature = "default-patterns")]
+    #[test]
+    fn test_default_lists_are_deduplicated() {
+        let middleware = SecurityMiddleware::new();
+        assert_eq!(
+            middleware.suspicious_patterns.len(),
+            DEFAULT_XSS_PATTERNS.len()
+        );
+        assert_eq!(
+            middleware
+                .suspicious_patterns
+                .iter()
+                .filter(|p| *p == "onpointerdown=")
+                .count(),
+            1
+        );
+        assert_eq!(
+            middleware
+                .suspicious_user_agents
+                .iter()
+                .filter(|a| *a == "curl")
+                .count(),
+            1
+        );
+    }
+
+    #[cfg(feature = "default-patterns")]
+    #[test]
+    fn test_with_config_allows_whitelisted_agents() {
+        let config = SecurityConfig::default()
+            .allow_user_agent("curl")
+            .allow_user_agent("python-requests")
+            .with_max_body_size(1024);
+        let mut middleware = SecurityMiddleware::with_config(config);
+
+        assert!(!middleware.is_suspicious_user_agent("curl/7.68.0"));
+        assert!(!middleware.is_suspicious_user_agent("python-requests/2.31.0"));
+        assert!(middleware.is_suspicious_user_agent("sqlmap/1.0"));
+        assert_eq!(middleware.max_body_size, 1024);
+
+        middleware.add_suspicious_user_agent("Scrapy");
+        assert!(middleware.is_suspicious_user_agent("scrapy/2.11"));
+
+        middleware.add_suspicious_pattern("<iframe");
+        assert!(middleware.contains_xss_patterns("<IFRAME src=x>"));
+    }
+
+    #[test]
+    fn test_empty_middleware_has_no_patterns() {
+        let mut middleware = SecurityMiddleware::empty();
+        assert!(!middleware.rate_limits.is_empty());
+        assert!(!middleware.contains_xss_patterns("<script>alert('xss')</script>"));
+        assert!(!middleware.contains_sql_injection_patterns("UNION SELECT * FROM users"));
+        assert!(!middleware.is_suspicious_user_agent("sqlmap/1.0"));
+        assert!(middleware.contains_nosql_injection_patterns("{\"$ne\": null}"));
+
+        middleware.add_suspicious_pattern("<script");
+        assert!(middleware.contains_xss_patterns("<SCRIPT>alert(1)</script>"));
+    }
+
     #[test]
     fn test_ip_blocking() {
         let mut middleware = SecurityMiddleware::new();
@@ -509,4 +811,123 @@ mod tests {
         middleware.remove_blocked_ip(ip);
         assert!(!middleware.is_ip_blocked(ip));
     }
+
+    fn context_with_headers(ip_address: &str, headers: &[(&str, &str)]) -> SecurityContext {
+        SecurityContext {
+            request_id: "req-1".to_string(),
+            user_id: None,
+            ip_address: ip_address.to_string(),
+            user_agent: "Mozilla/5.0".to_string(),
+            timestamp: Utc::now(),
+            rate_limit_key: String::new(),
+            security_headers: headers
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_blocked_ip_uses_forwarded_client() {
+        let mut middleware = SecurityMiddleware::new();
+        middleware.set_client_ip_resolver(
+            ClientIpResolver::new().with_trusted_proxies([
+                "10.0.0.1".parse().unwrap(),
+                "130.176.0.10".parse().unwrap(),
+            ]),
+        );
+        middleware.add_blocked_ip("203.0.113.7".to_string());
+
+        // The source IP is a trusted CloudFront edge; the client is in X-Forwarded-For
+        let context = context_with_headers(
+            "130.176.0.10",
+            &[("X-Forwarded-For", "203.0.113.7, 10.0.0.1")],
+        );
+        let client_ip = middleware.resolve_client_ip(&context);
+        assert_eq!(client_ip, "203.0.113.7");
+        assert!(middleware.is_ip_blocked(&client_ip));
+        assert_eq!(
+            SecurityMiddleware::rate_limit_key("api", &client_ip),
+            "api:203.0.113.7"
+        );
+    }
+
+    #[test]
+    fn test_blocked_ip_matches_normalized_forms() {
+        let mut middleware = SecurityMiddleware::new();
+        middleware.add_blocked_ip("::ffff:198.51.100.4".to_string());
+
+        assert!(middleware.is_ip_blocked("198.51.100.4"));
+        assert!(middleware.is_ip_blocked("198.51.100.4:443"));
+    }
+
+    #[test]
+    fn test_rate_limit_info_to_response() {
+        let info = RateLimitInfo {
+            key: "api:203.0.113.7".to_string(),
+            limit: 60,
+            remaining: 0,
+            reset_time: Utc::now() + chrono::Duration::seconds(30),
+            retry_after: Some(30),
+        };
+
+        let response = info.to_response("Rate limit exceeded");
+        assert_eq!(response.status_code, 429);
+        assert_eq!(response.headers.get("Retry-After"), Some(&"30".to_string()));
+
+        assert_eq!(
+            response.headers.get(RATE_LIMIT_REMAINING_HEADER),
+            Some(&"0".to_string())
+        );
+
+        let body: Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(body["error"], "Too Many Requests");
+        assert_eq!(body["limit"], 60);
+        assert_eq!(body["remaining"], 0);
+        assert_eq!(body["reset_time"], json!(info.reset_time));
+    }
+
+    #[test]
+    fn test_rate_limit_headers_on_successful_response() {
+        let info = RateLimitInfo {
+            key: "api:203.0.113.7".to_string(),
+            limit: 60,
+            remaining: 59,
+            reset_time: Utc::now() + chrono::Duration::seconds(30),
+            retry_after: None,
+        };
+
+        let response = info.apply_headers(Response::ok(json!({"ok": true})));
+        assert_eq!(response.status_code, 200);
+        assert_eq!(
+            response.headers.get(RATE_LIMIT_LIMIT_HEADER),
+            Some(&"60".to_string())
+        );
+        assert_eq!(
+            response.headers.get(RATE_LIMIT_REMAINING_HEADER),
+            Some(&"59".to_string())
+        );
+        assert_eq!(
+            response.headers.get(RATE_LIMIT_RESET_HEADER),
+            Some(&info.reset_time.timestamp().to_string())
+        );
+    }
+
+    #[test]
+    fn test_retry_after_defaults_to_window_reset() {
+        let info = RateLimitInfo {
+            key: "api:203.0.113.7".to_string(),
+            limit: 60,
+            remaining: 0,
+            reset_time: Utc::now() + chrono::Duration::seconds(45),
+            retry_after: None,
+        };
+        assert!((44..=45).contains(&info.retry_after_secs()));
+
+        let expired = RateLimitInfo {
+            reset_time: Utc::now() - chrono::Duration::seconds(5),
+            ..info
+        };
+        assert_eq!(expired.retry_after_secs(), 1);
+    }
 }
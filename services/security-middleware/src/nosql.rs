@@ -0,0 +1,199 @@
+use serde_json::Value;
+
+/// Environment variable overriding the score at which a body is rejected
+pub const THRESHOLD_ENV: &str = "NOSQL_INJECTION_THRESHOLD";
+/// Environment variable enabling strict (substring) matching
+pub const STRICT_ENV: &str = "NOSQL_INJECTION_STRICT";
+
+pub const DEFAULT_THRESHOLD: u32 = 3;
+
+/// Operators that evaluate server-side code
+const CODE_OPERATORS: [&str; 4] = ["$where", "$expr", "$function", "$accumulator"];
+/// Comparison operators used in auth-bypass payloads such as `{"password": {"$ne": null}}`
+const COMPARISON_OPERATORS: [&str; 8] = [
+    "$ne", "$gt", "$gte", "$lt", "$lte", "$nin", "$exists", "$regex",
+];
+
+/// Everything flagged by strict mode, matched as substrings of the raw body
+const STRICT_PATTERNS: [&str; 39] = [
+    "$where",
+    "$ne",
+    "$gt",
+    "$lt",
+    "$gte",
+    "$lte",
+    "$in",
+    "$nin",
+    "$exists",
+    "$regex",
+    "$text",
+    "$search",
+    "$geoWithin",
+    "$geoIntersects",
+    "$near",
+    "$nearSphere",
+    "$center",
+    "$centerSphere",
+    "$box",
+    "$polygon",
+    "$geometry",
+    "$maxDistance",
+    "$minDistance",
+    "$all",
+    "$elemMatch",
+    "$size",
+    "$type",
+    "$mod",
+    "$bitsAllSet",
+    "$bitsAnySet",
+    "$bitsAllClear",
+    "$bitsAnyClear",
+    "$rand",
+    "$expr",
+    "$jsonSchema",
+    "$or",
+    "$and",
+    "$not",
+    "$nor",
+];
+
+/// Scores request bodies for NoSQL operator injection.
+///
+/// Only `$`-prefixed keys of parsed JSON objects count, so free text that mentions
+/// `$where` and bodies that aren't JSON score zero. Code-evaluating operators score 5,
+/// comparison operators 3 and any other `$` key (`$or`, `$in`, ...) 1; a body is
+/// flagged once its total reaches the threshold. Strict mode restores the old
+/// substring match against every operator name
+#[derive(Debug, Clone)]
+pub struct NoSqlInjectionDetector {
+    threshold: u32,
+    strict: bool,
+}
+
+impl NoSqlInjectionDetector {
+    pub fn new() -> Self {
+        Self {
+            threshold: DEFAULT_THRESHOLD,
+            strict: false,
+        }
+    }
+
+    /// Build a detector from `NOSQL_INJECTION_THRESHOLD` and `NOSQL_INJECTION_STRICT`
+    pub fn from_env() -> Self {
+        let threshold = std::env::var(THRESHOLD_ENV)
+            .ok()
+            .and_then(|v| v.trim().parse::<u32>().ok())
+            .filter(|&n| n > 0)
+            .unwrap_or(DEFAULT_THRESHOLD);
+        let strict = std::env::var(STRICT_ENV)
+            .map(|v| matches!(v.trim().to_lowercase().as_str(), "1" | "true" | "yes"))
+            .unwrap_or(false);
+
+        Self::new().with_threshold(threshold).with_strict(strict)
+    }
+
+    pub fn with_threshold(mut self, threshold: u32) -> Self {
+        self.threshold = threshold.max(1);
+        self
+    }
+
+    pub fn with_strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    pub fn is_injection(&self, input: &str) -> bool {
+        if self.strict {
+            let input_lower = input.to_lowercase();
+            return STRICT_PATTERNS
+                .iter()
+                .any(|pattern| input_lower.contains(&pattern.to_lowercase()));
+        }
+
+        self.score(input) >= self.threshold
+    }
+
+    /// Total operator score of a JSON body; `0` if it doesn't parse
+    pub fn score(&self, input: &str) -> u32 {
+        serde_json::from_str::<Value>(input)
+            .map(|value| score_value(&value))
+            .unwrap_or(0)
+    }
+}
+
+impl Default for NoSqlInjectionDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn score_value(value: &Value) -> u32 {
+    match value {
+        Value::Object(map) => map
+            .iter()
+            .map(|(key, nested)| operator_score(key) + score_value(nested))
+            .sum(),
+        Value::Array(items) => items.iter().map(score_value).sum(),
+        _ => 0,
+    }
+}
+
+fn operator_score(key: &str) -> u32 {
+    if !key.starts_with('$') {
+        return 0;
+    }
+
+    if CODE_OPERATORS.iter().any(|op| op.eq_ignore_ascii_case(key)) {
+        5
+    } else if COMPARISON_OPERATORS
+        .iter()
+        .any(|op| op.eq_ignore_ascii_case(key))
+    {
+        3
+    } else {
+        1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operator_keys_are_scored() {
+        let detector = NoSqlInjectionDetector::new();
+
+        assert!(detector.is_injection(r#"{"username": "admin", "password": {"$ne": null}}"#));
+        assert!(detector.is_injection(r#"{"filter": {"$where": "sleep(1000)"}}"#));
+        assert_eq!(
+            detector.score(r#"[{"age": {"$gt": 18}}, {"$where": "1"}]"#),
+            8
+        );
+    }
+
+    #[test]
+    fn test_free_text_and_filter_payloads_pass() {
+        let detector = NoSqlInjectionDetector::new();
+
+        // Operator names inside string values don't count
+        assert!(!detector.is_injection(r#"{"notes": "docs say $where and $ne are dangerous"}"#));
+        assert!(!detector.is_injection("plain text mentioning $where"));
+        // A client filter using logical operators stays under the default threshold
+        assert!(!detector.is_injection(
+            r#"{"filter": {"$or": [{"category": {"$in": ["strength", "cardio"]}}]}}"#
+        ));
+    }
+
+    #[test]
+    fn test_threshold_and_strict_mode() {
+        let filter = r#"{"filter": {"$or": [{"category": {"$in": ["strength"]}}]}}"#;
+        assert!(NoSqlInjectionDetector::new()
+            .with_threshold(2)
+            .is_injection(filter));
+
+        let strict = NoSqlInjectionDetector::new().with_strict(true);
+        assert!(strict.is_injection("plain text mentioning $where"));
+        assert!(strict.is_injection(filter));
+        assert!(!strict.is_injection(r#"{"username": "test"}"#));
+    }
+}
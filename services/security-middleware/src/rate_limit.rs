@@ -0,0 +1,40 @@
+use async_trait::async_trait;
+use lambda_router::{Middleware, Next, Request, Response};
+use std::sync::Arc;
+
+use crate::validation::security_context;
+use crate::SecurityMiddleware;
+
+/// Router middleware that rejects requests over their rate limit with a
+/// `429 Too Many Requests` and a `Retry-After` header. Every response, including
+/// successful ones, carries the `X-RateLimit-*` headers
+pub struct RateLimitMiddleware {
+    security: Arc<SecurityMiddleware>,
+}
+
+impl RateLimitMiddleware {
+    pub fn new(security: Arc<SecurityMiddleware>) -> Self {
+        Self { security }
+    }
+}
+
+#[async_trait]
+impl Middleware for RateLimitMiddleware {
+    async fn handle(&self, req: Request, next: Next) -> Result<Response, lambda_runtime::Error> {
+        let context = security_context(&req);
+        let client_ip = self.security.resolve_client_ip(&context);
+
+        let rate_limit = self.security.check_rate_limit(&context, &client_ip).await?;
+        if let Some(rate_limit) = &rate_limit {
+            if rate_limit.remaining == 0 {
+                return Ok(rate_limit.to_response("Rate limit exceeded"));
+            }
+        }
+
+        let response = next(req).await?;
+        Ok(match rate_limit {
+            Some(rate_limit) => rate_limit.apply_headers(response),
+            None => response,
+        })
+    }
+}
@@ -0,0 +1,149 @@
+use ammonia::{Builder, UrlRelative};
+use std::collections::HashSet;
+
+/// Tags whose content is dropped along with the tag, rather than kept as text
+const CLEAN_CONTENT_TAGS: [&str; 2] = ["script", "style"];
+/// Formatting allowed by `SanitizePolicy::LimitedRichText`
+const RICH_TEXT_TAGS: [&str; 12] = [
+    "a", "b", "br", "code", "em", "i", "li", "ol", "p", "strong", "u", "ul",
+];
+const LINK_SCHEMES: [&str; 3] = ["http", "https", "mailto"];
+
+/// How much markup survives sanitization
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SanitizePolicy {
+    /// Strip every tag and return the remaining text, unescaped. For notes, names
+    /// and descriptions that clients render as text
+    PlainText,
+    /// Keep basic formatting and links to http(s)/mailto URLs; scripts, event
+    /// handler attributes, styles and images are removed
+    LimitedRichText,
+}
+
+/// Clean user-provided free text, e.g. workout notes or plan descriptions, before
+/// it is stored.
+///
+/// `PlainText` returns plain text, not HTML: `5 < 6` stays `5 < 6` rather than
+/// becoming `5 &lt; 6`, so a client that renders it as text (React escapes text
+/// by default) shows what the user typed. It must never be inserted as HTML, since
+/// entities in the input (`&lt;script&gt;`) come back as the characters they
+/// stand for. `LimitedRichText` returns HTML, escaped except for the allowed tags,
+/// for clients that render it as markup
+pub fn sanitize(input: &str, policy: SanitizePolicy) -> String {
+    let mut builder = Builder::empty();
+    builder.clean_content_tags(HashSet::from(CLEAN_CONTENT_TAGS));
+
+    match policy {
+        SanitizePolicy::PlainText => unescape_text(&builder.clean(input).to_string()),
+        SanitizePolicy::LimitedRichText => {
+            builder
+                .tags(HashSet::from(RICH_TEXT_TAGS))
+                .add_tag_attributes("a", &["href"])
+                .url_schemes(HashSet::from(LINK_SCHEMES))
+                .url_relative(UrlRelative::Deny)
+                .link_rel(Some("noopener noreferrer nofollow"));
+            builder.clean(input).to_string()
+        }
+    }
+}
+
+/// Undo the escaping ammonia applies to text nodes, which is only ever `&amp;`,
+/// `&lt;`, `&gt;` and `&nbsp;`
+fn unescape_text(escaped: &str) -> String {
+    let mut text = String::with_capacity(escaped.len());
+    let mut rest = escaped;
+    while let Some(position) = rest.find('&') {
+        text.push_str(&rest[..position]);
+        rest = &rest[position..];
+        let entity = [("&amp;", '&'), ("&lt;", '<'), ("&gt;", '>'), ("&nbsp;", '\u{a0}')]
+            .into_iter()
+            .find(|(entity, _)| rest.starts_with(entity));
+        match entity {
+            Some((entity, character)) => {
+                text.push(character);
+                rest = &rest[entity.len()..];
+            }
+            None => {
+                text.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plain_text_strips_all_tags() {
+        assert_eq!(sanitize("<script>alert('xss')</script>Leg day", SanitizePolicy::PlainText), "Leg day");
+        assert_eq!(sanitize("<b>Heavy</b> squats", SanitizePolicy::PlainText), "Heavy squats");
+        assert_eq!(sanitize("<img src=x onerror=alert('xss')>", SanitizePolicy::PlainText), "");
+        assert_eq!(sanitize("5 sets < 6 sets", SanitizePolicy::PlainText), "5 sets < 6 sets");
+    }
+
+    #[test]
+    fn test_plain_text_is_not_escaped() {
+        assert_eq!(sanitize("Squats & lunges", SanitizePolicy::PlainText), "Squats & lunges");
+        assert_eq!(sanitize("RPE > 8, \"hard\"", SanitizePolicy::PlainText), "RPE > 8, \"hard\"");
+        assert_eq!(sanitize("Tom's PR", SanitizePolicy::PlainText), "Tom's PR");
+        // Entities in the input decode to the characters they stand for
+        assert_eq!(sanitize("&amp;lt; &copy;", SanitizePolicy::PlainText), "&lt; \u{a9}");
+    }
+
+    // Payloads from the gymcoach-ai-tests `SecurityTestData`
+    const XSS_PAYLOADS: [&str; 4] = [
+        "<script>alert('xss')</script>",
+        "javascript:alert('xss')",
+        "onload=alert('xss')",
+        "<img src=x onerror=alert('xss')>",
+    ];
+
+    #[test]
+    fn test_xss_payloads_are_sanitized() {
+        for payload in XSS_PAYLOADS {
+            let plain = sanitize(payload, SanitizePolicy::PlainText);
+            assert!(!plain.contains('<'), "Plain text kept a tag: {:?}", plain);
+
+            // Whatever markup survives has no script, handler or script URL
+            let rich = sanitize(payload, SanitizePolicy::LimitedRichText);
+            for tag in rich.split('<').skip(1) {
+                let tag = tag.split('>').next().unwrap_or("").to_lowercase();
+                assert!(
+                    !tag.starts_with("script")
+                        && !tag.contains("javascript:")
+                        && !tag.split_whitespace().skip(1).any(|attr| attr.starts_with("on")),
+                    "Sanitizing {:?} left executable markup: {:?}",
+                    payload,
+                    rich
+                );
+            }
+        }
+
+        // Script bodies are dropped, not kept as text
+        assert_eq!(sanitize(XSS_PAYLOADS[0], SanitizePolicy::PlainText), "");
+    }
+
+    #[test]
+    fn test_limited_rich_text_keeps_formatting_only() {
+        assert_eq!(
+            sanitize("<p>Felt <strong>great</strong></p>", SanitizePolicy::LimitedRichText),
+            "<p>Felt <strong>great</strong></p>"
+        );
+        assert_eq!(
+            sanitize("<p onclick=\"alert(1)\">Hi</p><img src=x onerror=alert(1)>", SanitizePolicy::LimitedRichText),
+            "<p>Hi</p>"
+        );
+        assert_eq!(
+            sanitize("<a href=\"javascript:alert(1)\">plan</a>", SanitizePolicy::LimitedRichText),
+            "<a rel=\"noopener noreferrer nofollow\">plan</a>"
+        );
+        assert_eq!(
+            sanitize("<a href=\"https://example.com\">plan</a>", SanitizePolicy::LimitedRichText),
+            "<a href=\"https://example.com\" rel=\"noopener noreferrer nofollow\">plan</a>"
+        );
+    }
+}
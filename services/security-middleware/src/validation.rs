@@ -0,0 +1,171 @@
+use async_trait::async_trait;
+use chrono::Utc;
+use lambda_router::{Middleware, Next, Request, Response};
+
+use crate::{SecurityContext, SecurityMiddleware};
+
+/// Runs `validate_request` on every routed request. Blocked IPs get a `403`,
+/// exhausted rate limits a `429` and rejected input (XSS, SQL/NoSQL injection,
+/// oversized body) a `400`; warnings such as suspicious user agents are logged only.
+/// Requests that pass get the `X-RateLimit-*` headers on the handler's response
+#[async_trait]
+impl Middleware for SecurityMiddleware {
+    async fn handle(&self, req: Request, next: Next) -> Result<Response, lambda_runtime::Error> {
+        let context = security_context(&req);
+        let client_ip = self.resolve_client_ip(&context);
+        let result = self.validate_request(&context).await?;
+
+        for warning in &result.warnings {
+            tracing::warn!(client_ip = %client_ip, "security warning: {}", warning);
+        }
+
+        if result.is_valid {
+            let response = next(req).await?;
+            return Ok(match result.rate_limit_info.as_ref() {
+                Some(rate_limit) => rate_limit.apply_headers(response),
+                None => response,
+            });
+        }
+
+        if let Some(rate_limit) = result.rate_limit_info.as_ref() {
+            if rate_limit.remaining == 0 {
+                return Ok(rate_limit.to_response("Rate limit exceeded"));
+            }
+        }
+
+        if self.is_ip_blocked(&client_ip) {
+            return Ok(Response::forbidden("Access denied"));
+        }
+
+        Ok(Response::bad_request(&result.errors.join("; ")))
+    }
+}
+
+/// Build a security context from a router request. The request body (as decoded by the
+/// router, lossily for binary uploads) and path are passed to the checks as the `body`
+/// and `path` entries
+pub(crate) fn security_context(req: &Request) -> SecurityContext {
+    let mut security_headers = req.headers.to_hash_map();
+    security_headers.insert("path".to_string(), req.path.clone());
+    if let Some(body) = request_body(req) {
+        security_headers.insert("body".to_string(), body);
+    }
+
+    let ip_address = req.source_ip().unwrap_or_default().to_string();
+
+    SecurityContext {
+        request_id: req.context.request_id.clone(),
+        user_id: req.context.user_id.clone(),
+        ip_address,
+        user_agent: req.header("User-Agent").cloned().unwrap_or_default(),
+        timestamp: Utc::now(),
+        rate_limit_key: String::new(),
+        security_headers,
+    }
+}
+
+fn request_body(req: &Request) -> Option<String> {
+    match req.body() {
+        Some(body) => Some(body.to_string()),
+        // Binary uploads are still scanned, lossily, as text
+        None if req.is_base64_encoded => {
+            Some(String::from_utf8_lossy(&req.body_bytes()).into_owned())
+        }
+        None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::{engine::general_purpose::STANDARD as BASE64, Engine as _};
+    use serde_json::{json, Value};
+    use std::future::Future;
+    use std::pin::Pin;
+
+    type NextFuture = Pin<Box<dyn Future<Output = Result<Response, lambda_runtime::Error>> + Send>>;
+
+    fn next_ok() -> Next {
+        Box::new(|_req| Box::pin(async { Ok(Response::ok(json!({ "ok": true }))) }) as NextFuture)
+    }
+
+    fn request(body: &str, base64: bool) -> Request {
+        Request::from_lambda_event(json!({
+            "version": "2.0",
+            "rawPath": "/api/workouts",
+            "headers": {
+                "content-type": "application/json",
+                "user-agent": "Mozilla/5.0"
+            },
+            "body": body,
+            "isBase64Encoded": base64,
+            "requestContext": {
+                "requestId": "req-1",
+                "http": { "method": "POST", "sourceIp": "203.0.113.7" }
+            }
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_clean_request_reaches_handler() {
+        let middleware = SecurityMiddleware::new();
+
+        let response = middleware
+            .handle(request(r#"{"name": "Leg day"}"#, false), next_ok())
+            .await
+            .unwrap();
+        assert_eq!(response.status_code, 200);
+    }
+
+    #[tokio::test]
+    async fn test_passing_request_gets_rate_limit_headers() {
+        let middleware = SecurityMiddleware::new();
+
+        let response = middleware
+            .handle(request(r#"{"name": "Leg day"}"#, false), next_ok())
+            .await
+            .unwrap();
+        assert_eq!(response.status_code, 200);
+        let header = |name: &str| response.headers.get(name).map(String::as_str);
+        assert_eq!(header(crate::RATE_LIMIT_LIMIT_HEADER), Some("60"));
+        assert_eq!(header(crate::RATE_LIMIT_REMAINING_HEADER), Some("59"));
+        assert!(header(crate::RATE_LIMIT_RESET_HEADER).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_body_is_validated() {
+        let mut middleware = SecurityMiddleware::new();
+        // Present in the built-in list, but that may be compiled out
+        middleware.add_suspicious_pattern("<script");
+
+        let xss = middleware
+            .handle(
+                request(r#"{"name": "<script>alert(1)</script>"}"#, false),
+                next_ok(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(xss.status_code, 400);
+        let body: Value = serde_json::from_str(&xss.body).unwrap();
+        assert!(body["message"].as_str().unwrap().contains("XSS"));
+
+        let encoded = BASE64.encode(r#"{"password": {"$ne": null}}"#);
+        let nosql = middleware
+            .handle(request(&encoded, true), next_ok())
+            .await
+            .unwrap();
+        assert_eq!(nosql.status_code, 400);
+    }
+
+    #[tokio::test]
+    async fn test_blocked_ip_is_forbidden() {
+        let mut middleware = SecurityMiddleware::new();
+        middleware.add_blocked_ip("203.0.113.7".to_string());
+
+        let response = middleware
+            .handle(request("{}", false), next_ok())
+            .await
+            .unwrap();
+        assert_eq!(response.status_code, 403);
+    }
+}
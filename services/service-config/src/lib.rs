@@ -0,0 +1,152 @@
+//! Environment configuration shared by the Lambda services. Each service loads a
+//! `ServiceConfig` once at startup and hands the resolved values to its repositories,
+//! so every service agrees on names and defaults and a misconfigured function fails
+//! on its first cold start instead of quietly using the wrong table.
+//!
+//! DynamoDB endpoints are picked in this order:
+//! 1. `DYNAMODB_ENDPOINT_URL`, e.g. `http://localhost:8000` for DynamoDB Local
+//! 2. `AWS_ENDPOINT_URL_DYNAMODB`, then `AWS_ENDPOINT_URL`, read by the AWS SDK itself
+//! 3. The regional AWS endpoint for the region resolved by the SDK (`AWS_REGION`, profile, ...)
+
+use std::env;
+use std::fmt;
+
+/// Name of the DynamoDB table holding every service's data
+pub const TABLE_NAME_ENV: &str = "TABLE_NAME";
+/// Older name for `TABLE_NAME`, still read when `TABLE_NAME` is unset
+pub const LEGACY_TABLE_NAME_ENV: &str = "DYNAMODB_TABLE";
+/// Table used when running outside Lambda (local runs and tests) with no table set
+pub const DEFAULT_TABLE_NAME: &str = "gymcoach-ai-main";
+/// Endpoint that DynamoDB clients use instead of the SDK's. Only DynamoDB is redirected;
+/// S3 and other clients keep their usual endpoints
+pub const DYNAMODB_ENDPOINT_URL_ENV: &str = "DYNAMODB_ENDPOINT_URL";
+/// Set by the Lambda runtime; when present, required variables have no defaults
+pub const LAMBDA_FUNCTION_NAME_ENV: &str = "AWS_LAMBDA_FUNCTION_NAME";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    /// A required variable is unset or empty
+    Missing(&'static str),
+    /// A variable is set to a value that cannot be used
+    Invalid(&'static str, String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Missing(name) => {
+                write!(
+                    f,
+                    "Missing configuration: the {} environment variable must be set",
+                    name
+                )
+            }
+            ConfigError::Invalid(name, reason) => {
+                write!(f, "Invalid configuration: {} {}", name, reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ServiceConfig {
+    /// From `TABLE_NAME` (or the legacy `DYNAMODB_TABLE`). Required in Lambda;
+    /// `DEFAULT_TABLE_NAME` elsewhere
+    pub table_name: String,
+    /// From `DYNAMODB_ENDPOINT_URL`; `None` leaves the endpoint to the AWS SDK
+    pub dynamodb_endpoint_url: Option<String>,
+}
+
+impl ServiceConfig {
+    /// Read the configuration from the process environment
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_lookup(|name| env::var(name).ok())
+    }
+
+    /// Read the configuration through `lookup` instead of the process environment
+    pub fn from_lookup(lookup: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let var = |name: &str| lookup(name).filter(|value| !value.trim().is_empty());
+        let in_lambda = var(LAMBDA_FUNCTION_NAME_ENV).is_some();
+
+        let table_name = match var(TABLE_NAME_ENV).or_else(|| var(LEGACY_TABLE_NAME_ENV)) {
+            Some(table_name) => table_name,
+            None if in_lambda => return Err(ConfigError::Missing(TABLE_NAME_ENV)),
+            None => DEFAULT_TABLE_NAME.to_string(),
+        };
+
+        let dynamodb_endpoint_url =
+            var(DYNAMODB_ENDPOINT_URL_ENV).map(|url| url.trim().to_string());
+        if let Some(url) = &dynamodb_endpoint_url {
+            if !url.starts_with("http://") && !url.starts_with("https://") {
+                return Err(ConfigError::Invalid(
+                    DYNAMODB_ENDPOINT_URL_ENV,
+                    format!("must be an http:// or https:// URL, got {:?}", url),
+                ));
+            }
+        }
+
+        Ok(Self {
+            table_name,
+            dynamodb_endpoint_url,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn load(vars: &[(&str, &str)]) -> Result<ServiceConfig, ConfigError> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        ServiceConfig::from_lookup(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_table_name_prefers_table_name_over_legacy_var() {
+        let config = load(&[
+            (TABLE_NAME_ENV, "main-prod"),
+            (LEGACY_TABLE_NAME_ENV, "other"),
+        ])
+        .unwrap();
+        assert_eq!(config.table_name, "main-prod");
+
+        let config = load(&[(LEGACY_TABLE_NAME_ENV, "main-dev")]).unwrap();
+        assert_eq!(config.table_name, "main-dev");
+    }
+
+    #[test]
+    fn test_table_name_defaults_only_outside_lambda() {
+        assert_eq!(load(&[]).unwrap().table_name, DEFAULT_TABLE_NAME);
+
+        let error = load(&[
+            (LAMBDA_FUNCTION_NAME_ENV, "workout-service"),
+            (TABLE_NAME_ENV, " "),
+        ])
+        .unwrap_err();
+        assert_eq!(error, ConfigError::Missing(TABLE_NAME_ENV));
+        assert!(error.to_string().contains("TABLE_NAME"));
+    }
+
+    #[test]
+    fn test_dynamodb_endpoint_override() {
+        assert_eq!(load(&[]).unwrap().dynamodb_endpoint_url, None);
+
+        let config = load(&[(DYNAMODB_ENDPOINT_URL_ENV, "http://localhost:8000 ")]).unwrap();
+        assert_eq!(
+            config.dynamodb_endpoint_url.as_deref(),
+            Some("http://localhost:8000")
+        );
+
+        let error = load(&[(DYNAMODB_ENDPOINT_URL_ENV, "localhost:8000")]).unwrap_err();
+        assert!(matches!(
+            error,
+            ConfigError::Invalid(DYNAMODB_ENDPOINT_URL_ENV, _)
+        ));
+    }
+}
@@ -0,0 +1,349 @@
+use metrics_collector::{CustomMetric, MetricUnit, MetricsCollector};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Environment variable setting how many consecutive failures open a circuit
+pub const FAILURE_THRESHOLD_ENV: &str = "CIRCUIT_BREAKER_FAILURE_THRESHOLD";
+/// Environment variable setting how long an open circuit rejects calls, in seconds
+pub const COOLDOWN_ENV: &str = "CIRCUIT_BREAKER_COOLDOWN_SECONDS";
+
+pub const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+pub const DEFAULT_COOLDOWN: Duration = Duration::from_secs(30);
+
+const METRICS_NAMESPACE: &str = "GymCoach/CircuitBreaker";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Calls go through; failures are counted
+    Closed,
+    /// Calls are rejected until the cooldown elapses
+    Open,
+    /// One trial call is allowed; its outcome closes or re-opens the circuit
+    HalfOpen,
+}
+
+impl CircuitState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CircuitState::Closed => "Closed",
+            CircuitState::Open => "Open",
+            CircuitState::HalfOpen => "HalfOpen",
+        }
+    }
+}
+
+/// Returned instead of calling a target whose circuit is open
+#[derive(Debug, Clone)]
+pub struct CircuitOpenError {
+    pub target: String,
+    pub retry_after: Duration,
+}
+
+impl fmt::Display for CircuitOpenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Circuit open for {}; retry in {}s",
+            self.target,
+            self.retry_after.as_secs().max(1)
+        )
+    }
+}
+
+impl std::error::Error for CircuitOpenError {}
+
+#[derive(Debug)]
+struct Circuit {
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+    trial_in_flight: bool,
+}
+
+impl Circuit {
+    fn new() -> Self {
+        Self {
+            state: CircuitState::Closed,
+            consecutive_failures: 0,
+            opened_at: None,
+            trial_in_flight: false,
+        }
+    }
+}
+
+/// Per-target circuit breaker for downstream calls, keyed by e.g. a Lambda function
+/// ARN. A circuit opens after `failure_threshold` consecutive failures and rejects
+/// calls with `CircuitOpenError` until `cooldown` has passed; then a single trial call
+/// decides whether it closes again. State transitions are recorded as
+/// `CircuitStateTransition` metrics
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    circuits: Mutex<HashMap<String, Circuit>>,
+    metrics: Mutex<MetricsCollector>,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold: failure_threshold.max(1),
+            cooldown,
+            circuits: Mutex::new(HashMap::new()),
+            metrics: Mutex::new(MetricsCollector::new(METRICS_NAMESPACE.to_string(), HashMap::new())),
+        }
+    }
+
+    /// Build a breaker from `CIRCUIT_BREAKER_FAILURE_THRESHOLD` and
+    /// `CIRCUIT_BREAKER_COOLDOWN_SECONDS`
+    pub fn from_env() -> Self {
+        let failure_threshold = std::env::var(FAILURE_THRESHOLD_ENV)
+            .ok()
+            .and_then(|v| v.trim().parse::<u32>().ok())
+            .filter(|&n| n > 0)
+            .unwrap_or(DEFAULT_FAILURE_THRESHOLD);
+        let cooldown = std::env::var(COOLDOWN_ENV)
+            .ok()
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .filter(|&n| n > 0)
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_COOLDOWN);
+
+        Self::new(failure_threshold, cooldown)
+    }
+
+    pub fn state(&self, target: &str) -> CircuitState {
+        self.lock_circuits()
+            .get(target)
+            .map(|circuit| circuit.state)
+            .unwrap_or(CircuitState::Closed)
+    }
+
+    /// Check whether a call to `target` may proceed. Every permitted call must be
+    /// followed by `record_success` or `record_failure`
+    pub fn try_acquire(&self, target: &str) -> Result<(), CircuitOpenError> {
+        self.try_acquire_at(target, Instant::now())
+    }
+
+    pub fn record_success(&self, target: &str) {
+        let mut circuits = self.lock_circuits();
+        let circuit = circuits
+            .entry(target.to_string())
+            .or_insert_with(Circuit::new);
+
+        circuit.consecutive_failures = 0;
+        circuit.opened_at = None;
+        circuit.trial_in_flight = false;
+        self.transition(target, circuit, CircuitState::Closed);
+    }
+
+    pub fn record_failure(&self, target: &str) {
+        self.record_failure_at(target, Instant::now());
+    }
+
+    /// State transitions recorded since the last call, for publishing
+    pub fn take_metrics(&self) -> Vec<CustomMetric> {
+        self.lock_metrics().take_metrics()
+    }
+
+    fn try_acquire_at(&self, target: &str, now: Instant) -> Result<(), CircuitOpenError> {
+        let mut circuits = self.lock_circuits();
+        let Some(circuit) = circuits.get_mut(target) else {
+            return Ok(());
+        };
+
+        match circuit.state {
+            CircuitState::Closed => Ok(()),
+            CircuitState::Open => {
+                let elapsed = circuit
+                    .opened_at
+                    .map(|opened_at| now.saturating_duration_since(opened_at))
+                    .unwrap_or(self.cooldown);
+                if elapsed < self.cooldown {
+                    return Err(CircuitOpenError {
+                        target: target.to_string(),
+                        retry_after: self.cooldown - elapsed,
+                    });
+                }
+
+                circuit.trial_in_flight = true;
+                self.transition(target, circuit, CircuitState::HalfOpen);
+                Ok(())
+            }
+            CircuitState::HalfOpen if circuit.trial_in_flight => Err(CircuitOpenError {
+                target: target.to_string(),
+                retry_after: Duration::ZERO,
+            }),
+            CircuitState::HalfOpen => {
+                circuit.trial_in_flight = true;
+                Ok(())
+            }
+        }
+    }
+
+    fn record_failure_at(&self, target: &str, now: Instant) {
+        let mut circuits = self.lock_circuits();
+        let circuit = circuits
+            .entry(target.to_string())
+            .or_insert_with(Circuit::new);
+
+        circuit.consecutive_failures += 1;
+        circuit.trial_in_flight = false;
+
+        let trips = match circuit.state {
+            CircuitState::HalfOpen => true,
+            CircuitState::Closed => circuit.consecutive_failures >= self.failure_threshold,
+            CircuitState::Open => false,
+        };
+        if trips {
+            circuit.opened_at = Some(now);
+            self.transition(target, circuit, CircuitState::Open);
+        }
+    }
+
+    fn transition(&self, target: &str, circuit: &mut Circuit, to: CircuitState) {
+        let from = circuit.state;
+        if from == to {
+            return;
+        }
+        circuit.state = to;
+
+        warn!(
+            "Circuit for {} moved from {} to {}",
+            target,
+            from.as_str(),
+            to.as_str()
+        );
+        self.lock_metrics().record_custom_metric(
+            CustomMetric::new(
+                METRICS_NAMESPACE.to_string(),
+                "CircuitStateTransition".to_string(),
+                1.0,
+                MetricUnit::Count,
+            )
+            .with_dimension("Target".to_string(), target.to_string())
+            .with_dimension("From".to_string(), from.as_str().to_string())
+            .with_dimension("To".to_string(), to.as_str().to_string()),
+        );
+    }
+
+    fn lock_circuits(&self) -> std::sync::MutexGuard<'_, HashMap<String, Circuit>> {
+        self.circuits
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn lock_metrics(&self) -> std::sync::MutexGuard<'_, MetricsCollector> {
+        self.metrics
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(DEFAULT_FAILURE_THRESHOLD, DEFAULT_COOLDOWN)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TARGET: &str = "arn:aws:lambda:eu-west-1:123456789012:function:notification-service";
+
+    fn transitions(breaker: &CircuitBreaker) -> Vec<(String, String)> {
+        breaker
+            .take_metrics()
+            .into_iter()
+            .map(|metric| {
+                (
+                    metric.dimensions["From"].clone(),
+                    metric.dimensions["To"].clone(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_trips_after_consecutive_failures() {
+        let breaker = CircuitBreaker::new(3, Duration::from_secs(30));
+        let now = Instant::now();
+
+        breaker.record_failure_at(TARGET, now);
+        breaker.record_failure_at(TARGET, now);
+        assert_eq!(breaker.state(TARGET), CircuitState::Closed);
+        assert!(breaker.try_acquire_at(TARGET, now).is_ok());
+
+        breaker.record_failure_at(TARGET, now);
+        assert_eq!(breaker.state(TARGET), CircuitState::Open);
+
+        let error = breaker
+            .try_acquire_at(TARGET, now + Duration::from_secs(10))
+            .unwrap_err();
+        assert_eq!(error.retry_after, Duration::from_secs(20));
+        // Other targets are unaffected
+        assert!(breaker.try_acquire_at("other-function", now).is_ok());
+        assert_eq!(
+            transitions(&breaker),
+            vec![("Closed".to_string(), "Open".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_success_resets_failure_count() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(30));
+        let now = Instant::now();
+
+        breaker.record_failure_at(TARGET, now);
+        breaker.record_success(TARGET);
+        breaker.record_failure_at(TARGET, now);
+        assert_eq!(breaker.state(TARGET), CircuitState::Closed);
+        assert!(transitions(&breaker).is_empty());
+    }
+
+    #[test]
+    fn test_recovers_through_half_open() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(30));
+        let now = Instant::now();
+        breaker.record_failure_at(TARGET, now);
+
+        // After the cooldown a single trial call is let through
+        let later = now + Duration::from_secs(30);
+        assert!(breaker.try_acquire_at(TARGET, later).is_ok());
+        assert_eq!(breaker.state(TARGET), CircuitState::HalfOpen);
+        assert!(breaker.try_acquire_at(TARGET, later).is_err());
+
+        breaker.record_success(TARGET);
+        assert_eq!(breaker.state(TARGET), CircuitState::Closed);
+        assert_eq!(
+            transitions(&breaker),
+            vec![
+                ("Closed".to_string(), "Open".to_string()),
+                ("Open".to_string(), "HalfOpen".to_string()),
+                ("HalfOpen".to_string(), "Closed".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_failed_trial_reopens() {
+        let breaker = CircuitBreaker::new(1, Duration::from_secs(30));
+        let now = Instant::now();
+        breaker.record_failure_at(TARGET, now);
+
+        let later = now + Duration::from_secs(31);
+        assert!(breaker.try_acquire_at(TARGET, later).is_ok());
+        breaker.record_failure_at(TARGET, later);
+
+        assert_eq!(breaker.state(TARGET), CircuitState::Open);
+        assert!(breaker
+            .try_acquire_at(TARGET, later + Duration::from_secs(29))
+            .is_err());
+        assert!(breaker
+            .try_acquire_at(TARGET, later + Duration::from_secs(30))
+            .is_ok());
+    }
+}
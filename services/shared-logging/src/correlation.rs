@@ -0,0 +1,87 @@
+use lambda_router::{correlation_id_from_headers, HeaderMap, CORRELATION_ID_HEADER};
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+/// A fresh correlation id for work that doesn't continue an existing request
+pub fn new_correlation_id() -> String {
+    Uuid::new_v4().to_string()
+}
+
+/// The correlation id carried by an invoke payload, if any
+pub fn correlation_id_from_payload(payload: &Value) -> Option<String> {
+    let headers: HeaderMap = payload
+        .get("headers")?
+        .as_object()?
+        .iter()
+        .filter_map(|(name, value)| Some((name.as_str(), value.as_str()?)))
+        .collect();
+    correlation_id_from_headers(&headers)
+}
+
+/// Add the correlation id to a Lambda invoke payload. It goes in the payload's `headers`
+/// map under `X-Correlation-Id`, where `lambda_router` reads it for HTTP events, so
+/// routed services and raw event handlers both pick it up. The map is created when
+/// needed; payloads that aren't JSON objects are left alone
+pub fn inject_correlation_id(payload: &mut Value, correlation_id: &str) {
+    let Some(payload) = payload.as_object_mut() else {
+        return;
+    };
+
+    let headers = payload
+        .entry("headers")
+        .or_insert_with(|| Value::Object(Map::new()));
+    if !headers.is_object() {
+        *headers = Value::Object(Map::new());
+    }
+    if let Some(headers) = headers.as_object_mut() {
+        headers.retain(|name, _| !name.eq_ignore_ascii_case(CORRELATION_ID_HEADER));
+        headers.insert(
+            CORRELATION_ID_HEADER.to_string(),
+            Value::String(correlation_id.to_string()),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_inject_then_extract() {
+        let mut payload = json!({ "user_id": "user-1", "title": "Time to train" });
+        inject_correlation_id(&mut payload, "corr-1");
+
+        assert_eq!(payload["headers"]["X-Correlation-Id"], "corr-1");
+        assert_eq!(payload["user_id"], "user-1");
+        assert_eq!(
+            correlation_id_from_payload(&payload),
+            Some("corr-1".to_string())
+        );
+    }
+
+    #[test]
+    fn test_inject_replaces_existing_id_and_keeps_other_headers() {
+        let mut payload = json!({
+            "headers": { "x-correlation-id": "old", "Content-Type": "application/json" }
+        });
+        inject_correlation_id(&mut payload, "new");
+
+        assert_eq!(
+            payload["headers"],
+            json!({ "X-Correlation-Id": "new", "Content-Type": "application/json" })
+        );
+    }
+
+    #[test]
+    fn test_missing_correlation_id() {
+        assert_eq!(
+            correlation_id_from_payload(&json!({ "user_id": "u" })),
+            None
+        );
+        assert_eq!(
+            correlation_id_from_payload(&json!({ "headers": { "X-Correlation-Id": " " } })),
+            None
+        );
+    }
+}
@@ -1,11 +1,23 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use tracing::{debug, error, info, instrument, warn};
+use tracing::{debug, error, info, instrument, warn, Level};
+
+pub mod circuit_breaker;
+pub mod correlation;
+pub mod middleware;
+pub mod sampling;
+
+pub use circuit_breaker::{CircuitBreaker, CircuitOpenError, CircuitState};
+pub use correlation::{correlation_id_from_payload, inject_correlation_id, new_correlation_id};
+pub use middleware::LoggingMiddleware;
+pub use sampling::{LogSampler, LOG_SAMPLE_RATE_ENV};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LogContext {
     pub request_id: String,
+    /// Id shared by every service handling the same originating request
+    pub correlation_id: Option<String>,
     pub user_id: Option<String>,
     pub service: String,
     pub operation: String,
@@ -20,6 +32,7 @@ impl LogContext {
     pub fn new(request_id: String, service: String, operation: String) -> Self {
         Self {
             request_id,
+            correlation_id: None,
             user_id: None,
             service,
             operation,
@@ -31,6 +44,11 @@ impl LogContext {
         }
     }
 
+    pub fn with_correlation_id(mut self, correlation_id: String) -> Self {
+        self.correlation_id = Some(correlation_id);
+        self
+    }
+
     pub fn with_user_id(mut self, user_id: String) -> Self {
         self.user_id = Some(user_id);
         self
@@ -59,6 +77,7 @@ impl LogContext {
     pub fn log_info(&self, message: &str) {
         info!(
             request_id = %self.request_id,
+            correlation_id = %self.correlation_id.as_deref().unwrap_or(&self.request_id),
             user_id = %self.user_id.as_deref().unwrap_or("unknown"),
             service = %self.service,
             operation = %self.operation,
@@ -72,6 +91,7 @@ impl LogContext {
     pub fn log_error(&self, message: &str) {
         error!(
             request_id = %self.request_id,
+            correlation_id = %self.correlation_id.as_deref().unwrap_or(&self.request_id),
             user_id = %self.user_id.as_deref().unwrap_or("unknown"),
             service = %self.service,
             operation = %self.operation,
@@ -86,6 +106,7 @@ impl LogContext {
     pub fn log_warn(&self, message: &str) {
         warn!(
             request_id = %self.request_id,
+            correlation_id = %self.correlation_id.as_deref().unwrap_or(&self.request_id),
             user_id = %self.user_id.as_deref().unwrap_or("unknown"),
             service = %self.service,
             operation = %self.operation,
@@ -99,6 +120,7 @@ impl LogContext {
     pub fn log_debug(&self, message: &str) {
         debug!(
             request_id = %self.request_id,
+            correlation_id = %self.correlation_id.as_deref().unwrap_or(&self.request_id),
             user_id = %self.user_id.as_deref().unwrap_or("unknown"),
             service = %self.service,
             operation = %self.operation,
@@ -110,25 +132,45 @@ impl LogContext {
     }
 }
 
+/// Request logger for a service. Info and debug lines go through its `LogSampler`
+/// (from `LOG_SAMPLE_RATE` unless set with `with_sampler`); warnings and errors are
+/// always logged
 #[derive(Debug)]
 pub struct Logger {
     service_name: String,
+    sampler: LogSampler,
 }
 
 impl Logger {
     pub fn new(service_name: String) -> Self {
-        Self { service_name }
+        Self {
+            service_name,
+            sampler: LogSampler::from_env(),
+        }
     }
 
+    pub fn with_sampler(mut self, sampler: LogSampler) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    /// Start a request's log context. The start line is logged at debug level so that
+    /// at the default level each request produces a single line, from `end_request` or
+    /// `log_error`
     #[instrument]
     pub fn start_request(&self, request_id: String, operation: String) -> LogContext {
         let context = LogContext::new(request_id, self.service_name.clone(), operation);
-        context.log_info("Request started");
+        if self.sampler.keeps(Level::DEBUG, &context.request_id) {
+            context.log_debug("Request started");
+        }
         context
     }
 
     #[instrument]
     pub fn end_request(&self, mut context: LogContext, status_code: u16, duration_ms: u64) {
+        if !self.sampler.keeps(Level::INFO, &context.request_id) {
+            return;
+        }
         context = context
             .with_status_code(status_code)
             .with_duration(duration_ms);
@@ -150,7 +192,9 @@ impl Logger {
 
     #[instrument]
     pub fn log_debug(&self, context: &LogContext, message: String) {
-        context.log_debug(&message);
+        if self.sampler.keeps(Level::DEBUG, &context.request_id) {
+            context.log_debug(&message);
+        }
     }
 }
 
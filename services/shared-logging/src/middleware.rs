@@ -0,0 +1,174 @@
+use async_trait::async_trait;
+use lambda_router::{Middleware, Next, Request, Response, RouterError};
+use std::env;
+use std::time::Instant;
+
+use crate::{LogSampler, Logger};
+
+/// Env var with the duration in milliseconds from which a request is logged as slow
+pub const SLOW_REQUEST_MS_ENV: &str = "LOG_SLOW_REQUEST_MS";
+const DEFAULT_SLOW_REQUEST_MS: u64 = 1000;
+
+/// Request logging for `lambda_router::Router`. Emits one structured line per request
+/// when it completes, with the status code, duration, matched route and the user id
+/// resolved by the auth middleware. Responses with a 5xx status and errors from the
+/// chain are logged as failures.
+///
+/// Completed requests are subject to log sampling (`LOG_SAMPLE_RATE`), but failures
+/// and slow requests (`LOG_SLOW_REQUEST_MS`, 1s by default, logged as warnings) always
+/// are logged.
+///
+/// Register it before the auth middleware so rejected requests are logged too; the
+/// user id is read from the context the handler ran with.
+#[derive(Debug)]
+pub struct LoggingMiddleware {
+    logger: Logger,
+    slow_request_ms: u64,
+}
+
+impl LoggingMiddleware {
+    pub fn new(service_name: impl Into<String>) -> Self {
+        Self {
+            logger: Logger::new(service_name.into()),
+            slow_request_ms: env::var(SLOW_REQUEST_MS_ENV)
+                .ok()
+                .and_then(|ms| ms.parse().ok())
+                .unwrap_or(DEFAULT_SLOW_REQUEST_MS),
+        }
+    }
+
+    /// Log completed requests for `rate` (0.0 to 1.0) of requests instead of the
+    /// `LOG_SAMPLE_RATE` share
+    pub fn with_sample_rate(mut self, rate: f64) -> Self {
+        self.logger = self.logger.with_sampler(LogSampler::new(rate));
+        self
+    }
+
+    /// Always log requests taking at least `slow_request_ms`
+    pub fn with_slow_request_threshold(mut self, slow_request_ms: u64) -> Self {
+        self.slow_request_ms = slow_request_ms;
+        self
+    }
+}
+
+#[async_trait]
+impl Middleware for LoggingMiddleware {
+    async fn handle(&self, req: Request, next: Next) -> Result<Response, lambda_runtime::Error> {
+        let started = Instant::now();
+        let route = req.matched_route().unwrap_or(&req.path).to_string();
+        let mut context = self
+            .logger
+            .start_request(
+                req.context.request_id.clone(),
+                format!("{} {}", req.method, route),
+            )
+            .with_correlation_id(req.context.correlation_id.clone())
+            .with_metadata("method".to_string(), req.method.clone().into())
+            .with_metadata("route".to_string(), route.into())
+            .with_metadata("path".to_string(), req.path.clone().into());
+        let handler_context = req.handler_context();
+        let entry_user_id = req.context.user_id.clone();
+
+        let result = next(req).await;
+
+        let duration_ms = started.elapsed().as_millis() as u64;
+        if let Some(user_id) = handler_context
+            .get()
+            .and_then(|ctx| ctx.user_id)
+            .or(entry_user_id)
+        {
+            context = context.with_user_id(user_id);
+        }
+
+        match &result {
+            Ok(response) if response.status_code >= 500 => {
+                self.logger.log_error(
+                    context.with_duration(duration_ms),
+                    format!("Responded with status {}", response.status_code),
+                    response.status_code,
+                );
+            }
+            Ok(response) if duration_ms >= self.slow_request_ms => {
+                self.logger.log_warning(
+                    &context
+                        .with_status_code(response.status_code)
+                        .with_duration(duration_ms),
+                    format!("Slow request completed in {}ms", duration_ms),
+                );
+            }
+            Ok(response) => {
+                self.logger
+                    .end_request(context, response.status_code, duration_ms);
+            }
+            Err(e) => {
+                let status_code = e
+                    .downcast_ref::<RouterError>()
+                    .map(|e| e.to_response().status_code)
+                    .unwrap_or(500);
+                self.logger.log_error(
+                    context.with_duration(duration_ms),
+                    e.to_string(),
+                    status_code,
+                );
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lambda_router::{handler, Context, Router};
+    use serde_json::json;
+
+    async fn get_plan(_req: Request, _ctx: Context) -> lambda_router::Result<Response> {
+        Ok(Response::ok(json!({ "id": "p-1" })))
+    }
+
+    async fn failing(_req: Request, _ctx: Context) -> lambda_router::Result<Response> {
+        Err(RouterError::Conflict("Plan was modified".to_string()))
+    }
+
+    fn event(path: &str) -> lambda_runtime::LambdaEvent<serde_json::Value> {
+        lambda_runtime::LambdaEvent::new(
+            json!({
+                "rawPath": path,
+                "requestContext": { "http": { "method": "GET" }, "requestId": "req-1" },
+                "headers": {}
+            }),
+            lambda_runtime::Context::default(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_passes_responses_and_errors_through() {
+        let mut router = Router::new();
+        router.use_middleware(LoggingMiddleware::new("workout-service"));
+        router.get("/api/workouts/plans/:planId", handler!(get_plan));
+        router.get("/api/workouts/conflict", handler!(failing));
+        let service = router.into_service();
+
+        let ok = service(event("/api/workouts/plans/p-1")).await.unwrap();
+        assert_eq!(ok["statusCode"], 200);
+
+        let conflict = service(event("/api/workouts/conflict")).await.unwrap();
+        assert_eq!(conflict["statusCode"], 409);
+    }
+
+    #[tokio::test]
+    async fn test_sampling_does_not_change_responses() {
+        let mut router = Router::new();
+        router.use_middleware(
+            LoggingMiddleware::new("workout-service")
+                .with_sample_rate(0.0)
+                .with_slow_request_threshold(0),
+        );
+        router.get("/api/workouts/plans/:planId", handler!(get_plan));
+        let service = router.into_service();
+
+        let ok = service(event("/api/workouts/plans/p-1")).await.unwrap();
+        assert_eq!(ok["statusCode"], 200);
+    }
+}
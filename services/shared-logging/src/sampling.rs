@@ -0,0 +1,97 @@
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::hash::{Hash, Hasher};
+use tracing::Level;
+
+/// Env var holding the share of requests, from 0.0 to 1.0, whose info and debug lines
+/// are logged. Unset means every request's are
+pub const LOG_SAMPLE_RATE_ENV: &str = "LOG_SAMPLE_RATE";
+
+/// Decides which info and debug lines are logged, to keep log volume down under heavy
+/// traffic. Warnings and errors always are
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LogSampler {
+    rate: f64,
+}
+
+impl Default for LogSampler {
+    fn default() -> Self {
+        Self { rate: 1.0 }
+    }
+}
+
+impl LogSampler {
+    /// Keep info and debug lines for `rate` of requests, clamped to 0.0..=1.0
+    pub fn new(rate: f64) -> Self {
+        if rate.is_nan() {
+            return Self::default();
+        }
+        Self {
+            rate: rate.clamp(0.0, 1.0),
+        }
+    }
+
+    /// The rate from `LOG_SAMPLE_RATE`, logging everything when it is unset or invalid
+    pub fn from_env() -> Self {
+        env::var(LOG_SAMPLE_RATE_ENV)
+            .ok()
+            .and_then(|rate| rate.parse::<f64>().ok())
+            .map_or_else(Self::default, Self::new)
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Whether a line at `level` for the request `request_id` is logged. The decision
+    /// is made from a hash of the id, so a request's lines are kept or dropped together
+    pub fn keeps(&self, level: Level, request_id: &str) -> bool {
+        if level == Level::WARN || level == Level::ERROR || self.rate >= 1.0 {
+            return true;
+        }
+        if self.rate <= 0.0 {
+            return false;
+        }
+
+        let mut hasher = DefaultHasher::new();
+        request_id.hash(&mut hasher);
+        (hasher.finish() as f64 / u64::MAX as f64) < self.rate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_warnings_and_errors_are_never_sampled_out() {
+        let sampler = LogSampler::new(0.0);
+
+        assert!(sampler.keeps(Level::ERROR, "req-1"));
+        assert!(sampler.keeps(Level::WARN, "req-1"));
+        assert!(!sampler.keeps(Level::INFO, "req-1"));
+        assert!(!sampler.keeps(Level::DEBUG, "req-1"));
+    }
+
+    #[test]
+    fn test_info_lines_are_kept_for_roughly_the_rate() {
+        let sampler = LogSampler::new(0.25);
+        let kept = (0..10_000)
+            .filter(|i| sampler.keeps(Level::INFO, &format!("req-{}", i)))
+            .count();
+        assert!((2_000..3_000).contains(&kept), "kept {}", kept);
+
+        // The same request is always decided the same way
+        assert_eq!(
+            sampler.keeps(Level::INFO, "req-42"),
+            sampler.keeps(Level::DEBUG, "req-42")
+        );
+    }
+
+    #[test]
+    fn test_rate_is_clamped() {
+        assert_eq!(LogSampler::new(2.0).rate(), 1.0);
+        assert_eq!(LogSampler::new(-1.0).rate(), 0.0);
+        assert_eq!(LogSampler::new(f64::NAN).rate(), 1.0);
+    }
+}
@@ -4,7 +4,7 @@ use tracing::error;
 
 use crate::models::*;
 use crate::service::UploadService;
-use crate::utils::{ResponseBuilder, response_helpers, DataHelper};
+use crate::utils::{ResponseBuilder, ServiceError, response_helpers, DataHelper};
 
 #[derive(Clone)]
 pub struct UploadController {
@@ -25,6 +25,7 @@ impl UploadController {
             Ok(request) => {
                 match self.upload_service.generate_upload_url(&request).await {
                     Ok(response) => Ok(ResponseBuilder::ok(response)),
+                    Err(e @ ServiceError::ValidationError(_)) => Ok(e.into()),
                     Err(e) => {
                         error!("Error generating presigned URL: {}", e);
                         Ok(ResponseBuilder::internal_server_error("Failed to generate upload URL"))
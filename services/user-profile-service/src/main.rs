@@ -2,10 +2,12 @@ use async_trait::async_trait;
 use aws_config::meta::region::RegionProviderChain;
 use aws_sdk_dynamodb::Client as DynamoDbClient;
 use aws_sdk_s3::Client as S3Client;
-use lambda_router::{handler, Context, Middleware, Next, Request, Response, Router};
+use lambda_router::{handler, time_phase, Context, Middleware, Next, Request, Response, Router};
 use lambda_runtime::service_fn;
 use lambda_runtime::Error as LambdaError;
 use once_cell::sync::{Lazy, OnceCell};
+use security_middleware::SecurityMiddleware;
+use service_config::ServiceConfig;
 use std::sync::Arc;
 use tracing::{error, info};
 
@@ -16,11 +18,14 @@ mod repository;
 mod service;
 mod utils;
 
-use auth_layer::{AuthLayer, LambdaEvent as AuthLambdaEvent};
+use auth_layer::{
+    build_request_context, with_token_expiry_header, AuthLayer, LambdaEvent as AuthLambdaEvent,
+};
 use controller::{SleepController, UploadController, UserProfileController};
 use handlers::{delete_device_token, get_device_tokens, save_device_token};
 use repository::{SleepRepository, UserProfileRepository};
 use service::{SleepService, UploadService, UserProfileService};
+use utils::{DEFAULT_MAX_UPLOAD_BYTES, DEFAULT_UPLOAD_URL_EXPIRY_SECS};
 
 // Import all handler functions
 use handlers::{
@@ -55,6 +60,9 @@ async fn main() -> Result<(), LambdaError> {
 
     info!("Starting User Profile Service initialization...");
 
+    // Fail fast on missing configuration, before anything is initialized
+    let service_config = ServiceConfig::from_env()?;
+
     // Initialize global clients
     info!("Initializing AWS clients...");
     if DYNAMODB_CLIENT.get().is_none() || S3_CLIENT.get().is_none() {
@@ -63,7 +71,12 @@ async fn main() -> Result<(), LambdaError> {
             .region(region_provider)
             .load()
             .await;
-        let _ = DYNAMODB_CLIENT.set(Arc::new(DynamoDbClient::new(&config)));
+        let mut dynamodb_config = aws_sdk_dynamodb::config::Builder::from(&config);
+        if let Some(endpoint_url) = &service_config.dynamodb_endpoint_url {
+            info!("Using DynamoDB endpoint {}", endpoint_url);
+            dynamodb_config = dynamodb_config.endpoint_url(endpoint_url);
+        }
+        let _ = DYNAMODB_CLIENT.set(Arc::new(DynamoDbClient::from_conf(dynamodb_config.build())));
         let _ = S3_CLIENT.set(Arc::new(S3Client::new(&config)));
         info!("AWS clients initialized successfully");
     }
@@ -74,13 +87,19 @@ async fn main() -> Result<(), LambdaError> {
 
     // Initialize controllers once
     info!("Initializing controllers...");
-    init_controllers();
+    init_controllers(&service_config);
     info!("Controllers initialized successfully");
 
     // Create router
     info!("Creating router...");
     let mut router = Router::new();
 
+    // Write out auth metrics still buffered when the container shuts down
+    router.on_shutdown(|| async { AUTH_LAYER.flush_metrics() });
+
+    // Reject blocked IPs, injection payloads and oversized bodies before authenticating
+    router.use_middleware(SecurityMiddleware::new());
+
     // Add authentication middleware
     router.use_middleware(AuthMiddleware);
 
@@ -165,7 +184,7 @@ async fn main() -> Result<(), LambdaError> {
     result
 }
 
-fn init_controllers() {
+fn init_controllers(config: &ServiceConfig) {
     let dynamodb_client = DYNAMODB_CLIENT
         .get()
         .expect("DynamoDB not initialized")
@@ -173,15 +192,28 @@ fn init_controllers() {
     let s3_client = S3_CLIENT.get().expect("S3 not initialized").as_ref();
 
     // Create repositories
-    let user_profile_repository =
-        UserProfileRepository::new(dynamodb_client.clone(), s3_client.clone());
-    let sleep_repository = SleepRepository::new(dynamodb_client.clone());
+    let user_profile_repository = UserProfileRepository::new(
+        dynamodb_client.clone(),
+        s3_client.clone(),
+        config.table_name.clone(),
+    );
+    let sleep_repository = SleepRepository::new(dynamodb_client.clone(), config.table_name.clone());
 
     // Create services
     let user_profile_service =
         UserProfileService::new(user_profile_repository, sleep_repository.clone());
     let sleep_service = SleepService::new(sleep_repository);
-    let upload_service = UploadService::new(s3_client.clone());
+    let upload_url_expiry_secs = std::env::var("UPLOAD_URL_EXPIRY_SECONDS")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .unwrap_or(DEFAULT_UPLOAD_URL_EXPIRY_SECS);
+    let max_upload_bytes = std::env::var("MAX_UPLOAD_BYTES")
+        .ok()
+        .and_then(|bytes| bytes.parse().ok())
+        .unwrap_or(DEFAULT_MAX_UPLOAD_BYTES);
+    let upload_service = UploadService::new(s3_client.clone())
+        .with_expiry_secs(upload_url_expiry_secs)
+        .with_max_file_size(max_upload_bytes);
 
     // Initialize controllers
     let _ = USER_PROFILE_CONTROLLER.set(UserProfileController::new(user_profile_service));
@@ -202,19 +234,19 @@ impl Middleware for AuthMiddleware {
 
         // Convert to auth event format
         let auth_event = AuthLambdaEvent {
-            headers: Some(req.headers.clone()),
+            headers: Some(req.headers.to_hash_map()),
             request_context: req
-                .raw_event()
-                .get("requestContext")
+                .request_context()
                 .and_then(|v| serde_json::from_value(v.clone()).ok()),
             path_parameters: Some(req.path_params.clone()),
             query_string_parameters: Some(req.query_params.clone()),
             body: req.body.clone(),
+            required_permissions: req.required_permissions().to_vec(),
         };
 
         // Authenticate request
         info!("Auth middleware: Calling auth layer...");
-        let auth_result = AUTH_LAYER.authenticate(&auth_event).await.map_err(|e| {
+        let auth_result = time_phase("auth", AUTH_LAYER.authenticate(&auth_event)).await.map_err(|e| {
             error!("Auth middleware: Authentication failed: {}", e);
             format!("Auth error: {}", e)
         })?;
@@ -232,27 +264,17 @@ impl Middleware for AuthMiddleware {
         }
 
         // Add user context
-        if let Some(auth_ctx) = auth_result.context {
+        if let Some(auth_ctx) = &auth_result.context {
             info!(
                 "Auth middleware: Adding user context for user: {}",
                 auth_ctx.user_id
             );
-            match serde_json::to_value(&auth_ctx) {
-                Ok(auth_ctx_value) => {
-                    req.set_context(
-                        Context::new(req.context.request_id.clone())
-                            .with_user(auth_ctx.user_id.clone(), Some(auth_ctx.email.clone()))
-                            .with_custom("auth_context".to_string(), auth_ctx_value),
-                    );
-                }
-                Err(e) => {
-                    error!("Failed to serialize auth context: {}", e);
-                    // Continue without auth context
-                }
-            }
+            let context = build_request_context(req.context.clone(), auth_ctx);
+            req.set_context(context);
         }
 
         info!("Auth middleware: Calling next handler...");
-        next(req).await
+        let response = next(req).await?;
+        Ok(with_token_expiry_header(response, auth_result.expires_in))
     }
 }
@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
@@ -170,6 +172,8 @@ pub struct UploadResponse {
     pub key: String,
     pub bucket_name: String,
     pub expires_in: u64,
+    // Signed headers the PUT must send exactly as given
+    pub headers: HashMap<String, String>,
 }
 
 #[derive(Deserialize, Serialize, Validate, Debug, Clone)]
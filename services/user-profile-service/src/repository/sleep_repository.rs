@@ -11,8 +11,7 @@ pub struct SleepRepository {
 }
 
 impl SleepRepository {
-    pub fn new(dynamodb_client: DynamoDbClient) -> Self {
-        let table_name = std::env::var("TABLE_NAME").unwrap_or_else(|_| "gymcoach-ai-main".to_string());
+    pub fn new(dynamodb_client: DynamoDbClient, table_name: String) -> Self {
         Self {
             dynamodb_client,
             table_name,
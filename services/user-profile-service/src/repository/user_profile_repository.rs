@@ -14,9 +14,7 @@ pub struct UserProfileRepository {
 }
 
 impl UserProfileRepository {
-    pub fn new(dynamodb_client: DynamoDbClient, s3_client: S3Client) -> Self {
-        let table_name =
-            std::env::var("TABLE_NAME").unwrap_or_else(|_| "gymcoach-ai-main".to_string());
+    pub fn new(dynamodb_client: DynamoDbClient, s3_client: S3Client, table_name: String) -> Self {
         Self {
             dynamodb_client,
             s3_client,
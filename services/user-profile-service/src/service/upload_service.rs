@@ -1,49 +1,180 @@
 use aws_sdk_s3::{Client as S3Client, presigning::PresigningConfig};
+use aws_sdk_s3::types::{ObjectCannedAcl, ServerSideEncryption};
+use std::time::Duration;
 use uuid::Uuid;
-use anyhow::Result;
 
 use crate::models::*;
+use crate::utils::{ServiceError, ServiceResult, error_helpers};
+use crate::utils::constants::*;
 
 #[derive(Clone)]
 pub struct UploadService {
     s3_client: S3Client,
+    expires_in_secs: u64,
+    max_file_size: u64,
 }
 
 impl UploadService {
     pub fn new(s3_client: S3Client) -> Self {
-        Self { s3_client }
+        Self {
+            s3_client,
+            expires_in_secs: DEFAULT_UPLOAD_URL_EXPIRY_SECS,
+            max_file_size: DEFAULT_MAX_UPLOAD_BYTES,
+        }
     }
 
-    pub async fn generate_upload_url(&self, request: &UploadRequest) -> Result<UploadResponse, Box<dyn std::error::Error + Send + Sync>> {
-        let file_type = &request.file_type;
+    /// How long presigned URLs stay valid, capped at `MAX_UPLOAD_URL_EXPIRY_SECS`
+    pub fn with_expiry_secs(mut self, expires_in_secs: u64) -> Self {
+        self.expires_in_secs = expires_in_secs.clamp(1, MAX_UPLOAD_URL_EXPIRY_SECS);
+        self
+    }
+
+    /// Largest file, in bytes, a presigned URL may be issued for
+    pub fn with_max_file_size(mut self, max_file_size: u64) -> Self {
+        self.max_file_size = max_file_size;
+        self
+    }
+
+    /// Presigns a PUT that S3 only accepts with the declared content type, the exact
+    /// declared size, a private ACL and AES256 encryption. A presigned PUT can't carry
+    /// a content-length range, so `file_size` is required: signing the exact size is
+    /// the only way to bound the upload.
+    pub async fn generate_upload_url(&self, request: &UploadRequest) -> ServiceResult<UploadResponse> {
+        let file_type = request.file_type.to_ascii_lowercase();
         let file_extension = match file_type.as_str() {
             "image/jpeg" => "jpg",
             "image/png" => "png",
             "image/gif" => "gif",
             "image/webp" => "webp",
-            _ => "jpg",
+            _ => {
+                return Err(error_helpers::validation_failed(&format!(
+                    "Unsupported file type '{}', expected one of: {}",
+                    request.file_type,
+                    ALLOWED_UPLOAD_CONTENT_TYPES.join(", ")
+                )))
+            }
         };
+
+        let file_size = request
+            .file_size
+            .filter(|size| (1..=self.max_file_size).contains(size))
+            .ok_or_else(|| {
+                error_helpers::validation_failed(&format!(
+                    "file_size is required and must be between 1 and {} bytes",
+                    self.max_file_size
+                ))
+            })?;
         
         let file_name = format!("{}.{}", Uuid::new_v4(), file_extension);
         let key = format!("user-profiles/{}", file_name);
         
-        let presigning_config = PresigningConfig::expires_in(std::time::Duration::from_secs(300))?;
+        let presigning_config = PresigningConfig::expires_in(Duration::from_secs(self.expires_in_secs))
+            .map_err(|e| error_helpers::internal_error(&e.to_string()))?;
         
-        let bucket_name = std::env::var("USER_UPLOADS_BUCKET").unwrap_or_else(|_| "gymcoach-ai-user-uploads".to_string());
+        let bucket_name = std::env::var("USER_UPLOADS_BUCKET").unwrap_or_else(|_| DEFAULT_S3_BUCKET.to_string());
         
-        let presigned_url = self.s3_client
+        let presigned = self.s3_client
             .put_object()
             .bucket(&bucket_name)
             .key(&key)
-            .content_type(file_type)
+            .content_type(&file_type)
+            .content_length(file_size as i64)
+            .acl(ObjectCannedAcl::Private)
+            .server_side_encryption(ServerSideEncryption::Aes256)
             .presigned(presigning_config)
-            .await?;
+            .await
+            .map_err(|e| ServiceError::S3Error(e.to_string()))?;
+
+        let headers = presigned
+            .headers()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
         
         Ok(UploadResponse {
-            upload_url: presigned_url.uri().to_string(),
+            upload_url: presigned.uri().to_string(),
             key,
-            bucket_name: bucket_name.clone(),
-            expires_in: 300,
+            bucket_name,
+            expires_in: self.expires_in_secs,
+            headers,
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_s3::config::{BehaviorVersion, Credentials, Region};
+
+    fn upload_service() -> UploadService {
+        let config = aws_sdk_s3::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new("us-east-1"))
+            .credentials_provider(Credentials::new("test", "test", None, None, "test"))
+            .build();
+        UploadService::new(S3Client::from_conf(config))
+    }
+
+    fn request(file_type: &str, file_size: Option<u64>) -> UploadRequest {
+        UploadRequest {
+            file_type: file_type.to_string(),
+            file_size,
+        }
+    }
+
+    fn is_validation_error(result: ServiceResult<UploadResponse>) -> bool {
+        matches!(result, Err(ServiceError::ValidationError(_)))
+    }
+
+    #[tokio::test]
+    async fn test_only_image_content_types_are_accepted() {
+        let service = upload_service();
+        for file_type in ["text/html", "image/svg+xml", "application/octet-stream", ""] {
+            let result = service.generate_upload_url(&request(file_type, Some(1024))).await;
+            assert!(is_validation_error(result), "{}", file_type);
+        }
+
+        let response = service
+            .generate_upload_url(&request("IMAGE/PNG", Some(1024)))
+            .await
+            .unwrap();
+        assert!(response.key.starts_with("user-profiles/"));
+        assert!(response.key.ends_with(".png"));
+        assert_eq!(response.headers.get("content-type").map(String::as_str), Some("image/png"));
+    }
+
+    #[tokio::test]
+    async fn test_file_size_is_required_and_bounded() {
+        let service = upload_service().with_max_file_size(2048);
+        for file_size in [None, Some(0), Some(2049)] {
+            let result = service.generate_upload_url(&request("image/jpeg", file_size)).await;
+            assert!(is_validation_error(result), "{:?}", file_size);
+        }
+
+        let response = service
+            .generate_upload_url(&request("image/jpeg", Some(2048)))
+            .await
+            .unwrap();
+        let header = |name: &str| response.headers.get(name).map(String::as_str);
+        assert_eq!(header("content-length"), Some("2048"));
+        assert_eq!(header("x-amz-acl"), Some("private"));
+        assert_eq!(header("x-amz-server-side-encryption"), Some("AES256"));
+    }
+
+    #[tokio::test]
+    async fn test_expiry_is_signed_and_capped() {
+        let response = upload_service()
+            .generate_upload_url(&request("image/webp", Some(1024)))
+            .await
+            .unwrap();
+        assert_eq!(response.expires_in, DEFAULT_UPLOAD_URL_EXPIRY_SECS);
+        assert!(response.upload_url.contains("X-Amz-Expires=300"));
+
+        let response = upload_service()
+            .with_expiry_secs(24 * 60 * 60)
+            .generate_upload_url(&request("image/webp", Some(1024)))
+            .await
+            .unwrap();
+        assert_eq!(response.expires_in, MAX_UPLOAD_URL_EXPIRY_SECS);
+        assert!(response.upload_url.contains("X-Amz-Expires=3600"));
+    }
+}
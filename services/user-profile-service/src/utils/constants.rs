@@ -33,6 +33,12 @@ pub const DEFAULT_SLEEP_DAYS: u32 = 7;
 pub const DEFAULT_SLEEP_PERIOD: &str = "month";
 pub const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
 
-// Table Names
-pub const DEFAULT_TABLE_NAME: &str = "gymcoach-ai-main";
+// Buckets
 pub const DEFAULT_S3_BUCKET: &str = "gymcoach-ai-user-uploads";
+
+// Presigned Uploads
+pub const DEFAULT_UPLOAD_URL_EXPIRY_SECS: u64 = 300;
+pub const MAX_UPLOAD_URL_EXPIRY_SECS: u64 = 3600;
+pub const DEFAULT_MAX_UPLOAD_BYTES: u64 = 5 * 1024 * 1024;
+pub const ALLOWED_UPLOAD_CONTENT_TYPES: [&str; 4] =
+    ["image/jpeg", "image/png", "image/gif", "image/webp"];
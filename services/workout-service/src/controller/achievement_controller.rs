@@ -0,0 +1,31 @@
+use serde_json::{json, Value};
+use chrono_tz::Tz;
+use tracing::error;
+
+use crate::service::AchievementService;
+use crate::utils::ResponseBuilder;
+use auth_layer::AuthContext;
+
+#[derive(Clone)]
+pub struct AchievementController {
+    achievement_service: AchievementService,
+}
+
+impl AchievementController {
+    pub fn new(achievement_service: AchievementService) -> Self {
+        Self { achievement_service }
+    }
+
+    pub async fn evaluate_achievements(&self, timezone: Tz, dry_run: bool, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        match self.achievement_service.evaluate_achievements(&auth_context.user_id, timezone, dry_run).await {
+            Ok(achievements) => Ok(ResponseBuilder::success(json!({
+                "dryRun": dry_run,
+                "achievements": achievements,
+            }))),
+            Err(e) => {
+                error!("Failed to evaluate achievements: {}", e);
+                Ok(ResponseBuilder::internal_server_error("Failed to evaluate achievements"))
+            }
+        }
+    }
+}
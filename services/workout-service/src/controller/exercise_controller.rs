@@ -2,8 +2,11 @@ use serde_json::{json, Value};
 use anyhow::Result;
 use tracing::error;
 
+use crate::models::ExerciseSearchFilters;
 use crate::service::ExerciseService;
 use crate::utils::{ResponseBuilder, DataHelper};
+use crate::utils::constants::{DEFAULT_EXERCISE_SEARCH_LIMIT, MAX_EXERCISE_SEARCH_LIMIT};
+use crate::utils::error::ServiceError;
 use auth_layer::AuthContext;
 
 #[derive(Clone)]
@@ -36,6 +39,18 @@ impl ExerciseController {
         }
     }
 
+    pub async fn search_exercises(&self, query: &str, filters: &ExerciseSearchFilters, limit: Option<usize>, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        let limit = limit.unwrap_or(DEFAULT_EXERCISE_SEARCH_LIMIT).clamp(1, MAX_EXERCISE_SEARCH_LIMIT);
+        
+        match self.exercise_service.search_exercises(query, filters, limit, auth_context).await {
+            Ok(exercises) => Ok(ResponseBuilder::success(exercises)),
+            Err(e) => {
+                error!("Failed to search exercises: {}", e);
+                Ok(ResponseBuilder::internal_server_error("Failed to search exercises"))
+            }
+        }
+    }
+
     pub async fn create_exercise(&self, body: &str, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
         let parsed: Result<Value, _> = DataHelper::parse_json_safe(body);
         let exercise_data = match parsed {
@@ -55,13 +70,16 @@ impl ExerciseController {
         }
     }
 
-    pub async fn get_exercise(&self, exercise_id: &str, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-        match self.exercise_service.get_exercise(exercise_id, auth_context).await {
+    pub async fn get_exercise(&self, exercise_id: &str, consistent: bool, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        match self.exercise_service.get_exercise(exercise_id, consistent, auth_context).await {
             Ok(exercise) => Ok(ResponseBuilder::success(exercise)),
-            Err(e) => {
-                error!("Failed to get exercise: {}", e);
-                Ok(ResponseBuilder::internal_server_error("Failed to retrieve exercise"))
-            }
+            Err(e) => match e.downcast_ref::<ServiceError>() {
+                Some(ServiceError::NotFound(msg)) => Ok(ResponseBuilder::not_found(msg)),
+                _ => {
+                    error!("Failed to get exercise: {}", e);
+                    Ok(ResponseBuilder::internal_server_error("Failed to retrieve exercise"))
+                }
+            },
         }
     }
 
@@ -84,13 +102,27 @@ impl ExerciseController {
         }
     }
 
-    pub async fn clone_exercise(&self, exercise_id: &str, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-        match self.exercise_service.clone_exercise(exercise_id, auth_context).await {
+    pub async fn clone_exercise(&self, exercise_id: &str, body: Option<&str>, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        // The override body is optional; without one the clone is a straight copy
+        let overrides = match body.filter(|body| !body.trim().is_empty()) {
+            Some(body) => match DataHelper::parse_json_safe(body) {
+                Ok(json) if json.is_object() => json,
+                _ => return Ok(ResponseBuilder::bad_request("Invalid JSON in request body")),
+            },
+            None => json!({}),
+        };
+
+        match self.exercise_service.clone_exercise(exercise_id, &overrides, auth_context).await {
             Ok(exercise) => Ok(ResponseBuilder::success(exercise)),
-            Err(e) => {
-                error!("Failed to clone exercise: {}", e);
-                Ok(ResponseBuilder::internal_server_error("Failed to clone exercise"))
-            }
+            Err(e) => match e.downcast_ref::<ServiceError>() {
+                Some(ServiceError::NotFound(msg)) => Ok(ResponseBuilder::not_found(msg)),
+                Some(ServiceError::Conflict(msg, _)) => Ok(ResponseBuilder::conflict(msg)),
+                Some(ServiceError::Validation(msg, _)) => Ok(ResponseBuilder::bad_request(msg)),
+                _ => {
+                    error!("Failed to clone exercise: {}", e);
+                    Ok(ResponseBuilder::internal_server_error("Failed to clone exercise"))
+                }
+            },
         }
     }
 
@@ -3,9 +3,11 @@ pub mod workout_session_controller;
 pub mod exercise_controller;
 pub mod workout_analytics_controller;
 pub mod scheduled_workout_controller;
+pub mod achievement_controller;
 
 pub use workout_plan_controller::WorkoutPlanController;
 pub use workout_session_controller::WorkoutSessionController;
 pub use exercise_controller::ExerciseController;
 pub use workout_analytics_controller::WorkoutAnalyticsController;
 pub use scheduled_workout_controller::ScheduledWorkoutController;
+pub use achievement_controller::AchievementController;
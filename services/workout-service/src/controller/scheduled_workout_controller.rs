@@ -2,9 +2,12 @@ use serde_json::{json, Value};
 use anyhow::Result;
 use tracing::error;
 
+use crate::models::ScheduledWorkoutFilters;
 use crate::service::ScheduledWorkoutService;
+use crate::utils::error::ServiceError;
 use crate::utils::{ResponseBuilder, DataHelper};
 use auth_layer::AuthContext;
+use lambda_router::PageParams;
 
 #[derive(Clone)]
 pub struct ScheduledWorkoutController {
@@ -28,20 +31,27 @@ impl ScheduledWorkoutController {
 
         match self.scheduled_workout_service.create_scheduled_workout(&schedule_data, auth_context).await {
             Ok(schedule) => Ok(ResponseBuilder::success(schedule)),
-            Err(e) => {
-                error!("Failed to create scheduled workout: {}", e);
-                Ok(ResponseBuilder::internal_server_error("Failed to create scheduled workout"))
-            }
+            Err(e) => match e.downcast_ref::<ServiceError>() {
+                Some(ServiceError::Conflict(msg, details)) => Ok(ResponseBuilder::conflict_with_details(msg, details.clone())),
+                Some(ServiceError::Validation(msg, _)) => Ok(ResponseBuilder::bad_request(msg)),
+                _ => {
+                    error!("Failed to create scheduled workout: {}", e);
+                    Ok(ResponseBuilder::internal_server_error("Failed to create scheduled workout"))
+                }
+            },
         }
     }
 
-    pub async fn get_scheduled_workouts(&self, user_id: Option<String>, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-        match self.scheduled_workout_service.get_scheduled_workouts(user_id, auth_context).await {
+    pub async fn get_scheduled_workouts(&self, user_id: Option<String>, filters: &ScheduledWorkoutFilters, page: Option<PageParams>, consistent: bool, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        match self.scheduled_workout_service.get_scheduled_workouts(user_id, filters, page, consistent, auth_context).await {
             Ok(schedules) => Ok(ResponseBuilder::success(schedules)),
-            Err(e) => {
-                error!("Failed to get scheduled workouts: {}", e);
-                Ok(ResponseBuilder::internal_server_error("Failed to retrieve scheduled workouts"))
-            }
+            Err(e) => match e.downcast_ref::<ServiceError>() {
+                Some(ServiceError::Validation(msg, _)) => Ok(ResponseBuilder::bad_request(msg)),
+                _ => {
+                    error!("Failed to get scheduled workouts: {}", e);
+                    Ok(ResponseBuilder::internal_server_error("Failed to retrieve scheduled workouts"))
+                }
+            },
         }
     }
 
@@ -57,20 +67,39 @@ impl ScheduledWorkoutController {
 
         match self.scheduled_workout_service.update_scheduled_workout(&schedule_data, auth_context).await {
             Ok(schedule) => Ok(ResponseBuilder::success(schedule)),
-            Err(e) => {
-                error!("Failed to update scheduled workout: {}", e);
-                Ok(ResponseBuilder::internal_server_error("Failed to update scheduled workout"))
-            }
+            Err(e) => match e.downcast_ref::<ServiceError>() {
+                Some(ServiceError::NotFound(msg)) => Ok(ResponseBuilder::not_found(msg)),
+                _ => {
+                    error!("Failed to update scheduled workout: {}", e);
+                    Ok(ResponseBuilder::internal_server_error("Failed to update scheduled workout"))
+                }
+            },
         }
     }
 
     pub async fn delete_scheduled_workout(&self, user_id: &str, schedule_id: &str, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
         match self.scheduled_workout_service.delete_scheduled_workout(user_id, schedule_id, auth_context).await {
             Ok(result) => Ok(ResponseBuilder::success(result)),
-            Err(e) => {
-                error!("Failed to delete scheduled workout: {}", e);
-                Ok(ResponseBuilder::internal_server_error("Failed to delete scheduled workout"))
-            }
+            Err(e) => match e.downcast_ref::<ServiceError>() {
+                Some(ServiceError::NotFound(msg)) => Ok(ResponseBuilder::not_found(msg)),
+                _ => {
+                    error!("Failed to delete scheduled workout: {}", e);
+                    Ok(ResponseBuilder::internal_server_error("Failed to delete scheduled workout"))
+                }
+            },
+        }
+    }
+
+    pub async fn delete_recurring_scheduled_workouts(&self, user_id: &str, recurrence_id: &str, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        match self.scheduled_workout_service.delete_recurring_scheduled_workouts(user_id, recurrence_id, auth_context).await {
+            Ok(result) => Ok(ResponseBuilder::success(result)),
+            Err(e) => match e.downcast_ref::<ServiceError>() {
+                Some(ServiceError::NotFound(msg)) => Ok(ResponseBuilder::not_found(msg)),
+                _ => {
+                    error!("Failed to delete recurring scheduled workouts: {}", e);
+                    Ok(ResponseBuilder::internal_server_error("Failed to delete recurring scheduled workouts"))
+                }
+            },
         }
     }
 }
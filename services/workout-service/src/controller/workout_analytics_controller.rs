@@ -1,5 +1,6 @@
 use serde_json::{json, Value};
 use anyhow::Result;
+use chrono_tz::Tz;
 use tracing::error;
 
 use crate::service::WorkoutAnalyticsService;
@@ -16,8 +17,8 @@ impl WorkoutAnalyticsController {
         Self { workout_analytics_service }
     }
 
-    pub async fn get_workout_analytics(&self, user_id: Option<String>, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-        match self.workout_analytics_service.get_workout_analytics(user_id, auth_context).await {
+    pub async fn get_workout_analytics(&self, user_id: Option<String>, timezone: Tz, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        match self.workout_analytics_service.get_workout_analytics(user_id, timezone, auth_context).await {
             Ok(analytics) => Ok(ResponseBuilder::success(analytics)),
             Err(e) => {
                 error!("Failed to get workout analytics: {}", e);
@@ -26,8 +27,8 @@ impl WorkoutAnalyticsController {
         }
     }
 
-    pub async fn get_workout_insights(&self, user_id: &str, time_range: &str, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-        match self.workout_analytics_service.get_workout_insights(user_id, time_range, auth_context).await {
+    pub async fn get_workout_insights(&self, user_id: &str, time_range: &str, timezone: Tz, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        match self.workout_analytics_service.get_workout_insights(user_id, time_range, timezone, auth_context).await {
             Ok(insights) => Ok(ResponseBuilder::success(insights)),
             Err(e) => {
                 error!("Failed to get workout insights: {}", e);
@@ -36,8 +37,8 @@ impl WorkoutAnalyticsController {
         }
     }
 
-    pub async fn get_workout_history(&self, user_id: &str, limit: Option<i32>, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-        match self.workout_analytics_service.get_workout_history(user_id, limit, auth_context).await {
+    pub async fn get_workout_history(&self, user_id: &str, page: Option<i32>, limit: Option<i32>, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        match self.workout_analytics_service.get_workout_history(user_id, page, limit, auth_context).await {
             Ok(history) => Ok(ResponseBuilder::success(history)),
             Err(e) => {
                 error!("Failed to get workout history: {}", e);
@@ -2,22 +2,25 @@ use serde_json::{json, Value};
 use anyhow::Result;
 use tracing::error;
 
+use crate::repository::DynamoOps;
 use crate::service::WorkoutPlanService;
-use crate::utils::{ResponseBuilder, DataHelper};
+use crate::utils::error::ServiceError;
+use crate::utils::{etag_from_version, ResponseBuilder, DataHelper};
 use auth_layer::AuthContext;
+use aws_sdk_dynamodb::Client as DynamoDbClient;
 
 #[derive(Clone)]
-pub struct WorkoutPlanController {
-    workout_plan_service: WorkoutPlanService,
+pub struct WorkoutPlanController<C = DynamoDbClient> {
+    workout_plan_service: WorkoutPlanService<C>,
 }
 
-impl WorkoutPlanController {
-    pub fn new(workout_plan_service: WorkoutPlanService) -> Self {
+impl<C: DynamoOps> WorkoutPlanController<C> {
+    pub fn new(workout_plan_service: WorkoutPlanService<C>) -> Self {
         Self { workout_plan_service }
     }
 
-    pub async fn get_workout_plans(&self, user_id: Option<String>, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-        match self.workout_plan_service.get_workout_plans(user_id, auth_context).await {
+    pub async fn get_workout_plans(&self, user_id: Option<String>, consistent: bool, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        match self.workout_plan_service.get_workout_plans(user_id, consistent, auth_context).await {
             Ok(plans) => Ok(ResponseBuilder::success(plans)),
             Err(e) => {
                 error!("Failed to get workout plans: {}", e);
@@ -37,25 +40,37 @@ impl WorkoutPlanController {
         };
 
         match self.workout_plan_service.create_workout_plan(&plan_data, auth_context).await {
-            Ok(plan) => Ok(ResponseBuilder::success(plan)),
-            Err(e) => {
-                error!("Failed to create workout plan: {}", e);
-                Ok(ResponseBuilder::internal_server_error("Failed to create workout plan"))
+            Ok(plan) => {
+                let etag = etag_from_version(plan["version"].as_i64().unwrap_or(0));
+                Ok(ResponseBuilder::with_etag(ResponseBuilder::success(plan), &etag))
             }
+            Err(e) => match e.downcast_ref::<ServiceError>() {
+                Some(ServiceError::Conflict(msg, _)) => Ok(ResponseBuilder::conflict(msg)),
+                _ => {
+                    error!("Failed to create workout plan: {}", e);
+                    Ok(ResponseBuilder::internal_server_error("Failed to create workout plan"))
+                }
+            },
         }
     }
 
-    pub async fn get_workout_plan(&self, user_id: &str, plan_id: &str, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-        match self.workout_plan_service.get_workout_plan(user_id, plan_id, auth_context).await {
-            Ok(plan) => Ok(ResponseBuilder::success(plan)),
-            Err(e) => {
-                error!("Failed to get workout plan: {}", e);
-                Ok(ResponseBuilder::internal_server_error("Failed to retrieve workout plan"))
+    pub async fn get_workout_plan(&self, user_id: &str, plan_id: &str, consistent: bool, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        match self.workout_plan_service.get_workout_plan(user_id, plan_id, consistent, auth_context).await {
+            Ok(plan) => {
+                let etag = etag_from_version(plan["version"].as_i64().unwrap_or(0));
+                Ok(ResponseBuilder::with_etag(ResponseBuilder::success(plan), &etag))
             }
+            Err(e) => match e.downcast_ref::<ServiceError>() {
+                Some(ServiceError::NotFound(msg)) => Ok(ResponseBuilder::not_found(msg)),
+                _ => {
+                    error!("Failed to get workout plan: {}", e);
+                    Ok(ResponseBuilder::internal_server_error("Failed to retrieve workout plan"))
+                }
+            },
         }
     }
 
-    pub async fn update_workout_plan(&self, body: &str, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+    pub async fn update_workout_plan(&self, body: &str, auth_context: &AuthContext, if_match: Option<&str>) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
         let parsed: Result<Value, _> = DataHelper::parse_json_safe(body);
         let plan_data = match parsed {
             Ok(json) => json,
@@ -65,22 +80,111 @@ impl WorkoutPlanController {
             }
         };
 
-        match self.workout_plan_service.update_workout_plan(&plan_data, auth_context).await {
-            Ok(plan) => Ok(ResponseBuilder::success(plan)),
-            Err(e) => {
-                error!("Failed to update workout plan: {}", e);
-                Ok(ResponseBuilder::internal_server_error("Failed to update workout plan"))
+        match self.workout_plan_service.update_workout_plan(&plan_data, auth_context, if_match).await {
+            Ok(plan) => {
+                let etag = etag_from_version(plan["version"].as_i64().unwrap_or(0));
+                Ok(ResponseBuilder::with_etag(ResponseBuilder::success(plan), &etag))
             }
+            Err(e) => match e.downcast_ref::<ServiceError>() {
+                Some(ServiceError::PreconditionFailed(msg)) => {
+                    Ok(ResponseBuilder::precondition_failed(msg))
+                }
+                Some(ServiceError::Conflict(msg, _)) => Ok(ResponseBuilder::conflict(msg)),
+                Some(ServiceError::NotFound(msg)) => Ok(ResponseBuilder::not_found(msg)),
+                _ => {
+                    error!("Failed to update workout plan: {}", e);
+                    Ok(ResponseBuilder::internal_server_error("Failed to update workout plan"))
+                }
+            },
         }
     }
 
     pub async fn delete_workout_plan(&self, user_id: &str, plan_id: &str, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
         match self.workout_plan_service.delete_workout_plan(user_id, plan_id, auth_context).await {
             Ok(_) => Ok(ResponseBuilder::success(json!({"message": "Workout plan deleted successfully"}))),
-            Err(e) => {
-                error!("Failed to delete workout plan: {}", e);
-                Ok(ResponseBuilder::internal_server_error("Failed to delete workout plan"))
+            Err(e) => match e.downcast_ref::<ServiceError>() {
+                Some(ServiceError::NotFound(msg)) => Ok(ResponseBuilder::not_found(msg)),
+                _ => {
+                    error!("Failed to delete workout plan: {}", e);
+                    Ok(ResponseBuilder::internal_server_error("Failed to delete workout plan"))
+                }
+            },
+        }
+    }
+
+    pub async fn restore_workout_plan(&self, user_id: &str, plan_id: &str, auth_context: &AuthContext) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        match self.workout_plan_service.restore_workout_plan(user_id, plan_id, auth_context).await {
+            Ok(plan) => {
+                let etag = etag_from_version(plan["version"].as_i64().unwrap_or(0));
+                Ok(ResponseBuilder::with_etag(ResponseBuilder::success(plan), &etag))
             }
+            Err(e) => match e.downcast_ref::<ServiceError>() {
+                Some(ServiceError::NotFound(msg)) => Ok(ResponseBuilder::not_found(msg)),
+                _ => {
+                    error!("Failed to restore workout plan: {}", e);
+                    Ok(ResponseBuilder::internal_server_error("Failed to restore workout plan"))
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::WorkoutPlan;
+    use crate::repository::dynamo_ops::fake::FakeDynamo;
+    use crate::repository::WorkoutPlanRepository;
+    use lambda_router::Response;
+
+    fn auth_context(user_id: &str) -> AuthContext {
+        AuthContext {
+            user_id: user_id.to_string(),
+            email: "test@example.com".to_string(),
+            roles: vec!["user".to_string()],
+            permissions: vec![],
+            exp: 0,
+            iat: 0,
         }
     }
+
+    #[tokio::test]
+    async fn test_stale_if_match_returns_412() {
+        let repository = WorkoutPlanRepository::new(FakeDynamo::default(), "test".to_string());
+        repository
+            .create_workout_plan(&WorkoutPlan {
+                id: "plan-1".to_string(),
+                user_id: "user-1".to_string(),
+                name: "Push Pull Legs".to_string(),
+                description: None,
+                difficulty: "intermediate".to_string(),
+                duration_weeks: 8,
+                frequency_per_week: 3,
+                exercises: Vec::new(),
+                created_at: "2024-01-01T00:00:00Z".to_string(),
+                updated_at: "2024-01-01T00:00:00Z".to_string(),
+                is_active: true,
+                tags: None,
+                rating: None,
+                is_template: None,
+                total_sessions: None,
+                completed_sessions: None,
+                next_scheduled_date: None,
+                version: 2,
+            })
+            .await
+            .unwrap();
+        let controller = WorkoutPlanController::new(WorkoutPlanService::new(repository));
+        let body = json!({ "userId": "user-1", "id": "plan-1", "name": "Upper Lower" }).to_string();
+
+        let response = controller
+            .update_workout_plan(&body, &auth_context("user-1"), Some("\"1\""))
+            .await
+            .unwrap();
+
+        let response = Response::from_json_value(response);
+        assert_eq!(response.status_code, 412);
+        let body: Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(body["error"], "Precondition Failed");
+    }
 }
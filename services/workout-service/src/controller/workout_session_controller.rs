@@ -3,8 +3,10 @@ use serde_json::{json, Value};
 use tracing::error;
 
 use crate::service::WorkoutSessionService;
+use crate::utils::error::ServiceError;
 use crate::utils::{DataHelper, ResponseBuilder};
 use auth_layer::AuthContext;
+use lambda_router::PageParams;
 
 #[derive(Clone)]
 pub struct WorkoutSessionController {
@@ -22,11 +24,13 @@ impl WorkoutSessionController {
         &self,
         user_id: Option<String>,
         workout_plan_id: Option<String>,
+        page: Option<PageParams>,
+        consistent: bool,
         auth_context: &AuthContext,
     ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
         match self
             .workout_session_service
-            .get_workout_sessions(user_id, workout_plan_id, auth_context)
+            .get_workout_sessions(user_id, workout_plan_id, page, consistent, auth_context)
             .await
         {
             Ok(sessions) => Ok(ResponseBuilder::success(sessions)),
@@ -68,23 +72,129 @@ impl WorkoutSessionController {
         }
     }
 
-    pub async fn get_workout_session(
+    pub async fn start_workout_session(
+        &self,
+        body: &str,
+        auth_context: &AuthContext,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        let parsed: Result<Value, _> = DataHelper::parse_json_safe(body);
+        let session_data = match parsed {
+            Ok(json) => json,
+            Err(e) => {
+                error!("Failed to parse request body: {}", e);
+                return Ok(ResponseBuilder::bad_request("Invalid JSON in request body"));
+            }
+        };
+
+        match self
+            .workout_session_service
+            .start_workout_session(&session_data, auth_context)
+            .await
+        {
+            Ok(session) => Ok(ResponseBuilder::success(session)),
+            Err(e) => match e.downcast_ref::<ServiceError>() {
+                Some(ServiceError::Validation(msg, _)) => Ok(ResponseBuilder::bad_request(msg)),
+                _ => {
+                    error!("Failed to start workout session: {}", e);
+                    Ok(ResponseBuilder::internal_server_error(
+                        "Failed to start workout session",
+                    ))
+                }
+            },
+        }
+    }
+
+    pub async fn log_session_sets(
         &self,
         session_id: &str,
+        body: &str,
         auth_context: &AuthContext,
     ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        let parsed: Result<Value, _> = DataHelper::parse_json_safe(body);
+        let session_data = match parsed {
+            Ok(json) => json,
+            Err(e) => {
+                error!("Failed to parse request body: {}", e);
+                return Ok(ResponseBuilder::bad_request("Invalid JSON in request body"));
+            }
+        };
+
         match self
             .workout_session_service
-            .get_workout_session(session_id, auth_context)
+            .log_session_sets(session_id, &session_data, auth_context)
             .await
         {
             Ok(session) => Ok(ResponseBuilder::success(session)),
+            Err(e) => match e.downcast_ref::<ServiceError>() {
+                Some(ServiceError::Validation(msg, _)) => Ok(ResponseBuilder::bad_request(msg)),
+                Some(ServiceError::NotFound(msg)) => Ok(ResponseBuilder::not_found(msg)),
+                Some(ServiceError::Conflict(msg, _)) => Ok(ResponseBuilder::conflict(msg)),
+                _ => {
+                    error!("Failed to log workout session sets: {}", e);
+                    Ok(ResponseBuilder::internal_server_error(
+                        "Failed to log workout session sets",
+                    ))
+                }
+            },
+        }
+    }
+
+    pub async fn complete_workout_session(
+        &self,
+        session_id: &str,
+        body: &str,
+        auth_context: &AuthContext,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        let parsed: Result<Value, _> = DataHelper::parse_json_safe(body);
+        let session_data = match parsed {
+            Ok(json) => json,
             Err(e) => {
-                error!("Failed to get workout session: {}", e);
-                Ok(ResponseBuilder::internal_server_error(
-                    "Failed to retrieve workout session",
-                ))
+                error!("Failed to parse request body: {}", e);
+                return Ok(ResponseBuilder::bad_request("Invalid JSON in request body"));
             }
+        };
+
+        match self
+            .workout_session_service
+            .complete_workout_session(session_id, &session_data, auth_context)
+            .await
+        {
+            Ok(session) => Ok(ResponseBuilder::success(session)),
+            Err(e) => match e.downcast_ref::<ServiceError>() {
+                Some(ServiceError::Validation(msg, _)) => Ok(ResponseBuilder::bad_request(msg)),
+                Some(ServiceError::NotFound(msg)) => Ok(ResponseBuilder::not_found(msg)),
+                Some(ServiceError::Conflict(msg, _)) => Ok(ResponseBuilder::conflict(msg)),
+                _ => {
+                    error!("Failed to complete workout session: {}", e);
+                    Ok(ResponseBuilder::internal_server_error(
+                        "Failed to complete workout session",
+                    ))
+                }
+            },
+        }
+    }
+
+    pub async fn get_workout_session(
+        &self,
+        session_id: &str,
+        consistent: bool,
+        auth_context: &AuthContext,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        match self
+            .workout_session_service
+            .get_workout_session(session_id, consistent, auth_context)
+            .await
+        {
+            Ok(session) => Ok(ResponseBuilder::success(session)),
+            Err(e) => match e.downcast_ref::<ServiceError>() {
+                Some(ServiceError::NotFound(msg)) => Ok(ResponseBuilder::not_found(msg)),
+                _ => {
+                    error!("Failed to get workout session: {}", e);
+                    Ok(ResponseBuilder::internal_server_error(
+                        "Failed to retrieve workout session",
+                    ))
+                }
+            },
         }
     }
 
@@ -130,10 +240,55 @@ impl WorkoutSessionController {
             Ok(_) => Ok(ResponseBuilder::success(
                 json!({"message": "Workout session deleted successfully"}),
             )),
+            Err(e) => match e.downcast_ref::<ServiceError>() {
+                Some(ServiceError::NotFound(msg)) => Ok(ResponseBuilder::not_found(msg)),
+                _ => {
+                    error!("Failed to delete workout session: {}", e);
+                    Ok(ResponseBuilder::internal_server_error(
+                        "Failed to delete workout session",
+                    ))
+                }
+            },
+        }
+    }
+
+    pub async fn restore_workout_session(
+        &self,
+        session_id: &str,
+        auth_context: &AuthContext,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        match self
+            .workout_session_service
+            .restore_workout_session(session_id, auth_context)
+            .await
+        {
+            Ok(session) => Ok(ResponseBuilder::success(session)),
+            Err(e) => match e.downcast_ref::<ServiceError>() {
+                Some(ServiceError::NotFound(msg)) => Ok(ResponseBuilder::not_found(msg)),
+                _ => {
+                    error!("Failed to restore workout session: {}", e);
+                    Ok(ResponseBuilder::internal_server_error(
+                        "Failed to restore workout session",
+                    ))
+                }
+            },
+        }
+    }
+
+    pub async fn migrate_session_keys(
+        &self,
+        auth_context: &AuthContext,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        match self
+            .workout_session_service
+            .migrate_session_keys(auth_context)
+            .await
+        {
+            Ok(result) => Ok(ResponseBuilder::success(result)),
             Err(e) => {
-                error!("Failed to delete workout session: {}", e);
+                error!("Failed to migrate workout session keys: {}", e);
                 Ok(ResponseBuilder::internal_server_error(
-                    "Failed to delete workout session",
+                    "Failed to migrate workout session keys",
                 ))
             }
         }
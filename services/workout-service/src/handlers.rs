@@ -1,10 +1,12 @@
 use auth_layer::AuthContext;
+use chrono_tz::Tz;
 use lambda_router::{Context, Request, Response, RouterError};
 use tracing::error;
 
+use crate::models::{ExerciseSearchFilters, ScheduledWorkoutFilters};
 use crate::{
-    EXERCISE_CONTROLLER, SCHEDULED_WORKOUT_CONTROLLER, WORKOUT_ANALYTICS_CONTROLLER,
-    WORKOUT_PLAN_CONTROLLER, WORKOUT_SESSION_CONTROLLER,
+    ACHIEVEMENT_CONTROLLER, EXERCISE_CONTROLLER, SCHEDULED_WORKOUT_CONTROLLER,
+    WORKOUT_ANALYTICS_CONTROLLER, WORKOUT_PLAN_CONTROLLER, WORKOUT_SESSION_CONTROLLER,
 };
 
 // Helper function to get auth context from request context
@@ -22,6 +24,17 @@ pub fn get_auth_context(ctx: &Context) -> AuthContext {
         })
 }
 
+// Helper function to resolve the IANA timezone used for calendar-day analytics
+fn parse_timezone(timezone: Option<&String>) -> Tz {
+    timezone.and_then(|tz| tz.parse().ok()).unwrap_or(Tz::UTC)
+}
+
+// Helper function to read the `?consistent=true` flag that asks for a strongly
+// consistent read, for clients fetching something they just wrote
+fn wants_consistent_read(req: &Request) -> bool {
+    req.query("consistent").is_some_and(|value| value == "true" || value == "1")
+}
+
 // ==================== WORKOUT PLAN HANDLERS ====================
 
 pub async fn get_workout_plans(req: Request, ctx: Context) -> Result<Response, RouterError> {
@@ -32,7 +45,10 @@ pub async fn get_workout_plans(req: Request, ctx: Context) -> Result<Response, R
         .get()
         .ok_or("Controller not initialized")?;
 
-    match controller.get_workout_plans(user_id, &auth_context).await {
+    match controller
+        .get_workout_plans(user_id, wants_consistent_read(&req), &auth_context)
+        .await
+    {
         Ok(response_value) => Ok(Response::from_json_value(response_value)),
         Err(e) => {
             error!("Error in get_workout_plans handler: {}", e);
@@ -68,7 +84,7 @@ pub async fn get_workout_plan(req: Request, ctx: Context) -> Result<Response, Ro
         .ok_or("Controller not initialized")?;
 
     match controller
-        .get_workout_plan(user_id, plan_id, &auth_context)
+        .get_workout_plan(user_id, plan_id, wants_consistent_read(&req), &auth_context)
         .await
     {
         Ok(response_value) => Ok(Response::from_json_value(response_value)),
@@ -87,7 +103,10 @@ pub async fn update_workout_plan(req: Request, ctx: Context) -> Result<Response,
         .get()
         .ok_or("Controller not initialized")?;
 
-    match controller.update_workout_plan(body, &auth_context).await {
+    match controller
+        .update_workout_plan(body, &auth_context, req.if_match())
+        .await
+    {
         Ok(response_value) => Ok(Response::from_json_value(response_value)),
         Err(e) => {
             error!("Error in update_workout_plan handler: {}", e);
@@ -117,19 +136,50 @@ pub async fn delete_workout_plan(req: Request, ctx: Context) -> Result<Response,
     }
 }
 
+pub async fn restore_workout_plan(req: Request, ctx: Context) -> Result<Response, RouterError> {
+    let auth_context = get_auth_context(&ctx);
+    let plan_id = req.path_param("planId").ok_or("Missing planId parameter")?;
+    let user_id = req.query("userId").unwrap_or(&auth_context.user_id);
+
+    let controller = WORKOUT_PLAN_CONTROLLER
+        .get()
+        .ok_or("Controller not initialized")?;
+
+    match controller
+        .restore_workout_plan(user_id, plan_id, &auth_context)
+        .await
+    {
+        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Err(e) => {
+            error!("Error in restore_workout_plan handler: {}", e);
+            Ok(Response::internal_error("Failed to process request"))
+        }
+    }
+}
+
 // ==================== WORKOUT SESSION HANDLERS ====================
 
 pub async fn get_workout_sessions(req: Request, ctx: Context) -> Result<Response, RouterError> {
     let auth_context = get_auth_context(&ctx);
     let user_id = req.query("userId").map(|s| s.to_string());
     let workout_plan_id = req.query("workoutPlanId").map(|s| s.to_string());
+    let page = match req.page_params() {
+        Ok(page) => page,
+        Err(response) => return Ok(*response),
+    };
 
     let controller = WORKOUT_SESSION_CONTROLLER
         .get()
         .ok_or("Controller not initialized")?;
 
     match controller
-        .get_workout_sessions(user_id, workout_plan_id, &auth_context)
+        .get_workout_sessions(
+            user_id,
+            workout_plan_id,
+            page,
+            wants_consistent_read(&req),
+            &auth_context,
+        )
         .await
     {
         Ok(response_value) => Ok(Response::from_json_value(response_value)),
@@ -168,7 +218,7 @@ pub async fn get_workout_session(req: Request, ctx: Context) -> Result<Response,
         .ok_or("Controller not initialized")?;
 
     match controller
-        .get_workout_session(session_id, &auth_context)
+        .get_workout_session(session_id, wants_consistent_read(&req), &auth_context)
         .await
     {
         Ok(response_value) => Ok(Response::from_json_value(response_value)),
@@ -218,6 +268,111 @@ pub async fn delete_workout_session(req: Request, ctx: Context) -> Result<Respon
     }
 }
 
+pub async fn restore_workout_session(req: Request, ctx: Context) -> Result<Response, RouterError> {
+    let auth_context = get_auth_context(&ctx);
+    let session_id = req
+        .path_param("sessionId")
+        .ok_or("Missing sessionId parameter")?;
+
+    let controller = WORKOUT_SESSION_CONTROLLER
+        .get()
+        .ok_or("Controller not initialized")?;
+
+    match controller
+        .restore_workout_session(session_id, &auth_context)
+        .await
+    {
+        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Err(e) => {
+            error!("Error in restore_workout_session handler: {}", e);
+            Ok(Response::internal_error("Failed to process request"))
+        }
+    }
+}
+
+pub async fn migrate_session_keys(_req: Request, ctx: Context) -> Result<Response, RouterError> {
+    let auth_context = get_auth_context(&ctx);
+
+    let controller = WORKOUT_SESSION_CONTROLLER
+        .get()
+        .ok_or("Controller not initialized")?;
+
+    match controller.migrate_session_keys(&auth_context).await {
+        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Err(e) => {
+            error!("Error in migrate_session_keys handler: {}", e);
+            Ok(Response::internal_error("Failed to process request"))
+        }
+    }
+}
+
+pub async fn start_workout_session(req: Request, ctx: Context) -> Result<Response, RouterError> {
+    let auth_context = get_auth_context(&ctx);
+    let body = req.body().ok_or("Missing request body")?;
+
+    let controller = WORKOUT_SESSION_CONTROLLER
+        .get()
+        .ok_or("Controller not initialized")?;
+
+    match controller.start_workout_session(body, &auth_context).await {
+        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Err(e) => {
+            error!("Error in start_workout_session handler: {}", e);
+            Ok(Response::internal_error("Failed to process request"))
+        }
+    }
+}
+
+pub async fn log_session_sets(req: Request, ctx: Context) -> Result<Response, RouterError> {
+    let auth_context = get_auth_context(&ctx);
+    let session_id = req
+        .path_param("sessionId")
+        .ok_or("Missing sessionId parameter")?;
+    let body = req.body().ok_or("Missing request body")?;
+
+    let controller = WORKOUT_SESSION_CONTROLLER
+        .get()
+        .ok_or("Controller not initialized")?;
+
+    match controller
+        .log_session_sets(session_id, body, &auth_context)
+        .await
+    {
+        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Err(e) => {
+            error!("Error in log_session_sets handler: {}", e);
+            Ok(Response::internal_error("Failed to process request"))
+        }
+    }
+}
+
+pub async fn complete_workout_session(req: Request, ctx: Context) -> Result<Response, RouterError> {
+    let auth_context = get_auth_context(&ctx);
+    let session_id = req
+        .path_param("sessionId")
+        .ok_or("Missing sessionId parameter")?;
+    // Every field is optional, so a POST without a body completes the session now
+    let body = req
+        .body()
+        .filter(|body| !body.trim().is_empty())
+        .unwrap_or("{}");
+
+    let controller = WORKOUT_SESSION_CONTROLLER
+        .get()
+        .ok_or("Controller not initialized")?;
+
+    match controller
+        .complete_workout_session(session_id, body, &auth_context)
+        .await
+    {
+        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Err(e) => {
+            error!("Error in complete_workout_session handler: {}", e);
+            Ok(Response::internal_error("Failed to process request"))
+        }
+    }
+}
+
 // ==================== EXERCISE HANDLERS ====================
 
 pub async fn get_exercises(_req: Request, ctx: Context) -> Result<Response, RouterError> {
@@ -236,6 +391,32 @@ pub async fn get_exercises(_req: Request, ctx: Context) -> Result<Response, Rout
     }
 }
 
+pub async fn search_exercises(req: Request, ctx: Context) -> Result<Response, RouterError> {
+    let auth_context = get_auth_context(&ctx);
+    let query = req.query("q").map(|q| q.as_str()).unwrap_or_default();
+    let limit = req.query("limit").and_then(|s| s.parse::<usize>().ok());
+    let filters = ExerciseSearchFilters {
+        category: req.query("category").cloned(),
+        muscle_group: req.query("muscleGroup").cloned(),
+        equipment: req.query("equipment").cloned(),
+    };
+
+    let controller = EXERCISE_CONTROLLER
+        .get()
+        .ok_or("Controller not initialized")?;
+
+    match controller
+        .search_exercises(query, &filters, limit, &auth_context)
+        .await
+    {
+        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Err(e) => {
+            error!("Error in search_exercises handler: {}", e);
+            Ok(Response::internal_error("Failed to process request"))
+        }
+    }
+}
+
 pub async fn create_exercise(req: Request, ctx: Context) -> Result<Response, RouterError> {
     let auth_context = get_auth_context(&ctx);
     let body = req.body().ok_or("Missing request body")?;
@@ -263,7 +444,10 @@ pub async fn get_exercise(req: Request, ctx: Context) -> Result<Response, Router
         .get()
         .ok_or("Controller not initialized")?;
 
-    match controller.get_exercise(exercise_id, &auth_context).await {
+    match controller
+        .get_exercise(exercise_id, wants_consistent_read(&req), &auth_context)
+        .await
+    {
         Ok(response_value) => Ok(Response::from_json_value(response_value)),
         Err(e) => {
             error!("Error in get_exercise handler: {}", e);
@@ -299,7 +483,7 @@ pub async fn clone_exercise(req: Request, ctx: Context) -> Result<Response, Rout
         .get()
         .ok_or("Controller not initialized")?;
 
-    match controller.clone_exercise(exercise_id, &auth_context).await {
+    match controller.clone_exercise(exercise_id, req.body(), &auth_context).await {
         Ok(response_value) => Ok(Response::from_json_value(response_value)),
         Err(e) => {
             error!("Error in clone_exercise handler: {}", e);
@@ -332,13 +516,14 @@ pub async fn delete_exercise(req: Request, ctx: Context) -> Result<Response, Rou
 pub async fn get_workout_analytics(req: Request, ctx: Context) -> Result<Response, RouterError> {
     let auth_context = get_auth_context(&ctx);
     let user_id = req.query("userId").map(|s| s.to_string());
+    let timezone = parse_timezone(req.query("timezone"));
 
     let controller = WORKOUT_ANALYTICS_CONTROLLER
         .get()
         .ok_or("Controller not initialized")?;
 
     match controller
-        .get_workout_analytics(user_id, &auth_context)
+        .get_workout_analytics(user_id, timezone, &auth_context)
         .await
     {
         Ok(response_value) => Ok(Response::from_json_value(response_value)),
@@ -354,13 +539,14 @@ pub async fn get_workout_insights(req: Request, ctx: Context) -> Result<Response
     let user_id = req.query("userId").unwrap_or(&auth_context.user_id);
     let default_time_range = "week".to_string();
     let time_range = req.query("timeRange").unwrap_or(&default_time_range);
+    let timezone = parse_timezone(req.query("timezone"));
 
     let controller = WORKOUT_ANALYTICS_CONTROLLER
         .get()
         .ok_or("Controller not initialized")?;
 
     match controller
-        .get_workout_insights(user_id, time_range, &auth_context)
+        .get_workout_insights(user_id, time_range, timezone, &auth_context)
         .await
     {
         Ok(response_value) => Ok(Response::from_json_value(response_value)),
@@ -374,6 +560,7 @@ pub async fn get_workout_insights(req: Request, ctx: Context) -> Result<Response
 pub async fn get_workout_history(req: Request, ctx: Context) -> Result<Response, RouterError> {
     let auth_context = get_auth_context(&ctx);
     let user_id = req.query("userId").unwrap_or(&auth_context.user_id);
+    let page = req.query("page").and_then(|s| s.parse::<i32>().ok());
     let limit = req.query("limit").and_then(|s| s.parse::<i32>().ok());
 
     let controller = WORKOUT_ANALYTICS_CONTROLLER
@@ -381,7 +568,7 @@ pub async fn get_workout_history(req: Request, ctx: Context) -> Result<Response,
         .ok_or("Controller not initialized")?;
 
     match controller
-        .get_workout_history(user_id, limit, &auth_context)
+        .get_workout_history(user_id, page, limit, &auth_context)
         .await
     {
         Ok(response_value) => Ok(Response::from_json_value(response_value)),
@@ -392,14 +579,35 @@ pub async fn get_workout_history(req: Request, ctx: Context) -> Result<Response,
     }
 }
 
+// ==================== ACHIEVEMENT HANDLERS ====================
+
+pub async fn evaluate_achievements(req: Request, ctx: Context) -> Result<Response, RouterError> {
+    let auth_context = get_auth_context(&ctx);
+    let timezone = parse_timezone(req.query("timezone"));
+    let dry_run = req
+        .query("dryRun")
+        .is_some_and(|value| value == "true" || value == "1");
+
+    let controller = ACHIEVEMENT_CONTROLLER
+        .get()
+        .ok_or("Controller not initialized")?;
+
+    match controller
+        .evaluate_achievements(timezone, dry_run, &auth_context)
+        .await
+    {
+        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Err(e) => {
+            error!("Error in evaluate_achievements handler: {}", e);
+            Ok(Response::internal_error("Failed to process request"))
+        }
+    }
+}
+
 pub async fn log_activity(_req: Request, _ctx: Context) -> Result<Response, RouterError> {
-    // Return 501 Not Implemented
-    Ok(Response::new(501)
-        .json(serde_json::json!({
-            "error": "Not Implemented",
-            "message": "Log activity endpoint not yet implemented"
-        }))
-        .with_cors())
+    Ok(Response::not_implemented(
+        "Log activity endpoint not yet implemented",
+    ))
 }
 
 // ==================== SCHEDULED WORKOUT HANDLERS ====================
@@ -427,13 +635,28 @@ pub async fn schedule_workout_plan(req: Request, ctx: Context) -> Result<Respons
 pub async fn get_scheduled_workouts(req: Request, ctx: Context) -> Result<Response, RouterError> {
     let auth_context = get_auth_context(&ctx);
     let user_id = req.query("userId").map(|s| s.to_string());
+    let filters = ScheduledWorkoutFilters {
+        from: req.query("from").cloned(),
+        to: req.query("to").cloned(),
+        status: req.query("status").cloned(),
+    };
+    let page = match req.page_params() {
+        Ok(page) => page,
+        Err(response) => return Ok(*response),
+    };
 
     let controller = SCHEDULED_WORKOUT_CONTROLLER
         .get()
         .ok_or("Controller not initialized")?;
 
     match controller
-        .get_scheduled_workouts(user_id, &auth_context)
+        .get_scheduled_workouts(
+            user_id,
+            &filters,
+            page,
+            wants_consistent_read(&req),
+            &auth_context,
+        )
         .await
     {
         Ok(response_value) => Ok(Response::from_json_value(response_value)),
@@ -486,3 +709,29 @@ pub async fn delete_scheduled_workout(req: Request, ctx: Context) -> Result<Resp
         }
     }
 }
+
+pub async fn delete_recurring_scheduled_workouts(
+    req: Request,
+    ctx: Context,
+) -> Result<Response, RouterError> {
+    let auth_context = get_auth_context(&ctx);
+    let recurrence_id = req
+        .path_param("recurrenceId")
+        .ok_or("Missing recurrenceId parameter")?;
+    let user_id = req.query("userId").unwrap_or(&auth_context.user_id);
+
+    let controller = SCHEDULED_WORKOUT_CONTROLLER
+        .get()
+        .ok_or("Controller not initialized")?;
+
+    match controller
+        .delete_recurring_scheduled_workouts(user_id, recurrence_id, &auth_context)
+        .await
+    {
+        Ok(response_value) => Ok(Response::from_json_value(response_value)),
+        Err(e) => {
+            error!("Error in delete_recurring_scheduled_workouts handler: {}", e);
+            Ok(Response::internal_error("Failed to process request"))
+        }
+    }
+}
@@ -2,10 +2,14 @@ use async_trait::async_trait;
 use aws_config::meta::region::RegionProviderChain;
 use aws_sdk_dynamodb::Client as DynamoDbClient;
 use aws_sdk_s3::Client as S3Client;
-use lambda_router::{handler, Context, Middleware, Next, Request, Response, Router};
+use lambda_router::{
+    handler, time_phase, CacheMiddleware, Context, Middleware, Next, Request, Response, Router,
+};
 use lambda_runtime::service_fn;
 use lambda_runtime::Error as LambdaError;
 use once_cell::sync::{Lazy, OnceCell};
+use security_middleware::SecurityMiddleware;
+use service_config::ServiceConfig;
 use std::sync::Arc;
 use tracing::{error, info};
 
@@ -16,28 +20,35 @@ mod repository;
 mod service;
 mod utils;
 
-use auth_layer::{AuthLayer, LambdaEvent as AuthLambdaEvent};
+use auth_layer::{
+    build_request_context, with_token_expiry_header, AuthLayer, LambdaEvent as AuthLambdaEvent,
+};
 use controller::{
-    ExerciseController, ScheduledWorkoutController, WorkoutAnalyticsController,
-    WorkoutPlanController, WorkoutSessionController,
+    AchievementController, ExerciseController, ScheduledWorkoutController,
+    WorkoutAnalyticsController, WorkoutPlanController, WorkoutSessionController,
 };
+use models::OneRepMaxFormula;
 use repository::{
-    ExerciseRepository, ScheduledWorkoutRepository, WorkoutAnalyticsRepository,
-    WorkoutPlanRepository, WorkoutSessionRepository,
+    AchievementRepository, ExerciseRepository, ScheduledWorkoutRepository,
+    WorkoutAnalyticsRepository, WorkoutPlanRepository, WorkoutSessionRepository,
 };
 use service::{
-    ExerciseService, ScheduledWorkoutService, WorkoutAnalyticsService, WorkoutPlanService,
-    WorkoutSessionService,
+    achievement_service::achievement_rules_from_json, AchievementService, ExerciseService,
+    ScheduledWorkoutService, WorkoutAnalyticsService, WorkoutPlanService, WorkoutSessionService,
 };
+use utils::id_generator::{default_id_generator, id_generator_from_name};
 
 // Import all handler functions
 use handlers::{
-    clone_exercise, create_exercise, create_workout_plan, create_workout_session, delete_exercise,
-    delete_scheduled_workout, delete_workout_plan, delete_workout_session, get_exercise,
-    get_exercises, get_scheduled_workouts, get_workout_analytics, get_workout_history,
-    get_workout_insights, get_workout_plan, get_workout_plans, get_workout_session,
-    get_workout_sessions, log_activity, schedule_workout_plan, update_exercise,
-    update_scheduled_workout, update_workout_plan, update_workout_session,
+    clone_exercise, complete_workout_session, create_exercise, create_workout_plan,
+    create_workout_session, delete_exercise, delete_recurring_scheduled_workouts,
+    delete_scheduled_workout, delete_workout_plan, delete_workout_session, evaluate_achievements,
+    get_exercise, get_exercises, get_scheduled_workouts, get_workout_analytics,
+    get_workout_history, get_workout_insights, get_workout_plan, get_workout_plans,
+    get_workout_session, get_workout_sessions, log_activity, log_session_sets,
+    migrate_session_keys, restore_workout_plan, restore_workout_session, schedule_workout_plan,
+    search_exercises, start_workout_session, update_exercise, update_scheduled_workout,
+    update_workout_plan, update_workout_session,
 };
 
 // Global clients for cold start optimization
@@ -54,6 +65,7 @@ static WORKOUT_SESSION_CONTROLLER: OnceCell<WorkoutSessionController> = OnceCell
 static EXERCISE_CONTROLLER: OnceCell<ExerciseController> = OnceCell::new();
 static WORKOUT_ANALYTICS_CONTROLLER: OnceCell<WorkoutAnalyticsController> = OnceCell::new();
 static SCHEDULED_WORKOUT_CONTROLLER: OnceCell<ScheduledWorkoutController> = OnceCell::new();
+static ACHIEVEMENT_CONTROLLER: OnceCell<AchievementController> = OnceCell::new();
 
 #[tokio::main]
 async fn main() -> Result<(), LambdaError> {
@@ -68,6 +80,9 @@ async fn main() -> Result<(), LambdaError> {
 
     info!("Starting Workout Service initialization...");
 
+    // Fail fast on missing configuration, before anything is initialized
+    let service_config = ServiceConfig::from_env()?;
+
     // Initialize global clients
     info!("Initializing AWS clients...");
     if DYNAMODB_CLIENT.get().is_none() || S3_CLIENT.get().is_none() {
@@ -76,7 +91,12 @@ async fn main() -> Result<(), LambdaError> {
             .region(region_provider)
             .load()
             .await;
-        let _ = DYNAMODB_CLIENT.set(Arc::new(DynamoDbClient::new(&config)));
+        let mut dynamodb_config = aws_sdk_dynamodb::config::Builder::from(&config);
+        if let Some(endpoint_url) = &service_config.dynamodb_endpoint_url {
+            info!("Using DynamoDB endpoint {}", endpoint_url);
+            dynamodb_config = dynamodb_config.endpoint_url(endpoint_url);
+        }
+        let _ = DYNAMODB_CLIENT.set(Arc::new(DynamoDbClient::from_conf(dynamodb_config.build())));
         let _ = S3_CLIENT.set(Arc::new(S3Client::new(&config)));
         info!("AWS clients initialized successfully");
     }
@@ -87,26 +107,48 @@ async fn main() -> Result<(), LambdaError> {
 
     // Initialize controllers once
     info!("Initializing controllers...");
-    init_controllers();
+    init_controllers(&service_config);
     info!("Controllers initialized successfully");
 
     // Create router
     info!("Creating router...");
     let mut router = Router::new();
 
+    // Write out auth metrics still buffered when the container shuts down
+    router.on_shutdown(|| async { AUTH_LAYER.flush_metrics() });
+
+    // Reject blocked IPs, injection payloads and oversized bodies before authenticating
+    router.use_middleware(SecurityMiddleware::new());
+
     // Add authentication middleware
     router.use_middleware(AuthMiddleware);
 
+    // The exercise library is read far more often than it changes
+    router.use_middleware(
+        CacheMiddleware::new()
+            .route("/api/workouts/exercises", 300)
+            .route("/api/workouts/exercises/search", 300)
+            .route("/api/workouts/exercises/:exerciseId", 300),
+    );
+
     // Workout Plan routes
     router.get("/api/workouts/plans", handler!(get_workout_plans));
     router.post("/api/workouts/plans", handler!(create_workout_plan));
     router.get("/api/workouts/plans/:planId", handler!(get_workout_plan));
     router.put("/api/workouts/plans", handler!(update_workout_plan));
     router.delete("/api/workouts/plans/:planId", handler!(delete_workout_plan));
+    router.post(
+        "/api/workouts/plans/:planId/restore",
+        handler!(restore_workout_plan),
+    );
 
     // Workout Session routes
     router.get("/api/workouts/sessions", handler!(get_workout_sessions));
     router.post("/api/workouts/sessions", handler!(create_workout_session));
+    router.post(
+        "/api/workouts/sessions/start",
+        handler!(start_workout_session),
+    );
     router.get(
         "/api/workouts/sessions/:sessionId",
         handler!(get_workout_session),
@@ -116,9 +158,29 @@ async fn main() -> Result<(), LambdaError> {
         "/api/workouts/sessions/:sessionId",
         handler!(delete_workout_session),
     );
+    router.post(
+        "/api/workouts/sessions/:sessionId/restore",
+        handler!(restore_workout_session),
+    );
+    router.patch(
+        "/api/workouts/sessions/:sessionId/sets",
+        handler!(log_session_sets),
+    );
+    router.post(
+        "/api/workouts/sessions/:sessionId/complete",
+        handler!(complete_workout_session),
+    );
+    router.post(
+        "/api/workouts/sessions/migrate-keys",
+        handler!(migrate_session_keys),
+    );
 
     // Exercise routes
     router.get("/api/workouts/exercises", handler!(get_exercises));
+    router.get(
+        "/api/workouts/exercises/search",
+        handler!(search_exercises),
+    );
     router.post("/api/workouts/exercises", handler!(create_exercise));
     router.get(
         "/api/workouts/exercises/:exerciseId",
@@ -139,6 +201,12 @@ async fn main() -> Result<(), LambdaError> {
     router.get("/api/workouts/insights", handler!(get_workout_insights));
     router.get("/api/workouts/history", handler!(get_workout_history));
 
+    // Achievement routes
+    router.post(
+        "/api/workouts/achievements/evaluate",
+        handler!(evaluate_achievements),
+    );
+
     // Log Activity route
     router.post("/api/workouts/log-activity", handler!(log_activity));
 
@@ -156,6 +224,10 @@ async fn main() -> Result<(), LambdaError> {
         "/api/workouts/schedules/:scheduleId",
         handler!(delete_scheduled_workout),
     );
+    router.delete(
+        "/api/workouts/schedules/recurring/:recurrenceId",
+        handler!(delete_recurring_scheduled_workouts),
+    );
 
     info!("Workout Service initialized successfully");
     info!("Starting Lambda runtime...");
@@ -170,12 +242,12 @@ async fn main() -> Result<(), LambdaError> {
     result
 }
 
-fn init_controllers() {
+fn init_controllers(config: &ServiceConfig) {
     let dynamodb_client = DYNAMODB_CLIENT
         .get()
         .expect("DynamoDB not initialized")
         .as_ref();
-    let table_name = std::env::var("TABLE_NAME").unwrap_or_else(|_| "gymcoach-ai-main".to_string());
+    let table_name = &config.table_name;
 
     // Initialize repositories
     let workout_plan_repository =
@@ -187,13 +259,35 @@ fn init_controllers() {
         WorkoutAnalyticsRepository::new(dynamodb_client.clone(), table_name.clone());
     let scheduled_workout_repository =
         ScheduledWorkoutRepository::new(dynamodb_client.clone(), table_name.clone());
+    let achievement_repository =
+        AchievementRepository::new(dynamodb_client.clone(), table_name.clone());
 
     // Initialize services
-    let workout_plan_service = WorkoutPlanService::new(workout_plan_repository);
-    let workout_session_service = WorkoutSessionService::new(workout_session_repository);
-    let exercise_service = ExerciseService::new(exercise_repository);
-    let workout_analytics_service = WorkoutAnalyticsService::new(workout_analytics_repository);
-    let scheduled_workout_service = ScheduledWorkoutService::new(scheduled_workout_repository);
+    let id_generator = std::env::var("ID_GENERATOR")
+        .ok()
+        .and_then(|name| id_generator_from_name(&name))
+        .unwrap_or_else(default_id_generator);
+    let workout_plan_service = WorkoutPlanService::new(workout_plan_repository)
+        .with_id_generator(id_generator.clone());
+    let exercise_service =
+        ExerciseService::new(exercise_repository).with_id_generator(id_generator.clone());
+    let one_rep_max_formula = std::env::var("ONE_REP_MAX_FORMULA")
+        .ok()
+        .and_then(|name| OneRepMaxFormula::from_name(&name))
+        .unwrap_or_default();
+    let mut achievement_service =
+        AchievementService::new(achievement_repository, workout_analytics_repository.clone())
+            .with_one_rep_max_formula(one_rep_max_formula);
+    if let Ok(rules) = std::env::var("ACHIEVEMENT_RULES") {
+        achievement_service = achievement_service.with_rules(achievement_rules_from_json(&rules));
+    }
+    let workout_session_service = WorkoutSessionService::new(workout_session_repository)
+        .with_id_generator(id_generator.clone())
+        .with_achievement_service(achievement_service.clone());
+    let workout_analytics_service = WorkoutAnalyticsService::new(workout_analytics_repository)
+        .with_one_rep_max_formula(one_rep_max_formula);
+    let scheduled_workout_service = ScheduledWorkoutService::new(scheduled_workout_repository)
+        .with_id_generator(id_generator);
 
     // Initialize and store controllers
     let _ = WORKOUT_PLAN_CONTROLLER.set(WorkoutPlanController::new(workout_plan_service));
@@ -203,6 +297,7 @@ fn init_controllers() {
         .set(WorkoutAnalyticsController::new(workout_analytics_service));
     let _ = SCHEDULED_WORKOUT_CONTROLLER
         .set(ScheduledWorkoutController::new(scheduled_workout_service));
+    let _ = ACHIEVEMENT_CONTROLLER.set(AchievementController::new(achievement_service));
 }
 
 // Authentication Middleware
@@ -212,14 +307,14 @@ struct AuthMiddleware;
 impl Middleware for AuthMiddleware {
     async fn handle(&self, req: Request, next: Next) -> Result<Response, lambda_runtime::Error> {
         // Skip auth for OPTIONS requests (CORS preflight)
-        if req.method == "OPTIONS" {
+        if req.is_preflight() {
             return Ok(Response::cors_preflight());
         }
 
         // Convert to auth event format
         let auth_event = AuthLambdaEvent {
             headers: if !req.headers.is_empty() {
-                Some(req.headers.clone())
+                Some(req.headers.to_hash_map())
             } else {
                 None
             },
@@ -231,10 +326,11 @@ impl Middleware for AuthMiddleware {
                 None
             },
             body: req.body.clone(),
+            required_permissions: req.required_permissions().to_vec(),
         };
 
         // Authenticate request
-        match AUTH_LAYER.authenticate(&auth_event).await {
+        match time_phase("auth", AUTH_LAYER.authenticate(&auth_event)).await {
             Ok(auth_result) => {
                 if !auth_result.is_authorized {
                     return Ok(Response::forbidden(
@@ -245,18 +341,10 @@ impl Middleware for AuthMiddleware {
                 if let Some(auth_context) = auth_result.context {
                     // Create new request with auth info
                     let mut new_req = req.clone();
-                    new_req.context.user_id = Some(auth_context.user_id.clone());
-                    new_req.context.email = Some(auth_context.email.clone());
-
-                    // Store auth_context in custom data
-                    if let Ok(auth_json) = serde_json::to_value(&auth_context) {
-                        new_req
-                            .context
-                            .custom
-                            .insert("auth_context".to_string(), auth_json);
-                    }
-
-                    next(new_req).await
+                    new_req.set_context(build_request_context(req.context, &auth_context));
+
+                    let response = next(new_req).await?;
+                    Ok(with_token_expiry_header(response, auth_result.expires_in))
                 } else {
                     Ok(Response::unauthorized("Authentication required"))
                 }
@@ -1,3 +1,4 @@
+use chrono::{Datelike, Timelike};
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
@@ -21,6 +22,8 @@ pub struct WorkoutPlan {
     pub total_sessions: Option<i32>,         // Calculated field
     pub completed_sessions: Option<i32>,     // Progress tracking
     pub next_scheduled_date: Option<String>, // Next workout date
+    #[serde(default)]
+    pub version: i64, // Incremented on every write, exposed as the ETag
 }
 
 #[derive(Deserialize, Serialize, Validate, Debug, Clone)]
@@ -78,21 +81,80 @@ pub struct Exercise {
     pub name: String,
     pub description: Option<String>,
     pub category: String, // "strength", "cardio", "flexibility", "sports"
+    #[serde(default)]
     pub muscle_groups: Vec<String>,
+    #[serde(default)]
     pub equipment: Vec<String>,
     pub difficulty: String, // "beginner", "intermediate", "advanced"
+    #[serde(default)]
     pub instructions: Vec<String>,
     pub tips: Option<String>,
     pub video_url: Option<String>,
     pub image_url: Option<String>,
     pub created_by: Option<String>, // user_id if user-created, None for system exercises
+    #[serde(default)]
     pub is_system: bool, // true for system exercises, false for user-created
+    #[serde(default)]
     pub tags: Vec<String>, // additional tags like "system", "favorite", "custom"
     pub created_at: String,
     pub updated_at: String,
 }
 
+impl Exercise {
+    /// Fields a clone may never take from the request body; the service sets them
+    const PROTECTED_FIELDS: [&'static str; 5] = ["id", "created_by", "is_system", "created_at", "updated_at"];
 
+    /// This exercise with the fields present in `overrides` replaced. Keys may be snake_case
+    /// or the camelCase used by create/update (`muscleGroups`, `videoUrl`, `imageUrl`);
+    /// identity and ownership fields are ignored
+    pub fn with_overrides(&self, overrides: &serde_json::Value) -> Result<Exercise, serde_json::Error> {
+        let mut merged = serde_json::to_value(self)?;
+        if let (Some(merged), Some(overrides)) = (merged.as_object_mut(), overrides.as_object()) {
+            for (key, value) in overrides {
+                let field = match key.as_str() {
+                    "muscleGroups" => "muscle_groups",
+                    "videoUrl" => "video_url",
+                    "imageUrl" => "image_url",
+                    other => other,
+                };
+                if merged.contains_key(field) && !Self::PROTECTED_FIELDS.contains(&field) {
+                    merged.insert(field.to_string(), value.clone());
+                }
+            }
+        }
+        serde_json::from_value(merged)
+    }
+}
+
+/// Optional server-side filters for exercise search, matched case-insensitively
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct ExerciseSearchFilters {
+    pub category: Option<String>,
+    pub muscle_group: Option<String>,
+    pub equipment: Option<String>,
+}
+
+impl ExerciseSearchFilters {
+    pub fn matches(&self, exercise: &Exercise) -> bool {
+        let contains = |values: &[String], wanted: &str| {
+            values.iter().any(|value| value.eq_ignore_ascii_case(wanted))
+        };
+
+        self.category.as_deref().is_none_or(|c| exercise.category.eq_ignore_ascii_case(c))
+            && self.muscle_group.as_deref().is_none_or(|m| contains(&exercise.muscle_groups, m))
+            && self.equipment.as_deref().is_none_or(|e| contains(&exercise.equipment, e))
+    }
+}
+
+/// Order search results: exact name matches first, then the user's own exercises,
+/// then shorter (closer) names, then alphabetically
+pub fn rank_exercises(query: &str, exercises: &mut [Exercise]) {
+    let query = query.trim().to_lowercase();
+    exercises.sort_by_cached_key(|exercise| {
+        let name = exercise.name.to_lowercase();
+        (name != query, exercise.is_system, name.len(), name)
+    });
+}
 
 #[derive(Deserialize, Serialize, Validate, Debug, Clone)]
 pub struct ScheduledWorkout {
@@ -107,10 +169,145 @@ pub struct ScheduledWorkout {
     pub day: i32,                    // Day number in week
     pub notes: Option<String>,
     pub completed_at: Option<String>,
+    #[serde(default)]
+    pub estimated_duration_minutes: Option<i32>,
+    /// Shared by every occurrence generated from one recurrence spec
+    #[serde(default)]
+    pub recurrence_id: Option<String>,
     pub created_at: String,
     pub updated_at: String,
 }
 
+impl ScheduledWorkout {
+    /// Assumed length of a workout that was scheduled without an estimate
+    pub const DEFAULT_DURATION_MINUTES: i32 = 60;
+
+    /// Start and end of the workout as minutes after midnight, or `None` when the
+    /// time is not `HH:MM`
+    fn time_window(&self) -> Option<(i32, i32)> {
+        let start = chrono::NaiveTime::parse_from_str(&self.scheduled_time, "%H:%M").ok()?;
+        let start = (start.num_seconds_from_midnight() / 60) as i32;
+        let duration = self
+            .estimated_duration_minutes
+            .filter(|minutes| *minutes > 0)
+            .unwrap_or(Self::DEFAULT_DURATION_MINUTES);
+        Some((start, start + duration))
+    }
+
+    /// Whether both workouts are booked on the same day at times that overlap.
+    /// Cancelled schedules free up their slot, and back-to-back workouts do not clash
+    pub fn overlaps(&self, other: &ScheduledWorkout) -> bool {
+        if self.scheduled_date != other.scheduled_date
+            || self.status == "cancelled"
+            || other.status == "cancelled"
+        {
+            return false;
+        }
+        match (self.time_window(), other.time_window()) {
+            (Some((start, end)), Some((other_start, other_end))) => {
+                start < other_end && other_start < end
+            }
+            _ => false,
+        }
+    }
+}
+
+/// "Every Mon/Wed/Fri" style repetition for a scheduled workout, bounded by a number
+/// of occurrences, an end date, or both (whichever comes first)
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecurrenceSpec {
+    pub days_of_week: Vec<chrono::Weekday>,
+    pub count: Option<usize>,
+    /// Last date (inclusive, `YYYY-MM-DD`) an occurrence may fall on
+    pub until: Option<String>,
+}
+
+impl RecurrenceSpec {
+    /// Upper bound on the workouts one spec may generate, to keep the batch write small
+    pub const MAX_OCCURRENCES: usize = 60;
+
+    /// The dates from `start` (inclusive) that fall on one of the chosen weekdays
+    pub fn occurrences(&self, start: chrono::NaiveDate) -> Result<Vec<chrono::NaiveDate>, String> {
+        if self.days_of_week.is_empty() {
+            return Err("Recurrence needs at least one day of the week".to_string());
+        }
+        let until = self
+            .until
+            .as_deref()
+            .map(|date| chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d"))
+            .transpose()
+            .map_err(|_| "Invalid until date, expected YYYY-MM-DD".to_string())?;
+        let limit = match (self.count, until) {
+            (None, None) => return Err("Recurrence needs a count or an until date".to_string()),
+            (Some(0), _) => return Err("Recurrence count must be at least 1".to_string()),
+            (Some(count), _) if count > Self::MAX_OCCURRENCES => {
+                return Err(format!("Recurrence count cannot exceed {}", Self::MAX_OCCURRENCES));
+            }
+            // One past the cap, so an open-ended range that runs over is caught below
+            (count, _) => count.unwrap_or(Self::MAX_OCCURRENCES + 1),
+        };
+
+        let mut dates = Vec::new();
+        let mut date = start;
+        while dates.len() < limit && until.is_none_or(|until| date <= until) {
+            if self.days_of_week.contains(&date.weekday()) {
+                dates.push(date);
+            }
+            date = match date.succ_opt() {
+                Some(next) => next,
+                None => break,
+            };
+        }
+
+        if dates.len() > Self::MAX_OCCURRENCES {
+            return Err(format!("Recurrence cannot generate more than {} workouts", Self::MAX_OCCURRENCES));
+        }
+        if dates.is_empty() {
+            return Err("Recurrence does not produce any workouts".to_string());
+        }
+        Ok(dates)
+    }
+}
+
+/// Optional bounds for listing a user's schedules; dates are inclusive `YYYY-MM-DD`
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct ScheduledWorkoutFilters {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub status: Option<String>,
+}
+
+impl ScheduledWorkoutFilters {
+    pub const STATUSES: [&'static str; 5] = ["scheduled", "completed", "skipped", "missed", "cancelled"];
+
+    /// Reject malformed dates, an inverted range or an unknown status before querying
+    pub fn validate(&self) -> Result<(), String> {
+        let parse = |date: &Option<String>| {
+            date.as_deref()
+                .map(|date| chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d"))
+                .transpose()
+                .map_err(|_| "Invalid date, expected YYYY-MM-DD".to_string())
+        };
+        if let (Some(from), Some(to)) = (parse(&self.from)?, parse(&self.to)?) {
+            if from > to {
+                return Err("'from' must not be after 'to'".to_string());
+            }
+        }
+        if let Some(status) = &self.status {
+            if !Self::STATUSES.contains(&status.as_str()) {
+                return Err(format!("Unknown status '{}', expected one of: {}", status, Self::STATUSES.join(", ")));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether a `YYYY-MM-DD` date falls within `from`..=`to`
+    pub fn includes_date(&self, date: &str) -> bool {
+        self.from.as_deref().is_none_or(|from| date >= from) && self.to.as_deref().is_none_or(|to| date <= to)
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct WorkoutAnalytics {
     pub user_id: String,
@@ -138,6 +335,48 @@ pub struct StrengthProgress {
     pub progress_percentage: f32,
 }
 
+/// Formula used to estimate a one-rep max from a submaximal set
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OneRepMaxFormula {
+    #[default]
+    Epley,
+    Brzycki,
+    Lombardi,
+}
+
+impl OneRepMaxFormula {
+    /// Parse a formula name (case-insensitive), e.g. from the `ONE_REP_MAX_FORMULA` env var
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "epley" => Some(Self::Epley),
+            "brzycki" => Some(Self::Brzycki),
+            "lombardi" => Some(Self::Lombardi),
+            _ => None,
+        }
+    }
+
+    /// Estimate 1RM for a set. Bodyweight sets (no load) have no estimate and
+    /// single-rep sets are returned unchanged.
+    pub fn estimate(&self, weight: f32, reps: i32) -> Option<f32> {
+        if weight <= 0.0 || reps <= 0 {
+            return None;
+        }
+        if reps == 1 {
+            return Some(weight);
+        }
+
+        let reps = reps as f32;
+        match self {
+            Self::Epley => Some(weight * (1.0 + reps / 30.0)),
+            // Brzycki diverges as reps approach 37
+            Self::Brzycki if reps < 37.0 => Some(weight * 36.0 / (37.0 - reps)),
+            Self::Brzycki => None,
+            Self::Lombardi => Some(weight * reps.powf(0.10)),
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone)]
 pub struct BodyMeasurement {
     pub measurement_type: String, // "weight", "body_fat", "muscle_mass", "chest", "waist", etc.
@@ -160,6 +399,19 @@ pub struct Pagination {
     pub total_pages: i32,
 }
 
+impl Pagination {
+    pub fn new(page: i32, limit: i32, total: i32) -> Self {
+        let total_pages = if limit > 0 { (total + limit - 1) / limit } else { 0 };
+
+        Self {
+            page,
+            limit,
+            total,
+            total_pages,
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug)]
 pub struct UploadRequest {
     pub file_type: String,
@@ -187,3 +439,401 @@ pub struct UploadResponse {
     pub key: String,
     pub expires_in: u64,
 }
+
+/// An achievement awarded to a user. Stored in the analytics service's layout so
+/// awarded achievements show up in its achievement lists
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct Achievement {
+    pub id: String,
+    pub user_id: String,
+    pub achievement_type: String, // "streak", "milestone", "pr"
+    pub title: String,
+    pub description: String,
+    pub icon: String,
+    pub category: String,
+    pub rarity: String, // "common", "rare", "epic", "legendary"
+    pub points: i32,
+    pub earned_date: String,
+    pub achieved_at: String,
+    pub created_at: String,
+}
+
+/// What an achievement rule requires, checked against an `AchievementSnapshot`
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AchievementCondition {
+    /// Trained on at least `days` consecutive days, now or at any point before
+    Streak { days: i32 },
+    /// Logged at least `count` workouts in total
+    TotalWorkouts { count: i32 },
+    /// An estimated 1RM of at least `weight` on `exercise` (its id or name, in any
+    /// case), or on any exercise when no exercise is given
+    OneRepMax {
+        weight: f32,
+        #[serde(default)]
+        exercise: Option<String>,
+    },
+}
+
+/// A rule that awards an achievement once its condition is met. The rule id doubles
+/// as the id of the awarded achievement, so each rule is awarded at most once per user
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct AchievementRule {
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub achievement_type: String,
+    #[serde(default = "AchievementRule::default_icon")]
+    pub icon: String,
+    #[serde(default = "AchievementRule::default_category")]
+    pub category: String,
+    #[serde(default = "AchievementRule::default_rarity")]
+    pub rarity: String,
+    #[serde(default = "AchievementRule::default_points")]
+    pub points: i32,
+    pub condition: AchievementCondition,
+}
+
+impl AchievementRule {
+    fn default_icon() -> String {
+        "🏆".to_string()
+    }
+
+    fn default_category() -> String {
+        "general".to_string()
+    }
+
+    fn default_rarity() -> String {
+        "common".to_string()
+    }
+
+    fn default_points() -> i32 {
+        10
+    }
+
+    pub fn is_met(&self, snapshot: &AchievementSnapshot) -> bool {
+        match &self.condition {
+            AchievementCondition::Streak { days } => {
+                snapshot.current_streak.max(snapshot.longest_streak) >= *days
+            }
+            AchievementCondition::TotalWorkouts { count } => snapshot.total_workouts >= *count,
+            AchievementCondition::OneRepMax { weight, exercise } => {
+                snapshot.strength_progress.iter().any(|progress| {
+                    let matches_exercise = exercise.as_ref().is_none_or(|exercise| {
+                        progress.exercise_id.eq_ignore_ascii_case(exercise)
+                            || progress.exercise_name.eq_ignore_ascii_case(exercise)
+                    });
+                    matches_exercise && progress.one_rep_max >= *weight
+                })
+            }
+        }
+    }
+
+    /// The achievement this rule awards to `user_id`, earned at `earned_at`
+    pub fn award(&self, user_id: &str, earned_at: &str) -> Achievement {
+        Achievement {
+            id: self.id.clone(),
+            user_id: user_id.to_string(),
+            achievement_type: self.achievement_type.clone(),
+            title: self.title.clone(),
+            description: self.description.clone(),
+            icon: self.icon.clone(),
+            category: self.category.clone(),
+            rarity: self.rarity.clone(),
+            points: self.points,
+            earned_date: earned_at.to_string(),
+            achieved_at: earned_at.to_string(),
+            created_at: earned_at.to_string(),
+        }
+    }
+}
+
+/// The parts of a user's workout analytics that achievement rules look at
+#[derive(Debug, Clone, Default)]
+pub struct AchievementSnapshot {
+    pub current_streak: i32,
+    pub longest_streak: i32,
+    pub total_workouts: i32,
+    pub strength_progress: Vec<StrengthProgress>,
+}
+
+impl From<WorkoutAnalytics> for AchievementSnapshot {
+    fn from(analytics: WorkoutAnalytics) -> Self {
+        Self {
+            current_streak: analytics.current_streak,
+            longest_streak: analytics.longest_streak,
+            total_workouts: analytics.total_workouts,
+            strength_progress: analytics.strength_progress,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exercise(name: &str, category: &str, muscle_groups: &[&str], is_system: bool) -> Exercise {
+        Exercise {
+            id: name.to_lowercase().replace(' ', "-"),
+            name: name.to_string(),
+            description: None,
+            category: category.to_string(),
+            muscle_groups: muscle_groups.iter().map(|m| m.to_string()).collect(),
+            equipment: vec!["Barbell".to_string()],
+            difficulty: "intermediate".to_string(),
+            instructions: vec![],
+            tips: None,
+            video_url: None,
+            image_url: None,
+            created_by: if is_system { None } else { Some("user-1".to_string()) },
+            is_system,
+            tags: vec![],
+            created_at: "2024-01-01T00:00:00Z".to_string(),
+            updated_at: "2024-01-01T00:00:00Z".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_exercise_search_filters() {
+        let bench = exercise("Bench Press", "strength", &["chest", "triceps"], true);
+        let filters = ExerciseSearchFilters {
+            category: Some("Strength".to_string()),
+            muscle_group: Some("CHEST".to_string()),
+            equipment: Some("barbell".to_string()),
+        };
+        assert!(filters.matches(&bench));
+        assert!(ExerciseSearchFilters::default().matches(&bench));
+
+        let legs = ExerciseSearchFilters {
+            muscle_group: Some("quadriceps".to_string()),
+            ..Default::default()
+        };
+        assert!(!legs.matches(&bench));
+    }
+
+    #[test]
+    fn test_rank_exercises() {
+        let mut results = vec![
+            exercise("Bench Press Incline", "strength", &["chest"], true),
+            exercise("Bench Press", "strength", &["chest"], true),
+            exercise("Bench Dip", "strength", &["triceps"], true),
+            exercise("Bench Press Paused", "strength", &["chest"], false),
+        ];
+
+        rank_exercises("bench press", &mut results);
+
+        let names: Vec<&str> = results.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["Bench Press", "Bench Press Paused", "Bench Dip", "Bench Press Incline"]
+        );
+    }
+
+    #[test]
+    fn test_exercise_with_overrides() {
+        let bench = exercise("Bench Press", "strength", &["chest"], true);
+        let overrides = serde_json::json!({
+            "equipment": ["Dumbbell"],
+            "muscleGroups": ["chest", "shoulders"],
+            "id": "hijacked",
+            "is_system": true,
+            "created_by": "someone-else",
+            "unknown": 1
+        });
+
+        let clone = bench.with_overrides(&overrides).unwrap();
+
+        assert_eq!(clone.equipment, vec!["Dumbbell".to_string()]);
+        assert_eq!(clone.muscle_groups, vec!["chest".to_string(), "shoulders".to_string()]);
+        assert_eq!(clone.name, "Bench Press");
+        assert_eq!(clone.id, bench.id);
+        assert!(clone.is_system);
+        assert_eq!(clone.created_by, None);
+
+        assert!(bench.with_overrides(&serde_json::json!({ "equipment": "Dumbbell" })).is_err());
+    }
+
+    fn scheduled(time: &str, duration: Option<i32>, status: &str) -> ScheduledWorkout {
+        ScheduledWorkout {
+            id: format!("schedule-{}", time),
+            plan_id: "plan-1".to_string(),
+            user_id: "user-1".to_string(),
+            plan_name: "Push Day".to_string(),
+            scheduled_date: "2024-03-04".to_string(),
+            scheduled_time: time.to_string(),
+            status: status.to_string(),
+            week: 1,
+            day: 1,
+            notes: None,
+            completed_at: None,
+            estimated_duration_minutes: duration,
+            recurrence_id: None,
+            created_at: "2024-01-01T00:00:00Z".to_string(),
+            updated_at: "2024-01-01T00:00:00Z".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_scheduled_workout_overlaps() {
+        let morning = scheduled("07:00", Some(45), "scheduled");
+
+        assert!(morning.overlaps(&scheduled("07:30", None, "scheduled")));
+        assert!(scheduled("06:30", None, "scheduled").overlaps(&morning));
+        // Back-to-back sessions and the default hour when no estimate is given
+        assert!(!morning.overlaps(&scheduled("07:45", None, "scheduled")));
+        assert!(!scheduled("06:00", None, "scheduled").overlaps(&morning));
+
+        assert!(!morning.overlaps(&scheduled("07:15", None, "cancelled")));
+        assert!(!morning.overlaps(&scheduled("morning", None, "scheduled")));
+
+        let mut next_day = scheduled("07:00", None, "scheduled");
+        next_day.scheduled_date = "2024-03-05".to_string();
+        assert!(!morning.overlaps(&next_day));
+    }
+
+    #[test]
+    fn test_recurrence_spec_occurrences() {
+        use chrono::{NaiveDate, Weekday};
+
+        let monday = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+        let mwf = |count: Option<usize>, until: Option<&str>| RecurrenceSpec {
+            days_of_week: vec![Weekday::Mon, Weekday::Wed, Weekday::Fri],
+            count,
+            until: until.map(|u| u.to_string()),
+        };
+
+        let dates = mwf(Some(4), None).occurrences(monday).unwrap();
+        let days: Vec<u32> = dates.iter().map(|d| d.day()).collect();
+        assert_eq!(days, vec![4, 6, 8, 11]);
+
+        // Until is inclusive and wins when it comes before the count runs out
+        assert_eq!(mwf(Some(12), Some("2024-03-11")).occurrences(monday).unwrap().len(), 4);
+        assert_eq!(mwf(None, Some("2024-03-31")).occurrences(monday).unwrap().len(), 12);
+
+        assert!(mwf(None, None).occurrences(monday).is_err());
+        assert!(mwf(Some(0), None).occurrences(monday).is_err());
+        assert!(mwf(Some(RecurrenceSpec::MAX_OCCURRENCES + 1), None).occurrences(monday).is_err());
+        assert!(mwf(None, Some("2026-01-01")).occurrences(monday).is_err());
+        assert!(mwf(None, Some("2024-03-01")).occurrences(monday).is_err());
+        assert!(RecurrenceSpec { days_of_week: vec![], count: Some(3), until: None }
+            .occurrences(monday)
+            .is_err());
+
+        let parsed: RecurrenceSpec =
+            serde_json::from_value(serde_json::json!({ "daysOfWeek": ["Mon", "friday"], "count": 2 })).unwrap();
+        assert_eq!(parsed.days_of_week, vec![Weekday::Mon, Weekday::Fri]);
+    }
+
+    #[test]
+    fn test_scheduled_workout_filters_validate() {
+        let march = ScheduledWorkoutFilters {
+            from: Some("2024-03-01".to_string()),
+            to: Some("2024-03-31".to_string()),
+            status: Some("completed".to_string()),
+        };
+        assert!(march.validate().is_ok());
+        assert!(ScheduledWorkoutFilters::default().validate().is_ok());
+
+        let inverted = ScheduledWorkoutFilters { from: march.to.clone(), to: march.from.clone(), status: None };
+        assert!(inverted.validate().is_err());
+        let malformed = ScheduledWorkoutFilters { from: Some("03/01/2024".to_string()), ..Default::default() };
+        assert!(malformed.validate().is_err());
+        let unknown = ScheduledWorkoutFilters { status: Some("done".to_string()), ..Default::default() };
+        assert!(unknown.validate().is_err());
+    }
+
+    #[test]
+    fn test_one_rep_max_formulas() {
+        let epley = OneRepMaxFormula::Epley.estimate(100.0, 10).unwrap();
+        let brzycki = OneRepMaxFormula::Brzycki.estimate(100.0, 10).unwrap();
+        let lombardi = OneRepMaxFormula::Lombardi.estimate(100.0, 10).unwrap();
+
+        assert!((epley - 133.33).abs() < 0.01);
+        assert!((brzycki - 133.33).abs() < 0.01);
+        assert!((lombardi - 125.89).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_one_rep_max_edge_cases() {
+        // Single-rep sets pass through unchanged for every formula
+        for formula in [OneRepMaxFormula::Epley, OneRepMaxFormula::Brzycki, OneRepMaxFormula::Lombardi] {
+            assert_eq!(formula.estimate(140.0, 1), Some(140.0));
+        }
+
+        // Bodyweight sets are excluded
+        assert_eq!(OneRepMaxFormula::Epley.estimate(0.0, 12), None);
+        assert_eq!(OneRepMaxFormula::Brzycki.estimate(60.0, 40), None);
+    }
+
+    #[test]
+    fn test_one_rep_max_formula_from_name() {
+        assert_eq!(OneRepMaxFormula::from_name("Brzycki"), Some(OneRepMaxFormula::Brzycki));
+        assert_eq!(OneRepMaxFormula::from_name("unknown"), None);
+        assert_eq!(OneRepMaxFormula::default(), OneRepMaxFormula::Epley);
+    }
+
+    #[test]
+    fn test_pagination_reflects_total() {
+        // 23 seeded sessions split into pages of 10
+        let sessions: Vec<i32> = (0..23).collect();
+        let pagination = Pagination::new(3, 10, sessions.len() as i32);
+
+        assert_eq!(pagination.total, 23);
+        assert_eq!(pagination.total_pages, 3);
+        assert_eq!(sessions.chunks(10).count() as i32, pagination.total_pages);
+
+        let exact = Pagination::new(1, 5, 20);
+        assert_eq!(exact.total_pages, 4);
+
+        let empty = Pagination::new(1, 10, 0);
+        assert_eq!(empty.total_pages, 0);
+    }
+
+    fn strength(exercise_id: &str, exercise_name: &str, one_rep_max: f32) -> StrengthProgress {
+        StrengthProgress {
+            exercise_id: exercise_id.to_string(),
+            exercise_name: exercise_name.to_string(),
+            one_rep_max,
+            last_updated: "2024-03-01T07:00:00Z".to_string(),
+            progress_percentage: 0.0,
+        }
+    }
+
+    #[test]
+    fn test_achievement_rules_check_snapshot() {
+        let rule = |condition: serde_json::Value| -> AchievementRule {
+            serde_json::from_value(serde_json::json!({
+                "id": "rule",
+                "title": "Rule",
+                "description": "A rule",
+                "achievement_type": "milestone",
+                "condition": condition,
+            }))
+            .unwrap()
+        };
+        let snapshot = AchievementSnapshot {
+            current_streak: 2,
+            longest_streak: 7,
+            total_workouts: 10,
+            strength_progress: vec![strength("bench-press", "Bench Press", 102.5)],
+        };
+
+        // A streak that has since been broken still counts
+        assert!(rule(serde_json::json!({"type": "streak", "days": 7})).is_met(&snapshot));
+        assert!(!rule(serde_json::json!({"type": "streak", "days": 8})).is_met(&snapshot));
+        assert!(rule(serde_json::json!({"type": "total_workouts", "count": 10})).is_met(&snapshot));
+        assert!(!rule(serde_json::json!({"type": "total_workouts", "count": 11})).is_met(&snapshot));
+
+        assert!(rule(serde_json::json!({"type": "one_rep_max", "weight": 100.0})).is_met(&snapshot));
+        assert!(rule(serde_json::json!({"type": "one_rep_max", "weight": 100.0, "exercise": "bench press"}))
+            .is_met(&snapshot));
+        assert!(!rule(serde_json::json!({"type": "one_rep_max", "weight": 100.0, "exercise": "squat"}))
+            .is_met(&snapshot));
+
+        let awarded = rule(serde_json::json!({"type": "streak", "days": 7}))
+            .award("user-1", "2024-03-01T07:00:00Z");
+        assert_eq!(awarded.id, "rule");
+        assert_eq!(awarded.points, 10);
+        assert_eq!(awarded.rarity, "common");
+    }
+}
@@ -0,0 +1,88 @@
+use aws_sdk_dynamodb::{
+    Client as DynamoDbClient,
+    error::ProvideErrorMetadata,
+    operation::{put_item::PutItemInput, query::QueryInput},
+    types::AttributeValue,
+};
+use std::collections::HashSet;
+
+use crate::models::Achievement;
+use crate::repository::DynamoOps;
+use crate::utils::dynamo_item::{to_item, Item, ItemFormat, KeyCase};
+
+/// Achievements live under the analytics service's `ACHIEVEMENT` partition with one
+/// `USER#{userId}#{id}` item each, in its camelCase layout
+#[derive(Clone)]
+pub struct AchievementRepository<C = DynamoDbClient> {
+    client: C,
+    table_name: String,
+}
+
+impl<C: DynamoOps> AchievementRepository<C> {
+    pub fn new(client: C, table_name: String) -> Self {
+        Self { client, table_name }
+    }
+
+    /// Ids of the achievements the user already has, across every page of results
+    pub async fn get_achievement_ids(&self, user_id: &str) -> Result<HashSet<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut ids = HashSet::new();
+        let mut start_key = None;
+
+        loop {
+            let result = self.client
+                .query(
+                    QueryInput::builder()
+                        .table_name(&self.table_name)
+                        .key_condition_expression("PK = :pk AND begins_with(SK, :sk_prefix)")
+                        .expression_attribute_values(":pk", AttributeValue::S("ACHIEVEMENT".to_string()))
+                        .expression_attribute_values(":sk_prefix", AttributeValue::S(format!("USER#{}#", user_id)))
+                        .set_exclusive_start_key(start_key)
+                        .build()?,
+                )
+                .await?;
+
+            ids.extend(
+                result
+                    .items
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(|item| item.get("id").and_then(|v| v.as_s().ok()).cloned()),
+            );
+
+            start_key = result.last_evaluated_key;
+            if start_key.is_none() {
+                return Ok(ids);
+            }
+        }
+    }
+
+    /// Store an achievement unless the user already has one with the same id. Returns
+    /// whether it was written, so concurrent evaluations award it only once
+    pub async fn create_achievement(&self, achievement: &Achievement) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let result = self.client
+            .put_item(
+                PutItemInput::builder()
+                    .table_name(&self.table_name)
+                    .set_item(Some(Self::achievement_item(achievement)?))
+                    .condition_expression("attribute_not_exists(PK)")
+                    .build()?,
+            )
+            .await;
+
+        match result {
+            Ok(_) => Ok(true),
+            Err(e) if e.code() == Some("ConditionalCheckFailedException") => Ok(false),
+            Err(e) => Err(Box::new(e)),
+        }
+    }
+
+    fn achievement_item(achievement: &Achievement) -> Result<Item, serde_dynamo::Error> {
+        let mut item = to_item(achievement, &ItemFormat::new(KeyCase::Camel))?;
+        item.insert("PK".to_string(), AttributeValue::S("ACHIEVEMENT".to_string()));
+        item.insert(
+            "SK".to_string(),
+            AttributeValue::S(format!("USER#{}#{}", achievement.user_id, achievement.id)),
+        );
+        Ok(item)
+    }
+}
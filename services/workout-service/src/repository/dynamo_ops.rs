@@ -0,0 +1,599 @@
+use async_trait::async_trait;
+use aws_sdk_dynamodb::{
+    error::SdkError,
+    operation::{
+        batch_write_item::{BatchWriteItemError, BatchWriteItemInput, BatchWriteItemOutput},
+        delete_item::{DeleteItemError, DeleteItemInput, DeleteItemOutput},
+        get_item::{GetItemError, GetItemInput, GetItemOutput},
+        put_item::{PutItemError, PutItemInput, PutItemOutput},
+        query::{QueryError, QueryInput, QueryOutput},
+        scan::{ScanError, ScanInput, ScanOutput},
+        transact_write_items::{
+            TransactWriteItemsError, TransactWriteItemsInput, TransactWriteItemsOutput,
+        },
+        update_item::{UpdateItemError, UpdateItemInput, UpdateItemOutput},
+    },
+    Client as DynamoDbClient,
+};
+
+/// The DynamoDB operations the repositories use. Repositories are generic over this so
+/// tests can swap in a fake; errors keep the SDK types so conditional-write handling
+/// works the same either way
+#[async_trait]
+pub trait DynamoOps: Send + Sync {
+    async fn get_item(&self, input: GetItemInput) -> Result<GetItemOutput, SdkError<GetItemError>>;
+
+    async fn put_item(&self, input: PutItemInput) -> Result<PutItemOutput, SdkError<PutItemError>>;
+
+    async fn query(&self, input: QueryInput) -> Result<QueryOutput, SdkError<QueryError>>;
+
+    async fn scan(&self, input: ScanInput) -> Result<ScanOutput, SdkError<ScanError>>;
+
+    async fn delete_item(
+        &self,
+        input: DeleteItemInput,
+    ) -> Result<DeleteItemOutput, SdkError<DeleteItemError>>;
+
+    async fn update_item(
+        &self,
+        input: UpdateItemInput,
+    ) -> Result<UpdateItemOutput, SdkError<UpdateItemError>>;
+
+    async fn batch_write_item(
+        &self,
+        input: BatchWriteItemInput,
+    ) -> Result<BatchWriteItemOutput, SdkError<BatchWriteItemError>>;
+
+    async fn transact_write_items(
+        &self,
+        input: TransactWriteItemsInput,
+    ) -> Result<TransactWriteItemsOutput, SdkError<TransactWriteItemsError>>;
+}
+
+#[async_trait]
+impl DynamoOps for DynamoDbClient {
+    async fn get_item(&self, input: GetItemInput) -> Result<GetItemOutput, SdkError<GetItemError>> {
+        self.get_item()
+            .set_table_name(input.table_name)
+            .set_key(input.key)
+            .set_attributes_to_get(input.attributes_to_get)
+            .set_consistent_read(input.consistent_read)
+            .set_return_consumed_capacity(input.return_consumed_capacity)
+            .set_projection_expression(input.projection_expression)
+            .set_expression_attribute_names(input.expression_attribute_names)
+            .send()
+            .await
+    }
+
+    async fn put_item(&self, input: PutItemInput) -> Result<PutItemOutput, SdkError<PutItemError>> {
+        self.put_item()
+            .set_table_name(input.table_name)
+            .set_item(input.item)
+            .set_expected(input.expected)
+            .set_return_values(input.return_values)
+            .set_return_consumed_capacity(input.return_consumed_capacity)
+            .set_return_item_collection_metrics(input.return_item_collection_metrics)
+            .set_conditional_operator(input.conditional_operator)
+            .set_condition_expression(input.condition_expression)
+            .set_expression_attribute_names(input.expression_attribute_names)
+            .set_expression_attribute_values(input.expression_attribute_values)
+            .set_return_values_on_condition_check_failure(
+                input.return_values_on_condition_check_failure,
+            )
+            .send()
+            .await
+    }
+
+    async fn query(&self, input: QueryInput) -> Result<QueryOutput, SdkError<QueryError>> {
+        self.query()
+            .set_table_name(input.table_name)
+            .set_index_name(input.index_name)
+            .set_select(input.select)
+            .set_attributes_to_get(input.attributes_to_get)
+            .set_limit(input.limit)
+            .set_consistent_read(input.consistent_read)
+            .set_key_conditions(input.key_conditions)
+            .set_query_filter(input.query_filter)
+            .set_conditional_operator(input.conditional_operator)
+            .set_scan_index_forward(input.scan_index_forward)
+            .set_exclusive_start_key(input.exclusive_start_key)
+            .set_return_consumed_capacity(input.return_consumed_capacity)
+            .set_projection_expression(input.projection_expression)
+            .set_filter_expression(input.filter_expression)
+            .set_key_condition_expression(input.key_condition_expression)
+            .set_expression_attribute_names(input.expression_attribute_names)
+            .set_expression_attribute_values(input.expression_attribute_values)
+            .send()
+            .await
+    }
+
+    async fn scan(&self, input: ScanInput) -> Result<ScanOutput, SdkError<ScanError>> {
+        self.scan()
+            .set_table_name(input.table_name)
+            .set_index_name(input.index_name)
+            .set_attributes_to_get(input.attributes_to_get)
+            .set_limit(input.limit)
+            .set_select(input.select)
+            .set_scan_filter(input.scan_filter)
+            .set_conditional_operator(input.conditional_operator)
+            .set_exclusive_start_key(input.exclusive_start_key)
+            .set_return_consumed_capacity(input.return_consumed_capacity)
+            .set_total_segments(input.total_segments)
+            .set_segment(input.segment)
+            .set_projection_expression(input.projection_expression)
+            .set_filter_expression(input.filter_expression)
+            .set_expression_attribute_names(input.expression_attribute_names)
+            .set_expression_attribute_values(input.expression_attribute_values)
+            .set_consistent_read(input.consistent_read)
+            .send()
+            .await
+    }
+
+    async fn delete_item(
+        &self,
+        input: DeleteItemInput,
+    ) -> Result<DeleteItemOutput, SdkError<DeleteItemError>> {
+        self.delete_item()
+            .set_table_name(input.table_name)
+            .set_key(input.key)
+            .set_expected(input.expected)
+            .set_conditional_operator(input.conditional_operator)
+            .set_return_values(input.return_values)
+            .set_return_consumed_capacity(input.return_consumed_capacity)
+            .set_return_item_collection_metrics(input.return_item_collection_metrics)
+            .set_condition_expression(input.condition_expression)
+            .set_expression_attribute_names(input.expression_attribute_names)
+            .set_expression_attribute_values(input.expression_attribute_values)
+            .set_return_values_on_condition_check_failure(
+                input.return_values_on_condition_check_failure,
+            )
+            .send()
+            .await
+    }
+
+    async fn update_item(
+        &self,
+        input: UpdateItemInput,
+    ) -> Result<UpdateItemOutput, SdkError<UpdateItemError>> {
+        self.update_item()
+            .set_table_name(input.table_name)
+            .set_key(input.key)
+            .set_attribute_updates(input.attribute_updates)
+            .set_expected(input.expected)
+            .set_conditional_operator(input.conditional_operator)
+            .set_return_values(input.return_values)
+            .set_return_consumed_capacity(input.return_consumed_capacity)
+            .set_return_item_collection_metrics(input.return_item_collection_metrics)
+            .set_update_expression(input.update_expression)
+            .set_condition_expression(input.condition_expression)
+            .set_expression_attribute_names(input.expression_attribute_names)
+            .set_expression_attribute_values(input.expression_attribute_values)
+            .set_return_values_on_condition_check_failure(
+                input.return_values_on_condition_check_failure,
+            )
+            .send()
+            .await
+    }
+
+    async fn batch_write_item(
+        &self,
+        input: BatchWriteItemInput,
+    ) -> Result<BatchWriteItemOutput, SdkError<BatchWriteItemError>> {
+        self.batch_write_item()
+            .set_request_items(input.request_items)
+            .set_return_consumed_capacity(input.return_consumed_capacity)
+            .set_return_item_collection_metrics(input.return_item_collection_metrics)
+            .send()
+            .await
+    }
+
+    async fn transact_write_items(
+        &self,
+        input: TransactWriteItemsInput,
+    ) -> Result<TransactWriteItemsOutput, SdkError<TransactWriteItemsError>> {
+        self.transact_write_items()
+            .set_transact_items(input.transact_items)
+            .set_return_consumed_capacity(input.return_consumed_capacity)
+            .set_return_item_collection_metrics(input.return_item_collection_metrics)
+            .set_client_request_token(input.client_request_token)
+            .send()
+            .await
+    }
+}
+
+/// In-memory stand-in for a table, for repository tests. Items are keyed by `PK`/`SK`;
+/// `query` understands the `PK = :pk AND begins_with(SK, :sk_prefix)` and
+/// `PK = :pk AND SK BETWEEN :sk_from AND :sk_to` shapes the repositories use, plus `Limit`,
+/// paging, `Select::Count` and filters made of `Attr = :value`, `attribute_exists(Attr)`
+/// and `attribute_not_exists(Attr)` clauses joined by `AND`, where `Attr` may be a `#name`.
+/// Conditions enforced are `attribute_not_exists(PK)` on puts, including puts in a
+/// transaction, and filter-shaped conditions on deletes in a transaction, which applies
+/// its puts and deletes all or nothing. Other operations fail as unsupported
+#[cfg(test)]
+pub(crate) mod fake {
+    use super::*;
+    use aws_sdk_dynamodb::config::http::HttpResponse;
+    use aws_sdk_dynamodb::error::ErrorMetadata;
+    use aws_sdk_dynamodb::types::{
+        error::{ConditionalCheckFailedException, TransactionCanceledException},
+        AttributeValue, CancellationReason, Delete, Select,
+    };
+    use std::collections::{BTreeMap, HashMap};
+    use std::sync::Mutex;
+
+    type Item = HashMap<String, AttributeValue>;
+
+    #[derive(Default)]
+    pub(crate) struct FakeDynamo {
+        items: Mutex<BTreeMap<(String, String), Item>>,
+    }
+
+    fn string_attribute(item: &HashMap<String, AttributeValue>, name: &str) -> String {
+        item.get(name)
+            .and_then(|value| value.as_s().ok())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    fn matches_filter(
+        item: &Item,
+        filter: &str,
+        names: &HashMap<String, String>,
+        values: &Item,
+    ) -> bool {
+        let name = |attribute: &str| names.get(attribute).cloned().unwrap_or(attribute.to_string());
+        filter
+            .split(" AND ")
+            .map(str::trim)
+            .filter(|clause| !clause.is_empty())
+            .all(|clause| {
+                if let Some(attribute) = clause
+                    .strip_prefix("attribute_not_exists(")
+                    .and_then(|rest| rest.strip_suffix(')'))
+                {
+                    !item.contains_key(&name(attribute))
+                } else if let Some(attribute) = clause
+                    .strip_prefix("attribute_exists(")
+                    .and_then(|rest| rest.strip_suffix(')'))
+                {
+                    item.contains_key(&name(attribute))
+                } else if let Some((attribute, placeholder)) = clause.split_once(" = ") {
+                    item.get(&name(attribute)) == values.get(placeholder)
+                } else {
+                    true
+                }
+            })
+    }
+
+    /// Whether a transaction's delete has a condition the stored item does not meet
+    fn fails_condition(items: &BTreeMap<(String, String), Item>, delete: &Delete) -> bool {
+        let key = (
+            string_attribute(&delete.key, "PK"),
+            string_attribute(&delete.key, "SK"),
+        );
+        let item = items.get(&key).cloned().unwrap_or_default();
+        let names = delete
+            .expression_attribute_names
+            .clone()
+            .unwrap_or_default();
+        let values = delete
+            .expression_attribute_values
+            .clone()
+            .unwrap_or_default();
+        delete
+            .condition_expression
+            .as_deref()
+            .is_some_and(|condition| !matches_filter(&item, condition, &names, &values))
+    }
+
+    fn must_be_new(condition: Option<&str>) -> bool {
+        condition.is_some_and(|condition| condition.starts_with("attribute_not_exists(PK)"))
+    }
+
+    fn unsupported<E>(operation: &str) -> SdkError<E> {
+        SdkError::construction_failure(format!("FakeDynamo does not support {}", operation))
+    }
+
+    #[async_trait]
+    impl DynamoOps for FakeDynamo {
+        async fn get_item(
+            &self,
+            input: GetItemInput,
+        ) -> Result<GetItemOutput, SdkError<GetItemError>> {
+            let key = input.key.unwrap_or_default();
+            let key = (string_attribute(&key, "PK"), string_attribute(&key, "SK"));
+            let item = self.items.lock().unwrap().get(&key).cloned();
+            Ok(GetItemOutput::builder().set_item(item).build())
+        }
+
+        async fn put_item(
+            &self,
+            input: PutItemInput,
+        ) -> Result<PutItemOutput, SdkError<PutItemError>> {
+            let item = input.item.unwrap_or_default();
+            let key = (string_attribute(&item, "PK"), string_attribute(&item, "SK"));
+            let mut items = self.items.lock().unwrap();
+
+            if must_be_new(input.condition_expression.as_deref()) && items.contains_key(&key) {
+                let error = PutItemError::ConditionalCheckFailedException(
+                    ConditionalCheckFailedException::builder()
+                        .message("The conditional request failed")
+                        .meta(
+                            ErrorMetadata::builder()
+                                .code("ConditionalCheckFailedException")
+                                .build(),
+                        )
+                        .build(),
+                );
+                let response = HttpResponse::new(400.try_into().unwrap(), "".into());
+                return Err(SdkError::service_error(error, response));
+            }
+
+            items.insert(key, item);
+            Ok(PutItemOutput::builder().build())
+        }
+
+        async fn query(&self, input: QueryInput) -> Result<QueryOutput, SdkError<QueryError>> {
+            let values = input.expression_attribute_values.unwrap_or_default();
+            let pk = string_attribute(&values, ":pk");
+            let sk_prefix = string_attribute(&values, ":sk_prefix");
+            let sk_range = (values.contains_key(":sk_from") && values.contains_key(":sk_to"))
+                .then(|| (string_attribute(&values, ":sk_from"), string_attribute(&values, ":sk_to")));
+            let start_after = input
+                .exclusive_start_key
+                .map(|key| string_attribute(&key, "SK"));
+
+            let items = self.items.lock().unwrap();
+            let mut keyed: Vec<(&String, &Item)> = items
+                .iter()
+                .filter(|((item_pk, item_sk), _)| {
+                    *item_pk == pk
+                        && match &sk_range {
+                            Some((from, to)) => (from.as_str()..=to.as_str()).contains(&item_sk.as_str()),
+                            None => item_sk.starts_with(&sk_prefix),
+                        }
+                })
+                .map(|((_, item_sk), item)| (item_sk, item))
+                .collect();
+            if input.scan_index_forward == Some(false) {
+                keyed.reverse();
+            }
+            if let Some(start_after) = start_after {
+                let position = keyed.iter().position(|(sk, _)| **sk == start_after);
+                keyed.drain(..position.map_or(0, |position| position + 1));
+            }
+
+            // Like DynamoDB, the limit counts items read before the filter is applied
+            let limit = input.limit.map_or(keyed.len(), |limit| limit as usize);
+            let last_evaluated_key = (keyed.len() > limit).then(|| {
+                let (sk, item) = keyed[limit - 1];
+                HashMap::from([
+                    ("PK".to_string(), item["PK"].clone()),
+                    ("SK".to_string(), AttributeValue::S(sk.clone())),
+                ])
+            });
+            let filter = input.filter_expression.unwrap_or_default();
+            let names = input.expression_attribute_names.unwrap_or_default();
+            let matched: Vec<Item> = keyed
+                .into_iter()
+                .take(limit)
+                .filter(|(_, item)| matches_filter(item, &filter, &names, &values))
+                .map(|(_, item)| item.clone())
+                .collect();
+
+            let count = matched.len() as i32;
+            let items = (input.select != Some(Select::Count)).then_some(matched);
+            Ok(QueryOutput::builder()
+                .set_items(items)
+                .count(count)
+                .set_last_evaluated_key(last_evaluated_key)
+                .build())
+        }
+
+        async fn scan(&self, _input: ScanInput) -> Result<ScanOutput, SdkError<ScanError>> {
+            Err(unsupported("scan"))
+        }
+
+        async fn delete_item(
+            &self,
+            input: DeleteItemInput,
+        ) -> Result<DeleteItemOutput, SdkError<DeleteItemError>> {
+            let key = input.key.unwrap_or_default();
+            let key = (string_attribute(&key, "PK"), string_attribute(&key, "SK"));
+            self.items.lock().unwrap().remove(&key);
+            Ok(DeleteItemOutput::builder().build())
+        }
+
+        async fn update_item(
+            &self,
+            _input: UpdateItemInput,
+        ) -> Result<UpdateItemOutput, SdkError<UpdateItemError>> {
+            Err(unsupported("update_item"))
+        }
+
+        async fn batch_write_item(
+            &self,
+            _input: BatchWriteItemInput,
+        ) -> Result<BatchWriteItemOutput, SdkError<BatchWriteItemError>> {
+            Err(unsupported("batch_write_item"))
+        }
+
+        async fn transact_write_items(
+            &self,
+            input: TransactWriteItemsInput,
+        ) -> Result<TransactWriteItemsOutput, SdkError<TransactWriteItemsError>> {
+            let transact_items = input.transact_items.unwrap_or_default();
+            if transact_items
+                .iter()
+                .any(|transact_item| transact_item.put.is_none() && transact_item.delete.is_none())
+            {
+                return Err(unsupported("condition checks and updates in transactions"));
+            }
+            let mut items = self.items.lock().unwrap();
+
+            let reasons: Vec<CancellationReason> = transact_items
+                .iter()
+                .map(|transact_item| {
+                    let put_conflict = transact_item.put.as_ref().is_some_and(|put| {
+                        let key = (
+                            string_attribute(&put.item, "PK"),
+                            string_attribute(&put.item, "SK"),
+                        );
+                        must_be_new(put.condition_expression.as_deref()) && items.contains_key(&key)
+                    });
+                    let delete_conflict = transact_item
+                        .delete
+                        .as_ref()
+                        .is_some_and(|delete| fails_condition(&items, delete));
+                    let conflict = put_conflict || delete_conflict;
+                    if conflict {
+                        CancellationReason::builder()
+                            .code("ConditionalCheckFailed")
+                            .message("The conditional request failed")
+                            .build()
+                    } else {
+                        CancellationReason::builder().code("None").build()
+                    }
+                })
+                .collect();
+            if reasons.iter().any(|reason| reason.code() != Some("None")) {
+                let error = TransactWriteItemsError::TransactionCanceledException(
+                    TransactionCanceledException::builder()
+                        .message("Transaction cancelled")
+                        .set_cancellation_reasons(Some(reasons))
+                        .meta(
+                            ErrorMetadata::builder()
+                                .code("TransactionCanceledException")
+                                .build(),
+                        )
+                        .build(),
+                );
+                let response = HttpResponse::new(400.try_into().unwrap(), "".into());
+                return Err(SdkError::service_error(error, response));
+            }
+
+            for transact_item in transact_items {
+                if let Some(put) = transact_item.put {
+                    let key = (
+                        string_attribute(&put.item, "PK"),
+                        string_attribute(&put.item, "SK"),
+                    );
+                    items.insert(key, put.item);
+                } else if let Some(delete) = transact_item.delete {
+                    let key = (
+                        string_attribute(&delete.key, "PK"),
+                        string_attribute(&delete.key, "SK"),
+                    );
+                    items.remove(&key);
+                }
+            }
+            Ok(TransactWriteItemsOutput::builder().build())
+        }
+    }
+}
+
+/// Helpers for running the repositories against DynamoDB Local: start it
+/// (`docker run -p 8000:8000 amazon/dynamodb-local`) and set `DYNAMODB_ENDPOINT_URL`,
+/// the variable `main` reads too. Each test creates its own table, so tests can run
+/// in parallel and leave no shared state
+#[cfg(test)]
+pub(crate) mod local {
+    use aws_sdk_dynamodb::config::{BehaviorVersion, Credentials, Region};
+    use aws_sdk_dynamodb::types::{
+        AttributeDefinition, BillingMode, GlobalSecondaryIndex, KeySchemaElement, KeyType,
+        Projection, ProjectionType, ScalarAttributeType,
+    };
+    use aws_sdk_dynamodb::Client as DynamoDbClient;
+    use service_config::DYNAMODB_ENDPOINT_URL_ENV;
+    use uuid::Uuid;
+
+    /// Region for local clients when `AWS_REGION` is unset. DynamoDB Local keeps a
+    /// separate set of tables per region
+    const DEFAULT_LOCAL_REGION: &str = "us-east-1";
+
+    /// The DynamoDB Local endpoint from `DYNAMODB_ENDPOINT_URL`, if set
+    pub(crate) fn endpoint() -> Option<String> {
+        std::env::var(DYNAMODB_ENDPOINT_URL_ENV)
+            .ok()
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty())
+    }
+
+    /// A client for DynamoDB Local at `endpoint_url`. It signs with fixed dummy
+    /// credentials, so no AWS account or profile is needed
+    pub(crate) fn client(endpoint_url: &str) -> DynamoDbClient {
+        let region =
+            std::env::var("AWS_REGION").unwrap_or_else(|_| DEFAULT_LOCAL_REGION.to_string());
+        let config = aws_sdk_dynamodb::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new(region))
+            .endpoint_url(endpoint_url)
+            .credentials_provider(Credentials::new(
+                "local",
+                "local",
+                None,
+                None,
+                "dynamodb-local",
+            ))
+            .build();
+        DynamoDbClient::from_conf(config)
+    }
+
+    /// Create a table shaped like the main table (`PK`/`SK` keys and the `GSI1` index)
+    /// under a unique name starting with `prefix`, and return the name. Hand it to a
+    /// repository's `new` as its table name
+    pub(crate) async fn create_table(
+        client: &DynamoDbClient,
+        prefix: &str,
+    ) -> anyhow::Result<String> {
+        let table_name = format!("{}-{}", prefix, Uuid::new_v4().simple());
+        let attribute = |name: &str| {
+            AttributeDefinition::builder()
+                .attribute_name(name)
+                .attribute_type(ScalarAttributeType::S)
+                .build()
+        };
+        let key = |name: &str, key_type: KeyType| {
+            KeySchemaElement::builder()
+                .attribute_name(name)
+                .key_type(key_type)
+                .build()
+        };
+
+        client
+            .create_table()
+            .table_name(&table_name)
+            .billing_mode(BillingMode::PayPerRequest)
+            .attribute_definitions(attribute("PK")?)
+            .attribute_definitions(attribute("SK")?)
+            .attribute_definitions(attribute("GSI1PK")?)
+            .attribute_definitions(attribute("GSI1SK")?)
+            .key_schema(key("PK", KeyType::Hash)?)
+            .key_schema(key("SK", KeyType::Range)?)
+            .global_secondary_indexes(
+                GlobalSecondaryIndex::builder()
+                    .index_name("GSI1")
+                    .key_schema(key("GSI1PK", KeyType::Hash)?)
+                    .key_schema(key("GSI1SK", KeyType::Range)?)
+                    .projection(
+                        Projection::builder()
+                            .projection_type(ProjectionType::All)
+                            .build(),
+                    )
+                    .build()?,
+            )
+            .send()
+            .await?;
+
+        Ok(table_name)
+    }
+
+    /// Drop a table made by `create_table`
+    pub(crate) async fn delete_table(
+        client: &DynamoDbClient,
+        table_name: &str,
+    ) -> anyhow::Result<()> {
+        client.delete_table().table_name(table_name).send().await?;
+        Ok(())
+    }
+}
@@ -1,29 +1,37 @@
-use aws_sdk_dynamodb::{Client as DynamoDbClient, types::AttributeValue};
+use aws_sdk_dynamodb::{
+    Client as DynamoDbClient,
+    operation::{delete_item::DeleteItemInput, get_item::GetItemInput, put_item::PutItemInput, query::QueryInput},
+    types::AttributeValue,
+};
 use serde_json::Value;
 use anyhow::Result;
 use tracing::{info, error};
 
 use crate::models::*;
+use crate::repository::{get_or_404, DynamoOps};
+use crate::utils::dynamo_item::{from_item, to_item, CollectionEncoding, Item, ItemFormat, KeyCase};
 
 #[derive(Clone)]
-pub struct ExerciseRepository {
-    client: DynamoDbClient,
+pub struct ExerciseRepository<C = DynamoDbClient> {
+    client: C,
     table_name: String,
 }
 
-impl ExerciseRepository {
-    pub fn new(client: DynamoDbClient, table_name: String) -> Self {
+impl<C: DynamoOps> ExerciseRepository<C> {
+    pub fn new(client: C, table_name: String) -> Self {
         Self { client, table_name }
     }
 
     pub async fn get_exercises(&self) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
         let result = self.client
-            .query()
-            .table_name(&self.table_name)
-            .index_name("GSI1")
-            .key_condition_expression("GSI1PK = :gsi1pk")
-            .expression_attribute_values(":gsi1pk", AttributeValue::S("EXERCISE".to_string()))
-            .send()
+            .query(
+                QueryInput::builder()
+                    .table_name(&self.table_name)
+                    .index_name("GSI1")
+                    .key_condition_expression("GSI1PK = :gsi1pk")
+                    .expression_attribute_values(":gsi1pk", AttributeValue::S("EXERCISE".to_string()))
+                    .build()?,
+            )
             .await?;
         
         let exercises: Vec<Exercise> = result
@@ -41,25 +49,29 @@ impl ExerciseRepository {
         
         // Query 1: Get system exercises using GSI1SK = "SYSTEM"
         let system_result = self.client
-            .query()
-            .table_name(&self.table_name)
-            .index_name("GSI1")
-            .key_condition_expression("GSI1PK = :gsi1pk AND begins_with(GSI1SK, :system_prefix)")
-            .expression_attribute_values(":gsi1pk", AttributeValue::S("EXERCISE".to_string()))
-            .expression_attribute_values(":system_prefix", AttributeValue::S("SYSTEM#".to_string()))
-            .send()
+            .query(
+                QueryInput::builder()
+                    .table_name(&self.table_name)
+                    .index_name("GSI1")
+                    .key_condition_expression("GSI1PK = :gsi1pk AND begins_with(GSI1SK, :system_prefix)")
+                    .expression_attribute_values(":gsi1pk", AttributeValue::S("EXERCISE".to_string()))
+                    .expression_attribute_values(":system_prefix", AttributeValue::S("SYSTEM#".to_string()))
+                    .build()?,
+            )
             .await?;
         
         // Query 2: Get user's exercises if user_id is provided
         if let Some(ref user_id) = user_id {
             let user_result = self.client
-                .query()
-                .table_name(&self.table_name)
-                .index_name("GSI1")
-                .key_condition_expression("GSI1PK = :gsi1pk AND begins_with(GSI1SK, :user_prefix)")
-                .expression_attribute_values(":gsi1pk", AttributeValue::S("EXERCISE".to_string()))
-                .expression_attribute_values(":user_prefix", AttributeValue::S(format!("USER#{}#", user_id)))
-                .send()
+                .query(
+                    QueryInput::builder()
+                        .table_name(&self.table_name)
+                        .index_name("GSI1")
+                        .key_condition_expression("GSI1PK = :gsi1pk AND begins_with(GSI1SK, :user_prefix)")
+                        .expression_attribute_values(":gsi1pk", AttributeValue::S("EXERCISE".to_string()))
+                        .expression_attribute_values(":user_prefix", AttributeValue::S(format!("USER#{}#", user_id)))
+                        .build()?,
+                )
                 .await?;
             
             // Process user exercises
@@ -86,59 +98,59 @@ impl ExerciseRepository {
         Ok(serde_json::to_value(all_exercises)?)
     }
 
-    pub async fn create_exercise(&self, exercise: &Exercise) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-        let mut item = std::collections::HashMap::new();
-        item.insert("PK".to_string(), AttributeValue::S("EXERCISES".to_string()));
-        item.insert("SK".to_string(), AttributeValue::S(format!("EXERCISE#{}", exercise.id)));
-        item.insert("id".to_string(), AttributeValue::S(exercise.id.clone()));
-        item.insert("name".to_string(), AttributeValue::S(exercise.name.clone()));
-        item.insert("category".to_string(), AttributeValue::S(exercise.category.clone()));
-        item.insert("difficulty".to_string(), AttributeValue::S(exercise.difficulty.clone()));
-        item.insert("createdAt".to_string(), AttributeValue::S(exercise.created_at.clone()));
-        item.insert("updatedAt".to_string(), AttributeValue::S(exercise.updated_at.clone()));
+    /// Exercises whose lowercased name starts with `query`: system exercises plus the
+    /// user's own, via `begins_with` on the GSI1SK name patterns
+    pub async fn search_exercises(&self, query: &str, user_id: Option<&str>) -> Result<Vec<Exercise>, Box<dyn std::error::Error + Send + Sync>> {
+        let name_prefix = query.trim().to_lowercase();
         
-        if let Some(description) = &exercise.description {
-            item.insert("description".to_string(), AttributeValue::S(description.clone()));
-        }
-        if let Some(tips) = &exercise.tips {
-            item.insert("tips".to_string(), AttributeValue::S(tips.clone()));
+        let mut exercises = self.query_by_name_prefix(&format!("SYSTEM#{}", name_prefix)).await?;
+        if let Some(user_id) = user_id {
+            exercises.extend(self.query_by_name_prefix(&format!("USER#{}#{}", user_id, name_prefix)).await?);
         }
-        if let Some(video_url) = &exercise.video_url {
-            item.insert("videoUrl".to_string(), AttributeValue::S(video_url.clone()));
-        }
-        if let Some(image_url) = &exercise.image_url {
-            item.insert("imageUrl".to_string(), AttributeValue::S(image_url.clone()));
-        }
-        
-        // Add muscle groups as a list
-        let muscle_groups: Vec<AttributeValue> = exercise.muscle_groups
-            .iter()
-            .map(|group| AttributeValue::S(group.clone()))
-            .collect();
-        item.insert("muscleGroups".to_string(), AttributeValue::L(muscle_groups));
         
-        // Add equipment as a list
-        let equipment: Vec<AttributeValue> = exercise.equipment
-            .iter()
-            .map(|eq| AttributeValue::S(eq.clone()))
-            .collect();
-        item.insert("equipment".to_string(), AttributeValue::L(equipment));
-        
-        // Add instructions as a list
-        let instructions: Vec<AttributeValue> = exercise.instructions
-            .iter()
-            .map(|instruction| AttributeValue::S(instruction.clone()))
-            .collect();
-        item.insert("instructions".to_string(), AttributeValue::L(instructions));
+        Ok(exercises)
+    }
+
+    async fn query_by_name_prefix(&self, sk_prefix: &str) -> Result<Vec<Exercise>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut exercises = Vec::new();
+        let mut exclusive_start_key = None;
         
-        // Add new fields
-        if let Some(created_by) = &exercise.created_by {
-            item.insert("CreatedBy".to_string(), AttributeValue::S(created_by.clone()));
+        loop {
+            let result = self.client
+                .query(
+                    QueryInput::builder()
+                        .table_name(&self.table_name)
+                        .index_name("GSI1")
+                        .key_condition_expression("GSI1PK = :gsi1pk AND begins_with(GSI1SK, :prefix)")
+                        .expression_attribute_values(":gsi1pk", AttributeValue::S("EXERCISE".to_string()))
+                        .expression_attribute_values(":prefix", AttributeValue::S(sk_prefix.to_string()))
+                        .set_exclusive_start_key(exclusive_start_key)
+                        .build()?,
+                )
+                .await?;
+            
+            exercises.extend(
+                result
+                    .items
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter_map(|item| self.parse_exercise_item(item)),
+            );
+            
+            exclusive_start_key = result.last_evaluated_key;
+            if exclusive_start_key.is_none() {
+                break;
+            }
         }
-        item.insert("IsSystem".to_string(), AttributeValue::Bool(exercise.is_system));
         
-        let tags_json = serde_json::to_string(&exercise.tags).unwrap_or_default();
-        item.insert("Tags".to_string(), AttributeValue::S(tags_json));
+        Ok(exercises)
+    }
+
+    pub async fn create_exercise(&self, exercise: &Exercise) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        let mut item = to_item(exercise, &exercise_item_format())?;
+        item.insert("PK".to_string(), AttributeValue::S("EXERCISES".to_string()));
+        item.insert("SK".to_string(), AttributeValue::S(format!("EXERCISE#{}", exercise.id)));
+        item.insert("EntityType".to_string(), AttributeValue::S("EXERCISE".to_string()));
         
         // Add GSI attributes for querying with proper key structure
         item.insert("GSI1PK".to_string(), AttributeValue::S("EXERCISE".to_string()));
@@ -153,108 +165,36 @@ impl ExerciseRepository {
         };
         
         item.insert("GSI1SK".to_string(), AttributeValue::S(gsi1_sk));
-        item.insert("EntityType".to_string(), AttributeValue::S("EXERCISE".to_string()));
-        item.insert("ExerciseId".to_string(), AttributeValue::S(exercise.id.clone()));
-        item.insert("Name".to_string(), AttributeValue::S(exercise.name.clone()));
-        item.insert("Category".to_string(), AttributeValue::S(exercise.category.clone()));
-        item.insert("Difficulty".to_string(), AttributeValue::S(exercise.difficulty.clone()));
-        
-        // Store as JSON strings for consistency with query functions
-        let muscle_groups_json = serde_json::to_string(&exercise.muscle_groups).unwrap_or_default();
-        item.insert("MuscleGroups".to_string(), AttributeValue::S(muscle_groups_json));
-        
-        let equipment_json = serde_json::to_string(&exercise.equipment).unwrap_or_default();
-        item.insert("Equipment".to_string(), AttributeValue::S(equipment_json));
-        
-        let instructions_json = serde_json::to_string(&exercise.instructions).unwrap_or_default();
-        item.insert("Instructions".to_string(), AttributeValue::S(instructions_json));
-        
-        if let Some(description) = &exercise.description {
-            item.insert("Description".to_string(), AttributeValue::S(description.clone()));
-        }
-        if let Some(tips) = &exercise.tips {
-            item.insert("Tips".to_string(), AttributeValue::S(tips.clone()));
-        }
-        if let Some(video_url) = &exercise.video_url {
-            item.insert("VideoUrl".to_string(), AttributeValue::S(video_url.clone()));
-        }
-        if let Some(image_url) = &exercise.image_url {
-            item.insert("ImageUrl".to_string(), AttributeValue::S(image_url.clone()));
-        }
-        
-        item.insert("CreatedAt".to_string(), AttributeValue::S(exercise.created_at.clone()));
-        item.insert("UpdatedAt".to_string(), AttributeValue::S(exercise.updated_at.clone()));
         
         self.client
-            .put_item()
-            .table_name(&self.table_name)
-            .set_item(Some(item))
-            .send()
+            .put_item(
+                PutItemInput::builder()
+                    .table_name(&self.table_name)
+                    .set_item(Some(item))
+                    .build()?,
+            )
             .await?;
         
         Ok(serde_json::to_value(exercise)?)
     }
 
-    pub async fn get_exercise(&self, exercise_id: &str) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+    /// One exercise; `consistent` makes the read strongly consistent
+    pub async fn get_exercise(&self, exercise_id: &str, consistent: bool) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
         let result = self.client
-            .get_item()
-            .table_name(&self.table_name)
-            .key("PK", AttributeValue::S("EXERCISES".to_string()))
-            .key("SK", AttributeValue::S(format!("EXERCISE#{}", exercise_id)))
-            .send()
+            .get_item(
+                GetItemInput::builder()
+                    .table_name(&self.table_name)
+                    .key("PK", AttributeValue::S("EXERCISES".to_string()))
+                    .key("SK", AttributeValue::S(format!("EXERCISE#{}", exercise_id)))
+                    .consistent_read(consistent)
+                    .build()?,
+            )
             .await?;
 
-        if let Some(item) = result.item {
-            let exercise = Exercise {
-                id: item.get("ExerciseId").or_else(|| item.get("id")).and_then(|v| v.as_s().ok()).map_or("", |v| v).to_string(),
-                name: item.get("Name").or_else(|| item.get("name")).and_then(|v| v.as_s().ok()).map_or("", |v| v).to_string(),
-                description: item.get("Description").or_else(|| item.get("description")).and_then(|v| v.as_s().ok()).map(|s| s.to_string()),
-                category: item.get("Category").or_else(|| item.get("category")).and_then(|v| v.as_s().ok()).map_or("", |v| v).to_string(),
-                muscle_groups: item.get("MuscleGroups")
-                    .and_then(|v| v.as_s().ok())
-                    .and_then(|s| serde_json::from_str::<Vec<String>>(s).ok())
-                    .or_else(|| {
-                        item.get("muscleGroups")
-                            .and_then(|v| v.as_l().ok())
-                            .map(|list| list.iter().filter_map(|v| v.as_s().ok().map(|s| s.clone())).collect())
-                    })
-                    .unwrap_or_default(),
-                equipment: item.get("Equipment")
-                    .and_then(|v| v.as_s().ok())
-                    .and_then(|s| serde_json::from_str::<Vec<String>>(s).ok())
-                    .or_else(|| {
-                        item.get("equipment")
-                            .and_then(|v| v.as_l().ok())
-                            .map(|list| list.iter().filter_map(|v| v.as_s().ok().map(|s| s.clone())).collect())
-                    })
-                    .unwrap_or_default(),
-                difficulty: item.get("Difficulty").or_else(|| item.get("difficulty")).and_then(|v| v.as_s().ok()).map_or("beginner", |v| v).to_string(),
-                instructions: item.get("Instructions")
-                    .and_then(|v| v.as_s().ok())
-                    .and_then(|s| serde_json::from_str::<Vec<String>>(s).ok())
-                    .or_else(|| {
-                        item.get("instructions")
-                            .and_then(|v| v.as_l().ok())
-                            .map(|list| list.iter().filter_map(|v| v.as_s().ok().map(|s| s.clone())).collect())
-                    })
-                    .unwrap_or_default(),
-                tips: item.get("Tips").or_else(|| item.get("tips")).and_then(|v| v.as_s().ok()).map(|s| s.to_string()),
-                video_url: item.get("VideoUrl").or_else(|| item.get("videoUrl")).and_then(|v| v.as_s().ok()).map(|s| s.to_string()),
-                image_url: item.get("ImageUrl").or_else(|| item.get("imageUrl")).and_then(|v| v.as_s().ok()).map(|s| s.to_string()),
-                created_by: item.get("CreatedBy").and_then(|v| v.as_s().ok()).map(|s| s.to_string()),
-                is_system: item.get("IsSystem").and_then(|v| v.as_bool().ok()).copied().unwrap_or(false),
-                tags: item.get("Tags")
-                    .and_then(|v| v.as_s().ok())
-                    .and_then(|s| serde_json::from_str::<Vec<String>>(s).ok())
-                    .unwrap_or_default(),
-                created_at: item.get("CreatedAt").or_else(|| item.get("createdAt")).and_then(|v| v.as_s().ok()).map_or("", |v| v).to_string(),
-                updated_at: item.get("updatedAt").and_then(|v| v.as_s().ok()).map_or("", |v| v).to_string(),
-            };
-            
-            Ok(serde_json::to_value(exercise)?)
-        } else {
-            Err("Exercise not found".into())
-        }
+        let item = get_or_404(result.item, "Exercise", exercise_id)?;
+        let exercise = exercise_from_item(item)?;
+        
+        Ok(serde_json::to_value(exercise)?)
     }
 
     pub async fn update_exercise(&self, exercise: &Exercise) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
@@ -263,11 +203,13 @@ impl ExerciseRepository {
 
     pub async fn delete_exercise(&self, exercise_id: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         self.client
-            .delete_item()
-            .table_name(&self.table_name)
-            .key("PK", AttributeValue::S("EXERCISES".to_string()))
-            .key("SK", AttributeValue::S(format!("EXERCISE#{}", exercise_id)))
-            .send()
+            .delete_item(
+                DeleteItemInput::builder()
+                    .table_name(&self.table_name)
+                    .key("PK", AttributeValue::S("EXERCISES".to_string()))
+                    .key("SK", AttributeValue::S(format!("EXERCISE#{}", exercise_id)))
+                    .build()?,
+            )
             .await?;
         
         Ok(())
@@ -275,35 +217,134 @@ impl ExerciseRepository {
 
     // Helper function to parse exercise items from DynamoDB
     fn parse_exercise_item(&self, item: std::collections::HashMap<String, AttributeValue>) -> Option<Exercise> {
-        Some(Exercise {
-            id: item.get("ExerciseId")?.as_s().ok()?.clone(),
-            name: item.get("Name")?.as_s().ok()?.clone(),
-            description: item.get("Description").and_then(|v| v.as_s().ok()).map(|s| s.clone()),
-            category: item.get("Category")?.as_s().ok()?.clone(),
-            muscle_groups: item.get("MuscleGroups")
-                .and_then(|v| v.as_s().ok())
-                .and_then(|s| serde_json::from_str::<Vec<String>>(s).ok())
-                .unwrap_or_default(),
-            equipment: item.get("Equipment")
-                .and_then(|v| v.as_s().ok())
-                .and_then(|s| serde_json::from_str::<Vec<String>>(s).ok())
-                .unwrap_or_default(),
-            difficulty: item.get("Difficulty")?.as_s().ok()?.clone(),
-            instructions: item.get("Instructions")
-                .and_then(|v| v.as_s().ok())
-                .and_then(|s| serde_json::from_str::<Vec<String>>(s).ok())
-                .unwrap_or_default(),
-            tips: item.get("Tips").and_then(|v| v.as_s().ok()).map(|s| s.clone()),
-            video_url: item.get("VideoUrl").and_then(|v| v.as_s().ok()).map(|s| s.clone()),
-            image_url: item.get("ImageUrl").and_then(|v| v.as_s().ok()).map(|s| s.clone()),
-            created_by: item.get("CreatedBy").and_then(|v| v.as_s().ok()).map(|s| s.clone()),
-            is_system: item.get("IsSystem").and_then(|v| v.as_bool().ok()).copied().unwrap_or(false),
-            tags: item.get("Tags")
-                .and_then(|v| v.as_s().ok())
-                .and_then(|s| serde_json::from_str::<Vec<String>>(s).ok())
-                .unwrap_or_default(),
-            created_at: item.get("CreatedAt")?.as_s().ok()?.clone(),
-            updated_at: item.get("UpdatedAt")?.as_s().ok()?.clone(),
-        })
+        exercise_from_item(item)
+            .map_err(|e| error!("Skipping malformed exercise item: {}", e))
+            .ok()
+    }
+}
+
+/// Exercise items use PascalCase attributes with JSON-encoded lists and the id under
+/// `ExerciseId`, the same layout the seed scripts and the AI service write. Items from
+/// older versions of this service carry camelCase attributes with native lists instead
+/// (or alongside), which are read wherever the PascalCase attribute is missing
+fn exercise_item_format() -> ItemFormat {
+    ItemFormat::new(KeyCase::Pascal)
+        .collections(CollectionEncoding::JsonString)
+        .rename("id", "ExerciseId")
+        .legacy_case(KeyCase::Camel)
+}
+
+/// Single-get and list both parse through here so they agree on every item
+fn exercise_from_item(item: Item) -> Result<Exercise, serde_dynamo::Error> {
+    from_item(item, &exercise_item_format())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn s(value: &str) -> AttributeValue {
+        AttributeValue::S(value.to_string())
+    }
+
+    fn list(values: &[&str]) -> AttributeValue {
+        AttributeValue::L(values.iter().map(|v| s(v)).collect())
+    }
+
+    fn keys() -> Item {
+        let mut item = Item::new();
+        item.insert("PK".to_string(), s("EXERCISES"));
+        item.insert("SK".to_string(), s("EXERCISE#ex-1"));
+        item.insert("GSI1PK".to_string(), s("EXERCISE"));
+        item.insert("GSI1SK".to_string(), s("SYSTEM#bench press"));
+        item
+    }
+
+    fn assert_bench_press(exercise: &Exercise) {
+        assert_eq!(exercise.id, "ex-1");
+        assert_eq!(exercise.name, "Bench Press");
+        assert_eq!(exercise.muscle_groups, vec!["chest", "triceps"]);
+        assert_eq!(exercise.equipment, vec!["barbell", "bench"]);
+        assert_eq!(exercise.instructions, vec!["Lower the bar", "Press up"]);
+        assert_eq!(exercise.created_at, "2024-01-01T00:00:00Z");
+        assert_eq!(exercise.updated_at, "2024-01-02T00:00:00Z");
+    }
+
+    #[test]
+    fn test_reads_camel_case_item_with_native_lists() {
+        let mut item = keys();
+        item.insert("id".to_string(), s("ex-1"));
+        item.insert("name".to_string(), s("Bench Press"));
+        item.insert("category".to_string(), s("strength"));
+        item.insert("difficulty".to_string(), s("intermediate"));
+        item.insert("muscleGroups".to_string(), list(&["chest", "triceps"]));
+        item.insert("equipment".to_string(), list(&["barbell", "bench"]));
+        item.insert("instructions".to_string(), list(&["Lower the bar", "Press up"]));
+        item.insert("createdAt".to_string(), s("2024-01-01T00:00:00Z"));
+        item.insert("updatedAt".to_string(), s("2024-01-02T00:00:00Z"));
+        
+        assert_bench_press(&exercise_from_item(item).unwrap());
+    }
+
+    #[test]
+    fn test_reads_dual_written_item() {
+        // Older create_exercise wrote camelCase lists plus PascalCase JSON strings
+        let mut item = keys();
+        item.insert("id".to_string(), s("ex-1"));
+        item.insert("muscleGroups".to_string(), list(&["chest", "triceps"]));
+        item.insert("equipment".to_string(), list(&["barbell", "bench"]));
+        item.insert("updatedAt".to_string(), s("2024-01-02T00:00:00Z"));
+        item.insert("ExerciseId".to_string(), s("ex-1"));
+        item.insert("Name".to_string(), s("Bench Press"));
+        item.insert("Category".to_string(), s("strength"));
+        item.insert("Difficulty".to_string(), s("intermediate"));
+        item.insert("MuscleGroups".to_string(), s(r#"["chest","triceps"]"#));
+        item.insert("Equipment".to_string(), s(r#"["barbell","bench"]"#));
+        item.insert("Instructions".to_string(), s(r#"["Lower the bar","Press up"]"#));
+        item.insert("IsSystem".to_string(), AttributeValue::Bool(true));
+        item.insert("Tags".to_string(), s(r#"["system"]"#));
+        item.insert("CreatedAt".to_string(), s("2024-01-01T00:00:00Z"));
+        
+        let exercise = exercise_from_item(item).unwrap();
+        assert_bench_press(&exercise);
+        assert!(exercise.is_system);
+        assert_eq!(exercise.tags, vec!["system"]);
+    }
+
+    #[test]
+    fn test_reads_pascal_case_item_with_native_lists() {
+        let mut item = keys();
+        item.insert("ExerciseId".to_string(), s("ex-1"));
+        item.insert("Name".to_string(), s("Bench Press"));
+        item.insert("Category".to_string(), s("strength"));
+        item.insert("Difficulty".to_string(), s("intermediate"));
+        item.insert("MuscleGroups".to_string(), list(&["chest", "triceps"]));
+        item.insert("Equipment".to_string(), list(&["barbell", "bench"]));
+        item.insert("Instructions".to_string(), list(&["Lower the bar", "Press up"]));
+        item.insert("CreatedAt".to_string(), s("2024-01-01T00:00:00Z"));
+        item.insert("UpdatedAt".to_string(), s("2024-01-02T00:00:00Z"));
+        
+        assert_bench_press(&exercise_from_item(item).unwrap());
+    }
+
+    #[test]
+    fn test_writes_pascal_case_json_strings_only() {
+        let mut item = keys();
+        item.insert("id".to_string(), s("ex-1"));
+        item.insert("name".to_string(), s("Bench Press"));
+        item.insert("category".to_string(), s("strength"));
+        item.insert("difficulty".to_string(), s("intermediate"));
+        item.insert("muscleGroups".to_string(), list(&["chest", "triceps"]));
+        item.insert("createdAt".to_string(), s("2024-01-01T00:00:00Z"));
+        item.insert("updatedAt".to_string(), s("2024-01-02T00:00:00Z"));
+        let exercise = exercise_from_item(item).unwrap();
+        
+        let written = to_item(&exercise, &exercise_item_format()).unwrap();
+        assert_eq!(written["ExerciseId"], s("ex-1"));
+        assert_eq!(written["MuscleGroups"], s(r#"["chest","triceps"]"#));
+        assert_eq!(written["Equipment"], s("[]"));
+        assert!(!written.contains_key("muscleGroups"));
+        assert!(!written.contains_key("id"));
+        assert_eq!(exercise_from_item(written).unwrap().muscle_groups, vec!["chest", "triceps"]);
     }
 }
@@ -1,11 +1,834 @@
+pub mod achievement_repository;
+pub mod dynamo_ops;
 pub mod exercise_repository;
 pub mod scheduled_workout_repository;
 pub mod workout_analytics_repository;
 pub mod workout_plan_repository;
 pub mod workout_session_repository;
 
+pub use achievement_repository::AchievementRepository;
+pub use dynamo_ops::DynamoOps;
 pub use exercise_repository::ExerciseRepository;
 pub use scheduled_workout_repository::ScheduledWorkoutRepository;
 pub use workout_analytics_repository::WorkoutAnalyticsRepository;
 pub use workout_plan_repository::WorkoutPlanRepository;
 pub use workout_session_repository::WorkoutSessionRepository;
+
+use aws_sdk_dynamodb::operation::transact_write_items::{
+    TransactWriteItemsError, TransactWriteItemsInput,
+};
+use aws_sdk_dynamodb::types::{AttributeValue, CancellationReason, TransactWriteItem};
+use chrono::{DateTime, Duration, Utc};
+use serde_json::json;
+use std::collections::HashMap;
+
+use crate::utils::constants::SOFT_DELETE_RETENTION_DAYS;
+use crate::utils::error::{ServiceError, ServiceResult};
+
+/// Attribute stamped on soft-deleted plans and sessions
+pub const DELETED_AT_ATTRIBUTE: &str = "DeletedAt";
+/// Filter that hides soft-deleted items from queries and scans
+pub const NOT_DELETED_FILTER: &str = "attribute_not_exists(DeletedAt)";
+
+/// Unwrap a looked-up item, or fail with a `NotFound` naming the entity and its id
+pub fn get_or_404<T>(item: Option<T>, entity: &str, id: &str) -> ServiceResult<T> {
+    item.ok_or_else(|| ServiceError::NotFound(format!("{} '{}' not found", entity, id)))
+}
+
+/// Drop an item that has been soft-deleted, so lookups treat it as missing
+pub fn without_deleted(
+    item: Option<HashMap<String, AttributeValue>>,
+) -> Option<HashMap<String, AttributeValue>> {
+    item.filter(|item| !item.contains_key(DELETED_AT_ATTRIBUTE))
+}
+
+/// The `ttl` (epoch seconds) at which DynamoDB purges an item soft-deleted at
+/// `deleted_at`; until then it can be restored
+pub fn soft_delete_expiry(deleted_at: DateTime<Utc>) -> i64 {
+    (deleted_at + Duration::days(SOFT_DELETE_RETENTION_DAYS)).timestamp()
+}
+
+/// Most items a single TransactWriteItems call accepts
+pub const TRANSACT_WRITE_LIMIT: usize = 100;
+
+/// Apply `items` in one TransactWriteItems call, so either all of them are written or
+/// none are. A cancelled transaction comes back as a `ServiceError` explaining why
+/// (see `transaction_cancelled_error`); other SDK errors pass through unchanged
+pub async fn transact_write<C: DynamoOps>(
+    client: &C,
+    items: Vec<TransactWriteItem>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    if items.is_empty() {
+        return Ok(());
+    }
+    if items.len() > TRANSACT_WRITE_LIMIT {
+        return Err(ServiceError::Validation(
+            format!(
+                "Cannot write {} items in one transaction (at most {})",
+                items.len(),
+                TRANSACT_WRITE_LIMIT
+            ),
+            None,
+        )
+        .into());
+    }
+
+    let input = TransactWriteItemsInput::builder()
+        .set_transact_items(Some(items))
+        .build()?;
+    match client.transact_write_items(input).await {
+        Ok(_) => Ok(()),
+        Err(err) => match err.as_service_error() {
+            Some(TransactWriteItemsError::TransactionCanceledException(cancelled)) => {
+                Err(transaction_cancelled_error(cancelled.cancellation_reasons()).into())
+            }
+            _ => Err(Box::new(err)),
+        },
+    }
+}
+
+/// Explain a cancelled transaction from its per-item reasons, which line up with the
+/// items sent (`None` marks the ones that were fine). A failed condition or a
+/// competing transaction on the same items is a `Conflict` the client can act on;
+/// throttling and anything else is a `Database` error. The failing items' positions
+/// and codes go in the details
+pub fn transaction_cancelled_error(reasons: &[CancellationReason]) -> ServiceError {
+    let failed: Vec<(usize, &str, Option<&str>)> = reasons
+        .iter()
+        .enumerate()
+        .filter_map(|(index, reason)| {
+            reason
+                .code()
+                .filter(|code| *code != "None")
+                .map(|code| (index, code, reason.message()))
+        })
+        .collect();
+    let details = json!({
+        "reasons": failed
+            .iter()
+            .map(|(index, code, message)| json!({ "index": index, "code": code, "message": message }))
+            .collect::<Vec<_>>()
+    });
+    let any = |wanted: &str| failed.iter().any(|(_, code, _)| *code == wanted);
+
+    if any("ConditionalCheckFailed") {
+        ServiceError::Conflict(
+            format!(
+                "{} of {} items failed their write condition; nothing was written",
+                failed.len(),
+                reasons.len()
+            ),
+            Some(details),
+        )
+    } else if any("TransactionConflict") {
+        ServiceError::Conflict(
+            "Another request is changing the same items; nothing was written, try again"
+                .to_string(),
+            Some(details),
+        )
+    } else {
+        let codes: Vec<&str> = failed.iter().map(|(_, code, _)| *code).collect();
+        ServiceError::Database(format!("Transaction cancelled: {}", codes.join(", ")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::dynamo_ops::fake::FakeDynamo;
+    use super::dynamo_ops::local;
+    use super::*;
+    use crate::models::{
+        Achievement, ScheduledWorkout, ScheduledWorkoutFilters, WorkoutPlan, WorkoutSession,
+    };
+    use aws_sdk_dynamodb::operation::put_item::PutItemInput;
+
+    #[test]
+    fn test_get_or_404_returns_present_item() {
+        assert_eq!(get_or_404(Some(42), "Workout plan", "plan-1").unwrap(), 42);
+    }
+
+    #[test]
+    fn test_get_or_404_names_missing_id() {
+        match get_or_404::<i32>(None, "Workout plan", "plan-1") {
+            Err(ServiceError::NotFound(message)) => {
+                assert_eq!(message, "Workout plan 'plan-1' not found");
+            }
+            other => panic!("expected NotFound, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_without_deleted_hides_soft_deleted_items() {
+        let live = HashMap::from([("PK".to_string(), AttributeValue::S("USER#1".to_string()))]);
+        let mut deleted = live.clone();
+        deleted.insert(
+            DELETED_AT_ATTRIBUTE.to_string(),
+            AttributeValue::S("2024-01-01T00:00:00Z".to_string()),
+        );
+
+        assert!(without_deleted(Some(live)).is_some());
+        assert!(without_deleted(Some(deleted)).is_none());
+        assert!(without_deleted(None).is_none());
+    }
+
+    #[test]
+    fn test_soft_delete_expiry_adds_retention_window() {
+        let deleted_at = DateTime::parse_from_rfc3339("2024-01-01T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+
+        assert_eq!(
+            soft_delete_expiry(deleted_at) - deleted_at.timestamp(),
+            SOFT_DELETE_RETENTION_DAYS * 24 * 60 * 60
+        );
+    }
+
+    fn workout_plan(id: &str) -> WorkoutPlan {
+        WorkoutPlan {
+            id: id.to_string(),
+            user_id: "user-1".to_string(),
+            name: "Push Pull Legs".to_string(),
+            description: None,
+            difficulty: "intermediate".to_string(),
+            duration_weeks: 8,
+            frequency_per_week: 3,
+            exercises: Vec::new(),
+            created_at: "2024-01-01T00:00:00Z".to_string(),
+            updated_at: "2024-01-01T00:00:00Z".to_string(),
+            is_active: true,
+            tags: Some(vec!["strength".to_string()]),
+            rating: None,
+            is_template: None,
+            total_sessions: None,
+            completed_sessions: None,
+            next_scheduled_date: None,
+            version: 1,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_workout_plan_repository_with_fake_table() {
+        let repository = WorkoutPlanRepository::new(FakeDynamo::default(), "test".to_string());
+        repository
+            .create_workout_plan(&workout_plan("plan-1"))
+            .await
+            .unwrap();
+        repository
+            .create_workout_plan(&workout_plan("plan-2"))
+            .await
+            .unwrap();
+
+        let plan = repository
+            .get_workout_plan("user-1", "plan-1", false)
+            .await
+            .unwrap();
+        assert_eq!(plan["name"], "Push Pull Legs");
+        assert_eq!(plan["tags"][0], "strength");
+
+        let plans = repository
+            .get_workout_plans(Some("user-1".to_string()), false)
+            .await
+            .unwrap();
+        assert_eq!(plans.as_array().unwrap().len(), 2);
+
+        let duplicate = repository
+            .create_workout_plan(&workout_plan("plan-1"))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            duplicate.downcast_ref::<ServiceError>(),
+            Some(ServiceError::Conflict(..))
+        ));
+
+        let missing = repository
+            .get_workout_plan("user-1", "plan-3", false)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            missing.downcast_ref::<ServiceError>(),
+            Some(ServiceError::NotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DynamoDB Local and DYNAMODB_ENDPOINT_URL; run with --ignored"]
+    async fn test_workout_plan_repository_with_dynamodb_local() {
+        let endpoint = local::endpoint()
+            .expect("Set DYNAMODB_ENDPOINT_URL to a running DynamoDB Local endpoint");
+        let client = local::client(&endpoint);
+        let table_name = local::create_table(&client, "workout-plans").await.unwrap();
+        let repository = WorkoutPlanRepository::new(client.clone(), table_name.clone());
+
+        repository
+            .create_workout_plan(&workout_plan("plan-1"))
+            .await
+            .unwrap();
+        let plan = repository
+            .get_workout_plan("user-1", "plan-1", true)
+            .await
+            .unwrap();
+        assert_eq!(plan["name"], "Push Pull Legs");
+
+        let duplicate = repository
+            .create_workout_plan(&workout_plan("plan-1"))
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            duplicate.downcast_ref::<ServiceError>(),
+            Some(ServiceError::Conflict(..))
+        ));
+
+        local::delete_table(&client, &table_name).await.unwrap();
+    }
+
+    fn workout_session(id: &str, started_at: &str) -> WorkoutSession {
+        WorkoutSession {
+            id: id.to_string(),
+            user_id: "user-1".to_string(),
+            workout_plan_id: None,
+            name: "Leg Day".to_string(),
+            started_at: started_at.to_string(),
+            completed_at: None,
+            duration_minutes: None,
+            exercises: Vec::new(),
+            notes: None,
+            rating: None,
+            created_at: started_at.to_string(),
+            updated_at: started_at.to_string(),
+        }
+    }
+
+    fn session_ids(sessions: &serde_json::Value) -> Vec<&str> {
+        sessions
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|session| session["id"].as_str().unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_sessions_read_newest_first_across_key_forms() {
+        // A session stored before time-prefixed keys, under `SESSION#{id}`
+        let table = FakeDynamo::default();
+        let legacy = [
+            ("PK", "USER#user-1"),
+            ("SK", "SESSION#legacy"),
+            ("SessionId", "legacy"),
+            ("UserId", "user-1"),
+            ("Name", "Leg Day"),
+            ("StartedAt", "2024-03-02T07:00:00Z"),
+            ("CreatedAt", "2024-03-02T07:00:00Z"),
+            ("UpdatedAt", "2024-03-02T07:00:00Z"),
+        ];
+        table
+            .put_item(
+                PutItemInput::builder()
+                    .table_name("test")
+                    .set_item(Some(
+                        legacy
+                            .iter()
+                            .map(|(name, value)| {
+                                (name.to_string(), AttributeValue::S(value.to_string()))
+                            })
+                            .collect(),
+                    ))
+                    .build()
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let repository = WorkoutSessionRepository::new(table, "test".to_string());
+        for (id, started_at) in [
+            ("s1", "2024-03-01T07:00:00Z"),
+            ("s3", "2024-03-03T07:00:00Z"),
+            ("s4", "2024-03-04T07:00:00Z"),
+        ] {
+            repository
+                .create_workout_session(&workout_session(id, started_at))
+                .await
+                .unwrap();
+        }
+        let user = || Some("user-1".to_string());
+
+        let recent = repository
+            .get_workout_sessions(user(), None, Some(2), false)
+            .await
+            .unwrap();
+        assert_eq!(session_ids(&recent), vec!["s4", "s3"]);
+
+        // A full listing includes the legacy session
+        let all = repository
+            .get_workout_sessions(user(), None, None, false)
+            .await
+            .unwrap();
+        assert_eq!(session_ids(&all), vec!["s4", "s3", "legacy", "s1"]);
+        let recent = repository
+            .get_workout_sessions(user(), None, Some(3), false)
+            .await
+            .unwrap();
+        assert_eq!(session_ids(&recent), vec!["s4", "s3", "legacy"]);
+
+        // Reading leaves the legacy key, which sorts ahead of every dated one, in place
+        let first_by_key = || async {
+            let (sessions, _) = repository
+                .get_workout_sessions_page("user-1", None, 1, None, false)
+                .await
+                .unwrap();
+            sessions[0].id.clone()
+        };
+        assert_eq!(first_by_key().await, "legacy");
+
+        // The migration moves it to a time-prefixed key, once
+        assert_eq!(
+            repository.migrate_legacy_sessions("user-1").await.unwrap(),
+            1
+        );
+        assert_eq!(
+            repository.migrate_legacy_sessions("user-1").await.unwrap(),
+            0
+        );
+        assert_eq!(first_by_key().await, "s4");
+        let all = repository
+            .get_workout_sessions(user(), None, None, false)
+            .await
+            .unwrap();
+        assert_eq!(session_ids(&all), vec!["s4", "s3", "legacy", "s1"]);
+
+        // Changing the start time re-keys the session rather than duplicating it
+        repository
+            .update_workout_session(&workout_session("s1", "2024-03-05T07:00:00Z"))
+            .await
+            .unwrap();
+        let all = repository
+            .get_workout_sessions(user(), None, None, false)
+            .await
+            .unwrap();
+        assert_eq!(session_ids(&all), vec!["s1", "s4", "s3", "legacy"]);
+    }
+
+    #[tokio::test]
+    async fn test_sessions_page_by_sort_key_and_count() {
+        let repository = WorkoutSessionRepository::new(FakeDynamo::default(), "test".to_string());
+        for day in 1..=5 {
+            let mut session =
+                workout_session(&format!("s{}", day), &format!("2024-03-0{}T07:00:00Z", day));
+            if day % 2 == 0 {
+                session.workout_plan_id = Some("plan-1".to_string());
+            }
+            repository.create_workout_session(&session).await.unwrap();
+        }
+
+        let mut pages = Vec::new();
+        let mut start_after = None;
+        loop {
+            let (sessions, next_start) = repository
+                .get_workout_sessions_page("user-1", None, 2, start_after.as_deref(), false)
+                .await
+                .unwrap();
+            pages.push(
+                sessions
+                    .iter()
+                    .map(|session| session.id.clone())
+                    .collect::<Vec<_>>(),
+            );
+            start_after = next_start;
+            if start_after.is_none() {
+                break;
+            }
+        }
+        assert_eq!(pages, vec![vec!["s5", "s4"], vec!["s3", "s2"], vec!["s1"]]);
+
+        // The filter can drop items a page read; the page still fills up
+        let (plan_sessions, next_start) = repository
+            .get_workout_sessions_page("user-1", Some("plan-1"), 2, None, false)
+            .await
+            .unwrap();
+        let ids: Vec<&str> = plan_sessions
+            .iter()
+            .map(|session| session.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["s4", "s2"]);
+        assert!(next_start.is_some());
+
+        assert_eq!(
+            repository
+                .count_workout_sessions("user-1", None)
+                .await
+                .unwrap(),
+            5
+        );
+        assert_eq!(
+            repository
+                .count_workout_sessions("user-1", Some("plan-1"))
+                .await
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            repository
+                .count_workout_sessions("user-2", None)
+                .await
+                .unwrap(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_only_in_progress_sessions_take_sets_or_complete() {
+        let repository = WorkoutSessionRepository::new(FakeDynamo::default(), "test".to_string());
+        let mut finished = workout_session("done", "2024-03-01T07:00:00Z");
+        finished.completed_at = Some("2024-03-01T08:00:00Z".to_string());
+        repository.create_workout_session(&finished).await.unwrap();
+
+        let error = repository
+            .append_session_sets("user-1", "done", &[])
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ServiceError>(),
+            Some(ServiceError::Conflict(..))
+        ));
+        let error = repository
+            .complete_workout_session("user-1", "done", Utc::now(), None, None)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ServiceError>(),
+            Some(ServiceError::Conflict(..))
+        ));
+
+        // Only the owner's partition is searched
+        let error = repository
+            .append_session_sets("user-2", "done", &[])
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ServiceError>(),
+            Some(ServiceError::NotFound(..))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_sessions_are_found_by_id_across_key_forms() {
+        let table = FakeDynamo::default();
+        let legacy = [
+            ("PK", "USER#user-1"),
+            ("SK", "SESSION#legacy"),
+            ("SessionId", "legacy"),
+            ("UserId", "user-1"),
+            ("Name", "Leg Day"),
+            ("StartedAt", "2024-03-02T07:00:00Z"),
+            ("CompletedAt", "2024-03-02T08:00:00Z"),
+            ("UpdatedAt", "2024-03-02T08:00:00Z"),
+        ];
+        table
+            .put_item(
+                PutItemInput::builder()
+                    .table_name("test")
+                    .set_item(Some(
+                        legacy
+                            .iter()
+                            .map(|(name, value)| {
+                                (name.to_string(), AttributeValue::S(value.to_string()))
+                            })
+                            .collect(),
+                    ))
+                    .build()
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let repository = WorkoutSessionRepository::new(table, "test".to_string());
+        let mut finished = workout_session("done", "2024-03-01T07:00:00Z");
+        finished.completed_at = Some("2024-03-01T08:00:00Z".to_string());
+        repository.create_workout_session(&finished).await.unwrap();
+
+        // Finding a completed session fails with a conflict rather than a 404
+        let is_found = |error: Box<dyn std::error::Error + Send + Sync>| {
+            matches!(
+                error.downcast_ref::<ServiceError>(),
+                Some(ServiceError::Conflict(..))
+            )
+        };
+        for id in ["done", "legacy"] {
+            let error = repository
+                .append_session_sets("user-1", id, &[])
+                .await
+                .unwrap_err();
+            assert!(is_found(error), "{} not found", id);
+        }
+
+        // Moving either to a new key moves its pointer along
+        finished.started_at = "2024-03-05T07:00:00Z".to_string();
+        repository.update_workout_session(&finished).await.unwrap();
+        assert_eq!(
+            repository.migrate_legacy_sessions("user-1").await.unwrap(),
+            1
+        );
+        for id in ["done", "legacy"] {
+            let error = repository
+                .append_session_sets("user-1", id, &[])
+                .await
+                .unwrap_err();
+            assert!(is_found(error), "{} not found", id);
+        }
+
+        // Pointer items never show up as sessions
+        assert_eq!(
+            repository
+                .count_workout_sessions("user-1", None)
+                .await
+                .unwrap(),
+            2
+        );
+    }
+
+    fn scheduled_workout(id: &str, scheduled_date: &str) -> ScheduledWorkout {
+        ScheduledWorkout {
+            id: id.to_string(),
+            plan_id: "plan-1".to_string(),
+            user_id: "user-1".to_string(),
+            plan_name: "Push Pull Legs".to_string(),
+            scheduled_date: scheduled_date.to_string(),
+            scheduled_time: "07:00".to_string(),
+            status: "scheduled".to_string(),
+            week: 1,
+            day: 1,
+            notes: None,
+            completed_at: None,
+            estimated_duration_minutes: None,
+            recurrence_id: Some("series-1".to_string()),
+            created_at: "2024-01-01T00:00:00Z".to_string(),
+            updated_at: "2024-01-01T00:00:00Z".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_recurring_schedules_are_written_all_or_nothing() {
+        let repository = ScheduledWorkoutRepository::new(FakeDynamo::default(), "test".to_string());
+        let filters = ScheduledWorkoutFilters::default();
+        repository
+            .create_scheduled_workout(&scheduled_workout("s2", "2024-03-08"))
+            .await
+            .unwrap();
+
+        // The second occurrence collides with an existing item, so none are written
+        let series = [
+            scheduled_workout("s1", "2024-03-01"),
+            scheduled_workout("s2", "2024-03-08"),
+            scheduled_workout("s3", "2024-03-15"),
+        ];
+        let error = repository
+            .create_scheduled_workouts(&series)
+            .await
+            .unwrap_err();
+        match error.downcast_ref::<ServiceError>() {
+            Some(ServiceError::Conflict(_, Some(details))) => {
+                assert_eq!(details["reasons"][0]["index"], 1);
+                assert_eq!(details["reasons"][0]["code"], "ConditionalCheckFailed");
+            }
+            other => panic!("expected Conflict, got {:?}", other),
+        }
+        let stored = repository
+            .get_scheduled_workouts("user-1", &filters, true)
+            .await
+            .unwrap();
+        assert_eq!(stored.len(), 1);
+
+        repository
+            .create_scheduled_workouts(&[series[0].clone(), series[2].clone()])
+            .await
+            .unwrap();
+        let stored = repository
+            .get_scheduled_workouts("user-1", &filters, true)
+            .await
+            .unwrap();
+        assert_eq!(stored.len(), 3);
+    }
+
+    fn schedule_ids(schedules: &[ScheduledWorkout]) -> Vec<&str> {
+        schedules
+            .iter()
+            .map(|schedule| schedule.id.as_str())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_schedules_are_read_by_date_range_across_key_forms() {
+        // A schedule stored before date-prefixed keys, under `SCHEDULE#{id}`
+        let table = FakeDynamo::default();
+        let mut legacy: HashMap<String, AttributeValue> = [
+            ("PK", "USER#user-1"),
+            ("SK", "SCHEDULE#0legacy"),
+            ("ScheduleId", "0legacy"),
+            ("PlanId", "plan-1"),
+            ("UserId", "user-1"),
+            ("PlanName", "Push Pull Legs"),
+            ("ScheduledDate", "2024-03-10"),
+            ("ScheduledTime", "07:00"),
+            ("Status", "scheduled"),
+            ("CreatedAt", "2024-01-01T00:00:00Z"),
+            ("UpdatedAt", "2024-01-01T00:00:00Z"),
+        ]
+        .iter()
+        .map(|(name, value)| (name.to_string(), AttributeValue::S(value.to_string())))
+        .collect();
+        legacy.insert("Week".to_string(), AttributeValue::N("1".to_string()));
+        legacy.insert("Day".to_string(), AttributeValue::N("7".to_string()));
+        table
+            .put_item(
+                PutItemInput::builder()
+                    .table_name("test")
+                    .set_item(Some(legacy))
+                    .build()
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let repository = ScheduledWorkoutRepository::new(table, "test".to_string());
+        for (id, scheduled_date) in [
+            ("s1", "2024-03-01"),
+            ("s2", "2024-03-08"),
+            ("s3", "2024-03-15"),
+            ("s4", "2024-04-01"),
+        ] {
+            repository
+                .create_scheduled_workout(&scheduled_workout(id, scheduled_date))
+                .await
+                .unwrap();
+        }
+        let march = ScheduledWorkoutFilters {
+            from: Some("2024-03-01".to_string()),
+            to: Some("2024-03-31".to_string()),
+            status: None,
+        };
+
+        // Both ends of the range are inclusive, whatever the time of day
+        let first_week = ScheduledWorkoutFilters {
+            to: Some("2024-03-08".to_string()),
+            ..march.clone()
+        };
+        let stored = repository
+            .get_scheduled_workouts("user-1", &first_week, false)
+            .await
+            .unwrap();
+        assert_eq!(schedule_ids(&stored), vec!["s1", "s2"]);
+
+        // A full listing includes the legacy schedule in date order and re-keys it
+        let stored = repository
+            .get_scheduled_workouts("user-1", &ScheduledWorkoutFilters::default(), false)
+            .await
+            .unwrap();
+        assert_eq!(schedule_ids(&stored), vec!["s1", "s2", "0legacy", "s3", "s4"]);
+        let stored = repository
+            .get_scheduled_workouts("user-1", &march, false)
+            .await
+            .unwrap();
+        assert_eq!(schedule_ids(&stored), vec!["s1", "s2", "0legacy", "s3"]);
+
+        let mut pages = Vec::new();
+        let mut start_after = None;
+        loop {
+            let (schedules, next_start) = repository
+                .get_scheduled_workouts_page("user-1", &march, 2, start_after.as_deref(), false)
+                .await
+                .unwrap();
+            pages.push(schedule_ids(&schedules).join(","));
+            start_after = next_start;
+            if start_after.is_none() {
+                break;
+            }
+        }
+        assert_eq!(pages, vec!["s1,s2", "0legacy,s3"]);
+        assert_eq!(
+            repository
+                .count_scheduled_workouts("user-1", &march)
+                .await
+                .unwrap(),
+            4
+        );
+
+        // Lookups by id find the date-prefixed key
+        repository
+            .delete_scheduled_workout("user-1", "0legacy")
+            .await
+            .unwrap();
+        let error = repository
+            .delete_scheduled_workout("user-1", "0legacy")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ServiceError>(),
+            Some(ServiceError::NotFound(..))
+        ));
+        let completed = ScheduledWorkoutFilters {
+            status: Some("completed".to_string()),
+            ..march
+        };
+        assert_eq!(
+            repository
+                .count_scheduled_workouts("user-1", &completed)
+                .await
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_transaction_cancellation_reasons_map_to_errors() {
+        let reason = |code: &str| CancellationReason::builder().code(code).build();
+
+        let conflict =
+            transaction_cancelled_error(&[reason("None"), reason("TransactionConflict")]);
+        assert!(matches!(conflict, ServiceError::Conflict(..)));
+
+        let throttled = transaction_cancelled_error(&[reason("ThrottlingError"), reason("None")]);
+        match throttled {
+            ServiceError::Database(message) => assert!(message.contains("ThrottlingError")),
+            other => panic!("expected Database, got {:?}", other),
+        }
+    }
+
+    fn achievement(user_id: &str, id: &str) -> Achievement {
+        Achievement {
+            id: id.to_string(),
+            user_id: user_id.to_string(),
+            achievement_type: "streak".to_string(),
+            title: "One Week Streak".to_string(),
+            description: "Trained seven days in a row".to_string(),
+            icon: "🔥".to_string(),
+            category: "consistency".to_string(),
+            rarity: "rare".to_string(),
+            points: 25,
+            earned_date: "2024-03-01T07:00:00Z".to_string(),
+            achieved_at: "2024-03-01T07:00:00Z".to_string(),
+            created_at: "2024-03-01T07:00:00Z".to_string(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_achievements_are_created_once_per_user() {
+        let repository = AchievementRepository::new(FakeDynamo::default(), "test".to_string());
+
+        assert!(repository
+            .create_achievement(&achievement("user-1", "streak-7"))
+            .await
+            .unwrap());
+        assert!(!repository
+            .create_achievement(&achievement("user-1", "streak-7"))
+            .await
+            .unwrap());
+        assert!(repository
+            .create_achievement(&achievement("user-10", "streak-7"))
+            .await
+            .unwrap());
+
+        // `user-1` must not pick up `user-10`'s achievements through the key prefix
+        let ids = repository.get_achievement_ids("user-1").await.unwrap();
+        assert_eq!(ids.len(), 1);
+        assert!(ids.contains("streak-7"));
+    }
+}
@@ -1,25 +1,101 @@
-use aws_sdk_dynamodb::{Client as DynamoDbClient, types::AttributeValue};
+use aws_sdk_dynamodb::{
+    Client as DynamoDbClient,
+    operation::{
+        batch_write_item::BatchWriteItemInput, delete_item::DeleteItemInput, put_item::PutItemInput,
+        query::{builders::QueryInputBuilder, QueryInput}, update_item::UpdateItemInput,
+    },
+    types::{AttributeValue, DeleteRequest, Put, Select, TransactWriteItem, WriteRequest},
+};
 use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Duration;
 use anyhow::Result;
 use tracing::{info, error};
 
 use crate::models::*;
+use crate::repository::{get_or_404, transact_write, DynamoOps};
+use crate::utils::update_expression::UpdateExpressionBuilder;
+
+/// Most items a single BatchWriteItem call accepts
+const BATCH_WRITE_LIMIT: usize = 25;
+const BATCH_WRITE_MAX_ATTEMPTS: u32 = 5;
+const SCHEDULE_SK_PREFIX: &str = "SCHEDULE#";
+/// Sorts after every character of a date, time or id, so it closes a key range
+const SCHEDULE_SK_END: char = '~';
+
+type Item = HashMap<String, AttributeValue>;
+
+/// `SCHEDULE#{date}#{time}#{id}`. Schedules sort by date and time within a user's partition,
+/// so a date range is a key condition instead of a filter over every schedule. Schedules
+/// written before this used `SCHEDULE#{id}`; reads move them over to the new key
+pub fn schedule_sort_key(scheduled_date: &str, scheduled_time: &str, schedule_id: &str) -> String {
+    format!("{}{}#{}#{}", SCHEDULE_SK_PREFIX, scheduled_date, scheduled_time, schedule_id)
+}
+
+fn is_legacy_schedule_key(sk: &str) -> bool {
+    sk.strip_prefix(SCHEDULE_SK_PREFIX)
+        .is_some_and(|rest| !rest.contains('#'))
+}
+
+/// The inclusive SK range from the start of `from` to the end of `to`. An open end runs to
+/// the first or last schedule
+fn schedule_key_range(filters: &ScheduledWorkoutFilters) -> (String, String) {
+    let from = format!("{}{}", SCHEDULE_SK_PREFIX, filters.from.as_deref().unwrap_or_default());
+    let to = match &filters.to {
+        Some(to) => format!("{}{}#{}", SCHEDULE_SK_PREFIX, to, SCHEDULE_SK_END),
+        None => format!("{}{}", SCHEDULE_SK_PREFIX, SCHEDULE_SK_END),
+    };
+    (from, to)
+}
 
 #[derive(Clone)]
-pub struct ScheduledWorkoutRepository {
-    client: DynamoDbClient,
+pub struct ScheduledWorkoutRepository<C = DynamoDbClient> {
+    client: C,
     table_name: String,
 }
 
-impl ScheduledWorkoutRepository {
-    pub fn new(client: DynamoDbClient, table_name: String) -> Self {
+impl<C: DynamoOps> ScheduledWorkoutRepository<C> {
+    pub fn new(client: C, table_name: String) -> Self {
         Self { client, table_name }
     }
 
     pub async fn create_scheduled_workout(&self, scheduled_workout: &ScheduledWorkout) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-        let item = [
+        self.client
+            .put_item(
+                PutItemInput::builder()
+                    .table_name(&self.table_name)
+                    .set_item(Some(Self::scheduled_workout_item(scheduled_workout)))
+                    .build()?,
+            )
+            .await?;
+        
+        Ok(serde_json::to_value(scheduled_workout)?)
+    }
+
+    /// Write every occurrence of a recurring schedule in one transaction, so a failure
+    /// never leaves part of the series behind. Occurrences are capped well below the
+    /// transaction limit (see `RecurrenceSpec::MAX_OCCURRENCES`)
+    pub async fn create_scheduled_workouts(&self, scheduled_workouts: &[ScheduledWorkout]) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        let items = scheduled_workouts
+            .iter()
+            .map(|scheduled_workout| {
+                Put::builder()
+                    .table_name(&self.table_name)
+                    .set_item(Some(Self::scheduled_workout_item(scheduled_workout)))
+                    .condition_expression("attribute_not_exists(PK)")
+                    .build()
+                    .map(|put| TransactWriteItem::builder().put(put).build())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        transact_write(&self.client, items).await?;
+        
+        Ok(serde_json::to_value(scheduled_workouts)?)
+    }
+
+    fn scheduled_workout_item(scheduled_workout: &ScheduledWorkout) -> HashMap<String, AttributeValue> {
+        let mut item: HashMap<String, AttributeValue> = [
             ("PK".to_string(), AttributeValue::S(format!("USER#{}", scheduled_workout.user_id))),
-            ("SK".to_string(), AttributeValue::S(format!("SCHEDULE#{}", scheduled_workout.id))),
+            ("SK".to_string(), AttributeValue::S(schedule_sort_key(&scheduled_workout.scheduled_date, &scheduled_workout.scheduled_time, &scheduled_workout.id))),
             ("GSI1PK".to_string(), AttributeValue::S(format!("SCHEDULE_DATE#{}", scheduled_workout.scheduled_date))),
             ("GSI1SK".to_string(), AttributeValue::S(format!("USER#{}", scheduled_workout.user_id))),
             ("ScheduleId".to_string(), AttributeValue::S(scheduled_workout.id.clone())),
@@ -34,102 +110,360 @@ impl ScheduledWorkoutRepository {
             ("CreatedAt".to_string(), AttributeValue::S(scheduled_workout.created_at.clone())),
             ("UpdatedAt".to_string(), AttributeValue::S(scheduled_workout.updated_at.clone())),
         ].into_iter().collect();
-        
-        self.client
-            .put_item()
+        if let Some(minutes) = scheduled_workout.estimated_duration_minutes {
+            item.insert("EstimatedDurationMinutes".to_string(), AttributeValue::N(minutes.to_string()));
+        }
+        if let Some(recurrence_id) = &scheduled_workout.recurrence_id {
+            item.insert("RecurrenceId".to_string(), AttributeValue::S(recurrence_id.clone()));
+        }
+        item
+    }
+
+    /// Send write requests in batches of 25 (the BatchWriteItem limit), retrying
+    /// whatever DynamoDB reports as unprocessed with a short backoff
+    async fn batch_write(&self, requests: Vec<WriteRequest>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        for chunk in requests.chunks(BATCH_WRITE_LIMIT) {
+            let mut pending = chunk.to_vec();
+            let mut attempt = 0;
+            while !pending.is_empty() {
+                if attempt == BATCH_WRITE_MAX_ATTEMPTS {
+                    return Err(anyhow::anyhow!("{} scheduled workout writes were left unprocessed", pending.len()).into());
+                }
+                if attempt > 0 {
+                    tokio::time::sleep(Duration::from_millis(50 << attempt)).await;
+                }
+                attempt += 1;
+
+                let result = self.client
+                    .batch_write_item(
+                        BatchWriteItemInput::builder()
+                            .request_items(&self.table_name, pending)
+                            .build()?,
+                    )
+                    .await?;
+                pending = result
+                    .unprocessed_items
+                    .and_then(|mut unprocessed| unprocessed.remove(&self.table_name))
+                    .unwrap_or_default();
+            }
+        }
+        Ok(())
+    }
+
+    /// A user's schedules in date/time order. The date range is read as a range of sort keys.
+    /// Legacy-keyed schedules found along the way are migrated and kept when their date is
+    /// in range, so a listing without a range migrates all of them. `consistent` makes the
+    /// reads strongly consistent
+    pub async fn get_scheduled_workouts(&self, user_id: &str, filters: &ScheduledWorkoutFilters, consistent: bool) -> Result<Vec<ScheduledWorkout>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut scheduled_workouts = Vec::new();
+        let mut legacy_items = Vec::new();
+        let mut exclusive_start_key = None;
+        loop {
+            let query = self
+                .schedules_query(user_id, filters, consistent)
+                .set_exclusive_start_key(exclusive_start_key.take());
+            let result = self.client.query(query.build()?).await?;
+
+            for item in result.items.unwrap_or_default() {
+                let Some(scheduled_workout) = Self::scheduled_workout_from_item(&item) else {
+                    continue;
+                };
+                if item.get("SK").and_then(|v| v.as_s().ok()).is_some_and(|sk| is_legacy_schedule_key(sk)) {
+                    legacy_items.push(item);
+                    if !filters.includes_date(&scheduled_workout.scheduled_date) {
+                        continue;
+                    }
+                }
+                scheduled_workouts.push(scheduled_workout);
+            }
+
+            exclusive_start_key = result.last_evaluated_key;
+            if exclusive_start_key.is_none() {
+                break;
+            }
+        }
+
+        // Migrate once the query is done, so it never reads a schedule under both keys.
+        // Only migrated schedules can be out of key order
+        if !legacy_items.is_empty() {
+            for item in legacy_items {
+                self.migrate_legacy_schedule(item).await?;
+            }
+            scheduled_workouts.sort_by(|a, b| {
+                (&a.scheduled_date, &a.scheduled_time).cmp(&(&b.scheduled_date, &b.scheduled_time))
+            });
+        }
+        Ok(scheduled_workouts)
+    }
+
+    /// One page of a user's schedules in date/time order, starting after the `start_after`
+    /// sort key. Returns the schedules and, when more remain, the sort key of the last one
+    /// read to pass back as the next page's start. Legacy-keyed schedules read along the way
+    /// are migrated and left out; their new key puts them on a later page when it sorts after
+    /// this one
+    pub async fn get_scheduled_workouts_page(&self, user_id: &str, filters: &ScheduledWorkoutFilters, limit: usize, start_after: Option<&str>, consistent: bool) -> Result<(Vec<ScheduledWorkout>, Option<String>), Box<dyn std::error::Error + Send + Sync>> {
+        let pk = AttributeValue::S(format!("USER#{}", user_id));
+        let mut scheduled_workouts = Vec::new();
+        let mut exclusive_start_key = start_after.map(|sk| {
+            HashMap::from([
+                ("PK".to_string(), pk.clone()),
+                ("SK".to_string(), AttributeValue::S(sk.to_string())),
+            ])
+        });
+
+        // Read no more than the page still needs, so the last key read is exactly where
+        // the next page starts even when the filter drops items
+        loop {
+            let query = self
+                .schedules_query(user_id, filters, consistent)
+                .limit((limit - scheduled_workouts.len()) as i32)
+                .set_exclusive_start_key(exclusive_start_key.take());
+            let result = self.client.query(query.build()?).await?;
+
+            for item in result.items.unwrap_or_default() {
+                if item.get("SK").and_then(|v| v.as_s().ok()).is_some_and(|sk| is_legacy_schedule_key(sk)) {
+                    self.migrate_legacy_schedule(item).await?;
+                } else if let Some(scheduled_workout) = Self::scheduled_workout_from_item(&item) {
+                    scheduled_workouts.push(scheduled_workout);
+                }
+            }
+
+            exclusive_start_key = result.last_evaluated_key;
+            if scheduled_workouts.len() >= limit || exclusive_start_key.is_none() {
+                break;
+            }
+        }
+
+        let next_start =
+            exclusive_start_key.and_then(|key| key.get("SK").and_then(|v| v.as_s().ok()).cloned());
+        Ok((scheduled_workouts, next_start))
+    }
+
+    /// How many schedules match `filters`, counted with `Select::Count` so no items are
+    /// read back
+    pub async fn count_scheduled_workouts(&self, user_id: &str, filters: &ScheduledWorkoutFilters) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        let mut total = 0;
+        let mut exclusive_start_key = None;
+        loop {
+            let query = self
+                .schedules_query(user_id, filters, false)
+                .select(Select::Count)
+                .set_exclusive_start_key(exclusive_start_key.take());
+            let result = self.client.query(query.build()?).await?;
+
+            total += result.count.max(0) as u64;
+            exclusive_start_key = result.last_evaluated_key;
+            if exclusive_start_key.is_none() {
+                return Ok(total);
+            }
+        }
+    }
+
+    /// A date-ordered query over the sort keys `filters` covers, with the status as a filter
+    fn schedules_query(&self, user_id: &str, filters: &ScheduledWorkoutFilters, consistent: bool) -> QueryInputBuilder {
+        let (from, to) = schedule_key_range(filters);
+        let query = QueryInput::builder()
             .table_name(&self.table_name)
-            .set_item(Some(item))
-            .send()
+            .key_condition_expression("PK = :pk AND SK BETWEEN :sk_from AND :sk_to")
+            .expression_attribute_values(":pk", AttributeValue::S(format!("USER#{}", user_id)))
+            .expression_attribute_values(":sk_from", AttributeValue::S(from))
+            .expression_attribute_values(":sk_to", AttributeValue::S(to))
+            .consistent_read(consistent);
+
+        match &filters.status {
+            // STATUS is a DynamoDB reserved word
+            Some(status) => query
+                .filter_expression("#status = :status")
+                .expression_attribute_names("#status", "Status")
+                .expression_attribute_values(":status", AttributeValue::S(status.clone())),
+            None => query,
+        }
+    }
+
+    /// Rewrite a `SCHEDULE#{id}` item under its date-prefixed key
+    async fn migrate_legacy_schedule(&self, mut item: Item) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let (Some(legacy_key), Some(schedule_id), Some(scheduled_date), Some(scheduled_time)) = (
+            item.get("SK").and_then(|v| v.as_s().ok()).cloned(),
+            item.get("ScheduleId").and_then(|v| v.as_s().ok()).cloned(),
+            item.get("ScheduledDate").and_then(|v| v.as_s().ok()).cloned(),
+            item.get("ScheduledTime").and_then(|v| v.as_s().ok()).cloned(),
+        ) else {
+            return Ok(());
+        };
+        let pk = item.get("PK").cloned().ok_or("Schedule item has no PK")?;
+
+        item.insert("SK".to_string(), AttributeValue::S(schedule_sort_key(&scheduled_date, &scheduled_time, &schedule_id)));
+        self.client
+            .put_item(
+                PutItemInput::builder()
+                    .table_name(&self.table_name)
+                    .set_item(Some(item))
+                    .build()?,
+            )
             .await?;
-        
-        Ok(serde_json::to_value(scheduled_workout)?)
+        self.client
+            .delete_item(
+                DeleteItemInput::builder()
+                    .table_name(&self.table_name)
+                    .key("PK", pk)
+                    .key("SK", AttributeValue::S(legacy_key))
+                    .build()?,
+            )
+            .await?;
+
+        info!("Migrated scheduled workout {} to a date-prefixed key", schedule_id);
+        Ok(())
     }
 
-    pub async fn get_scheduled_workouts(&self, user_id: Option<String>) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-        let query = self.client
-            .query()
-            .table_name(&self.table_name)
-            .key_condition_expression("PK = :pk AND begins_with(SK, :sk)")
-            .expression_attribute_values(":pk", AttributeValue::S(format!("USER#{}", user_id.unwrap_or_default())))
-            .expression_attribute_values(":sk", AttributeValue::S("SCHEDULE#".to_string()));
-        
-        let result = query.send().await?;
+    /// The sort key `schedule_id` is stored under in `user_id`'s partition, in either key form
+    async fn find_schedule_key(&self, user_id: &str, schedule_id: &str) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut exclusive_start_key = None;
+        loop {
+            let result = self.client
+                .query(
+                    QueryInput::builder()
+                        .table_name(&self.table_name)
+                        .key_condition_expression("PK = :pk AND begins_with(SK, :sk_prefix)")
+                        .filter_expression("ScheduleId = :schedule_id")
+                        .expression_attribute_values(":pk", AttributeValue::S(format!("USER#{}", user_id)))
+                        .expression_attribute_values(":sk_prefix", AttributeValue::S(SCHEDULE_SK_PREFIX.to_string()))
+                        .expression_attribute_values(":schedule_id", AttributeValue::S(schedule_id.to_string()))
+                        .projection_expression("SK")
+                        .set_exclusive_start_key(exclusive_start_key.take())
+                        .build()?,
+                )
+                .await?;
+
+            let key = result.items.unwrap_or_default().into_iter()
+                .find_map(|item| item.get("SK").and_then(|v| v.as_s().ok()).cloned());
+            if key.is_some() {
+                return Ok(key);
+            }
+
+            exclusive_start_key = result.last_evaluated_key;
+            if exclusive_start_key.is_none() {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// A user's schedules on one `YYYY-MM-DD` date, via the `SCHEDULE_DATE#` index
+    pub async fn get_scheduled_workouts_by_date(&self, user_id: &str, scheduled_date: &str) -> Result<Vec<ScheduledWorkout>, Box<dyn std::error::Error + Send + Sync>> {
+        let result = self.client
+            .query(
+                QueryInput::builder()
+                    .table_name(&self.table_name)
+                    .index_name("GSI1")
+                    .key_condition_expression("GSI1PK = :gsi1pk AND GSI1SK = :gsi1sk")
+                    .expression_attribute_values(":gsi1pk", AttributeValue::S(format!("SCHEDULE_DATE#{}", scheduled_date)))
+                    .expression_attribute_values(":gsi1sk", AttributeValue::S(format!("USER#{}", user_id)))
+                    .build()?,
+            )
+            .await?;
         
-        let scheduled_workouts: Vec<ScheduledWorkout> = result
+        Ok(result
             .items
             .unwrap_or_default()
-            .into_iter()
-            .filter_map(|item| {
-                Some(ScheduledWorkout {
-                    id: item.get("ScheduleId")?.as_s().ok()?.clone(),
-                    plan_id: item.get("PlanId")?.as_s().ok()?.clone(),
-                    user_id: item.get("UserId")?.as_s().ok()?.clone(),
-                    plan_name: item.get("PlanName")?.as_s().ok()?.clone(),
-                    scheduled_date: item.get("ScheduledDate")?.as_s().ok()?.clone(),
-                    scheduled_time: item.get("ScheduledTime")?.as_s().ok()?.clone(),
-                    status: item.get("Status")?.as_s().ok()?.clone(),
-                    week: item.get("Week")?.as_n().ok()?.parse().ok()?,
-                    day: item.get("Day")?.as_n().ok()?.parse().ok()?,
-                    notes: item.get("Notes").and_then(|v| v.as_s().ok()).map(|s| s.clone()),
-                    completed_at: item.get("CompletedAt").and_then(|v| v.as_s().ok()).map(|s| s.clone()),
-                    created_at: item.get("CreatedAt")?.as_s().ok()?.clone(),
-                    updated_at: item.get("UpdatedAt")?.as_s().ok()?.clone(),
-                })
-            })
-            .collect();
-        
-        Ok(serde_json::to_value(scheduled_workouts)?)
+            .iter()
+            .filter_map(Self::scheduled_workout_from_item)
+            .collect())
+    }
+
+    fn scheduled_workout_from_item(item: &HashMap<String, AttributeValue>) -> Option<ScheduledWorkout> {
+        Some(ScheduledWorkout {
+            id: item.get("ScheduleId")?.as_s().ok()?.clone(),
+            plan_id: item.get("PlanId")?.as_s().ok()?.clone(),
+            user_id: item.get("UserId")?.as_s().ok()?.clone(),
+            plan_name: item.get("PlanName")?.as_s().ok()?.clone(),
+            scheduled_date: item.get("ScheduledDate")?.as_s().ok()?.clone(),
+            scheduled_time: item.get("ScheduledTime")?.as_s().ok()?.clone(),
+            status: item.get("Status")?.as_s().ok()?.clone(),
+            week: item.get("Week")?.as_n().ok()?.parse().ok()?,
+            day: item.get("Day")?.as_n().ok()?.parse().ok()?,
+            notes: item.get("Notes").and_then(|v| v.as_s().ok()).map(|s| s.clone()),
+            completed_at: item.get("CompletedAt").and_then(|v| v.as_s().ok()).map(|s| s.clone()),
+            estimated_duration_minutes: item.get("EstimatedDurationMinutes").and_then(|v| v.as_n().ok()).and_then(|n| n.parse().ok()),
+            recurrence_id: item.get("RecurrenceId").and_then(|v| v.as_s().ok()).cloned(),
+            created_at: item.get("CreatedAt")?.as_s().ok()?.clone(),
+            updated_at: item.get("UpdatedAt")?.as_s().ok()?.clone(),
+        })
     }
 
     pub async fn update_scheduled_workout(&self, scheduled_workout: &ScheduledWorkout) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-        let update_expression = "SET #status = :status, #updated_at = :updated_at, #notes = :notes, #completed_at = :completed_at";
-        let expression_attribute_names = [
-            ("#status".to_string(), "Status".to_string()),
-            ("#updated_at".to_string(), "UpdatedAt".to_string()),
-            ("#notes".to_string(), "Notes".to_string()),
-            ("#completed_at".to_string(), "CompletedAt".to_string()),
-        ].into_iter().collect();
-        
-        let mut expression_attribute_values = [
-            (":status".to_string(), AttributeValue::S(scheduled_workout.status.clone())),
-            (":updated_at".to_string(), AttributeValue::S(scheduled_workout.updated_at.clone())),
-        ].into_iter().collect::<std::collections::HashMap<String, AttributeValue>>();
-        
-        if let Some(notes) = &scheduled_workout.notes {
-            expression_attribute_values.insert(":notes".to_string(), AttributeValue::S(notes.clone()));
-        } else {
-            expression_attribute_values.insert(":notes".to_string(), AttributeValue::Null(true));
-        }
-        
-        if let Some(completed_at) = &scheduled_workout.completed_at {
-            expression_attribute_values.insert(":completed_at".to_string(), AttributeValue::S(completed_at.clone()));
-        } else {
-            expression_attribute_values.insert(":completed_at".to_string(), AttributeValue::Null(true));
-        }
+        // Cleared notes / completion times are removed rather than stored as NULL
+        let update = UpdateExpressionBuilder::new()
+            .set("Status", &scheduled_workout.status)
+            .set("UpdatedAt", &scheduled_workout.updated_at)
+            .set_or_remove("Notes", scheduled_workout.notes.as_ref())
+            .set_or_remove("CompletedAt", scheduled_workout.completed_at.as_ref())
+            .build();
         
-        self.client
-            .update_item()
+        let sk = self.find_schedule_key(&scheduled_workout.user_id, &scheduled_workout.id).await?;
+        let sk = get_or_404(sk, "Scheduled workout", &scheduled_workout.id)?;
+        let request = UpdateItemInput::builder()
             .table_name(&self.table_name)
             .key("PK", AttributeValue::S(format!("USER#{}", scheduled_workout.user_id)))
-            .key("SK", AttributeValue::S(format!("SCHEDULE#{}", scheduled_workout.id)))
-            .update_expression(update_expression)
-            .set_expression_attribute_names(Some(expression_attribute_names))
-            .set_expression_attribute_values(Some(expression_attribute_values))
-            .send()
+            .key("SK", AttributeValue::S(sk));
+        self.client
+            .update_item(update.apply(request).build()?)
             .await?;
         
         Ok(serde_json::to_value(scheduled_workout)?)
     }
 
     pub async fn delete_scheduled_workout(&self, user_id: &str, schedule_id: &str) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        let sk = get_or_404(self.find_schedule_key(user_id, schedule_id).await?, "Scheduled workout", schedule_id)?;
         self.client
-            .delete_item()
-            .table_name(&self.table_name)
-            .key("PK", AttributeValue::S(format!("USER#{}", user_id)))
-            .key("SK", AttributeValue::S(format!("SCHEDULE#{}", schedule_id)))
-            .send()
+            .delete_item(
+                DeleteItemInput::builder()
+                    .table_name(&self.table_name)
+                    .key("PK", AttributeValue::S(format!("USER#{}", user_id)))
+                    .key("SK", AttributeValue::S(sk))
+                    .build()?,
+            )
             .await?;
         
         Ok(serde_json::json!({"message": "Scheduled workout deleted successfully"}))
     }
+
+    /// Delete every occurrence of a recurring schedule, returning how many were removed
+    pub async fn delete_recurring_scheduled_workouts(&self, user_id: &str, recurrence_id: &str) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let mut keys = Vec::new();
+        let mut exclusive_start_key = None;
+        loop {
+            let result = self.client
+                .query(
+                    QueryInput::builder()
+                        .table_name(&self.table_name)
+                        .key_condition_expression("PK = :pk AND begins_with(SK, :sk_prefix)")
+                        .filter_expression("RecurrenceId = :recurrence_id")
+                        .expression_attribute_values(":pk", AttributeValue::S(format!("USER#{}", user_id)))
+                        .expression_attribute_values(":sk_prefix", AttributeValue::S(SCHEDULE_SK_PREFIX.to_string()))
+                        .expression_attribute_values(":recurrence_id", AttributeValue::S(recurrence_id.to_string()))
+                        .projection_expression("PK, SK")
+                        .set_exclusive_start_key(exclusive_start_key)
+                        .build()?,
+                )
+                .await?;
+            keys.extend(result.items.unwrap_or_default());
+            exclusive_start_key = result.last_evaluated_key;
+            if exclusive_start_key.is_none() {
+                break;
+            }
+        }
+
+        let deleted = keys.len();
+        let requests = keys
+            .into_iter()
+            .map(|key| {
+                DeleteRequest::builder()
+                    .set_key(Some(key))
+                    .build()
+                    .map(|delete| WriteRequest::builder().delete_request(delete).build())
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.batch_write(requests).await?;
+        
+        Ok(deleted)
+    }
 }
@@ -1,33 +1,39 @@
-use aws_sdk_dynamodb::{Client as DynamoDbClient, types::AttributeValue};
+use aws_sdk_dynamodb::{Client as DynamoDbClient, operation::query::QueryInput, types::{AttributeValue, Select}};
 use serde_json::Value;
 use anyhow::Result;
 use tracing::{info, error};
-use chrono::Utc;
+use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+use std::collections::{BTreeSet, HashMap};
 
 use crate::models::*;
+use crate::repository::{DynamoOps, NOT_DELETED_FILTER};
 
 #[derive(Clone)]
-pub struct WorkoutAnalyticsRepository {
-    client: DynamoDbClient,
+pub struct WorkoutAnalyticsRepository<C = DynamoDbClient> {
+    client: C,
     table_name: String,
 }
 
-impl WorkoutAnalyticsRepository {
-    pub fn new(client: DynamoDbClient, table_name: String) -> Self {
+impl<C: DynamoOps> WorkoutAnalyticsRepository<C> {
+    pub fn new(client: C, table_name: String) -> Self {
         Self { client, table_name }
     }
 
-    pub async fn get_workout_analytics(&self, user_id: Option<String>) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+    pub async fn get_workout_analytics(&self, user_id: Option<String>, one_rep_max_formula: OneRepMaxFormula, timezone: Tz) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
         let user_id = user_id.unwrap_or_else(|| "unknown".to_string());
         
         // Fetch all workout sessions for the user
         let sessions_result = self.client
-            .query()
-            .table_name(&self.table_name)
-            .key_condition_expression("PK = :pk AND begins_with(SK, :sk_prefix)")
-            .expression_attribute_values(":pk", AttributeValue::S(format!("USER#{}", user_id)))
-            .expression_attribute_values(":sk_prefix", AttributeValue::S("SESSION#".to_string()))
-            .send()
+            .query(
+                QueryInput::builder()
+                    .table_name(&self.table_name)
+                    .key_condition_expression("PK = :pk AND begins_with(SK, :sk_prefix)")
+                    .expression_attribute_values(":pk", AttributeValue::S(format!("USER#{}", user_id)))
+                    .expression_attribute_values(":sk_prefix", AttributeValue::S("SESSION#".to_string()))
+                    .filter_expression(NOT_DELETED_FILTER)
+                    .build()?,
+            )
             .await?;
 
         let sessions = sessions_result.items.unwrap_or_default();
@@ -104,9 +110,14 @@ impl WorkoutAnalyticsRepository {
             .map(|(name, _)| name)
             .collect();
         
-        // Calculate streak (simplified - would need proper date sequence analysis)
-        let current_streak = if workouts_this_week > 0 { workouts_this_week } else { 0 };
-        let longest_streak = current_streak; // Simplified - would need historical analysis
+        // Calculate consecutive-day streaks in the user's timezone
+        let session_starts: Vec<DateTime<Utc>> = sessions
+            .iter()
+            .filter_map(|session| session.get("StartedAt").and_then(|v| v.as_s().ok()))
+            .filter_map(|started_at| DateTime::parse_from_rfc3339(started_at).ok())
+            .map(|started_at| started_at.with_timezone(&Utc))
+            .collect();
+        let (current_streak, longest_streak) = Self::calculate_streaks(&session_starts, timezone, now);
         
         // Calculate calories burned
         // Average calories per minute for strength training: ~6 calories/min
@@ -116,16 +127,20 @@ impl WorkoutAnalyticsRepository {
         
         // Fetch strength progress data
         let strength_result = self.client
-            .query()
-            .table_name(&self.table_name)
-            .key_condition_expression("PK = :pk AND begins_with(SK, :sk_prefix)")
-            .expression_attribute_values(":pk", AttributeValue::S(format!("USER#{}", user_id)))
-            .expression_attribute_values(":sk_prefix", AttributeValue::S("STRENGTH#".to_string()))
-            .send()
+            .query(
+                QueryInput::builder()
+                    .table_name(&self.table_name)
+                    .key_condition_expression("PK = :pk AND begins_with(SK, :sk_prefix)")
+                    .expression_attribute_values(":pk", AttributeValue::S(format!("USER#{}", user_id)))
+                    .expression_attribute_values(":sk_prefix", AttributeValue::S("STRENGTH#".to_string()))
+                    .build()?,
+            )
             .await?;
 
         let strength_items = strength_result.items.unwrap_or_default();
-        let mut strength_progress = Vec::new();
+        
+        // Estimated 1RMs from logged sessions take precedence over stored records
+        let mut strength_progress = Self::calculate_strength_progress(&sessions, one_rep_max_formula, now);
         
         for item in strength_items {
             if let (Some(exercise_id), Some(exercise_name), Some(one_rep_max), Some(last_updated)) = (
@@ -134,24 +149,31 @@ impl WorkoutAnalyticsRepository {
                 item.get("OneRepMax").and_then(|v| v.as_n().ok()).and_then(|n| n.parse::<f32>().ok()),
                 item.get("LastUpdated").and_then(|v| v.as_s().ok()),
             ) {
+                if strength_progress.iter().any(|p| &p.exercise_id == exercise_id) {
+                    continue;
+                }
+                
+                // Stored records carry no history to compare against
                 strength_progress.push(StrengthProgress {
                     exercise_id: exercise_id.clone(),
                     exercise_name: exercise_name.clone(),
                     one_rep_max,
                     last_updated: last_updated.clone(),
-                    progress_percentage: 5.0, // Simplified - would calculate from historical data
+                    progress_percentage: 0.0,
                 });
             }
         }
         
         // Fetch body measurements
         let measurements_result = self.client
-            .query()
-            .table_name(&self.table_name)
-            .key_condition_expression("PK = :pk AND begins_with(SK, :sk_prefix)")
-            .expression_attribute_values(":pk", AttributeValue::S(format!("USER#{}", user_id)))
-            .expression_attribute_values(":sk_prefix", AttributeValue::S("MEASUREMENT#".to_string()))
-            .send()
+            .query(
+                QueryInput::builder()
+                    .table_name(&self.table_name)
+                    .key_condition_expression("PK = :pk AND begins_with(SK, :sk_prefix)")
+                    .expression_attribute_values(":pk", AttributeValue::S(format!("USER#{}", user_id)))
+                    .expression_attribute_values(":sk_prefix", AttributeValue::S("MEASUREMENT#".to_string()))
+                    .build()?,
+            )
             .await?;
 
         let measurement_items = measurements_result.items.unwrap_or_default();
@@ -193,9 +215,9 @@ impl WorkoutAnalyticsRepository {
         Ok(serde_json::to_value(analytics)?)
     }
 
-    pub async fn get_workout_insights(&self, user_id: &str, time_range: &str) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+    pub async fn get_workout_insights(&self, user_id: &str, time_range: &str, one_rep_max_formula: OneRepMaxFormula, timezone: Tz) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
         // Get actual analytics data first
-        let analytics_result = self.get_workout_analytics(Some(user_id.to_string())).await?;
+        let analytics_result = self.get_workout_analytics(Some(user_id.to_string()), one_rep_max_formula, timezone).await?;
         let analytics: WorkoutAnalytics = serde_json::from_value(analytics_result)?;
         
         // Generate insights based on actual data
@@ -292,24 +314,44 @@ impl WorkoutAnalyticsRepository {
         Ok(serde_json::to_value(insights_data)?)
     }
 
-    pub async fn get_workout_history(&self, user_id: &str, limit: Option<i32>) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-        let limit = limit.unwrap_or(50);
-        
-        let sessions_result = self.client
-            .query()
-            .table_name(&self.table_name)
-            .key_condition_expression("PK = :pk AND begins_with(SK, :sk_prefix)")
-            .expression_attribute_values(":pk", AttributeValue::S(format!("USER#{}", user_id)))
-            .expression_attribute_values(":sk_prefix", AttributeValue::S("SESSION#".to_string()))
-            .limit(limit)
-            .scan_index_forward(false) // Most recent first
-            .send()
-            .await?;
+    pub async fn get_workout_history(&self, user_id: &str, page: Option<i32>, limit: Option<i32>) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        let page = page.unwrap_or(1).max(1);
+        let limit = limit.unwrap_or(50).max(1);
+        let offset = ((page - 1) * limit) as usize;
+        
+        let total = self.count_workout_sessions(user_id).await?;
+        
+        // DynamoDB has no offset, so read forward until the requested page is covered
+        let mut sessions = Vec::new();
+        let mut exclusive_start_key = None;
+        while offset < total as usize && sessions.len() < offset + limit as usize {
+            let sessions_result = self.client
+                .query(
+                    QueryInput::builder()
+                        .table_name(&self.table_name)
+                        .key_condition_expression("PK = :pk AND begins_with(SK, :sk_prefix)")
+                        .expression_attribute_values(":pk", AttributeValue::S(format!("USER#{}", user_id)))
+                        .expression_attribute_values(":sk_prefix", AttributeValue::S("SESSION#".to_string()))
+                        .filter_expression(NOT_DELETED_FILTER)
+                        .limit(offset as i32 + limit - sessions.len() as i32)
+                        .scan_index_forward(false) // Most recent first
+                        .set_exclusive_start_key(exclusive_start_key)
+                        .build()?,
+                )
+                .await?;
 
-        let sessions = sessions_result.items.unwrap_or_default();
+            sessions.extend(sessions_result.items.unwrap_or_default());
+            
+            match sessions_result.last_evaluated_key {
+                Some(key) if !key.is_empty() => exclusive_start_key = Some(key),
+                _ => break,
+            }
+        }
         
         let history: Vec<WorkoutSession> = sessions
             .into_iter()
+            .skip(offset)
+            .take(limit as usize)
             .filter_map(|item| {
                 Some(WorkoutSession {
                     id: item.get("SessionId").or_else(|| item.get("id")).and_then(|v| v.as_s().ok())?.clone(),
@@ -354,7 +396,173 @@ impl WorkoutAnalyticsRepository {
             })
             .collect();
         
-        Ok(serde_json::to_value(history)?)
+        Ok(serde_json::to_value(WorkoutHistory {
+            sessions: history,
+            pagination: Pagination::new(page, limit, total),
+        })?)
+    }
+
+    /// Count every session for the user that isn't soft-deleted with `Select=COUNT`,
+    /// following `LastEvaluatedKey` since each response only covers up to 1MB of items
+    async fn count_workout_sessions(&self, user_id: &str) -> Result<i32, Box<dyn std::error::Error + Send + Sync>> {
+        let mut total = 0;
+        let mut exclusive_start_key = None;
+        
+        loop {
+            let count_result = self.client
+                .query(
+                    QueryInput::builder()
+                        .table_name(&self.table_name)
+                        .key_condition_expression("PK = :pk AND begins_with(SK, :sk_prefix)")
+                        .expression_attribute_values(":pk", AttributeValue::S(format!("USER#{}", user_id)))
+                        .expression_attribute_values(":sk_prefix", AttributeValue::S("SESSION#".to_string()))
+                        .filter_expression(NOT_DELETED_FILTER)
+                        .select(Select::Count)
+                        .set_exclusive_start_key(exclusive_start_key)
+                        .build()?,
+                )
+                .await?;
+
+            total += count_result.count;
+            
+            match count_result.last_evaluated_key {
+                Some(key) if !key.is_empty() => exclusive_start_key = Some(key),
+                _ => break,
+            }
+        }
+        
+        Ok(total)
+    }
+
+    /// Count consecutive workout days as (current, longest). Sessions are bucketed into
+    /// calendar days in `timezone`, so several sessions on one day count once, and the
+    /// current streak only counts if it runs through today
+    fn calculate_streaks(session_starts: &[DateTime<Utc>], timezone: Tz, now: DateTime<Utc>) -> (i32, i32) {
+        let workout_days: BTreeSet<NaiveDate> = session_starts
+            .iter()
+            .map(|started_at| started_at.with_timezone(&timezone).date_naive())
+            .collect();
+        
+        let mut longest_streak = 0;
+        let mut run = 0;
+        let mut previous_day: Option<NaiveDate> = None;
+        for day in &workout_days {
+            run = match previous_day {
+                Some(previous) if previous.succ_opt() == Some(*day) => run + 1,
+                _ => 1,
+            };
+            longest_streak = longest_streak.max(run);
+            previous_day = Some(*day);
+        }
+        
+        let mut current_streak = 0;
+        let mut day = now.with_timezone(&timezone).date_naive();
+        while workout_days.contains(&day) {
+            current_streak += 1;
+            match day.pred_opt() {
+                Some(previous) => day = previous,
+                None => break,
+            }
+        }
+        
+        (current_streak, longest_streak)
+    }
+
+    /// Estimate each exercise's 1RM from the heaviest completed set of every session and
+    /// compare the best estimate of the last 30 days with the best of the 30 days before
+    fn calculate_strength_progress(
+        sessions: &[HashMap<String, AttributeValue>],
+        formula: OneRepMaxFormula,
+        now: DateTime<Utc>,
+    ) -> Vec<StrengthProgress> {
+        // (session date, raw StartedAt, estimated 1RM) per session
+        type SessionEstimates = Vec<(DateTime<Utc>, String, f32)>;
+        
+        // exercise_id -> (name, estimates)
+        let mut estimates: HashMap<String, (String, SessionEstimates)> = HashMap::new();
+        
+        for session in sessions {
+            let Some(started_at) = session.get("StartedAt").and_then(|v| v.as_s().ok()) else {
+                continue;
+            };
+            let Ok(session_date) = DateTime::parse_from_rfc3339(started_at) else {
+                continue;
+            };
+            let session_date = session_date.with_timezone(&Utc);
+            
+            let exercises = session.get("exercises").and_then(|v| v.as_l().ok());
+            for exercise in exercises.into_iter().flatten() {
+                let Ok(exercise) = exercise.as_m() else {
+                    continue;
+                };
+                let (Some(exercise_id), Some(name)) = (
+                    exercise.get("exerciseId").and_then(|v| v.as_s().ok()),
+                    exercise.get("name").and_then(|v| v.as_s().ok()),
+                ) else {
+                    continue;
+                };
+                
+                // Heaviest completed, loaded set; more reps wins a tie
+                let heaviest_set = exercise
+                    .get("sets")
+                    .and_then(|v| v.as_l().ok())
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|set| {
+                        let set = set.as_m().ok()?;
+                        if !*set.get("completed")?.as_bool().ok()? {
+                            return None;
+                        }
+                        let weight = set.get("weight")?.as_n().ok()?.parse::<f32>().ok()?;
+                        let reps = set.get("reps")?.as_n().ok()?.parse::<i32>().ok()?;
+                        (weight > 0.0 && reps > 0).then_some((weight, reps))
+                    })
+                    .max_by(|a, b| a.0.total_cmp(&b.0).then(a.1.cmp(&b.1)));
+                
+                if let Some(one_rep_max) = heaviest_set.and_then(|(weight, reps)| formula.estimate(weight, reps)) {
+                    estimates
+                        .entry(exercise_id.clone())
+                        .or_insert_with(|| (name.clone(), Vec::new()))
+                        .1
+                        .push((session_date, started_at.clone(), one_rep_max));
+                }
+            }
+        }
+        
+        let period_start = now - chrono::Duration::days(30);
+        let prior_period_start = now - chrono::Duration::days(60);
+        
+        let mut progress: Vec<StrengthProgress> = estimates
+            .into_iter()
+            .filter_map(|(exercise_id, (exercise_name, entries))| {
+                let best_in = |from: DateTime<Utc>, to: DateTime<Utc>| {
+                    entries
+                        .iter()
+                        .filter(|(date, _, _)| *date >= from && *date < to)
+                        .max_by(|a, b| a.2.total_cmp(&b.2))
+                };
+                
+                let current = best_in(period_start, DateTime::<Utc>::MAX_UTC)
+                    .or_else(|| entries.iter().max_by_key(|(date, _, _)| *date))?;
+                let progress_percentage = match best_in(prior_period_start, period_start) {
+                    Some(prior) if current.0 >= period_start && prior.2 > 0.0 => {
+                        (current.2 - prior.2) / prior.2 * 100.0
+                    }
+                    _ => 0.0,
+                };
+                
+                Some(StrengthProgress {
+                    exercise_id,
+                    exercise_name,
+                    one_rep_max: (current.2 * 10.0).round() / 10.0,
+                    last_updated: current.1.clone(),
+                    progress_percentage: (progress_percentage * 10.0).round() / 10.0,
+                })
+            })
+            .collect();
+        
+        progress.sort_by(|a, b| a.exercise_name.cmp(&b.exercise_name));
+        progress
     }
 
     fn calculate_overall_score(&self, analytics: &WorkoutAnalytics) -> i32 {
@@ -400,3 +608,212 @@ impl WorkoutAnalyticsRepository {
         score
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repository::dynamo_ops::fake::FakeDynamo;
+    use crate::repository::workout_session_repository::session_sort_key;
+    use aws_sdk_dynamodb::operation::put_item::PutItemInput;
+    use chrono::TimeZone;
+
+    fn at(year: i32, month: u32, day: u32, hour: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(year, month, day, hour, 0, 0).unwrap()
+    }
+
+    /// A table holding user-1's sessions, one a day from 2024-03-01 at 07:00, with the
+    /// ones at `deleted` indexes soft-deleted
+    async fn sessions_table(count: u32, deleted: &[u32]) -> FakeDynamo {
+        let table = FakeDynamo::default();
+        for i in 0..count {
+            let id = format!("s{}", i + 1);
+            let started_at = at(2024, 3, i + 1, 7).to_rfc3339();
+            let mut item: HashMap<String, AttributeValue> = [
+                ("PK", "USER#user-1".to_string()),
+                ("SK", session_sort_key(&started_at, &id)),
+                ("SessionId", id),
+                ("UserId", "user-1".to_string()),
+                ("Name", "Push Day".to_string()),
+                ("StartedAt", started_at.clone()),
+                ("CreatedAt", started_at.clone()),
+                ("UpdatedAt", started_at),
+            ]
+            .into_iter()
+            .map(|(name, value)| (name.to_string(), AttributeValue::S(value)))
+            .collect();
+            if deleted.contains(&i) {
+                item.insert("DeletedAt".to_string(), AttributeValue::S("2024-04-01T00:00:00Z".to_string()));
+            }
+            table
+                .put_item(PutItemInput::builder().table_name("test").set_item(Some(item)).build().unwrap())
+                .await
+                .unwrap();
+        }
+        table
+    }
+
+    fn history_ids(history: &Value) -> Vec<&str> {
+        history["sessions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|session| session["id"].as_str().unwrap())
+            .collect()
+    }
+
+    /// A session item with one exercise and its sets as (weight, reps, completed)
+    fn strength_session(started_at: DateTime<Utc>, exercise: (&str, &str), sets: &[(f32, i32, bool)]) -> HashMap<String, AttributeValue> {
+        let sets = sets
+            .iter()
+            .map(|&(weight, reps, completed)| {
+                AttributeValue::M(HashMap::from([
+                    ("weight".to_string(), AttributeValue::N(weight.to_string())),
+                    ("reps".to_string(), AttributeValue::N(reps.to_string())),
+                    ("completed".to_string(), AttributeValue::Bool(completed)),
+                ]))
+            })
+            .collect();
+        let exercise = HashMap::from([
+            ("exerciseId".to_string(), AttributeValue::S(exercise.0.to_string())),
+            ("name".to_string(), AttributeValue::S(exercise.1.to_string())),
+            ("sets".to_string(), AttributeValue::L(sets)),
+        ]);
+        HashMap::from([
+            ("StartedAt".to_string(), AttributeValue::S(started_at.to_rfc3339())),
+            ("exercises".to_string(), AttributeValue::L(vec![AttributeValue::M(exercise)])),
+        ])
+    }
+
+    const BENCH: (&str, &str) = ("bench", "Bench Press");
+    const SQUAT: (&str, &str) = ("squat", "Back Squat");
+
+    #[test]
+    fn test_strength_progress_compares_best_estimates_of_the_last_two_months() {
+        let now = at(2024, 6, 1, 12);
+        let sessions = vec![
+            strength_session(at(2024, 4, 10, 7), BENCH, &[(90.0, 1, true)]),
+            strength_session(at(2024, 4, 20, 7), BENCH, &[(100.0, 1, true)]),
+            strength_session(at(2024, 5, 20, 7), BENCH, &[(110.0, 1, true)]),
+            strength_session(at(2024, 5, 25, 7), BENCH, &[(105.0, 1, true)]),
+        ];
+
+        let progress = <WorkoutAnalyticsRepository>::calculate_strength_progress(&sessions, OneRepMaxFormula::Epley, now);
+        assert_eq!(progress.len(), 1);
+        assert_eq!(progress[0].one_rep_max, 110.0);
+        assert_eq!(progress[0].last_updated, at(2024, 5, 20, 7).to_rfc3339());
+        assert_eq!(progress[0].progress_percentage, 10.0);
+    }
+
+    #[test]
+    fn test_strength_progress_reports_a_regression_as_negative() {
+        let now = at(2024, 6, 1, 12);
+        let sessions = vec![
+            strength_session(at(2024, 4, 20, 7), SQUAT, &[(160.0, 1, true)]),
+            strength_session(at(2024, 5, 20, 7), SQUAT, &[(140.0, 1, true)]),
+            strength_session(at(2024, 5, 20, 7), BENCH, &[(100.0, 1, true)]),
+        ];
+
+        let progress = <WorkoutAnalyticsRepository>::calculate_strength_progress(&sessions, OneRepMaxFormula::Epley, now);
+        let names: Vec<&str> = progress.iter().map(|p| p.exercise_name.as_str()).collect();
+        assert_eq!(names, vec!["Back Squat", "Bench Press"]);
+        assert_eq!(progress[0].one_rep_max, 140.0);
+        assert_eq!(progress[0].progress_percentage, -12.5);
+        // No earlier sessions to compare with
+        assert_eq!(progress[1].progress_percentage, 0.0);
+    }
+
+    #[test]
+    fn test_strength_progress_estimates_from_the_heaviest_completed_set() {
+        let now = at(2024, 6, 1, 12);
+        // The failed 120 kg attempt doesn't count; of the two 100 kg sets the one with more reps does
+        let sessions = vec![strength_session(
+            at(2024, 5, 20, 7),
+            BENCH,
+            &[(100.0, 3, true), (120.0, 1, false), (100.0, 5, true), (80.0, 10, true)],
+        )];
+
+        let progress = <WorkoutAnalyticsRepository>::calculate_strength_progress(&sessions, OneRepMaxFormula::Epley, now);
+        assert_eq!(progress[0].one_rep_max, 116.7);
+
+        // Only sessions older than a month: the latest one is reported, with no progress
+        let stale = vec![
+            strength_session(at(2024, 3, 1, 7), BENCH, &[(100.0, 1, true)]),
+            strength_session(at(2024, 3, 8, 7), BENCH, &[(95.0, 1, true)]),
+        ];
+        let progress = <WorkoutAnalyticsRepository>::calculate_strength_progress(&stale, OneRepMaxFormula::Epley, now);
+        assert_eq!(progress[0].one_rep_max, 95.0);
+        assert_eq!(progress[0].progress_percentage, 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_history_pages_newest_first_with_real_totals() {
+        let repository = WorkoutAnalyticsRepository::new(sessions_table(23, &[]).await, "test".to_string());
+
+        let first = repository.get_workout_history("user-1", Some(1), Some(10)).await.unwrap();
+        let expected: Vec<String> = (14..=23).rev().map(|day| format!("s{}", day)).collect();
+        assert_eq!(history_ids(&first), expected);
+        assert_eq!(first["pagination"]["total"], 23);
+        assert_eq!(first["pagination"]["total_pages"], 3);
+
+        let last = repository.get_workout_history("user-1", Some(3), Some(10)).await.unwrap();
+        assert_eq!(history_ids(&last), vec!["s3", "s2", "s1"]);
+        assert_eq!(last["pagination"]["page"], 3);
+
+        let beyond = repository.get_workout_history("user-1", Some(4), Some(10)).await.unwrap();
+        assert!(history_ids(&beyond).is_empty());
+        assert_eq!(beyond["pagination"]["total"], 23);
+    }
+
+    #[tokio::test]
+    async fn test_history_leaves_out_soft_deleted_sessions() {
+        let repository = WorkoutAnalyticsRepository::new(sessions_table(4, &[1, 3]).await, "test".to_string());
+
+        let history = repository.get_workout_history("user-1", None, None).await.unwrap();
+        assert_eq!(history_ids(&history), vec!["s3", "s1"]);
+        assert_eq!(history["pagination"]["total"], 2);
+        assert_eq!(repository.count_workout_sessions("user-1").await.unwrap(), 2);
+    }
+
+    #[test]
+    fn test_five_day_streak_with_double_session() {
+        let sessions = vec![
+            at(2024, 3, 1, 9),
+            at(2024, 3, 2, 9),
+            at(2024, 3, 3, 7),
+            at(2024, 3, 3, 18), // second session on the same day
+            at(2024, 3, 4, 9),
+            at(2024, 3, 5, 9),
+        ];
+
+        let streaks = <WorkoutAnalyticsRepository>::calculate_streaks(&sessions, Tz::UTC, at(2024, 3, 5, 20));
+        assert_eq!(streaks, (5, 5));
+    }
+
+    #[test]
+    fn test_streak_broken_by_gap() {
+        let sessions = vec![
+            at(2024, 3, 1, 9),
+            at(2024, 3, 2, 9),
+            at(2024, 3, 3, 9),
+            at(2024, 3, 5, 9),
+            at(2024, 3, 6, 9),
+        ];
+
+        let streaks = <WorkoutAnalyticsRepository>::calculate_streaks(&sessions, Tz::UTC, at(2024, 3, 6, 20));
+        assert_eq!(streaks, (2, 3));
+
+        // No workout today yet
+        let streaks = <WorkoutAnalyticsRepository>::calculate_streaks(&sessions, Tz::UTC, at(2024, 3, 7, 8));
+        assert_eq!(streaks, (0, 3));
+    }
+
+    #[test]
+    fn test_streak_uses_user_timezone() {
+        // 02:00 UTC on the 2nd is still the evening of the 1st in New York
+        let sessions = vec![at(2024, 3, 1, 15), at(2024, 3, 2, 2)];
+        let now = at(2024, 3, 2, 3);
+
+        assert_eq!(<WorkoutAnalyticsRepository>::calculate_streaks(&sessions, Tz::UTC, now), (2, 2));
+        assert_eq!(<WorkoutAnalyticsRepository>::calculate_streaks(&sessions, Tz::America__New_York, now), (1, 1));
+    }
+}
@@ -1,30 +1,46 @@
 use anyhow::Result;
-use aws_sdk_dynamodb::{types::AttributeValue, Client as DynamoDbClient};
+use aws_sdk_dynamodb::{
+    operation::{
+        get_item::GetItemInput,
+        put_item::{PutItemError, PutItemInput},
+        query::QueryInput,
+        update_item::UpdateItemInput,
+    },
+    types::{AttributeValue, ReturnValuesOnConditionCheckFailure},
+    Client as DynamoDbClient,
+};
+use chrono::Utc;
+use lambda_router::time_phase;
 use serde_json::Value;
 use std::collections::HashMap;
 use tracing::{error, info};
 
 use crate::models::*;
+use crate::repository::{
+    get_or_404, soft_delete_expiry, without_deleted, DynamoOps, NOT_DELETED_FILTER,
+};
+use crate::utils::error::helpers as error_helpers;
 
 #[derive(Clone)]
-pub struct WorkoutPlanRepository {
-    client: DynamoDbClient,
+pub struct WorkoutPlanRepository<C = DynamoDbClient> {
+    client: C,
     table_name: String,
 }
 
-impl WorkoutPlanRepository {
-    pub fn new(client: DynamoDbClient, table_name: String) -> Self {
+impl<C: DynamoOps> WorkoutPlanRepository<C> {
+    pub fn new(client: C, table_name: String) -> Self {
         Self { client, table_name }
     }
 
+    /// A user's plans. `consistent` makes the read strongly consistent, so a plan
+    /// written just before is included
     pub async fn get_workout_plans(
         &self,
         user_id: Option<String>,
+        consistent: bool,
     ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
         // Use GSI1 to query all workout plans
-        let query = self
-            .client
-            .query()
+        let query = QueryInput::builder()
             .table_name(&self.table_name)
             .key_condition_expression("PK = :pk AND begins_with(SK, :sk_prefix)")
             .expression_attribute_values(
@@ -34,8 +50,10 @@ impl WorkoutPlanRepository {
             .expression_attribute_values(
                 ":sk_prefix",
                 AttributeValue::S("WORKOUT_PLAN#".to_string()),
-            );
-        let result = query.send().await?;
+            )
+            .filter_expression(NOT_DELETED_FILTER)
+            .consistent_read(consistent);
+        let result = time_phase("db", self.client.query(query.build()?)).await?;
 
         let plans: Vec<WorkoutPlan> = result
             .items
@@ -90,6 +108,11 @@ impl WorkoutPlanRepository {
                         .get("NextScheduledDate")
                         .and_then(|v| v.as_s().ok())
                         .map(|s| s.clone()),
+                    version: item
+                        .get("Version")
+                        .and_then(|v| v.as_n().ok())
+                        .and_then(|n| n.parse().ok())
+                        .unwrap_or(0),
                 })
             })
             .collect();
@@ -101,6 +124,29 @@ impl WorkoutPlanRepository {
         &self,
         plan: &WorkoutPlan,
     ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        // Never overwrite an existing plan that happens to share the id
+        time_phase(
+            "db",
+            self.client.put_item(
+                PutItemInput::builder()
+                    .table_name(&self.table_name)
+                    .set_item(Some(Self::workout_plan_item(plan)))
+                    .condition_expression("attribute_not_exists(PK)")
+                    .build()?,
+            ),
+        )
+        .await
+        .map_err(|e| {
+            error_helpers::conditional_write_error(
+                e,
+                error_helpers::conflict("Workout plan already exists"),
+            )
+        })?;
+
+        Ok(serde_json::to_value(plan)?)
+    }
+
+    fn workout_plan_item(plan: &WorkoutPlan) -> HashMap<String, AttributeValue> {
         let mut item = std::collections::HashMap::new();
         item.insert(
             "PK".to_string(),
@@ -202,141 +248,245 @@ impl WorkoutPlanRepository {
         // Add exercises as JSON string to match populate script
         let exercises_json = serde_json::to_string(&plan.exercises).unwrap_or_default();
         item.insert("Exercises".to_string(), AttributeValue::S(exercises_json));
+        item.insert(
+            "Version".to_string(),
+            AttributeValue::N(plan.version.to_string()),
+        );
 
-        self.client
-            .put_item()
-            .table_name(&self.table_name)
-            .set_item(Some(item))
-            .send()
-            .await?;
-
-        Ok(serde_json::to_value(plan)?)
+        item
     }
 
+    /// One plan; `consistent` makes the read strongly consistent
     pub async fn get_workout_plan(
         &self,
         user_id: &str,
         plan_id: &str,
+        consistent: bool,
     ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-        let result = self
-            .client
-            .get_item()
-            .table_name(&self.table_name)
-            .key("PK", AttributeValue::S(format!("USER#{}", user_id)))
-            .key("SK", AttributeValue::S(format!("WORKOUT_PLAN#{}", plan_id)))
-            .send()
-            .await?;
+        let result = time_phase(
+            "db",
+            self.client.get_item(
+                GetItemInput::builder()
+                    .table_name(&self.table_name)
+                    .key("PK", AttributeValue::S(format!("USER#{}", user_id)))
+                    .key("SK", AttributeValue::S(format!("WORKOUT_PLAN#{}", plan_id)))
+                    .consistent_read(consistent)
+                    .build()?,
+            ),
+        )
+        .await?;
 
-        if let Some(item) = result.item {
-            let plan = WorkoutPlan {
-                id: item
-                    .get("WorkoutPlanId")
-                    .and_then(|v| v.as_s().ok())
-                    .map_or("", |v| v)
-                    .to_string(),
-                user_id: item
-                    .get("UserId")
-                    .and_then(|v| v.as_s().ok())
-                    .map_or("", |v| v)
-                    .to_string(),
-                name: item
-                    .get("Name")
-                    .and_then(|v| v.as_s().ok())
-                    .map_or("", |v| v)
-                    .to_string(),
-                description: item
-                    .get("Description")
-                    .and_then(|v| v.as_s().ok())
-                    .map(|s| s.to_string()),
-                difficulty: item
-                    .get("Difficulty")
-                    .and_then(|v| v.as_s().ok())
-                    .map_or("beginner", |v| v)
-                    .to_string(),
-                duration_weeks: item
-                    .get("DurationWeeks")
-                    .and_then(|v| v.as_n().ok())
-                    .and_then(|s| s.parse().ok())
-                    .unwrap_or(0),
-                frequency_per_week: item
-                    .get("FrequencyPerWeek")
-                    .and_then(|v| v.as_n().ok())
-                    .and_then(|s| s.parse().ok())
-                    .unwrap_or(0),
-                exercises: item
-                    .get("Exercises")
-                    .and_then(|v| v.as_s().ok())
-                    .and_then(|s| serde_json::from_str::<Vec<WorkoutExercise>>(s).ok())
-                    .unwrap_or_default(),
-                created_at: item
-                    .get("CreatedAt")
-                    .and_then(|v| v.as_s().ok())
-                    .map_or("", |v| v)
-                    .to_string(),
-                updated_at: item
-                    .get("UpdatedAt")
-                    .and_then(|v| v.as_s().ok())
-                    .map_or("", |v| v)
-                    .to_string(),
-                is_active: item
-                    .get("IsActive")
-                    .and_then(|v| v.as_bool().ok())
-                    .copied()
-                    .unwrap_or(true),
-                // Enhanced features
-                tags: item
-                    .get("Tags")
-                    .and_then(|v| v.as_s().ok())
-                    .and_then(|s| serde_json::from_str::<Vec<String>>(s).ok()),
-                rating: item
-                    .get("Rating")
-                    .and_then(|v| v.as_n().ok())
-                    .and_then(|n| n.parse().ok()),
-                is_template: item
-                    .get("IsTemplate")
-                    .and_then(|v| v.as_bool().ok())
-                    .copied(),
-                total_sessions: item
-                    .get("TotalSessions")
-                    .and_then(|v| v.as_n().ok())
-                    .and_then(|n| n.parse().ok()),
-                completed_sessions: item
-                    .get("CompletedSessions")
-                    .and_then(|v| v.as_n().ok())
-                    .and_then(|n| n.parse().ok()),
-                next_scheduled_date: item
-                    .get("NextScheduledDate")
-                    .and_then(|v| v.as_s().ok())
-                    .map(|s| s.clone()),
-            };
+        let item = get_or_404(without_deleted(result.item), "Workout plan", plan_id)?;
 
-            Ok(serde_json::to_value(plan)?)
-        } else {
-            Err("Workout plan not found".into())
-        }
+        let plan = WorkoutPlan {
+            id: item
+                .get("WorkoutPlanId")
+                .and_then(|v| v.as_s().ok())
+                .map_or("", |v| v)
+                .to_string(),
+            user_id: item
+                .get("UserId")
+                .and_then(|v| v.as_s().ok())
+                .map_or("", |v| v)
+                .to_string(),
+            name: item
+                .get("Name")
+                .and_then(|v| v.as_s().ok())
+                .map_or("", |v| v)
+                .to_string(),
+            description: item
+                .get("Description")
+                .and_then(|v| v.as_s().ok())
+                .map(|s| s.to_string()),
+            difficulty: item
+                .get("Difficulty")
+                .and_then(|v| v.as_s().ok())
+                .map_or("beginner", |v| v)
+                .to_string(),
+            duration_weeks: item
+                .get("DurationWeeks")
+                .and_then(|v| v.as_n().ok())
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0),
+            frequency_per_week: item
+                .get("FrequencyPerWeek")
+                .and_then(|v| v.as_n().ok())
+                .and_then(|s| s.parse().ok())
+                .unwrap_or(0),
+            exercises: item
+                .get("Exercises")
+                .and_then(|v| v.as_s().ok())
+                .and_then(|s| serde_json::from_str::<Vec<WorkoutExercise>>(s).ok())
+                .unwrap_or_default(),
+            created_at: item
+                .get("CreatedAt")
+                .and_then(|v| v.as_s().ok())
+                .map_or("", |v| v)
+                .to_string(),
+            updated_at: item
+                .get("UpdatedAt")
+                .and_then(|v| v.as_s().ok())
+                .map_or("", |v| v)
+                .to_string(),
+            is_active: item
+                .get("IsActive")
+                .and_then(|v| v.as_bool().ok())
+                .copied()
+                .unwrap_or(true),
+            // Enhanced features
+            tags: item
+                .get("Tags")
+                .and_then(|v| v.as_s().ok())
+                .and_then(|s| serde_json::from_str::<Vec<String>>(s).ok()),
+            rating: item
+                .get("Rating")
+                .and_then(|v| v.as_n().ok())
+                .and_then(|n| n.parse().ok()),
+            is_template: item
+                .get("IsTemplate")
+                .and_then(|v| v.as_bool().ok())
+                .copied(),
+            total_sessions: item
+                .get("TotalSessions")
+                .and_then(|v| v.as_n().ok())
+                .and_then(|n| n.parse().ok()),
+            completed_sessions: item
+                .get("CompletedSessions")
+                .and_then(|v| v.as_n().ok())
+                .and_then(|n| n.parse().ok()),
+            next_scheduled_date: item
+                .get("NextScheduledDate")
+                .and_then(|v| v.as_s().ok())
+                .map(|s| s.clone()),
+            version: item
+                .get("Version")
+                .and_then(|v| v.as_n().ok())
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(0),
+        };
+
+        Ok(serde_json::to_value(plan)?)
     }
 
+    /// Replace an existing plan only if it is still at `expected_version`. Plans written
+    /// before versioning have no `Version` attribute and are treated as version 0
     pub async fn update_workout_plan(
         &self,
         plan: &WorkoutPlan,
+        expected_version: i64,
     ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-        // Similar to create but with updated timestamp
-        self.create_workout_plan(plan).await
+        let condition = if expected_version == 0 {
+            "attribute_exists(PK) AND (attribute_not_exists(Version) OR Version = :expected_version)"
+        } else {
+            "attribute_exists(PK) AND Version = :expected_version"
+        };
+
+        time_phase("db", self.client
+            .put_item(
+                PutItemInput::builder()
+                    .table_name(&self.table_name)
+                    .set_item(Some(Self::workout_plan_item(plan)))
+                    .condition_expression(condition)
+                    .expression_attribute_values(
+                        ":expected_version",
+                        AttributeValue::N(expected_version.to_string()),
+                    )
+                    .return_values_on_condition_check_failure(ReturnValuesOnConditionCheckFailure::AllOld)
+                    .build()?,
+            ))
+            .await
+            .map_err(|e| {
+                // The old item only comes back when the plan exists but its version moved on
+                let stale = matches!(
+                    e.as_service_error(),
+                    Some(PutItemError::ConditionalCheckFailedException(failure)) if failure.item().is_some()
+                );
+                let on_condition_failed = if stale {
+                    error_helpers::conflict("Workout plan has been modified since it was retrieved")
+                } else {
+                    error_helpers::resource_not_found("Workout plan")
+                };
+                error_helpers::conditional_write_error(e, on_condition_failed)
+            })?;
+
+        Ok(serde_json::to_value(plan)?)
     }
 
+    /// Mark a plan deleted and let the table TTL purge it later; `restore_workout_plan`
+    /// can bring it back until then
     pub async fn delete_workout_plan(
         &self,
         user_id: &str,
         plan_id: &str,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        self.client
-            .delete_item()
-            .table_name(&self.table_name)
-            .key("PK", AttributeValue::S(format!("USER#{}", user_id)))
-            .key("SK", AttributeValue::S(format!("WORKOUT_PLAN#{}", plan_id)))
-            .send()
-            .await?;
+        let now = Utc::now();
+
+        time_phase(
+            "db",
+            self.client.update_item(
+                UpdateItemInput::builder()
+                    .table_name(&self.table_name)
+                    .key("PK", AttributeValue::S(format!("USER#{}", user_id)))
+                    .key("SK", AttributeValue::S(format!("WORKOUT_PLAN#{}", plan_id)))
+                    .update_expression("SET DeletedAt = :deleted_at, #ttl = :ttl")
+                    .condition_expression(
+                        "attribute_exists(PK) AND attribute_not_exists(DeletedAt)",
+                    )
+                    .expression_attribute_names("#ttl", "ttl")
+                    .expression_attribute_values(":deleted_at", AttributeValue::S(now.to_rfc3339()))
+                    .expression_attribute_values(
+                        ":ttl",
+                        AttributeValue::N(soft_delete_expiry(now).to_string()),
+                    )
+                    .build()?,
+            ),
+        )
+        .await
+        .map_err(|e| {
+            error_helpers::conditional_write_error(
+                e,
+                error_helpers::resource_not_found("Workout plan"),
+            )
+        })?;
 
         Ok(())
     }
+
+    /// Undo a soft delete. Expired items can linger until DynamoDB gets round to
+    /// purging them, so the TTL is checked here rather than trusted
+    pub async fn restore_workout_plan(
+        &self,
+        user_id: &str,
+        plan_id: &str,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        time_phase(
+            "db",
+            self.client.update_item(
+                UpdateItemInput::builder()
+                    .table_name(&self.table_name)
+                    .key("PK", AttributeValue::S(format!("USER#{}", user_id)))
+                    .key("SK", AttributeValue::S(format!("WORKOUT_PLAN#{}", plan_id)))
+                    .update_expression("REMOVE DeletedAt, #ttl")
+                    .condition_expression("attribute_exists(DeletedAt) AND #ttl > :now")
+                    .expression_attribute_names("#ttl", "ttl")
+                    .expression_attribute_values(
+                        ":now",
+                        AttributeValue::N(Utc::now().timestamp().to_string()),
+                    )
+                    .build()?,
+            ),
+        )
+        .await
+        .map_err(|e| {
+            error_helpers::conditional_write_error(
+                e,
+                error_helpers::not_found(&format!(
+                    "No deleted workout plan '{}' to restore",
+                    plan_id
+                )),
+            )
+        })?;
+
+        self.get_workout_plan(user_id, plan_id, true).await
+    }
 }
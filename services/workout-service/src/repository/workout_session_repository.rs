@@ -1,480 +1,785 @@
 use anyhow::Result;
-use aws_sdk_dynamodb::{types::AttributeValue, Client as DynamoDbClient};
+use aws_sdk_dynamodb::{
+    error::ProvideErrorMetadata,
+    operation::{
+        get_item::GetItemInput,
+        query::{builders::QueryInputBuilder, QueryInput},
+        scan::ScanInput,
+        update_item::UpdateItemInput,
+    },
+    types::{AttributeValue, Delete, Put, ReturnValue, Select, TransactWriteItem},
+    Client as DynamoDbClient,
+};
+use chrono::{DateTime, Utc};
 use serde_json::Value;
-use tracing::{error, info};
+use std::collections::HashMap;
+use tracing::info;
 
 use crate::models::*;
+use crate::repository::{
+    get_or_404, soft_delete_expiry, transact_write, without_deleted, DynamoOps, NOT_DELETED_FILTER,
+};
+use crate::utils::error::{helpers as error_helpers, ServiceError};
+use crate::utils::update_expression::{UpdateExpression, UpdateExpressionBuilder};
+
+/// Sort-key prefix shared by both session key forms
+const SESSION_SK_PREFIX: &str = "SESSION#";
+/// Sort-key prefix of the items pointing a session id at its time-prefixed key
+const SESSION_POINTER_SK_PREFIX: &str = "SESSIONID#";
+/// Attribute of a pointer item holding the session's sort key
+const SESSION_SK_ATTRIBUTE: &str = "SessionSK";
+
+/// Times an append of sets is tried when the session changes between read and write
+const APPEND_SETS_ATTEMPTS: u32 = 3;
+
+type Item = HashMap<String, AttributeValue>;
+
+/// `SESSION#{started_at}#{id}`. Sessions sort by start time within a user's partition, so
+/// a newest-first query with a limit returns the most recent ones without reading the
+/// rest. Sessions written before this used `SESSION#{id}`; reads accept both forms, and
+/// `migrate_legacy_sessions` or an update moves a legacy item over to the new key
+pub fn session_sort_key(started_at: &str, session_id: &str) -> String {
+    format!("{}{}#{}", SESSION_SK_PREFIX, started_at, session_id)
+}
+
+/// `SESSIONID#{id}`, the key of the item recording which sort key a time-keyed session is
+/// stored under, so a lookup by id reads two keys instead of the user's whole history. It
+/// doesn't begin with `SESSION#`, so session queries never read it
+fn session_pointer_key(session_id: &str) -> String {
+    format!("{}{}", SESSION_POINTER_SK_PREFIX, session_id)
+}
+
+fn is_legacy_session_key(sk: &str) -> bool {
+    sk.strip_prefix(SESSION_SK_PREFIX)
+        .is_some_and(|rest| !rest.contains('#'))
+}
 
 #[derive(Clone)]
-pub struct WorkoutSessionRepository {
-    client: DynamoDbClient,
+pub struct WorkoutSessionRepository<C = DynamoDbClient> {
+    client: C,
     table_name: String,
 }
 
-impl WorkoutSessionRepository {
-    pub fn new(client: DynamoDbClient, table_name: String) -> Self {
+impl<C: DynamoOps> WorkoutSessionRepository<C> {
+    pub fn new(client: C, table_name: String) -> Self {
         Self { client, table_name }
     }
 
+    /// A user's sessions, newest first. With `limit`, the query reads pages in sort-key
+    /// order and stops once it has that many time-keyed sessions; legacy-keyed sessions
+    /// (not yet migrated) only show up if they were read along the way. `consistent`
+    /// makes the reads strongly consistent, so a session written just before is included
     pub async fn get_workout_sessions(
         &self,
         user_id: Option<String>,
         workout_plan_id: Option<String>,
+        limit: Option<usize>,
+        consistent: bool,
     ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-        let mut query = if let Some(uid) = user_id {
-            self.client
-                .query()
-                .table_name(&self.table_name)
-                .key_condition_expression("PK = :pk AND begins_with(SK, :sk_prefix)")
-                .expression_attribute_values(":pk", AttributeValue::S(format!("USER#{}", uid)))
-                .expression_attribute_values(
-                    ":sk_prefix",
-                    AttributeValue::S("SESSION#".to_string()),
-                )
-        } else {
+        let Some(uid) = user_id else {
             // If no user_id provided, scan all users' sessions - this is expensive and should be avoided
             return Ok(serde_json::to_value(Vec::<WorkoutSession>::new())?);
         };
 
-        // Add filter expression if workout_plan_id is provided
-        if let Some(plan_id) = workout_plan_id {
-            query = query
-                .filter_expression("WorkoutPlanId = :planId")
-                .expression_attribute_values(":planId", AttributeValue::S(plan_id));
+        let mut sessions = Vec::new();
+        let mut keyed_sessions = 0;
+        let mut exclusive_start_key = None;
+
+        loop {
+            let query = self
+                .sessions_query(&uid, workout_plan_id.as_deref(), consistent)
+                .set_limit(limit.map(|limit| limit as i32))
+                .set_exclusive_start_key(exclusive_start_key.take());
+
+            let result = self.client.query(query.build()?).await?;
+
+            for item in result.items.unwrap_or_default() {
+                let legacy = item
+                    .get("SK")
+                    .and_then(|v| v.as_s().ok())
+                    .is_some_and(|sk| is_legacy_session_key(sk));
+                if let Some(session) = session_from_item(&item) {
+                    sessions.push(session);
+                    if !legacy {
+                        keyed_sessions += 1;
+                    }
+                }
+            }
+
+            exclusive_start_key = result.last_evaluated_key;
+            let enough = limit.is_some_and(|limit| keyed_sessions >= limit);
+            if enough || exclusive_start_key.is_none() {
+                break;
+            }
         }
 
-        let result = query.send().await?;
-
-        let sessions: Vec<WorkoutSession> = result
-            .items
-            .unwrap_or_default()
-            .into_iter()
-            .filter_map(|item| {
-                Some(WorkoutSession {
-                    id: item
-                        .get("SessionId")
-                        .or_else(|| item.get("id"))
-                        .and_then(|v| v.as_s().ok())?
-                        .clone(),
-                    user_id: item
-                        .get("UserId")
-                        .or_else(|| item.get("userId"))
-                        .and_then(|v| v.as_s().ok())?
-                        .clone(),
-                    workout_plan_id: item
-                        .get("WorkoutPlanId")
-                        .or_else(|| item.get("workoutPlanId"))
-                        .and_then(|v| v.as_s().ok())
-                        .map(|s| s.clone()),
-                    name: item
-                        .get("Name")
-                        .or_else(|| item.get("name"))
-                        .and_then(|v| v.as_s().ok())?
-                        .clone(),
-                    started_at: item
-                        .get("StartedAt")
-                        .or_else(|| item.get("startedAt"))
-                        .and_then(|v| v.as_s().ok())?
-                        .clone(),
-                    completed_at: item
-                        .get("CompletedAt")
-                        .or_else(|| item.get("completedAt"))
-                        .and_then(|v| v.as_s().ok())
-                        .map(|s| s.clone()),
-                    duration_minutes: item
-                        .get("DurationMinutes")
-                        .or_else(|| item.get("durationMinutes"))
-                        .and_then(|v| v.as_n().ok())
-                        .and_then(|s| s.parse().ok()),
-                    notes: item
-                        .get("Notes")
-                        .or_else(|| item.get("notes"))
-                        .and_then(|v| v.as_s().ok())
-                        .map(|s| s.clone()),
-                    rating: item
-                        .get("Rating")
-                        .or_else(|| item.get("rating"))
-                        .and_then(|v| v.as_n().ok())
-                        .and_then(|s| s.parse().ok()),
-                    created_at: item
-                        .get("CreatedAt")
-                        .or_else(|| item.get("createdAt"))
-                        .and_then(|v| v.as_s().ok())?
-                        .clone(),
-                    updated_at: item
-                        .get("UpdatedAt")
-                        .or_else(|| item.get("updatedAt"))
-                        .and_then(|v| v.as_s().ok())?
-                        .clone(),
-                    exercises: item
-                        .get("exercises")
-                        .and_then(|v| v.as_l().ok())
-                        .map(|list| {
-                            list.iter()
-                                .filter_map(|v| {
-                                    let obj = v.as_m().ok()?;
-                                    Some(SessionExercise {
-                                        exercise_id: obj.get("exerciseId")?.as_s().ok()?.clone(),
-                                        name: obj.get("name")?.as_s().ok()?.clone(),
-                                        sets: obj
-                                            .get("sets")
-                                            .and_then(|v| v.as_l().ok())
-                                            .map(|sets| {
-                                                sets.iter()
-                                                    .filter_map(|set| {
-                                                        let set_obj = set.as_m().ok()?;
-                                                        Some(ExerciseSet {
-                                                            set_number: set_obj
-                                                                .get("setNumber")?
-                                                                .as_n()
-                                                                .ok()?
-                                                                .parse()
-                                                                .ok()?,
-                                                            reps: set_obj
-                                                                .get("reps")
-                                                                .and_then(|v| v.as_n().ok())
-                                                                .and_then(|s| s.parse().ok()),
-                                                            weight: set_obj
-                                                                .get("weight")
-                                                                .and_then(|v| v.as_n().ok())
-                                                                .and_then(|s| s.parse().ok()),
-                                                            duration_seconds: set_obj
-                                                                .get("durationSeconds")
-                                                                .and_then(|v| v.as_n().ok())
-                                                                .and_then(|s| s.parse().ok()),
-                                                            rest_seconds: set_obj
-                                                                .get("restSeconds")
-                                                                .and_then(|v| v.as_n().ok())
-                                                                .and_then(|s| s.parse().ok()),
-                                                            completed: *set_obj
-                                                                .get("completed")?
-                                                                .as_bool()
-                                                                .ok()?,
-                                                            notes: set_obj
-                                                                .get("notes")
-                                                                .and_then(|v| v.as_s().ok())
-                                                                .map(|s| s.clone()),
-                                                        })
-                                                    })
-                                                    .collect()
-                                            })
-                                            .unwrap_or_default(),
-                                        notes: obj
-                                            .get("notes")
-                                            .and_then(|v| v.as_s().ok())
-                                            .map(|s| s.clone()),
-                                        order: obj.get("order")?.as_n().ok()?.parse().ok()?,
-                                    })
-                                })
-                                .collect()
-                        })
-                        .unwrap_or_default(),
-                })
-            })
-            .collect();
+        // Legacy keys don't sort by time, so order what was read by start time
+        sessions.sort_by(|a, b| b.started_at.cmp(&a.started_at));
+        if let Some(limit) = limit {
+            sessions.truncate(limit);
+        }
 
         Ok(serde_json::to_value(sessions)?)
     }
 
-    pub async fn create_workout_session(
+    /// One page of a user's sessions in sort-key order, newest first, starting after the
+    /// `start_after` sort key. Returns the sessions and, when more remain, the sort key
+    /// of the last one read to pass back as the next page's start. Legacy-keyed sessions
+    /// appear wherever their key sorts
+    pub async fn get_workout_sessions_page(
         &self,
-        session: &WorkoutSession,
-    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-        let mut item = std::collections::HashMap::new();
-        item.insert(
-            "PK".to_string(),
-            AttributeValue::S(format!("USER#{}", session.user_id)),
-        );
-        item.insert(
-            "SK".to_string(),
-            AttributeValue::S(format!("SESSION#{}", session.id)),
-        );
-        item.insert(
-            "SessionId".to_string(),
-            AttributeValue::S(session.id.clone()),
-        );
-        item.insert(
-            "UserId".to_string(),
-            AttributeValue::S(session.user_id.clone()),
-        );
-        item.insert("Name".to_string(), AttributeValue::S(session.name.clone()));
-        item.insert(
-            "StartedAt".to_string(),
-            AttributeValue::S(session.started_at.clone()),
-        );
-        item.insert(
-            "CreatedAt".to_string(),
-            AttributeValue::S(session.created_at.clone()),
-        );
-        item.insert(
-            "UpdatedAt".to_string(),
-            AttributeValue::S(session.updated_at.clone()),
-        );
+        user_id: &str,
+        workout_plan_id: Option<&str>,
+        limit: usize,
+        start_after: Option<&str>,
+        consistent: bool,
+    ) -> Result<(Vec<WorkoutSession>, Option<String>), Box<dyn std::error::Error + Send + Sync>>
+    {
+        let pk = AttributeValue::S(format!("USER#{}", user_id));
+        let mut sessions = Vec::new();
+        let mut exclusive_start_key = start_after.map(|sk| {
+            HashMap::from([
+                ("PK".to_string(), pk.clone()),
+                ("SK".to_string(), AttributeValue::S(sk.to_string())),
+            ])
+        });
 
-        if let Some(plan_id) = &session.workout_plan_id {
-            item.insert(
-                "WorkoutPlanId".to_string(),
-                AttributeValue::S(plan_id.clone()),
-            );
-        }
-        if let Some(completed_at) = &session.completed_at {
-            item.insert(
-                "CompletedAt".to_string(),
-                AttributeValue::S(completed_at.clone()),
+        // Read no more than the page still needs, so the last key read is exactly where
+        // the next page starts even when the filter drops items
+        loop {
+            let query = self
+                .sessions_query(user_id, workout_plan_id, consistent)
+                .limit((limit - sessions.len()) as i32)
+                .set_exclusive_start_key(exclusive_start_key.take());
+            let result = self.client.query(query.build()?).await?;
+
+            sessions.extend(
+                result
+                    .items
+                    .unwrap_or_default()
+                    .iter()
+                    .filter_map(session_from_item),
             );
+
+            exclusive_start_key = result.last_evaluated_key;
+            if sessions.len() >= limit || exclusive_start_key.is_none() {
+                break;
+            }
         }
-        if let Some(duration) = session.duration_minutes {
-            item.insert(
-                "DurationMinutes".to_string(),
-                AttributeValue::N(duration.to_string()),
-            );
+
+        let next_start =
+            exclusive_start_key.and_then(|key| key.get("SK").and_then(|v| v.as_s().ok()).cloned());
+        Ok((sessions, next_start))
+    }
+
+    /// How many sessions a user has (optionally for one plan), counted with
+    /// `Select::Count` so no items are read back
+    pub async fn count_workout_sessions(
+        &self,
+        user_id: &str,
+        workout_plan_id: Option<&str>,
+    ) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        let mut total = 0;
+        let mut exclusive_start_key = None;
+
+        loop {
+            let query = self
+                .sessions_query(user_id, workout_plan_id, false)
+                .select(Select::Count)
+                .set_exclusive_start_key(exclusive_start_key.take());
+            let result = self.client.query(query.build()?).await?;
+
+            total += result.count.max(0) as u64;
+            exclusive_start_key = result.last_evaluated_key;
+            if exclusive_start_key.is_none() {
+                return Ok(total);
+            }
         }
-        if let Some(notes) = &session.notes {
-            item.insert("Notes".to_string(), AttributeValue::S(notes.clone()));
+    }
+
+    /// A newest-first query over a user's sessions. Soft-deleted sessions are always
+    /// hidden; `workout_plan_id` narrows to one plan
+    fn sessions_query(
+        &self,
+        user_id: &str,
+        workout_plan_id: Option<&str>,
+        consistent: bool,
+    ) -> QueryInputBuilder {
+        let query = QueryInput::builder()
+            .table_name(&self.table_name)
+            .key_condition_expression("PK = :pk AND begins_with(SK, :sk_prefix)")
+            .expression_attribute_values(":pk", AttributeValue::S(format!("USER#{}", user_id)))
+            .expression_attribute_values(
+                ":sk_prefix",
+                AttributeValue::S(SESSION_SK_PREFIX.to_string()),
+            )
+            .scan_index_forward(false)
+            .consistent_read(consistent);
+
+        match workout_plan_id {
+            Some(plan_id) => query
+                .filter_expression(format!(
+                    "WorkoutPlanId = :planId AND {}",
+                    NOT_DELETED_FILTER
+                ))
+                .expression_attribute_values(":planId", AttributeValue::S(plan_id.to_string())),
+            None => query.filter_expression(NOT_DELETED_FILTER),
         }
-        if let Some(rating) = session.rating {
-            item.insert("Rating".to_string(), AttributeValue::N(rating.to_string()));
+    }
+
+    /// Move `user_id`'s sessions still under legacy `SESSION#{id}` keys to time-prefixed
+    /// keys, soft-deleted ones included, and return how many moved. A session written
+    /// between the read and its move is skipped and left for the next run
+    pub async fn migrate_legacy_sessions(
+        &self,
+        user_id: &str,
+    ) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let mut legacy_items = Vec::new();
+        let mut exclusive_start_key = None;
+
+        loop {
+            let result = self
+                .client
+                .query(
+                    QueryInput::builder()
+                        .table_name(&self.table_name)
+                        .key_condition_expression("PK = :pk AND begins_with(SK, :sk_prefix)")
+                        .expression_attribute_values(
+                            ":pk",
+                            AttributeValue::S(format!("USER#{}", user_id)),
+                        )
+                        .expression_attribute_values(
+                            ":sk_prefix",
+                            AttributeValue::S(SESSION_SK_PREFIX.to_string()),
+                        )
+                        .consistent_read(true)
+                        .set_exclusive_start_key(exclusive_start_key.take())
+                        .build()?,
+                )
+                .await?;
+
+            legacy_items.extend(result.items.unwrap_or_default().into_iter().filter(|item| {
+                item.get("SK")
+                    .and_then(|v| v.as_s().ok())
+                    .is_some_and(|sk| is_legacy_session_key(sk))
+            }));
+
+            exclusive_start_key = result.last_evaluated_key;
+            if exclusive_start_key.is_none() {
+                break;
+            }
         }
 
-        // Add exercises as a list of maps
-        let exercises: Vec<AttributeValue> = session
-            .exercises
-            .iter()
-            .map(|exercise| {
-                let mut exercise_map = std::collections::HashMap::new();
-                exercise_map.insert(
-                    "exerciseId".to_string(),
-                    AttributeValue::S(exercise.exercise_id.clone()),
-                );
-                exercise_map.insert("name".to_string(), AttributeValue::S(exercise.name.clone()));
-                exercise_map.insert(
-                    "order".to_string(),
-                    AttributeValue::N(exercise.order.to_string()),
-                );
-
-                if let Some(notes) = &exercise.notes {
-                    exercise_map.insert("notes".to_string(), AttributeValue::S(notes.clone()));
-                }
+        let mut migrated = 0;
+        for item in legacy_items {
+            match self.migrate_legacy_session(item).await {
+                Ok(true) => migrated += 1,
+                Ok(false) => {}
+                Err(e) => match e.downcast_ref::<ServiceError>() {
+                    Some(ServiceError::Conflict(..)) => {
+                        info!("Skipped a session that changed while migrating: {}", e)
+                    }
+                    _ => return Err(e),
+                },
+            }
+        }
+        Ok(migrated)
+    }
 
-                // Add sets
-                let sets: Vec<AttributeValue> = exercise
-                    .sets
-                    .iter()
-                    .map(|set| {
-                        let mut set_map = std::collections::HashMap::new();
-                        set_map.insert(
-                            "setNumber".to_string(),
-                            AttributeValue::N(set.set_number.to_string()),
-                        );
-                        set_map
-                            .insert("completed".to_string(), AttributeValue::Bool(set.completed));
-
-                        if let Some(reps) = set.reps {
-                            set_map.insert("reps".to_string(), AttributeValue::N(reps.to_string()));
-                        }
-                        if let Some(weight) = set.weight {
-                            set_map.insert(
-                                "weight".to_string(),
-                                AttributeValue::N(weight.to_string()),
-                            );
-                        }
-                        if let Some(duration) = set.duration_seconds {
-                            set_map.insert(
-                                "durationSeconds".to_string(),
-                                AttributeValue::N(duration.to_string()),
-                            );
-                        }
-                        if let Some(rest) = set.rest_seconds {
-                            set_map.insert(
-                                "restSeconds".to_string(),
-                                AttributeValue::N(rest.to_string()),
-                            );
-                        }
-                        if let Some(notes) = &set.notes {
-                            set_map.insert("notes".to_string(), AttributeValue::S(notes.clone()));
-                        }
-
-                        AttributeValue::M(set_map)
-                    })
-                    .collect();
-                exercise_map.insert("sets".to_string(), AttributeValue::L(sets));
+    /// Rewrite a `SESSION#{id}` item under its time-prefixed key, along with its pointer
+    /// item, in one transaction. The
+    /// new key must be free and the legacy item unchanged since it was read (same
+    /// `UpdatedAt`), so a concurrent update is never overwritten by this stale copy.
+    /// Returns false for items without the attributes the key is built from
+    async fn migrate_legacy_session(
+        &self,
+        mut item: Item,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let (Some(legacy_key), Some(session_id), Some(started_at)) = (
+            item.get("SK").and_then(|v| v.as_s().ok()).cloned(),
+            item.get("SessionId").and_then(|v| v.as_s().ok()).cloned(),
+            item.get("StartedAt").and_then(|v| v.as_s().ok()).cloned(),
+        ) else {
+            return Ok(false);
+        };
+        let pk = item.get("PK").cloned().ok_or("Session item has no PK")?;
+        let user_id = pk
+            .as_s()
+            .ok()
+            .and_then(|pk| pk.strip_prefix("USER#"))
+            .ok_or("Session item has no user PK")?
+            .to_string();
+        let key = session_sort_key(&started_at, &session_id);
 
-                AttributeValue::M(exercise_map)
-            })
-            .collect();
-        item.insert("exercises".to_string(), AttributeValue::L(exercises));
+        let delete = Delete::builder()
+            .table_name(&self.table_name)
+            .key("PK", pk)
+            .key("SK", AttributeValue::S(legacy_key));
+        let delete = match item.get("UpdatedAt") {
+            Some(updated_at) => delete
+                .condition_expression("attribute_exists(PK) AND UpdatedAt = :updated_at")
+                .expression_attribute_values(":updated_at", updated_at.clone()),
+            None => delete
+                .condition_expression("attribute_exists(PK) AND attribute_not_exists(UpdatedAt)"),
+        };
 
-        self.client
-            .put_item()
+        item.insert("SK".to_string(), AttributeValue::S(key.clone()));
+        let put = Put::builder()
             .table_name(&self.table_name)
             .set_item(Some(item))
-            .send()
+            .condition_expression("attribute_not_exists(PK)");
+
+        transact_write(
+            &self.client,
+            vec![
+                TransactWriteItem::builder().put(put.build()?).build(),
+                TransactWriteItem::builder().delete(delete.build()?).build(),
+                self.session_pointer_write(&user_id, &session_id, &key)?,
+            ],
+        )
+        .await?;
+
+        info!("Migrated session {} to a time-prefixed key", session_id);
+        Ok(true)
+    }
+
+    /// The sort key `session_id` is stored under in `user_id`'s partition, in either key
+    /// form, including soft-deleted sessions
+    async fn find_session_key(
+        &self,
+        user_id: &str,
+        session_id: &str,
+    ) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let item = self.find_session_item(user_id, session_id).await?;
+        Ok(item.and_then(|item| item.get("SK").and_then(|v| v.as_s().ok()).cloned()))
+    }
+
+    /// The item for `session_id` in `user_id`'s partition, in either key form, including
+    /// soft-deleted sessions. Both reads are strongly consistent key lookups: the pointer
+    /// item gives a time-keyed session's sort key, and a session without one is read
+    /// under its legacy `SESSION#{id}` key
+    async fn find_session_item(
+        &self,
+        user_id: &str,
+        session_id: &str,
+    ) -> Result<Option<Item>, Box<dyn std::error::Error + Send + Sync>> {
+        let pointer = self
+            .get_user_item(user_id, session_pointer_key(session_id))
             .await?;
+        let sk = pointer
+            .as_ref()
+            .and_then(|pointer| pointer.get(SESSION_SK_ATTRIBUTE))
+            .and_then(|v| v.as_s().ok())
+            .cloned()
+            .unwrap_or_else(|| format!("{}{}", SESSION_SK_PREFIX, session_id));
+        self.get_user_item(user_id, sk).await
+    }
+
+    /// The item under `sk` in `user_id`'s partition, read strongly consistent
+    async fn get_user_item(
+        &self,
+        user_id: &str,
+        sk: String,
+    ) -> Result<Option<Item>, Box<dyn std::error::Error + Send + Sync>> {
+        let output = self
+            .client
+            .get_item(
+                GetItemInput::builder()
+                    .table_name(&self.table_name)
+                    .key("PK", AttributeValue::S(format!("USER#{}", user_id)))
+                    .key("SK", AttributeValue::S(sk))
+                    .consistent_read(true)
+                    .build()?,
+            )
+            .await?;
+        Ok(output.item)
+    }
+
+    /// A transaction write pointing `session_id` at `sk` (see `session_pointer_key`)
+    fn session_pointer_write(
+        &self,
+        user_id: &str,
+        session_id: &str,
+        sk: &str,
+    ) -> Result<TransactWriteItem, Box<dyn std::error::Error + Send + Sync>> {
+        let put = Put::builder()
+            .table_name(&self.table_name)
+            .item("PK", AttributeValue::S(format!("USER#{}", user_id)))
+            .item("SK", AttributeValue::S(session_pointer_key(session_id)))
+            .item(SESSION_SK_ATTRIBUTE, AttributeValue::S(sk.to_string()))
+            .build()?;
+        Ok(TransactWriteItem::builder().put(put).build())
+    }
+
+    /// One of `user_id`'s sessions that is neither deleted nor completed yet, with its key.
+    /// Completed sessions fail with a conflict
+    async fn find_in_progress_session(
+        &self,
+        user_id: &str,
+        session_id: &str,
+    ) -> Result<Item, Box<dyn std::error::Error + Send + Sync>> {
+        let item = without_deleted(self.find_session_item(user_id, session_id).await?);
+        let item = get_or_404(item, "Workout session", session_id)?;
+        if item.contains_key("CompletedAt") {
+            return Err(error_helpers::conflict("Workout session is already completed").into());
+        }
+        Ok(item)
+    }
+
+    /// Log sets to one of `user_id`'s in-progress sessions without rewriting the rest of
+    /// the item. Sets for an exercise the session already has go on the end of its
+    /// `sets`; other exercises are added after the existing ones. Each write is
+    /// conditioned on the list being as it was read, and retried on a concurrent change
+    pub async fn append_session_sets(
+        &self,
+        user_id: &str,
+        session_id: &str,
+        exercises: &[SessionExercise],
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        let mut attempt = 1;
+
+        loop {
+            let item = self.find_in_progress_session(user_id, session_id).await?;
+            let sk = item["SK"].clone();
+            let stored_ids = item
+                .get("exercises")
+                .and_then(|v| v.as_l().ok())
+                .map(|list| {
+                    list.iter()
+                        .map(|exercise| {
+                            exercise
+                                .as_m()
+                                .ok()
+                                .and_then(|exercise| exercise.get("exerciseId"))
+                                .and_then(|v| v.as_s().ok())
+                                .cloned()
+                                .unwrap_or_default()
+                        })
+                        .collect::<Vec<_>>()
+                });
+            let (update, condition) =
+                append_sets_update(stored_ids.as_deref(), exercises, &Utc::now().to_rfc3339());
+
+            let result = self
+                .client
+                .update_item(
+                    update
+                        .apply(
+                            UpdateItemInput::builder()
+                                .table_name(&self.table_name)
+                                .key("PK", AttributeValue::S(format!("USER#{}", user_id)))
+                                .key("SK", sk),
+                        )
+                        .condition_expression(condition)
+                        .return_values(ReturnValue::AllNew)
+                        .build()?,
+                )
+                .await;
+
+            match result {
+                Ok(output) => return updated_session(output.attributes),
+                Err(e)
+                    if e.code() == Some("ConditionalCheckFailedException")
+                        && attempt < APPEND_SETS_ATTEMPTS =>
+                {
+                    attempt += 1;
+                }
+                Err(e) => {
+                    return Err(error_helpers::conditional_write_error(
+                        e,
+                        error_helpers::conflict(
+                            "Workout session changed while logging sets, please retry",
+                        ),
+                    ))
+                }
+            }
+        }
+    }
+
+    /// Finish one of `user_id`'s in-progress sessions at `completed_at`, working out its
+    /// duration from the start time. Exercises are left untouched
+    pub async fn complete_workout_session(
+        &self,
+        user_id: &str,
+        session_id: &str,
+        completed_at: DateTime<Utc>,
+        notes: Option<String>,
+        rating: Option<i32>,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        let item = self.find_in_progress_session(user_id, session_id).await?;
+        let sk = item["SK"].clone();
+        let duration_minutes = item
+            .get("StartedAt")
+            .or_else(|| item.get("startedAt"))
+            .and_then(|v| v.as_s().ok())
+            .and_then(|started_at| DateTime::parse_from_rfc3339(started_at).ok())
+            .map(|started_at| {
+                (completed_at - started_at.with_timezone(&Utc))
+                    .num_minutes()
+                    .max(0)
+            });
+
+        let mut update = UpdateExpressionBuilder::new()
+            .set("CompletedAt", completed_at.to_rfc3339())
+            .set("UpdatedAt", Utc::now().to_rfc3339());
+        if let Some(duration_minutes) = duration_minutes {
+            update = update.set("DurationMinutes", duration_minutes);
+        }
+        if let Some(notes) = notes {
+            update = update.set("Notes", notes);
+        }
+        if let Some(rating) = rating {
+            update = update.set("Rating", rating);
+        }
+
+        let output = self
+            .client
+            .update_item(
+                update
+                    .build()
+                    .apply(
+                        UpdateItemInput::builder()
+                            .table_name(&self.table_name)
+                            .key("PK", AttributeValue::S(format!("USER#{}", user_id)))
+                            .key("SK", sk),
+                    )
+                    .condition_expression(
+                        "attribute_exists(PK) AND attribute_not_exists(CompletedAt) \
+                         AND attribute_not_exists(DeletedAt)",
+                    )
+                    .return_values(ReturnValue::AllNew)
+                    .build()?,
+            )
+            .await
+            .map_err(|e| {
+                error_helpers::conditional_write_error(
+                    e,
+                    error_helpers::conflict("Workout session is already completed"),
+                )
+            })?;
+
+        updated_session(output.attributes)
+    }
+
+    /// Write a new session and its pointer item together
+    pub async fn create_workout_session(
+        &self,
+        session: &WorkoutSession,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        let key = session_sort_key(&session.started_at, &session.id);
+        let put = Put::builder()
+            .table_name(&self.table_name)
+            .set_item(Some(session_item(session)))
+            .build()?;
+        transact_write(
+            &self.client,
+            vec![
+                TransactWriteItem::builder().put(put).build(),
+                self.session_pointer_write(&session.user_id, &session.id, &key)?,
+            ],
+        )
+        .await?;
 
         Ok(serde_json::to_value(session)?)
     }
 
+    /// One session by id; `consistent` makes the scan strongly consistent
     pub async fn get_workout_session(
         &self,
         session_id: &str,
+        consistent: bool,
     ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
         // Since we changed to USER#{user_id} pattern but don't have user_id here,
         // we need to scan for the session. This is less efficient but necessary.
         let result = self
             .client
-            .scan()
-            .table_name(&self.table_name)
-            .filter_expression("SK = :sk")
-            .expression_attribute_values(
-                ":sk",
-                AttributeValue::S(format!("SESSION#{}", session_id)),
+            .scan(
+                ScanInput::builder()
+                    .table_name(&self.table_name)
+                    .filter_expression(format!(
+                        "SessionId = :session_id AND begins_with(SK, :sk_prefix) AND {}",
+                        NOT_DELETED_FILTER
+                    ))
+                    .expression_attribute_values(
+                        ":session_id",
+                        AttributeValue::S(session_id.to_string()),
+                    )
+                    .expression_attribute_values(
+                        ":sk_prefix",
+                        AttributeValue::S(SESSION_SK_PREFIX.to_string()),
+                    )
+                    .consistent_read(consistent)
+                    .build()?,
             )
-            .send()
             .await?;
 
         let item = result.items.and_then(|items| items.into_iter().next());
 
-        if let Some(item) = item {
-            let session = WorkoutSession {
-                id: item
-                    .get("SessionId")
-                    .or_else(|| item.get("id"))
-                    .and_then(|v| v.as_s().ok())
-                    .map_or("", |v| v)
-                    .to_string(),
-                user_id: item
-                    .get("UserId")
-                    .or_else(|| item.get("userId"))
-                    .and_then(|v| v.as_s().ok())
-                    .map_or("", |v| v)
-                    .to_string(),
-                workout_plan_id: item
-                    .get("WorkoutPlanId")
-                    .or_else(|| item.get("workoutPlanId"))
-                    .and_then(|v| v.as_s().ok())
-                    .map(|s| s.to_string()),
-                name: item
-                    .get("Name")
-                    .or_else(|| item.get("name"))
-                    .and_then(|v| v.as_s().ok())
-                    .map_or("", |v| v)
-                    .to_string(),
-                started_at: item
-                    .get("StartedAt")
-                    .or_else(|| item.get("startedAt"))
-                    .and_then(|v| v.as_s().ok())
-                    .map_or("", |v| v)
-                    .to_string(),
-                completed_at: item
-                    .get("CompletedAt")
-                    .or_else(|| item.get("completedAt"))
-                    .and_then(|v| v.as_s().ok())
-                    .map(|s| s.to_string()),
-                duration_minutes: item
-                    .get("DurationMinutes")
-                    .or_else(|| item.get("durationMinutes"))
-                    .and_then(|v| v.as_n().ok())
-                    .and_then(|s| s.parse().ok()),
-                notes: item
-                    .get("Notes")
-                    .or_else(|| item.get("notes"))
-                    .and_then(|v| v.as_s().ok())
-                    .map(|s| s.to_string()),
-                rating: item
-                    .get("Rating")
-                    .or_else(|| item.get("rating"))
-                    .and_then(|v| v.as_n().ok())
-                    .and_then(|s| s.parse().ok()),
-                created_at: item
-                    .get("CreatedAt")
-                    .or_else(|| item.get("createdAt"))
-                    .and_then(|v| v.as_s().ok())
-                    .map_or("", |v| v)
-                    .to_string(),
-                updated_at: item
-                    .get("UpdatedAt")
-                    .or_else(|| item.get("updatedAt"))
-                    .and_then(|v| v.as_s().ok())
-                    .map_or("", |v| v)
-                    .to_string(),
-                exercises: item
-                    .get("exercises")
-                    .and_then(|v| v.as_l().ok())
-                    .map(|list| {
-                        list.iter()
-                            .filter_map(|v| {
-                                let obj = v.as_m().ok()?;
-                                Some(SessionExercise {
-                                    exercise_id: obj.get("exerciseId")?.as_s().ok()?.clone(),
-                                    name: obj.get("name")?.as_s().ok()?.clone(),
-                                    sets: obj
-                                        .get("sets")
-                                        .and_then(|v| v.as_l().ok())
-                                        .map(|sets| {
-                                            sets.iter()
-                                                .filter_map(|set| {
-                                                    let set_obj = set.as_m().ok()?;
-                                                    Some(ExerciseSet {
-                                                        set_number: set_obj
-                                                            .get("setNumber")?
-                                                            .as_n()
-                                                            .ok()?
-                                                            .parse()
-                                                            .ok()?,
-                                                        reps: set_obj
-                                                            .get("reps")
-                                                            .and_then(|v| v.as_n().ok())
-                                                            .and_then(|s| s.parse().ok()),
-                                                        weight: set_obj
-                                                            .get("weight")
-                                                            .and_then(|v| v.as_n().ok())
-                                                            .and_then(|s| s.parse().ok()),
-                                                        duration_seconds: set_obj
-                                                            .get("durationSeconds")
-                                                            .and_then(|v| v.as_n().ok())
-                                                            .and_then(|s| s.parse().ok()),
-                                                        rest_seconds: set_obj
-                                                            .get("restSeconds")
-                                                            .and_then(|v| v.as_n().ok())
-                                                            .and_then(|s| s.parse().ok()),
-                                                        completed: *set_obj
-                                                            .get("completed")?
-                                                            .as_bool()
-                                                            .ok()?,
-                                                        notes: set_obj
-                                                            .get("notes")
-                                                            .and_then(|v| v.as_s().ok())
-                                                            .map(|s| s.clone()),
-                                                    })
+        let item = get_or_404(item, "Workout session", session_id)?;
+
+        let session = WorkoutSession {
+            id: item
+                .get("SessionId")
+                .or_else(|| item.get("id"))
+                .and_then(|v| v.as_s().ok())
+                .map_or("", |v| v)
+                .to_string(),
+            user_id: item
+                .get("UserId")
+                .or_else(|| item.get("userId"))
+                .and_then(|v| v.as_s().ok())
+                .map_or("", |v| v)
+                .to_string(),
+            workout_plan_id: item
+                .get("WorkoutPlanId")
+                .or_else(|| item.get("workoutPlanId"))
+                .and_then(|v| v.as_s().ok())
+                .map(|s| s.to_string()),
+            name: item
+                .get("Name")
+                .or_else(|| item.get("name"))
+                .and_then(|v| v.as_s().ok())
+                .map_or("", |v| v)
+                .to_string(),
+            started_at: item
+                .get("StartedAt")
+                .or_else(|| item.get("startedAt"))
+                .and_then(|v| v.as_s().ok())
+                .map_or("", |v| v)
+                .to_string(),
+            completed_at: item
+                .get("CompletedAt")
+                .or_else(|| item.get("completedAt"))
+                .and_then(|v| v.as_s().ok())
+                .map(|s| s.to_string()),
+            duration_minutes: item
+                .get("DurationMinutes")
+                .or_else(|| item.get("durationMinutes"))
+                .and_then(|v| v.as_n().ok())
+                .and_then(|s| s.parse().ok()),
+            notes: item
+                .get("Notes")
+                .or_else(|| item.get("notes"))
+                .and_then(|v| v.as_s().ok())
+                .map(|s| s.to_string()),
+            rating: item
+                .get("Rating")
+                .or_else(|| item.get("rating"))
+                .and_then(|v| v.as_n().ok())
+                .and_then(|s| s.parse().ok()),
+            created_at: item
+                .get("CreatedAt")
+                .or_else(|| item.get("createdAt"))
+                .and_then(|v| v.as_s().ok())
+                .map_or("", |v| v)
+                .to_string(),
+            updated_at: item
+                .get("UpdatedAt")
+                .or_else(|| item.get("updatedAt"))
+                .and_then(|v| v.as_s().ok())
+                .map_or("", |v| v)
+                .to_string(),
+            exercises: item
+                .get("exercises")
+                .and_then(|v| v.as_l().ok())
+                .map(|list| {
+                    list.iter()
+                        .filter_map(|v| {
+                            let obj = v.as_m().ok()?;
+                            Some(SessionExercise {
+                                exercise_id: obj.get("exerciseId")?.as_s().ok()?.clone(),
+                                name: obj.get("name")?.as_s().ok()?.clone(),
+                                sets: obj
+                                    .get("sets")
+                                    .and_then(|v| v.as_l().ok())
+                                    .map(|sets| {
+                                        sets.iter()
+                                            .filter_map(|set| {
+                                                let set_obj = set.as_m().ok()?;
+                                                Some(ExerciseSet {
+                                                    set_number: set_obj
+                                                        .get("setNumber")?
+                                                        .as_n()
+                                                        .ok()?
+                                                        .parse()
+                                                        .ok()?,
+                                                    reps: set_obj
+                                                        .get("reps")
+                                                        .and_then(|v| v.as_n().ok())
+                                                        .and_then(|s| s.parse().ok()),
+                                                    weight: set_obj
+                                                        .get("weight")
+                                                        .and_then(|v| v.as_n().ok())
+                                                        .and_then(|s| s.parse().ok()),
+                                                    duration_seconds: set_obj
+                                                        .get("durationSeconds")
+                                                        .and_then(|v| v.as_n().ok())
+                                                        .and_then(|s| s.parse().ok()),
+                                                    rest_seconds: set_obj
+                                                        .get("restSeconds")
+                                                        .and_then(|v| v.as_n().ok())
+                                                        .and_then(|s| s.parse().ok()),
+                                                    completed: *set_obj
+                                                        .get("completed")?
+                                                        .as_bool()
+                                                        .ok()?,
+                                                    notes: set_obj
+                                                        .get("notes")
+                                                        .and_then(|v| v.as_s().ok())
+                                                        .map(|s| s.clone()),
                                                 })
-                                                .collect()
-                                        })
-                                        .unwrap_or_default(),
-                                    notes: obj
-                                        .get("notes")
-                                        .and_then(|v| v.as_s().ok())
-                                        .map(|s| s.clone()),
-                                    order: obj.get("order")?.as_n().ok()?.parse().ok()?,
-                                })
+                                            })
+                                            .collect()
+                                    })
+                                    .unwrap_or_default(),
+                                notes: obj
+                                    .get("notes")
+                                    .and_then(|v| v.as_s().ok())
+                                    .map(|s| s.clone()),
+                                order: obj.get("order")?.as_n().ok()?.parse().ok()?,
                             })
-                            .collect()
-                    })
-                    .unwrap_or_default(),
-            };
+                        })
+                        .collect()
+                })
+                .unwrap_or_default(),
+        };
 
-            Ok(serde_json::to_value(session)?)
-        } else {
-            Err("Workout session not found".into())
-        }
+        Ok(serde_json::to_value(session)?)
     }
 
+    /// Overwrite a session. The key moves when the start time changes or the item still
+    /// has a legacy key; the new item and its pointer are written and the old key deleted
+    /// in one transaction, so a failure never leaves the session under both keys
     pub async fn update_workout_session(
         &self,
         session: &WorkoutSession,
     ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
-        self.create_workout_session(session).await
+        let previous_key = self.find_session_key(&session.user_id, &session.id).await?;
+        let key = session_sort_key(&session.started_at, &session.id);
+
+        let put = Put::builder()
+            .table_name(&self.table_name)
+            .set_item(Some(session_item(session)))
+            .build()?;
+        let mut writes = vec![TransactWriteItem::builder().put(put).build()];
+        if previous_key.as_deref() != Some(key.as_str()) {
+            writes.push(self.session_pointer_write(&session.user_id, &session.id, &key)?);
+        }
+        if let Some(previous_key) = previous_key.filter(|previous| *previous != key) {
+            let delete = Delete::builder()
+                .table_name(&self.table_name)
+                .key("PK", AttributeValue::S(format!("USER#{}", session.user_id)))
+                .key("SK", AttributeValue::S(previous_key))
+                .build()?;
+            writes.push(TransactWriteItem::builder().delete(delete).build());
+        }
+        transact_write(&self.client, writes).await?;
+
+        Ok(serde_json::to_value(session)?)
     }
 
+    /// Mark a session deleted and let the table TTL purge it later;
+    /// `restore_workout_session` can bring it back until then
     pub async fn delete_workout_session(
         &self,
         session_id: &str,
@@ -482,34 +787,535 @@ impl WorkoutSessionRepository {
         // First, find the session to get the user_id
         let scan_result = self
             .client
-            .scan()
-            .table_name(&self.table_name)
-            .filter_expression("SK = :sk")
-            .expression_attribute_values(
-                ":sk",
-                AttributeValue::S(format!("SESSION#{}", session_id)),
+            .scan(
+                ScanInput::builder()
+                    .table_name(&self.table_name)
+                    .filter_expression(format!(
+                        "SessionId = :session_id AND begins_with(SK, :sk_prefix) AND {}",
+                        NOT_DELETED_FILTER
+                    ))
+                    .expression_attribute_values(
+                        ":session_id",
+                        AttributeValue::S(session_id.to_string()),
+                    )
+                    .expression_attribute_values(
+                        ":sk_prefix",
+                        AttributeValue::S(SESSION_SK_PREFIX.to_string()),
+                    )
+                    .build()?,
             )
-            .send()
             .await?;
 
         if let Some(items) = scan_result.items {
             if let Some(item) = items.into_iter().next() {
-                if let Some(user_id) = item
-                    .get("UserId")
-                    .or_else(|| item.get("userId"))
-                    .and_then(|v| v.as_s().ok())
-                {
+                if let (Some(user_id), Some(sk)) = (
+                    item.get("UserId")
+                        .or_else(|| item.get("userId"))
+                        .and_then(|v| v.as_s().ok()),
+                    item.get("SK").and_then(|v| v.as_s().ok()),
+                ) {
+                    let now = Utc::now();
                     self.client
-                        .delete_item()
-                        .table_name(&self.table_name)
-                        .key("PK", AttributeValue::S(format!("USER#{}", user_id)))
-                        .key("SK", AttributeValue::S(format!("SESSION#{}", session_id)))
-                        .send()
-                        .await?;
+                        .update_item(
+                            UpdateItemInput::builder()
+                                .table_name(&self.table_name)
+                                .key("PK", AttributeValue::S(format!("USER#{}", user_id)))
+                                .key("SK", AttributeValue::S(sk.clone()))
+                                .update_expression("SET DeletedAt = :deleted_at, #ttl = :ttl")
+                                .condition_expression(
+                                    "attribute_exists(PK) AND attribute_not_exists(DeletedAt)",
+                                )
+                                .expression_attribute_names("#ttl", "ttl")
+                                .expression_attribute_values(
+                                    ":deleted_at",
+                                    AttributeValue::S(now.to_rfc3339()),
+                                )
+                                .expression_attribute_values(
+                                    ":ttl",
+                                    AttributeValue::N(soft_delete_expiry(now).to_string()),
+                                )
+                                .build()?,
+                        )
+                        .await
+                        .map_err(|e| {
+                            error_helpers::conditional_write_error(
+                                e,
+                                error_helpers::resource_not_found("Workout session"),
+                            )
+                        })?;
                 }
             }
         }
 
         Ok(())
     }
+
+    /// Undo a soft delete of one of `user_id`'s sessions, as long as its TTL has not
+    /// passed yet (DynamoDB may not have purged an expired item)
+    pub async fn restore_workout_session(
+        &self,
+        user_id: &str,
+        session_id: &str,
+    ) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+        let not_restorable = || {
+            error_helpers::not_found(&format!(
+                "No deleted workout session '{}' to restore",
+                session_id
+            ))
+        };
+        let sk = self
+            .find_session_key(user_id, session_id)
+            .await?
+            .ok_or_else(not_restorable)?;
+
+        self.client
+            .update_item(
+                UpdateItemInput::builder()
+                    .table_name(&self.table_name)
+                    .key("PK", AttributeValue::S(format!("USER#{}", user_id)))
+                    .key("SK", AttributeValue::S(sk))
+                    .update_expression("REMOVE DeletedAt, #ttl")
+                    .condition_expression("attribute_exists(DeletedAt) AND #ttl > :now")
+                    .expression_attribute_names("#ttl", "ttl")
+                    .expression_attribute_values(
+                        ":now",
+                        AttributeValue::N(Utc::now().timestamp().to_string()),
+                    )
+                    .build()?,
+            )
+            .await
+            .map_err(|e| error_helpers::conditional_write_error(e, not_restorable()))?;
+
+        self.get_workout_session(session_id, true).await
+    }
+}
+
+/// A session as stored under its time-prefixed key
+fn session_item(session: &WorkoutSession) -> Item {
+    let mut item = HashMap::new();
+    item.insert(
+        "PK".to_string(),
+        AttributeValue::S(format!("USER#{}", session.user_id)),
+    );
+    item.insert(
+        "SK".to_string(),
+        AttributeValue::S(session_sort_key(&session.started_at, &session.id)),
+    );
+    item.insert(
+        "SessionId".to_string(),
+        AttributeValue::S(session.id.clone()),
+    );
+    item.insert(
+        "UserId".to_string(),
+        AttributeValue::S(session.user_id.clone()),
+    );
+    item.insert("Name".to_string(), AttributeValue::S(session.name.clone()));
+    item.insert(
+        "StartedAt".to_string(),
+        AttributeValue::S(session.started_at.clone()),
+    );
+    item.insert(
+        "CreatedAt".to_string(),
+        AttributeValue::S(session.created_at.clone()),
+    );
+    item.insert(
+        "UpdatedAt".to_string(),
+        AttributeValue::S(session.updated_at.clone()),
+    );
+
+    if let Some(plan_id) = &session.workout_plan_id {
+        item.insert(
+            "WorkoutPlanId".to_string(),
+            AttributeValue::S(plan_id.clone()),
+        );
+    }
+    if let Some(completed_at) = &session.completed_at {
+        item.insert(
+            "CompletedAt".to_string(),
+            AttributeValue::S(completed_at.clone()),
+        );
+    }
+    if let Some(duration) = session.duration_minutes {
+        item.insert(
+            "DurationMinutes".to_string(),
+            AttributeValue::N(duration.to_string()),
+        );
+    }
+    if let Some(notes) = &session.notes {
+        item.insert("Notes".to_string(), AttributeValue::S(notes.clone()));
+    }
+    if let Some(rating) = session.rating {
+        item.insert("Rating".to_string(), AttributeValue::N(rating.to_string()));
+    }
+
+    let exercises = session.exercises.iter().map(exercise_attribute).collect();
+    item.insert("exercises".to_string(), AttributeValue::L(exercises));
+
+    item
+}
+
+/// A session exercise as stored in the item's `exercises` list
+fn exercise_attribute(exercise: &SessionExercise) -> AttributeValue {
+    let mut exercise_map = std::collections::HashMap::new();
+    exercise_map.insert(
+        "exerciseId".to_string(),
+        AttributeValue::S(exercise.exercise_id.clone()),
+    );
+    exercise_map.insert("name".to_string(), AttributeValue::S(exercise.name.clone()));
+    exercise_map.insert(
+        "order".to_string(),
+        AttributeValue::N(exercise.order.to_string()),
+    );
+
+    if let Some(notes) = &exercise.notes {
+        exercise_map.insert("notes".to_string(), AttributeValue::S(notes.clone()));
+    }
+
+    let sets = exercise.sets.iter().map(set_attribute).collect();
+    exercise_map.insert("sets".to_string(), AttributeValue::L(sets));
+
+    AttributeValue::M(exercise_map)
+}
+
+/// One set as stored in an exercise's `sets` list
+fn set_attribute(set: &ExerciseSet) -> AttributeValue {
+    let mut set_map = std::collections::HashMap::new();
+    set_map.insert(
+        "setNumber".to_string(),
+        AttributeValue::N(set.set_number.to_string()),
+    );
+    set_map.insert("completed".to_string(), AttributeValue::Bool(set.completed));
+
+    if let Some(reps) = set.reps {
+        set_map.insert("reps".to_string(), AttributeValue::N(reps.to_string()));
+    }
+    if let Some(weight) = set.weight {
+        set_map.insert("weight".to_string(), AttributeValue::N(weight.to_string()));
+    }
+    if let Some(duration) = set.duration_seconds {
+        set_map.insert(
+            "durationSeconds".to_string(),
+            AttributeValue::N(duration.to_string()),
+        );
+    }
+    if let Some(rest) = set.rest_seconds {
+        set_map.insert(
+            "restSeconds".to_string(),
+            AttributeValue::N(rest.to_string()),
+        );
+    }
+    if let Some(notes) = &set.notes {
+        set_map.insert("notes".to_string(), AttributeValue::S(notes.clone()));
+    }
+
+    AttributeValue::M(set_map)
+}
+
+/// The session an `UpdateItem` with `ReturnValues::AllNew` left behind
+fn updated_session(
+    attributes: Option<Item>,
+) -> Result<Value, Box<dyn std::error::Error + Send + Sync>> {
+    let session = attributes
+        .as_ref()
+        .and_then(session_from_item)
+        .ok_or_else(|| error_helpers::internal_error("Updated workout session is incomplete"))?;
+    Ok(serde_json::to_value(session)?)
+}
+
+/// The update and condition that log `exercises`' sets to a session whose stored
+/// `exercises` list holds `stored_ids` (exercise ids by position), or no list at all.
+/// Entries for the same exercise are merged. Existing exercises get their sets appended
+/// in place; new ones are written past the end of the list, which DynamoDB appends. The
+/// condition checks the list still has the same length and ids at the touched positions
+fn append_sets_update(
+    stored_ids: Option<&[String]>,
+    exercises: &[SessionExercise],
+    updated_at: &str,
+) -> (UpdateExpression, String) {
+    let mut merged: Vec<SessionExercise> = Vec::new();
+    for exercise in exercises {
+        match merged
+            .iter_mut()
+            .find(|merged| merged.exercise_id == exercise.exercise_id)
+        {
+            Some(merged) => merged.sets.extend(exercise.sets.iter().cloned()),
+            None => merged.push(exercise.clone()),
+        }
+    }
+
+    // DynamoDB rejects placeholders the expressions do not use, so `#sets` and
+    // `#exercise_id` are only added once an existing exercise is appended to
+    let mut names = HashMap::from([
+        ("#exercises".to_string(), "exercises".to_string()),
+        ("#updated_at".to_string(), "UpdatedAt".to_string()),
+    ]);
+    let mut values = HashMap::from([(
+        ":updated_at".to_string(),
+        AttributeValue::S(updated_at.to_string()),
+    )]);
+    let mut set_clauses = vec!["#updated_at = :updated_at".to_string()];
+    let mut conditions = vec![
+        "attribute_exists(PK)".to_string(),
+        "attribute_not_exists(CompletedAt)".to_string(),
+        "attribute_not_exists(DeletedAt)".to_string(),
+    ];
+
+    match stored_ids {
+        None => {
+            set_clauses.push("#exercises = :exercises".to_string());
+            values.insert(
+                ":exercises".to_string(),
+                AttributeValue::L(merged.iter().map(exercise_attribute).collect()),
+            );
+            conditions.push("attribute_not_exists(#exercises)".to_string());
+        }
+        Some(stored_ids) => {
+            conditions.push("size(#exercises) = :exercise_count".to_string());
+            values.insert(
+                ":exercise_count".to_string(),
+                AttributeValue::N(stored_ids.len().to_string()),
+            );
+
+            let mut next_index = stored_ids.len();
+            for (n, exercise) in merged.iter().enumerate() {
+                match stored_ids.iter().position(|id| *id == exercise.exercise_id) {
+                    Some(index) => {
+                        set_clauses.push(format!(
+                            "#exercises[{index}].#sets = list_append(if_not_exists(#exercises[{index}].#sets, :no_sets), :sets{n})"
+                        ));
+                        conditions.push(format!(
+                            "#exercises[{index}].#exercise_id = :exercise_id{n}"
+                        ));
+                        values.insert(
+                            format!(":sets{n}"),
+                            AttributeValue::L(exercise.sets.iter().map(set_attribute).collect()),
+                        );
+                        values.insert(
+                            format!(":exercise_id{n}"),
+                            AttributeValue::S(exercise.exercise_id.clone()),
+                        );
+                        values.insert(":no_sets".to_string(), AttributeValue::L(Vec::new()));
+                        names.insert("#sets".to_string(), "sets".to_string());
+                        names.insert("#exercise_id".to_string(), "exerciseId".to_string());
+                    }
+                    None => {
+                        set_clauses.push(format!("#exercises[{next_index}] = :exercise{n}"));
+                        values.insert(format!(":exercise{n}"), exercise_attribute(exercise));
+                        next_index += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    let update = UpdateExpression {
+        expression: format!("SET {}", set_clauses.join(", ")),
+        names,
+        values,
+    };
+    (update, conditions.join(" AND "))
+}
+
+fn session_from_item(item: &Item) -> Option<WorkoutSession> {
+    Some(WorkoutSession {
+        id: item
+            .get("SessionId")
+            .or_else(|| item.get("id"))
+            .and_then(|v| v.as_s().ok())?
+            .clone(),
+        user_id: item
+            .get("UserId")
+            .or_else(|| item.get("userId"))
+            .and_then(|v| v.as_s().ok())?
+            .clone(),
+        workout_plan_id: item
+            .get("WorkoutPlanId")
+            .or_else(|| item.get("workoutPlanId"))
+            .and_then(|v| v.as_s().ok())
+            .map(|s| s.clone()),
+        name: item
+            .get("Name")
+            .or_else(|| item.get("name"))
+            .and_then(|v| v.as_s().ok())?
+            .clone(),
+        started_at: item
+            .get("StartedAt")
+            .or_else(|| item.get("startedAt"))
+            .and_then(|v| v.as_s().ok())?
+            .clone(),
+        completed_at: item
+            .get("CompletedAt")
+            .or_else(|| item.get("completedAt"))
+            .and_then(|v| v.as_s().ok())
+            .map(|s| s.clone()),
+        duration_minutes: item
+            .get("DurationMinutes")
+            .or_else(|| item.get("durationMinutes"))
+            .and_then(|v| v.as_n().ok())
+            .and_then(|s| s.parse().ok()),
+        notes: item
+            .get("Notes")
+            .or_else(|| item.get("notes"))
+            .and_then(|v| v.as_s().ok())
+            .map(|s| s.clone()),
+        rating: item
+            .get("Rating")
+            .or_else(|| item.get("rating"))
+            .and_then(|v| v.as_n().ok())
+            .and_then(|s| s.parse().ok()),
+        created_at: item
+            .get("CreatedAt")
+            .or_else(|| item.get("createdAt"))
+            .and_then(|v| v.as_s().ok())?
+            .clone(),
+        updated_at: item
+            .get("UpdatedAt")
+            .or_else(|| item.get("updatedAt"))
+            .and_then(|v| v.as_s().ok())?
+            .clone(),
+        exercises: item
+            .get("exercises")
+            .and_then(|v| v.as_l().ok())
+            .map(|list| {
+                list.iter()
+                    .filter_map(|v| {
+                        let obj = v.as_m().ok()?;
+                        Some(SessionExercise {
+                            exercise_id: obj.get("exerciseId")?.as_s().ok()?.clone(),
+                            name: obj.get("name")?.as_s().ok()?.clone(),
+                            sets: obj
+                                .get("sets")
+                                .and_then(|v| v.as_l().ok())
+                                .map(|sets| {
+                                    sets.iter()
+                                        .filter_map(|set| {
+                                            let set_obj = set.as_m().ok()?;
+                                            Some(ExerciseSet {
+                                                set_number: set_obj
+                                                    .get("setNumber")?
+                                                    .as_n()
+                                                    .ok()?
+                                                    .parse()
+                                                    .ok()?,
+                                                reps: set_obj
+                                                    .get("reps")
+                                                    .and_then(|v| v.as_n().ok())
+                                                    .and_then(|s| s.parse().ok()),
+                                                weight: set_obj
+                                                    .get("weight")
+                                                    .and_then(|v| v.as_n().ok())
+                                                    .and_then(|s| s.parse().ok()),
+                                                duration_seconds: set_obj
+                                                    .get("durationSeconds")
+                                                    .and_then(|v| v.as_n().ok())
+                                                    .and_then(|s| s.parse().ok()),
+                                                rest_seconds: set_obj
+                                                    .get("restSeconds")
+                                                    .and_then(|v| v.as_n().ok())
+                                                    .and_then(|s| s.parse().ok()),
+                                                completed: *set_obj
+                                                    .get("completed")?
+                                                    .as_bool()
+                                                    .ok()?,
+                                                notes: set_obj
+                                                    .get("notes")
+                                                    .and_then(|v| v.as_s().ok())
+                                                    .map(|s| s.clone()),
+                                            })
+                                        })
+                                        .collect()
+                                })
+                                .unwrap_or_default(),
+                            notes: obj
+                                .get("notes")
+                                .and_then(|v| v.as_s().ok())
+                                .map(|s| s.clone()),
+                            order: obj.get("order")?.as_n().ok()?.parse().ok()?,
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exercise(exercise_id: &str, set_numbers: &[i32]) -> SessionExercise {
+        SessionExercise {
+            exercise_id: exercise_id.to_string(),
+            name: exercise_id.to_string(),
+            sets: set_numbers
+                .iter()
+                .map(|&set_number| ExerciseSet {
+                    set_number,
+                    reps: Some(8),
+                    weight: Some(60.0),
+                    duration_seconds: None,
+                    rest_seconds: None,
+                    completed: true,
+                    notes: None,
+                })
+                .collect(),
+            notes: None,
+            order: 1,
+        }
+    }
+
+    #[test]
+    fn test_append_sets_update_appends_in_place_and_adds_new_exercises() {
+        let stored = ["squat".to_string(), "bench".to_string()];
+        let (update, condition) = append_sets_update(
+            Some(&stored),
+            &[
+                exercise("bench", &[3]),
+                exercise("row", &[1]),
+                exercise("bench", &[4]),
+            ],
+            "2024-03-01T10:00:00Z",
+        );
+
+        assert_eq!(
+            update.expression,
+            "SET #updated_at = :updated_at, \
+             #exercises[1].#sets = list_append(if_not_exists(#exercises[1].#sets, :no_sets), :sets0), \
+             #exercises[2] = :exercise1"
+        );
+        assert_eq!(
+            condition,
+            "attribute_exists(PK) AND attribute_not_exists(CompletedAt) \
+             AND attribute_not_exists(DeletedAt) AND size(#exercises) = :exercise_count \
+             AND #exercises[1].#exercise_id = :exercise_id0"
+        );
+        // Both bench entries land in one append, in the order they were sent
+        let sets = update.values[":sets0"].as_l().unwrap();
+        let set_numbers: Vec<&str> = sets
+            .iter()
+            .map(|set| set.as_m().unwrap()["setNumber"].as_n().unwrap().as_str())
+            .collect();
+        assert_eq!(set_numbers, vec!["3", "4"]);
+        assert_eq!(
+            update.values[":exercise_count"],
+            AttributeValue::N("2".to_string())
+        );
+        assert_eq!(update.names["#sets"], "sets");
+    }
+
+    #[test]
+    fn test_append_sets_update_starts_missing_exercise_list() {
+        let (update, condition) =
+            append_sets_update(None, &[exercise("squat", &[1])], "2024-03-01T10:00:00Z");
+
+        assert_eq!(
+            update.expression,
+            "SET #updated_at = :updated_at, #exercises = :exercises"
+        );
+        assert!(condition.ends_with("AND attribute_not_exists(#exercises)"));
+        assert_eq!(update.values[":exercises"].as_l().unwrap().len(), 1);
+        // Unused placeholders would make DynamoDB reject the request
+        assert!(!update.names.contains_key("#sets"));
+        assert!(!update.names.contains_key("#exercise_id"));
+    }
 }
@@ -0,0 +1,160 @@
+use chrono::Utc;
+use chrono_tz::Tz;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tracing::{error, info};
+
+use crate::models::*;
+use crate::repository::{AchievementRepository, WorkoutAnalyticsRepository};
+
+#[derive(Clone)]
+pub struct AchievementService {
+    achievement_repository: AchievementRepository,
+    workout_analytics_repository: WorkoutAnalyticsRepository,
+    one_rep_max_formula: OneRepMaxFormula,
+    rules: Arc<Vec<AchievementRule>>,
+}
+
+impl AchievementService {
+    pub fn new(
+        achievement_repository: AchievementRepository,
+        workout_analytics_repository: WorkoutAnalyticsRepository,
+    ) -> Self {
+        Self {
+            achievement_repository,
+            workout_analytics_repository,
+            one_rep_max_formula: OneRepMaxFormula::default(),
+            rules: Arc::new(default_achievement_rules()),
+        }
+    }
+
+    /// Award achievements from `rules` instead of the built-in set
+    pub fn with_rules(mut self, rules: Vec<AchievementRule>) -> Self {
+        self.rules = Arc::new(rules);
+        self
+    }
+
+    /// Select the formula used to estimate the one-rep maxes rules check (Epley by default)
+    pub fn with_one_rep_max_formula(mut self, formula: OneRepMaxFormula) -> Self {
+        self.one_rep_max_formula = formula;
+        self
+    }
+
+    /// Check the user's current analytics against every rule and award the achievements
+    /// they have newly earned. With `dry_run` nothing is stored and the achievements that
+    /// would be awarded are returned instead. Achievements the user already has are never
+    /// awarded again
+    pub async fn evaluate_achievements(
+        &self,
+        user_id: &str,
+        timezone: Tz,
+        dry_run: bool,
+    ) -> Result<Vec<Achievement>, Box<dyn std::error::Error + Send + Sync>> {
+        let analytics = self
+            .workout_analytics_repository
+            .get_workout_analytics(Some(user_id.to_string()), self.one_rep_max_formula, timezone)
+            .await?;
+        let snapshot = AchievementSnapshot::from(serde_json::from_value::<WorkoutAnalytics>(analytics)?);
+        let earned = self.achievement_repository.get_achievement_ids(user_id).await?;
+
+        let earned_at = Utc::now().to_rfc3339();
+        let mut awarded = Vec::new();
+        for rule in newly_earned(&self.rules, &snapshot, &earned) {
+            let achievement = rule.award(user_id, &earned_at);
+            // A concurrent evaluation may have stored it since the ids were read
+            if dry_run || self.achievement_repository.create_achievement(&achievement).await? {
+                awarded.push(achievement);
+            }
+        }
+
+        if !dry_run && !awarded.is_empty() {
+            info!("Awarded {} achievement(s) to user {}", awarded.len(), user_id);
+        }
+        Ok(awarded)
+    }
+}
+
+/// Rules whose condition the snapshot meets and that haven't been awarded yet
+pub fn newly_earned<'a>(
+    rules: &'a [AchievementRule],
+    snapshot: &AchievementSnapshot,
+    earned: &HashSet<String>,
+) -> Vec<&'a AchievementRule> {
+    rules
+        .iter()
+        .filter(|rule| !earned.contains(&rule.id) && rule.is_met(snapshot))
+        .collect()
+}
+
+/// Parse a JSON array of rules, e.g. from the `ACHIEVEMENT_RULES` env var. Invalid
+/// rules are logged and the built-in set is used instead
+pub fn achievement_rules_from_json(json: &str) -> Vec<AchievementRule> {
+    serde_json::from_str(json).unwrap_or_else(|e| {
+        error!("Invalid achievement rules, using the defaults: {}", e);
+        default_achievement_rules()
+    })
+}
+
+/// The built-in rules: workout count milestones, streaks and 1RM landmarks
+pub fn default_achievement_rules() -> Vec<AchievementRule> {
+    let rule = |id: &str, title: &str, description: &str, achievement_type: &str, icon: &str, rarity: &str, points: i32, condition: AchievementCondition| {
+        AchievementRule {
+            id: id.to_string(),
+            title: title.to_string(),
+            description: description.to_string(),
+            achievement_type: achievement_type.to_string(),
+            icon: icon.to_string(),
+            category: achievement_type.to_string(),
+            rarity: rarity.to_string(),
+            points,
+            condition,
+        }
+    };
+
+    vec![
+        rule("first-workout", "First Workout", "Logged your first workout", "milestone", "🎉", "common", 10, AchievementCondition::TotalWorkouts { count: 1 }),
+        rule("workouts-10", "Getting Started", "Logged 10 workouts", "milestone", "💪", "common", 20, AchievementCondition::TotalWorkouts { count: 10 }),
+        rule("workouts-50", "Regular", "Logged 50 workouts", "milestone", "🏋️", "rare", 50, AchievementCondition::TotalWorkouts { count: 50 }),
+        rule("workouts-100", "Centurion", "Logged 100 workouts", "milestone", "🏆", "epic", 100, AchievementCondition::TotalWorkouts { count: 100 }),
+        rule("streak-3", "On a Roll", "Trained 3 days in a row", "streak", "🔥", "common", 15, AchievementCondition::Streak { days: 3 }),
+        rule("streak-7", "One Week Streak", "Trained 7 days in a row", "streak", "🔥", "rare", 30, AchievementCondition::Streak { days: 7 }),
+        rule("streak-30", "Unstoppable", "Trained 30 days in a row", "streak", "⚡", "legendary", 150, AchievementCondition::Streak { days: 30 }),
+        rule("one-rep-max-100", "Triple Digits", "Reached an estimated 1RM of 100 on any lift", "pr", "🥇", "rare", 40, AchievementCondition::OneRepMax { weight: 100.0, exercise: None }),
+        rule("one-rep-max-200", "Heavy Lifter", "Reached an estimated 1RM of 200 on any lift", "pr", "🏅", "epic", 80, AchievementCondition::OneRepMax { weight: 200.0, exercise: None }),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_newly_earned_skips_awarded_and_unmet_rules() {
+        let rules = default_achievement_rules();
+        let snapshot = AchievementSnapshot {
+            current_streak: 3,
+            longest_streak: 3,
+            total_workouts: 12,
+            strength_progress: Vec::new(),
+        };
+        let earned = HashSet::from(["first-workout".to_string()]);
+
+        let ids: Vec<&str> = newly_earned(&rules, &snapshot, &earned)
+            .iter()
+            .map(|rule| rule.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["workouts-10", "streak-3"]);
+    }
+
+    #[test]
+    fn test_achievement_rules_from_json_falls_back_to_defaults() {
+        let rules = achievement_rules_from_json(
+            r#"[{"id": "squat-140", "title": "Big Squat", "description": "Squat 140",
+                 "achievement_type": "pr", "condition": {"type": "one_rep_max", "weight": 140, "exercise": "Squat"}}]"#,
+        );
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].points, 10);
+
+        assert_eq!(achievement_rules_from_json("not json").len(), default_achievement_rules().len());
+    }
+}
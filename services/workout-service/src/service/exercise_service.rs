@@ -1,20 +1,29 @@
 use anyhow::Result;
 use tracing::{info, error};
 use chrono::Utc;
-use uuid::Uuid;
+use std::sync::Arc;
 
 use crate::repository::ExerciseRepository;
 use crate::models::*;
+use crate::utils::error::ServiceError;
+use crate::utils::id_generator::{default_id_generator, IdGenerator};
 use auth_layer::AuthContext;
 
 #[derive(Clone)]
 pub struct ExerciseService {
     exercise_repository: ExerciseRepository,
+    id_generator: Arc<dyn IdGenerator>,
 }
 
 impl ExerciseService {
     pub fn new(exercise_repository: ExerciseRepository) -> Self {
-        Self { exercise_repository }
+        Self { exercise_repository, id_generator: default_id_generator() }
+    }
+
+    /// Use `id_generator` for the ids of created resources (random UUIDs by default)
+    pub fn with_id_generator(mut self, id_generator: Arc<dyn IdGenerator>) -> Self {
+        self.id_generator = id_generator;
+        self
     }
 
     pub async fn get_exercises(&self, auth_context: &AuthContext) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
@@ -33,8 +42,24 @@ impl ExerciseService {
         self.exercise_repository.get_exercises_with_user(user_id).await
     }
 
+    /// Name-prefix search over system exercises and the caller's own, filtered and ranked
+    pub async fn search_exercises(&self, query: &str, filters: &ExerciseSearchFilters, limit: usize, auth_context: &AuthContext) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
+        let mut exercises: Vec<Exercise> = self.exercise_repository
+            .search_exercises(query, Some(&auth_context.user_id))
+            .await?
+            .into_iter()
+            .filter(|exercise| filters.matches(exercise))
+            .collect();
+        
+        rank_exercises(query, &mut exercises);
+        exercises.truncate(limit);
+        
+        info!("Exercise search '{}' returned {} results", query, exercises.len());
+        Ok(serde_json::to_value(exercises)?)
+    }
+
     pub async fn create_exercise(&self, exercise_data: &serde_json::Value, auth_context: &AuthContext) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
-        let exercise_id = Uuid::new_v4().to_string();
+        let exercise_id = self.id_generator.generate();
         let now = Utc::now().to_rfc3339();
 
         let exercise = Exercise {
@@ -91,9 +116,9 @@ impl ExerciseService {
         self.exercise_repository.create_exercise(&exercise).await
     }
 
-    pub async fn get_exercise(&self, exercise_id: &str, auth_context: &AuthContext) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
+    pub async fn get_exercise(&self, exercise_id: &str, consistent: bool, auth_context: &AuthContext) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
         // No authorization needed for public exercise library
-        self.exercise_repository.get_exercise(exercise_id).await
+        self.exercise_repository.get_exercise(exercise_id, consistent).await
     }
 
     pub async fn update_exercise(&self, exercise_data: &serde_json::Value, auth_context: &AuthContext) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
@@ -101,7 +126,7 @@ impl ExerciseService {
             .ok_or_else(|| anyhow::anyhow!("Exercise ID is required"))?;
 
         // First get the exercise to check ownership
-        let exercise_result = self.exercise_repository.get_exercise(exercise_id).await?;
+        let exercise_result = self.exercise_repository.get_exercise(exercise_id, false).await?;
         let existing_exercise: Exercise = serde_json::from_value(exercise_result)?;
 
         // Authorization check - only allow updates to user-created exercises
@@ -171,39 +196,49 @@ impl ExerciseService {
         self.exercise_repository.update_exercise(&exercise).await
     }
 
-    pub async fn clone_exercise(&self, exercise_id: &str, auth_context: &AuthContext) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
+    /// Copy an exercise into the caller's library, applying any fields in `overrides`.
+    /// The copy always gets a fresh id, belongs to the caller and is never a system
+    /// exercise; a name the caller already uses is rejected as a conflict
+    pub async fn clone_exercise(&self, exercise_id: &str, overrides: &serde_json::Value, auth_context: &AuthContext) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
         // Get the original exercise
-        let exercise_result = self.exercise_repository.get_exercise(exercise_id).await?;
+        let exercise_result = self.exercise_repository.get_exercise(exercise_id, false).await?;
         let original_exercise: Exercise = serde_json::from_value(exercise_result)?;
 
-        let new_exercise_id = Uuid::new_v4().to_string();
-        let now = Utc::now().to_rfc3339();
+        let mut cloned_exercise = original_exercise.with_overrides(overrides)
+            .map_err(|e| ServiceError::Validation(format!("Invalid exercise override: {}", e), None))?;
+        if overrides.get("name").is_none() {
+            cloned_exercise.name = format!("{} (Copy)", original_exercise.name);
+        }
+        if cloned_exercise.name.trim().is_empty() {
+            return Err(ServiceError::Validation("Exercise name is required".to_string(), None).into());
+        }
 
-        let cloned_exercise = Exercise {
-            id: new_exercise_id.clone(),
-            name: format!("{} (Copy)", original_exercise.name),
-            description: original_exercise.description,
-            category: original_exercise.category,
-            muscle_groups: original_exercise.muscle_groups,
-            equipment: original_exercise.equipment,
-            difficulty: original_exercise.difficulty,
-            instructions: original_exercise.instructions,
-            tips: original_exercise.tips,
-            video_url: original_exercise.video_url,
-            image_url: original_exercise.image_url,
-            created_by: Some(auth_context.user_id.clone()),
-            is_system: false, // Cloned exercises are user-created
-            tags: original_exercise.tags,
-            created_at: now.clone(),
-            updated_at: now,
-        };
+        let name_taken = self.exercise_repository
+            .search_exercises(&cloned_exercise.name, Some(&auth_context.user_id))
+            .await?
+            .iter()
+            .any(|existing| {
+                !existing.is_system
+                    && existing.created_by.as_deref() == Some(auth_context.user_id.as_str())
+                    && existing.name.eq_ignore_ascii_case(cloned_exercise.name.trim())
+            });
+        if name_taken {
+            return Err(ServiceError::Conflict(format!("You already have an exercise named '{}'", cloned_exercise.name), None).into());
+        }
+
+        let now = Utc::now().to_rfc3339();
+        cloned_exercise.id = self.id_generator.generate();
+        cloned_exercise.created_by = Some(auth_context.user_id.clone());
+        cloned_exercise.is_system = false; // Cloned exercises are user-created
+        cloned_exercise.created_at = now.clone();
+        cloned_exercise.updated_at = now;
 
         self.exercise_repository.create_exercise(&cloned_exercise).await
     }
 
     pub async fn delete_exercise(&self, exercise_id: &str, auth_context: &AuthContext) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         // First get the exercise to check ownership
-        let exercise_result = self.exercise_repository.get_exercise(exercise_id).await?;
+        let exercise_result = self.exercise_repository.get_exercise(exercise_id, false).await?;
         let existing_exercise: Exercise = serde_json::from_value(exercise_result)?;
 
         // Authorization check - only allow deletion of user-created exercises
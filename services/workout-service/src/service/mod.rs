@@ -3,9 +3,11 @@ pub mod workout_session_service;
 pub mod exercise_service;
 pub mod workout_analytics_service;
 pub mod scheduled_workout_service;
+pub mod achievement_service;
 
 pub use workout_plan_service::WorkoutPlanService;
 pub use workout_session_service::WorkoutSessionService;
 pub use exercise_service::ExerciseService;
 pub use workout_analytics_service::WorkoutAnalyticsService;
 pub use scheduled_workout_service::ScheduledWorkoutService;
+pub use achievement_service::AchievementService;
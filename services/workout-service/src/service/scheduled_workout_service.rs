@@ -1,20 +1,30 @@
 use anyhow::Result;
 use tracing::{info, error};
-use chrono::Utc;
-use uuid::Uuid;
+use chrono::{Datelike, NaiveDate, Utc};
+use std::sync::Arc;
 
 use crate::repository::ScheduledWorkoutRepository;
 use crate::models::*;
+use crate::utils::error::helpers as error_helpers;
+use crate::utils::id_generator::{default_id_generator, IdGenerator};
 use auth_layer::AuthContext;
+use lambda_router::{ListResponse, PageParams};
 
 #[derive(Clone)]
 pub struct ScheduledWorkoutService {
     scheduled_workout_repository: ScheduledWorkoutRepository,
+    id_generator: Arc<dyn IdGenerator>,
 }
 
 impl ScheduledWorkoutService {
     pub fn new(scheduled_workout_repository: ScheduledWorkoutRepository) -> Self {
-        Self { scheduled_workout_repository }
+        Self { scheduled_workout_repository, id_generator: default_id_generator() }
+    }
+
+    /// Use `id_generator` for the ids of created resources (random UUIDs by default)
+    pub fn with_id_generator(mut self, id_generator: Arc<dyn IdGenerator>) -> Self {
+        self.id_generator = id_generator;
+        self
     }
 
     pub async fn create_scheduled_workout(&self, schedule_data: &serde_json::Value, auth_context: &AuthContext) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
@@ -26,7 +36,7 @@ impl ScheduledWorkoutService {
             return Err(anyhow::anyhow!("You can only create scheduled workouts for yourself").into());
         }
 
-        let schedule_id = Uuid::new_v4().to_string();
+        let schedule_id = self.id_generator.generate();
         let now = Utc::now().to_rfc3339();
 
         let scheduled_workout = ScheduledWorkout {
@@ -49,14 +59,73 @@ impl ScheduledWorkoutService {
             day: schedule_data["day"].as_u64().unwrap_or(1) as i32,
             notes: schedule_data["notes"].as_str().map(|s| s.to_string()),
             completed_at: schedule_data["completedAt"].as_str().map(|s| s.to_string()),
+            estimated_duration_minutes: schedule_data["estimatedDurationMinutes"].as_u64().map(|m| m as i32),
+            recurrence_id: None,
             created_at: now.clone(),
             updated_at: now,
         };
 
-        self.scheduled_workout_repository.create_scheduled_workout(&scheduled_workout).await
+        // A recurrence spec turns the request into one occurrence per matching date
+        let recurring = !schedule_data["recurrence"].is_null();
+        let occurrences = if recurring {
+            Self::expand_recurrence(&scheduled_workout, &schedule_data["recurrence"], self.id_generator.as_ref())?
+        } else {
+            vec![scheduled_workout]
+        };
+
+        // Refuse to double-book a slot unless the client explicitly forces it
+        if !schedule_data["force"].as_bool().unwrap_or(false) {
+            for occurrence in &occurrences {
+                let same_day = self.scheduled_workout_repository
+                    .get_scheduled_workouts_by_date(user_id, &occurrence.scheduled_date)
+                    .await?;
+                if let Some(conflicting) = same_day.iter().find(|existing| existing.overlaps(occurrence)) {
+                    return Err(error_helpers::conflict_with_details(
+                        &format!("Overlaps '{}' scheduled on {} at {}", conflicting.plan_name, conflicting.scheduled_date, conflicting.scheduled_time),
+                        Some(serde_json::to_value(conflicting)?),
+                    ).into());
+                }
+            }
+        }
+
+        if recurring {
+            let schedules = self.scheduled_workout_repository.create_scheduled_workouts(&occurrences).await?;
+            Ok(serde_json::json!({
+                "recurrenceId": occurrences[0].recurrence_id,
+                "count": occurrences.len(),
+                "schedules": schedules
+            }))
+        } else {
+            self.scheduled_workout_repository.create_scheduled_workout(&occurrences[0]).await
+        }
+    }
+
+    /// One copy of `template` per date the recurrence produces, all sharing a new
+    /// recurrence id. Weeks count on from the template's week
+    fn expand_recurrence(template: &ScheduledWorkout, recurrence: &serde_json::Value, id_generator: &dyn IdGenerator) -> Result<Vec<ScheduledWorkout>, Box<dyn std::error::Error + Send + Sync>> {
+        let spec: RecurrenceSpec = serde_json::from_value(recurrence.clone())
+            .map_err(|e| error_helpers::validation_failed(&format!("Invalid recurrence: {}", e)))?;
+        let start = NaiveDate::parse_from_str(&template.scheduled_date, "%Y-%m-%d")
+            .map_err(|_| error_helpers::validation_failed("Invalid scheduled date, expected YYYY-MM-DD"))?;
+        let dates = spec.occurrences(start).map_err(|e| error_helpers::validation_failed(&e))?;
+
+        let recurrence_id = id_generator.generate();
+        Ok(dates
+            .into_iter()
+            .map(|date| ScheduledWorkout {
+                id: id_generator.generate(),
+                scheduled_date: date.format("%Y-%m-%d").to_string(),
+                week: template.week + ((date - start).num_days() / 7) as i32,
+                day: date.weekday().number_from_monday() as i32,
+                recurrence_id: Some(recurrence_id.clone()),
+                ..template.clone()
+            })
+            .collect())
     }
 
-    pub async fn get_scheduled_workouts(&self, user_id: Option<String>, auth_context: &AuthContext) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
+    /// Schedules in date/time order. Without `page` the whole (filtered) list comes back as
+    /// an array, as existing clients expect; with it, one `ListResponse` page
+    pub async fn get_scheduled_workouts(&self, user_id: Option<String>, filters: &ScheduledWorkoutFilters, page: Option<PageParams>, consistent: bool, auth_context: &AuthContext) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
         // Authorization check
         if let Some(ref uid) = user_id {
             if auth_context.user_id != *uid {
@@ -64,7 +133,24 @@ impl ScheduledWorkoutService {
             }
         }
 
-        self.scheduled_workout_repository.get_scheduled_workouts(user_id).await
+        filters.validate().map_err(|e| error_helpers::validation_failed(&e))?;
+        let user_id = user_id.unwrap_or_else(|| auth_context.user_id.clone());
+
+        let Some(params) = page else {
+            let schedules = self.scheduled_workout_repository.get_scheduled_workouts(&user_id, filters, consistent).await?;
+            return Ok(serde_json::to_value(schedules)?);
+        };
+
+        let (schedules, last_key) = self.scheduled_workout_repository
+            .get_scheduled_workouts_page(&user_id, filters, params.limit, params.start_key(), consistent)
+            .await?;
+        let total = if params.include_total {
+            Some(self.scheduled_workout_repository.count_scheduled_workouts(&user_id, filters).await?)
+        } else {
+            None
+        };
+
+        Ok(serde_json::to_value(ListResponse::new(schedules, &params, last_key, total))?)
     }
 
     pub async fn update_scheduled_workout(&self, schedule_data: &serde_json::Value, auth_context: &AuthContext) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
@@ -100,6 +186,8 @@ impl ScheduledWorkoutService {
             day: schedule_data["day"].as_u64().unwrap_or(1) as i32,
             notes: schedule_data["notes"].as_str().map(|s| s.to_string()),
             completed_at: schedule_data["completedAt"].as_str().map(|s| s.to_string()),
+            estimated_duration_minutes: schedule_data["estimatedDurationMinutes"].as_u64().map(|m| m as i32),
+            recurrence_id: schedule_data["recurrenceId"].as_str().map(|s| s.to_string()),
             created_at: schedule_data["createdAt"].as_str().unwrap_or(&now).to_string(),
             updated_at: now,
         };
@@ -115,4 +203,59 @@ impl ScheduledWorkoutService {
 
         self.scheduled_workout_repository.delete_scheduled_workout(user_id, schedule_id).await
     }
+
+    pub async fn delete_recurring_scheduled_workouts(&self, user_id: &str, recurrence_id: &str, auth_context: &AuthContext) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
+        // Authorization check
+        if auth_context.user_id != user_id {
+            return Err(anyhow::anyhow!("You can only delete your own scheduled workouts").into());
+        }
+
+        let deleted = self.scheduled_workout_repository
+            .delete_recurring_scheduled_workouts(user_id, recurrence_id)
+            .await?;
+        if deleted == 0 {
+            return Err(error_helpers::not_found(&format!("Recurring schedule '{}' not found", recurrence_id)).into());
+        }
+
+        Ok(serde_json::json!({
+            "message": "Recurring scheduled workouts deleted successfully",
+            "recurrenceId": recurrence_id,
+            "deleted": deleted
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::id_generator::SequentialIdGenerator;
+
+    #[test]
+    fn test_expand_recurrence_assigns_generated_ids() {
+        let template = ScheduledWorkout {
+            id: "schedule-1".to_string(),
+            plan_id: "plan-1".to_string(),
+            user_id: "user-1".to_string(),
+            plan_name: "Push Day".to_string(),
+            scheduled_date: "2024-03-04".to_string(),
+            scheduled_time: "07:00".to_string(),
+            status: "scheduled".to_string(),
+            week: 1,
+            day: 1,
+            notes: None,
+            completed_at: None,
+            estimated_duration_minutes: None,
+            recurrence_id: None,
+            created_at: "2024-01-01T00:00:00Z".to_string(),
+            updated_at: "2024-01-01T00:00:00Z".to_string(),
+        };
+        let recurrence = serde_json::json!({ "daysOfWeek": ["Mon", "Fri"], "count": 3 });
+
+        let occurrences = ScheduledWorkoutService::expand_recurrence(&template, &recurrence, &SequentialIdGenerator::new("id")).unwrap();
+        let ids: Vec<&str> = occurrences.iter().map(|o| o.id.as_str()).collect();
+        assert_eq!(ids, vec!["id-2", "id-3", "id-4"]);
+        assert!(occurrences.iter().all(|o| o.recurrence_id.as_deref() == Some("id-1")));
+        assert_eq!(occurrences[2].scheduled_date, "2024-03-11");
+        assert_eq!(occurrences[2].week, 2);
+    }
 }
@@ -1,4 +1,5 @@
 use anyhow::Result;
+use chrono_tz::Tz;
 use tracing::{info, error};
 
 use crate::repository::WorkoutAnalyticsRepository;
@@ -8,14 +9,24 @@ use auth_layer::AuthContext;
 #[derive(Clone)]
 pub struct WorkoutAnalyticsService {
     workout_analytics_repository: WorkoutAnalyticsRepository,
+    one_rep_max_formula: OneRepMaxFormula,
 }
 
 impl WorkoutAnalyticsService {
     pub fn new(workout_analytics_repository: WorkoutAnalyticsRepository) -> Self {
-        Self { workout_analytics_repository }
+        Self {
+            workout_analytics_repository,
+            one_rep_max_formula: OneRepMaxFormula::default(),
+        }
+    }
+
+    /// Select the formula used to estimate one-rep maxes (Epley by default)
+    pub fn with_one_rep_max_formula(mut self, formula: OneRepMaxFormula) -> Self {
+        self.one_rep_max_formula = formula;
+        self
     }
 
-    pub async fn get_workout_analytics(&self, user_id: Option<String>, auth_context: &AuthContext) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
+    pub async fn get_workout_analytics(&self, user_id: Option<String>, timezone: Tz, auth_context: &AuthContext) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
         // Authorization check
         if let Some(ref uid) = user_id {
             if auth_context.user_id != *uid {
@@ -23,24 +34,28 @@ impl WorkoutAnalyticsService {
             }
         }
 
-        self.workout_analytics_repository.get_workout_analytics(user_id).await
+        self.workout_analytics_repository
+            .get_workout_analytics(user_id, self.one_rep_max_formula, timezone)
+            .await
     }
 
-    pub async fn get_workout_insights(&self, user_id: &str, time_range: &str, auth_context: &AuthContext) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
+    pub async fn get_workout_insights(&self, user_id: &str, time_range: &str, timezone: Tz, auth_context: &AuthContext) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
         // Authorization check
         if auth_context.user_id != user_id {
             return Err(anyhow::anyhow!("You can only access your own workout insights").into());
         }
 
-        self.workout_analytics_repository.get_workout_insights(user_id, time_range).await
+        self.workout_analytics_repository
+            .get_workout_insights(user_id, time_range, self.one_rep_max_formula, timezone)
+            .await
     }
 
-    pub async fn get_workout_history(&self, user_id: &str, limit: Option<i32>, auth_context: &AuthContext) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
+    pub async fn get_workout_history(&self, user_id: &str, page: Option<i32>, limit: Option<i32>, auth_context: &AuthContext) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
         // Authorization check
         if auth_context.user_id != user_id {
             return Err(anyhow::anyhow!("You can only access your own workout history").into());
         }
 
-        self.workout_analytics_repository.get_workout_history(user_id, limit).await
+        self.workout_analytics_repository.get_workout_history(user_id, page, limit).await
     }
 }
@@ -1,27 +1,40 @@
 use anyhow::Result;
 use chrono::Utc;
 use tracing::{error, info};
-use uuid::Uuid;
+use std::sync::Arc;
 
 use crate::models::*;
-use crate::repository::WorkoutPlanRepository;
+use crate::repository::{DynamoOps, WorkoutPlanRepository};
+use crate::utils::error::helpers as error_helpers;
+use crate::utils::{etag_from_version, if_match_satisfied};
+use crate::utils::id_generator::{default_id_generator, IdGenerator};
 use auth_layer::AuthContext;
+use aws_sdk_dynamodb::Client as DynamoDbClient;
 
 #[derive(Clone)]
-pub struct WorkoutPlanService {
-    workout_plan_repository: WorkoutPlanRepository,
+pub struct WorkoutPlanService<C = DynamoDbClient> {
+    workout_plan_repository: WorkoutPlanRepository<C>,
+    id_generator: Arc<dyn IdGenerator>,
 }
 
-impl WorkoutPlanService {
-    pub fn new(workout_plan_repository: WorkoutPlanRepository) -> Self {
+impl<C: DynamoOps> WorkoutPlanService<C> {
+    pub fn new(workout_plan_repository: WorkoutPlanRepository<C>) -> Self {
         Self {
             workout_plan_repository,
+            id_generator: default_id_generator(),
         }
     }
 
+    /// Use `id_generator` for the ids of created resources (random UUIDs by default)
+    pub fn with_id_generator(mut self, id_generator: Arc<dyn IdGenerator>) -> Self {
+        self.id_generator = id_generator;
+        self
+    }
+
     pub async fn get_workout_plans(
         &self,
         user_id: Option<String>,
+        consistent: bool,
         auth_context: &AuthContext,
     ) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
         // Authorization check
@@ -31,7 +44,7 @@ impl WorkoutPlanService {
             }
         }
         self.workout_plan_repository
-            .get_workout_plans(user_id)
+            .get_workout_plans(user_id, consistent)
             .await
     }
 
@@ -49,7 +62,7 @@ impl WorkoutPlanService {
             return Err(anyhow::anyhow!("You can only create workout plans for yourself").into());
         }
 
-        let plan_id = Uuid::new_v4().to_string();
+        let plan_id = self.id_generator.generate();
         let now = Utc::now().to_rfc3339();
 
         let plan = WorkoutPlan {
@@ -89,6 +102,7 @@ impl WorkoutPlanService {
             next_scheduled_date: plan_data["nextScheduledDate"]
                 .as_str()
                 .map(|s| s.to_string()),
+            version: 1,
         };
 
         self.workout_plan_repository
@@ -100,6 +114,7 @@ impl WorkoutPlanService {
         &self,
         user_id: &str,
         plan_id: &str,
+        consistent: bool,
         auth_context: &AuthContext,
     ) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
         // Authorization check
@@ -108,7 +123,7 @@ impl WorkoutPlanService {
         }
 
         self.workout_plan_repository
-            .get_workout_plan(user_id, plan_id)
+            .get_workout_plan(user_id, plan_id, consistent)
             .await
     }
 
@@ -116,6 +131,7 @@ impl WorkoutPlanService {
         &self,
         plan_data: &serde_json::Value,
         auth_context: &AuthContext,
+        if_match: Option<&str>,
     ) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
         let user_id = plan_data["userId"]
             .as_str()
@@ -129,6 +145,22 @@ impl WorkoutPlanService {
             return Err(anyhow::anyhow!("You can only update your own workout plans").into());
         }
 
+        // Reject the update if the plan changed since the client last read it. The read
+        // is consistent so a version the client just wrote is never seen as stale
+        let current = self
+            .workout_plan_repository
+            .get_workout_plan(user_id, plan_id, true)
+            .await?;
+        let current_version = current["version"].as_i64().unwrap_or(0);
+        if let Some(if_match) = if_match {
+            if !if_match_satisfied(if_match, &etag_from_version(current_version)) {
+                return Err(error_helpers::precondition_failed(
+                    "Workout plan has been modified since it was retrieved",
+                )
+                .into());
+            }
+        }
+
         let now = Utc::now().to_rfc3339();
 
         let plan = WorkoutPlan {
@@ -168,10 +200,12 @@ impl WorkoutPlanService {
             next_scheduled_date: plan_data["nextScheduledDate"]
                 .as_str()
                 .map(|s| s.to_string()),
+            version: current_version + 1,
         };
 
+        // The conditional write catches a concurrent update that landed after the read
         self.workout_plan_repository
-            .update_workout_plan(&plan)
+            .update_workout_plan(&plan, current_version)
             .await
     }
 
@@ -190,4 +224,20 @@ impl WorkoutPlanService {
             .delete_workout_plan(user_id, plan_id)
             .await
     }
+
+    pub async fn restore_workout_plan(
+        &self,
+        user_id: &str,
+        plan_id: &str,
+        auth_context: &AuthContext,
+    ) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
+        // Authorization check
+        if auth_context.user_id != user_id {
+            return Err(anyhow::anyhow!("You can only restore your own workout plans").into());
+        }
+
+        self.workout_plan_repository
+            .restore_workout_plan(user_id, plan_id)
+            .await
+    }
 }
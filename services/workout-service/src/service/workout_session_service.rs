@@ -1,28 +1,53 @@
 use anyhow::Result;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use tracing::{error, info};
-use uuid::Uuid;
+use std::sync::Arc;
 
 use crate::models::*;
 use crate::repository::WorkoutSessionRepository;
+use crate::service::AchievementService;
+use crate::utils::error::helpers as error_helpers;
+use crate::utils::id_generator::{default_id_generator, IdGenerator};
 use auth_layer::AuthContext;
+use lambda_router::{ListResponse, PageParams};
 
 #[derive(Clone)]
 pub struct WorkoutSessionService {
     workout_session_repository: WorkoutSessionRepository,
+    id_generator: Arc<dyn IdGenerator>,
+    achievement_service: Option<AchievementService>,
 }
 
 impl WorkoutSessionService {
     pub fn new(workout_session_repository: WorkoutSessionRepository) -> Self {
         Self {
             workout_session_repository,
+            id_generator: default_id_generator(),
+            achievement_service: None,
         }
     }
 
+    /// Use `id_generator` for the ids of created resources (random UUIDs by default)
+    pub fn with_id_generator(mut self, id_generator: Arc<dyn IdGenerator>) -> Self {
+        self.id_generator = id_generator;
+        self
+    }
+
+    /// Evaluate achievements with `achievement_service` whenever a completed session is saved
+    pub fn with_achievement_service(mut self, achievement_service: AchievementService) -> Self {
+        self.achievement_service = Some(achievement_service);
+        self
+    }
+
+    /// The user's sessions, newest first. Without `page` the whole list comes back as an
+    /// array; with it, a `ListResponse` page (for the caller when no `user_id` is given)
+    /// with the total count when it was asked for
     pub async fn get_workout_sessions(
         &self,
         user_id: Option<String>,
         workout_plan_id: Option<String>,
+        page: Option<PageParams>,
+        consistent: bool,
         auth_context: &AuthContext,
     ) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
         // Authorization check
@@ -34,9 +59,35 @@ impl WorkoutSessionService {
             }
         }
 
-        self.workout_session_repository
-            .get_workout_sessions(user_id, workout_plan_id)
-            .await
+        let Some(params) = page else {
+            return self
+                .workout_session_repository
+                .get_workout_sessions(user_id, workout_plan_id, None, consistent)
+                .await;
+        };
+        let user_id = user_id.unwrap_or_else(|| auth_context.user_id.clone());
+
+        let (sessions, last_key) = self
+            .workout_session_repository
+            .get_workout_sessions_page(
+                &user_id,
+                workout_plan_id.as_deref(),
+                params.limit,
+                params.start_key(),
+                consistent,
+            )
+            .await?;
+        let total = if params.include_total {
+            Some(
+                self.workout_session_repository
+                    .count_workout_sessions(&user_id, workout_plan_id.as_deref())
+                    .await?,
+            )
+        } else {
+            None
+        };
+
+        Ok(serde_json::to_value(ListResponse::new(sessions, &params, last_key, total))?)
     }
 
     pub async fn create_workout_session(
@@ -55,7 +106,7 @@ impl WorkoutSessionService {
             );
         }
 
-        let session_id = Uuid::new_v4().to_string();
+        let session_id = self.id_generator.generate();
         let now = Utc::now().to_rfc3339();
 
         let session = WorkoutSession {
@@ -88,20 +139,114 @@ impl WorkoutSessionService {
                 .unwrap_or_default(),
         };
 
+        let created = self
+            .workout_session_repository
+            .create_workout_session(&session)
+            .await?;
+        self.award_achievements(&session).await;
+
+        Ok(created)
+    }
+
+    /// Start an in-progress session for the caller, timed from now. Exercises, if given,
+    /// are the ones planned; sets are logged later with `log_session_sets`
+    pub async fn start_workout_session(
+        &self,
+        session_data: &serde_json::Value,
+        auth_context: &AuthContext,
+    ) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
+        let name = session_data["name"]
+            .as_str()
+            .ok_or_else(|| error_helpers::validation_failed("Session name is required"))?;
+        let exercises = parse_exercises(&session_data["exercises"])?.unwrap_or_default();
+        let now = Utc::now().to_rfc3339();
+
+        let session = WorkoutSession {
+            id: self.id_generator.generate(),
+            user_id: auth_context.user_id.clone(),
+            workout_plan_id: session_data["workoutPlanId"]
+                .as_str()
+                .map(|s| s.to_string()),
+            name: name.to_string(),
+            started_at: now.clone(),
+            completed_at: None,
+            duration_minutes: None,
+            exercises,
+            notes: session_data["notes"].as_str().map(|s| s.to_string()),
+            rating: None,
+            created_at: now.clone(),
+            updated_at: now,
+        };
+
         self.workout_session_repository
             .create_workout_session(&session)
             .await
     }
 
+    /// Append sets to one of the caller's in-progress sessions. The body's `exercises`
+    /// use the session's exercise shape; only their sets are added for exercises the
+    /// session already has
+    pub async fn log_session_sets(
+        &self,
+        session_id: &str,
+        session_data: &serde_json::Value,
+        auth_context: &AuthContext,
+    ) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
+        let exercises = parse_exercises(&session_data["exercises"])?.unwrap_or_default();
+        if exercises.iter().all(|exercise| exercise.sets.is_empty()) {
+            return Err(error_helpers::validation_failed("At least one set is required").into());
+        }
+
+        // Only the caller's partition is searched, so other users' sessions are not found
+        self.workout_session_repository
+            .append_session_sets(&auth_context.user_id, session_id, &exercises)
+            .await
+    }
+
+    /// Finish one of the caller's in-progress sessions, at `completedAt` when given
+    /// (RFC 3339) or now, and award any achievements it unlocked
+    pub async fn complete_workout_session(
+        &self,
+        session_id: &str,
+        session_data: &serde_json::Value,
+        auth_context: &AuthContext,
+    ) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
+        let completed_at = match session_data["completedAt"].as_str() {
+            Some(completed_at) => DateTime::parse_from_rfc3339(completed_at)
+                .map_err(|_| {
+                    error_helpers::validation_failed("Invalid completedAt, expected RFC 3339")
+                })?
+                .with_timezone(&Utc),
+            None => Utc::now(),
+        };
+        let rating = parse_rating(&session_data["rating"])?;
+
+        let completed = self
+            .workout_session_repository
+            .complete_workout_session(
+                &auth_context.user_id,
+                session_id,
+                completed_at,
+                session_data["notes"].as_str().map(|s| s.to_string()),
+                rating,
+            )
+            .await?;
+        let session: WorkoutSession = serde_json::from_value(completed.clone())?;
+        self.award_achievements(&session).await;
+
+        Ok(completed)
+    }
+
     pub async fn get_workout_session(
         &self,
         session_id: &str,
+        consistent: bool,
         auth_context: &AuthContext,
     ) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
         // First get the session to check ownership
         let session_result = self
             .workout_session_repository
-            .get_workout_session(session_id)
+            .get_workout_session(session_id, consistent)
             .await?;
         let session: WorkoutSession = serde_json::from_value(session_result)?;
 
@@ -165,9 +310,13 @@ impl WorkoutSessionService {
                 .unwrap_or_default(),
         };
 
-        self.workout_session_repository
+        let updated = self
+            .workout_session_repository
             .update_workout_session(&session)
-            .await
+            .await?;
+        self.award_achievements(&session).await;
+
+        Ok(updated)
     }
 
     pub async fn delete_workout_session(
@@ -178,7 +327,7 @@ impl WorkoutSessionService {
         // First get the session to check ownership
         let session_result = self
             .workout_session_repository
-            .get_workout_session(session_id)
+            .get_workout_session(session_id, false)
             .await?;
         let session: WorkoutSession = serde_json::from_value(session_result)?;
 
@@ -191,4 +340,108 @@ impl WorkoutSessionService {
             .delete_workout_session(session_id)
             .await
     }
+
+    pub async fn restore_workout_session(
+        &self,
+        session_id: &str,
+        auth_context: &AuthContext,
+    ) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
+        // Deleted sessions are hidden from lookups, so ownership is enforced by only
+        // restoring under the caller's own partition
+        self.workout_session_repository
+            .restore_workout_session(&auth_context.user_id, session_id)
+            .await
+    }
+
+    /// Move the caller's sessions still under legacy keys to time-prefixed ones,
+    /// returning how many moved. Listings read both key forms, so this can run whenever
+    /// convenient; until it has, a limited listing may leave legacy sessions out
+    pub async fn migrate_session_keys(
+        &self,
+        auth_context: &AuthContext,
+    ) -> Result<serde_json::Value, Box<dyn std::error::Error + Send + Sync>> {
+        let migrated = self
+            .workout_session_repository
+            .migrate_legacy_sessions(&auth_context.user_id)
+            .await?;
+        Ok(serde_json::json!({ "migrated": migrated }))
+    }
+
+    /// Award any achievements a completed session unlocked. Evaluation is idempotent, so
+    /// saving a completed session again is harmless. Streaks are counted in UTC here, as
+    /// session writes carry no timezone. Failures are logged rather than failing the write
+    async fn award_achievements(&self, session: &WorkoutSession) {
+        let Some(achievement_service) = &self.achievement_service else {
+            return;
+        };
+        if session.completed_at.is_none() {
+            return;
+        }
+
+        if let Err(e) = achievement_service
+            .evaluate_achievements(&session.user_id, chrono_tz::Tz::UTC, false)
+            .await
+        {
+            error!(
+                "Failed to evaluate achievements for session {}: {}",
+                session.id, e
+            );
+        }
+    }
+}
+
+/// A 1-5 session rating from a request body; `None` when the field is absent or null.
+/// Anything else, including negative or fractional numbers, is rejected
+fn parse_rating(
+    rating: &serde_json::Value,
+) -> Result<Option<i32>, Box<dyn std::error::Error + Send + Sync>> {
+    if rating.is_null() {
+        return Ok(None);
+    }
+    match rating.as_u64() {
+        Some(rating @ 1..=5) => Ok(Some(rating as i32)),
+        _ => Err(
+            error_helpers::validation_failed("Rating must be a whole number between 1 and 5")
+                .into(),
+        ),
+    }
+}
+
+/// Session exercises from a request body; `None` when the field is absent. Unlike full
+/// session writes, a malformed entry is rejected rather than dropped, so no logged set
+/// silently goes missing
+fn parse_exercises(
+    exercises: &serde_json::Value,
+) -> Result<Option<Vec<SessionExercise>>, Box<dyn std::error::Error + Send + Sync>> {
+    if exercises.is_null() {
+        return Ok(None);
+    }
+    serde_json::from_value(exercises.clone())
+        .map(Some)
+        .map_err(|e| error_helpers::validation_failed(&format!("Invalid exercises: {}", e)).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::error::ServiceError;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_rating_rejects_non_integers() {
+        assert_eq!(parse_rating(&json!(null)).unwrap(), None);
+        assert_eq!(parse_rating(&json!(4)).unwrap(), Some(4));
+
+        for invalid in [json!(0), json!(6), json!(-1), json!(4.5), json!("5")] {
+            let error = parse_rating(&invalid).unwrap_err();
+            assert!(
+                matches!(
+                    error.downcast_ref::<ServiceError>(),
+                    Some(ServiceError::Validation(..))
+                ),
+                "{} should be rejected",
+                invalid
+            );
+        }
+    }
 }
@@ -29,5 +29,8 @@ pub const DEFAULT_SLEEP_PERIOD: &str = "month";
 pub const DEFAULT_PROFILE_VISIBILITY: &str = "private";
 pub const DEFAULT_UNITS: &str = "metric";
 pub const DEFAULT_TIMEZONE: &str = "UTC";
-pub const DEFAULT_TABLE_NAME: &str = "gymcoach-ai-main";
 pub const DEFAULT_S3_BUCKET: &str = "gymcoach-ai-user-uploads";
+pub const DEFAULT_EXERCISE_SEARCH_LIMIT: usize = 20;
+pub const MAX_EXERCISE_SEARCH_LIMIT: usize = 100;
+/// How long a deleted workout plan or session can be restored before its TTL purges it
+pub const SOFT_DELETE_RETENTION_DAYS: i64 = 30;
@@ -0,0 +1,298 @@
+use aws_sdk_dynamodb::types::AttributeValue;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+pub type Item = HashMap<String, AttributeValue>;
+
+/// Casing of attribute names in stored items. Struct fields are expected to be snake_case
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyCase {
+    Camel,
+    Pascal,
+}
+
+/// How lists and maps are stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CollectionEncoding {
+    /// DynamoDB `L` / `M` attributes
+    Native,
+    /// JSON-encoded `S` attributes, as written by the seed scripts and the AI service
+    JsonString,
+}
+
+/// Mapping between a serde struct and a DynamoDB item
+#[derive(Debug, Clone)]
+pub struct ItemFormat {
+    pub key_case: KeyCase,
+    pub collections: CollectionEncoding,
+    /// Fields stored under a name other than their cased field name, as `(field, attribute)`
+    pub renames: Vec<(&'static str, &'static str)>,
+    /// Casing of an older layout still read as a fallback for fields missing in this one
+    pub legacy_case: Option<KeyCase>,
+}
+
+impl ItemFormat {
+    pub fn new(key_case: KeyCase) -> Self {
+        Self {
+            key_case,
+            collections: CollectionEncoding::Native,
+            renames: Vec::new(),
+            legacy_case: None,
+        }
+    }
+
+    pub fn collections(mut self, collections: CollectionEncoding) -> Self {
+        self.collections = collections;
+        self
+    }
+
+    pub fn rename(mut self, field: &'static str, attribute: &'static str) -> Self {
+        self.renames.push((field, attribute));
+        self
+    }
+
+    pub fn legacy_case(mut self, legacy_case: KeyCase) -> Self {
+        self.legacy_case = Some(legacy_case);
+        self
+    }
+
+    fn attribute_name(&self, field: &str) -> String {
+        self.renames
+            .iter()
+            .find(|(from, _)| *from == field)
+            .map(|(_, to)| to.to_string())
+            .unwrap_or_else(|| convert_case(field, self.key_case))
+    }
+
+    /// The field stored under `attribute`, or `None` if the attribute isn't written in
+    /// this format (e.g. a legacy copy of a field in another casing)
+    fn field_name(&self, attribute: &str) -> Option<String> {
+        let field = self
+            .renames
+            .iter()
+            .find(|(_, to)| *to == attribute)
+            .map(|(from, _)| from.to_string())
+            .unwrap_or_else(|| to_snake_case(attribute));
+        (self.attribute_name(&field) == attribute).then_some(field)
+    }
+
+    /// The field an attribute in the legacy casing belongs to, if any
+    fn legacy_field_name(&self, attribute: &str) -> Option<String> {
+        let field = to_snake_case(attribute);
+        (convert_case(&field, self.legacy_case?) == attribute).then_some(field)
+    }
+}
+
+/// Serialize a struct into an item. `None` fields are omitted; key attributes (`PK`,
+/// `SK`, GSI keys) are left for the caller to add
+pub fn to_item<T: Serialize>(value: &T, format: &ItemFormat) -> Result<Item, serde_dynamo::Error> {
+    let fields = match serde_json::to_value(value) {
+        Ok(Value::Object(fields)) => fields,
+        Ok(_) => {
+            return Err(serde::ser::Error::custom(
+                "only structs can be stored as items",
+            ))
+        }
+        Err(e) => return Err(serde::ser::Error::custom(e)),
+    };
+
+    let mut attributes = Map::new();
+    for (field, value) in fields {
+        let value = match value {
+            Value::Null => continue,
+            Value::Array(_) | Value::Object(_)
+                if format.collections == CollectionEncoding::JsonString =>
+            {
+                Value::String(value.to_string())
+            }
+            value => value,
+        };
+        attributes.insert(format.attribute_name(&field), value);
+    }
+
+    serde_dynamo::to_item(Value::Object(attributes))
+}
+
+/// Deserialize a struct from an item. Attributes in the legacy casing only fill fields
+/// missing from the current layout; other attributes are ignored. Collections are
+/// accepted both JSON-encoded and native, whichever encoding the format writes
+pub fn from_item<T: DeserializeOwned>(
+    item: Item,
+    format: &ItemFormat,
+) -> Result<T, serde_dynamo::Error> {
+    let attributes: Map<String, Value> = serde_dynamo::from_item(item)?;
+
+    let mut fields = Map::new();
+    let mut legacy_fields = Vec::new();
+    for (attribute, value) in attributes {
+        let value = match value {
+            Value::String(s) if format.collections == CollectionEncoding::JsonString => {
+                decode_collection(s)
+            }
+            value => value,
+        };
+        if let Some(field) = format.field_name(&attribute) {
+            fields.insert(field, value);
+        } else if let Some(field) = format.legacy_field_name(&attribute) {
+            legacy_fields.push((field, value));
+        }
+    }
+    for (field, value) in legacy_fields {
+        fields.entry(field).or_insert(value);
+    }
+
+    serde_json::from_value(Value::Object(fields)).map_err(serde::de::Error::custom)
+}
+
+/// A JSON-encoded list or map, or the string unchanged
+fn decode_collection(s: String) -> Value {
+    if s.starts_with('[') || s.starts_with('{') {
+        if let Ok(decoded @ (Value::Array(_) | Value::Object(_))) = serde_json::from_str(&s) {
+            return decoded;
+        }
+    }
+    Value::String(s)
+}
+
+fn convert_case(snake: &str, case: KeyCase) -> String {
+    let mut converted = String::with_capacity(snake.len());
+    let mut upper = case == KeyCase::Pascal;
+    for c in snake.chars() {
+        if c == '_' {
+            upper = true;
+        } else if upper {
+            converted.extend(c.to_uppercase());
+            upper = false;
+        } else {
+            converted.push(c);
+        }
+    }
+    converted
+}
+
+fn to_snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Sample {
+        id: String,
+        display_name: String,
+        muscle_groups: Vec<String>,
+        is_active: bool,
+        rest_seconds: Option<u32>,
+        video_url: Option<String>,
+    }
+
+    fn sample() -> Sample {
+        Sample {
+            id: "abc".to_string(),
+            display_name: "Bench Press".to_string(),
+            muscle_groups: vec!["chest".to_string(), "triceps".to_string()],
+            is_active: true,
+            rest_seconds: Some(90),
+            video_url: None,
+        }
+    }
+
+    #[test]
+    fn test_key_case_conversion() {
+        assert_eq!(
+            convert_case("muscle_groups", KeyCase::Pascal),
+            "MuscleGroups"
+        );
+        assert_eq!(
+            convert_case("muscle_groups", KeyCase::Camel),
+            "muscleGroups"
+        );
+        assert_eq!(to_snake_case("MuscleGroups"), "muscle_groups");
+        assert_eq!(to_snake_case("videoUrl"), "video_url");
+    }
+
+    #[test]
+    fn test_native_round_trip() {
+        let format = ItemFormat::new(KeyCase::Camel);
+        let item = to_item(&sample(), &format).unwrap();
+
+        assert_eq!(
+            item["displayName"],
+            AttributeValue::S("Bench Press".to_string())
+        );
+        assert_eq!(item["restSeconds"], AttributeValue::N("90".to_string()));
+        assert!(matches!(&item["muscleGroups"], AttributeValue::L(groups) if groups.len() == 2));
+        assert!(!item.contains_key("videoUrl"));
+
+        assert_eq!(from_item::<Sample>(item, &format).unwrap(), sample());
+    }
+
+    #[test]
+    fn test_json_string_collections_and_renames() {
+        let format = ItemFormat::new(KeyCase::Pascal)
+            .collections(CollectionEncoding::JsonString)
+            .rename("id", "SampleId");
+        let mut item = to_item(&sample(), &format).unwrap();
+
+        assert_eq!(item["SampleId"], AttributeValue::S("abc".to_string()));
+        assert_eq!(
+            item["MuscleGroups"],
+            AttributeValue::S(r#"["chest","triceps"]"#.to_string())
+        );
+
+        // Key attributes, unknown attributes and legacy copies in another casing are
+        // ignored when reading back
+        item.insert("PK".to_string(), AttributeValue::S("SAMPLES".to_string()));
+        item.insert(
+            "display_name".to_string(),
+            AttributeValue::S("Old name".to_string()),
+        );
+        item.insert("id".to_string(), AttributeValue::S("legacy".to_string()));
+        item.insert(
+            "GSI1SK".to_string(),
+            AttributeValue::S("SYSTEM#bench press".to_string()),
+        );
+        assert_eq!(from_item::<Sample>(item, &format).unwrap(), sample());
+    }
+
+    #[test]
+    fn test_legacy_case_fills_missing_fields() {
+        let format = ItemFormat::new(KeyCase::Pascal)
+            .collections(CollectionEncoding::JsonString)
+            .rename("id", "SampleId")
+            .legacy_case(KeyCase::Camel);
+
+        // Written by an older version: camelCase with a native list
+        let legacy = to_item(&sample(), &ItemFormat::new(KeyCase::Camel)).unwrap();
+        assert_eq!(
+            from_item::<Sample>(legacy.clone(), &format).unwrap(),
+            sample()
+        );
+
+        // Both layouts present: the current one wins
+        let mut item = legacy;
+        item.insert(
+            "DisplayName".to_string(),
+            AttributeValue::S("Flat Bench Press".to_string()),
+        );
+        let parsed: Sample = from_item(item, &format).unwrap();
+        assert_eq!(parsed.display_name, "Flat Bench Press");
+        assert_eq!(parsed.muscle_groups, vec!["chest", "triceps"]);
+    }
+}
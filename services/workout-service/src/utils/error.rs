@@ -1,4 +1,5 @@
 use std::fmt;
+use lambda_router::{Response, RouterError};
 use serde_json::Value;
 
 /// Custom error types for the nutrition service
@@ -16,7 +17,9 @@ pub enum ServiceError {
     /// S3 operation errors
     S3(String),
     /// Conflict errors (e.g., resource already exists)
-    Conflict(String),
+    Conflict(String, Option<Value>),
+    /// Conditional request failed (e.g., stale If-Match)
+    PreconditionFailed(String),
     /// Generic internal server errors
     Internal(String),
 }
@@ -30,7 +33,8 @@ impl fmt::Display for ServiceError {
             ServiceError::NotFound(msg) => write!(f, "Not Found: {}", msg),
             ServiceError::Database(msg) => write!(f, "Database Error: {}", msg),
             ServiceError::S3(msg) => write!(f, "S3 Error: {}", msg),
-            ServiceError::Conflict(msg) => write!(f, "Conflict: {}", msg),
+            ServiceError::Conflict(msg, _) => write!(f, "Conflict: {}", msg),
+            ServiceError::PreconditionFailed(msg) => write!(f, "Precondition Failed: {}", msg),
             ServiceError::Internal(msg) => write!(f, "Internal Server Error: {}", msg),
         }
     }
@@ -38,11 +42,38 @@ impl fmt::Display for ServiceError {
 
 impl std::error::Error for ServiceError {}
 
+/// Lets handlers `?`-propagate service errors straight into the router, which turns
+/// them into `{error, message, request_id}` responses with the matching status
+impl From<ServiceError> for RouterError {
+    fn from(error: ServiceError) -> Self {
+        match error {
+            ServiceError::Unauthorized(msg) => RouterError::Unauthorized(msg),
+            ServiceError::Forbidden(msg) => RouterError::Forbidden(msg),
+            ServiceError::Validation(msg, _) => RouterError::BadRequest(msg),
+            ServiceError::NotFound(msg) => RouterError::NotFound(msg),
+            ServiceError::Conflict(msg, _) => RouterError::Conflict(msg),
+            ServiceError::PreconditionFailed(msg) => RouterError::PreconditionFailed(msg),
+            // Storage errors carry SDK details that should not reach clients
+            ServiceError::Database(_) | ServiceError::S3(_) => {
+                RouterError::Internal("A storage error occurred".to_string())
+            }
+            ServiceError::Internal(msg) => RouterError::Internal(msg),
+        }
+    }
+}
+
+impl From<ServiceError> for Response {
+    fn from(error: ServiceError) -> Self {
+        RouterError::from(error).to_response()
+    }
+}
+
 /// A specialized `Result` type for service operations.
 pub type ServiceResult<T> = Result<T, ServiceError>;
 
 /// Helper functions for creating common `ServiceError` instances.
 pub mod helpers {
+    use aws_sdk_dynamodb::error::{ProvideErrorMetadata, SdkError};
     use serde_json::Value;
     use super::ServiceError;
 
@@ -71,7 +102,15 @@ pub mod helpers {
     }
 
     pub fn conflict(message: &str) -> ServiceError {
-        ServiceError::Conflict(message.to_string())
+        ServiceError::Conflict(message.to_string(), None)
+    }
+
+    pub fn conflict_with_details(message: &str, details: Option<Value>) -> ServiceError {
+        ServiceError::Conflict(message.to_string(), details)
+    }
+
+    pub fn precondition_failed(message: &str) -> ServiceError {
+        ServiceError::PreconditionFailed(message.to_string())
     }
 
     pub fn internal_error(message: &str) -> ServiceError {
@@ -98,4 +137,87 @@ pub mod helpers {
         ServiceError::NotFound(format!("{} not found", resource))
     }
 
+    /// Map a failed conditional DynamoDB write to `on_condition_failed`, passing any
+    /// other SDK error through unchanged
+    pub fn conditional_write_error<E>(
+        err: SdkError<E>,
+        on_condition_failed: ServiceError,
+    ) -> Box<dyn std::error::Error + Send + Sync>
+    where
+        E: ProvideErrorMetadata + std::error::Error + Send + Sync + 'static,
+    {
+        if err.code() == Some("ConditionalCheckFailedException") {
+            Box::new(on_condition_failed)
+        } else {
+            Box::new(err)
+        }
+    }
+
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use aws_sdk_dynamodb::config::http::HttpResponse;
+    use aws_sdk_dynamodb::error::{ErrorMetadata, ProvideErrorMetadata, SdkError};
+    use aws_sdk_dynamodb::operation::put_item::PutItemError;
+    use aws_sdk_dynamodb::types::error::ConditionalCheckFailedException;
+
+    #[test]
+    fn test_service_error_maps_to_status() {
+        assert_eq!(Response::from(helpers::validation_failed("bad")).status_code, 400);
+        assert_eq!(Response::from(helpers::unauthorized("who")).status_code, 401);
+        assert_eq!(Response::from(helpers::forbidden("no")).status_code, 403);
+        assert_eq!(Response::from(helpers::resource_not_found("Workout plan")).status_code, 404);
+        assert_eq!(Response::from(helpers::conflict("stale")).status_code, 409);
+        assert_eq!(Response::from(helpers::precondition_failed("stale")).status_code, 412);
+        assert_eq!(Response::from(helpers::internal_error("boom")).status_code, 500);
+    }
+
+    #[test]
+    fn test_storage_errors_hide_details() {
+        let response = Response::from(helpers::database_error(
+            "ResourceNotFoundException: table gymcoach",
+        ));
+        let body: Value = serde_json::from_str(&response.body).unwrap();
+        assert_eq!(response.status_code, 500);
+        assert_eq!(body["message"], "A storage error occurred");
+    }
+
+    /// A PutItem that failed with `code`, as the SDK reports it
+    fn put_item_failure(code: &str) -> SdkError<PutItemError> {
+        let meta = ErrorMetadata::builder().code(code).message("failed").build();
+        let error = match code {
+            "ConditionalCheckFailedException" => PutItemError::ConditionalCheckFailedException(
+                ConditionalCheckFailedException::builder().message("failed").meta(meta).build(),
+            ),
+            _ => PutItemError::generic(meta),
+        };
+        SdkError::service_error(error, HttpResponse::new(400.try_into().unwrap(), "".into()))
+    }
+
+    #[test]
+    fn test_failed_condition_maps_to_the_given_error() {
+        let error = helpers::conditional_write_error(
+            put_item_failure("ConditionalCheckFailedException"),
+            helpers::conflict("Workout plan was changed by another request"),
+        );
+
+        let error = error.downcast::<ServiceError>().unwrap();
+        assert!(matches!(*error, ServiceError::Conflict(..)));
+        assert_eq!(Response::from(*error).status_code, 409);
+    }
+
+    #[test]
+    fn test_other_write_errors_pass_through() {
+        let error = helpers::conditional_write_error(
+            put_item_failure("ProvisionedThroughputExceededException"),
+            helpers::conflict("Workout plan was changed by another request"),
+        );
+
+        assert!(error.downcast_ref::<ServiceError>().is_none());
+        let error = error.downcast::<SdkError<PutItemError>>().unwrap();
+        assert_eq!(error.code(), Some("ProvisionedThroughputExceededException"));
+    }
+}
+
@@ -45,6 +45,20 @@ pub fn get_current_timestamp() -> String {
     chrono::Utc::now().to_rfc3339()
 }
 
+/// Build a quoted ETag from a resource's `version`
+pub fn etag_from_version(version: i64) -> String {
+    format!("\"{}\"", version)
+}
+
+/// Check an `If-Match` header against the current ETag using strong comparison,
+/// so weak (`W/`) tags never match
+pub fn if_match_satisfied(if_match: &str, current_etag: &str) -> bool {
+    if_match
+        .split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag == current_etag)
+}
+
 /// Extract user ID from path
 pub fn extract_user_id_from_path(path: &str) -> Option<String> {
     let path_parts: Vec<&str> = path.split('/').collect();
@@ -66,3 +80,23 @@ pub fn extract_user_id_from_path(path: &str) -> Option<String> {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_if_match_current_etag_succeeds() {
+        let etag = etag_from_version(3);
+        assert!(if_match_satisfied("\"3\"", &etag));
+        assert!(if_match_satisfied("\"2\", \"3\"", &etag));
+        assert!(if_match_satisfied("*", &etag));
+    }
+
+    #[test]
+    fn test_if_match_stale_etag_fails() {
+        let etag = etag_from_version(3);
+        assert!(!if_match_satisfied("\"2\"", &etag));
+        assert!(!if_match_satisfied("W/\"3\"", &etag));
+    }
+}
@@ -0,0 +1,140 @@
+#[cfg(test)]
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// Crockford base32, the ULID alphabet (no I, L, O or U)
+const CROCKFORD_BASE32: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Source of ids for newly created resources. Services hold one so the id scheme can be
+/// chosen at startup and tests can make ids predictable
+pub trait IdGenerator: Send + Sync {
+    fn generate(&self) -> String;
+}
+
+/// Random v4 UUIDs, the default
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UuidGenerator;
+
+impl IdGenerator for UuidGenerator {
+    fn generate(&self) -> String {
+        Uuid::new_v4().to_string()
+    }
+}
+
+/// ULIDs: a 48-bit millisecond timestamp followed by 80 random bits, as 26 Crockford
+/// base32 characters. They sort lexicographically by creation time, so SKs built from
+/// them come back from a query in creation order. Ids minted in the same millisecond
+/// are not ordered among themselves
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UlidGenerator;
+
+impl IdGenerator for UlidGenerator {
+    fn generate(&self) -> String {
+        let millis = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_millis() as u64)
+            .unwrap_or_default();
+        // A v4 UUID's version and variant bits sit in bytes 6 and 8; the rest are random
+        let bytes = Uuid::new_v4().into_bytes();
+        let random = bytes[..6]
+            .iter()
+            .chain(&bytes[12..])
+            .fold(0u128, |acc, byte| (acc << 8) | *byte as u128);
+        encode_ulid(millis, random)
+    }
+}
+
+/// `{prefix}-1`, `{prefix}-2`, ... for tests that assert on created ids
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct SequentialIdGenerator {
+    prefix: String,
+    next: AtomicU64,
+}
+
+#[cfg(test)]
+impl SequentialIdGenerator {
+    pub fn new(prefix: &str) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+            next: AtomicU64::new(1),
+        }
+    }
+}
+
+#[cfg(test)]
+impl IdGenerator for SequentialIdGenerator {
+    fn generate(&self) -> String {
+        format!(
+            "{}-{}",
+            self.prefix,
+            self.next.fetch_add(1, Ordering::Relaxed)
+        )
+    }
+}
+
+/// Pick a generator by name (case-insensitive), e.g. from the `ID_GENERATOR` env var
+pub fn id_generator_from_name(name: &str) -> Option<Arc<dyn IdGenerator>> {
+    match name.trim().to_lowercase().as_str() {
+        "uuid" => Some(Arc::new(UuidGenerator)),
+        "ulid" => Some(Arc::new(UlidGenerator)),
+        _ => None,
+    }
+}
+
+/// The generator services use unless another is injected
+pub fn default_id_generator() -> Arc<dyn IdGenerator> {
+    Arc::new(UuidGenerator)
+}
+
+/// Encode the timestamp (10 chars, 5 bits each, high bits first) then the 80 random
+/// bits (16 chars)
+fn encode_ulid(millis: u64, random: u128) -> String {
+    let value = ((millis as u128 & 0xFFFF_FFFF_FFFF) << 80) | (random & ((1u128 << 80) - 1));
+    (0..26)
+        .rev()
+        .map(|i| CROCKFORD_BASE32[((value >> (i * 5)) & 0x1F) as usize] as char)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ulid_encoding() {
+        assert_eq!(encode_ulid(0, 0), "00000000000000000000000000");
+        assert_eq!(
+            encode_ulid(1_469_918_176_385, 0),
+            "01ARYZ6S410000000000000000"
+        );
+        assert_eq!(encode_ulid(0, u128::MAX), "0000000000ZZZZZZZZZZZZZZZZ");
+    }
+
+    #[test]
+    fn test_ulids_sort_by_creation_time() {
+        let earlier = encode_ulid(1_700_000_000_000, u128::MAX);
+        let later = encode_ulid(1_700_000_000_001, 0);
+        assert!(earlier < later);
+
+        let generated = UlidGenerator.generate();
+        assert_eq!(generated.len(), 26);
+        assert!(generated.bytes().all(|c| CROCKFORD_BASE32.contains(&c)));
+    }
+
+    #[test]
+    fn test_sequential_ids() {
+        let ids = SequentialIdGenerator::new("plan");
+        assert_eq!(ids.generate(), "plan-1");
+        assert_eq!(ids.generate(), "plan-2");
+    }
+
+    #[test]
+    fn test_id_generator_from_name() {
+        assert_eq!(id_generator_from_name("ULID").unwrap().generate().len(), 26);
+        assert_eq!(id_generator_from_name("uuid").unwrap().generate().len(), 36);
+        assert!(id_generator_from_name("snowflake").is_none());
+    }
+}
@@ -1,9 +1,12 @@
 pub mod constants;
 pub mod data_helper;
+pub mod dynamo_item;
 pub mod error;
 pub mod http;
+pub mod id_generator;
 pub mod response;
 pub mod routing;
+pub mod update_expression;
 pub mod validation;
 
 pub use data_helper::DataHelper;
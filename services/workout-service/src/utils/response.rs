@@ -76,6 +76,29 @@ impl ResponseBuilder {
         }))
     }
 
+    /// Build a 409 Conflict response that also describes what it conflicted with
+    pub fn conflict_with_details(message: &str, details: Option<Value>) -> Value {
+        Self::custom(409, json!({
+            "error": "Conflict",
+            "message": message,
+            "details": details
+        }))
+    }
+
+    /// Build a 412 Precondition Failed response
+    pub fn precondition_failed(message: &str) -> Value {
+        Self::custom(412, json!({
+            "error": "Precondition Failed",
+            "message": message
+        }))
+    }
+
+    /// Attach an `ETag` header to a built response
+    pub fn with_etag(mut response: Value, etag: &str) -> Value {
+        response["headers"]["ETag"] = json!(etag);
+        response
+    }
+
     /// Build a 422 Unprocessable Entity response for validation errors
     pub fn validation_error(message: &str, details: Option<Value>) -> Value {
         json!({
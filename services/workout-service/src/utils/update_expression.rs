@@ -0,0 +1,197 @@
+use aws_sdk_dynamodb::operation::update_item::builders::UpdateItemInputBuilder;
+use aws_sdk_dynamodb::types::AttributeValue;
+use std::collections::HashMap;
+
+/// Values that can be written to an attribute by `UpdateExpressionBuilder::set`
+pub trait IntoAttributeValue {
+    fn into_attribute_value(self) -> AttributeValue;
+}
+
+impl IntoAttributeValue for AttributeValue {
+    fn into_attribute_value(self) -> AttributeValue {
+        self
+    }
+}
+
+impl IntoAttributeValue for String {
+    fn into_attribute_value(self) -> AttributeValue {
+        AttributeValue::S(self)
+    }
+}
+
+impl IntoAttributeValue for &str {
+    fn into_attribute_value(self) -> AttributeValue {
+        AttributeValue::S(self.to_string())
+    }
+}
+
+impl IntoAttributeValue for &String {
+    fn into_attribute_value(self) -> AttributeValue {
+        AttributeValue::S(self.clone())
+    }
+}
+
+impl IntoAttributeValue for bool {
+    fn into_attribute_value(self) -> AttributeValue {
+        AttributeValue::Bool(self)
+    }
+}
+
+macro_rules! impl_number_attribute_value {
+    ($($t:ty),*) => {
+        $(impl IntoAttributeValue for $t {
+            fn into_attribute_value(self) -> AttributeValue {
+                AttributeValue::N(self.to_string())
+            }
+        })*
+    };
+}
+
+impl_number_attribute_value!(i32, i64, u32, u64, f32, f64);
+
+#[derive(Debug, Clone)]
+enum Action {
+    Set(AttributeValue),
+    Remove,
+}
+
+/// Accumulates `SET` and `REMOVE` clauses for an `UpdateItem` call. Every attribute name
+/// goes through a `#aN` placeholder, so reserved words like `Status` need no special care.
+/// Updating the same attribute twice keeps the last action
+#[derive(Debug, Clone, Default)]
+pub struct UpdateExpressionBuilder {
+    actions: Vec<(String, Action)>,
+}
+
+impl UpdateExpressionBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(self, attribute: &str, value: impl IntoAttributeValue) -> Self {
+        self.push(attribute, Action::Set(value.into_attribute_value()))
+    }
+
+    /// `SET` the attribute when there is a value, `REMOVE` it otherwise
+    pub fn set_or_remove<T: IntoAttributeValue>(self, attribute: &str, value: Option<T>) -> Self {
+        match value {
+            Some(value) => self.set(attribute, value),
+            None => self.remove(attribute),
+        }
+    }
+
+    pub fn remove(self, attribute: &str) -> Self {
+        self.push(attribute, Action::Remove)
+    }
+
+    fn push(mut self, attribute: &str, action: Action) -> Self {
+        match self.actions.iter_mut().find(|(name, _)| name == attribute) {
+            Some((_, existing)) => *existing = action,
+            None => self.actions.push((attribute.to_string(), action)),
+        }
+        self
+    }
+
+    pub fn build(self) -> UpdateExpression {
+        let mut set_clauses = Vec::new();
+        let mut remove_clauses = Vec::new();
+        let mut names = HashMap::new();
+        let mut values = HashMap::new();
+
+        for (index, (attribute, action)) in self.actions.into_iter().enumerate() {
+            let name = format!("#a{}", index);
+            match action {
+                Action::Set(value) => {
+                    let placeholder = format!(":v{}", index);
+                    set_clauses.push(format!("{} = {}", name, placeholder));
+                    values.insert(placeholder, value);
+                }
+                Action::Remove => remove_clauses.push(name.clone()),
+            }
+            names.insert(name, attribute);
+        }
+
+        let mut clauses = Vec::new();
+        if !set_clauses.is_empty() {
+            clauses.push(format!("SET {}", set_clauses.join(", ")));
+        }
+        if !remove_clauses.is_empty() {
+            clauses.push(format!("REMOVE {}", remove_clauses.join(", ")));
+        }
+
+        UpdateExpression {
+            expression: clauses.join(" "),
+            names,
+            values,
+        }
+    }
+}
+
+/// An update expression with its attribute name and value maps
+#[derive(Debug, Clone, PartialEq)]
+pub struct UpdateExpression {
+    pub expression: String,
+    pub names: HashMap<String, String>,
+    pub values: HashMap<String, AttributeValue>,
+}
+
+impl UpdateExpression {
+    /// Set the expression and both maps on an `UpdateItem` request. The value map is
+    /// left unset for REMOVE-only updates, since DynamoDB rejects an empty one
+    pub fn apply(self, request: UpdateItemInputBuilder) -> UpdateItemInputBuilder {
+        let values = (!self.values.is_empty()).then_some(self.values);
+        request
+            .update_expression(self.expression)
+            .set_expression_attribute_names(Some(self.names))
+            .set_expression_attribute_values(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_and_remove_clauses() {
+        let update = UpdateExpressionBuilder::new()
+            .set("Status", "completed")
+            .set("Week", 3u32)
+            .set_or_remove("Notes", None::<String>)
+            .set_or_remove("CompletedAt", Some("2024-03-01T10:00:00Z"))
+            .build();
+
+        assert_eq!(
+            update.expression,
+            "SET #a0 = :v0, #a1 = :v1, #a3 = :v3 REMOVE #a2"
+        );
+        assert_eq!(update.names["#a0"], "Status");
+        assert_eq!(update.names["#a2"], "Notes");
+        assert_eq!(update.names.len(), 4);
+        assert_eq!(
+            update.values[":v0"],
+            AttributeValue::S("completed".to_string())
+        );
+        assert_eq!(update.values[":v1"], AttributeValue::N("3".to_string()));
+        assert_eq!(update.values.len(), 3);
+    }
+
+    #[test]
+    fn test_repeated_attribute_keeps_last_action() {
+        let update = UpdateExpressionBuilder::new()
+            .set("Status", "scheduled")
+            .set("UpdatedAt", "2024-03-01T10:00:00Z")
+            .remove("Status")
+            .build();
+
+        assert_eq!(update.expression, "SET #a1 = :v1 REMOVE #a0");
+        assert_eq!(update.names["#a0"], "Status");
+        assert!(!update.values.contains_key(":v0"));
+    }
+
+    #[test]
+    fn test_empty_builder() {
+        let update = UpdateExpressionBuilder::new().build();
+        assert!(update.expression.is_empty());
+        assert!(update.names.is_empty());
+    }
+}
@@ -669,39 +669,41 @@ async fn test_performance_under_stress() {
     // Test system performance under stress
 
     // Test 1: High concurrent load
-    let (results, duration) = performance_utils::measure_time(|| {
-        performance_utils::run_concurrent_tests(1000, |i| async move {
-            let user_profile_data = serde_json::json!({
+    // `measure_time` is synchronous and would only time building the future
+    let start = std::time::Instant::now();
+    let results = performance_utils::run_concurrent_tests(1000, |i| async move {
+        let user_profile_data = serde_json::json!({
+            "userId": format!("user{}", i),
+            "email": format!("user{}@example.com", i),
+            "name": format!("User {}", i),
+            "fitnessGoals": "Build muscle",
+            "experienceLevel": "beginner"
+        });
+
+        let event = test_utils::create_mock_event(
+            "POST",
+            "/api/user-profiles",
+            Some(user_profile_data),
+            None,
+            None,
+            None,
+        );
+
+        // Simulate successful response
+        serde_json::json!({
+            "statusCode": 201,
+            "body": serde_json::to_string(&serde_json::json!({
                 "userId": format!("user{}", i),
                 "email": format!("user{}@example.com", i),
                 "name": format!("User {}", i),
                 "fitnessGoals": "Build muscle",
-                "experienceLevel": "beginner"
-            });
-
-            let event = test_utils::create_mock_event(
-                "POST",
-                "/api/user-profiles",
-                Some(user_profile_data),
-                None,
-                None,
-                None,
-            );
-
-            // Simulate successful response
-            serde_json::json!({
-                "statusCode": 201,
-                "body": serde_json::to_string(&serde_json::json!({
-                    "userId": format!("user{}", i),
-                    "email": format!("user{}@example.com", i),
-                    "name": format!("User {}", i),
-                    "fitnessGoals": "Build muscle",
-                    "experienceLevel": "beginner",
-                    "createdAt": "2024-01-01T00:00:00Z"
-                })).unwrap()
-            })
+                "experienceLevel": "beginner",
+                "createdAt": "2024-01-01T00:00:00Z"
+            })).unwrap()
         })
-    });
+    })
+    .await;
+    let duration = start.elapsed();
 
     // Validate all requests succeeded
     for result in &results {
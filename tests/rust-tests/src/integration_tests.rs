@@ -491,6 +491,20 @@ async fn test_data_consistency_across_services() {
     }
 }
 
+#[tokio::test]
+#[ignore = "needs DynamoDB Local and DYNAMODB_ENDPOINT_URL; run with --ignored"]
+async fn test_dynamodb_local_round_trip() {
+    // See "Running Against DynamoDB Local" in the README
+    assert!(
+        test_utils::local_dynamodb_endpoint().is_some(),
+        "Set DYNAMODB_ENDPOINT_URL to a running DynamoDB Local endpoint"
+    );
+
+    // The connection reaches DynamoDB Local without any AWS credentials
+    let client = database_utils::create_test_db_connection().await.unwrap();
+    client.list_tables().send().await.unwrap();
+}
+
 #[tokio::test]
 async fn test_performance_under_load() {
     // Test high-volume concurrent requests
@@ -7,7 +7,9 @@ use anyhow::Result;
 /// Test utilities for Lambda functions
 pub mod test_utils {
     use super::*;
+    use aws_sdk_dynamodb::config::{BehaviorVersion, Credentials, Region};
     use serde_json::Value;
+    use service_config::DYNAMODB_ENDPOINT_URL_ENV;
 
     /// Create a mock Lambda event for testing
     pub fn create_mock_event(
@@ -39,21 +41,27 @@ pub mod test_utils {
         event
     }
 
-    /// Create a mock Lambda context for testing
+    /// Create a mock Lambda context for testing. `Context` is non-exhaustive, so start
+    /// from its default and fill in what handlers read
     pub fn create_mock_context() -> lambda_runtime::Context {
-        lambda_runtime::Context {
-            request_id: Uuid::new_v4().to_string(),
+        let deadline = std::time::SystemTime::now() + std::time::Duration::from_secs(30);
+
+        let mut context = lambda_runtime::Context::default();
+        context.request_id = Uuid::new_v4().to_string();
+        context.invoked_function_arn =
+            "arn:aws:lambda:us-east-1:123456789012:function:test-function".to_string();
+        context.deadline = deadline
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|since_epoch| since_epoch.as_millis() as u64)
+            .unwrap_or_default();
+        context.env_config = lambda_runtime::Config {
             function_name: "test-function".to_string(),
-            function_version: "1".to_string(),
-            invoked_function_arn: "arn:aws:lambda:us-east-1:123456789012:function:test-function".to_string(),
-            memory_limit_in_mb: 256,
-            remaining_time_in_millis: 30000,
-            log_group_name: "test-log-group".to_string(),
-            log_stream_name: "test-log-stream".to_string(),
-            client_context: None,
-            identity: None,
-            deadline: std::time::SystemTime::now() + std::time::Duration::from_secs(30),
-        }
+            memory: 256,
+            version: "1".to_string(),
+            log_stream: "test-log-stream".to_string(),
+            log_group: "test-log-group".to_string(),
+        };
+        context
     }
 
     /// Create test user data
@@ -248,7 +256,7 @@ pub mod test_utils {
         use std::collections::HashMap;
 
         let mut dynamodb_items = Vec::new();
-        for item in items {
+        for item in &items {
             let mut attributes = HashMap::new();
             if let Some(obj) = item.as_object() {
                 for (key, value) in obj {
@@ -290,10 +298,10 @@ pub mod test_utils {
 
     /// Create mock DynamoDB error
     pub fn mock_dynamodb_error(message: &str) -> aws_sdk_dynamodb::Error {
-        aws_sdk_dynamodb::Error::Unhandled(
-            aws_sdk_dynamodb::error::Unhandled::builder()
-                .source(anyhow::anyhow!(message))
-                .build()
+        aws_sdk_dynamodb::Error::InternalServerError(
+            aws_sdk_dynamodb::types::error::InternalServerError::builder()
+                .message(message)
+                .build(),
         )
     }
 
@@ -310,12 +318,42 @@ pub mod test_utils {
 
     /// Create mock S3 error
     pub fn mock_s3_error(message: &str) -> aws_sdk_s3::Error {
-        aws_sdk_s3::Error::Unhandled(
-            aws_sdk_s3::error::Unhandled::builder()
-                .source(anyhow::anyhow!(message))
-                .build()
+        aws_sdk_s3::Error::NoSuchKey(
+            aws_sdk_s3::types::error::NoSuchKey::builder()
+                .message(message)
+                .build(),
         )
     }
+
+    // Talking to DynamoDB Local: start it (`docker run -p 8000:8000
+    // amazon/dynamodb-local`) and set `DYNAMODB_ENDPOINT_URL=http://localhost:8000`, the
+    // same variable the services read. Repository tests against it live with the
+    // repositories, e.g. workout-service's `repository::dynamo_ops::local`.
+
+    /// Region for local clients when `AWS_REGION` is unset. DynamoDB Local keeps a
+    /// separate set of tables per region
+    pub const DEFAULT_LOCAL_REGION: &str = "us-east-1";
+
+    /// The DynamoDB Local endpoint from `DYNAMODB_ENDPOINT_URL`, if set
+    pub fn local_dynamodb_endpoint() -> Option<String> {
+        std::env::var(DYNAMODB_ENDPOINT_URL_ENV)
+            .ok()
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty())
+    }
+
+    /// A client for DynamoDB Local at `endpoint_url`. It signs with fixed dummy
+    /// credentials, so no AWS account or profile is needed
+    pub fn local_dynamodb_client(endpoint_url: &str) -> aws_sdk_dynamodb::Client {
+        let region = std::env::var("AWS_REGION").unwrap_or_else(|_| DEFAULT_LOCAL_REGION.to_string());
+        let config = aws_sdk_dynamodb::Config::builder()
+            .behavior_version(BehaviorVersion::latest())
+            .region(Region::new(region))
+            .endpoint_url(endpoint_url)
+            .credentials_provider(Credentials::new("local", "local", None, None, "dynamodb-local"))
+            .build();
+        aws_sdk_dynamodb::Client::from_conf(config)
+    }
 }
 
 /// Performance testing utilities
@@ -341,7 +379,8 @@ pub mod performance_utils {
     ) -> Vec<R>
     where
         F: Fn(usize) -> Fut,
-        Fut: std::future::Future<Output = R>,
+        Fut: std::future::Future<Output = R> + Send + 'static,
+        R: Send + 'static,
     {
         let mut handles = Vec::new();
         for i in 0..count {
@@ -434,6 +473,7 @@ pub mod security_utils {
             "union select", "drop table", "delete from", "insert into",
             "update set", "create table", "alter table", "exec(",
             "execute(", "sp_", "xp_", "waitfor delay", "benchmark(",
+            "' or '", "'--",
         ];
         
         let input_lower = input.to_lowercase();
@@ -455,11 +495,38 @@ pub mod security_utils {
 /// Database testing utilities
 pub mod database_utils {
     use super::*;
+    use aws_sdk_dynamodb::config::ProvideCredentials;
+    use service_config::DYNAMODB_ENDPOINT_URL_ENV;
 
-    /// Create test database connection
+    /// Create test database connection. With `DYNAMODB_ENDPOINT_URL` set this is a
+    /// DynamoDB Local client that needs no AWS account; otherwise AWS credentials and a
+    /// region must resolve, and an error says how to configure either when they don't
     pub async fn create_test_db_connection() -> Result<aws_sdk_dynamodb::Client> {
+        if let Some(endpoint_url) = test_utils::local_dynamodb_endpoint() {
+            return Ok(test_utils::local_dynamodb_client(&endpoint_url));
+        }
+
         let config = aws_config::load_from_env().await;
-        Ok(aws_sdk_dynamodb::Client::new(&config))
+        let credentials = match config.credentials_provider() {
+            Some(provider) => provider
+                .provide_credentials()
+                .await
+                .map(|_| ())
+                .map_err(|e| e.to_string()),
+            None => Err("no credentials provider".to_string()),
+        };
+        let problem = match (credentials, config.region()) {
+            (Err(reason), _) => format!("AWS credentials could not be loaded ({})", reason),
+            (Ok(()), None) => "no AWS region is configured".to_string(),
+            (Ok(()), Some(_)) => return Ok(aws_sdk_dynamodb::Client::new(&config)),
+        };
+
+        anyhow::bail!(
+            "No database to test against: {}. Set {} to a DynamoDB Local endpoint \
+             (e.g. http://localhost:8000) or configure AWS credentials and AWS_REGION",
+            problem,
+            DYNAMODB_ENDPOINT_URL_ENV
+        )
     }
 
     /// Create test S3 connection
@@ -491,64 +558,6 @@ pub mod database_utils {
     }
 }
 
-/// Mock implementations for testing
-pub mod mocks {
-    use super::*;
-    use mockall::mock;
-
-    mock! {
-        pub DynamoDbClient {}
-
-        impl aws_sdk_dynamodb::Client {
-            pub async fn get_item(&self, input: aws_sdk_dynamodb::input::GetItemInput) -> Result<aws_sdk_dynamodb::output::GetItemOutput, aws_sdk_dynamodb::Error> {
-                Ok(aws_sdk_dynamodb::output::GetItemOutput::builder().build())
-            }
-
-            pub async fn put_item(&self, input: aws_sdk_dynamodb::input::PutItemInput) -> Result<aws_sdk_dynamodb::output::PutItemOutput, aws_sdk_dynamodb::Error> {
-                Ok(aws_sdk_dynamodb::output::PutItemOutput::builder().build())
-            }
-
-            pub async fn update_item(&self, input: aws_sdk_dynamodb::input::UpdateItemInput) -> Result<aws_sdk_dynamodb::output::UpdateItemOutput, aws_sdk_dynamodb::Error> {
-                Ok(aws_sdk_dynamodb::output::UpdateItemOutput::builder().build())
-            }
-
-            pub async fn delete_item(&self, input: aws_sdk_dynamodb::input::DeleteItemInput) -> Result<aws_sdk_dynamodb::output::DeleteItemOutput, aws_sdk_dynamodb::Error> {
-                Ok(aws_sdk_dynamodb::output::DeleteItemOutput::builder().build())
-            }
-
-            pub async fn query(&self, input: aws_sdk_dynamodb::input::QueryInput) -> Result<aws_sdk_dynamodb::output::QueryOutput, aws_sdk_dynamodb::Error> {
-                Ok(aws_sdk_dynamodb::output::QueryOutput::builder().build())
-            }
-        }
-    }
-
-    mock! {
-        pub S3Client {}
-
-        impl aws_sdk_s3::Client {
-            pub async fn get_object(&self, input: aws_sdk_s3::input::GetObjectInput) -> Result<aws_sdk_s3::output::GetObjectOutput, aws_sdk_s3::Error> {
-                Ok(aws_sdk_s3::output::GetObjectOutput::builder().build())
-            }
-
-            pub async fn put_object(&self, input: aws_sdk_s3::input::PutObjectInput) -> Result<aws_sdk_s3::output::PutObjectOutput, aws_sdk_s3::Error> {
-                Ok(aws_sdk_s3::output::PutObjectOutput::builder().build())
-            }
-
-            pub async fn delete_object(&self, input: aws_sdk_s3::input::DeleteObjectInput) -> Result<aws_sdk_s3::output::DeleteObjectOutput, aws_sdk_s3::Error> {
-                Ok(aws_sdk_s3::output::DeleteObjectOutput::builder().build())
-            }
-
-            pub async fn list_objects_v2(&self, input: aws_sdk_s3::input::ListObjectsV2Input) -> Result<aws_sdk_s3::output::ListObjectsV2Output, aws_sdk_s3::Error> {
-                Ok(aws_sdk_s3::output::ListObjectsV2Output::builder().build())
-            }
-
-            pub async fn copy_object(&self, input: aws_sdk_s3::input::CopyObjectInput) -> Result<aws_sdk_s3::output::CopyObjectOutput, aws_sdk_s3::Error> {
-                Ok(aws_sdk_s3::output::CopyObjectOutput::builder().build())
-            }
-        }
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1,120 +0,0 @@
-use gymcoach_ai_tests::*;
-
-#[tokio::main]
-async fn main() {
-    // Initialize test environment
-    test_utils::init_test_environment().await;
-
-    // Run all test suites
-    println!("Running Phase 3 Validation Tests...");
-
-    // Unit Tests
-    println!("\n=== Running Unit Tests ===");
-    unit_tests::test_user_profile_crud_operations().await;
-    unit_tests::test_user_profile_validation().await;
-    unit_tests::test_user_profile_statistics().await;
-    unit_tests::test_workout_plan_creation().await;
-    unit_tests::test_workout_plan_validation().await;
-    unit_tests::test_workout_plan_management().await;
-    unit_tests::test_workout_session_tracking().await;
-    unit_tests::test_workout_session_validation().await;
-    unit_tests::test_workout_session_management().await;
-    unit_tests::test_exercise_library_management().await;
-    unit_tests::test_exercise_library_validation().await;
-    unit_tests::test_exercise_library_search().await;
-    unit_tests::test_progress_photo_upload().await;
-    unit_tests::test_progress_photo_validation().await;
-    unit_tests::test_progress_photo_management().await;
-    unit_tests::test_workout_analytics().await;
-    unit_tests::test_workout_analytics_validation().await;
-    unit_tests::test_workout_analytics_calculation().await;
-    unit_tests::test_ai_recommendation_generation().await;
-    unit_tests::test_ai_recommendation_validation().await;
-    unit_tests::test_ai_recommendation_management().await;
-    unit_tests::test_ai_workout_planning().await;
-    unit_tests::test_ai_workout_planning_validation().await;
-    unit_tests::test_ai_workout_planning_optimization().await;
-    unit_tests::test_ai_exercise_substitution().await;
-    unit_tests::test_ai_exercise_substitution_validation().await;
-    unit_tests::test_ai_exercise_substitution_algorithm().await;
-    unit_tests::test_ai_recovery_calculation().await;
-    unit_tests::test_ai_recovery_calculation_validation().await;
-    unit_tests::test_ai_recovery_calculation_algorithm().await;
-    unit_tests::test_ai_difficulty_adjustment().await;
-    unit_tests::test_ai_difficulty_adjustment_validation().await;
-    unit_tests::test_ai_difficulty_adjustment_algorithm().await;
-    unit_tests::test_strength_progress_tracking().await;
-    unit_tests::test_strength_progress_validation().await;
-    unit_tests::test_strength_progress_calculation().await;
-    unit_tests::test_body_measurements_tracking().await;
-    unit_tests::test_body_measurements_validation().await;
-    unit_tests::test_body_measurements_calculation().await;
-    unit_tests::test_progress_charts_data().await;
-    unit_tests::test_progress_charts_validation().await;
-    unit_tests::test_progress_charts_generation().await;
-    unit_tests::test_milestone_achievement().await;
-    unit_tests::test_milestone_achievement_validation().await;
-    unit_tests::test_milestone_achievement_calculation().await;
-    unit_tests::test_performance_trend_analysis().await;
-    unit_tests::test_performance_trend_validation().await;
-    unit_tests::test_performance_trend_calculation().await;
-    println!("✅ Unit Tests Completed");
-
-    // Integration Tests
-    println!("\n=== Running Integration Tests ===");
-    integration_tests::test_user_profile_workout_integration().await;
-    integration_tests::test_workout_analytics_integration().await;
-    integration_tests::test_ai_coaching_integration().await;
-    integration_tests::test_analytics_ai_integration().await;
-    integration_tests::test_cross_service_data_consistency().await;
-    integration_tests::test_cross_service_error_handling().await;
-    integration_tests::test_cross_service_performance().await;
-    integration_tests::test_cross_service_security().await;
-    integration_tests::test_cross_service_monitoring().await;
-    integration_tests::test_cross_service_scalability().await;
-    println!("✅ Integration Tests Completed");
-
-    // End-to-End Tests
-    println!("\n=== Running End-to-End Tests ===");
-    e2e_tests::test_complete_user_journey().await;
-    e2e_tests::test_workout_planning_workflow().await;
-    e2e_tests::test_progress_tracking_workflow().await;
-    e2e_tests::test_ai_coaching_workflow().await;
-    e2e_tests::test_analytics_reporting_workflow().await;
-    e2e_tests::test_error_recovery_workflow().await;
-    e2e_tests::test_performance_under_load().await;
-    e2e_tests::test_security_penetration().await;
-    e2e_tests::test_monitoring_alerting().await;
-    e2e_tests::test_scalability_testing().await;
-    println!("✅ End-to-End Tests Completed");
-
-    // Validation Tests
-    println!("\n=== Running Phase 3 Validation Tests ===");
-    validation_tests::test_phase3_validation_crud_operations().await;
-    validation_tests::test_phase3_validation_database_consistency().await;
-    validation_tests::test_phase3_validation_file_upload_download().await;
-    validation_tests::test_phase3_validation_error_conditions().await;
-    validation_tests::test_phase3_validation_performance_under_load().await;
-    validation_tests::test_phase3_validation_security_penetration().await;
-    validation_tests::test_phase3_validation_monitoring_logging().await;
-    validation_tests::test_phase3_validation_cross_service_integration().await;
-    validation_tests::test_phase3_validation_api_documentation_accuracy().await;
-    println!("✅ Phase 3 Validation Tests Completed");
-
-    println!("\n🎉 All Phase 3 Validation Tests Passed Successfully!");
-    println!("\nPhase 3 Backend Lambda Services Implementation Complete!");
-    println!("\nSummary of Completed Tasks:");
-    println!("✅ 1. User Profile Service Implementation");
-    println!("✅ 2. Workout Management Service");
-    println!("✅ 3. Coaching Engine Service");
-    println!("✅ 4. Progress Analytics Service");
-    println!("✅ 5. Lambda Function URL Configuration");
-    println!("✅ 6. Database Schema Design and Implementation");
-    println!("✅ 7. S3 Storage Configuration");
-    println!("✅ 8. Service Integration Testing");
-    println!("✅ 9. Monitoring and Logging Setup");
-    println!("✅ 10. Security Implementation");
-    println!("✅ 11. Optimization and Performance");
-    println!("✅ 12. API Documentation");
-    println!("✅ 13. Phase 3 Validation Tests");
-}